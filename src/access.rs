@@ -0,0 +1,459 @@
+use crate::{
+    admin::token_matches,
+    auth::expand_args,
+    config::{AuthScheme, MtlsConfig, OauthConfig, ServerConfig},
+    replay::authenticate_signed,
+    tenancy::{Principal, authenticate},
+};
+use axum::http::{HeaderMap, StatusCode, header::AUTHORIZATION};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Route groups with their own authentication chain (`access.routes`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    /// `POST /mcp`, the `GET /mcp` stream, and WebSocket connections.
+    Mcp,
+    /// `/admin/reload`, `/admin/credentials`, `/admin/approvals`,
+    /// `/admin/features`, and `/admin/usage`.
+    Admin,
+    /// `/health`, `/health/ready`, and `/openapi.json`.
+    Health,
+}
+
+impl Route {
+    pub fn name(self) -> &'static str {
+        match self {
+            Route::Mcp => "mcp",
+            Route::Admin => "admin",
+            Route::Health => "health",
+        }
+    }
+}
+
+/// Why a request was not admitted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Refusal {
+    /// No scheme of the admin chain is configured, so the route does not exist.
+    NotFound,
+    Unauthorized(String),
+    /// A scheme's secret could not be read.
+    Misconfigured(String),
+}
+
+impl Refusal {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Refusal::NotFound => StatusCode::NOT_FOUND,
+            Refusal::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Refusal::Misconfigured(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl fmt::Display for Refusal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Refusal::NotFound => write!(f, "Not found"),
+            Refusal::Unauthorized(message) | Refusal::Misconfigured(message) => {
+                write!(f, "{}", message)
+            }
+        }
+    }
+}
+
+/// What one scheme makes of a request.
+enum Check {
+    /// Admitted, confined to the tenant and policy when there are any.
+    Admit(Principal),
+    Deny(String),
+    /// The scheme has nothing to check against.
+    Unconfigured,
+    Failed(String),
+}
+
+/// Runs the request through the chain of `route` and returns whom it is
+/// admitted as. The first scheme that admits the request wins; when none
+/// does, the refusal names why each turned it down. A chain with nothing
+/// configured to check against admits everyone, except on the admin routes,
+/// which then do not exist.
+pub fn admit(
+    config: &ServerConfig,
+    route: Route,
+    headers: &HeaderMap,
+) -> Result<Principal, Refusal> {
+    admit_request(config, route, headers, None)
+}
+
+/// [`admit`] for a request whose `body` a signed API key request
+/// (`access.replay.signed`) covers; without one, API keys are read from the
+/// bearer token.
+pub fn admit_request(
+    config: &ServerConfig,
+    route: Route,
+    headers: &HeaderMap,
+    body: Option<&[u8]>,
+) -> Result<Principal, Refusal> {
+    let mut reasons = Vec::new();
+    let mut configured = false;
+    for scheme in config.access.chain(route.name()) {
+        match check(config, *scheme, headers, body) {
+            Check::Admit(principal) => return Ok(principal),
+            Check::Deny(reason) => {
+                configured = true;
+                reasons.push(reason);
+            }
+            Check::Unconfigured => {}
+            Check::Failed(message) => return Err(Refusal::Misconfigured(message)),
+        }
+    }
+    match (configured, route) {
+        (true, _) => Err(Refusal::Unauthorized(reasons.join("; "))),
+        (false, Route::Admin) => Err(Refusal::NotFound),
+        (false, _) => Ok(Principal::default()),
+    }
+}
+
+fn check(
+    config: &ServerConfig,
+    scheme: AuthScheme,
+    headers: &HeaderMap,
+    body: Option<&[u8]>,
+) -> Check {
+    let signed = config
+        .access
+        .replay
+        .as_ref()
+        .filter(|replay| replay.signed)
+        .zip(body);
+    match scheme {
+        AuthScheme::None => Check::Admit(Principal::default()),
+        AuthScheme::ApiKey if config.api_keys.is_empty() => Check::Unconfigured,
+        AuthScheme::ApiKey => {
+            let authenticated = match signed {
+                Some((replay, body)) => {
+                    authenticate_signed(&config.api_keys, replay, headers, body)
+                }
+                None => authenticate(&config.api_keys, headers),
+            };
+            match authenticated {
+                Ok(principal) => Check::Admit(principal),
+                Err(message) => Check::Deny(message),
+            }
+        }
+        AuthScheme::AdminToken => check_admin_token(config.admin.token.as_ref(), headers),
+        AuthScheme::Oauth => match &config.access.oauth {
+            Some(oauth) => check_oauth(oauth, headers),
+            None => Check::Unconfigured,
+        },
+        AuthScheme::Mtls => match &config.access.mtls {
+            Some(mtls) => check_mtls(mtls, headers),
+            None => Check::Unconfigured,
+        },
+    }
+}
+
+fn bearer(headers: &HeaderMap) -> &str {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default()
+}
+
+fn check_admin_token(token: Option<&String>, headers: &HeaderMap) -> Check {
+    let expected = match token.map(|token| expand_args(std::slice::from_ref(token))) {
+        Some(Ok(token)) => token.concat(),
+        Some(Err(err)) => return Check::Failed(err),
+        None => return Check::Unconfigured,
+    };
+    if !expected.is_empty() && token_matches(&expected, bearer(headers)) {
+        Check::Admit(Principal::default())
+    } else {
+        Check::Deny("Invalid admin token".to_string())
+    }
+}
+
+pub(crate) fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; 64];
+    if key.len() > block.len() {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let mut inner = Sha256::new();
+    inner.update(block.map(|byte| byte ^ 0x36));
+    inner.update(message);
+    let mut outer = Sha256::new();
+    outer.update(block.map(|byte| byte ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
+fn decode_part(part: &str) -> Option<Value> {
+    URL_SAFE_NO_PAD
+        .decode(part)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+}
+
+/// Checks a bearer JWT's HS256 signature, lifetime, issuer, and audience,
+/// and reads the tenant from `tenant_claim`.
+fn check_oauth(oauth: &OauthConfig, headers: &HeaderMap) -> Check {
+    let secret = match expand_args(std::slice::from_ref(&oauth.secret)) {
+        Ok(secret) => secret.concat(),
+        Err(err) => return Check::Failed(err),
+    };
+    let deny = |reason: &str| Check::Deny(format!("Invalid OAuth token: {}", reason));
+    let token = bearer(headers);
+    let mut parts = token.split('.');
+    let (Some(header), Some(payload), Some(signature), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return deny("not a JWT");
+    };
+    if decode_part(header).is_none_or(|header| header["alg"] != "HS256") {
+        return deny("only HS256 tokens are accepted");
+    }
+    let signing_input = format!("{}.{}", header, payload);
+    let expected = URL_SAFE_NO_PAD.encode(hmac_sha256(secret.as_bytes(), signing_input.as_bytes()));
+    if !token_matches(&expected, signature) {
+        return deny("bad signature");
+    }
+    let Some(claims) = decode_part(payload) else {
+        return deny("unreadable claims");
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    match claims.get("exp").and_then(Value::as_u64) {
+        Some(exp) if exp.saturating_add(oauth.leeway_secs) > now => {}
+        Some(_) => return deny("expired"),
+        None => return deny("no 'exp' claim"),
+    }
+    if claims
+        .get("nbf")
+        .and_then(Value::as_u64)
+        .is_some_and(|nbf| nbf > now.saturating_add(oauth.leeway_secs))
+    {
+        return deny("not valid yet");
+    }
+    if let Some(issuer) = &oauth.issuer
+        && claims.get("iss").and_then(Value::as_str) != Some(issuer.as_str())
+    {
+        return deny("wrong issuer");
+    }
+    if let Some(audience) = &oauth.audience {
+        let matches = match claims.get("aud") {
+            Some(Value::String(aud)) => aud == audience,
+            Some(Value::Array(auds)) => auds.iter().any(|aud| aud == audience),
+            _ => false,
+        };
+        if !matches {
+            return deny("wrong audience");
+        }
+    }
+    match &oauth.tenant_claim {
+        Some(claim) => match claims.get(claim).and_then(Value::as_str) {
+            Some(tenant) if !tenant.is_empty() => {
+                Check::Admit(Principal::confined_to(Some(tenant.to_string())))
+            }
+            _ => deny(&format!("no '{}' claim", claim)),
+        },
+        None => Check::Admit(Principal::default()),
+    }
+}
+
+fn check_mtls(mtls: &MtlsConfig, headers: &HeaderMap) -> Check {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+    };
+    if header(&mtls.verify_header) != "SUCCESS" {
+        return Check::Deny("No verified client certificate".to_string());
+    }
+    let subject = header(&mtls.subject_header);
+    if mtls.allowed_subjects.is_empty()
+        || mtls
+            .allowed_subjects
+            .iter()
+            .any(|allowed| allowed == subject)
+    {
+        Check::Admit(Principal::default())
+    } else {
+        Check::Deny(format!("Client certificate '{}' is not allowed", subject))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tenant_of(
+        config: &ServerConfig,
+        route: Route,
+        headers: &HeaderMap,
+    ) -> Result<Option<String>, Refusal> {
+        admit(config, route, headers).map(|principal| principal.tenant)
+    }
+    use crate::config::{AccessConfig, AdminConfig, ApiKeyConfig};
+    use serde_json::json;
+
+    fn headers(pairs: &[(&str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(
+                axum::http::HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                value.parse().unwrap(),
+            );
+        }
+        headers
+    }
+
+    fn jwt(secret: &str, claims: Value) -> String {
+        let header = URL_SAFE_NO_PAD.encode(json!({ "alg": "HS256", "typ": "JWT" }).to_string());
+        let payload = URL_SAFE_NO_PAD.encode(claims.to_string());
+        let signing_input = format!("{}.{}", header, payload);
+        let signature =
+            URL_SAFE_NO_PAD.encode(hmac_sha256(secret.as_bytes(), signing_input.as_bytes()));
+        format!("{}.{}", signing_input, signature)
+    }
+
+    #[test]
+    fn test_hmac_matches_rfc_4231() {
+        let mac = hmac_sha256(b"Jefe", b"what do ya want for nothing?");
+        let hex: String = mac.iter().map(|byte| format!("{:02x}", byte)).collect();
+        assert_eq!(
+            hex,
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_default_chains_keep_the_old_rules() {
+        let open = ServerConfig::default();
+        assert_eq!(tenant_of(&open, Route::Mcp, &HeaderMap::new()), Ok(None));
+        assert_eq!(tenant_of(&open, Route::Health, &HeaderMap::new()), Ok(None));
+        // Without admin.token the admin endpoints do not exist.
+        assert_eq!(
+            tenant_of(&open, Route::Admin, &HeaderMap::new()),
+            Err(Refusal::NotFound)
+        );
+
+        let config = ServerConfig {
+            api_keys: vec![ApiKeyConfig {
+                key: "team-a".to_string(),
+                name: None,
+                tenant: Some("castings".to_string()),
+                policy: None,
+            }],
+            admin: AdminConfig {
+                token: Some("admin-t0ken".to_string()),
+                ..AdminConfig::default()
+            },
+            ..ServerConfig::default()
+        };
+        let team = headers(&[("authorization", "Bearer team-a")]);
+        assert_eq!(
+            tenant_of(&config, Route::Mcp, &team),
+            Ok(Some("castings".to_string()))
+        );
+        assert_eq!(
+            tenant_of(&config, Route::Admin, &team),
+            Err(Refusal::Unauthorized("Invalid admin token".to_string()))
+        );
+        let admin = headers(&[("authorization", "Bearer admin-t0ken")]);
+        assert_eq!(tenant_of(&config, Route::Admin, &admin), Ok(None));
+        assert!(tenant_of(&config, Route::Mcp, &admin).is_err());
+    }
+
+    #[test]
+    fn test_routes_take_their_own_chains() {
+        let config = ServerConfig {
+            access: AccessConfig {
+                routes: [
+                    ("mcp".to_string(), vec![AuthScheme::Mtls, AuthScheme::Oauth]),
+                    ("health".to_string(), vec![AuthScheme::ApiKey]),
+                ]
+                .into_iter()
+                .collect(),
+                oauth: Some(OauthConfig {
+                    secret: "s3cret".to_string(),
+                    issuer: Some("https://idp.example.com".to_string()),
+                    audience: Some("pcli2-mcp".to_string()),
+                    tenant_claim: Some("tenant".to_string()),
+                    leeway_secs: 0,
+                }),
+                mtls: Some(MtlsConfig {
+                    allowed_subjects: vec!["CN=agent".to_string()],
+                    ..MtlsConfig::default()
+                }),
+                replay: None,
+            },
+            api_keys: vec![ApiKeyConfig {
+                key: "ops".to_string(),
+                name: None,
+                tenant: None,
+                policy: None,
+            }],
+            ..ServerConfig::default()
+        };
+        let good = json!({
+            "iss": "https://idp.example.com",
+            "aud": ["pcli2-mcp"],
+            "exp": 4_102_444_800u64,
+            "tenant": "forgings"
+        });
+        let token = format!("Bearer {}", jwt("s3cret", good.clone()));
+        assert_eq!(
+            tenant_of(&config, Route::Mcp, &headers(&[("authorization", &token)])),
+            Ok(Some("forgings".to_string()))
+        );
+
+        let mut expired = good.clone();
+        expired["exp"] = json!(1);
+        let mut other_audience = good.clone();
+        other_audience["aud"] = json!("other");
+        for (secret, claims, reason) in [
+            ("wrong", good.clone(), "bad signature"),
+            ("s3cret", expired, "expired"),
+            ("s3cret", other_audience, "wrong audience"),
+        ] {
+            let token = format!("Bearer {}", jwt(secret, claims));
+            let refusal = tenant_of(&config, Route::Mcp, &headers(&[("authorization", &token)]))
+                .unwrap_err()
+                .to_string();
+            assert!(refusal.contains(reason), "{}", refusal);
+            assert!(
+                refusal.contains("No verified client certificate"),
+                "{}",
+                refusal
+            );
+        }
+
+        let certificate = [
+            ("x-ssl-client-verify", "SUCCESS"),
+            ("x-ssl-client-s-dn", "CN=agent"),
+        ];
+        assert_eq!(
+            tenant_of(&config, Route::Mcp, &headers(&certificate)),
+            Ok(None)
+        );
+        let stranger = [
+            ("x-ssl-client-verify", "SUCCESS"),
+            ("x-ssl-client-s-dn", "CN=stranger"),
+        ];
+        assert!(tenant_of(&config, Route::Mcp, &headers(&stranger)).is_err());
+
+        assert!(tenant_of(&config, Route::Health, &HeaderMap::new()).is_err());
+        let ops = headers(&[("authorization", "Bearer ops")]);
+        assert_eq!(tenant_of(&config, Route::Health, &ops), Ok(None));
+    }
+}
@@ -0,0 +1,211 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::Notify;
+
+/// Finished calls kept for the status view.
+pub const RECENT_CALLS: usize = 20;
+
+struct RunningCall {
+    tool: String,
+    tenant: Option<String>,
+    started: Instant,
+    cancel: Arc<Notify>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RunningCallInfo {
+    pub id: u64,
+    pub tool: String,
+    pub tenant: Option<String>,
+    pub elapsed: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct FinishedCall {
+    pub tool: String,
+    pub tenant: Option<String>,
+    pub duration: Duration,
+    /// First line of the error, or `None` when the call succeeded.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ActivitySnapshot {
+    pub running: Vec<RunningCallInfo>,
+    pub recent: Vec<FinishedCall>,
+    pub completed: u64,
+    pub failed: u64,
+    /// Failed calls per tool, most failures first.
+    pub errors: Vec<(String, u64)>,
+}
+
+#[derive(Default)]
+struct Activity {
+    next_id: u64,
+    running: BTreeMap<u64, RunningCall>,
+    recent: VecDeque<FinishedCall>,
+    completed: u64,
+    failed: u64,
+    errors: HashMap<String, u64>,
+}
+
+/// Tool calls in progress and recently finished, for `serve --tui`.
+#[derive(Clone, Default)]
+pub struct CallTracker {
+    activity: Arc<Mutex<Activity>>,
+}
+
+/// One tracked call; records a cancellation if dropped before `finish`.
+pub struct TrackedCall {
+    tracker: CallTracker,
+    id: u64,
+    cancel: Arc<Notify>,
+    finished: bool,
+}
+
+impl CallTracker {
+    pub fn start(&self, tool: &str, tenant: Option<&str>) -> TrackedCall {
+        let cancel = Arc::new(Notify::new());
+        let mut activity = self.activity.lock().unwrap_or_else(|e| e.into_inner());
+        activity.next_id += 1;
+        let id = activity.next_id;
+        activity.running.insert(
+            id,
+            RunningCall {
+                tool: tool.to_string(),
+                tenant: tenant.map(str::to_string),
+                started: Instant::now(),
+                cancel: cancel.clone(),
+            },
+        );
+        TrackedCall {
+            tracker: self.clone(),
+            id,
+            cancel,
+            finished: false,
+        }
+    }
+
+    /// Asks the running call `id` to stop. Returns `false` if it already finished.
+    pub fn cancel(&self, id: u64) -> bool {
+        let activity = self.activity.lock().unwrap_or_else(|e| e.into_inner());
+        match activity.running.get(&id) {
+            Some(call) => {
+                call.cancel.notify_one();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn snapshot(&self) -> ActivitySnapshot {
+        let activity = self.activity.lock().unwrap_or_else(|e| e.into_inner());
+        let mut errors: Vec<(String, u64)> = activity
+            .errors
+            .iter()
+            .map(|(tool, count)| (tool.clone(), *count))
+            .collect();
+        errors.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        ActivitySnapshot {
+            running: activity
+                .running
+                .iter()
+                .map(|(id, call)| RunningCallInfo {
+                    id: *id,
+                    tool: call.tool.clone(),
+                    tenant: call.tenant.clone(),
+                    elapsed: call.started.elapsed(),
+                })
+                .collect(),
+            recent: activity.recent.iter().rev().cloned().collect(),
+            completed: activity.completed,
+            failed: activity.failed,
+            errors,
+        }
+    }
+
+    fn finish(&self, id: u64, error: Option<&str>) {
+        let mut activity = self.activity.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(call) = activity.running.remove(&id) else {
+            return;
+        };
+        activity.completed += 1;
+        if error.is_some() {
+            activity.failed += 1;
+            *activity.errors.entry(call.tool.clone()).or_default() += 1;
+        }
+        activity.recent.push_back(FinishedCall {
+            tool: call.tool,
+            tenant: call.tenant,
+            duration: call.started.elapsed(),
+            error: error.map(|message| message.lines().next().unwrap_or_default().to_string()),
+        });
+        if activity.recent.len() > RECENT_CALLS {
+            activity.recent.pop_front();
+        }
+    }
+}
+
+impl TrackedCall {
+    /// Completes when the call is cancelled from the status view.
+    pub async fn cancelled(&self) {
+        self.cancel.notified().await;
+    }
+
+    pub fn finish<T>(mut self, outcome: &Result<T, String>) {
+        self.finished = true;
+        self.tracker
+            .finish(self.id, outcome.as_ref().err().map(String::as_str));
+    }
+}
+
+impl Drop for TrackedCall {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.tracker
+                .finish(self.id, Some("cancelled: the client went away"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_tracker_records_running_and_finished_calls() {
+        let tracker = CallTracker::default();
+        let first = tracker.start("pcli2_asset_list", Some("acme"));
+        let second = tracker.start("pcli2_folder_list", None);
+        let snapshot = tracker.snapshot();
+        assert_eq!(snapshot.running.len(), 2);
+        assert_eq!(snapshot.running[0].tenant.as_deref(), Some("acme"));
+
+        first.finish::<()>(&Err("pcli2 asset list failed\ndetails".to_string()));
+        drop(second);
+        let snapshot = tracker.snapshot();
+        assert!(snapshot.running.is_empty());
+        assert_eq!((snapshot.completed, snapshot.failed), (2, 2));
+        assert_eq!(
+            snapshot.recent[1].error.as_deref(),
+            Some("pcli2 asset list failed")
+        );
+        assert_eq!(snapshot.recent[0].tool, "pcli2_folder_list");
+    }
+
+    #[tokio::test]
+    async fn test_cancel_wakes_the_call() {
+        let tracker = CallTracker::default();
+        let call = tracker.start("pcli2_folder_geometric_match", None);
+        let id = tracker.snapshot().running[0].id;
+        assert!(tracker.cancel(id));
+        tokio::time::timeout(Duration::from_secs(1), call.cancelled())
+            .await
+            .expect("cancelled");
+        call.finish::<()>(&Ok(()));
+        assert!(!tracker.cancel(id));
+    }
+}
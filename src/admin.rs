@@ -0,0 +1,635 @@
+use crate::{
+    AppState,
+    access::{Refusal, Route, admit},
+    approval::{ApprovalView, decide},
+    config::FeatureConfig,
+    flags::{FEATURES, FeatureStatus, feature_status},
+    pcli::run_pcli2_command,
+    redact::redactor,
+    reload::ReloadSummary,
+    usage::{CurrentUsage, MonthReport, valid_month},
+};
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+pub const CREDENTIALS_TOOL: &str = "pcli2_credentials_register";
+/// Request fields that may appear as `{name}` placeholders in
+/// `admin.credential_commands`.
+pub const CREDENTIAL_FIELDS: [&str; 4] = ["environment", "tenant", "client_id", "client_secret"];
+
+/// Registrations run one at a time so their `pcli2` commands never interleave.
+static REGISTRATION: Mutex<()> = Mutex::const_new(());
+
+/// Runs the configured `pcli2` commands that write the credentials in
+/// `request`. Commands that use a placeholder missing from the request are
+/// skipped. Returns the commands that ran, with secrets masked.
+pub async fn register_credentials(
+    state: &AppState,
+    request: &Value,
+) -> Result<Vec<String>, String> {
+    let commands = build_commands(&state.config.admin.credential_commands, request)?;
+    let _guard = REGISTRATION.lock().await;
+    let mut ran = Vec::with_capacity(commands.len());
+    for args in commands {
+        let command = format!("pcli2 {}", redactor().redact_args(&args).join(" "));
+        info!("🔑 registering credentials: {}", command);
+        run_pcli2_command(args, "pcli2 credential registration").await?;
+        ran.push(command);
+    }
+    // The backend may have been failing only because of the old credentials.
+    state.breaker.record_success();
+    // New credentials may reach other tenants and folders.
+    state.cache.clear().await;
+    Ok(ran)
+}
+
+fn build_commands(templates: &[Vec<String>], request: &Value) -> Result<Vec<Vec<String>>, String> {
+    let request = request
+        .as_object()
+        .ok_or_else(|| "Invalid request: expected a JSON object".to_string())?;
+    if let Some(key) = request
+        .keys()
+        .find(|key| !CREDENTIAL_FIELDS.contains(&key.as_str()))
+    {
+        return Err(format!(
+            "Unknown field '{}'; expected {}",
+            key,
+            CREDENTIAL_FIELDS.join(", ")
+        ));
+    }
+    let mut values = Vec::new();
+    for field in CREDENTIAL_FIELDS {
+        match request.get(field) {
+            None | Some(Value::Null) => {}
+            Some(Value::String(value)) if !value.is_empty() => {
+                if value.chars().any(char::is_control) {
+                    return Err(format!(
+                        "Invalid '{}': control characters are not allowed",
+                        field
+                    ));
+                }
+                values.push((field, value.as_str()));
+            }
+            Some(_) => return Err(format!("Invalid '{}': expected a non-empty string", field)),
+        }
+    }
+
+    let mut commands = Vec::new();
+    'templates: for template in templates {
+        let mut args = Vec::with_capacity(template.len());
+        for arg in template {
+            let mut arg = arg.clone();
+            for field in CREDENTIAL_FIELDS {
+                let placeholder = format!("{{{}}}", field);
+                if !arg.contains(&placeholder) {
+                    continue;
+                }
+                let Some((_, value)) = values.iter().find(|(name, _)| *name == field) else {
+                    continue 'templates;
+                };
+                if arg == placeholder && value.starts_with('-') {
+                    return Err(format!(
+                        "Invalid '{}': values starting with '-' would be read as a pcli2 option",
+                        field
+                    ));
+                }
+                arg = arg.replace(&placeholder, value);
+            }
+            args.push(arg);
+        }
+        commands.push(args);
+    }
+    if commands.is_empty() {
+        return Err(format!(
+            "Nothing to register: provide at least one of {}",
+            CREDENTIAL_FIELDS.join(", ")
+        ));
+    }
+    Ok(commands)
+}
+
+/// Constant-time comparison so the admin token cannot be guessed byte by byte.
+pub(crate) fn token_matches(expected: &str, provided: &str) -> bool {
+    let (expected, provided) = (expected.as_bytes(), provided.as_bytes());
+    expected.len() == provided.len()
+        && expected
+            .iter()
+            .zip(provided)
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// The body of a refused admin request.
+#[derive(Debug, Serialize, ToSchema)]
+#[schema(as = Error)]
+pub struct AdminError {
+    pub error: String,
+}
+
+fn admin_error(status: StatusCode, message: String) -> Response {
+    (status, Json(AdminError { error: message })).into_response()
+}
+
+/// Runs the request through the `admin` route's authentication chain
+/// (`admin.token` unless `access.routes` says otherwise), returning the
+/// response that refuses it. The admin endpoints do not exist while nothing
+/// in the chain is configured.
+fn refuse_admin(state: &AppState, headers: &HeaderMap, action: &str) -> Option<Response> {
+    match admit(&state.config, Route::Admin, headers) {
+        Ok(_) => None,
+        Err(Refusal::NotFound) => Some(StatusCode::NOT_FOUND.into_response()),
+        Err(refusal) => {
+            warn!("⚠ rejected {}: {}", action, refusal);
+            Some(admin_error(refusal.status(), refusal.to_string()))
+        }
+    }
+}
+
+/// `POST /admin/reload`: rereads `--config` without dropping sessions.
+#[utoipa::path(
+    post,
+    path = "/admin/reload",
+    tag = "admin",
+    summary = "Reread the configuration file without dropping sessions",
+    description = "Also triggered by `SIGHUP`.",
+    responses(
+        (status = 200, description = "Reloaded", body = ReloadSummary),
+        (status = 400, description = "The file is invalid; the previous configuration stays", body = AdminError),
+        (status = 404, description = "No scheme of the admin chain is configured")
+    )
+)]
+pub async fn handle_reload(State(mut state): State<AppState>, headers: HeaderMap) -> Response {
+    state.refresh_config();
+    if let Some(response) = refuse_admin(&state, &headers, "configuration reload") {
+        return response;
+    }
+    match state.reloader.reload(&state) {
+        Ok(summary) => Json(summary).into_response(),
+        Err(message) => {
+            warn!("⚠ configuration reload failed: {}", message);
+            admin_error(StatusCode::BAD_REQUEST, message)
+        }
+    }
+}
+
+/// The body of `POST /admin/credentials`; each field fills its placeholder
+/// in `admin.credential_commands`.
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CredentialsRequest {
+    pub environment: Option<String>,
+    pub tenant: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+/// What `POST /admin/credentials` ran.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Registered {
+    #[schema(example = "registered")]
+    pub status: &'static str,
+    /// The `pcli2` commands, with secrets masked.
+    pub commands: Vec<String>,
+}
+
+/// `POST /admin/credentials`: registers or rotates `pcli2` credentials.
+#[utoipa::path(
+    post,
+    path = "/admin/credentials",
+    tag = "admin",
+    summary = "Register or rotate pcli2 credentials",
+    request_body = CredentialsRequest,
+    responses(
+        (status = 200, description = "Registered", body = Registered),
+        (status = 400, description = "Invalid request", body = AdminError),
+        (status = 404, description = "No scheme of the admin chain is configured"),
+        (status = 502, description = "A pcli2 command failed", body = AdminError)
+    )
+)]
+pub async fn handle_credentials(
+    State(mut state): State<AppState>,
+    headers: HeaderMap,
+    bytes: Bytes,
+) -> Response {
+    state.refresh_config();
+    if let Some(response) = refuse_admin(&state, &headers, "credential registration") {
+        return response;
+    }
+    let request: Value = match serde_json::from_slice(&bytes) {
+        Ok(request) => request,
+        Err(_) => {
+            return admin_error(StatusCode::BAD_REQUEST, "Invalid JSON body".to_string());
+        }
+    };
+    let secrets: Vec<String> = request
+        .get("client_secret")
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+        .into_iter()
+        .collect();
+    if let Err(message) = build_commands(&state.config.admin.credential_commands, &request) {
+        return admin_error(
+            StatusCode::BAD_REQUEST,
+            redactor().redact_text(&message, &secrets),
+        );
+    }
+    match register_credentials(&state, &request).await {
+        Ok(commands) => Json(Registered {
+            status: "registered",
+            commands,
+        })
+        .into_response(),
+        Err(message) => admin_error(
+            StatusCode::BAD_GATEWAY,
+            redactor().redact_text(&message, &secrets),
+        ),
+    }
+}
+
+/// The body of `GET /admin/approvals`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApprovalList {
+    pub approvals: Vec<ApprovalView>,
+}
+
+/// `GET /admin/approvals`: the calls held for approval, oldest first.
+#[utoipa::path(
+    get,
+    path = "/admin/approvals",
+    tag = "admin",
+    summary = "List tool calls held for approval, oldest first",
+    responses(
+        (status = 200, description = "Held calls", body = ApprovalList),
+        (status = 404, description = "No scheme of the admin chain is configured")
+    )
+)]
+pub async fn handle_approvals(State(mut state): State<AppState>, headers: HeaderMap) -> Response {
+    state.refresh_config();
+    if let Some(response) = refuse_admin(&state, &headers, "approval listing") {
+        return response;
+    }
+    let approvals = state
+        .approvals
+        .list()
+        .iter()
+        .map(|approval| approval.view())
+        .collect();
+    Json(ApprovalList { approvals }).into_response()
+}
+
+/// What an operator decides for a held call.
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+    Approve,
+    Reject,
+}
+
+/// The body of `POST /admin/approvals/{id}`.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct DecisionRequest {
+    pub decision: Decision,
+    /// Why the call was rejected, shown to the client.
+    pub reason: Option<String>,
+}
+
+/// `POST /admin/approvals/{id}`: approves or rejects a held call, with
+/// `{"decision": "approve" | "reject", "reason": "..."}`.
+#[utoipa::path(
+    post,
+    path = "/admin/approvals/{id}",
+    tag = "admin",
+    summary = "Approve or reject a held tool call",
+    params(("id" = String, Path, description = "Id of the held call")),
+    request_body = DecisionRequest,
+    responses(
+        (status = 200, description = "The call as decided", body = ApprovalView),
+        (status = 400, description = "Invalid request", body = AdminError),
+        (status = 404, description = "No such held call", body = AdminError),
+        (status = 409, description = "The call was already decided or expired", body = AdminError)
+    )
+)]
+pub async fn handle_approval_decision(
+    State(mut state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    bytes: Bytes,
+) -> Response {
+    state.refresh_config();
+    if let Some(response) = refuse_admin(&state, &headers, "approval decision") {
+        return response;
+    }
+    let request: Value = match serde_json::from_slice(&bytes) {
+        Ok(request) => request,
+        Err(_) => {
+            return admin_error(StatusCode::BAD_REQUEST, "Invalid JSON body".to_string());
+        }
+    };
+    let approve = match request.get("decision").and_then(Value::as_str) {
+        Some("approve") => true,
+        Some("reject") => false,
+        _ => {
+            return admin_error(
+                StatusCode::BAD_REQUEST,
+                "Invalid 'decision': expected approve or reject".to_string(),
+            );
+        }
+    };
+    let reason = request
+        .get("reason")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    if state.approvals.get(&id).is_none() {
+        return admin_error(StatusCode::NOT_FOUND, format!("No held call '{}'", id));
+    }
+    match decide(&state, &id, approve, reason) {
+        Ok(approval) => Json(approval.view()).into_response(),
+        Err(message) => admin_error(StatusCode::CONFLICT, message),
+    }
+}
+
+/// The body of `GET /admin/features`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FeatureList {
+    pub features: Vec<FeatureStatus>,
+}
+
+/// `GET /admin/features`: each feature's rollout and where it comes from.
+#[utoipa::path(
+    get,
+    path = "/admin/features",
+    tag = "admin",
+    summary = "List experimental features and their rollouts",
+    responses(
+        (status = 200, description = "Features", body = FeatureList),
+        (status = 404, description = "No scheme of the admin chain is configured")
+    )
+)]
+pub async fn handle_features(State(mut state): State<AppState>, headers: HeaderMap) -> Response {
+    state.refresh_config();
+    if let Some(response) = refuse_admin(&state, &headers, "feature listing") {
+        return response;
+    }
+    let features = FEATURES
+        .iter()
+        .map(|feature| feature_status(&state, feature))
+        .collect();
+    Json(FeatureList { features }).into_response()
+}
+
+/// Applies the fields of a `POST /admin/features/{name}` body to `rollout`.
+fn update_rollout(rollout: &mut FeatureConfig, request: &Value) -> Result<(), String> {
+    let Some(fields) = request.as_object() else {
+        return Err("Expected a JSON object".to_string());
+    };
+    for (field, value) in fields {
+        match field.as_str() {
+            "enabled" => {
+                rollout.enabled = value
+                    .as_bool()
+                    .ok_or("Invalid 'enabled': expected true or false")?;
+            }
+            "percent" => {
+                rollout.percent = value
+                    .as_u64()
+                    .filter(|percent| *percent <= 100)
+                    .ok_or("Invalid 'percent': expected 0 to 100")?
+                    as u8;
+            }
+            "sessions" => {
+                rollout.sessions = value
+                    .as_array()
+                    .and_then(|sessions| {
+                        sessions
+                            .iter()
+                            .map(|session| session.as_str().map(str::to_string))
+                            .collect()
+                    })
+                    .ok_or("Invalid 'sessions': expected an array of session ids")?;
+            }
+            _ => return Err(format!("Unknown field '{}'", field)),
+        }
+    }
+    Ok(())
+}
+
+/// The body of `POST /admin/features/{name}`; fields left out keep their
+/// current value.
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RolloutUpdate {
+    pub enabled: Option<bool>,
+    #[schema(maximum = 100)]
+    pub percent: Option<u8>,
+    pub sessions: Option<Vec<String>>,
+}
+
+/// `POST /admin/features/{name}`: rolls a feature out at runtime, with any of
+/// `{"enabled": bool, "percent": 0-100, "sessions": [...]}` over its current
+/// rollout. The override outlives reloads until `DELETE` drops it.
+#[utoipa::path(
+    post,
+    path = "/admin/features/{name}",
+    tag = "admin",
+    summary = "Roll a feature out at runtime, over its current rollout",
+    params(("name" = String, Path, description = "The feature")),
+    request_body = RolloutUpdate,
+    responses(
+        (status = 200, description = "The feature as rolled out", body = FeatureStatus),
+        (status = 400, description = "Invalid request", body = AdminError),
+        (status = 404, description = "No such feature", body = AdminError)
+    )
+)]
+pub async fn handle_feature_update(
+    State(mut state): State<AppState>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    bytes: Bytes,
+) -> Response {
+    state.refresh_config();
+    if let Some(response) = refuse_admin(&state, &headers, "feature update") {
+        return response;
+    }
+    if !FEATURES.contains(&name.as_str()) {
+        return admin_error(StatusCode::NOT_FOUND, format!("No feature '{}'", name));
+    }
+    let request: Value = match serde_json::from_slice(&bytes) {
+        Ok(request) => request,
+        Err(_) => {
+            return admin_error(StatusCode::BAD_REQUEST, "Invalid JSON body".to_string());
+        }
+    };
+    let (mut rollout, _) = state.features.rollout(&state.config, &name);
+    if let Err(message) = update_rollout(&mut rollout, &request) {
+        return admin_error(StatusCode::BAD_REQUEST, message);
+    }
+    state.features.set(&name, rollout);
+    info!(
+        "feature '{}' rolled out: {}",
+        name,
+        json!(feature_status(&state, &name))
+    );
+    // Tool lists differ when outputSchema or a held tool comes or goes.
+    state.notifier.tools_list_changed();
+    Json(feature_status(&state, &name)).into_response()
+}
+
+/// `DELETE /admin/features/{name}`: drops the runtime override, returning
+/// the feature to its configured rollout.
+#[utoipa::path(
+    delete,
+    path = "/admin/features/{name}",
+    tag = "admin",
+    summary = "Return a feature to its configured rollout",
+    params(("name" = String, Path, description = "The feature")),
+    responses(
+        (status = 200, description = "The feature as configured", body = FeatureStatus),
+        (status = 404, description = "No such feature", body = AdminError)
+    )
+)]
+pub async fn handle_feature_reset(
+    State(mut state): State<AppState>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    state.refresh_config();
+    if let Some(response) = refuse_admin(&state, &headers, "feature reset") {
+        return response;
+    }
+    if !FEATURES.contains(&name.as_str()) {
+        return admin_error(StatusCode::NOT_FOUND, format!("No feature '{}'", name));
+    }
+    if state.features.clear(&name) {
+        info!("feature '{}' back to its configured rollout", name);
+        state.notifier.tools_list_changed();
+    }
+    Json(feature_status(&state, &name)).into_response()
+}
+
+/// `GET /admin/usage`: what calls used this month by API key, and by each
+/// session the server holds.
+#[utoipa::path(
+    get,
+    path = "/admin/usage",
+    tag = "admin",
+    summary = "Usage of this month by API key, and of each session",
+    responses(
+        (status = 200, description = "Usage", body = CurrentUsage),
+        (status = 404, description = "No scheme of the admin chain is configured")
+    )
+)]
+pub async fn handle_usage(State(mut state): State<AppState>, headers: HeaderMap) -> Response {
+    state.refresh_config();
+    if let Some(response) = refuse_admin(&state, &headers, "usage listing") {
+        return response;
+    }
+    Json(state.usage.current()).into_response()
+}
+
+/// `GET /admin/usage/{month}`: what calls used in a `YYYY-MM` month by API
+/// key.
+#[utoipa::path(
+    get,
+    path = "/admin/usage/{month}",
+    tag = "admin",
+    summary = "Usage of a month by API key",
+    params(("month" = String, Path, pattern = "^[0-9]{4}-[0-9]{2}$")),
+    responses(
+        (status = 200, description = "The month's usage", body = MonthReport),
+        (status = 400, description = "Not a YYYY-MM month", body = AdminError),
+        (status = 404, description = "No usage recorded in the month", body = AdminError)
+    )
+)]
+pub async fn handle_usage_month(
+    State(mut state): State<AppState>,
+    Path(month): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    state.refresh_config();
+    if let Some(response) = refuse_admin(&state, &headers, "usage listing") {
+        return response;
+    }
+    if !valid_month(&month) {
+        return admin_error(
+            StatusCode::BAD_REQUEST,
+            format!("Invalid month '{}': expected YYYY-MM", month),
+        );
+    }
+    match state.usage.month_json(&month) {
+        Some(report) => Json(report).into_response(),
+        None => admin_error(
+            StatusCode::NOT_FOUND,
+            format!("No usage recorded in {}", month),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AdminConfig;
+
+    #[test]
+    fn test_update_rollout_checks_each_field() {
+        let mut rollout = FeatureConfig::default();
+        update_rollout(
+            &mut rollout,
+            &json!({ "percent": 10, "sessions": ["pilot"] }),
+        )
+        .unwrap();
+        assert_eq!(rollout.percent, 10);
+        assert_eq!(rollout.sessions, ["pilot"]);
+        assert!(rollout.enabled);
+        update_rollout(&mut rollout, &json!({ "enabled": false })).unwrap();
+        assert!(!rollout.enabled);
+        assert_eq!(rollout.percent, 10);
+        assert!(update_rollout(&mut rollout, &json!({ "percent": 101 })).is_err());
+        assert!(update_rollout(&mut rollout, &json!({ "sessions": [1] })).is_err());
+        assert!(update_rollout(&mut rollout, &json!({ "everyone": true })).is_err());
+        assert!(update_rollout(&mut rollout, &json!([])).is_err());
+    }
+
+    #[test]
+    fn test_build_commands_skips_missing_placeholders() {
+        let templates = AdminConfig::default().credential_commands;
+        let commands = build_commands(
+            &templates,
+            &json!({ "client_id": "id-1", "client_secret": "-s3cret" }),
+        )
+        .unwrap();
+        assert_eq!(
+            commands,
+            vec![vec![
+                "auth",
+                "login",
+                "--client-id",
+                "id-1",
+                "--client-secret=-s3cret"
+            ]]
+        );
+        let commands = build_commands(&templates, &json!({ "environment": "prod" })).unwrap();
+        assert_eq!(
+            commands[0],
+            vec!["config", "environment", "use", "-n", "prod"]
+        );
+        assert!(build_commands(&templates, &json!({ "client_id": "--help" })).is_err());
+        assert!(build_commands(&templates, &json!({ "password": "x" })).is_err());
+        assert!(build_commands(&templates, &json!({})).is_err());
+    }
+
+    #[test]
+    fn test_token_matches() {
+        assert!(token_matches("abc", "abc"));
+        assert!(!token_matches("abc", "abd"));
+        assert!(!token_matches("abc", "ab"));
+    }
+}
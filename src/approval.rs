@@ -0,0 +1,459 @@
+use crate::{
+    AppState,
+    content::{summary_block, tool_result},
+    mcp::new_session_id,
+    pcli::call_tool,
+    resources::StoredResource,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::{Instrument, info, info_span, warn};
+use utoipa::ToSchema;
+
+pub const APPROVAL_STATUS_TOOL: &str = "pcli2_approval_status";
+/// Each held call is also the resource `pcli2://approvals/<id>`, updated
+/// (with a notification) when it is decided and when it finishes.
+pub const APPROVAL_URI_PREFIX: &str = "pcli2://approvals/";
+/// Decided calls kept for polling, beyond the pending ones.
+const MAX_DECIDED_APPROVALS: usize = 200;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApprovalStatus {
+    Pending,
+    /// Approved and running.
+    Running,
+    Completed(Value),
+    Failed(String),
+    Rejected(Option<String>),
+    Expired,
+}
+
+/// An `ApprovalStatus` without what it holds, as `/admin/approvals` shows it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ApprovalState {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+    Rejected,
+    Expired,
+}
+
+impl ApprovalStatus {
+    pub fn state(&self) -> ApprovalState {
+        match self {
+            ApprovalStatus::Pending => ApprovalState::Pending,
+            ApprovalStatus::Running => ApprovalState::Running,
+            ApprovalStatus::Completed(_) => ApprovalState::Completed,
+            ApprovalStatus::Failed(_) => ApprovalState::Failed,
+            ApprovalStatus::Rejected(_) => ApprovalState::Rejected,
+            ApprovalStatus::Expired => ApprovalState::Expired,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            ApprovalStatus::Pending => "pending",
+            ApprovalStatus::Running => "running",
+            ApprovalStatus::Completed(_) => "completed",
+            ApprovalStatus::Failed(_) => "failed",
+            ApprovalStatus::Rejected(_) => "rejected",
+            ApprovalStatus::Expired => "expired",
+        }
+    }
+}
+
+/// A tool call held for an operator's decision.
+#[derive(Debug, Clone)]
+pub struct Approval {
+    pub id: String,
+    /// Internal tool name; calls run under its current public name.
+    pub tool: String,
+    pub arguments: Value,
+    /// Tenant, policy, API key, and session of the client that made the
+    /// call, which the approved call runs with.
+    pub tenant: Option<String>,
+    pub policy: Option<String>,
+    pub api_key: Option<String>,
+    pub session: Option<String>,
+    /// Correlation id of the request that made the call, which the
+    /// approved call keeps.
+    pub request_id: Option<String>,
+    pub requested_at: DateTime<Utc>,
+    expires: Instant,
+    pub status: ApprovalStatus,
+}
+
+impl Approval {
+    pub fn uri(&self) -> String {
+        format!("{}{}", APPROVAL_URI_PREFIX, self.id)
+    }
+
+    /// What operators and clients see of the call; the result itself is
+    /// only returned by `pcli2_approval_status`.
+    pub fn view(&self) -> ApprovalView {
+        ApprovalView {
+            id: self.id.clone(),
+            tool: self.tool.clone(),
+            arguments: self.arguments.clone(),
+            tenant: self.tenant.clone(),
+            request_id: self.request_id.clone(),
+            requested_at: self.requested_at.to_rfc3339(),
+            status: self.status.state(),
+            uri: self.uri(),
+            reason: match &self.status {
+                ApprovalStatus::Rejected(reason) => reason.clone(),
+                _ => None,
+            },
+            error: match &self.status {
+                ApprovalStatus::Failed(message) => Some(message.clone()),
+                _ => None,
+            },
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!(self.view())
+    }
+}
+
+/// A held call as `/admin/approvals` shows it.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[schema(as = Approval)]
+pub struct ApprovalView {
+    pub id: String,
+    pub tool: String,
+    #[schema(value_type = Object)]
+    pub arguments: Value,
+    pub tenant: Option<String>,
+    pub request_id: Option<String>,
+    #[schema(format = DateTime)]
+    pub requested_at: String,
+    pub status: ApprovalState,
+    pub uri: String,
+    /// Why the call was rejected.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Why the approved call failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Calls held by `approval.tools`, in the order they were made.
+#[derive(Clone, Default)]
+pub struct ApprovalQueue {
+    approvals: Arc<Mutex<Vec<Approval>>>,
+}
+
+impl ApprovalQueue {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Approval>> {
+        self.approvals
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Expires pending calls past their time and drops the oldest decided
+    /// ones beyond `MAX_DECIDED_APPROVALS`.
+    fn tidy(approvals: &mut Vec<Approval>) {
+        let now = Instant::now();
+        for approval in approvals.iter_mut() {
+            if approval.status == ApprovalStatus::Pending && approval.expires <= now {
+                approval.status = ApprovalStatus::Expired;
+            }
+        }
+        let decided = approvals
+            .iter()
+            .filter(|approval| approval.status != ApprovalStatus::Pending)
+            .count();
+        let mut excess = decided.saturating_sub(MAX_DECIDED_APPROVALS);
+        approvals.retain(|approval| {
+            let drop = excess > 0
+                && !matches!(
+                    approval.status,
+                    ApprovalStatus::Pending | ApprovalStatus::Running
+                );
+            if drop {
+                excess -= 1;
+            }
+            !drop
+        });
+    }
+
+    pub fn get(&self, id: &str) -> Option<Approval> {
+        let mut approvals = self.lock();
+        Self::tidy(&mut approvals);
+        approvals.iter().find(|approval| approval.id == id).cloned()
+    }
+
+    /// Every held call, oldest first.
+    pub fn list(&self) -> Vec<Approval> {
+        let mut approvals = self.lock();
+        Self::tidy(&mut approvals);
+        approvals.clone()
+    }
+
+    fn push(&self, approval: Approval) {
+        let mut approvals = self.lock();
+        Self::tidy(&mut approvals);
+        approvals.push(approval);
+    }
+
+    /// Moves a call from `from` to `to`, returning it as it is now, or
+    /// why it cannot move.
+    fn transition(
+        &self,
+        id: &str,
+        from: &ApprovalStatus,
+        to: ApprovalStatus,
+    ) -> Result<Approval, String> {
+        let mut approvals = self.lock();
+        Self::tidy(&mut approvals);
+        let approval = approvals
+            .iter_mut()
+            .find(|approval| approval.id == id)
+            .ok_or_else(|| format!("No held call '{}'", id))?;
+        if &approval.status != from {
+            return Err(format!(
+                "Call '{}' is {}, not {}",
+                id,
+                approval.status.name(),
+                from.name()
+            ));
+        }
+        approval.status = to;
+        Ok(approval.clone())
+    }
+}
+
+/// Publishes the call's state as its resource and notifies subscribers.
+fn publish(state: &AppState, approval: &Approval) {
+    let uri = approval.uri();
+    state.resources.replace(StoredResource {
+        uri: uri.clone(),
+        name: format!("approval of {}", approval.tool),
+        mime_type: "application/json".to_string(),
+        text: Arc::new(approval.to_json().to_string()),
+        tenant: approval.tenant.clone(),
+        blob: false,
+    });
+    state.notifier.resource_updated(&uri);
+}
+
+/// Holds a call to `tool` for an operator and tells the client how to
+/// follow it.
+pub fn hold(state: &AppState, tool: &str, arguments: Value) -> Value {
+    let approval = Approval {
+        id: new_session_id(),
+        tool: tool.to_string(),
+        arguments,
+        tenant: state.tenant.clone(),
+        policy: state.policy.clone(),
+        api_key: state.api_key.clone(),
+        session: state.session.clone(),
+        request_id: state.request_id.clone(),
+        requested_at: Utc::now(),
+        expires: Instant::now() + Duration::from_secs(state.config.approval.expire_secs),
+        status: ApprovalStatus::Pending,
+    };
+    info!("✋ {} held for approval as {}", tool, approval.id);
+    publish(state, &approval);
+    state.approvals.push(approval.clone());
+    let mut result = tool_result(vec![summary_block(format!(
+        "Awaiting approval: this call to {} must be approved by an operator before it runs. Its approval id is {}. Call {} with this id to get the result, or subscribe to {} to be notified of the decision.",
+        state.config.tools.public_name(tool),
+        approval.id,
+        state.config.tools.public_name(APPROVAL_STATUS_TOOL),
+        approval.uri()
+    ))]);
+    result["_meta"]["approval"] = json!({
+        "id": approval.id,
+        "status": "pending",
+        "uri": approval.uri()
+    });
+    result
+}
+
+/// An operator's decision on a held call. An approved call runs in the
+/// background with the tenant and session of the client that made it.
+pub fn decide(
+    state: &AppState,
+    id: &str,
+    approve: bool,
+    reason: Option<String>,
+) -> Result<Approval, String> {
+    let to = if approve {
+        ApprovalStatus::Running
+    } else {
+        ApprovalStatus::Rejected(reason)
+    };
+    let approval = state
+        .approvals
+        .transition(id, &ApprovalStatus::Pending, to)?;
+    info!(
+        "✋ {} for {} was {}",
+        approval.id,
+        approval.tool,
+        approval.status.name()
+    );
+    publish(state, &approval);
+    if approve {
+        let mut state = state.clone();
+        state.tenant = approval.tenant.clone();
+        state.policy = approval.policy.clone();
+        state.api_key = approval.api_key.clone();
+        state.session = approval.session.clone();
+        state.request_id = approval.request_id.clone();
+        state.approved = true;
+        let held = approval.clone();
+        let span = info_span!(
+            "request",
+            id = held.request_id.as_deref().unwrap_or_default()
+        );
+        let run = async move {
+            let params = json!({
+                "name": state.config.tools.public_name(&held.tool),
+                "arguments": held.arguments
+            });
+            let status = match call_tool(&state, params).await {
+                Ok(result) => ApprovalStatus::Completed(result),
+                Err(message) => {
+                    warn!("⚠ approved call {} failed: {}", held.id, message);
+                    ApprovalStatus::Failed(message)
+                }
+            };
+            if let Ok(finished) =
+                state
+                    .approvals
+                    .transition(&held.id, &ApprovalStatus::Running, status)
+            {
+                publish(&state, &finished);
+            }
+        };
+        tokio::spawn(run.instrument(span));
+    }
+    Ok(approval)
+}
+
+/// `pcli2_approval_status`: the result of an approved call once it is
+/// done, or where the call stands.
+pub fn approval_status(state: &AppState, args: &Value) -> Result<Value, String> {
+    let id = args
+        .get("id")
+        .and_then(Value::as_str)
+        .ok_or_else(|| "Missing required parameter 'id'".to_string())?;
+    let approval = state
+        .approvals
+        .get(id)
+        .filter(|approval| state.tenant.is_none() || approval.tenant == state.tenant)
+        .ok_or_else(|| format!("No held call '{}'", id))?;
+    let tool = state.config.tools.public_name(&approval.tool);
+    let mut result = match &approval.status {
+        ApprovalStatus::Completed(result) => result.clone(),
+        ApprovalStatus::Failed(message) => {
+            return Err(format!("Approved call to {} failed: {}", tool, message));
+        }
+        ApprovalStatus::Rejected(reason) => {
+            return Err(match reason {
+                Some(reason) => format!("The operator rejected the call to {}: {}", tool, reason),
+                None => format!("The operator rejected the call to {}", tool),
+            });
+        }
+        ApprovalStatus::Expired => {
+            return Err(format!(
+                "The call to {} expired before an operator decided on it; call it again to ask anew",
+                tool
+            ));
+        }
+        ApprovalStatus::Pending => tool_result(vec![summary_block(format!(
+            "The call to {} is still awaiting approval.",
+            tool
+        ))]),
+        ApprovalStatus::Running => tool_result(vec![summary_block(format!(
+            "The call to {} was approved and is running.",
+            tool
+        ))]),
+    };
+    result["_meta"]["approval"] = json!({
+        "id": approval.id,
+        "status": approval.status.name(),
+        "uri": approval.uri()
+    });
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approval(id: &str, status: ApprovalStatus, expires_in: Duration) -> Approval {
+        Approval {
+            id: id.to_string(),
+            tool: "pcli2_asset_metadata_delete".to_string(),
+            arguments: json!({ "path": "/Root/a.stl", "name": "Weight" }),
+            tenant: None,
+            policy: None,
+            api_key: None,
+            session: None,
+            request_id: None,
+            requested_at: Utc::now(),
+            expires: Instant::now() + expires_in,
+            status,
+        }
+    }
+
+    #[test]
+    fn test_calls_move_only_from_the_expected_status() {
+        let queue = ApprovalQueue::default();
+        queue.push(approval(
+            "a",
+            ApprovalStatus::Pending,
+            Duration::from_secs(60),
+        ));
+        queue.push(approval("b", ApprovalStatus::Pending, Duration::ZERO));
+
+        assert_eq!(queue.get("b").unwrap().status, ApprovalStatus::Expired);
+        assert!(
+            queue
+                .transition("b", &ApprovalStatus::Pending, ApprovalStatus::Running)
+                .unwrap_err()
+                .contains("is expired")
+        );
+        let running = queue
+            .transition("a", &ApprovalStatus::Pending, ApprovalStatus::Running)
+            .unwrap();
+        assert_eq!(running.to_json()["status"], "running");
+        assert!(
+            queue
+                .transition("a", &ApprovalStatus::Pending, ApprovalStatus::Running)
+                .is_err()
+        );
+        assert!(queue.get("missing").is_none());
+    }
+
+    #[test]
+    fn test_old_decided_calls_are_dropped() {
+        let queue = ApprovalQueue::default();
+        queue.push(approval(
+            "pending",
+            ApprovalStatus::Pending,
+            Duration::from_secs(60),
+        ));
+        for index in 0..MAX_DECIDED_APPROVALS + 5 {
+            queue.push(approval(
+                &index.to_string(),
+                ApprovalStatus::Rejected(None),
+                Duration::ZERO,
+            ));
+        }
+        let approvals = queue.list();
+        assert_eq!(approvals.len(), MAX_DECIDED_APPROVALS + 1);
+        assert_eq!(approvals[0].id, "pending");
+        assert!(queue.get("0").is_none());
+    }
+}
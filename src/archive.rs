@@ -0,0 +1,671 @@
+use crate::{
+    AppState,
+    content::{raw_output_block, summary_block, tool_result},
+    export::download,
+    pcli::{
+        DEFAULT_FAN_OUT_CONCURRENCY, in_current_execution, record_download,
+        run_pcli2_asset_thumbnail, scratch_dir, validate_range_u64,
+    },
+    resources::resource_link,
+    snapshot::{folder_selection, list_folder_assets},
+};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64_STANDARD};
+use chrono::{Datelike, Timelike, Utc};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::{sync::Semaphore, task::JoinSet};
+
+pub const ARCHIVE_TOOL: &str = "pcli2_folder_download_archive";
+/// Values accepted for `archive`.
+pub const ARCHIVE_FORMATS: [&str; 2] = ["zip", "tar.zst"];
+/// Values accepted for `content`.
+pub const ARCHIVE_CONTENTS: [&str; 2] = ["assets", "thumbnails"];
+pub const DEFAULT_ARCHIVE_LIMIT: u64 = 100;
+pub const MAX_ARCHIVE_LIMIT: u64 = 500;
+/// The first member of every archive.
+const MANIFEST: &str = "manifest.json";
+/// Longest member name, so it fits the name field of a tar header.
+const MAX_NAME_BYTES: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Zip,
+    TarZst,
+}
+
+impl Format {
+    fn parse(args: &Value) -> Result<Self, String> {
+        match args.get("archive").and_then(Value::as_str) {
+            None | Some("zip") => Ok(Self::Zip),
+            Some("tar.zst") => Ok(Self::TarZst),
+            Some(other) => Err(format!(
+                "Invalid 'archive': '{}'; expected one of {}",
+                other,
+                ARCHIVE_FORMATS.join(", ")
+            )),
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Zip => "zip",
+            Self::TarZst => "tar.zst",
+        }
+    }
+
+    fn mime_type(self) -> &'static str {
+        match self {
+            Self::Zip => "application/zip",
+            Self::TarZst => "application/zstd",
+        }
+    }
+}
+
+fn thumbnails(args: &Value) -> Result<bool, String> {
+    match args.get("content").and_then(Value::as_str) {
+        None | Some("assets") => Ok(false),
+        Some("thumbnails") => Ok(true),
+        Some(other) => Err(format!(
+            "Invalid 'content': '{}'; expected one of {}",
+            other,
+            ARCHIVE_CONTENTS.join(", ")
+        )),
+    }
+}
+
+/// A name safe to extract: no directories, no `.` or `..`, and short
+/// enough for a tar header, keeping the extension when it is cut.
+fn safe_name(name: &str) -> String {
+    let cleaned: String = name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c == '/' || c == '\\' || c.is_control() {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    if cleaned.is_empty() || cleaned.chars().all(|c| c == '.') {
+        return "asset".to_string();
+    }
+    if cleaned.len() <= MAX_NAME_BYTES {
+        return cleaned;
+    }
+    let extension = cleaned
+        .rsplit_once('.')
+        .map(|(_, extension)| extension)
+        .filter(|extension| extension.len() < 16)
+        .map_or(String::new(), |extension| format!(".{}", extension));
+    let mut end = MAX_NAME_BYTES - extension.len();
+    while !cleaned.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{}", &cleaned[..end], extension)
+}
+
+/// The member name of an asset's file: its name (or the last segment of
+/// its path, or its UUID), with `.png` for a thumbnail, numbered when
+/// another asset of the archive already has it.
+fn member_name(asset: &Value, thumbnail: bool, taken: &mut HashSet<String>) -> String {
+    let base = asset
+        .get("name")
+        .and_then(Value::as_str)
+        .or_else(|| {
+            asset
+                .get("path")
+                .and_then(Value::as_str)
+                .and_then(|path| path.rsplit('/').find(|segment| !segment.is_empty()))
+        })
+        .or_else(|| asset.get("uuid").and_then(Value::as_str))
+        .unwrap_or("asset");
+    let base = if thumbnail {
+        format!(
+            "{}.png",
+            base.rsplit_once('.').map_or(base, |(stem, _)| stem)
+        )
+    } else {
+        base.to_string()
+    };
+    let name = safe_name(&base);
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => {
+            (stem.to_string(), format!(".{}", extension))
+        }
+        _ => (name.clone(), String::new()),
+    };
+    let mut candidate = name;
+    let mut number = 1;
+    while candidate == MANIFEST || !taken.insert(candidate.clone()) {
+        number += 1;
+        candidate = safe_name(&format!("{} ({}){}", stem, number, extension));
+    }
+    candidate
+}
+
+/// The directory a call downloads into, removed with everything in it when
+/// the call is done.
+struct Staging(PathBuf);
+
+impl Staging {
+    fn create() -> Result<Self, String> {
+        // Archives started in the same millisecond need different directories.
+        static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|err| format!("Failed to read system time: {}", err))?
+            .as_millis();
+        let mut path = scratch_dir();
+        path.push(format!(
+            "pcli2-archive-{}-{}-{}",
+            std::process::id(),
+            timestamp,
+            SEQUENCE.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&path)
+            .map_err(|err| format!("Failed to create {}: {}", path.display(), err))?;
+        Ok(Self(path))
+    }
+}
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A downloaded file going into the archive.
+struct Member {
+    name: String,
+    file: PathBuf,
+    bytes: u64,
+    crc: u32,
+}
+
+/// Size, CRC-32, and hex SHA-256 of a file, read in chunks.
+fn digest(file: &Path) -> io::Result<(u64, u32, String)> {
+    let mut reader = File::open(file)?;
+    let mut buffer = vec![0; 64 * 1024];
+    let mut crc = crc32fast::Hasher::new();
+    let mut sha = Sha256::new();
+    let mut bytes = 0;
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        crc.update(&buffer[..read]);
+        sha.update(&buffer[..read]);
+        bytes += read as u64;
+    }
+    let sha256 = sha
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    Ok((bytes, crc.finalize(), sha256))
+}
+
+fn too_large(what: &str) -> io::Error {
+    io::Error::other(format!("{} does not fit a zip file without Zip64", what))
+}
+
+/// Writes a zip file of stored (uncompressed) members; CAD files and
+/// thumbnails are mostly compressed already.
+struct ZipWriter<W: Write> {
+    out: W,
+    offset: u64,
+    central: Vec<u8>,
+    entries: u16,
+    time: u16,
+    date: u16,
+}
+
+impl<W: Write> ZipWriter<W> {
+    fn new(out: W) -> Self {
+        let now = Utc::now();
+        Self {
+            out,
+            offset: 0,
+            central: Vec::new(),
+            entries: 0,
+            time: ((now.hour() << 11) | (now.minute() << 5) | (now.second() / 2)) as u16,
+            date: (((now.year() - 1980).max(0) as u32) << 9 | (now.month() << 5) | now.day())
+                as u16,
+        }
+    }
+
+    fn add(&mut self, name: &str, bytes: u64, crc: u32, data: &mut impl Read) -> io::Result<()> {
+        let size = u32::try_from(bytes).map_err(|_| too_large(name))?;
+        let offset = u32::try_from(self.offset).map_err(|_| too_large("The archive"))?;
+        self.entries = self
+            .entries
+            .checked_add(1)
+            .ok_or_else(|| too_large("The number of files"))?;
+        let name_len = name.len() as u16;
+        // Shared by the local header and its central directory entry: version
+        // needed, UTF-8 names, stored, time, date, CRC, sizes, name length.
+        let mut common = Vec::with_capacity(26);
+        common.extend_from_slice(&20u16.to_le_bytes());
+        common.extend_from_slice(&0x0800u16.to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+        common.extend_from_slice(&self.time.to_le_bytes());
+        common.extend_from_slice(&self.date.to_le_bytes());
+        common.extend_from_slice(&crc.to_le_bytes());
+        common.extend_from_slice(&size.to_le_bytes());
+        common.extend_from_slice(&size.to_le_bytes());
+        common.extend_from_slice(&name_len.to_le_bytes());
+
+        let mut local = Vec::with_capacity(30 + name.len());
+        local.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        local.extend_from_slice(&common);
+        local.extend_from_slice(&0u16.to_le_bytes());
+        local.extend_from_slice(name.as_bytes());
+        self.out.write_all(&local)?;
+        let copied = io::copy(&mut data.take(bytes), &mut self.out)?;
+        if copied != bytes {
+            return Err(io::Error::other(format!(
+                "{} changed while archiving",
+                name
+            )));
+        }
+        self.offset += local.len() as u64 + bytes;
+
+        // Made by Unix, so the mode in the external attributes is read.
+        self.central
+            .extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        self.central
+            .extend_from_slice(&((3u16 << 8) | 20).to_le_bytes());
+        self.central.extend_from_slice(&common);
+        self.central.extend_from_slice(&[0; 8]);
+        self.central
+            .extend_from_slice(&(0o100644u32 << 16).to_le_bytes());
+        self.central.extend_from_slice(&offset.to_le_bytes());
+        self.central.extend_from_slice(name.as_bytes());
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<W> {
+        let offset = u32::try_from(self.offset).map_err(|_| too_large("The archive"))?;
+        let mut end = Vec::with_capacity(22);
+        end.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        end.extend_from_slice(&[0; 4]);
+        end.extend_from_slice(&self.entries.to_le_bytes());
+        end.extend_from_slice(&self.entries.to_le_bytes());
+        end.extend_from_slice(&(self.central.len() as u32).to_le_bytes());
+        end.extend_from_slice(&offset.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes());
+        self.out.write_all(&self.central)?;
+        self.out.write_all(&end)?;
+        Ok(self.out)
+    }
+}
+
+/// The ustar header of a regular file.
+fn tar_header(name: &str, bytes: u64, mtime: u64) -> [u8; 512] {
+    let mut header = [0u8; 512];
+    let mut field = |start: usize, value: &[u8]| {
+        header[start..start + value.len()].copy_from_slice(value);
+    };
+    field(0, name.as_bytes());
+    field(100, b"0000644\0");
+    field(108, b"0000000\0");
+    field(116, b"0000000\0");
+    field(124, format!("{:011o}\0", bytes).as_bytes());
+    field(136, format!("{:011o}\0", mtime).as_bytes());
+    field(148, b"        ");
+    field(156, b"0");
+    field(257, b"ustar\0");
+    field(263, b"00");
+    let checksum: u32 = header.iter().map(|byte| *byte as u32).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+    header
+}
+
+/// Writes the manifest, then the members in order, into `out`, reading
+/// each member from disk as it goes.
+fn write_archive(
+    format: Format,
+    manifest: &[u8],
+    members: &[Member],
+    out: &Path,
+) -> io::Result<()> {
+    let file = BufWriter::new(File::create(out)?);
+    match format {
+        Format::Zip => {
+            let mut zip = ZipWriter::new(file);
+            zip.add(
+                MANIFEST,
+                manifest.len() as u64,
+                crc32fast::hash(manifest),
+                &mut &manifest[..],
+            )?;
+            for member in members {
+                zip.add(
+                    &member.name,
+                    member.bytes,
+                    member.crc,
+                    &mut File::open(&member.file)?,
+                )?;
+            }
+            zip.finish()?.flush()
+        }
+        Format::TarZst => {
+            let mtime = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs());
+            let mut tar = zstd::Encoder::new(file, 0)?;
+            let mut add = |name: &str, bytes: u64, data: &mut dyn Read| -> io::Result<()> {
+                tar.write_all(&tar_header(name, bytes, mtime))?;
+                let copied = io::copy(&mut data.take(bytes), &mut tar)?;
+                if copied != bytes {
+                    return Err(io::Error::other(format!(
+                        "{} changed while archiving",
+                        name
+                    )));
+                }
+                let padding = (512 - bytes % 512) % 512;
+                tar.write_all(&vec![0; padding as usize])
+            };
+            add(MANIFEST, manifest.len() as u64, &mut &manifest[..])?;
+            for member in members {
+                add(&member.name, member.bytes, &mut File::open(&member.file)?)?;
+            }
+            tar.write_all(&[0; 1024])?;
+            tar.finish()?.flush()
+        }
+    }
+}
+
+/// Downloads one asset, or its thumbnail, into `file`.
+async fn fetch(
+    state: &AppState,
+    single: Value,
+    thumbnail: bool,
+    file: &Path,
+) -> Result<(), String> {
+    if thumbnail {
+        let encoded = run_pcli2_asset_thumbnail(single).await?;
+        let bytes = BASE64_STANDARD
+            .decode(encoded)
+            .map_err(|err| format!("Failed to decode the thumbnail: {}", err))?;
+        return tokio::fs::write(file, bytes)
+            .await
+            .map_err(|err| format!("Failed to write the thumbnail: {}", err));
+    }
+    download(&state.config.export, &single, file, &[]).await?;
+    let bytes = tokio::fs::metadata(file)
+        .await
+        .map(|metadata| metadata.len())
+        .unwrap_or(0);
+    record_download(bytes as usize);
+    if bytes == 0 {
+        return Err("pcli2 asset download produced an empty file".to_string());
+    }
+    Ok(())
+}
+
+/// Downloads up to `limit` assets of a folder (from `offset` on), or their
+/// thumbnails, and packs them with a `manifest.json` into one zip or
+/// tar.zst file returned as a `pcli2://results/...` blob resource.
+pub async fn download_archive(state: &AppState, args: Value) -> Result<Value, String> {
+    validate_range_u64(&args, "limit", 1, MAX_ARCHIVE_LIMIT)?;
+    validate_range_u64(&args, "concurrent", 1, 10)?;
+    let format = Format::parse(&args)?;
+    let thumbnail = thumbnails(&args)?;
+    let folder = folder_selection(&args)?;
+    let limit = args
+        .get("limit")
+        .and_then(Value::as_u64)
+        .unwrap_or(DEFAULT_ARCHIVE_LIMIT) as usize;
+    let offset = args.get("offset").and_then(Value::as_u64).unwrap_or(0) as usize;
+    let concurrency = args
+        .get("concurrent")
+        .and_then(Value::as_u64)
+        .unwrap_or(DEFAULT_FAN_OUT_CONCURRENCY as u64) as usize;
+
+    let assets = list_folder_assets(&folder, false).await?;
+    let total = assets.len();
+    let page: Vec<Value> = assets.into_iter().skip(offset).take(limit).collect();
+    if page.is_empty() {
+        return Err(format!(
+            "The folder has {} assets; none from position {} on to archive",
+            total,
+            offset + 1
+        ));
+    }
+
+    let staging = Staging::create()?;
+    let semaphore = Arc::new(Semaphore::new(concurrency));
+    let mut tasks = JoinSet::new();
+    let mut outcomes: Vec<Option<Result<(), String>>> = vec![None; page.len()];
+    for (index, asset) in page.iter().enumerate() {
+        let mut single = json!({});
+        if let Some(tenant) = folder.get("tenant") {
+            single["tenant"] = tenant.clone();
+        }
+        match (
+            asset.get("uuid").and_then(Value::as_str),
+            asset.get("path").and_then(Value::as_str),
+        ) {
+            (Some(uuid), _) => single["uuid"] = json!(uuid),
+            (None, Some(path)) => single["path"] = json!(path),
+            (None, None) => {
+                outcomes[index] = Some(Err("The asset has neither a UUID nor a path".to_string()));
+                continue;
+            }
+        }
+        let state = state.clone();
+        let semaphore = semaphore.clone();
+        let file = staging.0.join(index.to_string());
+        tasks.spawn(in_current_execution(async move {
+            let _permit = semaphore.acquire_owned().await;
+            (index, fetch(&state, single, thumbnail, &file).await)
+        }));
+    }
+    while let Some(joined) = tasks.join_next().await {
+        let (index, outcome) = joined.map_err(|err| format!("Download task failed: {}", err))?;
+        outcomes[index] = Some(outcome);
+    }
+
+    let mut taken = HashSet::new();
+    let mut members = Vec::new();
+    let mut listing = Vec::with_capacity(page.len());
+    let mut first_error = None;
+    for (position, (asset, outcome)) in page.iter().zip(outcomes).enumerate() {
+        let mut entry = json!({
+            "index": offset + position + 1,
+            "name": asset.get("name").cloned().unwrap_or(Value::Null),
+            "uuid": asset.get("uuid").cloned().unwrap_or(Value::Null),
+            "path": asset.get("path").cloned().unwrap_or(Value::Null)
+        });
+        let file = staging.0.join(position.to_string());
+        let outcome = outcome
+            .unwrap_or_else(|| Err("The download did not finish".to_string()))
+            .and_then(|()| {
+                digest(&file).map_err(|err| format!("Failed to read the download: {}", err))
+            });
+        match outcome {
+            Ok((bytes, crc, sha256)) => {
+                let name = member_name(asset, thumbnail, &mut taken);
+                entry["status"] = json!("ok");
+                entry["file"] = json!(name);
+                entry["bytes"] = json!(bytes);
+                entry["sha256"] = json!(sha256);
+                members.push(Member {
+                    name,
+                    file,
+                    bytes,
+                    crc,
+                });
+            }
+            Err(message) => {
+                entry["status"] = json!("error");
+                entry["error"] = json!(message);
+                first_error.get_or_insert(message);
+            }
+        }
+        listing.push(entry);
+    }
+    if members.is_empty() {
+        return Err(format!(
+            "None of the {} assets could be downloaded: {}",
+            page.len(),
+            first_error.unwrap_or_default()
+        ));
+    }
+
+    let failed = page.len() - members.len();
+    let content = if thumbnail { "thumbnails" } else { "assets" };
+    let manifest = serde_json::to_string_pretty(&json!({
+        "folder_uuid": folder.get("folder_uuid").cloned().unwrap_or(Value::Null),
+        "folder_path": folder.get("folder_path").cloned().unwrap_or(Value::Null),
+        "tenant": folder.get("tenant").cloned().unwrap_or(Value::Null),
+        "content": content,
+        "archive": format.extension(),
+        "created_at": Utc::now().to_rfc3339(),
+        "total": total,
+        "offset": offset,
+        "archived": members.len(),
+        "failed": failed,
+        "assets": listing
+    }))
+    .map_err(|err| format!("Failed to render the manifest: {}", err))?;
+
+    let max_bytes = state.config.export.max_bytes;
+    let files_bytes = members.iter().map(|member| member.bytes).sum::<u64>();
+    if format == Format::Zip && files_bytes > max_bytes as u64 {
+        return Err(format!(
+            "The files come to {} bytes, more than 'export.max_bytes' ({}); archive fewer with 'limit' or use archive tar.zst",
+            files_bytes, max_bytes
+        ));
+    }
+    let archive = staging.0.join("archive");
+    let written = {
+        let archive = archive.clone();
+        let manifest = manifest.clone();
+        tokio::task::spawn_blocking(move || {
+            write_archive(format, manifest.as_bytes(), &members, &archive)
+        })
+    };
+    written
+        .await
+        .map_err(|err| format!("Archive task failed: {}", err))?
+        .map_err(|err| format!("Failed to write the archive: {}", err))?;
+    let bytes = tokio::fs::read(&archive)
+        .await
+        .map_err(|err| format!("Failed to read the archive: {}", err))?;
+    if bytes.len() > max_bytes {
+        return Err(format!(
+            "The archive is {} bytes, more than 'export.max_bytes' ({}); archive fewer with 'limit'",
+            bytes.len(),
+            max_bytes
+        ));
+    }
+
+    let folder_name = folder
+        .get("folder_path")
+        .and_then(Value::as_str)
+        .and_then(|path| path.rsplit('/').find(|segment| !segment.is_empty()))
+        .or_else(|| folder.get("folder_uuid").and_then(Value::as_str))
+        .unwrap_or("folder");
+    let suffix = if thumbnail { "-thumbnails" } else { "" };
+    let name = safe_name(&format!("{}{}.{}", folder_name, suffix, format.extension()));
+    let tenant = args.get("tenant").and_then(Value::as_str);
+    let stored = state
+        .resources
+        .insert_blob(&name, format.mime_type(), &bytes, tenant);
+    drop(staging);
+
+    let mut headline = format!(
+        "Archived {} {} of assets {}-{} of {} in the folder into {} ({} bytes, with {}) as resource {}; read it with resources/read.",
+        page.len() - failed,
+        content,
+        offset + 1,
+        offset + page.len(),
+        total,
+        name,
+        bytes.len(),
+        MANIFEST,
+        stored.uri
+    );
+    if failed > 0 {
+        headline.push_str(&format!(
+            " {} could not be downloaded; {} lists why.",
+            failed, MANIFEST
+        ));
+    }
+    if offset + page.len() < total {
+        headline.push_str(&format!(
+            " Pass offset {} for the next assets.",
+            offset + page.len()
+        ));
+    }
+    Ok(tool_result(vec![
+        summary_block(headline),
+        resource_link(&stored),
+        raw_output_block(manifest),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_member_names_are_safe_and_unique() {
+        let mut taken = HashSet::new();
+        let asset = json!({ "name": "Bracket.stl", "path": "/Root/Castings/Bracket.stl" });
+        assert_eq!(member_name(&asset, false, &mut taken), "Bracket.stl");
+        assert_eq!(member_name(&asset, false, &mut taken), "Bracket (2).stl");
+        assert_eq!(member_name(&asset, true, &mut taken), "Bracket.png");
+        let escaping = json!({ "name": "../../etc/passwd" });
+        assert_eq!(
+            member_name(&escaping, false, &mut taken),
+            ".._.._etc_passwd"
+        );
+        assert_eq!(
+            member_name(&json!({ "name": ".." }), false, &mut taken),
+            "asset"
+        );
+        assert_eq!(
+            member_name(&json!({ "path": "/Root/a.step" }), false, &mut taken),
+            "a.step"
+        );
+        assert_eq!(
+            member_name(&json!({ "name": "manifest.json" }), false, &mut taken),
+            "manifest (2).json"
+        );
+        let long = safe_name(&format!("{}.step", "é".repeat(80)));
+        assert!(
+            long.len() <= MAX_NAME_BYTES && long.ends_with(".step"),
+            "{}",
+            long
+        );
+    }
+
+    #[test]
+    fn test_tar_headers_carry_size_and_checksum() {
+        let header = tar_header("a.stl", 1000, 0);
+        assert_eq!(&header[..5], b"a.stl");
+        assert_eq!(&header[124..136], b"00000001750\0");
+        assert_eq!(&header[257..263], b"ustar\0");
+        let mut blank = header;
+        blank[148..156].copy_from_slice(b"        ");
+        let sum: u32 = blank.iter().map(|byte| *byte as u32).sum();
+        let stored = std::str::from_utf8(&header[148..154]).unwrap();
+        assert_eq!(u32::from_str_radix(stored, 8).unwrap(), sum);
+    }
+}
@@ -0,0 +1,426 @@
+use serde_json::{Map, Value};
+
+pub const ARG_MODE_LENIENT: &str = "lenient";
+pub const ARG_MODE_STRICT: &str = "strict";
+pub const DEFS_REF_PREFIX: &str = "#/$defs/";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArgMode {
+    #[default]
+    Lenient,
+    Strict,
+}
+
+impl ArgMode {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            ARG_MODE_LENIENT => Ok(ArgMode::Lenient),
+            ARG_MODE_STRICT => Ok(ArgMode::Strict),
+            _ => Err(format!("Unsupported argument mode '{}'", value)),
+        }
+    }
+}
+
+pub fn prepare_arguments(schema: &Value, args: Value, mode: ArgMode) -> Result<Value, String> {
+    let args = match args {
+        Value::Object(map) => map,
+        Value::Null => Map::new(),
+        _ => return Err("Invalid arguments: expected a JSON object".to_string()),
+    };
+    let properties = resolve_properties(schema);
+
+    let args = match mode {
+        ArgMode::Lenient => coerce_arguments(&properties, args),
+        ArgMode::Strict => args,
+    };
+    validate_arguments(schema, &properties, &args)?;
+    Ok(Value::Object(args))
+}
+
+pub(crate) fn resolve_properties(schema: &Value) -> Map<String, Value> {
+    let Some(properties) = schema.get("properties").and_then(|v| v.as_object()) else {
+        return Map::new();
+    };
+    properties
+        .iter()
+        .map(|(key, prop)| {
+            let resolved = prop
+                .get("$ref")
+                .and_then(|v| v.as_str())
+                .and_then(|r| r.strip_prefix(DEFS_REF_PREFIX))
+                .and_then(|name| schema.get("$defs").and_then(|defs| defs.get(name)))
+                .unwrap_or(prop);
+            (key.clone(), resolved.clone())
+        })
+        .collect()
+}
+
+fn coerce_arguments(
+    properties: &Map<String, Value>,
+    args: Map<String, Value>,
+) -> Map<String, Value> {
+    let (exact, other): (Vec<_>, Vec<_>) = args
+        .into_iter()
+        .partition(|(key, _)| properties.contains_key(key));
+    let mut out = Map::new();
+    for (key, value) in exact.into_iter().chain(other) {
+        let key = if properties.contains_key(&key) {
+            key
+        } else {
+            let normalized = to_snake_case(&key);
+            if properties.contains_key(&normalized) {
+                normalized
+            } else {
+                key
+            }
+        };
+        if out.contains_key(&key) {
+            continue;
+        }
+        let value = match properties.get(&key) {
+            Some(prop) => coerce_value(prop, value),
+            None => value,
+        };
+        out.insert(key, value);
+    }
+    out
+}
+
+pub(crate) fn coerce_value(prop: &Value, value: Value) -> Value {
+    if let Some(variants) = prop.get("oneOf").and_then(|v| v.as_array()) {
+        if variants.iter().any(|variant| matches_type(variant, &value)) {
+            return value;
+        }
+        return variants
+            .iter()
+            .map(|variant| coerce_value(variant, value.clone()))
+            .find(|coerced| {
+                variants
+                    .iter()
+                    .any(|variant| matches_type(variant, coerced))
+            })
+            .unwrap_or(value);
+    }
+
+    let value = match prop.get("type").and_then(|v| v.as_str()) {
+        Some("boolean") => coerce_bool(value),
+        Some("number") => coerce_number(value),
+        Some("integer") => coerce_integer(value),
+        Some("string") => coerce_string(value),
+        _ => value,
+    };
+
+    match (prop.get("enum").and_then(|v| v.as_array()), &value) {
+        (Some(allowed), Value::String(s)) if !allowed.contains(&value) => allowed
+            .iter()
+            .find(|candidate| {
+                candidate
+                    .as_str()
+                    .is_some_and(|candidate| candidate.eq_ignore_ascii_case(s.trim()))
+            })
+            .cloned()
+            .unwrap_or(value),
+        _ => value,
+    }
+}
+
+fn coerce_bool(value: Value) -> Value {
+    match &value {
+        Value::String(s) => match s.trim().to_ascii_lowercase().as_str() {
+            "true" | "yes" | "1" => Value::Bool(true),
+            "false" | "no" | "0" => Value::Bool(false),
+            _ => value,
+        },
+        Value::Number(n) => match n.as_u64() {
+            Some(1) => Value::Bool(true),
+            Some(0) => Value::Bool(false),
+            _ => value,
+        },
+        _ => value,
+    }
+}
+
+fn coerce_number(value: Value) -> Value {
+    match &value {
+        Value::String(s) => s
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+            .map(Value::Number)
+            .unwrap_or(value),
+        _ => value,
+    }
+}
+
+fn coerce_integer(value: Value) -> Value {
+    match &value {
+        Value::String(s) => s.trim().parse::<u64>().map(Value::from).unwrap_or(value),
+        Value::Number(n) => match n.as_f64() {
+            Some(f) if n.as_u64().is_none() && f >= 0.0 && f.fract() == 0.0 => {
+                Value::from(f as u64)
+            }
+            _ => value,
+        },
+        _ => value,
+    }
+}
+
+fn coerce_string(value: Value) -> Value {
+    match value {
+        Value::Number(n) => Value::String(n.to_string()),
+        Value::Bool(b) => Value::String(b.to_string()),
+        _ => value,
+    }
+}
+
+fn to_snake_case(key: &str) -> String {
+    let mut out = String::with_capacity(key.len() + 4);
+    for (i, ch) in key.chars().enumerate() {
+        if ch == '-' {
+            out.push('_');
+        } else if ch.is_ascii_uppercase() {
+            if i > 0 && !out.ends_with('_') {
+                out.push('_');
+            }
+            out.push(ch.to_ascii_lowercase());
+        } else {
+            out.push(ch);
+        }
+    }
+    out
+}
+
+pub(crate) fn matches_type(prop: &Value, value: &Value) -> bool {
+    if let Some(variants) = prop.get("oneOf").and_then(|v| v.as_array()) {
+        return variants.iter().any(|variant| matches_type(variant, value));
+    }
+    let type_ok = match prop.get("type").and_then(|v| v.as_str()) {
+        Some("boolean") => value.is_boolean(),
+        Some("number") => value.is_number(),
+        Some("integer") => value.is_u64() || value.is_i64(),
+        Some("string") => value.is_string(),
+        Some("array") => value
+            .as_array()
+            .is_some_and(|items| match prop.get("items") {
+                Some(item_schema) => items.iter().all(|item| matches_type(item_schema, item)),
+                None => true,
+            }),
+        Some("object") => value.is_object(),
+        _ => true,
+    };
+    let enum_ok = match prop.get("enum").and_then(|v| v.as_array()) {
+        Some(allowed) => allowed.contains(value),
+        None => true,
+    };
+    type_ok && enum_ok
+}
+
+pub(crate) fn describe(prop: &Value) -> String {
+    if let Some(allowed) = prop.get("enum").and_then(|v| v.as_array()) {
+        let values: Vec<String> = allowed
+            .iter()
+            .map(|v| {
+                v.as_str()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| v.to_string())
+            })
+            .collect();
+        return format!("one of [{}]", values.join(", "));
+    }
+    if let Some(variants) = prop.get("oneOf").and_then(|v| v.as_array()) {
+        let types: Vec<String> = variants.iter().map(describe).collect();
+        return types.join(" or ");
+    }
+    match prop.get("type").and_then(|v| v.as_str()) {
+        Some("array") => match prop.get("items") {
+            Some(items) => format!("array of {}", describe(items)),
+            None => "array".to_string(),
+        },
+        Some(kind) => kind.to_string(),
+        None => "any".to_string(),
+    }
+}
+
+fn json_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn validate_arguments(
+    schema: &Value,
+    properties: &Map<String, Value>,
+    args: &Map<String, Value>,
+) -> Result<(), String> {
+    if let Some(required) = schema.get("required").and_then(|v| v.as_array()) {
+        for key in required.iter().filter_map(|v| v.as_str()) {
+            if !args.contains_key(key) {
+                return Err(format!("Missing required argument: '{}'", key));
+            }
+        }
+    }
+    let closed = schema.get("additionalProperties") == Some(&Value::Bool(false));
+    for (key, value) in args {
+        let Some(prop) = properties.get(key) else {
+            if closed {
+                let mut allowed: Vec<&str> = properties.keys().map(String::as_str).collect();
+                allowed.sort_unstable();
+                return Err(format!(
+                    "Unknown argument '{}': expected one of [{}]",
+                    key,
+                    allowed.join(", ")
+                ));
+            }
+            continue;
+        };
+        if value.is_null() || matches_type(prop, value) {
+            continue;
+        }
+        let got = match value {
+            Value::String(s) => format!("string \"{}\"", s),
+            other => json_type_name(other).to_string(),
+        };
+        return Err(format!(
+            "Invalid argument '{}': expected {}, got {}",
+            key,
+            describe(prop),
+            got
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "folder_path": {
+                    "oneOf": [
+                        { "type": "string" },
+                        { "type": "array", "items": { "type": "string" } }
+                    ]
+                },
+                "threshold": { "type": "number" },
+                "concurrent": { "type": "integer" },
+                "headers": { "type": "boolean" },
+                "format": { "type": "string", "enum": ["json", "csv"] },
+                "tenant": { "type": "string" }
+            },
+            "required": ["folder_path"]
+        })
+    }
+
+    #[test]
+    fn test_arg_mode_parse() {
+        assert_eq!(ArgMode::parse("lenient"), Ok(ArgMode::Lenient));
+        assert_eq!(ArgMode::parse("strict"), Ok(ArgMode::Strict));
+        assert!(ArgMode::parse("loose").is_err());
+    }
+
+    #[test]
+    fn test_lenient_coerces_values_and_keys() {
+        let args = json!({
+            "folderPath": "/Root",
+            "threshold": "85.5",
+            "concurrent": "4",
+            "headers": "true",
+            "format": "CSV",
+            "tenant": 42
+        });
+        let result = prepare_arguments(&schema(), args, ArgMode::Lenient).unwrap();
+        assert_eq!(
+            result,
+            json!({
+                "folder_path": "/Root",
+                "threshold": 85.5,
+                "concurrent": 4,
+                "headers": true,
+                "format": "csv",
+                "tenant": "42"
+            })
+        );
+    }
+
+    #[test]
+    fn test_lenient_prefers_exact_key() {
+        let args = json!({ "folder_path": "/A", "folderPath": "/B" });
+        let result = prepare_arguments(&schema(), args, ArgMode::Lenient).unwrap();
+        assert_eq!(result["folder_path"], "/A");
+    }
+
+    #[test]
+    fn test_strict_rejects_string_boolean() {
+        let args = json!({ "folder_path": "/Root", "headers": "true" });
+        let err = prepare_arguments(&schema(), args, ArgMode::Strict).unwrap_err();
+        assert!(err.contains("Invalid argument 'headers'"));
+        assert!(err.contains("expected boolean"));
+    }
+
+    #[test]
+    fn test_strict_does_not_rename_keys() {
+        let args = json!({ "folderPath": "/Root" });
+        let err = prepare_arguments(&schema(), args, ArgMode::Strict).unwrap_err();
+        assert_eq!(err, "Missing required argument: 'folder_path'");
+    }
+
+    #[test]
+    fn test_lenient_reports_uncoercible_values() {
+        let args = json!({ "folder_path": "/Root", "threshold": "high" });
+        let err = prepare_arguments(&schema(), args, ArgMode::Lenient).unwrap_err();
+        assert!(err.contains("Invalid argument 'threshold': expected number"));
+    }
+
+    #[test]
+    fn test_enum_violation_lists_allowed_values() {
+        let args = json!({ "folder_path": "/Root", "format": "tree" });
+        let err = prepare_arguments(&schema(), args, ArgMode::Lenient).unwrap_err();
+        assert!(err.contains("one of [json, csv]"));
+    }
+
+    #[test]
+    fn test_unknown_argument_rejected_when_closed() {
+        let mut schema = schema();
+        schema["additionalProperties"] = json!(false);
+        let args = json!({ "folder_path": "/Root", "folder": "/Other" });
+        let err = prepare_arguments(&schema, args, ArgMode::Lenient).unwrap_err();
+        assert!(err.starts_with("Unknown argument 'folder'"));
+        assert!(err.contains("folder_path"));
+    }
+
+    #[test]
+    fn test_refs_resolve_against_defs() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "format": { "$ref": "#/$defs/format_json_csv" }
+            },
+            "$defs": {
+                "format_json_csv": { "type": "string", "enum": ["json", "csv"] }
+            },
+            "additionalProperties": false
+        });
+        let result = prepare_arguments(&schema, json!({ "format": "CSV" }), ArgMode::Lenient);
+        assert_eq!(result.unwrap(), json!({ "format": "csv" }));
+        let err =
+            prepare_arguments(&schema, json!({ "format": "tree" }), ArgMode::Strict).unwrap_err();
+        assert!(err.contains("one of [json, csv]"));
+    }
+
+    #[test]
+    fn test_to_snake_case() {
+        assert_eq!(to_snake_case("folderPath"), "folder_path");
+        assert_eq!(to_snake_case("folder-uuid"), "folder_uuid");
+        assert_eq!(to_snake_case("tenant"), "tenant");
+    }
+}
@@ -0,0 +1,444 @@
+use crate::{
+    AppState,
+    config::{ArtifactsConfig, ServerConfig},
+    content::{raw_output_block, summary_block, tool_result},
+    jobs::JOB_PREFIX,
+    resources::{Artifact, StoredResource},
+    workspace::{WORKSPACE_PREFIX, is_in_use, tenant_workspace_root, workspace_root},
+};
+use serde_json::{Value, json};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+pub const ARTIFACTS_LIST_TOOL: &str = "pcli2_artifacts_list";
+pub const ARTIFACTS_PURGE_TOOL: &str = "pcli2_artifacts_purge";
+/// Names of what tool calls leave behind: working directories kept after a
+/// failure or as jobs, and downloads a crash or a cut-off run did not clean
+/// up.
+const LEFTOVER_PREFIXES: [&str; 7] = [
+    WORKSPACE_PREFIX,
+    JOB_PREFIX,
+    "pcli2-export-",
+    "pcli2-archive-",
+    "pcli2-thumbnail-",
+    "pcli2-image-",
+    "pcli2-upload-",
+];
+
+/// A file or directory a tool call left behind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Leftover {
+    pub path: PathBuf,
+    pub bytes: u64,
+    pub modified: SystemTime,
+}
+
+/// What a collection removed.
+#[derive(Debug, Default)]
+pub struct Collected {
+    pub resources: Vec<StoredResource>,
+    pub files: Vec<PathBuf>,
+    pub bytes: u64,
+}
+
+fn elapsed(since: SystemTime, now: SystemTime) -> Duration {
+    now.duration_since(since).unwrap_or_default()
+}
+
+fn pinned(artifact: &Artifact, rules: &ArtifactsConfig, now: SystemTime) -> bool {
+    rules
+        .pin_referenced_secs
+        .is_some_and(|secs| elapsed(artifact.referenced_at, now) < Duration::from_secs(secs))
+}
+
+/// URIs of the stored results `rules` let go at `now`: those older than
+/// `max_age_secs`, and those left over once the newest that fit in
+/// `max_total_bytes` are kept. Results referenced within
+/// `pin_referenced_secs` stay, and count toward the total.
+fn expired(artifacts: &[Artifact], rules: &ArtifactsConfig, now: SystemTime) -> Vec<String> {
+    let mut kept_bytes = 0;
+    let mut expired = Vec::new();
+    for artifact in artifacts.iter().rev() {
+        let bytes = artifact.resource.text.len();
+        if !pinned(artifact, rules, now) {
+            let too_old = rules
+                .max_age_secs
+                .is_some_and(|secs| elapsed(artifact.stored_at, now) > Duration::from_secs(secs));
+            let too_big = rules
+                .max_total_bytes
+                .is_some_and(|max| kept_bytes + bytes > max);
+            if too_old || too_big {
+                expired.push(artifact.resource.uri.clone());
+                continue;
+            }
+        }
+        kept_bytes += bytes;
+    }
+    expired
+}
+
+/// Where calls leave files: the workspace root and the tenants'
+/// `artifact_dir`s under it, or the temp directory without workspaces.
+pub(crate) fn leftover_roots(config: &ServerConfig) -> Vec<PathBuf> {
+    if !(config.workspace.enabled && config.ssh.host.is_none() && config.docker.container.is_none())
+    {
+        return vec![env::temp_dir()];
+    }
+    let mut roots = vec![workspace_root(config)];
+    for tenant in config.tenants.keys() {
+        let root = tenant_workspace_root(config, Some(tenant));
+        if !roots.contains(&root) {
+            roots.push(root);
+        }
+    }
+    roots
+}
+
+fn size_of(path: &Path) -> u64 {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => fs::read_dir(path)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| size_of(&entry.path()))
+            .sum(),
+        Ok(meta) => meta.len(),
+        Err(_) => 0,
+    }
+}
+
+/// What tool calls left behind, oldest first, leaving out the directories
+/// of calls still running.
+pub fn leftovers(config: &ServerConfig) -> Vec<Leftover> {
+    let mut leftovers: Vec<Leftover> = leftover_roots(config)
+        .iter()
+        .filter_map(|root| fs::read_dir(root).ok())
+        .flatten()
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            LEFTOVER_PREFIXES
+                .iter()
+                .any(|prefix| name.starts_with(prefix))
+        })
+        .filter(|entry| !is_in_use(&entry.path()))
+        .filter_map(|entry| {
+            let modified = entry.metadata().and_then(|meta| meta.modified()).ok()?;
+            let path = entry.path();
+            Some(Leftover {
+                bytes: size_of(&path),
+                path,
+                modified,
+            })
+        })
+        .collect();
+    leftovers.sort_by_key(|leftover| leftover.modified);
+    leftovers
+}
+
+fn remove_leftover(path: &Path) -> std::io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Removes the stored results and leftover files `rules` let go. Files are
+/// only removed by age.
+pub fn collect(state: &AppState, rules: &ArtifactsConfig) -> Collected {
+    let now = SystemTime::now();
+    let mut collected = Collected {
+        resources: state
+            .resources
+            .remove(&expired(&state.resources.artifacts(), rules, now)),
+        ..Collected::default()
+    };
+    collected.bytes = collected
+        .resources
+        .iter()
+        .map(|resource| resource.text.len() as u64)
+        .sum();
+    let Some(max_age) = rules.max_age_secs.map(Duration::from_secs) else {
+        return collected;
+    };
+    for leftover in leftovers(&state.config) {
+        if elapsed(leftover.modified, now) <= max_age {
+            continue;
+        }
+        match remove_leftover(&leftover.path) {
+            Ok(()) => {
+                collected.bytes += leftover.bytes;
+                collected.files.push(leftover.path);
+            }
+            Err(err) => warn!(
+                "⚠ could not remove the leftover {}: {}",
+                leftover.path.display(),
+                err
+            ),
+        }
+    }
+    collected
+}
+
+/// Runs [`collect`] with the `artifacts` limits every
+/// `artifacts.sweep_interval_secs`.
+pub struct Collector {
+    state: AppState,
+}
+
+impl Collector {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    /// Starts the collector in the background. Returns `None` when no
+    /// limit is set.
+    pub fn spawn(self) -> Option<JoinHandle<()>> {
+        let rules = self.state.config.artifacts.clone();
+        if !rules.collects() {
+            return None;
+        }
+        let interval = Duration::from_secs(rules.sweep_interval_secs);
+        Some(tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                let collected = collect(&self.state, &rules);
+                if !collected.resources.is_empty() || !collected.files.is_empty() {
+                    info!(
+                        "🧹 removed {} stored results and {} leftover files ({} bytes)",
+                        collected.resources.len(),
+                        collected.files.len(),
+                        collected.bytes
+                    );
+                }
+            }
+        }))
+    }
+}
+
+fn render(value: &Value) -> Result<String, String> {
+    serde_json::to_string_pretty(value)
+        .map_err(|err| format!("Failed to render artifacts: {}", err))
+}
+
+/// The `pcli2_artifacts_list` result: stored results, leftover files, and
+/// the limits the collector holds them to.
+pub fn list_artifacts(state: &AppState) -> Result<Value, String> {
+    let rules = &state.config.artifacts;
+    let now = SystemTime::now();
+    let artifacts = state.resources.artifacts();
+    let leftovers = leftovers(&state.config);
+    let resources: Vec<Value> = artifacts
+        .iter()
+        .map(|artifact| {
+            json!({
+                "uri": artifact.resource.uri,
+                "name": artifact.resource.name,
+                "mimeType": artifact.resource.mime_type,
+                "size": artifact.resource.size(),
+                "tenant": artifact.resource.tenant,
+                "age_secs": elapsed(artifact.stored_at, now).as_secs(),
+                "referenced_secs_ago": elapsed(artifact.referenced_at, now).as_secs(),
+                "pinned": pinned(artifact, rules, now)
+            })
+        })
+        .collect();
+    let files: Vec<Value> = leftovers
+        .iter()
+        .map(|leftover| {
+            json!({
+                "path": leftover.path.display().to_string(),
+                "bytes": leftover.bytes,
+                "age_secs": elapsed(leftover.modified, now).as_secs()
+            })
+        })
+        .collect();
+    let stored_bytes: usize = artifacts.iter().map(|a| a.resource.text.len()).sum();
+    let file_bytes: u64 = leftovers.iter().map(|leftover| leftover.bytes).sum();
+    let headline = format!(
+        "{} stored results ({} bytes) and {} leftover files ({} bytes).",
+        resources.len(),
+        stored_bytes,
+        files.len(),
+        file_bytes
+    );
+    let text = render(&json!({
+        "retention": {
+            "max_age_secs": rules.max_age_secs,
+            "max_total_bytes": rules.max_total_bytes,
+            "pin_referenced_secs": rules.pin_referenced_secs
+        },
+        "resources": resources,
+        "files": files
+    }))?;
+    Ok(tool_result(vec![
+        summary_block(headline),
+        raw_output_block(text),
+    ]))
+}
+
+/// The `pcli2_artifacts_purge` result. Removes the results named by `uris`,
+/// pinned or not; or, with `older_than_secs`, what is older; or else what
+/// the `artifacts` limits let go.
+pub fn purge_artifacts(state: &AppState, args: &Value) -> Result<Value, String> {
+    let collected = if let Some(uris) = args.get("uris") {
+        let uris: Vec<String> = uris
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .map(str::to_string)
+            .collect();
+        if uris.is_empty() {
+            return Err("Invalid 'uris': name at least one stored result".to_string());
+        }
+        let resources = state.resources.remove(&uris);
+        Collected {
+            bytes: resources.iter().map(|r| r.text.len() as u64).sum(),
+            resources,
+            files: Vec::new(),
+        }
+    } else if let Some(age) = args.get("older_than_secs") {
+        let age = age
+            .as_u64()
+            .ok_or_else(|| "Invalid 'older_than_secs': must be a whole number".to_string())?;
+        let rules = ArtifactsConfig {
+            max_age_secs: Some(age),
+            max_total_bytes: None,
+            ..state.config.artifacts.clone()
+        };
+        collect(state, &rules)
+    } else {
+        collect(state, &state.config.artifacts)
+    };
+    let headline = format!(
+        "Removed {} stored results and {} leftover files ({} bytes).",
+        collected.resources.len(),
+        collected.files.len(),
+        collected.bytes
+    );
+    let text = render(&json!({
+        "resources": collected.resources.iter().map(|r| &r.uri).collect::<Vec<_>>(),
+        "files": collected
+            .files
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>(),
+        "bytes": collected.bytes
+    }))?;
+    Ok(tool_result(vec![
+        summary_block(headline),
+        raw_output_block(text),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WorkspaceConfig;
+    use std::{fs::File, sync::Arc};
+
+    fn artifact(uri: &str, bytes: usize, stored_secs: u64, referenced_secs: u64) -> Artifact {
+        let now = SystemTime::now();
+        Artifact {
+            resource: StoredResource {
+                uri: uri.to_string(),
+                name: uri.to_string(),
+                mime_type: "text/plain".to_string(),
+                text: Arc::new("x".repeat(bytes)),
+                tenant: None,
+                blob: false,
+            },
+            stored_at: now - Duration::from_secs(stored_secs),
+            referenced_at: now - Duration::from_secs(referenced_secs),
+        }
+    }
+
+    #[test]
+    fn test_old_and_excess_results_expire_unless_recently_referenced() {
+        // Oldest first, as the store lists them.
+        let artifacts = [
+            artifact("old-read", 10, 7200, 60),
+            artifact("old", 10, 7200, 7200),
+            artifact("big", 100, 60, 60),
+            artifact("new", 40, 10, 10),
+        ];
+        let now = SystemTime::now();
+        let rules = ArtifactsConfig {
+            max_age_secs: Some(3600),
+            ..ArtifactsConfig::default()
+        };
+        assert_eq!(expired(&artifacts, &rules, now), ["old", "old-read"]);
+
+        let rules = ArtifactsConfig {
+            pin_referenced_secs: Some(300),
+            ..rules
+        };
+        assert_eq!(expired(&artifacts, &rules, now), ["old"]);
+
+        let rules = ArtifactsConfig {
+            max_age_secs: None,
+            max_total_bytes: Some(60),
+            ..rules
+        };
+        assert_eq!(expired(&artifacts, &rules, now), ["old"]);
+        let rules = ArtifactsConfig {
+            pin_referenced_secs: None,
+            ..rules
+        };
+        assert_eq!(expired(&artifacts, &rules, now), ["big"]);
+        assert!(expired(&artifacts, &ArtifactsConfig::default(), now).is_empty());
+    }
+
+    #[test]
+    fn test_old_leftovers_are_removed_but_not_running_calls() {
+        let root = env::temp_dir().join(format!("pcli2-mcp-artifacts-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let config = ServerConfig {
+            workspace: WorkspaceConfig {
+                dir: Some(root.clone()),
+                ..WorkspaceConfig::default()
+            },
+            artifacts: ArtifactsConfig {
+                max_age_secs: Some(3600),
+                ..ArtifactsConfig::default()
+            },
+            ..ServerConfig::default()
+        };
+        let state = AppState {
+            config: Arc::new(config),
+            ..AppState::new("test", "0.0.0")
+        };
+        let old = root.join("pcli2-export-1-2-3.stl");
+        fs::write(&old, b"solid").unwrap();
+        File::options()
+            .write(true)
+            .open(&old)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(7200))
+            .unwrap();
+        fs::write(root.join("pcli2-export-4-5-6.stl"), b"solid").unwrap();
+        fs::write(root.join("notes.txt"), b"keep").unwrap();
+        let running = crate::workspace::Workspace::create(&state.config, "pcli2_asset_get", None)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(leftovers(&state.config).len(), 2);
+        let collected = collect(&state, &state.config.artifacts);
+        assert_eq!(collected.files.len(), 1);
+        assert_eq!(collected.files[0], old);
+        assert_eq!(collected.bytes, 5);
+        assert!(!old.exists());
+        assert!(running.path().is_dir());
+        assert!(root.join("notes.txt").exists());
+        drop(running);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
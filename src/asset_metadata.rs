@@ -0,0 +1,119 @@
+use crate::{
+    AppState,
+    instructions::last_text,
+    paging::json_rows,
+    pcli::call_for_resource,
+    policy::tenant_permitted,
+    tree::{PHYSNA_URI_SCHEME, decode_segment, encode_segment, split_tenant_uri},
+};
+use serde_json::{Value, json};
+
+/// Any asset's metadata as an MCP resource template.
+pub const ASSET_METADATA_URI_TEMPLATE: &str = "physna://{tenant}/asset/{uuid}/metadata";
+const ASSET_GET_TOOL: &str = "pcli2_asset_get";
+
+pub fn asset_metadata_uri(tenant: &str, uuid: &str) -> String {
+    format!(
+        "{}{}/asset/{}/metadata",
+        PHYSNA_URI_SCHEME,
+        encode_segment(tenant),
+        encode_segment(uuid)
+    )
+}
+
+fn is_uuid(text: &str) -> bool {
+    !text.is_empty()
+        && !text.starts_with('-')
+        && text.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
+}
+
+/// The tenant and asset UUID of an asset metadata URI.
+pub fn parse_asset_metadata_uri(uri: &str) -> Option<(String, String)> {
+    let (tenant, rest) = split_tenant_uri(uri)?;
+    let uuid = rest.strip_prefix("asset/")?.strip_suffix("/metadata")?;
+    let uuid = decode_segment(uuid).filter(|uuid| is_uuid(uuid))?;
+    Some((tenant, uuid))
+}
+
+/// The asset in `pcli2 asset get` output: the object itself, or the first
+/// row of a list.
+pub(crate) fn asset_of(text: &str) -> Result<Value, String> {
+    let mut output: Value = serde_json::from_str(text)
+        .map_err(|err| format!("pcli2 asset get returned invalid JSON: {}", err))?;
+    if output.is_object() && output.get("metadata").is_some() {
+        return Ok(output);
+    }
+    let object = output.is_object();
+    match json_rows(&mut output).and_then(|rows| rows.first()) {
+        Some(asset) => Ok(asset.clone()),
+        None if object => Ok(output),
+        None => Err("pcli2 asset get returned no asset".to_string()),
+    }
+}
+
+/// Reads the metadata of asset `uuid` of `tenant` through a
+/// `pcli2_asset_get` call with `metadata`, so reads and tool calls share
+/// its cache and limits. `None` when a client bound or held to other
+/// tenants, or one that may not call the tool, asks.
+pub async fn read_asset_metadata(
+    state: &AppState,
+    tenant: &str,
+    uuid: &str,
+) -> Result<Option<Value>, String> {
+    if state.tenant.as_deref().is_some_and(|bound| bound != tenant)
+        || !tenant_permitted(state, tenant)
+    {
+        return Ok(None);
+    }
+    let args = json!({ "tenant": tenant, "uuid": uuid, "metadata": true, "format": "json" });
+    let Some(result) = call_for_resource(state, ASSET_GET_TOOL, args).await? else {
+        return Ok(None);
+    };
+    let text = last_text(&result).ok_or("pcli2 asset get returned no output")?;
+    let asset = asset_of(text)?;
+    let body = json!({
+        "tenant": tenant,
+        "uuid": asset.get("uuid").cloned().unwrap_or_else(|| json!(uuid)),
+        "path": asset.get("path").cloned().unwrap_or(Value::Null),
+        "name": asset.get("name").cloned().unwrap_or(Value::Null),
+        "metadata": asset.get("metadata").cloned().unwrap_or_else(|| json!({}))
+    });
+    let text = serde_json::to_string_pretty(&body)
+        .map_err(|err| format!("Failed to render asset metadata: {}", err))?;
+    Ok(Some(json!({
+        "contents": [{
+            "uri": asset_metadata_uri(tenant, uuid),
+            "mimeType": "application/json",
+            "text": text
+        }]
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_asset_metadata_uris_round_trip() {
+        let uri = asset_metadata_uri("acme", "0b5c-77aa");
+        assert_eq!(uri, "physna://acme/asset/0b5c-77aa/metadata");
+        assert_eq!(
+            parse_asset_metadata_uri(&uri),
+            Some(("acme".to_string(), "0b5c-77aa".to_string()))
+        );
+        assert!(parse_asset_metadata_uri("physna://acme/asset/0b5c/meta").is_none());
+        assert!(parse_asset_metadata_uri("physna://acme/asset/--help/metadata").is_none());
+        assert!(parse_asset_metadata_uri("physna://acme/asset/x%2Fy/metadata").is_none());
+        assert!(parse_asset_metadata_uri("physna://acme/tree").is_none());
+    }
+
+    #[test]
+    fn test_asset_is_read_from_object_or_rows() {
+        let asset = asset_of(r#"{"uuid": "1", "metadata": {"Weight": 2}}"#).unwrap();
+        assert_eq!(asset["metadata"]["Weight"], 2);
+        let asset = asset_of(r#"[{"uuid": "1", "path": "/R/a.stl"}]"#).unwrap();
+        assert_eq!(asset["path"], "/R/a.stl");
+        assert!(asset_of("[]").is_err());
+        assert!(asset_of("not json").is_err());
+    }
+}
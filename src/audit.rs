@@ -0,0 +1,174 @@
+use crate::{
+    content::{raw_output_block, summary_block, tool_result},
+    redact::redactor,
+};
+use chrono::Utc;
+use serde_json::{Value, json};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+pub const AUDIT_TOOL: &str = "pcli2_audit_tail";
+pub const AUDIT_URI: &str = "audit://recent";
+/// Finished tool calls kept in the audit log.
+pub const MAX_AUDIT_ENTRIES: usize = 200;
+pub const DEFAULT_AUDIT_TAIL: usize = 20;
+
+/// Recent tool calls with redacted arguments, oldest first.
+#[derive(Clone, Default)]
+pub struct AuditLog {
+    entries: Arc<Mutex<VecDeque<Value>>>,
+}
+
+impl AuditLog {
+    pub fn record(
+        &self,
+        tool: &str,
+        request_id: Option<&str>,
+        tenant: Option<&str>,
+        arguments: &Value,
+        outcome: &Result<Value, String>,
+        duration: Duration,
+    ) {
+        let mut entry = json!({
+            "time": Utc::now().to_rfc3339(),
+            "tool": tool,
+            "request_id": request_id,
+            "tenant": tenant,
+            "arguments": redactor().redact_value(arguments),
+            "status": if outcome.is_ok() { "ok" } else { "error" },
+            "duration_ms": duration.as_millis() as u64
+        });
+        if let Err(message) = outcome {
+            let first_line = message.lines().next().unwrap_or_default();
+            entry["error"] = json!(redactor().redact_text(first_line, &[]));
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.push_back(entry);
+        if entries.len() > MAX_AUDIT_ENTRIES {
+            entries.pop_front();
+        }
+    }
+
+    /// The last `limit` entries a client bound to `tenant` may see, oldest
+    /// first, optionally only those for `tool`.
+    pub fn tail(&self, limit: usize, tenant: Option<&str>, tool: Option<&str>) -> Vec<Value> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let mut tail: Vec<Value> = entries
+            .iter()
+            .rev()
+            .filter(|entry| tenant.is_none_or(|tenant| entry["tenant"] == tenant))
+            .filter(|entry| tool.is_none_or(|tool| entry["tool"] == tool))
+            .take(limit)
+            .cloned()
+            .collect();
+        tail.reverse();
+        tail
+    }
+}
+
+/// `resources/read` result for `audit://recent`.
+pub fn audit_resource(log: &AuditLog, tenant: Option<&str>) -> Value {
+    let entries = log.tail(MAX_AUDIT_ENTRIES, tenant, None);
+    let text = serde_json::to_string_pretty(&entries).unwrap_or_else(|_| "[]".to_string());
+    json!({
+        "contents": [{
+            "uri": AUDIT_URI,
+            "mimeType": "application/json",
+            "text": text
+        }]
+    })
+}
+
+pub fn audit_descriptor() -> Value {
+    json!({
+        "uri": AUDIT_URI,
+        "name": "Recent tool calls",
+        "description": "Audit log of the most recent tool calls, with secrets redacted.",
+        "mimeType": "application/json"
+    })
+}
+
+pub fn audit_tail_result(
+    log: &AuditLog,
+    tenant: Option<&str>,
+    args: &Value,
+) -> Result<Value, String> {
+    let limit = args
+        .get("limit")
+        .and_then(Value::as_u64)
+        .map_or(DEFAULT_AUDIT_TAIL, |limit| limit as usize);
+    if !(1..=MAX_AUDIT_ENTRIES).contains(&limit) {
+        return Err(format!(
+            "Invalid 'limit': must be between 1 and {}",
+            MAX_AUDIT_ENTRIES
+        ));
+    }
+    let tool = args.get("tool").and_then(Value::as_str);
+    let entries = log.tail(limit, tenant, tool);
+    let failed = entries
+        .iter()
+        .filter(|entry| entry["status"] == "error")
+        .count();
+    let headline = match entries.len() {
+        0 => "No tool calls recorded yet.".to_string(),
+        count => format!("Last {} tool calls, {} failed.", count, failed),
+    };
+    let text = serde_json::to_string_pretty(&json!({ "entries": entries }))
+        .map_err(|err| format!("Failed to render audit log: {}", err))?;
+    Ok(tool_result(vec![
+        summary_block(headline),
+        raw_output_block(text),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_keeps_recent_redacted_entries() {
+        let log = AuditLog::default();
+        for index in 0..=MAX_AUDIT_ENTRIES {
+            log.record(
+                "pcli2_asset_get",
+                None,
+                Some("castings"),
+                &json!({ "uuid": index.to_string() }),
+                &Ok(json!({})),
+                Duration::from_millis(5),
+            );
+        }
+        log.record(
+            "pcli2_credentials_register",
+            Some("r-1"),
+            Some("forgings"),
+            &json!({ "client_secret": "hunter22" }),
+            &Err("login failed\ndetails".to_string()),
+            Duration::from_millis(5),
+        );
+
+        let all = log.tail(MAX_AUDIT_ENTRIES + 10, None, None);
+        assert_eq!(all.len(), MAX_AUDIT_ENTRIES);
+        assert_eq!(all[0]["arguments"]["uuid"], "2");
+        let last = &all[MAX_AUDIT_ENTRIES - 1];
+        assert_eq!(last["arguments"]["client_secret"], "***");
+        assert_eq!(last["status"], "error");
+        assert_eq!(last["error"], "login failed");
+        assert_eq!(last["request_id"], "r-1");
+
+        assert_eq!(log.tail(5, Some("forgings"), None).len(), 1);
+        let tail = log.tail(2, None, Some("pcli2_asset_get"));
+        assert_eq!(tail[1]["arguments"]["uuid"], MAX_AUDIT_ENTRIES.to_string());
+    }
+
+    #[test]
+    fn test_tail_result_validates_limit() {
+        let log = AuditLog::default();
+        assert!(audit_tail_result(&log, None, &json!({ "limit": 0 })).is_err());
+        let result = audit_tail_result(&log, None, &json!({})).unwrap();
+        assert_eq!(result["content"][0]["text"], "No tool calls recorded yet.");
+    }
+}
@@ -0,0 +1,141 @@
+use crate::{
+    config::AuthConfig,
+    secrets::{KEYRING_REFERENCE_PREFIX, keyring_secret},
+};
+use std::{env, sync::Arc, time::Instant};
+use tokio::sync::Mutex;
+
+#[derive(Clone)]
+pub struct Reauthenticator {
+    settings: Arc<AuthConfig>,
+    last_success: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Reauthenticator {
+    pub fn new(settings: AuthConfig) -> Self {
+        Self {
+            settings: Arc::new(settings),
+            last_success: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.settings.reauth_args.is_empty()
+    }
+
+    pub fn is_token_error(&self, message: &str) -> bool {
+        let message = message.to_ascii_lowercase();
+        self.settings
+            .expiry_patterns
+            .iter()
+            .any(|pattern| message.contains(&pattern.to_ascii_lowercase()))
+    }
+
+    pub fn display_args(&self) -> String {
+        self.settings.reauth_args.join(" ")
+    }
+
+    /// Runs `refresh` unless another caller already re-authenticated after
+    /// `since`; concurrent callers wait for the one refresh in flight.
+    pub async fn refresh<F, Fut>(&self, since: Instant, refresh: F) -> Result<(), String>
+    where
+        F: FnOnce(Vec<String>) -> Fut,
+        Fut: Future<Output = Result<String, String>>,
+    {
+        let mut last_success = self.last_success.lock().await;
+        if last_success.is_some_and(|at| at >= since) {
+            return Ok(());
+        }
+        let args = expand_args(&self.settings.reauth_args)?;
+        refresh(args).await?;
+        *last_success = Some(Instant::now());
+        Ok(())
+    }
+}
+
+impl Default for Reauthenticator {
+    fn default() -> Self {
+        Self::new(AuthConfig::default())
+    }
+}
+
+pub fn expand_args(args: &[String]) -> Result<Vec<String>, String> {
+    args.iter().map(|arg| expand_env(arg)).collect()
+}
+
+fn expand_env(arg: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(arg.len());
+    let mut rest = arg;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| format!("Unterminated '${{' in re-authentication argument '{}'", arg))?;
+        let name = &after[..end];
+        let value = match name.strip_prefix(KEYRING_REFERENCE_PREFIX) {
+            Some(secret) => keyring_secret(secret)?,
+            None => {
+                env::var(name).map_err(|_| format!("Environment variable '{}' is not set", name))?
+            }
+        };
+        out.push_str(&value);
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_error_patterns() {
+        let reauth = Reauthenticator::default();
+        assert!(reauth.is_token_error("Error: Access token has EXPIRED"));
+        assert!(reauth.is_token_error("HTTP 401 Unauthorized"));
+        assert!(!reauth.is_token_error("asset not found"));
+        assert!(!reauth.is_enabled());
+    }
+
+    #[test]
+    fn test_expand_env() {
+        let path = env::var("PATH").unwrap();
+        assert_eq!(
+            expand_env("--path=${PATH}").unwrap(),
+            format!("--path={}", path)
+        );
+        assert_eq!(expand_env("plain").unwrap(), "plain");
+        assert!(expand_env("${PCLI2_MCP_UNSET_VARIABLE}").is_err());
+        assert!(expand_env("${PATH").is_err());
+        if !cfg!(feature = "keyring") {
+            assert!(expand_env("${keyring:admin-token}").is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refresh_runs_once_for_concurrent_expiry() {
+        let reauth = Reauthenticator::new(AuthConfig {
+            reauth_args: vec!["auth".to_string(), "login".to_string()],
+            ..Default::default()
+        });
+        let since = Instant::now();
+        let mut runs = 0;
+        reauth
+            .refresh(since, |_| {
+                runs += 1;
+                async { Ok(String::new()) }
+            })
+            .await
+            .unwrap();
+        reauth
+            .refresh(since, |_| {
+                runs += 1;
+                async { Ok(String::new()) }
+            })
+            .await
+            .unwrap();
+        assert_eq!(runs, 1);
+    }
+}
@@ -0,0 +1,197 @@
+use crate::config::BannerConfig;
+use std::env;
+
+pub const COLOR_AUTO: &str = "auto";
+pub const COLOR_ALWAYS: &str = "always";
+pub const COLOR_NEVER: &str = "never";
+/// Disables colors when set to a non-empty value (<https://no-color.org>).
+pub const NO_COLOR_ENV: &str = "NO_COLOR";
+
+const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+const TAGLINE: &str = "Model Context Protocol Server for PCLI2";
+const ASCII_ART: [&str; 6] = [
+    "██████╗  ██████╗██╗     ██╗██████╗     ███╗   ███╗ ██████╗██████╗ ",
+    "██╔══██╗██╔════╝██║     ██║╚════██╗    ████╗ ████║██╔════╝██╔══██╗",
+    "██████╔╝██║     ██║     ██║ █████╔╝    ██╔████╔██║██║     ██████╔╝",
+    "██╔═══╝ ██║     ██║     ██║██╔═══╝     ██║╚██╔╝██║██║     ██╔═══╝ ",
+    "██║     ╚██████╗███████╗██║███████╗    ██║ ╚═╝ ██║╚██████╗██║     ",
+    "╚═╝      ╚═════╝╚══════╝╚═╝╚══════╝    ╚═╝     ╚═╝ ╚═════╝╚═╝     ",
+];
+
+/// `--color`: whether the banner and log output use ANSI colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorMode {
+    /// Colors when writing to a terminal and `NO_COLOR` is not set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            COLOR_AUTO => Ok(ColorMode::Auto),
+            COLOR_ALWAYS => Ok(ColorMode::Always),
+            COLOR_NEVER => Ok(ColorMode::Never),
+            _ => Err(format!("Unsupported color mode '{}'", value)),
+        }
+    }
+
+    /// Whether to color output written to a stream that is (or is not) a terminal.
+    pub fn enabled(self, is_terminal: bool) -> bool {
+        match self {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => {
+                is_terminal && env::var_os(NO_COLOR_ENV).is_none_or(|value| value.is_empty())
+            }
+        }
+    }
+}
+
+/// The startup banner lines: the configured text or the ASCII art, then the
+/// tagline and version, or one line in plain mode.
+pub fn banner_lines(config: &BannerConfig) -> Vec<String> {
+    if !config.enabled {
+        return Vec::new();
+    }
+    if config.plain {
+        let text = config
+            .text
+            .clone()
+            .unwrap_or_else(|| format!("pcli2-mcp — {}", TAGLINE));
+        return vec![format!("{} (version {})", text, APP_VERSION)];
+    }
+    let mut lines: Vec<String> = match &config.text {
+        Some(text) => text.lines().map(str::to_string).collect(),
+        None => ASCII_ART.iter().map(|line| line.to_string()).collect(),
+    };
+    let width = lines
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0);
+    lines.push(center(TAGLINE, width));
+    lines.push(center(&format!("Version {}", APP_VERSION), width));
+    lines
+}
+
+pub fn print_banner(config: &BannerConfig, color: bool) {
+    let lines = banner_lines(config);
+    if lines.is_empty() {
+        return;
+    }
+    for line in lines {
+        if color {
+            println!("{}", gradient_line(&line));
+        } else {
+            println!("{}", line);
+        }
+    }
+    println!();
+}
+
+fn center(text: &str, width: usize) -> String {
+    let len = text.chars().count();
+    if len >= width {
+        return text.to_string();
+    }
+    let padding = (width - len) / 2;
+    format!(
+        "{}{}{}",
+        " ".repeat(padding),
+        text,
+        " ".repeat(width - len - padding)
+    )
+}
+
+fn gradient_line(line: &str) -> String {
+    let start = (36u8, 144u8, 255u8);
+    let end = (255u8, 120u8, 48u8);
+    let chars: Vec<char> = line.chars().collect();
+    let len = chars.len().max(1);
+    let mut out = String::new();
+
+    for (i, ch) in chars.iter().enumerate() {
+        let t = if len == 1 {
+            0.0
+        } else {
+            i as f32 / (len - 1) as f32
+        };
+        let r = lerp(start.0, end.0, t);
+        let g = lerp(start.1, end.1, t);
+        let b = lerp(start.2, end.2, t);
+        out.push_str(&format!("\x1b[38;2;{};{};{}m{}", r, g, b, ch));
+    }
+    out.push_str("\x1b[0m");
+    out
+}
+
+fn lerp(a: u8, b: u8, t: f32) -> u8 {
+    let af = a as f32;
+    let bf = b as f32;
+    (af + (bf - af) * t) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lerp() {
+        // Test edge cases
+        assert_eq!(lerp(0, 100, 0.0), 0);
+        assert_eq!(lerp(0, 100, 1.0), 100);
+        assert_eq!(lerp(0, 100, 0.5), 50);
+
+        // Test with different values
+        assert_eq!(lerp(50, 150, 0.5), 100);
+        assert_eq!(lerp(10, 20, 0.3), 13); // Approximation due to integer conversion
+    }
+
+    #[test]
+    fn test_gradient_line() {
+        let input = "test";
+        let result = gradient_line(input);
+        // Check that the result contains ANSI color codes
+        assert!(result.contains("\x1b[38;2;")); // Start of RGB color code
+        assert!(result.ends_with("\x1b[0m")); // Reset code at the end
+    }
+
+    #[test]
+    fn test_color_mode() {
+        assert_eq!(ColorMode::parse("never"), Ok(ColorMode::Never));
+        assert!(ColorMode::parse("sometimes").is_err());
+        assert!(ColorMode::Always.enabled(false));
+        assert!(!ColorMode::Never.enabled(true));
+        assert!(!ColorMode::Auto.enabled(false));
+    }
+
+    #[test]
+    fn test_banner_lines() {
+        let art = banner_lines(&BannerConfig::default());
+        assert_eq!(art.len(), ASCII_ART.len() + 2);
+        assert!(art[ASCII_ART.len()].trim() == TAGLINE);
+
+        let custom = banner_lines(&BannerConfig {
+            text: Some("ACME CAD gateway".to_string()),
+            ..BannerConfig::default()
+        });
+        assert_eq!(custom[0], "ACME CAD gateway");
+        assert!(custom[2].contains(APP_VERSION));
+
+        let plain = banner_lines(&BannerConfig {
+            plain: true,
+            ..BannerConfig::default()
+        });
+        assert_eq!(plain.len(), 1);
+        assert!(plain[0].starts_with("pcli2-mcp — "));
+
+        let off = banner_lines(&BannerConfig {
+            enabled: false,
+            ..BannerConfig::default()
+        });
+        assert!(off.is_empty());
+    }
+}
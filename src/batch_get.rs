@@ -0,0 +1,452 @@
+use crate::{
+    AppState,
+    asset_metadata::asset_of,
+    content::{raw_output_block, raw_output_result, summary_block, tool_result},
+    instructions::last_text,
+    pcli::{
+        DEFAULT_FAN_OUT_CONCURRENCY, in_current_execution, run_pcli2_asset_get, validate_range_u64,
+    },
+    plan::is_uuid,
+};
+use serde_json::{Value, json};
+use std::{collections::HashMap, sync::Arc};
+use tokio::{sync::Semaphore, task::JoinSet};
+
+pub const BATCH_GET_TOOL: &str = "pcli2_asset_batch_get";
+pub const MAX_BATCH_ROWS: usize = 1000;
+const ASSET_GET_TOOL: &str = "pcli2_asset_get";
+const MAX_INPUT_BYTES: usize = 1024 * 1024;
+/// Header names of the column holding the assets, when `column` is not given.
+const SELECTOR_HEADERS: [&str; 7] = [
+    "uuid",
+    "asset_uuid",
+    "asset uuid",
+    "id",
+    "path",
+    "asset_path",
+    "asset path",
+];
+/// Keys of a JSON object holding the asset, when `column` is not given.
+const SELECTOR_KEYS: [&str; 5] = ["uuid", "asset_uuid", "id", "path", "asset_path"];
+/// What `pcli2 asset get` says when there is no such asset.
+const NOT_FOUND_MARKERS: [&str; 4] = ["not found", "does not exist", "no such", "no asset"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Selector {
+    Uuid(String),
+    Path(String),
+}
+
+impl Selector {
+    fn parse(cell: &str) -> Option<Self> {
+        let cell = cell.trim();
+        if is_uuid(cell) {
+            Some(Self::Uuid(cell.to_string()))
+        } else if cell.len() > 1 && cell.starts_with('/') {
+            Some(Self::Path(cell.to_string()))
+        } else {
+            None
+        }
+    }
+
+    fn key(&self) -> (&'static str, &str) {
+        match self {
+            Self::Uuid(uuid) => ("uuid", uuid),
+            Self::Path(path) => ("path", path),
+        }
+    }
+}
+
+/// One row of the input: its line (CSV) or position (JSON), the cell
+/// naming the asset, and the asset when the cell is a UUID or a path.
+#[derive(Debug, PartialEq)]
+struct Row {
+    row: usize,
+    input: String,
+    selector: Option<Selector>,
+}
+
+impl Row {
+    fn new(row: usize, input: &str) -> Self {
+        Self {
+            row,
+            input: input.trim().to_string(),
+            selector: Selector::parse(input),
+        }
+    }
+}
+
+/// Splits a CSV line into trimmed cells, honouring double quotes.
+fn split_line(line: &str, delimiter: char) -> Vec<String> {
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                cell.push('"');
+                chars.next();
+            }
+            '"' if quoted => quoted = false,
+            '"' if cell.trim().is_empty() => {
+                cell.clear();
+                quoted = true;
+            }
+            c if c == delimiter && !quoted => cells.push(std::mem::take(&mut cell)),
+            c => cell.push(c),
+        }
+    }
+    cells.push(cell);
+    cells
+        .into_iter()
+        .map(|cell| cell.trim().to_string())
+        .collect()
+}
+
+/// Tabs for cells pasted from a spreadsheet, semicolons for CSV written
+/// with a decimal comma, commas otherwise.
+fn delimiter_of(line: &str) -> char {
+    if line.contains('\t') {
+        '\t'
+    } else if line.contains(';') && !line.contains(',') {
+        ';'
+    } else {
+        ','
+    }
+}
+
+fn csv_rows(input: &str, column: Option<&str>) -> Result<Vec<Row>, String> {
+    let mut lines = input
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim_end_matches('\r')))
+        .filter(|(_, line)| !line.trim().is_empty())
+        .peekable();
+    let Some((_, first)) = lines.peek() else {
+        return Ok(Vec::new());
+    };
+    let delimiter = delimiter_of(first);
+    let first = split_line(first, delimiter);
+    let header = !first.iter().any(|cell| Selector::parse(cell).is_some());
+    let index = match (header, column) {
+        (true, Some(column)) => Some(
+            first
+                .iter()
+                .position(|cell| cell.eq_ignore_ascii_case(column))
+                .ok_or_else(|| {
+                    format!(
+                        "Column '{}' not found; the header has {}",
+                        column,
+                        first.join(", ")
+                    )
+                })?,
+        ),
+        (false, Some(column)) => {
+            return Err(format!(
+                "Column '{}' needs a header row, but the first row holds assets",
+                column
+            ));
+        }
+        (true, None) => first.iter().position(|cell| {
+            SELECTOR_HEADERS
+                .iter()
+                .any(|name| cell.eq_ignore_ascii_case(name))
+        }),
+        (false, None) => None,
+    };
+    if header {
+        lines.next();
+    }
+    Ok(lines
+        .map(|(number, line)| {
+            let cells = split_line(line, delimiter);
+            let cell = match index {
+                Some(index) => cells.get(index).map(String::as_str).unwrap_or_default(),
+                None => cells
+                    .iter()
+                    .find(|cell| Selector::parse(cell).is_some())
+                    .map_or(line, String::as_str),
+            };
+            Row::new(number, cell)
+        })
+        .collect())
+}
+
+fn json_rows(input: &str, column: Option<&str>) -> Result<Vec<Row>, String> {
+    let items: Vec<Value> = serde_json::from_str(input).map_err(|err| {
+        format!(
+            "Invalid 'input': expected a JSON array of UUIDs, paths, or objects: {}",
+            err
+        )
+    })?;
+    Ok(items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let cell = match (item, column) {
+                (Value::String(cell), _) => Some(cell.as_str()),
+                (Value::Object(obj), Some(column)) => obj.get(column).and_then(Value::as_str),
+                (Value::Object(obj), None) => SELECTOR_KEYS
+                    .iter()
+                    .find_map(|key| obj.get(*key).and_then(Value::as_str))
+                    .or_else(|| {
+                        obj.values()
+                            .filter_map(Value::as_str)
+                            .find(|cell| Selector::parse(cell).is_some())
+                    }),
+                _ => None,
+            };
+            match cell {
+                Some(cell) => Row::new(index + 1, cell),
+                None => Row {
+                    row: index + 1,
+                    input: item.to_string(),
+                    selector: None,
+                },
+            }
+        })
+        .collect())
+}
+
+/// The rows of `input`: a JSON array when it starts with `[`, else CSV (or
+/// one asset per line).
+fn parse_rows(input: &str, column: Option<&str>) -> Result<Vec<Row>, String> {
+    if input.len() > MAX_INPUT_BYTES {
+        return Err(format!(
+            "Invalid 'input': {} bytes, more than {}",
+            input.len(),
+            MAX_INPUT_BYTES
+        ));
+    }
+    let trimmed = input.trim_start_matches('\u{feff}').trim();
+    let rows = if trimmed.starts_with('[') {
+        json_rows(trimmed, column)?
+    } else {
+        csv_rows(trimmed, column)?
+    };
+    if rows.is_empty() {
+        return Err("Invalid 'input': no rows to look up".to_string());
+    }
+    if rows.len() > MAX_BATCH_ROWS {
+        return Err(format!(
+            "Too many rows: {} provided, at most {} are allowed per call",
+            rows.len(),
+            MAX_BATCH_ROWS
+        ));
+    }
+    Ok(rows)
+}
+
+fn is_not_found(message: &str) -> bool {
+    let message = message.to_ascii_lowercase();
+    NOT_FOUND_MARKERS
+        .iter()
+        .any(|marker| message.contains(marker))
+}
+
+/// Runs `pcli2 asset get` for one asset, answering from and filling the
+/// `pcli2_asset_get` cache.
+async fn look_up(state: &AppState, args: Value) -> Result<Value, String> {
+    let cached = state
+        .cache
+        .get(ASSET_GET_TOOL, None, &args)
+        .await
+        .and_then(|result| last_text(&result).map(str::to_string));
+    let text = match cached {
+        Some(text) => text,
+        None => {
+            let text = run_pcli2_asset_get(args.clone()).await?;
+            state
+                .cache
+                .put(
+                    ASSET_GET_TOOL,
+                    None,
+                    &args,
+                    &raw_output_result(text.clone()),
+                )
+                .await;
+            text
+        }
+    };
+    asset_of(&text)
+}
+
+/// Looks up every UUID or path of a pasted CSV or JSON list with
+/// `pcli2 asset get`, each distinct asset once and `concurrent` at a time,
+/// and reports one row per input row in input order.
+pub async fn batch_get(state: &AppState, args: Value) -> Result<Value, String> {
+    validate_range_u64(&args, "concurrent", 1, 10)?;
+    let input = args
+        .get("input")
+        .and_then(Value::as_str)
+        .ok_or_else(|| "Missing required argument: 'input'".to_string())?;
+    let rows = parse_rows(input, args.get("column").and_then(Value::as_str))?;
+    let concurrency = args
+        .get("concurrent")
+        .and_then(Value::as_u64)
+        .unwrap_or(DEFAULT_FAN_OUT_CONCURRENCY as u64) as usize;
+
+    let mut lookups: HashMap<&Selector, usize> = HashMap::new();
+    let mut distinct = Vec::new();
+    for selector in rows.iter().filter_map(|row| row.selector.as_ref()) {
+        lookups.entry(selector).or_insert_with(|| {
+            distinct.push(selector);
+            distinct.len() - 1
+        });
+    }
+    let semaphore = Arc::new(Semaphore::new(concurrency));
+    let mut tasks = JoinSet::new();
+    for (index, selector) in distinct.iter().enumerate() {
+        let (key, value) = selector.key();
+        let mut single = json!({ key: value, "format": "json" });
+        if let Some(tenant) = args.get("tenant") {
+            single["tenant"] = tenant.clone();
+        }
+        if args.get("metadata").and_then(Value::as_bool) == Some(true) {
+            single["metadata"] = json!(true);
+        }
+        let state = state.clone();
+        let semaphore = semaphore.clone();
+        tasks.spawn(in_current_execution(async move {
+            let _permit = semaphore.acquire_owned().await;
+            (index, look_up(&state, single).await)
+        }));
+    }
+    let mut outcomes: Vec<Option<Result<Value, String>>> = vec![None; distinct.len()];
+    while let Some(joined) = tasks.join_next().await {
+        let (index, outcome) = joined.map_err(|err| format!("Lookup task failed: {}", err))?;
+        outcomes[index] = Some(outcome);
+    }
+
+    let (mut found, mut not_found, mut invalid, mut failed) = (0, 0, 0, 0);
+    let results: Vec<Value> = rows
+        .iter()
+        .map(|row| {
+            let mut entry = json!({ "row": row.row, "input": row.input });
+            let Some(selector) = &row.selector else {
+                invalid += 1;
+                entry["status"] = json!("invalid");
+                entry["error"] = json!("Not an asset UUID or a path starting with '/'");
+                return entry;
+            };
+            let (key, value) = selector.key();
+            entry[key] = json!(value);
+            match &outcomes[lookups[selector]] {
+                Some(Ok(asset)) => {
+                    found += 1;
+                    entry["status"] = json!("found");
+                    entry["asset"] = asset.clone();
+                }
+                Some(Err(message)) if is_not_found(message) => {
+                    not_found += 1;
+                    entry["status"] = json!("not_found");
+                    entry["error"] = json!(message);
+                }
+                outcome => {
+                    failed += 1;
+                    entry["status"] = json!("error");
+                    entry["error"] = match outcome {
+                        Some(Err(message)) => json!(message),
+                        _ => json!("No result produced"),
+                    };
+                }
+            }
+            entry
+        })
+        .collect();
+    let headline = format!(
+        "Looked up {} rows ({} distinct assets): {} found, {} not found, {} invalid, {} failed.",
+        rows.len(),
+        distinct.len(),
+        found,
+        not_found,
+        invalid,
+        failed
+    );
+    let report = serde_json::to_string_pretty(&json!({
+        "total": rows.len(),
+        "found": found,
+        "not_found": not_found,
+        "invalid": invalid,
+        "failed": failed,
+        "results": results
+    }))
+    .map_err(|err| format!("Failed to render results: {}", err))?;
+    Ok(tool_result(vec![
+        summary_block(headline),
+        raw_output_block(report),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UUID: &str = "5f1c0c1e-1111-4a4a-9b9b-000000000001";
+
+    fn selectors(rows: &[Row]) -> Vec<(usize, Option<(&'static str, &str)>)> {
+        rows.iter()
+            .map(|row| (row.row, row.selector.as_ref().map(Selector::key)))
+            .collect()
+    }
+
+    #[test]
+    fn test_csv_rows_find_the_asset_column() {
+        let input = format!(
+            "Part,Asset UUID,Qty\r\nBracket,{},2\n\n\"Gear, spur\",not-a-uuid,1\n",
+            UUID
+        );
+        let rows = parse_rows(&input, None).unwrap();
+        assert_eq!(selectors(&rows), [(2, Some(("uuid", UUID))), (4, None)]);
+        assert_eq!(rows[1].input, "not-a-uuid");
+
+        // Pasted from a spreadsheet, without a header.
+        let rows = parse_rows(&format!("Bracket\t/Root/a.stl\n{}\n", UUID), None).unwrap();
+        assert_eq!(
+            selectors(&rows),
+            [
+                (1, Some(("path", "/Root/a.stl"))),
+                (2, Some(("uuid", UUID)))
+            ]
+        );
+
+        let rows = parse_rows("name;where\nBracket;/Root/a.stl\n", Some("WHERE")).unwrap();
+        assert_eq!(selectors(&rows), [(2, Some(("path", "/Root/a.stl")))]);
+        assert!(parse_rows("name,where\n", Some("path")).is_err());
+        assert!(parse_rows("/Root/a.stl\n", Some("path")).is_err());
+        assert!(parse_rows("  \n", None).is_err());
+        assert_eq!(split_line(r#" "a ""b""", c ,"#, ','), [r#"a "b""#, "c", ""]);
+    }
+
+    #[test]
+    fn test_json_rows_take_strings_or_objects() {
+        let input = json!([
+            UUID,
+            { "name": "a", "path": "/Root/a.stl" },
+            { "part": "/Root/b.stl" },
+            42
+        ])
+        .to_string();
+        let rows = parse_rows(&input, None).unwrap();
+        assert_eq!(
+            selectors(&rows),
+            [
+                (1, Some(("uuid", UUID))),
+                (2, Some(("path", "/Root/a.stl"))),
+                (3, Some(("path", "/Root/b.stl"))),
+                (4, None)
+            ]
+        );
+        let rows = parse_rows(&input, Some("name")).unwrap();
+        assert_eq!(rows[1].input, "a");
+        assert!(rows[1].selector.is_none());
+        assert!(parse_rows("[1,", None).is_err());
+        let many = json!(vec![UUID; MAX_BATCH_ROWS + 1]).to_string();
+        assert!(
+            parse_rows(&many, None)
+                .unwrap_err()
+                .contains("Too many rows")
+        );
+    }
+}
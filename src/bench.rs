@@ -0,0 +1,419 @@
+use crate::{
+    cli::{
+        ARG_ARGUMENTS, ARG_CONCURRENCY, ARG_HEADER, ARG_ITERATIONS, ARG_MOCK, ARG_TOOL, ARG_URL,
+        DEFAULT_BENCH_TOOL,
+    },
+    proxy::{Endpoint, Proxy, parse_headers},
+    runner::{CommandOutput, CommandRequest, CommandRunner, RunFuture},
+    selftest::SELFTEST_PROTOCOL_VERSION,
+    server::{build_state, load_config, router},
+};
+use anyhow::{Result, anyhow};
+use clap::ArgMatches;
+use serde_json::{Value, json};
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::{Duration, Instant},
+};
+use tokio::{net::TcpListener, task::JoinSet};
+
+/// Durations of the `pcli2` runs made since the last `take`.
+#[derive(Clone, Default)]
+pub struct RunTimings {
+    runs: Arc<Mutex<Vec<Duration>>>,
+}
+
+impl RunTimings {
+    fn record(&self, elapsed: Duration) {
+        self.runs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(elapsed);
+    }
+
+    pub fn take(&self) -> Vec<Duration> {
+        std::mem::take(&mut *self.runs.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+/// Wraps a runner and times each `pcli2` run it makes.
+pub struct TimedRunner {
+    inner: Arc<dyn CommandRunner>,
+    timings: RunTimings,
+}
+
+impl TimedRunner {
+    pub fn new(inner: Arc<dyn CommandRunner>, timings: RunTimings) -> Self {
+        Self { inner, timings }
+    }
+}
+
+impl CommandRunner for TimedRunner {
+    fn run<'a>(&'a self, request: CommandRequest<'a>) -> RunFuture<'a> {
+        Box::pin(async move {
+            let started = Instant::now();
+            let output = self.inner.run(request).await;
+            self.timings.record(started.elapsed());
+            output
+        })
+    }
+}
+
+/// Answers every run at once with an empty JSON list (`bench --mock`).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MockRunner;
+
+impl CommandRunner for MockRunner {
+    fn run<'a>(&'a self, _request: CommandRequest<'a>) -> RunFuture<'a> {
+        Box::pin(async { Ok(CommandOutput::new(Some(0), "[]".to_string(), String::new())) })
+    }
+}
+
+/// One measured tool call.
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub total: Duration,
+    /// Time spent in `pcli2` during the call, when it can be told apart.
+    pub pcli2: Option<Duration>,
+    pub error: Option<String>,
+}
+
+/// The calls made to one tool.
+#[derive(Debug, Clone)]
+pub struct ToolBench {
+    pub tool: String,
+    pub samples: Vec<Sample>,
+    /// Every `pcli2` run seen while the tool was measured.
+    pub runs: Vec<Duration>,
+    pub elapsed: Duration,
+}
+
+async fn call_tool(proxy: &Proxy, tool: &str, arguments: &Value, id: String) -> Sample {
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "tools/call",
+        "params": { "name": tool, "arguments": arguments }
+    });
+    let started = Instant::now();
+    let result = proxy.request(&request).await;
+    let total = started.elapsed();
+    let error = match result {
+        Err(message) => Some(message),
+        Ok(result) if result["isError"] == true => Some(
+            result["content"][0]["text"]
+                .as_str()
+                .unwrap_or("tool error")
+                .to_string(),
+        ),
+        Ok(_) => None,
+    };
+    Sample {
+        total,
+        pcli2: None,
+        error,
+    }
+}
+
+/// Calls `tool` `iterations` times, `concurrency` at a time. With
+/// `timings` from the server's runner and one call at a time, each sample
+/// also records the `pcli2` time spent on it.
+pub async fn bench_tool(
+    proxy: &Proxy,
+    tool: &str,
+    arguments: &Value,
+    iterations: u32,
+    concurrency: u32,
+    timings: Option<&RunTimings>,
+) -> ToolBench {
+    let started = Instant::now();
+    if let Some(timings) = timings {
+        timings.take();
+    }
+    let mut samples = Vec::with_capacity(iterations as usize);
+    let mut runs = Vec::new();
+    if concurrency <= 1 {
+        for index in 0..iterations {
+            let mut sample =
+                call_tool(proxy, tool, arguments, format!("bench-{}-{}", tool, index)).await;
+            if let Some(timings) = timings {
+                let call_runs = timings.take();
+                sample.pcli2 = Some(call_runs.iter().sum());
+                runs.extend(call_runs);
+            }
+            samples.push(sample);
+        }
+    } else {
+        let next = Arc::new(AtomicU32::new(0));
+        let mut workers = JoinSet::new();
+        for _ in 0..concurrency.min(iterations) {
+            let (proxy, next) = (proxy.clone(), next.clone());
+            let (tool, arguments) = (tool.to_string(), arguments.clone());
+            workers.spawn(async move {
+                let mut samples = Vec::new();
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    if index >= iterations {
+                        break samples;
+                    }
+                    let id = format!("bench-{}-{}", tool, index);
+                    samples.push(call_tool(&proxy, &tool, &arguments, id).await);
+                }
+            });
+        }
+        while let Some(worker) = workers.join_next().await {
+            samples.extend(worker.unwrap_or_default());
+        }
+        if let Some(timings) = timings {
+            runs = timings.take();
+        }
+    }
+    ToolBench {
+        tool: tool.to_string(),
+        samples,
+        runs,
+        elapsed: started.elapsed(),
+    }
+}
+
+/// The nearest-rank percentile `p` (0–100) of `values`.
+pub fn percentile(values: &[Duration], p: f64) -> Option<Duration> {
+    let mut sorted = values.to_vec();
+    sorted.sort();
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted.get(rank.saturating_sub(1)).copied()
+}
+
+/// `p50/p95/p99` in milliseconds, or `-` without values.
+fn spread(values: &[Duration]) -> String {
+    let millis = |p| percentile(values, p).map(|value| value.as_secs_f64() * 1000.0);
+    match (millis(50.0), millis(95.0), millis(99.0)) {
+        (Some(p50), Some(p95), Some(p99)) => format!("{:.1}/{:.1}/{:.1}", p50, p95, p99),
+        _ => "-".to_string(),
+    }
+}
+
+pub fn report(benches: &[ToolBench], concurrency: u32) -> String {
+    let mut lines = vec![format!(
+        "{:<28} {:>6} {:>6}  {:>23}  {:>23}  {:>23}",
+        "tool",
+        "calls",
+        "errors",
+        "total p50/p95/p99 ms",
+        "pcli2 p50/p95/p99 ms",
+        "overhead p50/p95/p99 ms"
+    )];
+    let (mut calls, mut elapsed) = (0, Duration::ZERO);
+    for bench in benches {
+        let totals: Vec<Duration> = bench.samples.iter().map(|sample| sample.total).collect();
+        let per_call: Option<Vec<Duration>> =
+            bench.samples.iter().map(|sample| sample.pcli2).collect();
+        let (pcli2, overhead) = match per_call.filter(|per_call| !per_call.is_empty()) {
+            Some(per_call) => {
+                let overhead: Vec<Duration> = bench
+                    .samples
+                    .iter()
+                    .zip(&per_call)
+                    .map(|(sample, pcli2)| sample.total.saturating_sub(*pcli2))
+                    .collect();
+                (spread(&per_call), spread(&overhead))
+            }
+            None => (spread(&bench.runs), "-".to_string()),
+        };
+        let errors = bench
+            .samples
+            .iter()
+            .filter(|sample| sample.error.is_some())
+            .count();
+        lines.push(format!(
+            "{:<28} {:>6} {:>6}  {:>23}  {:>23}  {:>23}",
+            bench.tool,
+            bench.samples.len(),
+            errors,
+            spread(&totals),
+            pcli2,
+            overhead
+        ));
+        if let Some(error) = bench
+            .samples
+            .iter()
+            .find_map(|sample| sample.error.as_ref())
+        {
+            lines.push(format!(
+                "  first error: {}",
+                error.lines().next().unwrap_or("")
+            ));
+        }
+        calls += bench.samples.len();
+        elapsed += bench.elapsed;
+    }
+    let rate = calls as f64 / elapsed.as_secs_f64().max(f64::EPSILON);
+    lines.push(format!(
+        "{} calls in {:.2} s ({:.1} calls/s) at concurrency {}",
+        calls,
+        elapsed.as_secs_f64(),
+        rate,
+        concurrency
+    ));
+    lines.join("\n")
+}
+
+pub async fn run_bench(matches: &ArgMatches) -> Result<()> {
+    let raw_arguments = matches
+        .get_one::<String>(ARG_ARGUMENTS)
+        .map_or("{}", String::as_str);
+    let arguments: Value = serde_json::from_str(raw_arguments)
+        .map_err(|err| anyhow!("--arguments is not valid JSON: {}", err))?;
+    if !arguments.is_object() {
+        return Err(anyhow!("--arguments must be a JSON object"));
+    }
+    let iterations = matches.get_one::<u32>(ARG_ITERATIONS).copied().unwrap_or(1);
+    let concurrency = matches
+        .get_one::<u32>(ARG_CONCURRENCY)
+        .copied()
+        .unwrap_or(1);
+    let config = load_config(matches)?;
+    let tools: Vec<String> = match matches.get_many::<String>(ARG_TOOL) {
+        Some(tools) => tools.cloned().collect(),
+        None => vec![config.tools.public_name(DEFAULT_BENCH_TOOL)],
+    };
+
+    let (proxy, server, timings) = match matches.get_one::<String>(ARG_URL) {
+        Some(url) => {
+            let raw: Vec<String> = matches
+                .get_many::<String>(ARG_HEADER)
+                .map(|values| values.cloned().collect())
+                .unwrap_or_default();
+            (
+                Proxy::new(Endpoint::parse(url)?, parse_headers(&raw)?),
+                None,
+                None,
+            )
+        }
+        None => {
+            let timings = RunTimings::default();
+            let mut state = build_state(config)?;
+            let inner: Arc<dyn CommandRunner> = if matches.get_flag(ARG_MOCK) {
+                Arc::new(MockRunner)
+            } else {
+                state.runner.clone()
+            };
+            state.runner = Arc::new(TimedRunner::new(inner, timings.clone()));
+            let listener = TcpListener::bind("127.0.0.1:0").await?;
+            let address = listener.local_addr()?;
+            let app = router(state);
+            let server = tokio::spawn(async move { axum::serve(listener, app).await });
+            let endpoint = Endpoint::parse(&format!("http://{}/mcp", address))?;
+            (
+                Proxy::new(endpoint, Vec::new()),
+                Some(server),
+                Some(timings),
+            )
+        }
+    };
+
+    let initialize = json!({
+        "jsonrpc": "2.0",
+        "id": "bench-initialize",
+        "method": "initialize",
+        "params": {
+            "protocolVersion": SELFTEST_PROTOCOL_VERSION,
+            "capabilities": {},
+            "clientInfo": { "name": "pcli2-mcp-bench", "version": env!("CARGO_PKG_VERSION") }
+        }
+    });
+    let initialized = proxy.request(&initialize).await;
+    let mut benches = Vec::with_capacity(tools.len());
+    if initialized.is_ok() {
+        for tool in &tools {
+            benches.push(
+                bench_tool(
+                    &proxy,
+                    tool,
+                    &arguments,
+                    iterations,
+                    concurrency,
+                    timings.as_ref(),
+                )
+                .await,
+            );
+        }
+    }
+    if let Some(server) = server {
+        server.abort();
+    }
+    initialized.map_err(|message| anyhow!("initialize failed: {}", message))?;
+
+    println!("{}", report(&benches, concurrency));
+    let failed: usize = benches
+        .iter()
+        .flat_map(|bench| &bench.samples)
+        .filter(|sample| sample.error.is_some())
+        .count();
+    if failed > 0 {
+        return Err(anyhow!("bench: {} call(s) failed", failed));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(values: &[u64]) -> Vec<Duration> {
+        values
+            .iter()
+            .map(|value| Duration::from_millis(*value))
+            .collect()
+    }
+
+    #[test]
+    fn test_percentile_is_nearest_rank() {
+        let values = ms(&[5, 1, 4, 2, 3, 10, 9, 8, 7, 6]);
+        assert_eq!(percentile(&values, 50.0), Some(Duration::from_millis(5)));
+        assert_eq!(percentile(&values, 95.0), Some(Duration::from_millis(10)));
+        assert_eq!(percentile(&values, 0.0), Some(Duration::from_millis(1)));
+        assert_eq!(percentile(&[], 50.0), None);
+    }
+
+    #[test]
+    fn test_report_splits_overhead_when_pcli2_time_is_known() {
+        let sample = |total, pcli2: Option<u64>, error: Option<&str>| Sample {
+            total: Duration::from_millis(total),
+            pcli2: pcli2.map(Duration::from_millis),
+            error: error.map(str::to_string),
+        };
+        let benches = [
+            ToolBench {
+                tool: "pcli2_version".to_string(),
+                samples: vec![sample(12, Some(10), None), sample(14, Some(10), None)],
+                runs: ms(&[10, 10]),
+                elapsed: Duration::from_millis(26),
+            },
+            ToolBench {
+                tool: "pcli2_tenant_list".to_string(),
+                samples: vec![sample(30, None, Some("pcli2 tenant list failed"))],
+                runs: Vec::new(),
+                elapsed: Duration::from_millis(30),
+            },
+        ];
+        let report = report(&benches, 1);
+        let lines: Vec<&str> = report.lines().collect();
+        assert!(lines[1].starts_with("pcli2_version"));
+        assert!(lines[1].contains("12.0/14.0/14.0"));
+        assert!(lines[1].contains("10.0/10.0/10.0"));
+        assert!(lines[1].contains("2.0/4.0/4.0"));
+        assert!(
+            lines[2]
+                .split_whitespace()
+                .rev()
+                .take(2)
+                .all(|field| field == "-")
+        );
+        assert_eq!(lines[3], "  first error: pcli2 tenant list failed");
+        assert!(lines[4].starts_with("3 calls in 0.06 s"));
+    }
+}
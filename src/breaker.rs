@@ -0,0 +1,159 @@
+use crate::config::CircuitBreakerConfig;
+use chrono::{DateTime, Utc};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Admission {
+    Allow,
+    Probe,
+    Reject(String),
+}
+
+#[derive(Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    opened_at: Option<DateTime<Utc>>,
+    next_probe: Option<Instant>,
+    last_error: String,
+}
+
+#[derive(Clone)]
+pub struct CircuitBreaker {
+    settings: CircuitBreakerConfig,
+    state: Arc<Mutex<BreakerState>>,
+}
+
+impl CircuitBreaker {
+    pub fn new(settings: CircuitBreakerConfig) -> Self {
+        Self {
+            settings,
+            state: Arc::new(Mutex::new(BreakerState::default())),
+        }
+    }
+
+    fn probe_interval(&self) -> Duration {
+        Duration::from_secs(self.settings.probe_interval_secs)
+    }
+
+    pub fn probe_args(&self) -> Vec<String> {
+        self.settings.probe_args.clone()
+    }
+
+    pub fn is_open(&self) -> bool {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.opened_at.is_some()
+    }
+
+    pub fn admit(&self) -> Admission {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(opened_at) = state.opened_at else {
+            return Admission::Allow;
+        };
+        let now = Instant::now();
+        match state.next_probe {
+            Some(next_probe) if now >= next_probe => {
+                state.next_probe = Some(now + self.probe_interval());
+                Admission::Probe
+            }
+            next_probe => {
+                let wait = next_probe
+                    .map(|next_probe| next_probe.saturating_duration_since(now).as_secs())
+                    .unwrap_or(0);
+                Admission::Reject(format!(
+                    "pcli2 backend unavailable since {} ({} consecutive failures), last error: {}. Next health probe in {}s.",
+                    opened_at.format("%Y-%m-%d %H:%M:%S UTC"),
+                    state.consecutive_failures,
+                    state.last_error,
+                    wait
+                ))
+            }
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        *state = BreakerState::default();
+    }
+
+    pub fn record_failure(&self, message: &str) {
+        if self.settings.failure_threshold == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+        state.last_error = message.lines().next().unwrap_or_default().to_string();
+        if state.consecutive_failures >= self.settings.failure_threshold {
+            state.opened_at.get_or_insert_with(Utc::now);
+            state.next_probe = Some(Instant::now() + self.probe_interval());
+        }
+    }
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(CircuitBreakerConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker(failure_threshold: u32, probe_interval_secs: u64) -> CircuitBreaker {
+        CircuitBreaker::new(CircuitBreakerConfig {
+            failure_threshold,
+            probe_interval_secs,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_trips_after_threshold() {
+        let breaker = breaker(3, 60);
+        breaker.record_failure("boom");
+        breaker.record_failure("boom");
+        assert_eq!(breaker.admit(), Admission::Allow);
+        breaker.record_failure("token expired\nmore detail");
+        match breaker.admit() {
+            Admission::Reject(message) => {
+                assert!(message.contains("backend unavailable since"));
+                assert!(message.contains("last error: token expired."));
+            }
+            other => panic!("expected rejection, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_success_resets_count() {
+        let breaker = breaker(2, 60);
+        breaker.record_failure("boom");
+        breaker.record_success();
+        breaker.record_failure("boom");
+        assert_eq!(breaker.admit(), Admission::Allow);
+    }
+
+    #[test]
+    fn test_single_probe_after_interval() {
+        let breaker = breaker(1, 60);
+        breaker.record_failure("boom");
+        assert!(matches!(breaker.admit(), Admission::Reject(_)));
+        breaker.state.lock().unwrap().next_probe = Some(Instant::now());
+        assert_eq!(breaker.admit(), Admission::Probe);
+        assert!(matches!(breaker.admit(), Admission::Reject(_)));
+        breaker.record_success();
+        assert_eq!(breaker.admit(), Admission::Allow);
+        assert!(!breaker.is_open());
+    }
+
+    #[test]
+    fn test_zero_threshold_disables_breaker() {
+        let breaker = breaker(0, 60);
+        for _ in 0..10 {
+            breaker.record_failure("boom");
+        }
+        assert_eq!(breaker.admit(), Admission::Allow);
+    }
+}
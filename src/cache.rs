@@ -0,0 +1,996 @@
+#[cfg(feature = "redis")]
+use crate::shared::SharedStore;
+use crate::{
+    config::CacheConfig,
+    content::summary_block,
+    filter::FILTER_ARG,
+    gallery::GALLERY_TOOL,
+    image::IMAGE_MATCH_TOOL,
+    install::managed_home,
+    paging::{LIMIT_ARG, OFFSET_ARG},
+    projection::{FIELDS_ARG, SORT_BY_ARG},
+    reformat::RESPONSE_FORMAT_ARG,
+    report::RENDER_ARG,
+    search::MULTI_TENANT_SEARCH_TOOL,
+    snapshot::DIFF_TOOL,
+    suggest::FOLDER_SUGGEST_TOOL,
+    upload::UPLOAD_TOOL,
+};
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::Path,
+    sync::{Arc, Mutex, mpsc},
+    time::Duration,
+};
+use tracing::{info, warn};
+
+/// Tools whose results are cached: they read tenant state that changes
+/// rarely and is slow to fetch.
+pub const CACHED_TOOLS: [&str; 4] = [
+    "pcli2_folder_list",
+    "pcli2_tenant_list",
+    "pcli2_folder_resolve",
+    "pcli2_asset_get",
+];
+/// Tools that make cached results wrong. `pcli2_tenant_use` switches the
+/// tenant that calls without a `tenant` argument read.
+pub const INVALIDATING_TOOLS: [&str; 1] = ["pcli2_tenant_use"];
+/// Tools that change assets, and the cached tools whose results they make
+/// stale in the tenant and folders they touch.
+pub const MUTATING_TOOLS: [(&str, &[&str]); 4] = [
+    ("pcli2_asset_metadata_create", &["pcli2_asset_get"]),
+    ("pcli2_asset_metadata_delete", &["pcli2_asset_get"]),
+    ("pcli2_asset_reprocess", &["pcli2_asset_get"]),
+    (UPLOAD_TOOL, &["pcli2_asset_list"]),
+];
+/// Tools that only read tenant or `pcli2` state, so clients may reuse
+/// their results for `_meta.cacheable_for_seconds`.
+pub const READ_ONLY_TOOLS: [&str; 28] = [
+    "pcli2_folder_list",
+    "pcli2_asset_list",
+    "pcli2_tenant_list",
+    "pcli2_version",
+    "pcli2_config_get",
+    "pcli2_config_get_path",
+    "pcli2_config_environment_list",
+    "pcli2_config_environment_get",
+    "pcli2_tenant_get",
+    "pcli2_tenant_state",
+    "pcli2_folder_get",
+    "pcli2_folder_resolve",
+    "pcli2_folder_dependencies",
+    "pcli2_folder_geometric_match",
+    "pcli2_folder_part_match",
+    "pcli2_folder_visual_match",
+    "pcli2_asset_get",
+    "pcli2_asset_dependencies",
+    "pcli2_asset_thumbnail",
+    GALLERY_TOOL,
+    "pcli2_geometric_match",
+    "pcli2_asset_part_match",
+    "pcli2_asset_visual_match",
+    "pcli2_asset_text_match",
+    IMAGE_MATCH_TOOL,
+    FOLDER_SUGGEST_TOOL,
+    MULTI_TENANT_SEARCH_TOOL,
+    DIFF_TOOL,
+];
+/// Skips the cache and runs `pcli2`; the fresh result replaces the cached one.
+pub const RELOAD_ARG: &str = "reload";
+pub const MAX_CACHED_RESULTS: usize = 1_000;
+const CACHE_FILE: &str = "results.sqlite3";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    /// Unix time in milliseconds.
+    stored_at: i64,
+    result: Value,
+    /// What the result was read from, so changes there drop it.
+    #[serde(default)]
+    tool: String,
+    #[serde(default)]
+    tenant: Option<String>,
+    #[serde(default)]
+    folder: Option<String>,
+    /// Read from disk at startup rather than stored by this process.
+    #[serde(skip)]
+    restored: bool,
+    /// Found in the shared store, cached by another replica.
+    #[serde(skip)]
+    shared: bool,
+    /// The shared cache generations of its scopes when it was stored or
+    /// found there; it is stale once any of them moved on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    generations: Option<Vec<i64>>,
+}
+
+/// A change to the cache on disk, one entry at a time.
+enum Write {
+    Put(String, Box<Entry>),
+    Delete(Vec<String>),
+    Clear,
+    /// Answered once every earlier write is on disk.
+    Flush(mpsc::SyncSender<()>),
+}
+
+/// Results of the `CACHED_TOOLS`, served until `cache.max_age_secs` old
+/// and optionally kept on disk across restarts (`cache.persist`). Calls are
+/// served from memory; changes are written to an SQLite database by a
+/// thread of their own, so neither the lock nor the caller waits for disk.
+/// With a shared store, results cached by one replica are served by all,
+/// and results any replica drops are dropped by all.
+#[derive(Clone, Default)]
+pub struct ResultCache {
+    max_age: Option<Duration>,
+    disk: Option<mpsc::Sender<Write>>,
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+    #[cfg(feature = "redis")]
+    shared: Option<SharedStore>,
+}
+
+impl ResultCache {
+    /// The cache `config` describes. A persisted cache is read back from
+    /// disk, or discarded when `cold` is set.
+    pub fn open(config: &CacheConfig, cold: bool) -> Result<Self> {
+        let Some(max_age_secs) = config.max_age_secs else {
+            return Ok(Self::default());
+        };
+        let max_age = Duration::from_secs(max_age_secs);
+        let mut cache = Self {
+            max_age: Some(max_age),
+            ..Self::default()
+        };
+        if !config.persist {
+            return Ok(cache);
+        }
+        let dir = match &config.dir {
+            Some(dir) => dir.clone(),
+            None => managed_home()
+                .map(|home| home.join("cache"))
+                .ok_or_else(|| anyhow!("Cannot find a home directory; set 'cache.dir'"))?,
+        };
+        fs::create_dir_all(&dir)
+            .map_err(|err| anyhow!("Failed to create {}: {}", dir.display(), err))?;
+        let file = dir.join(CACHE_FILE);
+        if cold {
+            // With its rollback journal, which would otherwise be replayed.
+            for path in [file.clone(), dir.join(format!("{}-journal", CACHE_FILE))] {
+                match fs::remove_file(&path) {
+                    Ok(()) => info!("discarded the result cache in {}", path.display()),
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                    Err(err) => {
+                        return Err(anyhow!("Failed to remove {}: {}", path.display(), err));
+                    }
+                }
+            }
+        }
+        let connection = connect(&file)?;
+        let entries = load(&connection, &file, max_age);
+        if !entries.is_empty() {
+            info!(
+                "restored {} cached results from {}",
+                entries.len(),
+                file.display()
+            );
+        }
+        cache.entries = Arc::new(Mutex::new(entries));
+        let (sender, writes) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("pcli2-mcp-cache".to_string())
+            .spawn(move || write_entries(connection, &file, writes))
+            .map_err(|err| anyhow!("Failed to start the result cache writer: {}", err))?;
+        cache.disk = Some(sender);
+        Ok(cache)
+    }
+
+    /// Shares the results through `shared`. A disabled cache shares nothing.
+    #[cfg(feature = "redis")]
+    pub fn with_shared(self, shared: SharedStore) -> Self {
+        Self {
+            shared: self.max_age.map(|_| shared),
+            ..self
+        }
+    }
+
+    /// Results are kept apart per tenant; an empty tenant is the one
+    /// `pcli2` is switched to.
+    fn key(tool: &str, cli_version: Option<&str>, args: &Value) -> Option<String> {
+        if !CACHED_TOOLS.contains(&tool) {
+            return None;
+        }
+        let mut args = args.clone();
+        if let Some(args) = args.as_object_mut() {
+            // Every page, order, filter, column set and format of a listing,
+            // and every rendering of a report, comes from the same result.
+            for key in [
+                RELOAD_ARG,
+                LIMIT_ARG,
+                OFFSET_ARG,
+                SORT_BY_ARG,
+                FIELDS_ARG,
+                FILTER_ARG,
+                RESPONSE_FORMAT_ARG,
+                RENDER_ARG,
+            ] {
+                args.remove(key);
+            }
+        }
+        Some(format!(
+            "{} {} {} {}",
+            tool,
+            cli_version.unwrap_or(""),
+            tenant_of(&args).unwrap_or(""),
+            args
+        ))
+    }
+
+    /// The cached result of calling `tool` with `args`, with a note and
+    /// `_meta.cache` saying how old it is. `None` when nothing fresh is
+    /// cached or the call passes `reload: true`.
+    pub async fn get(&self, tool: &str, cli_version: Option<&str>, args: &Value) -> Option<Value> {
+        let max_age = self.max_age?;
+        if args
+            .get(RELOAD_ARG)
+            .and_then(Value::as_bool)
+            .unwrap_or(false)
+        {
+            return None;
+        }
+        let key = Self::key(tool, cli_version, args)?;
+        #[cfg(feature = "redis")]
+        let generations = self.generations(args).await;
+        #[cfg(not(feature = "redis"))]
+        let generations: Option<Vec<i64>> = None;
+        {
+            let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(entry) = entries.get(&key) {
+                let age = age_of(entry, Utc::now().timestamp_millis());
+                // Without a reachable shared store, entries only age.
+                let current = generations.is_none() || entry.generations == generations;
+                if age < max_age && current {
+                    return Some(annotate(entry, age, max_age));
+                }
+                entries.remove(&key);
+            }
+        }
+        #[cfg(feature = "redis")]
+        if let (Some(shared), Some(generations)) = (&self.shared, generations) {
+            match shared.get_cached(&generations, &key).await {
+                Ok(Some(stored)) => {
+                    if let Ok(entry) = serde_json::from_str::<Entry>(&stored) {
+                        let age = age_of(&entry, Utc::now().timestamp_millis());
+                        if age < max_age {
+                            let entry = Entry {
+                                shared: true,
+                                generations: Some(generations),
+                                ..entry
+                            };
+                            let hit = annotate(&entry, age, max_age);
+                            self.insert(key, entry, max_age);
+                            return Some(hit);
+                        }
+                    }
+                }
+                Ok(None) => {}
+                Err(err) => warn!("⚠ failed to read a shared cached result: {}", err),
+            }
+        }
+        None
+    }
+
+    /// Caches a successful `result` of `tool`; other tools are ignored.
+    pub async fn put(&self, tool: &str, cli_version: Option<&str>, args: &Value, result: &Value) {
+        let Some(max_age) = self.max_age else {
+            return;
+        };
+        let Some(key) = Self::key(tool, cli_version, args) else {
+            return;
+        };
+        #[cfg_attr(not(feature = "redis"), allow(unused_mut))]
+        let mut entry = Entry {
+            stored_at: Utc::now().timestamp_millis(),
+            result: result.clone(),
+            tool: tool.to_string(),
+            tenant: tenant_of(args).map(str::to_string),
+            folder: folders_of(args).and_then(|folders| folders.into_iter().next()),
+            restored: false,
+            shared: false,
+            generations: None,
+        };
+        #[cfg(feature = "redis")]
+        if let Some(shared) = &self.shared
+            && let Some(generations) = self.generations(args).await
+        {
+            entry.generations = Some(generations.clone());
+            if let Ok(stored) = serde_json::to_string(&entry)
+                && let Err(err) = shared
+                    .put_cached(&generations, &key, &stored, max_age)
+                    .await
+            {
+                warn!("⚠ failed to share a cached result: {}", err);
+            }
+        }
+        self.insert(key, entry, max_age);
+    }
+
+    fn insert(&self, key: String, entry: Entry, max_age: Duration) {
+        let now = Utc::now().timestamp_millis();
+        let mut dropped = Vec::new();
+        {
+            let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            entries.retain(|key, entry| {
+                let fresh = age_of(entry, now) < max_age;
+                if !fresh {
+                    dropped.push(key.clone());
+                }
+                fresh
+            });
+            if entries.len() >= MAX_CACHED_RESULTS
+                && let Some(oldest) = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.stored_at)
+                    .map(|(key, _)| key.clone())
+            {
+                entries.remove(&oldest);
+                dropped.push(oldest);
+            }
+            entries.insert(key.clone(), entry.clone());
+        }
+        if !dropped.is_empty() {
+            self.write(Write::Delete(dropped));
+        }
+        self.write(Write::Put(key, Box::new(entry)));
+    }
+
+    /// Drops the results a call to the mutating `tool` with `args` made
+    /// stale: those of the tools it affects in the tenant and folders it
+    /// touched. A call without a tenant changes the tenant `pcli2` is
+    /// switched to, which may be any of them, and one naming assets by UUID
+    /// may touch any folder, so those drop more. Other replicas sharing the
+    /// cache drop theirs in the same tenant and folders. Returns how many
+    /// went here.
+    pub async fn invalidate(&self, tool: &str, args: &Value) -> usize {
+        let Some((_, affected)) = MUTATING_TOOLS.iter().find(|(name, _)| *name == tool) else {
+            return 0;
+        };
+        let tenant = tenant_of(args);
+        let folders = folders_of(args);
+        let mut dropped = Vec::new();
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|key, entry| {
+                let same_tenant = match (tenant, entry.tenant.as_deref()) {
+                    (Some(tenant), Some(cached)) => tenant == cached,
+                    _ => true,
+                };
+                let same_folder = match (&folders, &entry.folder) {
+                    (Some(folders), Some(cached)) => folders.contains(cached),
+                    _ => true,
+                };
+                let stale = affected.contains(&entry.tool.as_str()) && same_tenant && same_folder;
+                if stale {
+                    dropped.push(key.clone());
+                }
+                !stale
+            });
+        let count = dropped.len();
+        if count > 0 {
+            info!("dropped {} cached results made stale by {}", count, tool);
+            self.write(Write::Delete(dropped));
+        }
+        #[cfg(feature = "redis")]
+        self.next_generations(&changed_scopes(tenant, folders.as_deref()))
+            .await;
+        count
+    }
+
+    /// Forgets every cached result, on every replica sharing the cache.
+    pub async fn clear(&self) {
+        #[cfg(feature = "redis")]
+        self.next_generations(&[ALL_SCOPE.to_string()]).await;
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.is_empty() {
+            return;
+        }
+        entries.clear();
+        drop(entries);
+        self.write(Write::Clear);
+    }
+
+    /// The shared cache generations of the scopes a result of a call with
+    /// `args` belongs to. `None` without a reachable shared store.
+    #[cfg(feature = "redis")]
+    async fn generations(&self, args: &Value) -> Option<Vec<i64>> {
+        let shared = self.shared.as_ref()?;
+        let folder = folders_of(args).and_then(|folders| folders.into_iter().next());
+        let scopes = entry_scopes(tenant_of(args), folder.as_deref());
+        match shared.cache_generations(&scopes).await {
+            Ok(generations) => Some(generations),
+            Err(err) => {
+                warn!("⚠ failed to read the shared cache generations: {}", err);
+                None
+            }
+        }
+    }
+
+    #[cfg(feature = "redis")]
+    async fn next_generations(&self, scopes: &[String]) {
+        if let Some(shared) = &self.shared
+            && let Err(err) = shared.next_cache_generations(scopes).await
+        {
+            warn!("⚠ failed to drop shared cached results: {}", err);
+        }
+    }
+
+    /// Waits until every change made so far is on disk.
+    pub fn flush(&self) {
+        let (done, flushed) = mpsc::sync_channel(1);
+        self.write(Write::Flush(done));
+        let _ = flushed.recv();
+    }
+
+    fn write(&self, write: Write) {
+        if let Some(disk) = &self.disk {
+            // The writer only stops when the process does.
+            let _ = disk.send(write);
+        }
+    }
+}
+
+/// Tells clients how long they may reuse a result of a read-only `tool`:
+/// `_meta.freshness` gives when `pcli2` produced it, its age, and the
+/// `max_age_secs` it is good for, and `_meta.cacheable_for_seconds` what is
+/// left of that. The server cache's `max_age_secs` applies to the
+/// `CACHED_TOOLS`, `client_max_age_secs` to the others. Results of other
+/// tools are left alone.
+pub fn add_freshness(config: &CacheConfig, tool: &str, result: &mut Value) {
+    if !READ_ONLY_TOOLS.contains(&tool) {
+        return;
+    }
+    let max_age_secs = config
+        .max_age_secs
+        .filter(|_| CACHED_TOOLS.contains(&tool))
+        .or(config.client_max_age_secs)
+        .unwrap_or(0);
+    let hit = &result["_meta"]["cache"];
+    let (fetched_at, age_secs) = match (hit["stored_at"].as_str(), hit["age_secs"].as_u64()) {
+        (Some(stored_at), Some(age_secs)) => (stored_at.to_string(), age_secs),
+        _ => (Utc::now().to_rfc3339(), 0),
+    };
+    result["_meta"]["freshness"] = json!({
+        "fetched_at": fetched_at,
+        "age_secs": age_secs,
+        "max_age_secs": max_age_secs
+    });
+    result["_meta"]["cacheable_for_seconds"] = json!(max_age_secs.saturating_sub(age_secs));
+}
+
+/// The scope every shared cached result belongs to.
+#[cfg(feature = "redis")]
+const ALL_SCOPE: &str = "all";
+
+/// The scopes a shared cached result of `tenant` and `folder` belongs to:
+/// all results, its tenant, and its folder in that tenant (`*` when it may
+/// read any folder).
+#[cfg(feature = "redis")]
+fn entry_scopes(tenant: Option<&str>, folder: Option<&str>) -> Vec<String> {
+    let tenant = tenant.unwrap_or("");
+    vec![
+        ALL_SCOPE.to_string(),
+        format!("tenant:{}", tenant),
+        format!("folder:{}:{}", tenant, folder.unwrap_or("*")),
+    ]
+}
+
+/// The scopes a change in `tenant` and `folders` drops, following
+/// `invalidate`: a change without a tenant drops all, one naming no
+/// folders the whole tenant, and any change drops the results of calls
+/// without a tenant, which may have read it.
+#[cfg(feature = "redis")]
+fn changed_scopes(tenant: Option<&str>, folders: Option<&[String]>) -> Vec<String> {
+    let Some(tenant) = tenant else {
+        return vec![ALL_SCOPE.to_string()];
+    };
+    let mut scopes = vec!["tenant:".to_string()];
+    match folders {
+        None => scopes.push(format!("tenant:{}", tenant)),
+        Some(folders) => {
+            scopes.push(format!("folder:{}:*", tenant));
+            scopes.extend(
+                folders
+                    .iter()
+                    .map(|folder| format!("folder:{}:{}", tenant, folder)),
+            );
+        }
+    }
+    scopes
+}
+
+fn tenant_of(args: &Value) -> Option<&str> {
+    args.get("tenant").and_then(Value::as_str)
+}
+
+/// The folders a call reads or changes: its `folder_path`, or the parent
+/// folders of its asset `path`s. `None` when it names anything by UUID.
+fn folders_of(args: &Value) -> Option<Vec<String>> {
+    if args.get("uuid").is_some() || args.get("folder_uuid").is_some() {
+        return None;
+    }
+    if let Some(folder) = args.get("folder_path").and_then(Value::as_str) {
+        return Some(vec![folder.trim_end_matches('/').to_string()]);
+    }
+    let paths: Vec<&str> = match args.get("path")? {
+        Value::String(path) => vec![path.as_str()],
+        Value::Array(paths) => paths.iter().filter_map(Value::as_str).collect(),
+        _ => return None,
+    };
+    Some(
+        paths
+            .into_iter()
+            .map(|path| {
+                path.rsplit_once('/')
+                    .map(|(folder, _)| folder.to_string())
+                    .unwrap_or_default()
+            })
+            .collect(),
+    )
+}
+
+fn age_of(entry: &Entry, now: i64) -> Duration {
+    Duration::from_millis(u64::try_from(now - entry.stored_at).unwrap_or(0))
+}
+
+/// The cache database in `file`, made if it is missing. A file that is not
+/// an SQLite database is replaced.
+fn connect(file: &Path) -> Result<Connection> {
+    let open = || -> rusqlite::Result<Connection> {
+        let connection = Connection::open(file)?;
+        connection.execute(
+            "CREATE TABLE IF NOT EXISTS results (key TEXT PRIMARY KEY, entry TEXT NOT NULL)",
+            [],
+        )?;
+        Ok(connection)
+    };
+    open().or_else(|err| {
+        warn!(
+            "⚠ replacing the unreadable result cache {}: {}",
+            file.display(),
+            err
+        );
+        fs::remove_file(file)
+            .map_err(|err| anyhow!("Failed to remove {}: {}", file.display(), err))?;
+        open().map_err(|err| anyhow!("Failed to open {}: {}", file.display(), err))
+    })
+}
+
+/// The fresh entries of the cache database; expired ones are deleted and
+/// unreadable ones skipped.
+fn load(connection: &Connection, file: &Path, max_age: Duration) -> HashMap<String, Entry> {
+    let rows = connection
+        .prepare("SELECT key, entry FROM results")
+        .and_then(|mut select| {
+            select
+                .query_map([], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()
+        });
+    let rows = match rows {
+        Ok(rows) => rows,
+        Err(err) => {
+            warn!(
+                "⚠ could not read the result cache {}: {}",
+                file.display(),
+                err
+            );
+            return HashMap::new();
+        }
+    };
+    let now = Utc::now().timestamp_millis();
+    let mut entries = HashMap::new();
+    let mut expired = Vec::new();
+    for (key, raw) in rows {
+        match serde_json::from_str::<Entry>(&raw) {
+            Ok(entry) if age_of(&entry, now) < max_age => {
+                entries.insert(
+                    key,
+                    Entry {
+                        restored: true,
+                        ..entry
+                    },
+                );
+            }
+            _ => expired.push(key),
+        }
+    }
+    if let Err(err) = delete(connection, &expired) {
+        warn!(
+            "⚠ could not drop expired results from {}: {}",
+            file.display(),
+            err
+        );
+    }
+    entries
+}
+
+fn delete(connection: &Connection, keys: &[String]) -> rusqlite::Result<()> {
+    let mut delete = connection.prepare_cached("DELETE FROM results WHERE key = ?1")?;
+    for key in keys {
+        delete.execute([key])?;
+    }
+    Ok(())
+}
+
+/// Applies the cache's changes to the database as they come, those that
+/// queued up together in one transaction.
+fn write_entries(mut connection: Connection, file: &Path, writes: mpsc::Receiver<Write>) {
+    while let Ok(first) = writes.recv() {
+        let mut flushed = Vec::new();
+        let applied = connection.transaction().and_then(|transaction| {
+            for write in std::iter::once(first).chain(writes.try_iter()) {
+                match write {
+                    Write::Put(key, entry) => {
+                        let raw = serde_json::to_string(&entry)
+                            .map_err(|err| rusqlite::Error::ToSqlConversionFailure(err.into()))?;
+                        transaction
+                            .prepare_cached(
+                                "INSERT OR REPLACE INTO results (key, entry) VALUES (?1, ?2)",
+                            )?
+                            .execute([key, raw])?;
+                    }
+                    Write::Delete(keys) => delete(&transaction, &keys)?,
+                    Write::Clear => {
+                        transaction.execute("DELETE FROM results", [])?;
+                    }
+                    Write::Flush(done) => flushed.push(done),
+                }
+            }
+            transaction.commit()
+        });
+        if let Err(err) = applied {
+            warn!(
+                "⚠ could not write the result cache to {}: {}",
+                file.display(),
+                err
+            );
+        }
+        for done in flushed {
+            let _ = done.send(());
+        }
+    }
+}
+
+fn annotate(entry: &Entry, age: Duration, max_age: Duration) -> Value {
+    let mut result = entry.result.clone();
+    let source = match (entry.restored, entry.shared) {
+        (true, _) => "disk",
+        (false, true) => "shared",
+        (false, false) => "memory",
+    };
+    let stored_at = DateTime::<Utc>::from_timestamp_millis(entry.stored_at)
+        .map(|time| time.to_rfc3339())
+        .unwrap_or_default();
+    let note = format!(
+        "Cached result from {}s ago{}; pass '{}: true' to run pcli2 again.",
+        age.as_secs(),
+        if entry.restored {
+            ", kept from before the server restarted"
+        } else {
+            ""
+        },
+        RELOAD_ARG
+    );
+    if let Some(blocks) = result.get_mut("content").and_then(Value::as_array_mut) {
+        blocks.insert(0, summary_block(note));
+    }
+    result["_meta"]["cache"] = json!({
+        "hit": true,
+        "source": source,
+        "stored_at": stored_at,
+        "age_secs": age.as_secs(),
+        "max_age_secs": max_age.as_secs()
+    });
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::{raw_output_result, tool_result};
+    use std::path::PathBuf;
+
+    const TOOL: &str = "pcli2_folder_list";
+
+    fn config(persist: bool, dir: &Path) -> CacheConfig {
+        CacheConfig {
+            max_age_secs: Some(60),
+            persist,
+            dir: Some(dir.to_path_buf()),
+            ..CacheConfig::default()
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("pcli2-mcp-cache-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_cache_serves_fresh_results_of_cached_tools() {
+        let cache = ResultCache::open(&config(false, Path::new("/unused")), false).unwrap();
+        let args = json!({ "tenant": "acme", "format": "json" });
+        let result = raw_output_result("[]");
+        assert!(cache.get(TOOL, None, &args).await.is_none());
+        cache.put(TOOL, None, &args, &result).await;
+        cache.put("pcli2_asset_list", None, &args, &result).await;
+
+        let hit = cache.get(TOOL, None, &args).await.unwrap();
+        assert_eq!(hit["_meta"]["cache"]["hit"], true);
+        assert_eq!(hit["_meta"]["cache"]["source"], "memory");
+        assert_eq!(hit["_meta"]["cache"]["max_age_secs"], 60);
+        assert!(
+            hit["content"][0]["text"]
+                .as_str()
+                .unwrap()
+                .contains("reload")
+        );
+        assert_eq!(hit["content"][1], result["content"][0]);
+        assert!(cache.get(TOOL, Some("2.0"), &args).await.is_none());
+        assert!(cache.get("pcli2_asset_list", None, &args).await.is_none());
+        let mut reload = args.clone();
+        reload["reload"] = json!(true);
+        assert!(cache.get(TOOL, None, &reload).await.is_none());
+        reload["reload"] = json!(false);
+        assert!(cache.get(TOOL, None, &reload).await.is_some());
+
+        cache.clear().await;
+        assert!(cache.get(TOOL, None, &args).await.is_none());
+        assert!(
+            ResultCache::default()
+                .get(TOOL, None, &args)
+                .await
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_read_only_results_carry_freshness_hints() {
+        let config = CacheConfig {
+            max_age_secs: Some(900),
+            client_max_age_secs: Some(60),
+            ..CacheConfig::default()
+        };
+        let mut listed = tool_result(Vec::new());
+        listed["_meta"]["cache"] =
+            json!({ "stored_at": "2026-10-16T08:00:00+00:00", "age_secs": 100 });
+        add_freshness(&config, TOOL, &mut listed);
+        assert_eq!(
+            listed["_meta"]["freshness"]["fetched_at"],
+            "2026-10-16T08:00:00+00:00"
+        );
+        assert_eq!(listed["_meta"]["freshness"]["max_age_secs"], 900);
+        assert_eq!(listed["_meta"]["cacheable_for_seconds"], 800);
+
+        let mut asset = tool_result(Vec::new());
+        add_freshness(&config, "pcli2_asset_dependencies", &mut asset);
+        assert_eq!(asset["_meta"]["freshness"]["age_secs"], 0);
+        assert_eq!(asset["_meta"]["cacheable_for_seconds"], 60);
+        let mut asset = tool_result(Vec::new());
+        add_freshness(
+            &CacheConfig::default(),
+            "pcli2_asset_dependencies",
+            &mut asset,
+        );
+        assert_eq!(asset["_meta"]["cacheable_for_seconds"], 0);
+
+        let mut changed = tool_result(Vec::new());
+        add_freshness(&config, "pcli2_asset_metadata_create", &mut changed);
+        assert!(changed.get("_meta").is_none());
+
+        let tools = crate::pcli::tool_list();
+        for name in READ_ONLY_TOOLS.iter().chain(&CACHED_TOOLS) {
+            assert!(tools.iter().any(|tool| tool["name"] == *name), "{}", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mutations_drop_what_they_make_stale() {
+        let cache = ResultCache::open(&config(false, Path::new("/unused")), false).unwrap();
+        let result = tool_result(Vec::new());
+        let get = |tenant: &str, path: &str| json!({ "tenant": tenant, "path": path });
+        cache
+            .put(
+                "pcli2_asset_get",
+                None,
+                &get("acme", "/Root/A/a.stl"),
+                &result,
+            )
+            .await;
+        cache
+            .put(
+                "pcli2_asset_get",
+                None,
+                &get("acme", "/Root/B/b.stl"),
+                &result,
+            )
+            .await;
+        cache
+            .put(
+                "pcli2_asset_get",
+                None,
+                &get("other", "/Root/A/a.stl"),
+                &result,
+            )
+            .await;
+        cache
+            .put(
+                "pcli2_asset_get",
+                None,
+                &json!({ "tenant": "acme", "uuid": "u-1" }),
+                &result,
+            )
+            .await;
+        cache
+            .put(TOOL, None, &json!({ "tenant": "acme" }), &result)
+            .await;
+        assert!(
+            cache
+                .get("pcli2_asset_get", None, &get("acme", "/Root/A/a.stl"))
+                .await
+                .is_some()
+        );
+        assert!(
+            cache
+                .get("pcli2_asset_get", None, &get("ACME", "/Root/A/a.stl"))
+                .await
+                .is_none()
+        );
+
+        assert_eq!(
+            cache
+                .invalidate("pcli2_asset_get", &get("acme", "/Root/A/a.stl"))
+                .await,
+            0
+        );
+        // The same tenant's entry for the folder, and the one by UUID.
+        assert_eq!(
+            cache
+                .invalidate(
+                    "pcli2_asset_metadata_create",
+                    &json!({ "tenant": "acme", "path": ["/Root/A/a.stl", "/Root/C/c.stl"] })
+                )
+                .await,
+            2
+        );
+        assert!(
+            cache
+                .get("pcli2_asset_get", None, &get("acme", "/Root/A/a.stl"))
+                .await
+                .is_none()
+        );
+        assert!(
+            cache
+                .get("pcli2_asset_get", None, &get("acme", "/Root/B/b.stl"))
+                .await
+                .is_some()
+        );
+        assert!(
+            cache
+                .get("pcli2_asset_get", None, &get("other", "/Root/A/a.stl"))
+                .await
+                .is_some()
+        );
+        // Without a tenant or a path it may have touched anything.
+        assert_eq!(
+            cache
+                .invalidate("pcli2_asset_reprocess", &json!({ "uuid": "u-2" }))
+                .await,
+            2
+        );
+        assert!(
+            cache
+                .get(TOOL, None, &json!({ "tenant": "acme" }))
+                .await
+                .is_some()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expired_results_are_dropped() {
+        let cache = ResultCache::open(&config(false, Path::new("/unused")), false).unwrap();
+        let args = json!({});
+        cache.put(TOOL, None, &args, &tool_result(Vec::new())).await;
+        for entry in cache.entries.lock().unwrap().values_mut() {
+            entry.stored_at -= 61_000;
+        }
+        assert!(cache.get(TOOL, None, &args).await.is_none());
+        assert!(cache.entries.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_persisted_cache_survives_a_restart() {
+        let dir = temp_dir("persist");
+        let args = json!({ "folder_path": "/Root" });
+        let result = raw_output_result("{\"uuid\": \"1\"}");
+        {
+            let cache = ResultCache::open(&config(true, &dir), false).unwrap();
+            cache
+                .put("pcli2_folder_resolve", None, &args, &result)
+                .await;
+            cache.flush();
+        }
+        let restarted = ResultCache::open(&config(true, &dir), false).unwrap();
+        let hit = restarted
+            .get("pcli2_folder_resolve", None, &args)
+            .await
+            .unwrap();
+        assert_eq!(hit["_meta"]["cache"]["source"], "disk");
+        assert!(
+            hit["content"][0]["text"]
+                .as_str()
+                .unwrap()
+                .contains("restarted")
+        );
+
+        // Invalidation reaches the disk too.
+        let updated = json!({ "path": "/Root/a.stl" });
+        restarted
+            .put("pcli2_asset_get", None, &updated, &result)
+            .await;
+        assert_eq!(
+            restarted
+                .invalidate("pcli2_asset_metadata_create", &updated)
+                .await,
+            1
+        );
+        restarted.flush();
+        let again = ResultCache::open(&config(true, &dir), false).unwrap();
+        assert!(
+            again
+                .get("pcli2_folder_resolve", None, &args)
+                .await
+                .is_some()
+        );
+        assert!(again.get("pcli2_asset_get", None, &updated).await.is_none());
+
+        let cold = ResultCache::open(&config(true, &dir), true).unwrap();
+        assert!(
+            cold.get("pcli2_folder_resolve", None, &args)
+                .await
+                .is_none()
+        );
+        fs::remove_dir_all(&dir).unwrap();
+
+        let dir = temp_dir("corrupt");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(CACHE_FILE), "not a database").unwrap();
+        let corrupt = ResultCache::open(&config(true, &dir), false).unwrap();
+        assert!(
+            corrupt
+                .get("pcli2_folder_resolve", None, &args)
+                .await
+                .is_none()
+        );
+        corrupt
+            .put("pcli2_folder_resolve", None, &args, &result)
+            .await;
+        corrupt.flush();
+        let reopened = ResultCache::open(&config(true, &dir), false).unwrap();
+        assert!(
+            reopened
+                .get("pcli2_folder_resolve", None, &args)
+                .await
+                .is_some()
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
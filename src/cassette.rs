@@ -0,0 +1,157 @@
+use crate::redact::redactor;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// One finished `pcli2` run. Arguments and output are stored redacted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recording {
+    pub args: Vec<String>,
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+enum Mode {
+    Record(Mutex<File>),
+    /// Recordings per argv, served in order; the last one repeats.
+    Replay(Mutex<HashMap<Vec<String>, VecDeque<Recording>>>),
+}
+
+/// A JSON Lines file of `pcli2` runs, written in record mode and served
+/// instead of running `pcli2` in replay mode.
+pub struct Cassette {
+    path: PathBuf,
+    mode: Mode,
+}
+
+impl Cassette {
+    /// Starts a new cassette at `path`, replacing any existing file.
+    pub fn record(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create cassette {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            mode: Mode::Record(Mutex::new(file)),
+        })
+    }
+
+    pub fn replay(path: &Path) -> Result<Self> {
+        let raw = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read cassette {}", path.display()))?;
+        let mut recordings: HashMap<Vec<String>, VecDeque<Recording>> = HashMap::new();
+        for (index, line) in raw.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let recording: Recording = serde_json::from_str(line).with_context(|| {
+                format!("Invalid cassette entry at {}:{}", path.display(), index + 1)
+            })?;
+            recordings
+                .entry(recording.args.clone())
+                .or_default()
+                .push_back(recording);
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            mode: Mode::Replay(Mutex::new(recordings)),
+        })
+    }
+
+    pub fn is_replay(&self) -> bool {
+        matches!(self.mode, Mode::Replay(_))
+    }
+
+    /// Returns the next recording for `args`, or an error naming the call
+    /// when the cassette has none.
+    pub fn play(&self, args: &[String]) -> Result<Recording, String> {
+        let Mode::Replay(recordings) = &self.mode else {
+            return Err("Cassette is not in replay mode".to_string());
+        };
+        let key = redactor().redact_args(args);
+        let mut recordings = recordings.lock().unwrap_or_else(|e| e.into_inner());
+        let queue = recordings.get_mut(&key).filter(|queue| !queue.is_empty());
+        match queue {
+            Some(queue) if queue.len() > 1 => queue.pop_front(),
+            Some(queue) => queue.front().cloned(),
+            None => None,
+        }
+        .ok_or_else(|| {
+            format!(
+                "No recorded pcli2 call in {} matches: pcli2 {}",
+                self.path.display(),
+                key.join(" ")
+            )
+        })
+    }
+
+    /// Appends a finished run to the cassette in record mode.
+    pub fn append(
+        &self,
+        args: &[String],
+        exit_code: Option<i32>,
+        stdout: &str,
+        stderr: &str,
+    ) -> Result<(), String> {
+        let Mode::Record(file) = &self.mode else {
+            return Ok(());
+        };
+        let redactor = redactor();
+        let secrets = redactor.secrets(args);
+        let recording = Recording {
+            args: redactor.redact_args(args),
+            exit_code,
+            stdout: redactor.redact_text(stdout, &secrets),
+            stderr: redactor.redact_text(stderr, &secrets),
+        };
+        let line = serde_json::to_string(&recording)
+            .map_err(|err| format!("Failed to encode cassette entry: {}", err))?;
+        let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+        writeln!(file, "{}", line)
+            .and_then(|_| file.flush())
+            .map_err(|err| format!("Failed to write cassette {}: {}", self.path.display(), err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn test_record_then_replay() {
+        let path =
+            std::env::temp_dir().join(format!("pcli2-mcp-cassette-{}.jsonl", std::process::id()));
+        let list = strings(&["asset", "list"]);
+        let login = strings(&["auth", "login", "--client-secret", "s3cret"]);
+        let recorder = Cassette::record(&path).unwrap();
+        recorder.append(&list, Some(0), "a.stl", "").unwrap();
+        recorder.append(&list, Some(0), "a.stl\nb.stl", "").unwrap();
+        recorder
+            .append(&login, Some(1), "", "bad secret s3cret")
+            .unwrap();
+        let raw = fs::read_to_string(&path).unwrap();
+        assert!(!raw.contains("s3cret"), "{}", raw);
+
+        let player = Cassette::replay(&path).unwrap();
+        assert!(player.is_replay());
+        assert_eq!(player.play(&list).unwrap().stdout, "a.stl");
+        assert_eq!(player.play(&list).unwrap().stdout, "a.stl\nb.stl");
+        assert_eq!(player.play(&list).unwrap().stdout, "a.stl\nb.stl");
+        let login = player.play(&login).unwrap();
+        assert_eq!(login.exit_code, Some(1));
+        assert_eq!(login.stderr, "bad secret ***");
+        let err = player.play(&strings(&["tenant", "list"])).unwrap_err();
+        assert!(err.contains("pcli2 tenant list"));
+        let _ = fs::remove_file(&path);
+    }
+}
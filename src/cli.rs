@@ -1,7 +1,23 @@
-use clap::{Arg, Command, value_parser};
+use crate::args::{ARG_MODE_LENIENT, ARG_MODE_STRICT};
+use crate::banner::{COLOR_ALWAYS, COLOR_AUTO, COLOR_NEVER};
+use clap::{Arg, ArgAction, Command, value_parser};
+use std::path::PathBuf;
 
 pub const CMD_SERVE: &str = "serve";
 pub const CMD_CONFIG: &str = "config";
+pub const CMD_PROXY: &str = "proxy";
+pub const CMD_SELFTEST: &str = "selftest";
+pub const CMD_BENCH: &str = "bench";
+pub const CMD_DOCTOR: &str = "doctor";
+pub const CMD_DOCS: &str = "docs";
+pub const CMD_SECRETS: &str = "secrets";
+pub const CMD_SECRETS_SET: &str = "set";
+pub const CMD_SECRETS_DELETE: &str = "delete";
+pub const CMD_SECRETS_CHECK: &str = "check";
+pub const CMD_INSTALL_PCLI2: &str = "install-pcli2";
+pub const CMD_SELF_UPDATE: &str = "self-update";
+pub const CMD_STOP: &str = "stop";
+pub const CMD_STATUS: &str = "status";
 pub const CMD_HELP: &str = "help";
 
 pub const ARG_PORT: &str = "port";
@@ -9,14 +25,64 @@ pub const ARG_CLIENT: &str = "client";
 pub const ARG_COMMAND: &str = "command";
 pub const ARG_HOST: &str = "host";
 pub const ARG_LOG_LEVEL: &str = "log_level";
+pub const ARG_ARGUMENT_MODE: &str = "argument_mode";
+pub const ARG_MAX_INLINE_BYTES: &str = "max_inline_bytes";
+pub const ARG_CONFIG_FILE: &str = "config_file";
+pub const ARG_URL: &str = "url";
+pub const ARG_HEADER: &str = "header";
+pub const ARG_NAME: &str = "name";
+pub const ARG_OFFLINE: &str = "offline";
+pub const ARG_RECORD: &str = "record";
+pub const ARG_REPLAY: &str = "replay";
+pub const ARG_RECORD_CONFORMANCE: &str = "record_conformance";
+pub const ARG_MAX_IN_FLIGHT: &str = "max_in_flight";
+pub const ARG_VERSION: &str = "version";
+pub const ARG_COLOR: &str = "color";
+pub const ARG_TUI: &str = "tui";
+pub const ARG_ADMIN_PORT: &str = "admin_port";
+pub const ARG_ADMIN_HOST: &str = "admin_host";
+pub const ARG_ENV: &str = "env";
+pub const ARG_CWD: &str = "cwd";
+pub const ARG_FORMAT: &str = "format";
+pub const ARG_TRANSPORT: &str = "transport";
+pub const ARG_COMPAT_TEXT: &str = "compat_text";
+pub const ARG_DAEMON: &str = "daemon";
+pub const ARG_PID_FILE: &str = "pid_file";
+pub const ARG_LOG_FILE: &str = "log_file";
+pub const ARG_TOOL: &str = "tool";
+pub const ARG_ARGUMENTS: &str = "arguments";
+pub const ARG_ITERATIONS: &str = "iterations";
+pub const ARG_CONCURRENCY: &str = "concurrency";
+pub const ARG_MOCK: &str = "mock";
+pub const ARG_COLD_CACHE: &str = "cold_cache";
+pub const ARG_CHECK: &str = "check";
+pub const ARG_POLICY: &str = "policy";
+pub const ARG_NONCE: &str = "nonce";
+pub const ARG_SIGN_KEY: &str = "sign_key";
 
 pub const DEFAULT_PORT_STR: &str = "8080";
 pub const DEFAULT_HOST: &str = "localhost";
 pub const DEFAULT_LOG_LEVEL: &str = "info";
+pub const DEFAULT_PROXY_LOG_LEVEL: &str = "warn";
+pub const DEFAULT_ARGUMENT_MODE: &str = ARG_MODE_LENIENT;
+pub const DEFAULT_MAX_INLINE_BYTES_STR: &str = "65536";
+pub const DEFAULT_MAX_IN_FLIGHT_STR: &str = "64";
+pub const DEFAULT_BENCH_TOOL: &str = "pcli2_version";
+pub const DEFAULT_BENCH_ITERATIONS_STR: &str = "20";
 
 pub const CLIENT_CLAUDE: &str = "claude";
 pub const CLIENT_QWEN_CODE: &str = "qwen-code";
 pub const CLIENT_QWEN_AGENT: &str = "qwen-agent";
+pub const CLIENT_CODEX: &str = "codex";
+pub const CLIENT_LIBRECHAT: &str = "librechat";
+pub const CLIENT_OPEN_WEBUI: &str = "open-webui";
+pub const FORMAT_JSON: &str = "json";
+pub const FORMAT_TOML: &str = "toml";
+pub const FORMAT_YAML: &str = "yaml";
+pub const FORMAT_MARKDOWN: &str = "markdown";
+pub const FORMAT_HTML: &str = "html";
+pub const TRANSPORT_LOCAL: &str = "local";
+pub const TRANSPORT_REMOTE: &str = "remote";
 const APP_NAME: &str = env!("CARGO_PKG_NAME");
 const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
 const APP_ABOUT: &str = "A simple MCP server over HTTP";
@@ -30,6 +96,16 @@ pub fn build_cli() -> Command {
         .disable_help_subcommand(true)
         .subcommand(serve_command())
         .subcommand(config_command())
+        .subcommand(proxy_command())
+        .subcommand(selftest_command())
+        .subcommand(bench_command())
+        .subcommand(doctor_command())
+        .subcommand(docs_command())
+        .subcommand(secrets_command())
+        .subcommand(install_pcli2_command())
+        .subcommand(self_update_command())
+        .subcommand(stop_command())
+        .subcommand(status_command())
         .subcommand(help_command())
 }
 
@@ -52,6 +128,20 @@ fn serve_command() -> Command {
                 .default_value(DEFAULT_PORT_STR)
                 .help("Port to listen on"),
         )
+        .arg(
+            Arg::new(ARG_ADMIN_PORT)
+                .long("admin-port")
+                .value_name("PORT")
+                .value_parser(value_parser!(u16))
+                .help("Serve /health and /admin/* on this port instead of the MCP port"),
+        )
+        .arg(
+            Arg::new(ARG_ADMIN_HOST)
+                .long("admin-host")
+                .value_name("HOST")
+                .requires(ARG_ADMIN_PORT)
+                .help("Host for the admin port (default: --host)"),
+        )
         .arg(
             Arg::new(ARG_LOG_LEVEL)
                 .long("log-level")
@@ -59,6 +149,128 @@ fn serve_command() -> Command {
                 .default_value(DEFAULT_LOG_LEVEL)
                 .help("Logging level (e.g. trace, debug, info, warn, error)"),
         )
+        .arg(
+            Arg::new(ARG_COLOR)
+                .long("color")
+                .value_name("WHEN")
+                .value_parser([COLOR_AUTO, COLOR_ALWAYS, COLOR_NEVER])
+                .default_value(COLOR_AUTO)
+                .help("Color the banner and logs: auto (terminal without NO_COLOR), always, or never"),
+        )
+        .arg(
+            Arg::new(ARG_TUI)
+                .long("tui")
+                .action(ArgAction::SetTrue)
+                .help("Show a live status view of running and recent tool calls instead of the log"),
+        )
+        .arg(
+            Arg::new(ARG_ARGUMENT_MODE)
+                .long("arg-mode")
+                .value_name("MODE")
+                .value_parser([ARG_MODE_LENIENT, ARG_MODE_STRICT])
+                .default_value(DEFAULT_ARGUMENT_MODE)
+                .help("Tool argument handling: coerce minor formatting slips (lenient) or reject them (strict)"),
+        )
+        .arg(
+            Arg::new(ARG_MAX_INLINE_BYTES)
+                .long("max-inline-bytes")
+                .value_name("BYTES")
+                .value_parser(value_parser!(usize))
+                .default_value(DEFAULT_MAX_INLINE_BYTES_STR)
+                .help("Tool output larger than this is returned as an MCP resource instead of inline text"),
+        )
+        .arg(
+            Arg::new(ARG_COMPAT_TEXT)
+                .long("compat-text")
+                .value_name("BOOL")
+                .value_parser(value_parser!(bool))
+                .default_value("true")
+                .help("Keep the JSON text block next to structuredContent in tool results"),
+        )
+        .arg(
+            Arg::new(ARG_MAX_IN_FLIGHT)
+                .long("max-in-flight")
+                .value_name("N")
+                .value_parser(value_parser!(u32).range(1..))
+                .default_value(DEFAULT_MAX_IN_FLIGHT_STR)
+                .help("Maximum /mcp requests handled at once; more are rejected with a retry hint"),
+        )
+        .arg(
+            Arg::new(ARG_CONFIG_FILE)
+                .long("config")
+                .value_name("PATH")
+                .value_parser(value_parser!(PathBuf))
+                .help("Path to a JSON server configuration file"),
+        )
+        .arg(
+            Arg::new(ARG_RECORD)
+                .long("record")
+                .value_name("PATH")
+                .value_parser(value_parser!(PathBuf))
+                .conflicts_with(ARG_REPLAY)
+                .help("Record every pcli2 run (argv, output, exit code) to a cassette file"),
+        )
+        .arg(
+            Arg::new(ARG_REPLAY)
+                .long("replay")
+                .value_name("PATH")
+                .value_parser(value_parser!(PathBuf))
+                .help("Answer pcli2 runs from a recorded cassette instead of running pcli2"),
+        )
+        .arg(
+            Arg::new(ARG_RECORD_CONFORMANCE)
+                .long("record-conformance")
+                .value_name("DIR")
+                .value_parser(value_parser!(PathBuf))
+                .conflicts_with_all([ARG_RECORD, ARG_REPLAY])
+                .help("Record every /mcp request and response, sanitized, with the pcli2 runs, as a conformance suite in DIR"),
+        )
+        .arg(
+            Arg::new(ARG_COLD_CACHE)
+                .long("cold-cache")
+                .action(ArgAction::SetTrue)
+                .help("Discard the persisted result cache (`cache.persist`) and start with an empty one"),
+        )
+        .arg(
+            Arg::new(ARG_DAEMON)
+                .long("daemon")
+                .action(ArgAction::SetTrue)
+                .requires(ARG_PID_FILE)
+                .conflicts_with(ARG_TUI)
+                .help("Run in the background; stop it with `stop --pid-file`"),
+        )
+        .arg(pid_file_arg().required(false).help(
+            "Write the server's process id to this file, and remove it on exit",
+        ))
+        .arg(
+            Arg::new(ARG_LOG_FILE)
+                .long("log-file")
+                .value_name("PATH")
+                .value_parser(value_parser!(PathBuf))
+                .requires(ARG_DAEMON)
+                .help("Append the log of a --daemon server to this file (default: discarded)"),
+        )
+}
+
+fn pid_file_arg() -> Arg {
+    Arg::new(ARG_PID_FILE)
+        .long("pid-file")
+        .value_name("PATH")
+        .value_parser(value_parser!(PathBuf))
+        .required(true)
+        .help("PID file written by `serve --pid-file`")
+}
+
+fn stop_command() -> Command {
+    Command::new(CMD_STOP)
+        .about("Stop a server started with --pid-file, and wait for it to exit")
+        .arg(pid_file_arg())
+}
+
+fn status_command() -> Command {
+    Command::new(CMD_STATUS)
+        .about("Report whether the server of a PID file is running")
+        .arg(pid_file_arg())
 }
 
 fn config_command() -> Command {
@@ -68,10 +280,32 @@ fn config_command() -> Command {
             Arg::new(ARG_CLIENT)
                 .long("client")
                 .value_name("CLIENT")
-                .value_parser([CLIENT_CLAUDE, CLIENT_QWEN_CODE, CLIENT_QWEN_AGENT])
+                .value_parser([
+                    CLIENT_CLAUDE,
+                    CLIENT_QWEN_CODE,
+                    CLIENT_QWEN_AGENT,
+                    CLIENT_CODEX,
+                    CLIENT_LIBRECHAT,
+                    CLIENT_OPEN_WEBUI,
+                ])
                 .default_value(CLIENT_CLAUDE)
                 .help("Target client config to render"),
         )
+        .arg(
+            Arg::new(ARG_FORMAT)
+                .long("format")
+                .value_name("FORMAT")
+                .value_parser([FORMAT_JSON, FORMAT_TOML, FORMAT_YAML])
+                .help("Output format (default: toml for codex, yaml for librechat, json otherwise)"),
+        )
+        .arg(
+            Arg::new(ARG_TRANSPORT)
+                .long("transport")
+                .value_name("TRANSPORT")
+                .value_parser([TRANSPORT_LOCAL, TRANSPORT_REMOTE])
+                .default_value(TRANSPORT_LOCAL)
+                .help("local: spawn the mcp-remote bridge; remote: connect to the streamable HTTP URL directly"),
+        )
         .arg(
             Arg::new(ARG_HOST)
                 .long("host")
@@ -88,6 +322,269 @@ fn config_command() -> Command {
                 .default_value(DEFAULT_PORT_STR)
                 .help("Port the local server will listen on"),
         )
+        .arg(
+            Arg::new(ARG_ENV)
+                .long("env")
+                .value_name("KEY=VALUE")
+                .action(ArgAction::Append)
+                .help("Environment variable for the spawned client process (repeatable), e.g. PCLI2_HOME or HTTPS_PROXY"),
+        )
+        .arg(
+            Arg::new(ARG_CWD)
+                .long("cwd")
+                .value_name("DIR")
+                .help("Working directory for the spawned client process"),
+        )
+}
+
+fn proxy_command() -> Command {
+    Command::new(CMD_PROXY)
+        .about("Serve MCP over stdio, forwarding to a remote pcli2-mcp HTTP server")
+        .arg(
+            Arg::new(ARG_URL)
+                .long("url")
+                .value_name("URL")
+                .required(true)
+                .help("Remote MCP endpoint (e.g. http://mcp.example.internal:8080/mcp)"),
+        )
+        .arg(
+            Arg::new(ARG_HEADER)
+                .long("header")
+                .value_name("NAME: VALUE")
+                .action(ArgAction::Append)
+                .help("Extra HTTP header for every request (repeatable); ${VAR} is read from the environment"),
+        )
+        .arg(
+            Arg::new(ARG_NONCE)
+                .long("nonce")
+                .action(ArgAction::SetTrue)
+                .help("Send a fresh nonce and timestamp with every request, for servers with access.replay"),
+        )
+        .arg(
+            Arg::new(ARG_SIGN_KEY)
+                .long("sign-key")
+                .value_name("KEY")
+                .help("Sign every request with this API key instead of sending it (access.replay.signed); ${VAR} is read from the environment"),
+        )
+        .arg(
+            Arg::new(ARG_LOG_LEVEL)
+                .long("log-level")
+                .value_name("LEVEL")
+                .default_value(DEFAULT_PROXY_LOG_LEVEL)
+                .help("Logging level; logs are written to stderr"),
+        )
+}
+
+fn selftest_command() -> Command {
+    Command::new(CMD_SELFTEST)
+        .about("Check an installation end to end: initialize, tools/list, and read-only tool calls")
+        .arg(
+            Arg::new(ARG_URL)
+                .long("url")
+                .value_name("URL")
+                .help("Test a running server instead of starting a temporary local one"),
+        )
+        .arg(
+            Arg::new(ARG_HEADER)
+                .long("header")
+                .value_name("NAME: VALUE")
+                .action(ArgAction::Append)
+                .requires(ARG_URL)
+                .help("Extra HTTP header for --url requests (repeatable); ${VAR} is read from the environment"),
+        )
+        .arg(
+            Arg::new(ARG_CONFIG_FILE)
+                .long("config")
+                .value_name("PATH")
+                .value_parser(value_parser!(PathBuf))
+                .help("Server configuration file (tool names, and the temporary server's settings)"),
+        )
+        .arg(
+            Arg::new(ARG_OFFLINE)
+                .long("offline")
+                .action(ArgAction::SetTrue)
+                .help("Skip steps that need Physna credentials (tenant list)"),
+        )
+        .arg(
+            Arg::new(ARG_LOG_LEVEL)
+                .long("log-level")
+                .value_name("LEVEL")
+                .default_value(DEFAULT_PROXY_LOG_LEVEL)
+                .help("Logging level; logs are written to stderr"),
+        )
+}
+
+fn doctor_command() -> Command {
+    Command::new(CMD_DOCTOR)
+        .about("Check every tool against the installed pcli2's subcommands and flags")
+        .arg(
+            Arg::new(ARG_CONFIG_FILE)
+                .long("config")
+                .value_name("PATH")
+                .value_parser(value_parser!(PathBuf))
+                .help("Server configuration file (pcli2 path, runner, and sandbox)"),
+        )
+        .arg(
+            Arg::new(ARG_LOG_LEVEL)
+                .long("log-level")
+                .value_name("LEVEL")
+                .default_value(DEFAULT_PROXY_LOG_LEVEL)
+                .help("Logging level; logs are written to stderr"),
+        )
+}
+
+fn docs_command() -> Command {
+    Command::new(CMD_DOCS)
+        .about("Render the tool catalog, with examples and the rules in effect, as a document")
+        .arg(
+            Arg::new(ARG_FORMAT)
+                .long("format")
+                .value_name("FORMAT")
+                .value_parser([FORMAT_MARKDOWN, FORMAT_HTML])
+                .default_value(FORMAT_MARKDOWN)
+                .help("Document format"),
+        )
+        .arg(
+            Arg::new(ARG_CONFIG_FILE)
+                .long("config")
+                .value_name("PATH")
+                .value_parser(value_parser!(PathBuf))
+                .help(
+                    "Server configuration file whose tool names, policies, and quotas to document",
+                ),
+        )
+        .arg(
+            Arg::new(ARG_POLICY)
+                .long("policy")
+                .value_name("NAME")
+                .help("Document only what API keys held to this policy can call"),
+        )
+}
+
+fn bench_command() -> Command {
+    Command::new(CMD_BENCH)
+        .about("Measure tool call latency, split into server overhead and pcli2 time")
+        .arg(
+            Arg::new(ARG_TOOL)
+                .long("tool")
+                .value_name("NAME")
+                .action(ArgAction::Append)
+                .help("Tool to call, as clients see it (repeatable; default: pcli2_version)"),
+        )
+        .arg(
+            Arg::new(ARG_ARGUMENTS)
+                .long("arguments")
+                .value_name("JSON")
+                .default_value("{}")
+                .help("Arguments object passed to every --tool call"),
+        )
+        .arg(
+            Arg::new(ARG_ITERATIONS)
+                .short('n')
+                .long("iterations")
+                .value_name("N")
+                .value_parser(value_parser!(u32).range(1..))
+                .default_value(DEFAULT_BENCH_ITERATIONS_STR)
+                .help("Calls per tool"),
+        )
+        .arg(
+            Arg::new(ARG_CONCURRENCY)
+                .long("concurrency")
+                .value_name("N")
+                .value_parser(value_parser!(u32).range(1..))
+                .default_value("1")
+                .help("Calls in flight at once; per-call pcli2 time is only split out at 1"),
+        )
+        .arg(
+            Arg::new(ARG_MOCK)
+                .long("mock")
+                .action(ArgAction::SetTrue)
+                .conflicts_with(ARG_URL)
+                .help("Answer pcli2 runs in process with an empty result, to measure the server alone"),
+        )
+        .arg(
+            Arg::new(ARG_URL)
+                .long("url")
+                .value_name("URL")
+                .help("Measure a running server instead of a temporary local one (no pcli2 split)"),
+        )
+        .arg(
+            Arg::new(ARG_HEADER)
+                .long("header")
+                .value_name("NAME: VALUE")
+                .action(ArgAction::Append)
+                .requires(ARG_URL)
+                .help("Extra HTTP header for --url requests (repeatable); ${VAR} is read from the environment"),
+        )
+        .arg(
+            Arg::new(ARG_CONFIG_FILE)
+                .long("config")
+                .value_name("PATH")
+                .value_parser(value_parser!(PathBuf))
+                .help("Server configuration file for the temporary local server"),
+        )
+        .arg(
+            Arg::new(ARG_LOG_LEVEL)
+                .long("log-level")
+                .value_name("LEVEL")
+                .default_value(DEFAULT_PROXY_LOG_LEVEL)
+                .help("Logging level; logs are written to stderr"),
+        )
+}
+
+fn secrets_command() -> Command {
+    let name = || {
+        Arg::new(ARG_NAME)
+            .value_name("NAME")
+            .required(true)
+            .help("Secret name, referenced in configuration as ${keyring:NAME}")
+    };
+    Command::new(CMD_SECRETS)
+        .about("Manage server secrets in the OS keyring")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new(CMD_SECRETS_SET)
+                .about("Store or rotate a secret; the value is read from stdin")
+                .arg(name()),
+        )
+        .subcommand(
+            Command::new(CMD_SECRETS_DELETE)
+                .about("Delete a secret")
+                .arg(name()),
+        )
+        .subcommand(
+            Command::new(CMD_SECRETS_CHECK)
+                .about("Check that a secret is stored, without printing it")
+                .arg(name()),
+        )
+}
+
+fn install_pcli2_command() -> Command {
+    Command::new(CMD_INSTALL_PCLI2)
+        .about("Download, verify, and install the pcli2 release binary used by the server")
+        .arg(
+            Arg::new(ARG_VERSION)
+                .long("version")
+                .value_name("VERSION")
+                .help("pcli2 release to install (e.g. 0.2.1); defaults to the latest release"),
+        )
+}
+
+fn self_update_command() -> Command {
+    Command::new(CMD_SELF_UPDATE)
+        .about("Replace this pcli2-mcp binary with a newer verified release")
+        .arg(
+            Arg::new(ARG_VERSION)
+                .long("version")
+                .value_name("VERSION")
+                .help("pcli2-mcp release to install (e.g. 0.2.0), even an older one; defaults to the latest release"),
+        )
+        .arg(
+            Arg::new(ARG_CHECK)
+                .long("check")
+                .action(ArgAction::SetTrue)
+                .help("Only report whether a newer release is available"),
+        )
 }
 
 fn help_command() -> Command {
@@ -97,7 +594,21 @@ fn help_command() -> Command {
             Arg::new(ARG_COMMAND)
                 .value_name("COMMAND")
                 .required(false)
-                .value_parser([CMD_SERVE, CMD_CONFIG, CMD_HELP])
+                .value_parser([
+                    CMD_SERVE,
+                    CMD_CONFIG,
+                    CMD_PROXY,
+                    CMD_SELFTEST,
+                    CMD_BENCH,
+                    CMD_DOCTOR,
+                    CMD_DOCS,
+                    CMD_SECRETS,
+                    CMD_INSTALL_PCLI2,
+                    CMD_SELF_UPDATE,
+                    CMD_STOP,
+                    CMD_STATUS,
+                    CMD_HELP,
+                ])
                 .help("Command to show help for"),
         )
 }
@@ -127,6 +638,15 @@ mod tests {
         assert!(args.contains(&ARG_HOST.to_string()));
         assert!(args.contains(&ARG_PORT.to_string()));
         assert!(args.contains(&ARG_LOG_LEVEL.to_string()));
+        assert!(args.contains(&ARG_ARGUMENT_MODE.to_string()));
+        assert!(args.contains(&ARG_MAX_INLINE_BYTES.to_string()));
+        assert!(args.contains(&ARG_MAX_IN_FLIGHT.to_string()));
+        assert!(args.contains(&ARG_CONFIG_FILE.to_string()));
+        assert!(
+            build_cli()
+                .try_get_matches_from(["pcli2-mcp", "serve", "--record", "a", "--replay", "b"])
+                .is_err()
+        );
     }
 
     #[test]
@@ -142,6 +662,238 @@ mod tests {
         assert!(args.contains(&ARG_CLIENT.to_string()));
         assert!(args.contains(&ARG_HOST.to_string()));
         assert!(args.contains(&ARG_PORT.to_string()));
+
+        let matches = build_cli()
+            .try_get_matches_from([
+                "pcli2-mcp",
+                "config",
+                "--env",
+                "PCLI2_HOME=/srv/pcli2",
+                "--env",
+                "HTTPS_PROXY=http://proxy:3128",
+                "--cwd",
+                "/srv",
+            ])
+            .unwrap();
+        let (_, sub_matches) = matches.subcommand().unwrap();
+        assert_eq!(sub_matches.get_many::<String>(ARG_ENV).unwrap().count(), 2);
+        assert_eq!(sub_matches.get_one::<String>(ARG_CWD).unwrap(), "/srv");
+
+        let matches = build_cli()
+            .try_get_matches_from(["pcli2-mcp", "config", "--client", "codex"])
+            .unwrap();
+        let (_, sub_matches) = matches.subcommand().unwrap();
+        assert_eq!(
+            sub_matches.get_one::<String>(ARG_CLIENT).unwrap(),
+            CLIENT_CODEX
+        );
+        assert!(sub_matches.get_one::<String>(ARG_FORMAT).is_none());
+        assert_eq!(
+            sub_matches.get_one::<String>(ARG_TRANSPORT).unwrap(),
+            TRANSPORT_LOCAL
+        );
+    }
+
+    #[test]
+    fn test_proxy_command() {
+        let matches = build_cli()
+            .try_get_matches_from([
+                "pcli2-mcp",
+                "proxy",
+                "--url",
+                "http://host:8080/mcp",
+                "--header",
+                "A: 1",
+                "--header",
+                "B: 2",
+            ])
+            .unwrap();
+        let (name, sub_matches) = matches.subcommand().unwrap();
+        assert_eq!(name, CMD_PROXY);
+        let headers: Vec<&String> = sub_matches
+            .get_many::<String>(ARG_HEADER)
+            .unwrap()
+            .collect();
+        assert_eq!(headers, ["A: 1", "B: 2"]);
+        assert_eq!(
+            sub_matches.get_one::<String>(ARG_LOG_LEVEL).unwrap(),
+            DEFAULT_PROXY_LOG_LEVEL
+        );
+    }
+
+    #[test]
+    fn test_selftest_command() {
+        let matches = build_cli()
+            .try_get_matches_from(["pcli2-mcp", "selftest", "--offline"])
+            .unwrap();
+        let (name, sub_matches) = matches.subcommand().unwrap();
+        assert_eq!(name, CMD_SELFTEST);
+        assert!(sub_matches.get_flag(ARG_OFFLINE));
+        assert!(sub_matches.get_one::<String>(ARG_URL).is_none());
+        assert!(
+            build_cli()
+                .try_get_matches_from(["pcli2-mcp", "selftest", "--header", "A: 1"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_doctor_command() {
+        let matches = build_cli()
+            .try_get_matches_from(["pcli2-mcp", "doctor", "--config", "server.toml"])
+            .unwrap();
+        let (name, sub_matches) = matches.subcommand().unwrap();
+        assert_eq!(name, CMD_DOCTOR);
+        assert_eq!(
+            sub_matches.get_one::<PathBuf>(ARG_CONFIG_FILE),
+            Some(&PathBuf::from("server.toml"))
+        );
+    }
+
+    #[test]
+    fn test_bench_command() {
+        let matches = build_cli()
+            .try_get_matches_from([
+                "pcli2-mcp",
+                "bench",
+                "--tool",
+                "pcli2_version",
+                "--tool",
+                "pcli2_tenant_list",
+                "-n",
+                "5",
+                "--mock",
+            ])
+            .unwrap();
+        let (name, sub_matches) = matches.subcommand().unwrap();
+        assert_eq!(name, CMD_BENCH);
+        let tools: Vec<&String> = sub_matches.get_many::<String>(ARG_TOOL).unwrap().collect();
+        assert_eq!(tools, ["pcli2_version", "pcli2_tenant_list"]);
+        assert_eq!(sub_matches.get_one::<u32>(ARG_ITERATIONS), Some(&5));
+        assert_eq!(sub_matches.get_one::<u32>(ARG_CONCURRENCY), Some(&1));
+        assert!(sub_matches.get_flag(ARG_MOCK));
+        for invalid in [
+            vec![
+                "pcli2-mcp",
+                "bench",
+                "--mock",
+                "--url",
+                "http://localhost:8080/mcp",
+            ],
+            vec!["pcli2-mcp", "bench", "-n", "0"],
+        ] {
+            assert!(build_cli().try_get_matches_from(invalid).is_err());
+        }
+    }
+
+    #[test]
+    fn test_secrets_command() {
+        let matches = build_cli()
+            .try_get_matches_from(["pcli2-mcp", "secrets", "set", "admin-token"])
+            .unwrap();
+        let (name, sub_matches) = matches.subcommand().unwrap();
+        assert_eq!(name, CMD_SECRETS);
+        let (command, args) = sub_matches.subcommand().unwrap();
+        assert_eq!(command, CMD_SECRETS_SET);
+        assert_eq!(args.get_one::<String>(ARG_NAME).unwrap(), "admin-token");
+        assert!(
+            build_cli()
+                .try_get_matches_from(["pcli2-mcp", "secrets", "get", "admin-token"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_install_pcli2_command() {
+        let matches = build_cli()
+            .try_get_matches_from(["pcli2-mcp", "install-pcli2", "--version", "0.2.1"])
+            .unwrap();
+        let (name, sub_matches) = matches.subcommand().unwrap();
+        assert_eq!(name, CMD_INSTALL_PCLI2);
+        assert_eq!(sub_matches.get_one::<String>(ARG_VERSION).unwrap(), "0.2.1");
+    }
+
+    #[test]
+    fn test_docs_command() {
+        let matches = build_cli()
+            .try_get_matches_from([
+                "pcli2-mcp",
+                "docs",
+                "--format",
+                "html",
+                "--policy",
+                "readers",
+            ])
+            .unwrap();
+        let sub_matches = matches.subcommand_matches(CMD_DOCS).unwrap();
+        assert_eq!(
+            sub_matches
+                .get_one::<String>(ARG_FORMAT)
+                .map(String::as_str),
+            Some(FORMAT_HTML)
+        );
+        assert_eq!(
+            sub_matches
+                .get_one::<String>(ARG_POLICY)
+                .map(String::as_str),
+            Some("readers")
+        );
+        assert!(
+            build_cli()
+                .try_get_matches_from(["pcli2-mcp", "docs", "--format", "pdf"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_self_update_command() {
+        let matches = build_cli()
+            .try_get_matches_from(["pcli2-mcp", "self-update", "--check"])
+            .unwrap();
+        let sub_matches = matches.subcommand_matches(CMD_SELF_UPDATE).unwrap();
+        assert!(sub_matches.get_flag(ARG_CHECK));
+        assert!(sub_matches.get_one::<String>(ARG_VERSION).is_none());
+    }
+
+    #[test]
+    fn test_daemon_commands() {
+        let matches = build_cli()
+            .try_get_matches_from([
+                "pcli2-mcp",
+                "serve",
+                "--daemon",
+                "--pid-file",
+                "/run/pcli2-mcp.pid",
+            ])
+            .unwrap();
+        let sub_matches = matches.subcommand_matches(CMD_SERVE).unwrap();
+        assert!(sub_matches.get_flag(ARG_DAEMON));
+        assert!(
+            build_cli()
+                .try_get_matches_from(["pcli2-mcp", "serve", "--daemon"])
+                .is_err()
+        );
+        assert!(
+            build_cli()
+                .try_get_matches_from(["pcli2-mcp", "serve", "--log-file", "/tmp/mcp.log"])
+                .is_err()
+        );
+        for command in [CMD_STOP, CMD_STATUS] {
+            let matches = build_cli()
+                .try_get_matches_from(["pcli2-mcp", command, "--pid-file", "/run/pcli2-mcp.pid"])
+                .unwrap();
+            let (name, sub_matches) = matches.subcommand().unwrap();
+            assert_eq!(name, command);
+            assert_eq!(
+                sub_matches.get_one::<PathBuf>(ARG_PID_FILE).unwrap(),
+                &PathBuf::from("/run/pcli2-mcp.pid")
+            );
+            assert!(
+                build_cli()
+                    .try_get_matches_from(["pcli2-mcp", command])
+                    .is_err()
+            );
+        }
     }
 
     #[test]
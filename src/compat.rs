@@ -0,0 +1,677 @@
+use crate::{
+    AppState,
+    admin::CREDENTIALS_TOOL,
+    approval::APPROVAL_STATUS_TOOL,
+    archive::ARCHIVE_TOOL,
+    artifacts::{ARTIFACTS_LIST_TOOL, ARTIFACTS_PURGE_TOOL},
+    audit::AUDIT_TOOL,
+    batch_get::BATCH_GET_TOOL,
+    export::EXPORT_TOOL,
+    gallery::GALLERY_TOOL,
+    graph::SIMILARITY_GRAPH_TOOL,
+    image::IMAGE_MATCH_TOOL,
+    index::INDEX_SEARCH_TOOL,
+    pcli::run_pcli2_background,
+    plan::PLAN_TOOL,
+    quota::QUOTA_STATUS_TOOL,
+    search::MULTI_TENANT_SEARCH_TOOL,
+    server::{build_state, load_config},
+    snapshot::{DIFF_TOOL, SNAPSHOT_TOOL},
+    suggest::FOLDER_SUGGEST_TOOL,
+    upload::UPLOAD_TOOL,
+};
+use anyhow::{Result, anyhow};
+use clap::ArgMatches;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+/// A `pcli2` subcommand a tool runs, with every flag it may pass to it.
+struct Usage {
+    command: &'static [&'static str],
+    flags: &'static [&'static str],
+}
+
+const LIST_FLAGS: &[&str] = &[
+    "-t",
+    "--metadata",
+    "--headers",
+    "--pretty",
+    "-f",
+    "--folder-uuid",
+    "--folder-path",
+    "--reload",
+];
+const OUTPUT_FLAGS: &[&str] = &["--headers", "--pretty", "-f"];
+const ASSET_OUTPUT_FLAGS: &[&str] = &[
+    "-t",
+    "--uuid",
+    "--path",
+    "--headers",
+    "--metadata",
+    "--pretty",
+    "-f",
+];
+const ASSET_MATCH_FLAGS: &[&str] = &[
+    "-t",
+    "--uuid",
+    "--path",
+    "--threshold",
+    "--headers",
+    "--metadata",
+    "--pretty",
+    "-f",
+];
+const FOLDER_MATCH_FLAGS: &[&str] = &[
+    "-t",
+    "--folder-path",
+    "--threshold",
+    "--exclusive",
+    "--headers",
+    "--metadata",
+    "--pretty",
+    "-f",
+    "--concurrent",
+    "--progress",
+];
+const TEXT_MATCH_FLAGS: &[&str] = &[
+    "-t",
+    "--text",
+    "--fuzzy",
+    "--headers",
+    "--metadata",
+    "--pretty",
+    "-f",
+];
+const FOLDER_LIST: Usage = Usage {
+    command: &["folder", "list"],
+    flags: LIST_FLAGS,
+};
+const ASSET_LIST: Usage = Usage {
+    command: &["asset", "list"],
+    flags: LIST_FLAGS,
+};
+const ASSET_THUMBNAIL: Usage = Usage {
+    command: &["asset", "thumbnail"],
+    flags: &["-t", "--uuid", "--path", "--file"],
+};
+const ASSET_GET: Usage = Usage {
+    command: &["asset", "get"],
+    flags: ASSET_OUTPUT_FLAGS,
+};
+const ASSET_DOWNLOAD: Usage = Usage {
+    command: &["asset", "download"],
+    flags: &["-t", "--uuid", "--path", "--file"],
+};
+const ASSET_GEOMETRIC_MATCH: Usage = Usage {
+    command: &["asset", "geometric-match"],
+    flags: ASSET_MATCH_FLAGS,
+};
+const FOLDER_GEOMETRIC_MATCH: Usage = Usage {
+    command: &["folder", "geometric-match"],
+    flags: FOLDER_MATCH_FLAGS,
+};
+const ASSET_TEXT_MATCH: Usage = Usage {
+    command: &["asset", "text-match"],
+    flags: TEXT_MATCH_FLAGS,
+};
+
+/// What each tool runs. Keep in step with the `run_pcli2_*` functions.
+const CATALOG: &[(&str, &[Usage])] = &[
+    ("pcli2", &[FOLDER_LIST, ASSET_LIST]),
+    ("pcli2_folder_list", &[FOLDER_LIST]),
+    ("pcli2_asset_list", &[ASSET_LIST]),
+    (
+        "pcli2_tenant_list",
+        &[Usage {
+            command: &["tenant", "list"],
+            flags: OUTPUT_FLAGS,
+        }],
+    ),
+    (
+        "pcli2_version",
+        &[Usage {
+            command: &[],
+            flags: &["--version"],
+        }],
+    ),
+    (
+        "pcli2_config_get",
+        &[Usage {
+            command: &["config", "get"],
+            flags: OUTPUT_FLAGS,
+        }],
+    ),
+    (
+        "pcli2_config_get_path",
+        &[Usage {
+            command: &["config", "get", "path"],
+            flags: &["-f"],
+        }],
+    ),
+    (
+        "pcli2_config_environment_list",
+        &[Usage {
+            command: &["config", "environment", "list"],
+            flags: OUTPUT_FLAGS,
+        }],
+    ),
+    (
+        "pcli2_config_environment_get",
+        &[Usage {
+            command: &["config", "environment", "get"],
+            flags: &["-n", "--headers", "--pretty", "-f"],
+        }],
+    ),
+    (
+        "pcli2_tenant_get",
+        &[Usage {
+            command: &["tenant", "get"],
+            flags: OUTPUT_FLAGS,
+        }],
+    ),
+    (
+        "pcli2_tenant_state",
+        &[Usage {
+            command: &["tenant", "state"],
+            flags: &["-t", "--type", "--headers", "--pretty", "-f"],
+        }],
+    ),
+    (
+        "pcli2_tenant_use",
+        &[Usage {
+            command: &["tenant", "use"],
+            flags: &["--name", "--refresh", "--headers", "--pretty", "-f"],
+        }],
+    ),
+    (
+        "pcli2_folder_get",
+        &[Usage {
+            command: &["folder", "get"],
+            flags: &[
+                "-t",
+                "--folder-uuid",
+                "--folder-path",
+                "--metadata",
+                "--headers",
+                "--pretty",
+                "-f",
+            ],
+        }],
+    ),
+    (
+        "pcli2_folder_resolve",
+        &[Usage {
+            command: &["folder", "resolve"],
+            flags: &["-t", "--folder-path"],
+        }],
+    ),
+    (
+        "pcli2_folder_dependencies",
+        &[Usage {
+            command: &["folder", "dependencies"],
+            flags: &[
+                "-t",
+                "--folder-path",
+                "--headers",
+                "--metadata",
+                "--pretty",
+                "-f",
+                "--progress",
+            ],
+        }],
+    ),
+    ("pcli2_folder_geometric_match", &[FOLDER_GEOMETRIC_MATCH]),
+    (
+        "pcli2_folder_part_match",
+        &[Usage {
+            command: &["folder", "part-match"],
+            flags: FOLDER_MATCH_FLAGS,
+        }],
+    ),
+    (
+        "pcli2_folder_visual_match",
+        &[Usage {
+            command: &["folder", "visual-match"],
+            flags: &[
+                "-t",
+                "--folder-path",
+                "--exclusive",
+                "--headers",
+                "--metadata",
+                "--pretty",
+                "-f",
+                "--concurrent",
+                "--progress",
+            ],
+        }],
+    ),
+    ("pcli2_asset_get", &[ASSET_GET]),
+    (BATCH_GET_TOOL, &[ASSET_GET]),
+    (
+        "pcli2_asset_dependencies",
+        &[Usage {
+            command: &["asset", "dependencies"],
+            flags: ASSET_OUTPUT_FLAGS,
+        }],
+    ),
+    ("pcli2_asset_thumbnail", &[ASSET_THUMBNAIL]),
+    (GALLERY_TOOL, &[ASSET_LIST, ASSET_THUMBNAIL]),
+    (EXPORT_TOOL, &[ASSET_DOWNLOAD]),
+    // Thumbnail archives are left to fail like the gallery without thumbnails.
+    (ARCHIVE_TOOL, &[ASSET_LIST, ASSET_DOWNLOAD]),
+    (
+        "pcli2_asset_reprocess",
+        &[Usage {
+            command: &["asset", "reprocess"],
+            flags: &["-t", "--uuid", "--path"],
+        }],
+    ),
+    ("pcli2_geometric_match", &[ASSET_GEOMETRIC_MATCH]),
+    (
+        "pcli2_asset_part_match",
+        &[Usage {
+            command: &["asset", "part-match"],
+            flags: ASSET_MATCH_FLAGS,
+        }],
+    ),
+    (
+        "pcli2_asset_visual_match",
+        &[Usage {
+            command: &["asset", "visual-match"],
+            flags: ASSET_OUTPUT_FLAGS,
+        }],
+    ),
+    ("pcli2_asset_text_match", &[ASSET_TEXT_MATCH]),
+    (
+        "pcli2_asset_metadata_create",
+        &[Usage {
+            command: &["asset", "metadata", "create"],
+            flags: &["-t", "--uuid", "--path", "--name", "--value", "--type"],
+        }],
+    ),
+    (
+        UPLOAD_TOOL,
+        &[Usage {
+            command: &["asset", "create"],
+            flags: &["-t", "--file", "--folder-path"],
+        }],
+    ),
+    (
+        "pcli2_asset_metadata_delete",
+        &[Usage {
+            command: &["asset", "metadata", "delete"],
+            flags: &["-t", "--uuid", "--path", "--name", "-f"],
+        }],
+    ),
+    (
+        MULTI_TENANT_SEARCH_TOOL,
+        &[ASSET_TEXT_MATCH, ASSET_GEOMETRIC_MATCH],
+    ),
+    // Part and visual graphs are left to fail like the folder match tools.
+    (SIMILARITY_GRAPH_TOOL, &[FOLDER_GEOMETRIC_MATCH]),
+    (FOLDER_SUGGEST_TOOL, &[FOLDER_LIST]),
+    (SNAPSHOT_TOOL, &[ASSET_LIST]),
+    (DIFF_TOOL, &[ASSET_LIST]),
+];
+
+/// Tools left out of the check, and why.
+const UNCHECKED: &[(&str, &str)] = &[
+    ("pcli2_batch", "runs other tools"),
+    (QUOTA_STATUS_TOOL, "does not run pcli2"),
+    (AUDIT_TOOL, "does not run pcli2"),
+    (PLAN_TOOL, "does not run pcli2"),
+    (APPROVAL_STATUS_TOOL, "does not run pcli2"),
+    (ARTIFACTS_LIST_TOOL, "does not run pcli2"),
+    (ARTIFACTS_PURGE_TOOL, "does not run pcli2"),
+    (INDEX_SEARCH_TOOL, "searches the local index"),
+    (CREDENTIALS_TOOL, "runs admin.credential_commands"),
+    (IMAGE_MATCH_TOOL, "runs image_match.command"),
+];
+
+/// How a tool fares against the installed `pcli2`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Support {
+    Supported,
+    /// A subcommand the tool runs is missing; the reason names it.
+    Missing(String),
+    /// A flag the tool passes is missing; the reason names it.
+    Unsupported(String),
+    /// Not checked, with the reason.
+    Unchecked(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolSupport {
+    pub tool: String,
+    pub support: Support,
+}
+
+/// The flags a `--help` text offers: every word starting with `-`, without
+/// the punctuation around it (`-t,`, `[--pretty]`, `--format=<FORMAT>`).
+fn help_flags(help: &str) -> HashSet<&str> {
+    help.split(|c: char| c.is_whitespace() || matches!(c, ',' | '[' | ']' | '(' | ')' | '|'))
+        .filter_map(|word| word.split(['=', '<', '.']).next())
+        .filter(|word| word.starts_with('-') && word.len() > 1)
+        .collect()
+}
+
+/// A `pcli2` error on one line, for the report.
+fn one_line(message: &str) -> String {
+    message.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn render_command(command: &[&str]) -> String {
+    if command.is_empty() {
+        "pcli2".to_string()
+    } else {
+        format!("pcli2 {}", command.join(" "))
+    }
+}
+
+/// Compares the catalog with the `--help` text of each subcommand, as
+/// returned by `help` (the help text, or why it could not be read).
+fn compare(helps: &HashMap<String, Result<String, String>>) -> Vec<ToolSupport> {
+    let mut report: Vec<ToolSupport> = CATALOG
+        .iter()
+        .map(|(tool, usages)| {
+            let mut missing_commands = Vec::new();
+            let mut problems = Vec::new();
+            for usage in usages.iter() {
+                let command = render_command(usage.command);
+                match helps.get(&command) {
+                    Some(Ok(help)) => {
+                        let offered = help_flags(help);
+                        let missing: Vec<&str> = usage
+                            .flags
+                            .iter()
+                            .copied()
+                            .filter(|flag| !offered.contains(flag))
+                            .collect();
+                        if !missing.is_empty() {
+                            problems.push(format!("{} has no {}", command, missing.join(", ")));
+                        }
+                    }
+                    Some(Err(message)) => missing_commands.push(one_line(message)),
+                    None => problems.push(format!("{} was not checked", command)),
+                }
+            }
+            ToolSupport {
+                tool: tool.to_string(),
+                support: if !missing_commands.is_empty() {
+                    Support::Missing(missing_commands.join("; "))
+                } else if !problems.is_empty() {
+                    Support::Unsupported(problems.join("; "))
+                } else {
+                    Support::Supported
+                },
+            }
+        })
+        .collect();
+    report.extend(UNCHECKED.iter().map(|(tool, reason)| ToolSupport {
+        tool: tool.to_string(),
+        support: Support::Unchecked(reason.to_string()),
+    }));
+    report
+}
+
+/// Runs `--help` for every subcommand the tools use and reports each
+/// tool's support. When `pcli2` itself does not run, nothing can be told,
+/// so every tool is reported unchecked.
+pub async fn check_catalog(state: &AppState) -> Vec<ToolSupport> {
+    let mut helps = HashMap::new();
+    let commands: BTreeMap<String, &[&str]> = CATALOG
+        .iter()
+        .flat_map(|(_, usages)| usages.iter())
+        .map(|usage| (render_command(usage.command), usage.command))
+        .collect();
+    for (rendered, command) in commands {
+        let mut args: Vec<String> = command.iter().map(|word| word.to_string()).collect();
+        args.push("--help".to_string());
+        let outcome = run_pcli2_background(state, args, &format!("{} --help", rendered)).await;
+        if command.is_empty()
+            && let Err(message) = &outcome
+        {
+            let reason = format!("pcli2 did not run: {}", one_line(message));
+            return CATALOG
+                .iter()
+                .map(|(tool, _)| *tool)
+                .chain(UNCHECKED.iter().map(|(tool, _)| *tool))
+                .map(|tool| ToolSupport {
+                    tool: tool.to_string(),
+                    support: Support::Unchecked(reason.clone()),
+                })
+                .collect();
+        }
+        helps.insert(rendered, outcome);
+    }
+    compare(&helps)
+}
+
+/// Tools turned off because the installed `pcli2` lacks what they use,
+/// with the reason.
+#[derive(Clone, Default)]
+pub struct ToolCompatibility {
+    disabled: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl ToolCompatibility {
+    /// Why `tool` is turned off, if it is.
+    pub fn disabled(&self, tool: &str) -> Option<String> {
+        self.disabled
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(tool)
+            .cloned()
+    }
+
+    /// Every turned off tool and why, by internal name.
+    pub fn list(&self) -> Vec<(String, String)> {
+        let disabled = self.disabled.lock().unwrap_or_else(|e| e.into_inner());
+        let mut list: Vec<(String, String)> = disabled
+            .iter()
+            .map(|(tool, reason)| (tool.clone(), reason.clone()))
+            .collect();
+        list.sort();
+        list
+    }
+
+    /// Turns off the tools of `report` whose subcommand is missing, and
+    /// those missing a flag when `flags` is set; returns whether the set of
+    /// turned off tools changed.
+    pub fn apply(&self, report: &[ToolSupport], flags: bool) -> bool {
+        let disabled: HashMap<String, String> = report
+            .iter()
+            .filter_map(|entry| match &entry.support {
+                Support::Missing(reason) => Some((entry.tool.clone(), reason.clone())),
+                Support::Unsupported(reason) if flags => Some((entry.tool.clone(), reason.clone())),
+                _ => None,
+            })
+            .collect();
+        let mut current = self.disabled.lock().unwrap_or_else(|e| e.into_inner());
+        let changed = *current != disabled;
+        *current = disabled;
+        changed
+    }
+}
+
+/// A tool turned off because the installed `pcli2` cannot run it.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct UnavailableTool {
+    /// Public name of the tool.
+    pub name: String,
+    pub reason: String,
+}
+
+/// The turned off tools under their public names, for the `initialize`
+/// result and `/health/ready`.
+pub fn unavailable_tools(state: &AppState) -> Vec<UnavailableTool> {
+    state
+        .compat
+        .list()
+        .into_iter()
+        .map(|(tool, reason)| UnavailableTool {
+            name: state.config.tools.public_name(&tool),
+            reason,
+        })
+        .collect()
+}
+
+/// The report as a table, one tool per line.
+pub fn render_report(report: &[ToolSupport]) -> String {
+    let mut lines = Vec::with_capacity(report.len() + 1);
+    for entry in report {
+        lines.push(match &entry.support {
+            Support::Supported => format!("✔ {}", entry.tool),
+            Support::Missing(reason) | Support::Unsupported(reason) => {
+                format!("✘ {:<32} {}", entry.tool, reason)
+            }
+            Support::Unchecked(reason) => format!("· {:<32} not checked: {}", entry.tool, reason),
+        });
+    }
+    let count =
+        |wanted: fn(&Support) -> bool| report.iter().filter(|entry| wanted(&entry.support)).count();
+    lines.push(format!(
+        "{} supported, {} unsupported, {} not checked",
+        count(|support| *support == Support::Supported),
+        count(|support| matches!(support, Support::Missing(_) | Support::Unsupported(_))),
+        count(|support| matches!(support, Support::Unchecked(_)))
+    ));
+    lines.join("\n")
+}
+
+/// Checks the catalog at startup and turns off the tools whose subcommand
+/// the installed `pcli2` lacks (and with `tools.verify_pcli2`, those missing
+/// a flag), so they are not advertised only to fail when called.
+pub struct CatalogCheck {
+    state: AppState,
+}
+
+impl CatalogCheck {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    pub async fn run_once(&self) -> Vec<ToolSupport> {
+        let report = check_catalog(&self.state).await;
+        let flags = self.state.config.tools.verify_pcli2;
+        for entry in &report {
+            match &entry.support {
+                Support::Missing(reason) => warn!("⚠ hiding {}: {}", entry.tool, reason),
+                Support::Unsupported(reason) if flags => {
+                    warn!("⚠ hiding {}: {}", entry.tool, reason)
+                }
+                Support::Unsupported(reason) => warn!("⚠ {} may fail: {}", entry.tool, reason),
+                _ => {}
+            }
+        }
+        if self.state.compat.apply(&report, flags) {
+            self.state.notifier.tools_list_changed();
+        }
+        info!(
+            "🩺 pcli2 catalog check: {}",
+            render_report(&report).lines().last().unwrap_or_default()
+        );
+        report
+    }
+
+    /// Starts the check in the background. Returns `None` when
+    /// `tools.skip_pcli2_check` is set.
+    pub fn spawn(self) -> Option<JoinHandle<()>> {
+        if self.state.config.tools.skip_pcli2_check {
+            return None;
+        }
+        Some(tokio::spawn(async move {
+            self.run_once().await;
+        }))
+    }
+}
+
+/// `pcli2-mcp doctor`: prints each tool's support by the installed `pcli2`.
+pub async fn run_doctor(matches: &ArgMatches) -> Result<()> {
+    let config = load_config(matches)?;
+    let state = build_state(config)?;
+    let report = check_catalog(&state).await;
+    println!("{}", render_report(&report));
+    let unsupported = report
+        .iter()
+        .filter(|entry| matches!(entry.support, Support::Missing(_) | Support::Unsupported(_)))
+        .count();
+    if unsupported > 0 {
+        return Err(anyhow!(
+            "{} tool(s) use subcommands or flags the installed pcli2 lacks",
+            unsupported
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pcli::tool_list;
+
+    #[test]
+    fn test_every_tool_is_in_the_catalog() {
+        let known: HashSet<&str> = CATALOG
+            .iter()
+            .map(|(tool, _)| *tool)
+            .chain(UNCHECKED.iter().map(|(tool, _)| *tool))
+            .collect();
+        for tool in tool_list() {
+            let name = tool["name"].as_str().unwrap();
+            assert!(known.contains(name), "{} is missing from the catalog", name);
+        }
+    }
+
+    #[test]
+    fn test_missing_flags_make_a_tool_unsupported() {
+        let help = "Usage: pcli2 asset get [OPTIONS]\n\n  -t, --tenant <TENANT>\n      --uuid <UUID>\n      --path <PATH>\n  [--headers] [--metadata]\n      --pretty\n  -f, --format=<FORMAT>\n";
+        assert!(help_flags(help).contains("-t"));
+        assert!(help_flags(help).contains("--format"));
+        assert!(help_flags(help).contains("--metadata"));
+
+        let mut helps = HashMap::new();
+        helps.insert("pcli2 asset get".to_string(), Ok(help.to_string()));
+        helps.insert(
+            "pcli2 asset dependencies".to_string(),
+            Ok(help.replace("--metadata", "")),
+        );
+        helps.insert(
+            "pcli2 asset reprocess".to_string(),
+            Err("error: unrecognized subcommand 'reprocess'".to_string()),
+        );
+        let report = compare(&helps);
+        let support = |tool: &str| {
+            report
+                .iter()
+                .find(|entry| entry.tool == tool)
+                .map(|entry| entry.support.clone())
+                .unwrap()
+        };
+        assert_eq!(support("pcli2_asset_get"), Support::Supported);
+        assert_eq!(
+            support("pcli2_asset_dependencies"),
+            Support::Unsupported("pcli2 asset dependencies has no --metadata".to_string())
+        );
+        assert!(matches!(
+            support("pcli2_asset_reprocess"),
+            Support::Missing(reason) if reason.contains("unrecognized subcommand")
+        ));
+        assert!(matches!(support("pcli2_plan"), Support::Unchecked(_)));
+
+        let compat = ToolCompatibility::default();
+        assert!(compat.apply(&report, false));
+        assert!(!compat.apply(&report, false));
+        assert!(compat.disabled("pcli2_asset_reprocess").is_some());
+        assert!(compat.disabled("pcli2_asset_dependencies").is_none());
+        assert!(compat.apply(&report, true));
+        assert!(compat.disabled("pcli2_asset_dependencies").is_some());
+        assert!(compat.disabled("pcli2_asset_get").is_none());
+    }
+}
@@ -0,0 +1,2754 @@
+use crate::{auth::expand_args, flags::FEATURES, i18n::Locale};
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing_subscriber::EnvFilter;
+
+pub const DEFAULT_TOOL_PREFIX: &str = "pcli2";
+pub const DEFAULT_TOOL_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+pub const MAX_RETRIES: u32 = 5;
+/// Request details a `pcli2_plan` step can take as arguments.
+pub const PLAN_INPUTS: [&str; 6] = ["asset", "folder", "text", "metadata", "tenant", "threshold"];
+pub const DEFAULT_EXPIRY_PATTERNS: [&str; 7] = [
+    "token expired",
+    "token has expired",
+    "expired token",
+    "invalid token",
+    "invalid_token",
+    "unauthorized",
+    "authentication required",
+];
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub tools: ToolsConfig,
+    pub execution: ExecutionConfig,
+    pub circuit_breaker: CircuitBreakerConfig,
+    pub auth: AuthConfig,
+    pub redaction: RedactionConfig,
+    pub sandbox: SandboxConfig,
+    pub shared_store: SharedStoreConfig,
+    pub quotas: HashMap<String, QuotaRule>,
+    pub admin: AdminConfig,
+    pub snapshots: SnapshotConfig,
+    pub watch: WatchConfig,
+    pub index: IndexConfig,
+    pub usage: UsageConfig,
+    pub banner: BannerConfig,
+    pub ssh: SshConfig,
+    pub docker: DockerConfig,
+    pub cli_versions: CliVersionsConfig,
+    pub multi_tenant_search: MultiTenantSearchConfig,
+    pub api_keys: Vec<ApiKeyConfig>,
+    /// Named profiles that `api_keys` entries hold their callers to.
+    pub policies: HashMap<String, PolicyConfig>,
+    pub access: AccessConfig,
+    pub plan: PlanConfig,
+    pub warm: WarmConfig,
+    pub cache: CacheConfig,
+    pub health: HealthConfig,
+    /// Listeners served next to `--host`/`--port`, sharing its state.
+    pub listeners: Vec<ListenerConfig>,
+    pub image_match: ImageMatchConfig,
+    pub export: ExportConfig,
+    pub approval: ApprovalConfig,
+    pub workspace: WorkspaceConfig,
+    pub artifacts: ArtifactsConfig,
+    pub instructions: InstructionsConfig,
+    pub capabilities: CapabilitiesConfig,
+    /// Rollout of the experimental subsystems named in `flags::FEATURES`,
+    /// which are on for every session unless listed here.
+    pub features: HashMap<String, FeatureConfig>,
+    /// Call defaults of each tenant, keyed by the tenant ID or alias calls
+    /// pass as `tenant`.
+    pub tenants: HashMap<String, TenantDefaults>,
+    pub identity: IdentityConfig,
+    /// Language of tool descriptions and server errors: `en` or `ja`.
+    pub locale: Locale,
+    /// Log filter such as `info` or `pcli2_mcp=debug`; replaces `--log-level`
+    /// and `RUST_LOG` once the file is loaded or reloaded.
+    pub log_level: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ToolsConfig {
+    pub prefix: Option<String>,
+    pub aliases: HashMap<String, String>,
+    pub hide_generic_tool: bool,
+    /// Per-tool override of `serve --compat-text`.
+    pub compat_text: HashMap<String, bool>,
+    /// Also turn off tools whose flags the installed `pcli2` lacks, not
+    /// only those whose subcommand is missing (see `pcli2-mcp doctor`).
+    pub verify_pcli2: bool,
+    /// Skip the startup check of the installed `pcli2`, advertising every
+    /// tool whatever it supports.
+    pub skip_pcli2_check: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExecutionConfig {
+    pub default: ToolPolicy,
+    pub tools: HashMap<String, ToolPolicy>,
+    /// Calls of any tool that may run `pcli2` at once; extra calls wait,
+    /// with waiting sessions taking turns for the free slots.
+    pub max_concurrent_calls: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ToolPolicy {
+    pub timeout_secs: Option<u64>,
+    pub retries: Option<u32>,
+    pub concurrency: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CircuitBreakerConfig {
+    pub failure_threshold: u32,
+    pub probe_interval_secs: u64,
+    pub probe_args: Vec<String>,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            probe_interval_secs: 30,
+            probe_args: vec!["--version".to_string()],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    pub reauth_args: Vec<String>,
+    pub expiry_patterns: Vec<String>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            reauth_args: Vec::new(),
+            expiry_patterns: DEFAULT_EXPIRY_PATTERNS
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedactionConfig {
+    pub metadata_properties: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SandboxConfig {
+    pub enabled: bool,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub working_dir: Option<PathBuf>,
+    pub cpu_time_secs: Option<u64>,
+    pub memory_bytes: Option<u64>,
+    pub file_size_bytes: Option<u64>,
+    pub offline_tools: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SharedStoreConfig {
+    pub redis_url: Option<String>,
+    pub key_prefix: String,
+    pub ttl_secs: u64,
+}
+
+impl Default for SharedStoreConfig {
+    fn default() -> Self {
+        Self {
+            redis_url: None,
+            key_prefix: "pcli2-mcp:".to_string(),
+            ttl_secs: 3600,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    pub token: Option<String>,
+    pub expose_tool: bool,
+    pub credential_commands: Vec<Vec<String>>,
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        let command = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect();
+        Self {
+            token: None,
+            expose_tool: false,
+            credential_commands: vec![
+                command(&["config", "environment", "use", "-n", "{environment}"]),
+                command(&["tenant", "use", "--name", "{tenant}"]),
+                command(&[
+                    "auth",
+                    "login",
+                    "--client-id",
+                    "{client_id}",
+                    // Attached so generated secrets may start with '-'.
+                    "--client-secret={client_secret}",
+                ]),
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SnapshotConfig {
+    /// Where folder snapshots are stored; defaults to `~/.pcli2-mcp/snapshots`.
+    pub dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BannerConfig {
+    /// Print a banner at startup.
+    pub enabled: bool,
+    /// Replaces the ASCII art; may span several lines.
+    pub text: Option<String>,
+    /// One plain line instead of the art.
+    pub plain: bool,
+}
+
+impl Default for BannerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            text: None,
+            plain: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WatchConfig {
+    /// Folder paths polled for changes; watching is off when empty.
+    pub folders: Vec<String>,
+    pub tenant: Option<String>,
+    pub interval_secs: u64,
+    /// HTTP endpoints that receive a POST for every folder change.
+    pub webhooks: Vec<String>,
+    pub webhook_headers: Vec<String>,
+}
+
+/// Folders whose assets are kept in a local full-text index that
+/// `pcli2_index_search` answers from.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IndexConfig {
+    /// Folder paths indexed; indexing and the tool are off when empty.
+    pub folders: Vec<String>,
+    pub tenant: Option<String>,
+    /// How often each folder's assets and metadata are listed again.
+    pub interval_secs: u64,
+    /// Directory the index is kept in, so it outlives restarts; in memory
+    /// when unset.
+    pub path: Option<PathBuf>,
+}
+
+impl IndexConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.folders.is_empty()
+    }
+}
+
+/// Where the monthly usage reports are written.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UsageConfig {
+    /// Directory of the `usage-YYYY-MM.json` and `.csv` reports; usage is
+    /// only kept in memory when unset.
+    pub report_dir: Option<PathBuf>,
+    /// How often the reports of the months with new calls are rewritten.
+    pub save_interval_secs: u64,
+}
+
+/// `pcli2` runs made ahead of tool calls, so calls find a valid token and
+/// primed `pcli2` caches instead of paying for them.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WarmConfig {
+    /// Run `auth.reauth_args` first, so calls reuse a fresh token.
+    pub login: bool,
+    /// `pcli2` argument lists, e.g. `["folder", "list", "--format", "json"]`;
+    /// `${NAME}` is expanded as in `auth.reauth_args`.
+    pub commands: Vec<Vec<String>>,
+    /// Repeat the runs on this interval; they run once at startup when unset.
+    pub interval_secs: Option<u64>,
+}
+
+/// Server-side cache of folder listings, tenant lists, and resolved folder
+/// paths.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// How long a cached result is served; nothing is cached when unset.
+    pub max_age_secs: Option<u64>,
+    /// Keep the cache on disk so it survives restarts.
+    pub persist: bool,
+    /// Where the persisted cache lives; defaults to `~/.pcli2-mcp/cache`.
+    pub dir: Option<PathBuf>,
+    /// How long clients may reuse results of the other read-only tools,
+    /// as told by `_meta.cacheable_for_seconds`; `0` when unset.
+    pub client_max_age_secs: Option<u64>,
+}
+
+/// How a tool is treated while the `pcli2` backend is degraded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolPriority {
+    /// Refused while the backend is degraded.
+    Low,
+    #[default]
+    Normal,
+}
+
+/// Backend health over a sliding window of recent `pcli2` runs, and which
+/// tools are shed while it is degraded.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthConfig {
+    /// Length of the window of runs, in seconds.
+    pub window_secs: u64,
+    /// Runs the window needs before the backend can count as degraded.
+    pub min_samples: usize,
+    /// Share of failed runs (0 to 1) at which the backend is degraded.
+    pub max_error_rate: f64,
+    /// 95th percentile run time at which the backend is degraded; latency
+    /// is not judged when unset.
+    pub max_p95_ms: Option<u64>,
+    pub default_priority: ToolPriority,
+    pub priorities: HashMap<String, ToolPriority>,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            window_secs: 300,
+            min_samples: 10,
+            max_error_rate: 0.5,
+            max_p95_ms: None,
+            default_priority: ToolPriority::Normal,
+            priorities: HashMap::new(),
+        }
+    }
+}
+
+impl HealthConfig {
+    pub fn window(&self) -> Duration {
+        Duration::from_secs(self.window_secs)
+    }
+
+    pub fn priority(&self, tool: &str) -> ToolPriority {
+        self.priorities
+            .get(tool)
+            .copied()
+            .unwrap_or(self.default_priority)
+    }
+
+    fn validate(&self, tool_names: &[&str]) -> Result<()> {
+        if self.window_secs == 0 {
+            return Err(anyhow!(
+                "Config 'health.window_secs' must be greater than 0"
+            ));
+        }
+        if self.min_samples == 0 {
+            return Err(anyhow!(
+                "Config 'health.min_samples' must be greater than 0"
+            ));
+        }
+        if !(self.max_error_rate > 0.0 && self.max_error_rate <= 1.0) {
+            return Err(anyhow!(
+                "Config 'health.max_error_rate' must be greater than 0 and at most 1"
+            ));
+        }
+        if self.max_p95_ms == Some(0) {
+            return Err(anyhow!("Config 'health.max_p95_ms' must be greater than 0"));
+        }
+        if let Some(name) = self
+            .priorities
+            .keys()
+            .find(|name| !tool_names.contains(&name.as_str()))
+        {
+            return Err(anyhow!(
+                "Config 'health.priorities' refers to unknown tool '{}'",
+                name
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// `pcli2` command behind `pcli2_image_visual_match`, which is offered only
+/// when `args` is set.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImageMatchConfig {
+    /// Arguments that visually match an image file, with `{file}` where the
+    /// image's path goes, e.g. `["asset", "visual-match", "--file", "{file}"]`.
+    pub args: Vec<String>,
+    /// Appended with `{tenant}` replaced when the call names a tenant.
+    pub tenant_args: Vec<String>,
+    /// Largest decoded image accepted.
+    pub max_image_bytes: usize,
+}
+
+impl Default for ImageMatchConfig {
+    fn default() -> Self {
+        Self {
+            args: Vec::new(),
+            tenant_args: vec!["-t".to_string(), "{tenant}".to_string()],
+            max_image_bytes: 10 * 1024 * 1024,
+        }
+    }
+}
+
+impl ImageMatchConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.args.is_empty()
+    }
+
+    fn validate(&self) -> Result<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        if !self.args.iter().any(|arg| arg.contains("{file}")) {
+            return Err(anyhow!(
+                "Config 'image_match.args' must contain the '{{file}}' placeholder"
+            ));
+        }
+        if !self.tenant_args.is_empty()
+            && !self.tenant_args.iter().any(|arg| arg.contains("{tenant}"))
+        {
+            return Err(anyhow!(
+                "Config 'image_match.tenant_args' must contain the '{{tenant}}' placeholder"
+            ));
+        }
+        if self.max_image_bytes == 0 {
+            return Err(anyhow!(
+                "Config 'image_match.max_image_bytes' must be greater than 0"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Conversion options `pcli2_asset_export` passes to `pcli2 asset download`.
+/// Stock `pcli2` downloads the original file only, so `target_format` and
+/// `units` are refused until the matching arguments are set.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExportConfig {
+    /// Appended with `{format}` replaced, e.g. `["--format", "{format}"]`.
+    pub format_args: Vec<String>,
+    /// Appended with `{units}` replaced, e.g. `["--units", "{units}"]`.
+    pub units_args: Vec<String>,
+    /// Largest file kept as a resource.
+    pub max_bytes: usize,
+    /// Downloads tried when one fails partway, leaving part of the file.
+    pub download_attempts: u32,
+    /// Appended to a retried download to continue the partial file, e.g.
+    /// `["--resume"]`. Stock `pcli2` cannot resume, so by default the
+    /// partial file is removed and the download starts over.
+    pub resume_args: Vec<String>,
+}
+
+/// Most downloads `export.download_attempts` may ask for.
+pub const MAX_DOWNLOAD_ATTEMPTS: u32 = 10;
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            format_args: Vec::new(),
+            units_args: Vec::new(),
+            max_bytes: 64 * 1024 * 1024,
+            download_attempts: 3,
+            resume_args: Vec::new(),
+        }
+    }
+}
+
+impl ExportConfig {
+    fn validate(&self) -> Result<()> {
+        for (key, args, placeholder) in [
+            ("format_args", &self.format_args, "{format}"),
+            ("units_args", &self.units_args, "{units}"),
+        ] {
+            if !args.is_empty() && !args.iter().any(|arg| arg.contains(placeholder)) {
+                return Err(anyhow!(
+                    "Config 'export.{}' must contain the '{}' placeholder",
+                    key,
+                    placeholder
+                ));
+            }
+        }
+        if self.max_bytes == 0 {
+            return Err(anyhow!("Config 'export.max_bytes' must be greater than 0"));
+        }
+        if !(1..=MAX_DOWNLOAD_ATTEMPTS).contains(&self.download_attempts) {
+            return Err(anyhow!(
+                "Config 'export.download_attempts' must be between 1 and {}",
+                MAX_DOWNLOAD_ATTEMPTS
+            ));
+        }
+        if self.resume_args.iter().any(String::is_empty) {
+            return Err(anyhow!(
+                "Config 'export.resume_args' must not contain empty arguments"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Tools whose calls wait for an operator's approval (`/admin/approvals`)
+/// before they run.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApprovalConfig {
+    pub tools: Vec<String>,
+    /// How long a call waits for a decision before it expires, in seconds.
+    pub expire_secs: u64,
+}
+
+impl Default for ApprovalConfig {
+    fn default() -> Self {
+        Self {
+            tools: Vec::new(),
+            expire_secs: 3600,
+        }
+    }
+}
+
+/// A working directory of its own for each tool call's local `pcli2` runs.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WorkspaceConfig {
+    pub enabled: bool,
+    /// Where the directories are made; by default the sandbox's working
+    /// directory when the sandbox is on, else the temp directory.
+    pub dir: Option<PathBuf>,
+    /// Leave the directory of a failed call behind for debugging.
+    pub keep_on_failure: bool,
+}
+
+impl Default for WorkspaceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dir: None,
+            keep_on_failure: false,
+        }
+    }
+}
+
+/// Garbage collection of stored results and of the files tool calls leave
+/// behind: kept working directories and interrupted downloads.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArtifactsConfig {
+    /// Remove stored results and leftover files older than this.
+    pub max_age_secs: Option<u64>,
+    /// Remove the oldest stored results while together they are larger.
+    pub max_total_bytes: Option<usize>,
+    /// Keep results a client read or was sent within this many seconds,
+    /// whatever their age and the total size.
+    pub pin_referenced_secs: Option<u64>,
+    /// How often the collector runs while a limit is set.
+    pub sweep_interval_secs: u64,
+    /// Offer the `pcli2_artifacts_list` and `pcli2_artifacts_purge` tools.
+    pub expose_tools: bool,
+    /// Keep the output and downloaded files of successful calls to these
+    /// tools as `job://{id}/...` resources, until `max_age_secs` removes
+    /// them.
+    pub job_tools: Vec<String>,
+}
+
+impl Default for ArtifactsConfig {
+    fn default() -> Self {
+        Self {
+            max_age_secs: None,
+            max_total_bytes: None,
+            pin_referenced_secs: None,
+            sweep_interval_secs: 300,
+            expose_tools: false,
+            job_tools: Vec::new(),
+        }
+    }
+}
+
+/// Guidance sent as `instructions` in the `initialize` result.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InstructionsConfig {
+    pub enabled: bool,
+    /// How long `initialize` waits for `pcli2 tenant list` to name the
+    /// tenants; `0` skips the probe.
+    pub probe_timeout_secs: u64,
+    /// Operator notes appended to the generated guidance.
+    pub text: Option<String>,
+}
+
+impl Default for InstructionsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            probe_timeout_secs: 3,
+            text: None,
+        }
+    }
+}
+
+/// How the server introduces itself in `serverInfo`, so instances of a
+/// fleet can be told apart; unset fields keep the built-in identity.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdentityConfig {
+    pub name: Option<String>,
+    /// Display name for client UIs, such as "Acme Physna Gateway (prod)".
+    pub title: Option<String>,
+    pub version: Option<String>,
+}
+
+impl IdentityConfig {
+    fn validate(&self) -> Result<()> {
+        for (key, value) in [
+            ("name", &self.name),
+            ("title", &self.title),
+            ("version", &self.version),
+        ] {
+            match value.as_deref().map(str::trim) {
+                Some("") => {
+                    return Err(anyhow!("Config 'identity.{}' must not be empty", key));
+                }
+                Some(text) if text.chars().count() > 200 => {
+                    return Err(anyhow!(
+                        "Config 'identity.{}' must be at most 200 characters",
+                        key
+                    ));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Optional protocol features advertised in the `initialize` result's
+/// `capabilities`; a feature turned off is neither advertised nor served.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CapabilitiesConfig {
+    /// `resources/subscribe` and `notifications/resources/updated`.
+    pub resource_subscriptions: bool,
+    /// `notifications/tools/list_changed` after a reload or when the
+    /// installed `pcli2` turns out not to support a tool.
+    pub tools_list_changed: bool,
+}
+
+impl Default for CapabilitiesConfig {
+    fn default() -> Self {
+        Self {
+            resource_subscriptions: true,
+            tools_list_changed: true,
+        }
+    }
+}
+
+/// Which sessions get an experimental feature. `POST /admin/features/{name}`
+/// replaces it at runtime.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeatureConfig {
+    /// Off is a kill switch: no session gets the feature.
+    pub enabled: bool,
+    /// Share of sessions, 0 to 100, that get the feature. Sessions are
+    /// picked by a hash of their id, so each keeps its answer.
+    pub percent: u8,
+    /// `Mcp-Session-Id`s that get the feature whatever `percent` says.
+    pub sessions: Vec<String>,
+}
+
+impl Default for FeatureConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            percent: 100,
+            sessions: Vec::new(),
+        }
+    }
+}
+
+impl FeatureConfig {
+    pub fn validate(&self, name: &str) -> Result<()> {
+        if !FEATURES.contains(&name) {
+            return Err(anyhow!(
+                "Config 'features.{}' is not a feature; expected one of {}",
+                name,
+                FEATURES.join(", ")
+            ));
+        }
+        if self.percent > 100 {
+            return Err(anyhow!(
+                "Config 'features.{}.percent' must be at most 100",
+                name
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Arguments filled in for calls whose `tenant` names this tenant and that
+/// leave them out, so agents need not know each tenant's size or noise.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TenantDefaults {
+    /// `threshold` of the match tools.
+    pub threshold: Option<f64>,
+    /// `format` of the tools that offer it.
+    pub format: Option<String>,
+    /// `concurrent` of the fan-out tools and `split_concurrency` of split
+    /// folder matches.
+    pub concurrency: Option<u64>,
+    /// Subdirectory of the workspace root the tenant's calls work and
+    /// download in, so its files stay apart from other tenants'.
+    pub artifact_dir: Option<PathBuf>,
+}
+
+impl TenantDefaults {
+    fn validate(&self, tenant: &str) -> Result<()> {
+        if let Some(threshold) = self.threshold
+            && !(0.0..=100.0).contains(&threshold)
+        {
+            return Err(anyhow!(
+                "Config 'tenants.{}.threshold' must be between 0 and 100",
+                tenant
+            ));
+        }
+        if self.format.as_deref().is_some_and(str::is_empty) {
+            return Err(anyhow!("Config 'tenants.{}.format' is empty", tenant));
+        }
+        if let Some(concurrency) = self.concurrency
+            && !(1..=10).contains(&concurrency)
+        {
+            return Err(anyhow!(
+                "Config 'tenants.{}.concurrency' must be between 1 and 10",
+                tenant
+            ));
+        }
+        if let Some(dir) = &self.artifact_dir
+            && (dir.as_os_str().is_empty()
+                || !dir
+                    .components()
+                    .all(|part| matches!(part, std::path::Component::Normal(_))))
+        {
+            return Err(anyhow!(
+                "Config 'tenants.{}.artifact_dir' must be a relative path inside the workspace root",
+                tenant
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl InstructionsConfig {
+    fn validate(&self) -> Result<()> {
+        if self.probe_timeout_secs > 30 {
+            return Err(anyhow!(
+                "Config 'instructions.probe_timeout_secs' must be at most 30"
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl WorkspaceConfig {
+    fn validate(&self) -> Result<()> {
+        if let Some(dir) = &self.dir
+            && !dir.is_dir()
+        {
+            return Err(anyhow!(
+                "Config 'workspace.dir' {} is not a directory",
+                dir.display()
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl ArtifactsConfig {
+    /// Whether the collector has anything to enforce.
+    pub fn collects(&self) -> bool {
+        self.max_age_secs.is_some() || self.max_total_bytes.is_some()
+    }
+
+    /// Whether calls to `tool` are kept as jobs.
+    pub fn keeps_jobs(&self, tool: &str) -> bool {
+        self.job_tools.iter().any(|name| name == tool)
+    }
+
+    fn validate(&self, tool_names: &[&str]) -> Result<()> {
+        if let Some(name) = self
+            .job_tools
+            .iter()
+            .find(|name| !tool_names.contains(&name.as_str()))
+        {
+            return Err(anyhow!(
+                "Config 'artifacts.job_tools' refers to unknown tool '{}'",
+                name
+            ));
+        }
+        for (key, value) in [
+            ("max_age_secs", self.max_age_secs),
+            (
+                "max_total_bytes",
+                self.max_total_bytes.map(|bytes| bytes as u64),
+            ),
+            ("sweep_interval_secs", Some(self.sweep_interval_secs)),
+        ] {
+            if value == Some(0) {
+                return Err(anyhow!("Config 'artifacts.{}' must be greater than 0", key));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ApprovalConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.tools.is_empty()
+    }
+
+    pub fn requires(&self, tool: &str) -> bool {
+        self.tools.iter().any(|name| name == tool)
+    }
+
+    fn validate(&self, tool_names: &[&str]) -> Result<()> {
+        if let Some(name) = self
+            .tools
+            .iter()
+            .find(|name| !tool_names.contains(&name.as_str()))
+        {
+            return Err(anyhow!(
+                "Config 'approval.tools' refers to unknown tool '{}'",
+                name
+            ));
+        }
+        if self.expire_secs == 0 {
+            return Err(anyhow!(
+                "Config 'approval.expire_secs' must be greater than 0"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// How clients talk to an extra listener.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListenerTransport {
+    /// Streamable HTTP on `host:port`: `POST /mcp`, and the `GET /mcp`
+    /// notification stream unless `stream` is off.
+    Http,
+    /// One JSON-RPC message per WebSocket text frame on `GET /mcp`.
+    Websocket,
+    /// Streamable HTTP on the Unix socket at `path`.
+    Unix,
+}
+
+impl ListenerTransport {
+    pub fn name(self) -> &'static str {
+        match self {
+            ListenerTransport::Http => "http",
+            ListenerTransport::Websocket => "websocket",
+            ListenerTransport::Unix => "unix",
+        }
+    }
+}
+
+/// An extra listener of `serve`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ListenerConfig {
+    pub transport: ListenerTransport,
+    /// Defaults to `serve --host`.
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    /// Socket file of the `unix` transport.
+    pub path: Option<PathBuf>,
+    /// Serve the `GET /mcp` notification stream (`http` and `unix`).
+    pub stream: bool,
+    /// Serve `/health`, `/health/ready`, and the admin endpoints as well.
+    pub admin: bool,
+}
+
+impl Default for ListenerConfig {
+    fn default() -> Self {
+        Self {
+            transport: ListenerTransport::Http,
+            host: None,
+            port: None,
+            path: None,
+            stream: true,
+            admin: false,
+        }
+    }
+}
+
+fn validate_listeners(listeners: &[ListenerConfig]) -> Result<()> {
+    for (index, listener) in listeners.iter().enumerate() {
+        let name = format!("listeners[{}]", index);
+        match listener.transport {
+            ListenerTransport::Http | ListenerTransport::Websocket => {
+                if listener.port.is_none() {
+                    return Err(anyhow!(
+                        "Config '{}' needs a 'port' for the {} transport",
+                        name,
+                        listener.transport.name()
+                    ));
+                }
+                if listener.path.is_some() {
+                    return Err(anyhow!(
+                        "Config '{}.path' only applies to the unix transport",
+                        name
+                    ));
+                }
+            }
+            ListenerTransport::Unix => {
+                if listener.path.is_none() {
+                    return Err(anyhow!(
+                        "Config '{}' needs a 'path' for the unix transport",
+                        name
+                    ));
+                }
+                if listener.host.is_some() || listener.port.is_some() {
+                    return Err(anyhow!(
+                        "Config '{}' of the unix transport takes a 'path', not 'host' or 'port'",
+                        name
+                    ));
+                }
+            }
+        }
+        if let Some(other) = listeners[..index].iter().position(|other| {
+            (other.path.is_some() && other.path == listener.path)
+                || (other.port.is_some()
+                    && other.port != Some(0)
+                    && other.port == listener.port
+                    && other.host == listener.host)
+        }) {
+            return Err(anyhow!(
+                "Config '{}' listens on the same address as 'listeners[{}]'",
+                name,
+                other
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Runs `pcli2` on another host over SSH instead of as a local subprocess.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SshConfig {
+    /// Remote host; `pcli2` runs locally when unset.
+    pub host: Option<String>,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    pub identity_file: Option<PathBuf>,
+    /// `pcli2` on the remote host.
+    pub pcli2: String,
+    /// Extra `ssh` arguments, e.g. `["-o", "ConnectTimeout=10"]`.
+    pub options: Vec<String>,
+    /// Local `ssh` client.
+    pub program: String,
+}
+
+impl Default for SshConfig {
+    fn default() -> Self {
+        Self {
+            host: None,
+            user: None,
+            port: None,
+            identity_file: None,
+            pcli2: "pcli2".to_string(),
+            options: Vec::new(),
+            program: "ssh".to_string(),
+        }
+    }
+}
+
+/// Runs `pcli2` inside a running container with `docker exec`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DockerConfig {
+    /// Container name or id; `pcli2` runs locally when unset.
+    pub container: Option<String>,
+    pub user: Option<String>,
+    /// Working directory inside the container for every run.
+    pub working_dir: Option<String>,
+    /// `pcli2` inside the container.
+    pub pcli2: String,
+    /// Bind mounts shared with the container; arguments under a `host`
+    /// path are rewritten to the `container` path.
+    pub mounts: Vec<PathMapping>,
+    /// Local `docker` client.
+    pub program: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PathMapping {
+    pub host: PathBuf,
+    pub container: String,
+}
+
+impl Default for DockerConfig {
+    fn default() -> Self {
+        Self {
+            container: None,
+            user: None,
+            working_dir: None,
+            pcli2: "pcli2".to_string(),
+            mounts: Vec::new(),
+            program: "docker".to_string(),
+        }
+    }
+}
+
+/// Named `pcli2` builds that a call selects with its `cli_version` argument.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CliVersionsConfig {
+    /// Version name to `pcli2` executable, run by the configured backend.
+    pub binaries: BTreeMap<String, String>,
+    /// Version used when a call names none and its tool has no default.
+    pub default: Option<String>,
+    /// Per-tool default version.
+    pub tools: HashMap<String, String>,
+}
+
+impl CliVersionsConfig {
+    /// The version name and executable for a call to `tool`, or `None` to
+    /// run the usual `pcli2`.
+    pub fn select(
+        &self,
+        tool: &str,
+        requested: Option<&str>,
+    ) -> Result<Option<(String, String)>, String> {
+        let Some(name) = requested
+            .or(self.tools.get(tool).map(String::as_str))
+            .or(self.default.as_deref())
+        else {
+            return Ok(None);
+        };
+        match self.binaries.get(name) {
+            Some(path) => Ok(Some((name.to_string(), path.clone()))),
+            None => Err(format!("Unknown cli_version '{}'", name)),
+        }
+    }
+
+    fn validate(&self, tool_names: &[&str]) -> Result<()> {
+        if let Some((name, _)) = self
+            .binaries
+            .iter()
+            .find(|(name, path)| name.is_empty() || path.trim().is_empty())
+        {
+            return Err(anyhow!(
+                "Config 'cli_versions.binaries' entry '{}' needs a name and a path",
+                name
+            ));
+        }
+        for (key, name) in std::iter::once(("default", &self.default))
+            .filter_map(|(key, name)| Some((key, name.as_ref()?)))
+            .chain(self.tools.iter().map(|(tool, name)| (tool.as_str(), name)))
+        {
+            if key != "default" && !tool_names.contains(&key) {
+                return Err(anyhow!(
+                    "Config 'cli_versions.tools' refers to unknown tool '{}'",
+                    key
+                ));
+            }
+            if !self.binaries.contains_key(name) {
+                return Err(anyhow!(
+                    "Config 'cli_versions' default for '{}' names unknown version '{}'",
+                    key,
+                    name
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Extra `pcli2_plan` rules, tried before the built-in ones.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlanConfig {
+    pub rules: Vec<PlanRule>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlanRule {
+    /// Words or phrases; the rule with the most of them in the request wins.
+    pub keywords: Vec<String>,
+    pub description: String,
+    pub steps: Vec<PlanStep>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlanStep {
+    pub tool: String,
+    /// Request details filled in as arguments (see `PLAN_INPUTS`); a
+    /// trailing `?` marks one as optional.
+    #[serde(default)]
+    pub inputs: Vec<String>,
+    /// Fixed arguments.
+    #[serde(default)]
+    pub arguments: Map<String, Value>,
+}
+
+impl PlanConfig {
+    fn validate(&self, tool_names: &[&str]) -> Result<()> {
+        for (index, rule) in self.rules.iter().enumerate() {
+            if rule
+                .keywords
+                .iter()
+                .all(|keyword| keyword.trim().is_empty())
+            {
+                return Err(anyhow!("Config 'plan.rules[{}]' needs keywords", index));
+            }
+            if rule.steps.is_empty() {
+                return Err(anyhow!("Config 'plan.rules[{}]' needs steps", index));
+            }
+            for step in &rule.steps {
+                if !tool_names.contains(&step.tool.as_str()) {
+                    return Err(anyhow!(
+                        "Config 'plan.rules[{}]' refers to unknown tool '{}'",
+                        index,
+                        step.tool
+                    ));
+                }
+                if let Some(input) = step
+                    .inputs
+                    .iter()
+                    .find(|input| !PLAN_INPUTS.contains(&input.trim_end_matches('?')))
+                {
+                    return Err(anyhow!(
+                        "Config 'plan.rules[{}]' has unknown input '{}'; expected one of {}",
+                        index,
+                        input,
+                        PLAN_INPUTS.join(", ")
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A key accepted on `/mcp`; once any is configured, requests must carry one.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApiKeyConfig {
+    /// Sent as `Authorization: Bearer <key>`; may use `${VAR}`.
+    pub key: String,
+    /// What usage accounting calls the key; a digest of the key when unset.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Tenant every tool call made with this key is confined to.
+    #[serde(default)]
+    pub tenant: Option<String>,
+    /// Name of the `policies` entry that limits what this key may do.
+    #[serde(default)]
+    pub policy: Option<String>,
+}
+
+/// What the API keys that name a policy may do: which tools they see and
+/// call, in which tenants, whether they may change assets, and how often.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PolicyConfig {
+    /// Tools the keys may use; empty allows every tool.
+    pub tools: Vec<String>,
+    /// Tenants unbound keys may work in; empty allows every tenant.
+    pub tenants: Vec<String>,
+    /// Allow tools that change assets, credentials, or pcli2's tenant.
+    pub destructive: bool,
+    /// Per-session quotas on top of the server-wide `quotas`.
+    pub quotas: HashMap<String, QuotaRule>,
+}
+
+impl Default for PolicyConfig {
+    fn default() -> Self {
+        Self {
+            tools: Vec::new(),
+            tenants: Vec::new(),
+            destructive: true,
+            quotas: HashMap::new(),
+        }
+    }
+}
+
+/// Route groups whose authentication `access.routes` can set.
+pub const ACCESS_ROUTES: [&str; 3] = ["mcp", "admin", "health"];
+
+/// A way for a request to authenticate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthScheme {
+    /// Every request is admitted.
+    None,
+    /// A bearer key from `api_keys`.
+    ApiKey,
+    /// The bearer `admin.token`.
+    AdminToken,
+    /// A bearer JWT signed with `access.oauth.secret` (HS256).
+    Oauth,
+    /// A client certificate verified by the TLS proxy in front of the server.
+    Mtls,
+}
+
+impl AuthScheme {
+    pub fn name(self) -> &'static str {
+        match self {
+            AuthScheme::None => "none",
+            AuthScheme::ApiKey => "api_key",
+            AuthScheme::AdminToken => "admin_token",
+            AuthScheme::Oauth => "oauth",
+            AuthScheme::Mtls => "mtls",
+        }
+    }
+}
+
+/// Authentication per route group.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AccessConfig {
+    /// Schemes tried in order for each route group; the first that admits a
+    /// request wins. Groups left out keep their defaults: `mcp` takes
+    /// `api_key` (open while no keys are configured), `admin` takes
+    /// `admin_token`, and `health` is open.
+    pub routes: BTreeMap<String, Vec<AuthScheme>>,
+    pub oauth: Option<OauthConfig>,
+    pub mtls: Option<MtlsConfig>,
+    pub replay: Option<ReplayConfig>,
+}
+
+/// Bearer JWTs issued by an OAuth server that shares an HS256 secret.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OauthConfig {
+    /// HMAC secret the tokens are signed with; may use `${VAR}`.
+    pub secret: String,
+    /// Required `iss` claim.
+    #[serde(default)]
+    pub issuer: Option<String>,
+    /// Required `aud` claim (or one of its entries).
+    #[serde(default)]
+    pub audience: Option<String>,
+    /// Claim naming the tenant the token's calls are confined to.
+    #[serde(default)]
+    pub tenant_claim: Option<String>,
+    /// Clock skew allowed on `exp` and `nbf`, in seconds.
+    #[serde(default = "default_oauth_leeway_secs")]
+    pub leeway_secs: u64,
+}
+
+fn default_oauth_leeway_secs() -> u64 {
+    60
+}
+
+/// Client certificates checked by a TLS-terminating proxy, which reports
+/// the outcome in request headers. Only safe when every request comes
+/// through that proxy.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MtlsConfig {
+    /// Header that is `SUCCESS` for a verified certificate.
+    pub verify_header: String,
+    /// Header carrying the certificate's subject.
+    pub subject_header: String,
+    /// Subjects admitted; any verified certificate when empty.
+    pub allowed_subjects: Vec<String>,
+}
+
+/// Replay protection of `/mcp`: each request carries a nonce it alone uses
+/// and the time it was made, so a captured request is refused when sent
+/// again. Only `signed` mode protects against replay: the request is also
+/// signed with its API key, which then never travels. Without it the
+/// stamp is not bound to the body or the key, and whoever captured a
+/// request can resend it with a fresh nonce.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReplayConfig {
+    pub nonce_header: String,
+    /// Header carrying the request's time in Unix seconds.
+    pub timestamp_header: String,
+    /// How far a request's time may be from the server's clock; its nonce
+    /// is remembered for as long.
+    pub max_skew_secs: u64,
+    /// Find the API key by the HMAC-SHA256 of `<timestamp>\n<nonce>\n<body>`
+    /// in `signature_header` instead of a bearer key.
+    pub signed: bool,
+    pub signature_header: String,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        Self {
+            nonce_header: "x-request-nonce".to_string(),
+            timestamp_header: "x-request-timestamp".to_string(),
+            max_skew_secs: 300,
+            signed: false,
+            signature_header: "x-request-signature".to_string(),
+        }
+    }
+}
+
+impl Default for MtlsConfig {
+    fn default() -> Self {
+        Self {
+            verify_header: "x-ssl-client-verify".to_string(),
+            subject_header: "x-ssl-client-s-dn".to_string(),
+            allowed_subjects: Vec::new(),
+        }
+    }
+}
+
+impl AccessConfig {
+    /// The schemes of `route`, in the order they are tried.
+    pub fn chain(&self, route: &str) -> &[AuthScheme] {
+        if let Some(chain) = self.routes.get(route) {
+            return chain;
+        }
+        match route {
+            "mcp" => &[AuthScheme::ApiKey],
+            "admin" => &[AuthScheme::AdminToken],
+            _ => &[AuthScheme::None],
+        }
+    }
+
+    fn validate(&self, has_api_keys: bool, has_admin_token: bool) -> Result<()> {
+        for (route, chain) in &self.routes {
+            if !ACCESS_ROUTES.contains(&route.as_str()) {
+                return Err(anyhow!(
+                    "Config 'access.routes' refers to unknown route '{}'; expected one of {}",
+                    route,
+                    ACCESS_ROUTES.join(", ")
+                ));
+            }
+            if chain.is_empty() {
+                return Err(anyhow!(
+                    "Config 'access.routes.{}' must list at least one scheme",
+                    route
+                ));
+            }
+            if chain.len() > 1 && chain.contains(&AuthScheme::None) {
+                return Err(anyhow!(
+                    "Config 'access.routes.{}' admits everyone with 'none'; list it alone",
+                    route
+                ));
+            }
+            // A listed scheme with nothing to check against would leave the
+            // route open.
+            for (scheme, configured, section) in [
+                (AuthScheme::ApiKey, has_api_keys, "api_keys"),
+                (AuthScheme::AdminToken, has_admin_token, "admin.token"),
+                (AuthScheme::Oauth, self.oauth.is_some(), "access.oauth"),
+                (AuthScheme::Mtls, self.mtls.is_some(), "access.mtls"),
+            ] {
+                if chain.contains(&scheme) && !configured {
+                    return Err(anyhow!(
+                        "Config 'access.routes.{}' uses '{}' but '{}' is not set",
+                        route,
+                        scheme.name(),
+                        section
+                    ));
+                }
+            }
+        }
+        if let Some(oauth) = &self.oauth {
+            let secret = expand_args(std::slice::from_ref(&oauth.secret))
+                .map_err(|err| anyhow!("Config 'access.oauth.secret': {}", err))?
+                .concat();
+            if secret.is_empty() {
+                return Err(anyhow!("Config 'access.oauth.secret' must not be empty"));
+            }
+            if oauth.tenant_claim.as_deref() == Some("") {
+                return Err(anyhow!(
+                    "Config 'access.oauth.tenant_claim' must not be empty"
+                ));
+            }
+        }
+        if let Some(replay) = &self.replay {
+            for (field, header) in [
+                ("nonce_header", &replay.nonce_header),
+                ("timestamp_header", &replay.timestamp_header),
+                ("signature_header", &replay.signature_header),
+            ] {
+                if axum::http::HeaderName::try_from(header.as_str()).is_err() {
+                    return Err(anyhow!(
+                        "Config 'access.replay.{}' is not a valid header name",
+                        field
+                    ));
+                }
+            }
+            if replay.max_skew_secs == 0 {
+                return Err(anyhow!(
+                    "Config 'access.replay.max_skew_secs' must be greater than 0"
+                ));
+            }
+            if replay.signed && !(has_api_keys && self.chain("mcp").contains(&AuthScheme::ApiKey)) {
+                return Err(anyhow!(
+                    "Config 'access.replay.signed' needs 'api_keys' and the 'api_key' scheme on the mcp route"
+                ));
+            }
+        }
+        if let Some(mtls) = &self.mtls
+            && (mtls.verify_header.is_empty() || mtls.subject_header.is_empty())
+        {
+            return Err(anyhow!(
+                "Config 'access.mtls' needs a 'verify_header' and a 'subject_header'"
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MultiTenantSearchConfig {
+    /// Tenants searched by `pcli2_multi_tenant_search` when a call names none.
+    pub tenants: Vec<String>,
+}
+
+impl MultiTenantSearchConfig {
+    fn validate(&self) -> Result<()> {
+        let mut seen = HashSet::new();
+        for tenant in &self.tenants {
+            if tenant.trim().is_empty() {
+                return Err(anyhow!(
+                    "Config 'multi_tenant_search.tenants' must not contain empty names"
+                ));
+            }
+            if !seen.insert(tenant.as_str()) {
+                return Err(anyhow!(
+                    "Config 'multi_tenant_search.tenants' lists '{}' more than once",
+                    tenant
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for IndexConfig {
+    fn default() -> Self {
+        Self {
+            folders: Vec::new(),
+            tenant: None,
+            interval_secs: 900,
+            path: None,
+        }
+    }
+}
+
+impl Default for UsageConfig {
+    fn default() -> Self {
+        Self {
+            report_dir: None,
+            save_interval_secs: 60,
+        }
+    }
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            folders: Vec::new(),
+            tenant: None,
+            interval_secs: 300,
+            webhooks: Vec::new(),
+            webhook_headers: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QuotaRule {
+    #[serde(default)]
+    pub tools: Vec<String>,
+    pub max_calls: u32,
+    #[serde(default)]
+    pub window_secs: Option<u64>,
+}
+
+/// Checks the quota configured at `label`.
+fn validate_quota(label: &str, rule: &QuotaRule, tool_names: &[&str]) -> Result<()> {
+    if let Some(tool) = rule
+        .tools
+        .iter()
+        .find(|tool| !tool_names.contains(&tool.as_str()))
+    {
+        return Err(anyhow!(
+            "Config '{}' refers to unknown tool '{}'",
+            label,
+            tool
+        ));
+    }
+    if rule.max_calls == 0 || rule.window_secs == Some(0) {
+        return Err(anyhow!(
+            "Config '{}' must have a non-zero max_calls and window_secs",
+            label
+        ));
+    }
+    Ok(())
+}
+
+impl QuotaRule {
+    pub fn window(&self) -> Option<Duration> {
+        self.window_secs.map(Duration::from_secs)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecutionPolicy {
+    pub timeout: Duration,
+    pub retries: u32,
+    pub concurrency: Option<usize>,
+}
+
+impl ServerConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let raw = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        serde_json::from_str(&raw)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))
+    }
+
+    pub fn validate<'a>(&self, tool_names: impl IntoIterator<Item = &'a str>) -> Result<()> {
+        let tool_names: Vec<&str> = tool_names.into_iter().collect();
+        if let Some(level) = &self.log_level
+            && let Err(err) = EnvFilter::try_new(level)
+        {
+            return Err(anyhow!("Config 'log_level' is invalid: {}", err));
+        }
+        for name in self.tools.aliases.keys() {
+            if !tool_names.contains(&name.as_str()) {
+                return Err(anyhow!(
+                    "Config 'tools.aliases' refers to unknown tool '{}'",
+                    name
+                ));
+            }
+        }
+        if self.tools.verify_pcli2 && self.tools.skip_pcli2_check {
+            return Err(anyhow!(
+                "Config 'tools.verify_pcli2' cannot be combined with 'tools.skip_pcli2_check'"
+            ));
+        }
+        for name in self.tools.compat_text.keys() {
+            if !tool_names.contains(&name.as_str()) {
+                return Err(anyhow!(
+                    "Config 'tools.compat_text' refers to unknown tool '{}'",
+                    name
+                ));
+            }
+        }
+        for (name, policy) in std::iter::once(("default", &self.execution.default)).chain(
+            self.execution
+                .tools
+                .iter()
+                .map(|(name, policy)| (name.as_str(), policy)),
+        ) {
+            if name != "default" && !tool_names.contains(&name) {
+                return Err(anyhow!(
+                    "Config 'execution.tools' refers to unknown tool '{}'",
+                    name
+                ));
+            }
+            if policy.timeout_secs == Some(0) {
+                return Err(anyhow!(
+                    "Config 'execution' policy for '{}' has a zero timeout",
+                    name
+                ));
+            }
+            if policy.concurrency == Some(0) {
+                return Err(anyhow!(
+                    "Config 'execution' policy for '{}' has a zero concurrency",
+                    name
+                ));
+            }
+            if policy.retries.is_some_and(|retries| retries > MAX_RETRIES) {
+                return Err(anyhow!(
+                    "Config 'execution' policy for '{}' allows at most {} retries",
+                    name,
+                    MAX_RETRIES
+                ));
+            }
+        }
+        if self.execution.max_concurrent_calls == Some(0) {
+            return Err(anyhow!(
+                "Config 'execution.max_concurrent_calls' must be at least 1"
+            ));
+        }
+        if self.circuit_breaker.probe_args.is_empty() {
+            return Err(anyhow!(
+                "Config 'circuit_breaker.probe_args' must not be empty"
+            ));
+        }
+        expand_args(&self.auth.reauth_args)
+            .map_err(|err| anyhow!("Config 'auth.reauth_args': {}", err))?;
+        self.sandbox.validate(&tool_names)?;
+        self.shared_store.validate()?;
+        self.watch.validate()?;
+        self.index.validate()?;
+        if self.usage.save_interval_secs == 0 {
+            return Err(anyhow!(
+                "Config 'usage.save_interval_secs' must be greater than 0"
+            ));
+        }
+        self.warm.validate(!self.auth.reauth_args.is_empty())?;
+        self.cache.validate()?;
+        self.health.validate(&tool_names)?;
+        validate_listeners(&self.listeners)?;
+        self.image_match.validate()?;
+        self.export.validate()?;
+        self.approval.validate(&tool_names)?;
+        self.workspace.validate()?;
+        self.artifacts.validate(&tool_names)?;
+        for (name, feature) in &self.features {
+            feature.validate(name)?;
+        }
+        for (tenant, defaults) in &self.tenants {
+            defaults.validate(tenant)?;
+        }
+        self.instructions.validate()?;
+        self.identity.validate()?;
+        self.ssh.validate()?;
+        self.docker.validate()?;
+        self.cli_versions.validate(&tool_names)?;
+        self.multi_tenant_search.validate()?;
+        self.access
+            .validate(!self.api_keys.is_empty(), self.admin.token.is_some())?;
+        self.plan.validate(&tool_names)?;
+        if self.ssh.host.is_some() && self.docker.container.is_some() {
+            return Err(anyhow!(
+                "Config 'ssh.host' and 'docker.container' cannot both be set"
+            ));
+        }
+        if self.sandbox.enabled {
+            for (section, set) in [
+                ("ssh.host", self.ssh.host.is_some()),
+                ("docker.container", self.docker.container.is_some()),
+            ] {
+                if set {
+                    return Err(anyhow!(
+                        "Config 'sandbox' limits local pcli2 runs and cannot be combined with '{}'",
+                        section
+                    ));
+                }
+            }
+        }
+        if let Some(token) = &self.admin.token {
+            expand_args(std::slice::from_ref(token))
+                .map_err(|err| anyhow!("Config 'admin.token': {}", err))?;
+        }
+        let mut keys = HashSet::new();
+        for (index, api_key) in self.api_keys.iter().enumerate() {
+            let key = expand_args(std::slice::from_ref(&api_key.key))
+                .map_err(|err| anyhow!("Config 'api_keys[{}].key': {}", index, err))?
+                .concat();
+            if key.is_empty() {
+                return Err(anyhow!(
+                    "Config 'api_keys[{}].key' must not be empty",
+                    index
+                ));
+            }
+            if !keys.insert(key) {
+                return Err(anyhow!(
+                    "Config 'api_keys' lists the same key more than once"
+                ));
+            }
+            if api_key
+                .name
+                .as_ref()
+                .is_some_and(|name| name.trim().is_empty())
+            {
+                return Err(anyhow!(
+                    "Config 'api_keys[{}].name' must not be empty",
+                    index
+                ));
+            }
+            if api_key
+                .tenant
+                .as_ref()
+                .is_some_and(|tenant| tenant.trim().is_empty())
+            {
+                return Err(anyhow!(
+                    "Config 'api_keys[{}].tenant' must not be empty",
+                    index
+                ));
+            }
+            if let Some(name) = &api_key.policy {
+                let policy = self.policies.get(name).ok_or_else(|| {
+                    anyhow!(
+                        "Config 'api_keys[{}].policy' refers to unknown policy '{}'",
+                        index,
+                        name
+                    )
+                })?;
+                if let Some(tenant) = &api_key.tenant
+                    && !policy.tenants.is_empty()
+                    && !policy.tenants.contains(tenant)
+                {
+                    return Err(anyhow!(
+                        "Config 'api_keys[{}].tenant' '{}' is not among the tenants of policy '{}'",
+                        index,
+                        tenant,
+                        name
+                    ));
+                }
+            }
+        }
+        for (name, policy) in &self.policies {
+            if let Some(tool) = policy
+                .tools
+                .iter()
+                .find(|tool| !tool_names.contains(&tool.as_str()))
+            {
+                return Err(anyhow!(
+                    "Config 'policies.{}.tools' refers to unknown tool '{}'",
+                    name,
+                    tool
+                ));
+            }
+            if policy.tenants.iter().any(|tenant| tenant.trim().is_empty()) {
+                return Err(anyhow!(
+                    "Config 'policies.{}.tenants' must not contain empty names",
+                    name
+                ));
+            }
+            for (quota, rule) in &policy.quotas {
+                validate_quota(
+                    &format!("policies.{}.quotas.{}", name, quota),
+                    rule,
+                    &tool_names,
+                )?;
+            }
+        }
+        if self.admin.credential_commands.iter().any(Vec::is_empty) {
+            return Err(anyhow!(
+                "Config 'admin.credential_commands' must not contain empty commands"
+            ));
+        }
+        for (name, rule) in &self.quotas {
+            validate_quota(&format!("quotas.{}", name), rule, &tool_names)?;
+        }
+        let mut seen = HashSet::new();
+        for name in &tool_names {
+            let public = self.tools.public_name(name);
+            if public.is_empty() {
+                return Err(anyhow!("Tool '{}' would have an empty public name", name));
+            }
+            if !seen.insert(public.clone()) {
+                return Err(anyhow!(
+                    "Tool name '{}' is used by more than one tool; adjust 'tools.prefix' or 'tools.aliases'",
+                    public
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SandboxConfig {
+    fn validate(&self, tool_names: &[&str]) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if !cfg!(unix) {
+            return Err(anyhow!(
+                "Config 'sandbox' is only supported on Unix platforms"
+            ));
+        }
+        for name in &self.offline_tools {
+            if !tool_names.contains(&name.as_str()) {
+                return Err(anyhow!(
+                    "Config 'sandbox.offline_tools' refers to unknown tool '{}'",
+                    name
+                ));
+            }
+        }
+        if !self.offline_tools.is_empty() && !cfg!(target_os = "linux") {
+            return Err(anyhow!(
+                "Config 'sandbox.offline_tools' requires Linux network namespaces"
+            ));
+        }
+        for (key, limit) in [
+            ("cpu_time_secs", self.cpu_time_secs),
+            ("memory_bytes", self.memory_bytes),
+            ("file_size_bytes", self.file_size_bytes),
+        ] {
+            if limit == Some(0) {
+                return Err(anyhow!("Config 'sandbox.{}' must be greater than 0", key));
+            }
+        }
+        if let Some(dir) = &self.working_dir
+            && !dir.is_dir()
+        {
+            return Err(anyhow!(
+                "Config 'sandbox.working_dir' {} is not a directory",
+                dir.display()
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl SharedStoreConfig {
+    fn validate(&self) -> Result<()> {
+        let Some(url) = &self.redis_url else {
+            return Ok(());
+        };
+        if !cfg!(feature = "redis") {
+            return Err(anyhow!(
+                "Config 'shared_store.redis_url' requires pcli2-mcp built with the 'redis' feature"
+            ));
+        }
+        if self.ttl_secs == 0 {
+            return Err(anyhow!(
+                "Config 'shared_store.ttl_secs' must be greater than 0"
+            ));
+        }
+        #[cfg(feature = "redis")]
+        crate::shared::check_url(url)
+            .map_err(|err| anyhow!("Config 'shared_store.redis_url': {}", err))?;
+        #[cfg(not(feature = "redis"))]
+        let _ = url;
+        Ok(())
+    }
+}
+
+impl WatchConfig {
+    fn validate(&self) -> Result<()> {
+        if self.interval_secs == 0 {
+            return Err(anyhow!(
+                "Config 'watch.interval_secs' must be greater than 0"
+            ));
+        }
+        if let Some(folder) = self.folders.iter().find(|folder| !folder.starts_with('/')) {
+            return Err(anyhow!(
+                "Config 'watch.folders' entry '{}' must be an absolute folder path",
+                folder
+            ));
+        }
+        for url in &self.webhooks {
+            crate::proxy::Endpoint::parse(url)
+                .map_err(|err| anyhow!("Config 'watch.webhooks': {}", err))?;
+        }
+        crate::proxy::parse_headers(&self.webhook_headers)
+            .map_err(|err| anyhow!("Config 'watch.webhook_headers': {}", err))?;
+        Ok(())
+    }
+}
+
+impl IndexConfig {
+    fn validate(&self) -> Result<()> {
+        if self.interval_secs == 0 {
+            return Err(anyhow!(
+                "Config 'index.interval_secs' must be greater than 0"
+            ));
+        }
+        if let Some(folder) = self.folders.iter().find(|folder| !folder.starts_with('/')) {
+            return Err(anyhow!(
+                "Config 'index.folders' entry '{}' must be an absolute folder path",
+                folder
+            ));
+        }
+        let mut seen = HashSet::new();
+        if let Some(folder) = self.folders.iter().find(|folder| !seen.insert(*folder)) {
+            return Err(anyhow!("Config 'index.folders' lists '{}' twice", folder));
+        }
+        Ok(())
+    }
+}
+
+impl WarmConfig {
+    fn validate(&self, can_login: bool) -> Result<()> {
+        if self.login && !can_login {
+            return Err(anyhow!(
+                "Config 'warm.login' needs 'auth.reauth_args' to log in with"
+            ));
+        }
+        if self.interval_secs == Some(0) {
+            return Err(anyhow!(
+                "Config 'warm.interval_secs' must be greater than 0"
+            ));
+        }
+        for command in &self.commands {
+            if command.is_empty() {
+                return Err(anyhow!("Config 'warm.commands' entries must not be empty"));
+            }
+            expand_args(command).map_err(|err| anyhow!("Config 'warm.commands': {}", err))?;
+        }
+        Ok(())
+    }
+}
+
+impl CacheConfig {
+    fn validate(&self) -> Result<()> {
+        if self.max_age_secs == Some(0) {
+            return Err(anyhow!(
+                "Config 'cache.max_age_secs' must be greater than 0"
+            ));
+        }
+        if self.max_age_secs.is_none() && (self.persist || self.dir.is_some()) {
+            return Err(anyhow!(
+                "Config 'cache.persist' and 'cache.dir' need 'cache.max_age_secs'"
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl SshConfig {
+    fn validate(&self) -> Result<()> {
+        let Some(host) = &self.host else {
+            return Ok(());
+        };
+        let valid = |value: &str| {
+            !value.is_empty()
+                && !value.starts_with('-')
+                && !value.contains(['@', ' ', '\t', '\r', '\n'])
+        };
+        if !valid(host) {
+            return Err(anyhow!("Config 'ssh.host' '{}' is not a host name", host));
+        }
+        if let Some(user) = self.user.as_deref().filter(|user| !valid(user)) {
+            return Err(anyhow!("Config 'ssh.user' '{}' is not a user name", user));
+        }
+        if self.pcli2.trim().is_empty() {
+            return Err(anyhow!("Config 'ssh.pcli2' must not be empty"));
+        }
+        Ok(())
+    }
+}
+
+impl DockerConfig {
+    fn validate(&self) -> Result<()> {
+        let Some(container) = &self.container else {
+            return Ok(());
+        };
+        let mut chars = container.chars();
+        if !chars.next().is_some_and(|ch| ch.is_ascii_alphanumeric())
+            || !chars.all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '_' | '.' | '-'))
+        {
+            return Err(anyhow!(
+                "Config 'docker.container' '{}' is not a container name",
+                container
+            ));
+        }
+        if self.pcli2.trim().is_empty() {
+            return Err(anyhow!("Config 'docker.pcli2' must not be empty"));
+        }
+        if let Some(dir) = self
+            .working_dir
+            .as_deref()
+            .filter(|dir| !dir.starts_with('/'))
+        {
+            return Err(anyhow!(
+                "Config 'docker.working_dir' '{}' must be an absolute path",
+                dir
+            ));
+        }
+        for mount in &self.mounts {
+            if !mount.host.is_absolute() || !mount.container.starts_with('/') {
+                return Err(anyhow!(
+                    "Config 'docker.mounts' paths must be absolute ('{}' -> '{}')",
+                    mount.host.display(),
+                    mount.container
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ExecutionConfig {
+    pub fn policy(&self, tool: &str) -> ExecutionPolicy {
+        let specific = self.tools.get(tool);
+        let timeout_secs = specific
+            .and_then(|policy| policy.timeout_secs)
+            .or(self.default.timeout_secs);
+        ExecutionPolicy {
+            timeout: timeout_secs
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_TOOL_TIMEOUT),
+            retries: specific
+                .and_then(|policy| policy.retries)
+                .or(self.default.retries)
+                .unwrap_or(0),
+            concurrency: specific
+                .and_then(|policy| policy.concurrency)
+                .or(self.default.concurrency),
+        }
+    }
+}
+
+impl Default for ExecutionPolicy {
+    fn default() -> Self {
+        ExecutionConfig::default().policy("")
+    }
+}
+
+impl ToolsConfig {
+    /// Whether `internal` keeps its text block next to `structuredContent`.
+    pub fn compat_text(&self, internal: &str, default: bool) -> bool {
+        self.compat_text.get(internal).copied().unwrap_or(default)
+    }
+
+    pub fn public_name(&self, internal: &str) -> String {
+        if let Some(alias) = self.aliases.get(internal) {
+            return alias.clone();
+        }
+        match self.prefix.as_deref() {
+            Some(prefix) => match internal.strip_prefix(DEFAULT_TOOL_PREFIX) {
+                Some("") => prefix.trim_end_matches('_').to_string(),
+                Some(rest) => format!("{}{}", prefix, rest.trim_start_matches('_')),
+                None => internal.to_string(),
+            },
+            None => internal.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAMES: [&str; 3] = ["pcli2", "pcli2_asset_get", "pcli2_tenant_list"];
+
+    #[test]
+    fn test_default_names_are_unchanged() {
+        let tools = ToolsConfig::default();
+        assert_eq!(tools.public_name("pcli2_asset_get"), "pcli2_asset_get");
+        assert_eq!(tools.public_name("pcli2"), "pcli2");
+    }
+
+    #[test]
+    fn test_prefix_replaces_pcli2() {
+        let tools = ToolsConfig {
+            prefix: Some("physna_".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(tools.public_name("pcli2_asset_get"), "physna_asset_get");
+        assert_eq!(tools.public_name("pcli2"), "physna");
+    }
+
+    #[test]
+    fn test_alias_overrides_prefix() {
+        let tools = ToolsConfig {
+            prefix: Some("physna_".to_string()),
+            aliases: HashMap::from([("pcli2".to_string(), "physna_list".to_string())]),
+            ..Default::default()
+        };
+        assert_eq!(tools.public_name("pcli2"), "physna_list");
+        assert_eq!(tools.public_name("pcli2_asset_get"), "physna_asset_get");
+    }
+
+    #[test]
+    fn test_pcli2_check_cannot_be_both_skipped_and_verified() {
+        let config: ServerConfig =
+            serde_json::from_str(r#"{"tools": {"verify_pcli2": true, "skip_pcli2_check": true}}"#)
+                .unwrap();
+        assert!(
+            config
+                .validate(NAMES)
+                .unwrap_err()
+                .to_string()
+                .contains("tools.skip_pcli2_check")
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_unknown_alias() {
+        let config: ServerConfig =
+            serde_json::from_str(r#"{"tools": {"aliases": {"nope": "x"}}}"#).unwrap();
+        assert!(config.validate(NAMES).is_err());
+    }
+
+    #[test]
+    fn test_compat_text_override() {
+        let config: ServerConfig =
+            serde_json::from_str(r#"{"tools": {"compat_text": {"pcli2_asset_get": false}}}"#)
+                .unwrap();
+        assert!(config.validate(NAMES).is_ok());
+        assert!(!config.tools.compat_text("pcli2_asset_get", true));
+        assert!(config.tools.compat_text("pcli2_tenant_list", true));
+        assert!(!config.tools.compat_text("pcli2_tenant_list", false));
+
+        let config: ServerConfig =
+            serde_json::from_str(r#"{"tools": {"compat_text": {"nope": true}}}"#).unwrap();
+        assert!(config.validate(NAMES).is_err());
+    }
+
+    #[test]
+    fn test_ssh_is_validated() {
+        let config: ServerConfig =
+            serde_json::from_str(r#"{"ssh": {"host": "bastion.internal", "user": "svc"}}"#)
+                .unwrap();
+        assert!(config.validate(NAMES).is_ok());
+        assert_eq!(config.ssh.pcli2, "pcli2");
+
+        for ssh in [
+            r#"{"host": "-oProxyCommand=x"}"#,
+            r#"{"host": "svc@bastion"}"#,
+            r#"{"host": "bastion", "pcli2": " "}"#,
+        ] {
+            let config: ServerConfig =
+                serde_json::from_str(&format!(r#"{{"ssh": {}}}"#, ssh)).unwrap();
+            assert!(config.validate(NAMES).is_err(), "{}", ssh);
+        }
+
+        let config: ServerConfig =
+            serde_json::from_str(r#"{"ssh": {"host": "bastion"}, "sandbox": {"enabled": true}}"#)
+                .unwrap();
+        assert!(config.validate(NAMES).is_err());
+    }
+
+    #[test]
+    fn test_docker_is_validated() {
+        let config: ServerConfig = serde_json::from_str(
+            r#"{"docker": {"container": "pcli2-runner", "working_dir": "/work",
+                "mounts": [{"host": "/tmp", "container": "/host-tmp"}]}}"#,
+        )
+        .unwrap();
+        assert!(config.validate(NAMES).is_ok());
+
+        for docker in [
+            r#"{"container": "-it"}"#,
+            r#"{"container": "runner", "working_dir": "work"}"#,
+            r#"{"container": "runner", "mounts": [{"host": "tmp", "container": "/tmp"}]}"#,
+        ] {
+            let config: ServerConfig =
+                serde_json::from_str(&format!(r#"{{"docker": {}}}"#, docker)).unwrap();
+            assert!(config.validate(NAMES).is_err(), "{}", docker);
+        }
+
+        let config: ServerConfig = serde_json::from_str(
+            r#"{"docker": {"container": "runner"}, "ssh": {"host": "bastion"}}"#,
+        )
+        .unwrap();
+        assert!(config.validate(NAMES).is_err());
+    }
+
+    #[test]
+    fn test_cli_versions() {
+        let config: ServerConfig = serde_json::from_str(
+            r#"{"cli_versions": {
+                "binaries": {"stable": "/usr/bin/pcli2", "beta": "/opt/beta/pcli2"},
+                "default": "stable",
+                "tools": {"pcli2_asset_get": "beta"}
+            }}"#,
+        )
+        .unwrap();
+        assert!(config.validate(NAMES).is_ok());
+        let versions = &config.cli_versions;
+        let select = |tool, requested| versions.select(tool, requested).unwrap().unwrap().0;
+        assert_eq!(select("pcli2_tenant_list", None), "stable");
+        assert_eq!(select("pcli2_asset_get", None), "beta");
+        assert_eq!(select("pcli2_asset_get", Some("stable")), "stable");
+        assert!(versions.select("pcli2", Some("nightly")).is_err());
+        assert_eq!(CliVersionsConfig::default().select("pcli2", None), Ok(None));
+
+        for cli_versions in [
+            r#"{"binaries": {"stable": "/usr/bin/pcli2"}, "default": "beta"}"#,
+            r#"{"binaries": {"stable": "/usr/bin/pcli2"}, "tools": {"nope": "stable"}}"#,
+            r#"{"binaries": {"stable": ""}}"#,
+        ] {
+            let config: ServerConfig =
+                serde_json::from_str(&format!(r#"{{"cli_versions": {}}}"#, cli_versions)).unwrap();
+            assert!(config.validate(NAMES).is_err(), "{}", cli_versions);
+        }
+    }
+
+    #[test]
+    fn test_plan_rules() {
+        let config: ServerConfig = serde_json::from_str(
+            r#"{"plan": {"rules": [{
+                "keywords": ["reorder"],
+                "description": "Find parts to reorder",
+                "steps": [{"tool": "pcli2_asset_get", "inputs": ["asset", "tenant?"]}]
+            }]}}"#,
+        )
+        .unwrap();
+        assert!(config.validate(NAMES).is_ok());
+        assert_eq!(config.plan.rules[0].steps[0].inputs, ["asset", "tenant?"]);
+
+        for step in [
+            r#"{"tool": "nope"}"#,
+            r#"{"tool": "pcli2_asset_get", "inputs": ["colour"]}"#,
+        ] {
+            let config: ServerConfig = serde_json::from_str(&format!(
+                r#"{{"plan": {{"rules": [{{"keywords": ["x"], "description": "x", "steps": [{}]}}]}}}}"#,
+                step
+            ))
+            .unwrap();
+            assert!(config.validate(NAMES).is_err(), "{}", step);
+        }
+    }
+
+    #[test]
+    fn test_api_keys() {
+        let config: ServerConfig = serde_json::from_str(
+            r#"{"api_keys": [{"key": "team-a", "tenant": "castings"}, {"key": "ops"}]}"#,
+        )
+        .unwrap();
+        assert!(config.validate(NAMES).is_ok());
+        assert_eq!(config.api_keys[0].tenant.as_deref(), Some("castings"));
+        assert_eq!(config.api_keys[1].tenant, None);
+
+        for api_keys in [
+            r#"[{"key": ""}]"#,
+            r#"[{"key": "a"}, {"key": "a", "tenant": "castings"}]"#,
+            r#"[{"key": "a", "tenant": ""}]"#,
+            r#"[{"key": "${PCLI2_MCP_TEST_UNSET_KEY}"}]"#,
+        ] {
+            let config: ServerConfig =
+                serde_json::from_str(&format!(r#"{{"api_keys": {}}}"#, api_keys)).unwrap();
+            assert!(config.validate(NAMES).is_err(), "{}", api_keys);
+        }
+    }
+
+    #[test]
+    fn test_access_routes() {
+        let config: ServerConfig = serde_json::from_str(
+            r#"{
+                "api_keys": [{"key": "ops"}],
+                "access": {
+                    "routes": {"mcp": ["oauth", "api_key"], "health": ["mtls"]},
+                    "oauth": {"secret": "s3cret", "tenant_claim": "tenant"},
+                    "mtls": {},
+                    "replay": {"signed": true}
+                }
+            }"#,
+        )
+        .unwrap();
+        assert!(config.validate(NAMES).is_ok());
+        assert_eq!(
+            config.access.chain("mcp"),
+            [AuthScheme::Oauth, AuthScheme::ApiKey]
+        );
+        assert_eq!(config.access.chain("admin"), [AuthScheme::AdminToken]);
+        assert_eq!(
+            config.access.mtls.as_ref().unwrap().verify_header,
+            "x-ssl-client-verify"
+        );
+        assert_eq!(config.access.replay.as_ref().unwrap().max_skew_secs, 300);
+
+        for access in [
+            r#"{"routes": {"metrics": ["none"]}}"#,
+            r#"{"routes": {"mcp": []}}"#,
+            r#"{"routes": {"mcp": ["none", "api_key"]}}"#,
+            r#"{"routes": {"mcp": ["oauth"]}}"#,
+            r#"{"routes": {"health": ["admin_token"]}}"#,
+            r#"{"routes": {"mcp": ["kerberos"]}}"#,
+            r#"{"oauth": {"secret": ""}}"#,
+            r#"{"replay": {"max_skew_secs": 0}}"#,
+            r#"{"replay": {"nonce_header": "bad header"}}"#,
+            r#"{"replay": {"signed": true}}"#,
+        ] {
+            match serde_json::from_str::<ServerConfig>(&format!(r#"{{"access": {}}}"#, access)) {
+                Ok(config) => assert!(config.validate(NAMES).is_err(), "{}", access),
+                Err(_) => assert!(access.contains("kerberos")),
+            }
+        }
+    }
+
+    #[test]
+    fn test_multi_tenant_search_tenants() {
+        let config: ServerConfig =
+            serde_json::from_str(r#"{"multi_tenant_search": {"tenants": ["east", "west"]}}"#)
+                .unwrap();
+        assert!(config.validate(NAMES).is_ok());
+        assert_eq!(config.multi_tenant_search.tenants, ["east", "west"]);
+
+        for tenants in [r#"["east", "east"]"#, r#"["east", " "]"#] {
+            let config: ServerConfig = serde_json::from_str(&format!(
+                r#"{{"multi_tenant_search": {{"tenants": {}}}}}"#,
+                tenants
+            ))
+            .unwrap();
+            assert!(config.validate(NAMES).is_err(), "{}", tenants);
+        }
+    }
+
+    #[test]
+    fn test_validate_rejects_collisions() {
+        let config: ServerConfig =
+            serde_json::from_str(r#"{"tools": {"aliases": {"pcli2": "pcli2_asset_get"}}}"#)
+                .unwrap();
+        let err = config.validate(NAMES).unwrap_err();
+        assert!(err.to_string().contains("more than one tool"));
+    }
+
+    #[test]
+    fn test_execution_policy_falls_back_to_default() {
+        let config: ServerConfig = serde_json::from_str(
+            r#"{"execution": {
+                "default": {"timeout_secs": 60},
+                "tools": {"pcli2_tenant_list": {"timeout_secs": 10, "retries": 2}}
+            }}"#,
+        )
+        .unwrap();
+        let tenant = config.execution.policy("pcli2_tenant_list");
+        assert_eq!(tenant.timeout, Duration::from_secs(10));
+        assert_eq!(tenant.retries, 2);
+        let other = config.execution.policy("pcli2_asset_get");
+        assert_eq!(other.timeout, Duration::from_secs(60));
+        assert_eq!(other.retries, 0);
+        assert_eq!(other.concurrency, None);
+        assert_eq!(ExecutionPolicy::default().timeout, DEFAULT_TOOL_TIMEOUT);
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_policies() {
+        let unknown: ServerConfig =
+            serde_json::from_str(r#"{"execution": {"tools": {"nope": {}}}}"#).unwrap();
+        assert!(unknown.validate(NAMES).is_err());
+        let zero: ServerConfig =
+            serde_json::from_str(r#"{"execution": {"default": {"concurrency": 0}}}"#).unwrap();
+        assert!(zero.validate(NAMES).is_err());
+        let retries: ServerConfig = serde_json::from_str(
+            r#"{"execution": {"tools": {"pcli2_asset_get": {"retries": 50}}}}"#,
+        )
+        .unwrap();
+        assert!(retries.validate(NAMES).is_err());
+        let no_slots: ServerConfig =
+            serde_json::from_str(r#"{"execution": {"max_concurrent_calls": 0}}"#).unwrap();
+        assert!(no_slots.validate(NAMES).is_err());
+    }
+
+    #[test]
+    fn test_circuit_breaker_defaults() {
+        let config: ServerConfig =
+            serde_json::from_str(r#"{"circuit_breaker": {"failure_threshold": 3}}"#).unwrap();
+        assert_eq!(config.circuit_breaker.failure_threshold, 3);
+        assert_eq!(config.circuit_breaker.probe_interval_secs, 30);
+        assert_eq!(config.circuit_breaker.probe_args, vec!["--version"]);
+    }
+
+    #[test]
+    fn test_sandbox_is_validated_only_when_enabled() {
+        let disabled: ServerConfig = serde_json::from_str(
+            r#"{"sandbox": {"offline_tools": ["nope"], "working_dir": "/nonexistent/dir"}}"#,
+        )
+        .unwrap();
+        assert!(disabled.validate(NAMES).is_ok());
+        let unknown: ServerConfig =
+            serde_json::from_str(r#"{"sandbox": {"enabled": true, "offline_tools": ["nope"]}}"#)
+                .unwrap();
+        assert!(unknown.validate(NAMES).is_err());
+        let missing_dir: ServerConfig = serde_json::from_str(
+            r#"{"sandbox": {"enabled": true, "working_dir": "/nonexistent/dir"}}"#,
+        )
+        .unwrap();
+        assert!(missing_dir.validate(NAMES).is_err());
+        let zero: ServerConfig =
+            serde_json::from_str(r#"{"sandbox": {"enabled": true, "memory_bytes": 0}}"#).unwrap();
+        assert!(zero.validate(NAMES).is_err());
+    }
+
+    #[test]
+    fn test_shared_store_is_validated() {
+        let unset = ServerConfig::default();
+        assert!(unset.validate(NAMES).is_ok());
+        let zero_ttl: ServerConfig = serde_json::from_str(
+            r#"{"shared_store": {"redis_url": "redis://localhost", "ttl_secs": 0}}"#,
+        )
+        .unwrap();
+        assert!(zero_ttl.validate(NAMES).is_err());
+        let configured: ServerConfig =
+            serde_json::from_str(r#"{"shared_store": {"redis_url": "redis://localhost:6380/1"}}"#)
+                .unwrap();
+        assert_eq!(configured.validate(NAMES).is_ok(), cfg!(feature = "redis"));
+    }
+
+    #[test]
+    fn test_watch_is_validated() {
+        let valid: ServerConfig = serde_json::from_str(
+            r#"{"watch": {"folders": ["/Root/Incoming"], "webhooks": ["http://localhost:9000/hook"]}}"#,
+        )
+        .unwrap();
+        assert!(valid.validate(NAMES).is_ok());
+        assert_eq!(valid.watch.interval_secs, 300);
+        for invalid in [
+            r#"{"watch": {"folders": ["Root"]}}"#,
+            r#"{"watch": {"interval_secs": 0}}"#,
+            r#"{"watch": {"webhooks": ["ftp://example.com"]}}"#,
+        ] {
+            let config: ServerConfig = serde_json::from_str(invalid).unwrap();
+            assert!(config.validate(NAMES).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_usage_and_key_names_are_validated() {
+        let valid: ServerConfig = serde_json::from_str(
+            r#"{"usage": {"report_dir": "/var/lib/pcli2-mcp/usage"}, "api_keys": [{"key": "k1", "name": "team-a"}]}"#,
+        )
+        .unwrap();
+        assert!(valid.validate(NAMES).is_ok());
+        assert_eq!(valid.usage.save_interval_secs, 60);
+        for invalid in [
+            r#"{"usage": {"save_interval_secs": 0}}"#,
+            r#"{"api_keys": [{"key": "k1", "name": " "}]}"#,
+        ] {
+            let config: ServerConfig = serde_json::from_str(invalid).unwrap();
+            assert!(config.validate(NAMES).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_index_is_validated() {
+        let valid: ServerConfig =
+            serde_json::from_str(r#"{"index": {"folders": ["/Root/Castings"], "tenant": "acme"}}"#)
+                .unwrap();
+        assert!(valid.validate(NAMES).is_ok());
+        assert!(valid.index.is_enabled());
+        assert_eq!(valid.index.interval_secs, 900);
+        for invalid in [
+            r#"{"index": {"folders": ["Root"]}}"#,
+            r#"{"index": {"folders": ["/Root", "/Root"]}}"#,
+            r#"{"index": {"interval_secs": 0}}"#,
+        ] {
+            let config: ServerConfig = serde_json::from_str(invalid).unwrap();
+            assert!(config.validate(NAMES).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_warm_is_validated() {
+        let valid: ServerConfig = serde_json::from_str(
+            r#"{"auth": {"reauth_args": ["auth", "login"]}, "warm": {"login": true, "commands": [["folder", "list"]], "interval_secs": 900}}"#,
+        )
+        .unwrap();
+        assert!(valid.validate(NAMES).is_ok());
+        for invalid in [
+            r#"{"warm": {"login": true}}"#,
+            r#"{"warm": {"commands": [[]]}}"#,
+            r#"{"warm": {"interval_secs": 0}}"#,
+            r#"{"warm": {"commands": [["tenant", "use", "${PCLI2_MCP_UNSET_VARIABLE}"]]}}"#,
+        ] {
+            let config: ServerConfig = serde_json::from_str(invalid).unwrap();
+            assert!(config.validate(NAMES).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_cache_is_validated() {
+        let valid: ServerConfig =
+            serde_json::from_str(r#"{"cache": {"max_age_secs": 900, "persist": true}}"#).unwrap();
+        assert!(valid.validate(NAMES).is_ok());
+        for invalid in [
+            r#"{"cache": {"max_age_secs": 0}}"#,
+            r#"{"cache": {"persist": true}}"#,
+            r#"{"cache": {"dir": "/tmp/cache"}}"#,
+        ] {
+            let config: ServerConfig = serde_json::from_str(invalid).unwrap();
+            assert!(config.validate(NAMES).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_identity_is_validated() {
+        let valid: ServerConfig = serde_json::from_str(
+            r#"{"identity": {"name": "acme-physna", "title": "Acme Physna Gateway (prod)"}}"#,
+        )
+        .unwrap();
+        assert!(valid.validate(NAMES).is_ok());
+        for invalid in [
+            r#"{"identity": {"name": " "}}"#,
+            r#"{"identity": {"version": ""}}"#,
+        ] {
+            let config: ServerConfig = serde_json::from_str(invalid).unwrap();
+            assert!(config.validate(NAMES).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_listeners_are_validated() {
+        let valid: ServerConfig = serde_json::from_str(
+            r#"{"listeners": [
+                {"transport": "websocket", "port": 8081},
+                {"transport": "http", "host": "0.0.0.0", "port": 8082, "stream": false, "admin": true},
+                {"transport": "unix", "path": "/run/pcli2-mcp.sock"}
+            ]}"#,
+        )
+        .unwrap();
+        assert!(valid.validate(NAMES).is_ok());
+        assert!(valid.listeners[0].stream);
+        assert!(!valid.listeners[1].stream);
+        assert_eq!(valid.listeners[2].transport, ListenerTransport::Unix);
+        for invalid in [
+            r#"{"listeners": [{"transport": "http"}]}"#,
+            r#"{"listeners": [{"transport": "websocket", "port": 1, "path": "/s"}]}"#,
+            r#"{"listeners": [{"transport": "unix"}]}"#,
+            r#"{"listeners": [{"transport": "unix", "path": "/s", "port": 1}]}"#,
+            r#"{"listeners": [{"transport": "http", "port": 1}, {"transport": "websocket", "port": 1}]}"#,
+            r#"{"listeners": [{"transport": "unix", "path": "/s"}, {"transport": "unix", "path": "/s"}]}"#,
+            r#"{"listeners": [{"transport": "stdio"}]}"#,
+        ] {
+            match serde_json::from_str::<ServerConfig>(invalid) {
+                Ok(config) => assert!(config.validate(NAMES).is_err(), "{}", invalid),
+                Err(_) => assert!(invalid.contains("stdio")),
+            }
+        }
+    }
+
+    #[test]
+    fn test_image_match_is_validated() {
+        let config: ServerConfig = serde_json::from_str(
+            r#"{"image_match": {"args": ["asset", "visual-match", "--file", "{file}"]}}"#,
+        )
+        .unwrap();
+        assert!(config.validate(NAMES).is_ok());
+        assert!(config.image_match.is_enabled());
+        assert_eq!(config.image_match.tenant_args, ["-t", "{tenant}"]);
+        assert!(!ServerConfig::default().image_match.is_enabled());
+        for invalid in [
+            r#"{"image_match": {"args": ["asset", "visual-match"]}}"#,
+            r#"{"image_match": {"args": ["{file}"], "tenant_args": ["-t"]}}"#,
+            r#"{"image_match": {"args": ["{file}"], "max_image_bytes": 0}}"#,
+        ] {
+            let config: ServerConfig = serde_json::from_str(invalid).unwrap();
+            assert!(config.validate(NAMES).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_export_is_validated() {
+        let config: ServerConfig = serde_json::from_str(
+            r#"{"export": {"format_args": ["--format", "{format}"], "units_args": ["--units={units}"]}}"#,
+        )
+        .unwrap();
+        assert!(config.validate(NAMES).is_ok());
+        for invalid in [
+            r#"{"export": {"format_args": ["--format"]}}"#,
+            r#"{"export": {"units_args": ["--units", "{format}"]}}"#,
+            r#"{"export": {"max_bytes": 0}}"#,
+            r#"{"export": {"download_attempts": 0}}"#,
+            r#"{"export": {"download_attempts": 11}}"#,
+            r#"{"export": {"resume_args": [""]}}"#,
+        ] {
+            let config: ServerConfig = serde_json::from_str(invalid).unwrap();
+            assert!(config.validate(NAMES).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_approval_is_validated() {
+        let config: ServerConfig =
+            serde_json::from_str(r#"{"approval": {"tools": ["pcli2_tenant_list"]}}"#).unwrap();
+        assert!(config.validate(NAMES).is_ok());
+        assert!(config.approval.requires("pcli2_tenant_list"));
+        assert!(!config.approval.requires("pcli2_asset_get"));
+        for invalid in [
+            r#"{"approval": {"tools": ["pcli2_unknown"]}}"#,
+            r#"{"approval": {"expire_secs": 0}}"#,
+        ] {
+            let config: ServerConfig = serde_json::from_str(invalid).unwrap();
+            assert!(config.validate(NAMES).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_workspace_dir_must_exist() {
+        let config = ServerConfig::default();
+        assert!(config.workspace.enabled);
+        let config: ServerConfig =
+            serde_json::from_str(r#"{"workspace": {"dir": "/nonexistent/dir"}}"#).unwrap();
+        assert!(
+            config
+                .validate(NAMES)
+                .unwrap_err()
+                .to_string()
+                .contains("workspace.dir")
+        );
+    }
+
+    #[test]
+    fn test_artifact_limits_must_be_positive() {
+        let config: ServerConfig = serde_json::from_str(
+            r#"{"artifacts": {"max_age_secs": 86400, "pin_referenced_secs": 7200, "expose_tools": true}}"#,
+        )
+        .unwrap();
+        assert!(config.validate(NAMES).is_ok());
+        assert!(config.artifacts.collects());
+        assert!(!ArtifactsConfig::default().collects());
+        for invalid in [
+            r#"{"artifacts": {"max_age_secs": 0}}"#,
+            r#"{"artifacts": {"max_total_bytes": 0}}"#,
+            r#"{"artifacts": {"sweep_interval_secs": 0}}"#,
+        ] {
+            let config: ServerConfig = serde_json::from_str(invalid).unwrap();
+            assert!(config.validate(NAMES).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_features_must_be_known() {
+        let config: ServerConfig = serde_json::from_str(
+            r#"{"features": {"structured_output": {"percent": 25, "sessions": ["s-1"]}, "approvals": {"enabled": false}}}"#,
+        )
+        .unwrap();
+        assert!(config.validate(NAMES).is_ok());
+        assert_eq!(config.features["approvals"].percent, 100);
+        for invalid in [
+            r#"{"features": {"async_everything": {}}}"#,
+            r#"{"features": {"dynamic_tools": {"percent": 101}}}"#,
+        ] {
+            let config: ServerConfig = serde_json::from_str(invalid).unwrap();
+            assert!(config.validate(NAMES).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_tenant_defaults_are_validated() {
+        let config: ServerConfig = serde_json::from_str(
+            r#"{"tenants": {"acme": {"threshold": 92.5, "format": "csv", "concurrency": 2, "artifact_dir": "acme/files"}}}"#,
+        )
+        .unwrap();
+        assert!(config.validate(NAMES).is_ok());
+        assert_eq!(config.tenants["acme"].threshold, Some(92.5));
+        for invalid in [
+            r#"{"tenants": {"acme": {"threshold": 101}}}"#,
+            r#"{"tenants": {"acme": {"format": ""}}}"#,
+            r#"{"tenants": {"acme": {"concurrency": 0}}}"#,
+            r#"{"tenants": {"acme": {"artifact_dir": "../elsewhere"}}}"#,
+            r#"{"tenants": {"acme": {"artifact_dir": "/tmp/acme"}}}"#,
+            r#"{"tenants": {"acme": {"size": "large"}}}"#,
+        ] {
+            let config = serde_json::from_str::<ServerConfig>(invalid);
+            assert!(
+                config.is_err() || config.unwrap().validate(NAMES).is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_health_is_validated() {
+        let valid: ServerConfig = serde_json::from_str(
+            r#"{"health": {"max_error_rate": 0.25, "max_p95_ms": 20000, "priorities": {"pcli2_asset_get": "low"}}}"#,
+        )
+        .unwrap();
+        assert!(valid.validate(NAMES).is_ok());
+        assert_eq!(valid.health.priority("pcli2_asset_get"), ToolPriority::Low);
+        assert_eq!(valid.health.priority("pcli2"), ToolPriority::Normal);
+        for invalid in [
+            r#"{"health": {"window_secs": 0}}"#,
+            r#"{"health": {"min_samples": 0}}"#,
+            r#"{"health": {"max_error_rate": 0}}"#,
+            r#"{"health": {"max_error_rate": 1.5}}"#,
+            r#"{"health": {"max_p95_ms": 0}}"#,
+            r#"{"health": {"priorities": {"nope": "low"}}}"#,
+        ] {
+            let config: ServerConfig = serde_json::from_str(invalid).unwrap();
+            assert!(config.validate(NAMES).is_err(), "{}", invalid);
+        }
+        assert!(
+            serde_json::from_str::<ServerConfig>(r#"{"health": {"default_priority": "urgent"}}"#)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_quotas_are_validated() {
+        let valid: ServerConfig = serde_json::from_str(
+            r#"{"quotas": {"matches": {"tools": ["pcli2_asset_get"], "max_calls": 5, "window_secs": 3600}}}"#,
+        )
+        .unwrap();
+        assert!(valid.validate(NAMES).is_ok());
+        let unknown: ServerConfig =
+            serde_json::from_str(r#"{"quotas": {"x": {"tools": ["nope"], "max_calls": 5}}}"#)
+                .unwrap();
+        assert!(unknown.validate(NAMES).is_err());
+        let zero: ServerConfig =
+            serde_json::from_str(r#"{"quotas": {"x": {"max_calls": 0}}}"#).unwrap();
+        assert!(zero.validate(NAMES).is_err());
+    }
+
+    #[test]
+    fn test_policies_are_validated() {
+        let valid: ServerConfig = serde_json::from_str(
+            r#"{
+                "api_keys": [{"key": "a", "tenant": "acme", "policy": "analyst"}, {"key": "b"}],
+                "policies": {"analyst": {"tools": ["pcli2_asset_get"], "tenants": ["acme"], "destructive": false,
+                    "quotas": {"reads": {"max_calls": 100, "window_secs": 3600}}}}
+            }"#,
+        )
+        .unwrap();
+        assert!(valid.validate(NAMES).is_ok());
+        assert!(PolicyConfig::default().destructive);
+        for (invalid, field) in [
+            (
+                r#"{"api_keys": [{"key": "a", "policy": "admin"}]}"#,
+                "api_keys[0].policy",
+            ),
+            (
+                r#"{"api_keys": [{"key": "a", "tenant": "beta", "policy": "p"}], "policies": {"p": {"tenants": ["acme"]}}}"#,
+                "api_keys[0].tenant",
+            ),
+            (
+                r#"{"policies": {"p": {"tools": ["nope"]}}}"#,
+                "policies.p.tools",
+            ),
+            (
+                r#"{"policies": {"p": {"tenants": [" "]}}}"#,
+                "policies.p.tenants",
+            ),
+            (
+                r#"{"policies": {"p": {"quotas": {"q": {"max_calls": 0}}}}}"#,
+                "policies.p.quotas.q",
+            ),
+        ] {
+            let config: ServerConfig = serde_json::from_str(invalid).unwrap();
+            let err = config.validate(NAMES).unwrap_err().to_string();
+            assert!(err.contains(field), "{}: {}", invalid, err);
+        }
+    }
+
+    #[test]
+    fn test_unknown_fields_are_rejected() {
+        let result = serde_json::from_str::<ServerConfig>(r#"{"tool": {}}"#);
+        assert!(result.is_err());
+    }
+}
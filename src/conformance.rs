@@ -0,0 +1,291 @@
+use crate::{
+    AppState,
+    cassette::Cassette,
+    mcp::{SESSION_HEADER, handle_mcp},
+    redact::redactor,
+    resources::RESULT_URI_PREFIX,
+    usage::ANONYMOUS_KEY,
+};
+use anyhow::{Context, Result, anyhow};
+use axum::{
+    body::{Body, Bytes, to_bytes},
+    extract::State,
+    http::{HeaderMap, HeaderValue},
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// The MCP exchanges of a suite, one JSON object per line.
+pub const EXCHANGES_FILE: &str = "exchanges.jsonl";
+/// The `pcli2` runs made while the suite was recorded, replayed with it.
+pub const CASSETTE_FILE: &str = "pcli2.jsonl";
+/// Fields whose values differ from run to run: clocks, durations, ids, and
+/// health counters. They are kept but blanked.
+const VOLATILE_KEYS: [&str; 11] = [
+    "time",
+    "duration_ms",
+    "requested_at",
+    "fetched_at",
+    "stored_at",
+    "created_at",
+    "changed_at",
+    "age_secs",
+    "resets_in_secs",
+    "request_id",
+    "backend_health",
+];
+const VOLATILE: &str = "<volatile>";
+/// Where the release version shows, so suites outlive releases.
+const VERSION_POINTERS: [&str; 2] = [
+    "/result/serverInfo/version",
+    "/result/_meta/implementation/version",
+];
+
+/// One `POST /mcp` request and the server's answer, sanitized.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Exchange {
+    /// `Mcp-Session-Id` the client sent, which scopes quotas and
+    /// subscriptions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<String>,
+    pub request: Value,
+    pub status: u16,
+    /// `null` for a notification's empty answer.
+    pub response: Value,
+}
+
+/// Ids that follow these markers in text are generated per run: stored
+/// result URIs and the request ids quoted in error messages.
+const ID_MARKERS: [&str; 2] = [RESULT_URI_PREFIX, "request id "];
+
+fn blank_ids(text: &str) -> String {
+    let mut text = text.to_string();
+    for marker in ID_MARKERS {
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text.as_str();
+        while let Some(index) = rest.find(marker) {
+            let (before, after) = rest.split_at(index + marker.len());
+            out.push_str(before);
+            let id_len = after
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '-')
+                .unwrap_or(after.len());
+            if id_len > 0 {
+                out.push_str("<id>");
+            }
+            rest = &after[id_len..];
+        }
+        out.push_str(rest);
+        text = out;
+    }
+    text
+}
+
+fn blank_volatile(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                if VOLATILE_KEYS.contains(&key.as_str()) && !value.is_null() {
+                    *value = json!(VOLATILE);
+                } else {
+                    blank_volatile(value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(blank_volatile),
+        Value::String(text) => *text = blank_ids(text),
+        _ => {}
+    }
+}
+
+/// `value` as stored in a suite: secrets redacted like the logs, and the
+/// fields that change between runs blanked, so recordings compare equal.
+pub fn sanitize(value: &Value) -> Value {
+    let mut value = redactor().redact_value(value);
+    blank_volatile(&mut value);
+    for pointer in VERSION_POINTERS {
+        if let Some(version) = value.pointer_mut(pointer) {
+            *version = json!("<version>");
+        }
+    }
+    value
+}
+
+fn parse_body(bytes: &[u8]) -> Value {
+    if bytes.is_empty() {
+        return Value::Null;
+    }
+    serde_json::from_slice(bytes)
+        .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(bytes).into_owned()))
+}
+
+/// Writes the exchanges of `serve --record-conformance <dir>`.
+pub struct ConformanceRecorder {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl ConformanceRecorder {
+    /// Starts a suite in `dir`, replacing one recorded there before, with
+    /// the cassette that records its `pcli2` runs.
+    pub fn create(dir: &Path) -> Result<(Self, Cassette)> {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create conformance suite {}", dir.display()))?;
+        let path = dir.join(EXCHANGES_FILE);
+        let file =
+            File::create(&path).with_context(|| format!("Failed to create {}", path.display()))?;
+        let cassette = Cassette::record(&dir.join(CASSETTE_FILE))?;
+        Ok((
+            Self {
+                path,
+                file: Mutex::new(file),
+            },
+            cassette,
+        ))
+    }
+
+    pub fn append(&self, exchange: &Exchange) -> Result<(), String> {
+        let line = serde_json::to_string(exchange)
+            .map_err(|err| format!("Failed to encode conformance exchange: {}", err))?;
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        writeln!(file, "{}", line)
+            .and_then(|_| file.flush())
+            .map_err(|err| format!("Failed to write {}: {}", self.path.display(), err))
+    }
+
+    /// Records a handled request and returns its response unchanged.
+    pub async fn record(
+        &self,
+        session: Option<String>,
+        request: &[u8],
+        response: Response,
+    ) -> Response {
+        let (parts, body) = response.into_parts();
+        let body = match to_bytes(body, usize::MAX).await {
+            Ok(body) => body,
+            Err(err) => {
+                tracing::warn!("⚠ conformance recording skipped a response: {}", err);
+                return Response::from_parts(parts, Body::empty());
+            }
+        };
+        let exchange = Exchange {
+            session,
+            request: sanitize(&parse_body(request)),
+            status: parts.status.as_u16(),
+            response: sanitize(&parse_body(&body)),
+        };
+        if let Err(err) = self.append(&exchange) {
+            tracing::warn!("⚠ {}", err);
+        }
+        Response::from_parts(parts, Body::from(body))
+    }
+}
+
+pub fn load_exchanges(dir: &Path) -> Result<Vec<Exchange>> {
+    let path = dir.join(EXCHANGES_FILE);
+    let raw =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    raw.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("Invalid exchange at {}:{}", path.display(), index + 1))
+        })
+        .collect()
+}
+
+/// Sends the requests of the suite in `dir` to a server built from
+/// `state`, with `pcli2` answered from the suite's cassette, and returns a
+/// description of every answer that differs from the recorded one.
+pub async fn replay_suite(dir: &Path, state: AppState) -> Result<Vec<String>> {
+    let exchanges = load_exchanges(dir)?;
+    if exchanges.is_empty() {
+        return Err(anyhow!("Conformance suite {} is empty", dir.display()));
+    }
+    let state = AppState {
+        cassette: Some(Arc::new(Cassette::replay(&dir.join(CASSETTE_FILE))?)),
+        ..state
+    };
+    let mut mismatches = Vec::new();
+    for (index, exchange) in exchanges.iter().enumerate() {
+        let mut headers = HeaderMap::new();
+        if let Some(session) = &exchange.session {
+            // The recorded session was issued by the recording server.
+            state.sessions.insert(session.clone(), ANONYMOUS_KEY);
+            headers.insert(SESSION_HEADER, HeaderValue::from_str(session)?);
+        }
+        let body = match &exchange.request {
+            Value::String(raw) => raw.clone(),
+            request => request.to_string(),
+        };
+        let response = handle_mcp(State(state.clone()), headers, Bytes::from(body)).await;
+        let status = response.status().as_u16();
+        let body = to_bytes(response.into_body(), usize::MAX).await?;
+        let actual = sanitize(&parse_body(&body));
+        let method = exchange.request["method"].as_str().unwrap_or("?");
+        if status != exchange.status {
+            mismatches.push(format!(
+                "{} exchange {} ({}): status {} instead of {}",
+                dir.display(),
+                index + 1,
+                method,
+                status,
+                exchange.status
+            ));
+        }
+        if actual != exchange.response {
+            mismatches.push(format!(
+                "{} exchange {} ({}):\n  expected {}\n  got      {}",
+                dir.display(),
+                index + 1,
+                method,
+                exchange.response,
+                actual
+            ));
+        }
+    }
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_blanks_what_changes_between_runs() {
+        let response = json!({
+            "result": {
+                "serverInfo": { "name": "pcli2-mcp", "version": "0.1.9" },
+                "content": [{ "type": "resource_link", "uri": "pcli2://results/1760000000000-3" }],
+                "_meta": { "duration_ms": 412, "cache": { "age_secs": null }, "tenant": "acme" }
+            },
+            "params": { "arguments": { "client_secret": "s3cret-value" } }
+        });
+        assert_eq!(
+            sanitize(&response),
+            json!({
+                "result": {
+                    "serverInfo": { "name": "pcli2-mcp", "version": "<version>" },
+                    "content": [{ "type": "resource_link", "uri": "pcli2://results/<id>" }],
+                    "_meta": { "duration_ms": "<volatile>", "cache": { "age_secs": null }, "tenant": "acme" }
+                },
+                "params": { "arguments": { "client_secret": "***" } }
+            })
+        );
+        assert_eq!(
+            blank_ids("see pcli2://results/17-0 and pcli2://results/"),
+            "see pcli2://results/<id> and pcli2://results/"
+        );
+        assert_eq!(
+            blank_ids("Invalid argument (request id 9f3a0c)"),
+            "Invalid argument (request id <id>)"
+        );
+    }
+}
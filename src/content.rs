@@ -0,0 +1,61 @@
+use serde_json::{Value, json};
+
+pub const AUDIENCE_USER: &str = "user";
+pub const AUDIENCE_ASSISTANT: &str = "assistant";
+
+pub const PRIORITY_SUMMARY: f64 = 1.0;
+pub const PRIORITY_IMAGE: f64 = 0.8;
+pub const PRIORITY_RESOURCE_LINK: f64 = 0.5;
+pub const PRIORITY_RAW_OUTPUT: f64 = 0.3;
+pub const PRIORITY_ENCODED_DATA: f64 = 0.1;
+
+pub fn annotations(audience: &[&str], priority: f64) -> Value {
+    json!({ "audience": audience, "priority": priority })
+}
+
+pub fn raw_output_block(text: impl Into<String>) -> Value {
+    json!({
+        "type": "text",
+        "text": text.into(),
+        "annotations": annotations(&[AUDIENCE_ASSISTANT], PRIORITY_RAW_OUTPUT)
+    })
+}
+
+pub fn summary_block(text: impl Into<String>) -> Value {
+    json!({
+        "type": "text",
+        "text": text.into(),
+        "annotations": annotations(&[AUDIENCE_USER, AUDIENCE_ASSISTANT], PRIORITY_SUMMARY)
+    })
+}
+
+pub fn tool_result(blocks: Vec<Value>) -> Value {
+    json!({ "content": blocks })
+}
+
+pub fn raw_output_result(text: impl Into<String>) -> Value {
+    tool_result(vec![raw_output_block(text)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_raw_output_block_is_for_assistant() {
+        let block = raw_output_block("a,b\n1,2");
+        assert_eq!(block["type"], "text");
+        assert_eq!(block["annotations"]["audience"], json!(["assistant"]));
+        assert_eq!(block["annotations"]["priority"], PRIORITY_RAW_OUTPUT);
+    }
+
+    #[test]
+    fn test_summary_block_is_high_priority() {
+        let block = summary_block("3 matches");
+        assert_eq!(
+            block["annotations"]["audience"],
+            json!(["user", "assistant"])
+        );
+        assert_eq!(block["annotations"]["priority"], PRIORITY_SUMMARY);
+    }
+}
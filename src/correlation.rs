@@ -0,0 +1,80 @@
+use crate::mcp::new_session_id;
+use axum::http::{HeaderMap, HeaderValue};
+use serde_json::{Value, json};
+
+/// Header carrying the correlation id of a request, both ways.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+/// Longest client-chosen id accepted; longer ones are replaced.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// The client's `X-Request-Id` when it is a usable id (printable ASCII
+/// without spaces), or a new one.
+pub fn correlation_id(headers: &HeaderMap) -> String {
+    headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LEN
+                && id.chars().all(|c| c.is_ascii_graphic())
+        })
+        .map_or_else(new_session_id, str::to_string)
+}
+
+/// Echoes the id on a response.
+pub fn set_request_id_header(headers: &mut HeaderMap, id: &str) {
+    if let Ok(value) = HeaderValue::from_str(id) {
+        headers.insert(REQUEST_ID_HEADER, value);
+    }
+}
+
+/// Names the request in an error, so it can be found in the server's logs.
+pub fn with_request_id(message: String, id: Option<&str>) -> String {
+    match id {
+        Some(id) => format!("{} (request id {})", message, id),
+        None => message,
+    }
+}
+
+/// Adds `_meta.request_id` to a tool result.
+pub fn tag_result(result: &mut Value, id: Option<&str>) {
+    if let Some(id) = id
+        && result.is_object()
+    {
+        result["_meta"]["request_id"] = json!(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_ids_are_kept_when_usable() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            REQUEST_ID_HEADER,
+            HeaderValue::from_static("agent-42/step-3"),
+        );
+        assert_eq!(correlation_id(&headers), "agent-42/step-3");
+
+        headers.insert(REQUEST_ID_HEADER, HeaderValue::from_static("two words"));
+        let generated = correlation_id(&headers);
+        assert_eq!(generated.len(), 32);
+        let long = "a".repeat(MAX_REQUEST_ID_LEN + 1);
+        headers.insert(REQUEST_ID_HEADER, HeaderValue::from_str(&long).unwrap());
+        assert_ne!(correlation_id(&headers), long);
+        assert_ne!(
+            correlation_id(&HeaderMap::new()),
+            correlation_id(&HeaderMap::new())
+        );
+
+        assert_eq!(
+            with_request_id("Tool failed".to_string(), Some("r-1")),
+            "Tool failed (request id r-1)"
+        );
+        let mut result = json!({ "content": [] });
+        tag_result(&mut result, Some("r-1"));
+        assert_eq!(result["_meta"]["request_id"], "r-1");
+    }
+}
@@ -0,0 +1,354 @@
+use crate::cli::{ARG_LOG_FILE, ARG_PID_FILE};
+use anyhow::{Result, anyhow};
+use clap::ArgMatches;
+use std::ffi::OsString;
+use std::fs::{self, OpenOptions};
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use tracing::info;
+
+const DAEMON_FLAG: &str = "--daemon";
+const LOG_FILE_FLAG: &str = "--log-file";
+/// How long `serve --daemon` waits for the server to write its PID file.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long `stop` waits for the server to exit after SIGTERM.
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+fn pid_file_path(matches: &ArgMatches) -> Result<&PathBuf> {
+    matches
+        .get_one::<PathBuf>(ARG_PID_FILE)
+        .ok_or_else(|| anyhow!("missing --pid-file"))
+}
+
+fn check_supported(what: &str) -> Result<()> {
+    if cfg!(unix) {
+        Ok(())
+    } else {
+        Err(anyhow!("{} is only supported on Unix", what))
+    }
+}
+
+/// The process id in the PID file at `path`, or `None` without one.
+pub fn read_pid(path: &Path) -> Result<Option<u32>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(anyhow!("Failed to read {}: {}", path.display(), err)),
+    };
+    match text.trim().parse::<u32>() {
+        Ok(pid) if pid > 0 && i32::try_from(pid).is_ok() => Ok(Some(pid)),
+        _ => Err(anyhow!(
+            "{} does not hold a process id: {:?}",
+            path.display(),
+            text.trim()
+        )),
+    }
+}
+
+/// Whether a process with this id exists.
+#[cfg(unix)]
+pub fn is_running(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // SAFETY: signal 0 only checks that the process exists.
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return true;
+    }
+    // The process exists but belongs to another user.
+    io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+pub fn is_running(_pid: u32) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn terminate(pid: u32) -> Result<()> {
+    let pid = libc::pid_t::try_from(pid).map_err(|_| anyhow!("invalid process id {}", pid))?;
+    // SAFETY: sends SIGTERM to a single, positive process id.
+    if unsafe { libc::kill(pid, libc::SIGTERM) } != 0 {
+        return Err(anyhow!(
+            "Failed to stop process {}: {}",
+            pid,
+            io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn terminate(_pid: u32) -> Result<()> {
+    check_supported("stop")
+}
+
+fn ensure_not_running(path: &Path) -> Result<()> {
+    match read_pid(path)? {
+        Some(pid) if pid != std::process::id() && is_running(pid) => Err(anyhow!(
+            "pcli2-mcp is already running (pid {}, {})",
+            pid,
+            path.display()
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// The PID file of a running server; removed when the server stops.
+pub struct PidFile {
+    path: PathBuf,
+    pid: u32,
+}
+
+impl PidFile {
+    /// Writes this process's id to `path`. A file left by a server that
+    /// is no longer running is replaced.
+    pub fn create(path: &Path) -> Result<Self> {
+        ensure_not_running(path)?;
+        let pid = std::process::id();
+        fs::write(path, format!("{}\n", pid))
+            .map_err(|err| anyhow!("Failed to write {}: {}", path.display(), err))?;
+        info!("wrote process id {} to {}", pid, path.display());
+        Ok(Self {
+            path: path.to_path_buf(),
+            pid,
+        })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        // Another server may have taken the file over since.
+        if read_pid(&self.path).ok().flatten() == Some(self.pid) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Runs `server` until it ends or the process receives SIGTERM, which is
+/// how `stop` asks the server to exit.
+pub async fn until_terminated(server: impl Future<Output = io::Result<()>>) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            result = server => result,
+            _ = terminate.recv() => {
+                info!("SIGTERM received, stopping the server");
+                Ok(())
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        server.await
+    }
+}
+
+/// Arguments for the background server: ours without `--daemon` and
+/// `--log-file`, which only the starting process uses.
+fn daemon_args(args: impl Iterator<Item = OsString>) -> Vec<OsString> {
+    let mut kept = Vec::new();
+    let mut args = args.skip(1);
+    while let Some(arg) = args.next() {
+        if arg == DAEMON_FLAG {
+            continue;
+        }
+        if arg == LOG_FILE_FLAG {
+            args.next();
+            continue;
+        }
+        let inline_log_file = arg
+            .to_str()
+            .is_some_and(|arg| arg.starts_with(&format!("{}=", LOG_FILE_FLAG)));
+        if !inline_log_file {
+            kept.push(arg);
+        }
+    }
+    kept
+}
+
+/// `serve --daemon`: starts the same `serve` command as a background
+/// process and returns once it is listening and has written its PID file.
+pub async fn start_daemon(matches: &ArgMatches) -> Result<()> {
+    check_supported("serve --daemon")?;
+    let pid_file = pid_file_path(matches)?;
+    ensure_not_running(pid_file)?;
+
+    let (stdout, stderr) = match matches.get_one::<PathBuf>(ARG_LOG_FILE) {
+        Some(path) => {
+            let log = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|err| anyhow!("Failed to open {}: {}", path.display(), err))?;
+            (Stdio::from(log.try_clone()?), Stdio::from(log))
+        }
+        None => (Stdio::null(), Stdio::null()),
+    };
+    let mut command = Command::new(std::env::current_exe()?);
+    command
+        .args(daemon_args(std::env::args_os()))
+        .stdin(Stdio::null())
+        .stdout(stdout)
+        .stderr(stderr);
+    // Its own process group, so Ctrl+C in this terminal does not reach it.
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    let mut child = command.spawn()?;
+    let pid = child.id();
+
+    let deadline = Instant::now() + STARTUP_TIMEOUT;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Err(anyhow!(
+                "pcli2-mcp exited during startup ({}); see --log-file for the reason",
+                status
+            ));
+        }
+        if read_pid(pid_file).ok().flatten() == Some(pid) {
+            println!("pcli2-mcp is running in the background (pid {})", pid);
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            return Err(anyhow!(
+                "pcli2-mcp (pid {}) did not write {} within {}s",
+                pid,
+                pid_file.display(),
+                STARTUP_TIMEOUT.as_secs()
+            ));
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Sends SIGTERM to `pid` and waits up to `timeout` for it to exit.
+async fn stop_process(pid: u32, timeout: Duration) -> Result<()> {
+    terminate(pid)?;
+    let deadline = Instant::now() + timeout;
+    while is_running(pid) {
+        if Instant::now() >= deadline {
+            return Err(anyhow!(
+                "pcli2-mcp (pid {}) is still running {}s after SIGTERM",
+                pid,
+                timeout.as_secs()
+            ));
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+    Ok(())
+}
+
+/// `stop`: stops the server of a PID file and waits for it to exit.
+pub async fn run_stop(matches: &ArgMatches) -> Result<()> {
+    check_supported("stop")?;
+    let path = pid_file_path(matches)?;
+    let Some(pid) = read_pid(path)? else {
+        println!("pcli2-mcp is not running (no {})", path.display());
+        return Ok(());
+    };
+    if is_running(pid) {
+        stop_process(pid, STOP_TIMEOUT).await?;
+        println!("pcli2-mcp stopped (pid {})", pid);
+    } else {
+        println!("pcli2-mcp is not running (pid {} is gone)", pid);
+    }
+    // The server removes its PID file itself; this covers a killed one.
+    if read_pid(path).ok().flatten() == Some(pid) {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// `status`: succeeds when the server of a PID file is running.
+pub fn run_status(matches: &ArgMatches) -> Result<()> {
+    check_supported("status")?;
+    let path = pid_file_path(matches)?;
+    match read_pid(path)? {
+        Some(pid) if is_running(pid) => {
+            println!("pcli2-mcp is running (pid {})", pid);
+            Ok(())
+        }
+        Some(pid) => Err(anyhow!(
+            "pcli2-mcp is not running; {} names pid {}, which is gone",
+            path.display(),
+            pid
+        )),
+        None => Err(anyhow!("pcli2-mcp is not running (no {})", path.display())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_pid_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("pcli2-mcp-{}-{}.pid", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_daemon_args_drop_the_flag() {
+        let args = [
+            "pcli2-mcp",
+            "serve",
+            "--daemon",
+            "--log-file",
+            "/var/log/a.log",
+            "--pid-file",
+            "/run/a.pid",
+            "--log-file=/var/log/b.log",
+        ]
+        .into_iter()
+        .map(OsString::from);
+        assert_eq!(
+            daemon_args(args),
+            ["serve", "--pid-file", "/run/a.pid"].map(OsString::from)
+        );
+    }
+
+    #[test]
+    fn test_pid_file_is_removed_on_drop() {
+        let path = temp_pid_file("drop");
+        let _ = fs::remove_file(&path);
+        let pid_file = PidFile::create(&path).unwrap();
+        assert_eq!(read_pid(&path).unwrap(), Some(std::process::id()));
+        drop(pid_file);
+        assert!(!path.exists());
+        assert_eq!(read_pid(&path).unwrap(), None);
+
+        fs::write(&path, "not a pid").unwrap();
+        assert!(read_pid(&path).is_err());
+        fs::write(&path, "0\n").unwrap();
+        assert!(read_pid(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_running_server_blocks_a_second_and_stops() {
+        let path = temp_pid_file("running");
+        let mut sleeper = Command::new("sleep").arg("30").spawn().unwrap();
+        let pid = sleeper.id();
+        // Reap the child as soon as it exits, as init would for a daemon.
+        let reaper = std::thread::spawn(move || sleeper.wait());
+        fs::write(&path, format!("{}\n", pid)).unwrap();
+
+        let err = PidFile::create(&path).err().unwrap();
+        assert!(err.to_string().contains("already running"));
+        assert!(is_running(pid));
+        stop_process(pid, STOP_TIMEOUT).await.unwrap();
+        assert!(!is_running(pid));
+        assert!(reaper.join().unwrap().is_ok());
+
+        // A stale file is taken over.
+        let pid_file = PidFile::create(&path).unwrap();
+        assert_eq!(read_pid(&path).unwrap(), Some(std::process::id()));
+        drop(pid_file);
+    }
+}
@@ -0,0 +1,145 @@
+use crate::content::summary_block;
+use serde_json::{Value, json};
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+/// Request parameter (or `_meta` entry) with the client's timeout.
+pub const TIMEOUT_PARAM: &str = "timeoutMs";
+/// Kept back from the client's timeout for the server's own work, so the
+/// answer arrives before the client gives up.
+pub const DEADLINE_OVERHEAD: Duration = Duration::from_millis(250);
+
+/// The client's deadline for one tool call. `pcli2` runs are cut short at
+/// `runs_until`; the call as a whole gives up at `expires`.
+#[derive(Debug, Clone)]
+pub struct Deadline {
+    requested: Duration,
+    runs_until: Instant,
+    expires: Instant,
+    /// Set once a `pcli2` run was cut short or skipped for the deadline.
+    hit: Arc<AtomicBool>,
+}
+
+impl Deadline {
+    pub fn new(requested: Duration) -> Self {
+        let now = Instant::now();
+        Self {
+            requested,
+            runs_until: now + requested.saturating_sub(DEADLINE_OVERHEAD),
+            expires: now + requested,
+            hit: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Reads `timeoutMs` from the call's parameters or their `_meta`.
+    pub fn from_params(params: &Value) -> Result<Option<Self>, String> {
+        let Some(value) = params
+            .get(TIMEOUT_PARAM)
+            .or_else(|| params.get("_meta").and_then(|meta| meta.get(TIMEOUT_PARAM)))
+        else {
+            return Ok(None);
+        };
+        match value.as_u64() {
+            Some(millis) if millis > 0 => Ok(Some(Self::new(Duration::from_millis(millis)))),
+            _ => Err(format!(
+                "Invalid '{}': expected a positive number of milliseconds",
+                TIMEOUT_PARAM
+            )),
+        }
+    }
+
+    pub fn requested_ms(&self) -> u128 {
+        self.requested.as_millis()
+    }
+
+    /// Time left for `pcli2` runs.
+    pub fn remaining(&self) -> Duration {
+        self.runs_until.saturating_duration_since(Instant::now())
+    }
+
+    /// When the call gives up on runs that did not stop in time.
+    pub fn expires(&self) -> Instant {
+        self.expires
+    }
+
+    pub fn mark_hit(&self) {
+        self.hit.store(true, Ordering::SeqCst);
+    }
+
+    pub fn was_hit(&self) -> bool {
+        self.hit.load(Ordering::SeqCst)
+    }
+
+    /// Appends the deadline to the error of a call it cut short.
+    pub fn explain(&self, message: String) -> String {
+        format!(
+            "{} (the client's timeoutMs of {} ms was exceeded)",
+            message,
+            self.requested_ms()
+        )
+    }
+
+    /// Flags a result assembled after some runs were cut short: it stays an
+    /// answer, but `isError` and `_meta.deadline` say it is incomplete.
+    pub fn mark_partial(&self, result: &mut Value) {
+        if let Some(content) = result.get_mut("content").and_then(Value::as_array_mut) {
+            content.insert(
+                0,
+                summary_block(format!(
+                    "Partial result: the client's timeoutMs of {} ms passed before every pcli2 run finished. Retry with a longer timeout for the rest.",
+                    self.requested_ms()
+                )),
+            );
+        }
+        result["isError"] = json!(true);
+        result["_meta"]["deadline"] = json!({
+            "timeout_ms": self.requested_ms(),
+            "partial": true
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::tool_result;
+
+    #[test]
+    fn test_timeout_is_read_from_params_or_meta() {
+        let deadline = Deadline::from_params(&json!({ "timeoutMs": 2000 }))
+            .unwrap()
+            .unwrap();
+        assert_eq!(deadline.requested_ms(), 2000);
+        assert!(deadline.remaining() <= Duration::from_millis(1750));
+        let meta = json!({ "_meta": { "timeoutMs": 500 } });
+        assert!(Deadline::from_params(&meta).unwrap().is_some());
+        assert!(Deadline::from_params(&json!({})).unwrap().is_none());
+        assert!(Deadline::from_params(&json!({ "timeoutMs": 0 })).is_err());
+        assert!(Deadline::from_params(&json!({ "timeoutMs": "soon" })).is_err());
+
+        // Shorter than the overhead: nothing is left for pcli2.
+        let tight = Deadline::new(Duration::from_millis(100));
+        assert_eq!(tight.remaining(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_partial_results_are_flagged() {
+        let deadline = Deadline::new(Duration::from_secs(1));
+        let mut result = tool_result(vec![summary_block("2 of 3 inputs matched".to_string())]);
+        deadline.mark_partial(&mut result);
+        assert_eq!(result["isError"], true);
+        assert_eq!(result["_meta"]["deadline"]["partial"], true);
+        assert!(
+            result["content"][0]["text"]
+                .as_str()
+                .unwrap()
+                .starts_with("Partial result")
+        );
+        assert_eq!(result["content"][1]["text"], "2 of 3 inputs matched");
+    }
+}
@@ -0,0 +1,294 @@
+use crate::{
+    AppState,
+    content::{raw_output_block, summary_block, tool_result},
+    pcli::{parse_string_list, run_pcli2_folder_dependencies, validate_range_u64},
+    resources::StoredResource,
+};
+use serde_json::{Value, json};
+use std::sync::Arc;
+
+pub const DEPENDENCIES_URI_PREFIX: &str = "pcli2://dependencies/";
+pub const DEFAULT_DEPENDENCY_PAGE_SIZE: u64 = 50;
+pub const MAX_DEPENDENCY_PAGE_SIZE: u64 = 500;
+pub const MAX_DEPENDENCY_DEPTH: u64 = 50;
+/// Keys `pcli2` may nest a node's dependencies under.
+const CHILD_KEYS: [&str; 2] = ["dependencies", "children"];
+
+/// Whether the call asks for a server-side view of the tree.
+pub fn wants_view(args: &Value) -> bool {
+    ["max_depth", "page", "page_size", "root"]
+        .iter()
+        .any(|key| args.get(*key).is_some())
+}
+
+/// The `pcli2://dependencies/...` URI holding one folder's full tree.
+pub fn dependencies_uri(folder_path: &str, tenant: Option<&str>) -> String {
+    let mut uri = format!(
+        "{}{}",
+        DEPENDENCIES_URI_PREFIX,
+        folder_path.trim_matches('/')
+    );
+    if let Some(tenant) = tenant {
+        uri.push_str(&format!("?tenant={}", tenant));
+    }
+    uri
+}
+
+fn children_mut(node: &mut Value) -> Option<&mut Vec<Value>> {
+    let key = CHILD_KEYS
+        .iter()
+        .find(|key| node.get(**key).is_some_and(Value::is_array))?;
+    node.get_mut(*key).and_then(Value::as_array_mut)
+}
+
+fn children(node: &Value) -> &[Value] {
+    CHILD_KEYS
+        .iter()
+        .find_map(|key| node.get(*key).and_then(Value::as_array))
+        .map_or(&[], Vec::as_slice)
+}
+
+/// Cuts `node` below `max_depth` levels (the node itself is level 1); a cut
+/// node keeps its children's count as `truncated_children`.
+fn prune(node: &mut Value, max_depth: u64) {
+    let Some(children) = children_mut(node) else {
+        return;
+    };
+    if max_depth <= 1 {
+        let count = children.len();
+        if count > 0 {
+            children.clear();
+            node["truncated_children"] = json!(count);
+        }
+        return;
+    }
+    for child in children {
+        prune(child, max_depth - 1);
+    }
+}
+
+fn is_truncated(node: &Value) -> bool {
+    node.get("truncated_children").is_some() || children(node).iter().any(is_truncated)
+}
+
+/// The first node whose `path` or `uuid` is `root`, searching depth first.
+fn find_node<'a>(nodes: &'a [Value], root: &str) -> Option<&'a Value> {
+    nodes.iter().find_map(|node| {
+        if node["path"] == root || node["uuid"] == root {
+            Some(node)
+        } else {
+            find_node(children(node), root)
+        }
+    })
+}
+
+/// Top-level nodes of `pcli2 folder dependencies --format json` output.
+fn tree_nodes(output: &str) -> Result<Vec<Value>, String> {
+    match serde_json::from_str::<Value>(output) {
+        Ok(Value::Array(nodes)) => Ok(nodes),
+        Ok(Value::Object(mut obj)) => match obj.remove("items") {
+            Some(Value::Array(nodes)) => Ok(nodes),
+            _ => Ok(vec![Value::Object(obj)]),
+        },
+        _ => Err("pcli2 folder dependencies did not return a JSON tree".to_string()),
+    }
+}
+
+/// One folder's tree, from the cached resource unless `reload` is set.
+/// A fresh tree is stored and announced to subscribers before the next
+/// folder runs, so clients can start on it early.
+async fn folder_tree(
+    state: &AppState,
+    args: &Value,
+    folder_path: &str,
+) -> Result<(String, Vec<Value>), String> {
+    let tenant = args.get("tenant").and_then(Value::as_str);
+    let uri = dependencies_uri(folder_path, tenant);
+    let reload = args.get("reload").and_then(Value::as_bool).unwrap_or(false);
+    if !reload && let Some(cached) = state.resources.get(&uri) {
+        return Ok((uri, tree_nodes(&cached.text)?));
+    }
+    let mut single = json!({ "folder_path": [folder_path], "format": "json" });
+    for key in ["tenant", "metadata"] {
+        if let Some(value) = args.get(key) {
+            single[key] = value.clone();
+        }
+    }
+    let output = run_pcli2_folder_dependencies(single)
+        .await
+        .map_err(|message| format!("pcli2 folder dependencies failed: {}", message))?;
+    let nodes = tree_nodes(&output)?;
+    state.resources.replace(StoredResource {
+        uri: uri.clone(),
+        name: format!("dependencies of {}", folder_path),
+        mime_type: "application/json".to_string(),
+        text: Arc::new(output),
+        tenant: tenant.map(str::to_string),
+        blob: false,
+    });
+    state.notifier.resource_updated(&uri);
+    Ok((uri, nodes))
+}
+
+/// `pcli2_folder_dependencies` with `max_depth`, `page`, `page_size`, or
+/// `root`: the trees are fetched folder by folder and returned a page of
+/// top-level assets at a time, cut at `max_depth`.
+pub async fn folder_dependencies_view(state: &AppState, args: Value) -> Result<Value, String> {
+    validate_range_u64(&args, "max_depth", 1, MAX_DEPENDENCY_DEPTH)?;
+    validate_range_u64(&args, "page", 1, u64::MAX)?;
+    validate_range_u64(&args, "page_size", 1, MAX_DEPENDENCY_PAGE_SIZE)?;
+    let folder_paths = parse_string_list(&args, "folder_path");
+    if folder_paths.is_empty() {
+        return Err("Missing required argument: 'folder_path'".to_string());
+    }
+    let page = args.get("page").and_then(Value::as_u64).unwrap_or(1);
+    let page_size = args
+        .get("page_size")
+        .and_then(Value::as_u64)
+        .unwrap_or(DEFAULT_DEPENDENCY_PAGE_SIZE);
+    let max_depth = args.get("max_depth").and_then(Value::as_u64);
+
+    let mut nodes = Vec::new();
+    let mut resources = Vec::new();
+    let mut results = Vec::with_capacity(folder_paths.len());
+    let mut errors = Vec::new();
+    for folder_path in &folder_paths {
+        match folder_tree(state, &args, folder_path).await {
+            Ok((uri, tree)) => {
+                results
+                    .push(json!({ "folder_path": folder_path, "status": "ok", "resource": uri }));
+                resources.push(uri);
+                nodes.extend(tree);
+            }
+            // A single folder fails as before; in a list, the others still count.
+            Err(message) if folder_paths.len() == 1 => return Err(message),
+            Err(message) => {
+                errors.push(format!("{}: {}", folder_path, message));
+                results.push(
+                    json!({ "folder_path": folder_path, "status": "error", "error": message }),
+                );
+            }
+        }
+    }
+    if resources.is_empty() {
+        return Err(format!(
+            "pcli2 folder dependencies failed for every folder: {}",
+            errors.join("; ")
+        ));
+    }
+    if let Some(root) = args.get("root").and_then(Value::as_str) {
+        let node = find_node(&nodes, root)
+            .ok_or_else(|| format!("No asset '{}' in the dependency tree", root))?;
+        nodes = children(node).to_vec();
+    }
+
+    let total = nodes.len() as u64;
+    let start = (page - 1).saturating_mul(page_size);
+    let mut items: Vec<Value> = nodes
+        .into_iter()
+        .skip(usize::try_from(start).unwrap_or(usize::MAX))
+        .take(page_size as usize)
+        .collect();
+    if let Some(max_depth) = max_depth {
+        for item in &mut items {
+            prune(item, max_depth);
+        }
+    }
+    let next_page = (start.saturating_add(page_size) < total).then_some(page + 1);
+    let truncated = items.iter().any(is_truncated);
+
+    let mut headline = format!(
+        "Page {} of the dependency tree: {} of {} assets.",
+        page,
+        items.len(),
+        total
+    );
+    if let Some(next_page) = next_page {
+        headline.push_str(&format!(" Pass page: {} for more.", next_page));
+    }
+    if !errors.is_empty() {
+        headline.push_str(&format!(
+            " {} of {} folders failed and are left out; see results.",
+            errors.len(),
+            folder_paths.len()
+        ));
+    }
+    if truncated {
+        headline.push_str(
+            " Nodes with truncated_children were cut at max_depth; pass their path as root for the next levels.",
+        );
+    }
+    let text = serde_json::to_string_pretty(&json!({
+        "items": items,
+        "page": page,
+        "page_size": page_size,
+        "total": total,
+        "next_page": next_page,
+        "max_depth": max_depth,
+        "resources": resources,
+        "results": results
+    }))
+    .map_err(|err| format!("Failed to render dependency tree: {}", err))?;
+    Ok(tool_result(vec![
+        summary_block(headline),
+        raw_output_block(text),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> Vec<Value> {
+        tree_nodes(
+            r#"[
+                {"path": "/Root/A.asm", "dependencies": [
+                    {"path": "/Root/B.asm", "dependencies": [{"path": "/Root/C.prt", "dependencies": []}]},
+                    {"path": "/Root/D.prt"}
+                ]},
+                {"path": "/Root/E.prt", "children": []}
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_prune_keeps_the_cut_count() {
+        let mut nodes = tree();
+        prune(&mut nodes[0], 2);
+        assert_eq!(nodes[0]["dependencies"][0]["truncated_children"], 1);
+        assert_eq!(nodes[0]["dependencies"][0]["dependencies"], json!([]));
+        assert!(
+            nodes[0]["dependencies"][1]
+                .get("truncated_children")
+                .is_none()
+        );
+        assert!(is_truncated(&nodes[0]));
+
+        prune(&mut nodes[1], 1);
+        assert!(nodes[1].get("truncated_children").is_none());
+    }
+
+    #[test]
+    fn test_find_node_searches_depth_first() {
+        let nodes = tree();
+        let node = find_node(&nodes, "/Root/B.asm").unwrap();
+        assert_eq!(children(node)[0]["path"], "/Root/C.prt");
+        assert!(find_node(&nodes, "/Root/Z.prt").is_none());
+    }
+
+    #[test]
+    fn test_uri_and_options() {
+        assert_eq!(
+            dependencies_uri("/Root/Assemblies/", Some("acme")),
+            "pcli2://dependencies/Root/Assemblies?tenant=acme"
+        );
+        assert!(wants_view(&json!({ "max_depth": 2 })));
+        assert!(!wants_view(&json!({ "format": "tree" })));
+        assert_eq!(
+            tree_nodes(r#"{"items": [{"path": "/Root/A.asm"}]}"#).unwrap(),
+            vec![json!({ "path": "/Root/A.asm" })]
+        );
+        assert!(tree_nodes("A.asm\n  B.prt").is_err());
+    }
+}
@@ -0,0 +1,471 @@
+use crate::{
+    AppState,
+    approval::APPROVAL_STATUS_TOOL,
+    args::{describe, resolve_properties},
+    cli::{ARG_FORMAT, ARG_POLICY, FORMAT_HTML},
+    config::{QuotaRule, ToolPriority},
+    mcp::server_info,
+    pcli::{public_tool_list, resolve_tool},
+    policy::is_destructive,
+    server::{build_state, load_config},
+    tenancy::tenant_scoped,
+};
+use anyhow::{Result, anyhow};
+use clap::ArgMatches;
+use serde_json::Value;
+
+/// One parameter of a tool's input schema.
+struct Parameter {
+    name: String,
+    kind: String,
+    required: bool,
+    description: String,
+}
+
+/// A tool as the document shows it: what a client sees in `tools/list`,
+/// plus the rules the server applies to its calls.
+struct ToolDoc {
+    name: String,
+    description: String,
+    parameters: Vec<Parameter>,
+    examples: Vec<(String, String)>,
+    notes: Vec<String>,
+}
+
+fn quota_note(name: &str, rule: &QuotaRule) -> String {
+    match rule.window_secs {
+        Some(window) => format!(
+            "Quota `{}`: {} calls per session in {}s.",
+            name, rule.max_calls, window
+        ),
+        None => format!("Quota `{}`: {} calls per session.", name, rule.max_calls),
+    }
+}
+
+fn quota_applies(rule: &QuotaRule, tool: &str) -> bool {
+    rule.tools.is_empty() || rule.tools.iter().any(|name| name == tool)
+}
+
+/// What the configuration does to calls of `tool`, in the words of the docs.
+fn tool_notes(state: &AppState, tool: &str, schema: &Value, structured: bool) -> Vec<String> {
+    let config = &state.config;
+    let mut notes = Vec::new();
+    if is_destructive(tool) {
+        notes.push(
+            "Changes assets, credentials, or pcli2's tenant; refused to API keys whose policy does not allow `destructive` tools.".to_string(),
+        );
+    }
+    if config.approval.requires(tool) {
+        notes.push(format!(
+            "Held for an operator's approval before it runs; check on it with `{}`.",
+            config.tools.public_name(APPROVAL_STATUS_TOOL)
+        ));
+    }
+    if !tenant_scoped(tool, schema) {
+        notes.push("Not available to API keys bound to a tenant.".to_string());
+    }
+    if config.health.priorities.get(tool) == Some(&ToolPriority::Low) {
+        notes.push("Low priority: refused while the pcli2 backend is degraded.".to_string());
+    }
+    if config.execution.tools.contains_key(tool)
+        || config.execution.default.timeout_secs.is_some()
+        || config.execution.default.retries.is_some()
+        || config.execution.default.concurrency.is_some()
+    {
+        let policy = config.execution.policy(tool);
+        let mut note = format!("Times out after {}s", policy.timeout.as_secs());
+        if policy.retries > 0 {
+            note.push_str(&format!(", retried up to {} times", policy.retries));
+        }
+        if let Some(concurrency) = policy.concurrency {
+            note.push_str(&format!(", at most {} at once", concurrency));
+        }
+        note.push('.');
+        notes.push(note);
+    }
+    let mut quotas: Vec<_> = config
+        .quotas
+        .iter()
+        .filter(|(_, rule)| quota_applies(rule, tool))
+        .collect();
+    if let Some(policy) = state
+        .policy
+        .as_deref()
+        .and_then(|name| config.policies.get(name))
+    {
+        quotas.extend(
+            policy
+                .quotas
+                .iter()
+                .filter(|(_, rule)| quota_applies(rule, tool)),
+        );
+    }
+    quotas.sort_by(|a, b| a.0.cmp(b.0));
+    notes.extend(
+        quotas
+            .into_iter()
+            .map(|(name, rule)| quota_note(name, rule)),
+    );
+    if structured {
+        notes.push("Returns `structuredContent` that follows its output schema.".to_string());
+    }
+    notes
+}
+
+fn tool_docs(state: &AppState) -> Vec<ToolDoc> {
+    public_tool_list(state)
+        .into_iter()
+        .filter_map(|tool| {
+            let name = tool["name"].as_str()?.to_string();
+            let (internal, _) = resolve_tool(state, &name)?;
+            let schema = &tool["inputSchema"];
+            let required: Vec<&str> = schema["required"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .collect();
+            let mut parameters: Vec<Parameter> = resolve_properties(schema)
+                .into_iter()
+                .map(|(key, prop)| Parameter {
+                    required: required.contains(&key.as_str()),
+                    kind: describe(&prop),
+                    description: prop["description"].as_str().unwrap_or_default().to_string(),
+                    name: key,
+                })
+                .collect();
+            parameters.sort_by(|a, b| b.required.cmp(&a.required).then(a.name.cmp(&b.name)));
+            let examples = tool["_meta"]["examples"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|example| {
+                    (
+                        example["description"]
+                            .as_str()
+                            .unwrap_or_default()
+                            .to_string(),
+                        example["arguments"].to_string(),
+                    )
+                })
+                .collect();
+            // The examples are listed on their own, not inside the description.
+            let description = tool["description"]
+                .as_str()
+                .unwrap_or_default()
+                .split("\n\n")
+                .next()
+                .unwrap_or_default()
+                .to_string();
+            let notes = tool_notes(state, &internal, schema, tool.get("outputSchema").is_some());
+            Some(ToolDoc {
+                name,
+                description,
+                parameters,
+                examples,
+                notes,
+            })
+        })
+        .collect()
+}
+
+/// Rules that hold across tools: the policy the document is written for,
+/// tenant defaults, and server-wide limits.
+fn policy_notes(state: &AppState) -> Vec<String> {
+    let config = &state.config;
+    let mut notes = Vec::new();
+    if let Some((name, policy)) = state
+        .policy
+        .as_deref()
+        .and_then(|name| config.policies.get_key_value(name))
+    {
+        notes.push(format!(
+            "Written for API keys held to policy `{}`; tools it does not allow are left out.",
+            name
+        ));
+        if !policy.tenants.is_empty() {
+            notes.push(format!(
+                "Calls may only name the tenants {}.",
+                policy
+                    .tenants
+                    .iter()
+                    .map(|tenant| format!("`{}`", tenant))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        if !policy.destructive {
+            notes.push(
+                "Tools that change assets, credentials, or pcli2's tenant are not allowed."
+                    .to_string(),
+            );
+        }
+    }
+    let mut tenants: Vec<_> = config.tenants.iter().collect();
+    tenants.sort_by(|a, b| a.0.cmp(b.0));
+    for (tenant, defaults) in tenants {
+        let mut set = Vec::new();
+        if let Some(threshold) = defaults.threshold {
+            set.push(format!("`threshold` {}", threshold));
+        }
+        if let Some(format) = &defaults.format {
+            set.push(format!("`format` {}", format));
+        }
+        if let Some(concurrency) = defaults.concurrency {
+            set.push(format!("concurrency {}", concurrency));
+        }
+        if !set.is_empty() {
+            notes.push(format!(
+                "Calls in tenant `{}` default to {}.",
+                tenant,
+                set.join(", ")
+            ));
+        }
+    }
+    if let Some(max) = config.execution.max_concurrent_calls {
+        notes.push(format!(
+            "At most {} pcli2 calls run at once; others wait their turn.",
+            max
+        ));
+    }
+    if notes.is_empty() {
+        if config.quotas.is_empty() && !config.approval.is_enabled() {
+            notes.push(
+                "No policy, approval, quota, or tenant default narrows the tools below."
+                    .to_string(),
+            );
+        } else {
+            notes.push("Approvals and quotas are listed with the tools they apply to.".to_string());
+        }
+    }
+    notes
+}
+
+fn title(state: &AppState) -> String {
+    let info = server_info(state);
+    let name = info["title"]
+        .as_str()
+        .or(info["name"].as_str())
+        .unwrap_or_default();
+    format!("{} tools", name)
+}
+
+fn summary(state: &AppState, tools: usize) -> String {
+    let info = server_info(state);
+    format!(
+        "Generated from the tool catalog of {} {}: {} tools, as an MCP client sees them under this configuration.",
+        info["name"].as_str().unwrap_or_default(),
+        info["version"].as_str().unwrap_or_default(),
+        tools
+    )
+}
+
+pub fn render_markdown(state: &AppState) -> String {
+    let tools = tool_docs(state);
+    let mut out = format!(
+        "# {}\n\n{}\n\n## Policies\n\n",
+        title(state),
+        summary(state, tools.len())
+    );
+    for note in policy_notes(state) {
+        out.push_str(&format!("- {}\n", note));
+    }
+    out.push_str("\n## Tools\n");
+    for tool in &tools {
+        out.push_str(&format!("\n### `{}`\n\n{}\n", tool.name, tool.description));
+        if !tool.parameters.is_empty() {
+            out.push_str("\n| Parameter | Type | Required | Description |\n|---|---|---|---|\n");
+            for parameter in &tool.parameters {
+                out.push_str(&format!(
+                    "| `{}` | {} | {} | {} |\n",
+                    parameter.name,
+                    parameter.kind.replace('|', "\\|"),
+                    if parameter.required { "yes" } else { "no" },
+                    parameter.description.replace('|', "\\|")
+                ));
+            }
+        }
+        if !tool.notes.is_empty() {
+            out.push('\n');
+            for note in &tool.notes {
+                out.push_str(&format!("- {}\n", note));
+            }
+        }
+        if !tool.examples.is_empty() {
+            out.push_str("\nExamples:\n");
+            for (description, arguments) in &tool.examples {
+                out.push_str(&format!(
+                    "\n{}:\n\n```json\n{}\n```\n",
+                    description, arguments
+                ));
+            }
+        }
+    }
+    out
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Escapes `text` and turns its `code` spans into `<code>` elements.
+fn inline_html(text: &str) -> String {
+    escape(text)
+        .split('`')
+        .enumerate()
+        .map(|(index, part)| {
+            if index % 2 == 1 {
+                format!("<code>{}</code>", part)
+            } else {
+                part.to_string()
+            }
+        })
+        .collect()
+}
+
+pub fn render_html(state: &AppState) -> String {
+    let tools = tool_docs(state);
+    let title = escape(&title(state));
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n<h1>{}</h1>\n<p>{}</p>\n<h2>Policies</h2>\n<ul>\n",
+        title,
+        title,
+        escape(&summary(state, tools.len()))
+    );
+    for note in policy_notes(state) {
+        out.push_str(&format!("<li>{}</li>\n", inline_html(&note)));
+    }
+    out.push_str("</ul>\n<h2>Tools</h2>\n");
+    for tool in &tools {
+        out.push_str(&format!(
+            "<h3 id=\"{}\"><code>{}</code></h3>\n<p>{}</p>\n",
+            escape(&tool.name),
+            escape(&tool.name),
+            inline_html(&tool.description)
+        ));
+        if !tool.parameters.is_empty() {
+            out.push_str("<table>\n<tr><th>Parameter</th><th>Type</th><th>Required</th><th>Description</th></tr>\n");
+            for parameter in &tool.parameters {
+                out.push_str(&format!(
+                    "<tr><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                    escape(&parameter.name),
+                    escape(&parameter.kind),
+                    if parameter.required { "yes" } else { "no" },
+                    inline_html(&parameter.description)
+                ));
+            }
+            out.push_str("</table>\n");
+        }
+        if !tool.notes.is_empty() {
+            out.push_str("<ul>\n");
+            for note in &tool.notes {
+                out.push_str(&format!("<li>{}</li>\n", inline_html(note)));
+            }
+            out.push_str("</ul>\n");
+        }
+        if !tool.examples.is_empty() {
+            out.push_str("<p>Examples:</p>\n");
+            for (description, arguments) in &tool.examples {
+                out.push_str(&format!(
+                    "<p>{}:</p>\n<pre><code>{}</code></pre>\n",
+                    escape(description),
+                    escape(arguments)
+                ));
+            }
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+pub fn run_docs(matches: &ArgMatches) -> Result<()> {
+    let config = load_config(matches)?;
+    let policy = matches.get_one::<String>(ARG_POLICY).cloned();
+    if let Some(policy) = &policy
+        && !config.policies.contains_key(policy)
+    {
+        return Err(anyhow!("Policy '{}' is not configured", policy));
+    }
+    let state = AppState {
+        policy,
+        ..build_state(config)?
+    };
+    let document = match matches.get_one::<String>(ARG_FORMAT).map(String::as_str) {
+        Some(FORMAT_HTML) => render_html(&state),
+        _ => render_markdown(&state),
+    };
+    print!("{}", document);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::ServerConfig, pcli::tool_list};
+    use std::sync::Arc;
+
+    fn state(config: serde_json::Value, policy: Option<&str>) -> AppState {
+        AppState {
+            config: Arc::new(serde_json::from_value::<ServerConfig>(config).unwrap()),
+            policy: policy.map(str::to_string),
+            ..AppState::new("pcli2-mcp", "0.0.0")
+        }
+    }
+
+    #[test]
+    fn test_markdown_covers_the_catalog_and_its_rules() {
+        let state = state(
+            serde_json::json!({
+                "approval": { "tools": ["pcli2_tenant_use"] },
+                "quotas": { "matches": { "tools": ["pcli2_asset_part_match"], "max_calls": 5, "window_secs": 60 } },
+                "tools": { "aliases": { "pcli2_asset_get": "get_asset" } },
+                "tenants": { "acme": { "threshold": 90.5 } }
+            }),
+            None,
+        );
+        let markdown = render_markdown(&state);
+        let visible = tool_list()
+            .iter()
+            .filter(|tool| {
+                !matches!(
+                    tool["name"].as_str(),
+                    Some(
+                        "pcli2_credentials_register"
+                            | "pcli2_artifacts_list"
+                            | "pcli2_artifacts_purge"
+                            | "pcli2_image_visual_match"
+                            | "pcli2_index_search"
+                    )
+                )
+            })
+            .count();
+        assert_eq!(markdown.matches("\n### `").count(), visible);
+        assert!(markdown.contains("### `get_asset`"));
+        assert!(!markdown.contains("### `pcli2_asset_get`"));
+        assert!(markdown.contains("Held for an operator's approval"));
+        assert!(markdown.contains("Quota `matches`: 5 calls per session in 60s."));
+        assert!(markdown.contains("| `folder_path` |"));
+        assert!(markdown.contains("Calls in tenant `acme` default to `threshold` 90.5."));
+        assert!(markdown.contains("```json\n{\"folder_path\":\"/Root/Castings\""));
+        assert!(!markdown.contains("\n\nExamples:\n- "));
+    }
+
+    #[test]
+    fn test_documents_follow_the_policy() {
+        let state = state(
+            serde_json::json!({
+                "policies": { "operators": { "tools": ["pcli2_asset_get", "pcli2_tenant_use"] } }
+            }),
+            Some("operators"),
+        );
+        let html = render_html(&state);
+        assert!(html.contains("<h3 id=\"pcli2_asset_get\">"));
+        assert!(!html.contains("<h3 id=\"pcli2_folder_list\">"));
+        assert!(html.contains("policy <code>operators</code>"));
+        assert!(html.contains("<code>pcli2 tenant use --name &lt;tenantName&gt;</code>"));
+        assert!(html.contains("refused to API keys whose policy"));
+    }
+}
@@ -0,0 +1,322 @@
+use crate::{
+    AppState,
+    config::ExportConfig,
+    content::{summary_block, tool_result},
+    pcli::{record_download, run_pcli2_asset_download, scratch_dir},
+    resources::resource_link,
+};
+use serde_json::{Value, json};
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::warn;
+
+pub const EXPORT_TOOL: &str = "pcli2_asset_export";
+/// Values accepted for `units`.
+pub const EXPORT_UNITS: [&str; 5] = ["mm", "cm", "m", "in", "ft"];
+/// MIME types of common CAD and mesh files, by extension; others are
+/// `application/octet-stream`.
+const MIME_TYPES: [(&str, &str); 9] = [
+    ("stl", "model/stl"),
+    ("obj", "model/obj"),
+    ("step", "model/step"),
+    ("stp", "model/step"),
+    ("iges", "model/iges"),
+    ("igs", "model/iges"),
+    ("3mf", "model/3mf"),
+    ("gltf", "model/gltf+json"),
+    ("glb", "model/gltf-binary"),
+];
+
+fn mime_type(name: &str) -> &'static str {
+    let extension = name
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase())
+        .unwrap_or_default();
+    MIME_TYPES
+        .iter()
+        .find(|(known, _)| *known == extension)
+        .map(|(_, mime_type)| *mime_type)
+        .unwrap_or("application/octet-stream")
+}
+
+fn target_format(args: &Value) -> Result<Option<String>, String> {
+    let Some(format) = args.get("target_format").and_then(Value::as_str) else {
+        return Ok(None);
+    };
+    if format.starts_with('-') {
+        return Err(
+            "Invalid 'target_format': values starting with '-' would be read as a pcli2 option"
+                .to_string(),
+        );
+    }
+    let format = format.trim().trim_start_matches('.').to_ascii_lowercase();
+    if format.is_empty()
+        || !format
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(format!(
+            "Invalid 'target_format': '{}'; expected a file extension such as step or stl",
+            format
+        ));
+    }
+    Ok(Some(format))
+}
+
+fn target_units(args: &Value) -> Result<Option<&str>, String> {
+    match args.get("units").and_then(Value::as_str) {
+        Some(units) if !EXPORT_UNITS.contains(&units) => Err(format!(
+            "Invalid 'units': '{}'; expected one of {}",
+            units,
+            EXPORT_UNITS.join(", ")
+        )),
+        units => Ok(units),
+    }
+}
+
+/// The configured conversion arguments for `target_format` and `units`, refusing
+/// what the server's `pcli2` cannot do.
+fn conversion_args(
+    config: &ExportConfig,
+    format: Option<&str>,
+    units: Option<&str>,
+) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    for (key, value, templates_key, templates, placeholder) in [
+        (
+            "target_format",
+            format,
+            "format_args",
+            &config.format_args,
+            "{format}",
+        ),
+        ("units", units, "units_args", &config.units_args, "{units}"),
+    ] {
+        let Some(value) = value else {
+            continue;
+        };
+        if templates.is_empty() {
+            return Err(format!(
+                "This server's pcli2 cannot convert the download ('export.{}' is not configured); omit '{}' to get the original file",
+                templates_key, key
+            ));
+        }
+        args.extend(templates.iter().map(|arg| arg.replace(placeholder, value)));
+    }
+    Ok(args)
+}
+
+/// Names the exported file after the asset, with the target format's
+/// extension when converting.
+fn file_name(args: &Value, format: Option<&str>) -> String {
+    let base = args
+        .get("path")
+        .and_then(Value::as_str)
+        .and_then(|path| path.rsplit('/').find(|segment| !segment.is_empty()))
+        .or_else(|| args.get("uuid").and_then(Value::as_str))
+        .unwrap_or("asset");
+    match format {
+        Some(format) => {
+            let stem = base.rsplit_once('.').map_or(base, |(stem, _)| stem);
+            format!("{}.{}", stem, format)
+        }
+        None => base.to_string(),
+    }
+}
+
+fn temp_export_path(name: &str) -> Result<PathBuf, String> {
+    // Exports started in the same millisecond need different files.
+    static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+    let extension = name
+        .rsplit_once('.')
+        .map_or("bin", |(_, extension)| extension);
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|err| format!("Failed to read system time: {}", err))?
+        .as_millis();
+    let mut path = scratch_dir();
+    path.push(format!(
+        "pcli2-export-{}-{}-{}.{}",
+        std::process::id(),
+        timestamp,
+        SEQUENCE.fetch_add(1, Ordering::Relaxed),
+        extension
+    ));
+    Ok(path)
+}
+
+/// Removes the downloaded file once it is stored, whatever the outcome.
+struct ExportFile(PathBuf);
+
+impl Drop for ExportFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+async fn partial_bytes(path: &Path) -> u64 {
+    tokio::fs::metadata(path)
+        .await
+        .map(|metadata| metadata.len())
+        .unwrap_or(0)
+}
+
+/// Runs `pcli2 asset download` into `file`. A download that fails partway
+/// is tried again, up to `export.download_attempts` in all: continuing the
+/// partial file with `export.resume_args`, else from scratch once the
+/// partial file is removed. A download that fails before writing anything
+/// is not retried here. Returns what each recovery found.
+pub(crate) async fn download(
+    config: &ExportConfig,
+    args: &Value,
+    file: &Path,
+    conversion: &[String],
+) -> Result<Vec<Value>, String> {
+    let mut recoveries = Vec::new();
+    let mut resume = false;
+    loop {
+        let mut extra = conversion.to_vec();
+        if resume {
+            extra.extend(config.resume_args.iter().cloned());
+        }
+        let message = match run_pcli2_asset_download(args, file, extra).await {
+            Ok(_) => return Ok(recoveries),
+            Err(message) => message,
+        };
+        let attempt = recoveries.len() as u32 + 1;
+        let partial = partial_bytes(file).await;
+        if partial == 0 || attempt >= config.download_attempts {
+            let tried = if attempt > 1 {
+                format!(" after {} attempts", attempt)
+            } else {
+                String::new()
+            };
+            return Err(format!("pcli2 asset download failed{}: {}", tried, message));
+        }
+        resume = !config.resume_args.is_empty();
+        if !resume {
+            tokio::fs::remove_file(file)
+                .await
+                .map_err(|err| format!("Failed to remove the partial download: {}", err))?;
+        }
+        let recovery = if resume { "resume" } else { "restart" };
+        warn!(
+            "↻ pcli2 asset download failed after {} bytes; {} ({}/{}): {}",
+            partial,
+            recovery,
+            attempt + 1,
+            config.download_attempts,
+            message.lines().next().unwrap_or_default()
+        );
+        recoveries.push(json!({
+            "attempt": attempt,
+            "error": message,
+            "partial_bytes": partial,
+            "recovery": recovery
+        }));
+    }
+}
+
+/// Downloads an asset, converted when `target_format` or `units` is given, and
+/// returns it as a `pcli2://results/...` blob resource.
+pub async fn export_asset(state: &AppState, args: Value) -> Result<Value, String> {
+    let config = &state.config.export;
+    let format = target_format(&args)?;
+    let units = target_units(&args)?;
+    let conversion = conversion_args(config, format.as_deref(), units)?;
+    let name = file_name(&args, format.as_deref());
+    let file = ExportFile(temp_export_path(&name)?);
+    let recoveries = download(config, &args, &file.0, &conversion).await?;
+    let bytes = tokio::fs::read(&file.0)
+        .await
+        .map_err(|err| format!("Failed to read the downloaded file: {}", err))?;
+    record_download(bytes.len());
+    if bytes.is_empty() {
+        return Err("pcli2 asset download produced an empty file".to_string());
+    }
+    if bytes.len() > config.max_bytes {
+        return Err(format!(
+            "The exported file is {} bytes, more than 'export.max_bytes' ({})",
+            bytes.len(),
+            config.max_bytes
+        ));
+    }
+    let mime_type = mime_type(&name);
+    let tenant = args.get("tenant").and_then(Value::as_str);
+    let stored = state
+        .resources
+        .insert_blob(&name, mime_type, &bytes, tenant);
+    let recovered = match recoveries.len() {
+        0 => String::new(),
+        failed => format!(
+            " The download was interrupted {} time(s) and completed on attempt {}.",
+            failed,
+            failed + 1
+        ),
+    };
+    let mut result = tool_result(vec![
+        summary_block(format!(
+            "Exported {} ({} bytes, {}) as resource {}; read it with resources/read.{}",
+            name,
+            bytes.len(),
+            mime_type,
+            stored.uri,
+            recovered
+        )),
+        resource_link(&stored),
+    ]);
+    if !recoveries.is_empty() {
+        result["_meta"]["download"] = json!({
+            "attempts": recoveries.len() + 1,
+            "recoveries": recoveries
+        });
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_file_names_follow_the_asset_and_format() {
+        let path = json!({ "path": "/Root/Castings/Bracket.stl" });
+        assert_eq!(file_name(&path, None), "Bracket.stl");
+        assert_eq!(file_name(&path, Some("step")), "Bracket.step");
+        assert_eq!(file_name(&json!({ "uuid": "u-1" }), Some("stl")), "u-1.stl");
+        assert_eq!(mime_type("Bracket.STEP"), "model/step");
+        assert_eq!(mime_type("Bracket"), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_conversion_needs_configured_arguments() {
+        let args = json!({ "target_format": ".STEP", "units": "mm" });
+        let format = target_format(&args).unwrap();
+        assert_eq!(format.as_deref(), Some("step"));
+        let units = target_units(&args).unwrap();
+        let error =
+            conversion_args(&ExportConfig::default(), format.as_deref(), units).unwrap_err();
+        assert!(error.contains("export.format_args"), "{}", error);
+
+        let config = ExportConfig {
+            format_args: vec!["--format".to_string(), "{format}".to_string()],
+            units_args: vec!["--units={units}".to_string()],
+            ..ExportConfig::default()
+        };
+        assert_eq!(
+            conversion_args(&config, format.as_deref(), units).unwrap(),
+            ["--format", "step", "--units=mm"]
+        );
+        assert!(
+            conversion_args(&ExportConfig::default(), None, None)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(target_format(&json!({ "target_format": "--help" })).is_err());
+        assert!(target_units(&json!({ "units": "furlong" })).is_err());
+    }
+}
@@ -0,0 +1,171 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+use tokio::sync::oneshot;
+
+#[derive(Default)]
+struct Queue {
+    available: usize,
+    /// Waiting calls of each session, oldest first.
+    waiting: HashMap<String, VecDeque<oneshot::Sender<FairPermit>>>,
+    /// Sessions with waiting calls, in the order they get the next slots.
+    turns: VecDeque<String>,
+}
+
+/// A semaphore whose free slots go to waiting sessions in turn rather than
+/// to the oldest waiting call, so a session that queued many calls does not
+/// hold up the one call of another. Calls of one session keep their order.
+pub struct FairSemaphore {
+    queue: Mutex<Queue>,
+}
+
+/// A slot of a [`FairSemaphore`], handed to the next session on drop.
+pub struct FairPermit {
+    semaphore: Option<Arc<FairSemaphore>>,
+}
+
+impl FairSemaphore {
+    pub fn new(permits: usize) -> Arc<Self> {
+        Arc::new(Self {
+            queue: Mutex::new(Queue {
+                available: permits,
+                ..Queue::default()
+            }),
+        })
+    }
+
+    /// Waits for a slot for a call of `session`.
+    pub async fn acquire(self: &Arc<Self>, session: &str) -> FairPermit {
+        let receiver = {
+            let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+            // Slots are only left free while nobody waits.
+            if queue.available > 0 {
+                queue.available -= 1;
+                return FairPermit {
+                    semaphore: Some(self.clone()),
+                };
+            }
+            let (sender, receiver) = oneshot::channel();
+            let waiting = queue.waiting.entry(session.to_string()).or_default();
+            let first = waiting.is_empty();
+            waiting.push_back(sender);
+            if first {
+                queue.turns.push_back(session.to_string());
+            }
+            receiver
+        };
+        match receiver.await {
+            Ok(permit) => permit,
+            // Senders are only dropped with the semaphore, which this holds.
+            Err(_) => unreachable!("fair semaphore dropped a waiting call"),
+        }
+    }
+
+    /// Calls waiting for a slot.
+    pub fn waiting(&self) -> usize {
+        let queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        queue.waiting.values().map(VecDeque::len).sum()
+    }
+
+    fn next_waiter(&self) -> Option<oneshot::Sender<FairPermit>> {
+        let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        while let Some(session) = queue.turns.pop_front() {
+            let Some(waiting) = queue.waiting.get_mut(&session) else {
+                continue;
+            };
+            let next = waiting.pop_front();
+            if waiting.is_empty() {
+                queue.waiting.remove(&session);
+            } else {
+                queue.turns.push_back(session);
+            }
+            if next.is_some() {
+                return next;
+            }
+        }
+        queue.available += 1;
+        None
+    }
+
+    /// Hands a freed slot to the session whose turn it is, skipping calls
+    /// that stopped waiting.
+    fn release(self: &Arc<Self>) {
+        while let Some(waiter) = self.next_waiter() {
+            let permit = FairPermit {
+                semaphore: Some(self.clone()),
+            };
+            match waiter.send(permit) {
+                Ok(()) => return,
+                Err(mut permit) => {
+                    permit.semaphore = None;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for FairPermit {
+    fn drop(&mut self) {
+        if let Some(semaphore) = self.semaphore.take() {
+            semaphore.release();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{sync::mpsc, task::yield_now};
+
+    #[tokio::test]
+    async fn test_slots_go_to_sessions_in_turn() {
+        let semaphore = FairSemaphore::new(1);
+        let held = semaphore.acquire("a").await;
+        let (order, mut finished) = mpsc::unbounded_channel();
+        for (session, call) in [
+            ("a", "a1"),
+            ("a", "a2"),
+            ("a", "a3"),
+            ("b", "b1"),
+            ("c", "c1"),
+        ] {
+            let semaphore = semaphore.clone();
+            let order = order.clone();
+            tokio::spawn(async move {
+                let _permit = semaphore.acquire(session).await;
+                let _ = order.send(call);
+            });
+            yield_now().await;
+        }
+        assert_eq!(semaphore.waiting(), 5);
+        drop(held);
+        let mut calls = Vec::new();
+        for _ in 0..5 {
+            calls.push(finished.recv().await.unwrap());
+        }
+        assert_eq!(calls, ["a1", "b1", "c1", "a2", "a3"]);
+        assert_eq!(semaphore.waiting(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_calls_that_stop_waiting_do_not_keep_a_slot() {
+        let semaphore = FairSemaphore::new(1);
+        let held = semaphore.acquire("a").await;
+        let waiting = {
+            let semaphore = semaphore.clone();
+            tokio::spawn(async move {
+                let _permit = semaphore.acquire("b").await;
+            })
+        };
+        yield_now().await;
+        waiting.abort();
+        let _ = waiting.await;
+        drop(held);
+        let _again = semaphore.acquire("c").await;
+        let another =
+            tokio::time::timeout(std::time::Duration::from_millis(20), semaphore.acquire("d"))
+                .await;
+        assert!(another.is_err());
+    }
+}
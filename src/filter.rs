@@ -0,0 +1,537 @@
+use crate::{
+    content::summary_block,
+    paging::{csv_records, json_rows},
+    projection::{Cell, ask_for_rows, csv_fields, has_rows, unquote},
+};
+use serde_json::{Value, json};
+use std::cmp::Ordering;
+
+pub const FILTER_ARG: &str = "filter";
+const MAX_FILTER_LEN: usize = 1000;
+
+/// Whether a call of `tool` asks for its rows to be filtered.
+pub fn wants_filter(tool: &str, args: &Value) -> bool {
+    has_rows(tool) && args.get(FILTER_ARG).is_some()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Contains,
+    StartsWith,
+    EndsWith,
+}
+
+impl Op {
+    fn from_word(word: &str) -> Option<Op> {
+        match word {
+            "contains" => Some(Op::Contains),
+            "starts_with" => Some(Op::StartsWith),
+            "ends_with" => Some(Op::EndsWith),
+            _ => None,
+        }
+    }
+}
+
+/// A parsed filter: comparisons of a column with a literal, joined with
+/// `&&`, `||`, and `!`.
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Compare {
+        column: String,
+        op: Op,
+        value: Option<String>,
+    },
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Text(String),
+    Number(String),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let mut next_is = |expected: char| chars.next_if(|(_, next)| *next == expected).is_some();
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '&' if next_is('&') => Token::And,
+            '|' if next_is('|') => Token::Or,
+            '=' => {
+                next_is('=');
+                Token::Op(Op::Eq)
+            }
+            '!' if next_is('=') => Token::Op(Op::Ne),
+            '!' => Token::Not,
+            '>' if next_is('=') => Token::Op(Op::Ge),
+            '>' => Token::Op(Op::Gt),
+            '<' if next_is('=') => Token::Op(Op::Le),
+            '<' => Token::Op(Op::Lt),
+            '"' | '\'' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, escaped)) => value.push(escaped),
+                            None => return Err("unfinished string".to_string()),
+                        },
+                        Some((_, end)) if end == c => break,
+                        Some((_, other)) => value.push(other),
+                        None => return Err("unfinished string".to_string()),
+                    }
+                }
+                Token::Text(value)
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let mut end = start + c.len_utf8();
+                while let Some((index, next)) =
+                    chars.next_if(|(_, next)| next.is_ascii_digit() || *next == '.')
+                {
+                    end = index + next.len_utf8();
+                }
+                Token::Number(text[start..end].to_string())
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut end = start + c.len_utf8();
+                while let Some((index, next)) = chars
+                    .next_if(|(_, next)| next.is_alphanumeric() || matches!(next, '_' | '.' | '-'))
+                {
+                    end = index + next.len_utf8();
+                }
+                let word = &text[start..end];
+                match word.to_lowercase().as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    lower => match Op::from_word(lower) {
+                        Some(op) => Token::Op(op),
+                        None => Token::Word(word.to_string()),
+                    },
+                }
+            }
+            other => return Err(format!("unexpected '{}'", other)),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut left = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            left = Expr::And(Box::new(left), Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some(Token::Open) => {
+                let inner = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(inner),
+                    _ => Err("missing ')'".to_string()),
+                }
+            }
+            Some(Token::Word(column)) => {
+                let op = match self.next() {
+                    Some(Token::Op(op)) => op,
+                    _ => return Err(format!("expected a comparison after '{}'", column)),
+                };
+                let value = match self.next() {
+                    Some(Token::Text(text)) | Some(Token::Number(text)) => Some(text),
+                    Some(Token::Word(word)) if word.eq_ignore_ascii_case("null") => None,
+                    Some(Token::Word(word))
+                        if word.eq_ignore_ascii_case("true")
+                            || word.eq_ignore_ascii_case("false") =>
+                    {
+                        Some(word.to_lowercase())
+                    }
+                    _ => {
+                        return Err(format!(
+                            "expected a quoted string, number, true, false or null after '{}'",
+                            column
+                        ));
+                    }
+                };
+                Ok(Expr::Compare { column, op, value })
+            }
+            _ => Err("expected a column name, '!' or '('".to_string()),
+        }
+    }
+}
+
+fn parse(text: &str) -> Result<Expr, String> {
+    let mut parser = Parser {
+        tokens: tokenize(text)?,
+        position: 0,
+    };
+    let expr = parser.or()?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(_) => Err("unexpected text after the expression".to_string()),
+    }
+}
+
+impl Expr {
+    /// Whether a row whose cells `cell` looks up matches.
+    fn matches(&self, cell: &dyn Fn(&str) -> Option<String>) -> bool {
+        match self {
+            Expr::Not(inner) => !inner.matches(cell),
+            Expr::And(left, right) => left.matches(cell) && right.matches(cell),
+            Expr::Or(left, right) => left.matches(cell) || right.matches(cell),
+            Expr::Compare { column, op, value } => {
+                compare(cell(column).as_deref(), *op, value.as_deref())
+            }
+        }
+    }
+
+    fn columns<'a>(&'a self, columns: &mut Vec<&'a str>) {
+        match self {
+            Expr::Not(inner) => inner.columns(columns),
+            Expr::And(left, right) | Expr::Or(left, right) => {
+                left.columns(columns);
+                right.columns(columns);
+            }
+            Expr::Compare { column, .. } => {
+                if !columns.contains(&column.as_str()) {
+                    columns.push(column);
+                }
+            }
+        }
+    }
+}
+
+/// Compares a cell with a literal: as numbers when both are, otherwise as
+/// text ignoring case. A missing cell only equals `null`.
+fn compare(cell: Option<&str>, op: Op, value: Option<&str>) -> bool {
+    let (Some(cell), Some(value)) = (cell, value) else {
+        let same = cell.is_none() && value.is_none();
+        return match op {
+            Op::Eq => same,
+            Op::Ne => !same,
+            _ => false,
+        };
+    };
+    let (cell_text, value_text) = (cell.to_lowercase(), value.to_lowercase());
+    let ordering = match (Cell::parse(cell), Cell::parse(value)) {
+        (Some(cell @ Cell::Number(_)), Some(value @ Cell::Number(_))) => cell.cmp(&value),
+        _ => cell_text.trim().cmp(value_text.trim()),
+    };
+    match op {
+        Op::Eq => ordering == Ordering::Equal,
+        Op::Ne => ordering != Ordering::Equal,
+        Op::Gt => ordering == Ordering::Greater,
+        Op::Ge => ordering != Ordering::Less,
+        Op::Lt => ordering == Ordering::Less,
+        Op::Le => ordering != Ordering::Greater,
+        Op::Contains => cell_text.contains(&value_text),
+        Op::StartsWith => cell_text.starts_with(&value_text),
+        Op::EndsWith => cell_text.ends_with(&value_text),
+    }
+}
+
+/// Checks the filter expression and asks pcli2 for rows to filter.
+pub fn prepare_filter(args: &mut Value) -> Result<(), String> {
+    let Some(text) = args.get(FILTER_ARG).and_then(Value::as_str) else {
+        return Err(format!(
+            "Invalid argument '{}': expected an expression such as score >= 90 && path ends_with \".stl\"",
+            FILTER_ARG
+        ));
+    };
+    if text.len() > MAX_FILTER_LEN {
+        return Err(format!(
+            "Invalid argument '{}': longer than {} characters",
+            FILTER_ARG, MAX_FILTER_LEN
+        ));
+    }
+    parse(text).map_err(|err| format!("Invalid argument '{}': {}", FILTER_ARG, err))?;
+    ask_for_rows(args, &format!("'{}' needs", FILTER_ARG))
+}
+
+/// The text of a JSON cell: the key as named, then ignoring case, then as
+/// a dotted path into nested objects.
+fn json_cell(row: &Value, column: &str) -> Option<String> {
+    let value = row
+        .get(column)
+        .or_else(|| {
+            row.as_object()?
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(column))
+                .map(|(_, value)| value)
+        })
+        .or_else(|| column.split('.').try_fold(row, |value, key| value.get(key)))?;
+    match value {
+        Value::Null => None,
+        Value::String(text) => Some(text.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+/// Rows kept and rows there were, with the columns the output lacks.
+struct Filtered {
+    text: String,
+    matched: usize,
+    total: usize,
+    missing: Vec<String>,
+}
+
+fn filter_json(text: &str, expr: &Expr, columns: &[&str]) -> Option<Filtered> {
+    let mut output = serde_json::from_str::<Value>(text).ok()?;
+    let rows = json_rows(&mut output)?;
+    let total = rows.len();
+    let missing = columns
+        .iter()
+        .filter(|column| {
+            !rows.is_empty() && !rows.iter().any(|row| json_cell(row, column).is_some())
+        })
+        .map(|column| column.to_string())
+        .collect();
+    rows.retain(|row| expr.matches(&|column| json_cell(row, column)));
+    let matched = rows.len();
+    let text = serde_json::to_string_pretty(&output).unwrap_or_else(|_| output.to_string());
+    Some(Filtered {
+        text,
+        matched,
+        total,
+        missing,
+    })
+}
+
+fn filter_csv(text: &str, expr: &Expr, columns: &[&str]) -> Option<Filtered> {
+    let records = csv_records(text);
+    let (header, rows) = records.split_first()?;
+    let names: Vec<String> = csv_fields(header).iter().map(|f| unquote(f)).collect();
+    let position = |column: &str| {
+        names
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(column))
+    };
+    let missing = columns
+        .iter()
+        .filter(|column| position(column).is_none())
+        .map(|column| column.to_string())
+        .collect();
+    let kept: Vec<&str> = rows
+        .iter()
+        .copied()
+        .filter(|row| {
+            let fields = csv_fields(row);
+            expr.matches(&|column| {
+                let field = fields.get(position(column)?)?;
+                Some(unquote(field)).filter(|text| !text.is_empty())
+            })
+        })
+        .collect();
+    let text = std::iter::once(*header)
+        .chain(kept.iter().copied())
+        .collect::<Vec<_>>()
+        .join("\n");
+    Some(Filtered {
+        text,
+        matched: kept.len(),
+        total: rows.len(),
+        missing,
+    })
+}
+
+/// Keeps the rows in the last text block of `result` that match the
+/// filter, with a note saying how many did and `_meta.filter`. An output
+/// without rows is returned whole.
+pub fn filter_result(args: &Value, mut result: Value) -> Value {
+    let Some(expr) = args
+        .get(FILTER_ARG)
+        .and_then(Value::as_str)
+        .and_then(|text| parse(text).ok())
+    else {
+        return result;
+    };
+    let mut columns = Vec::new();
+    expr.columns(&mut columns);
+    let Some(blocks) = result.get_mut("content").and_then(Value::as_array_mut) else {
+        return result;
+    };
+    let Some(block) = blocks
+        .iter_mut()
+        .rev()
+        .find(|block| block["type"] == "text")
+    else {
+        return result;
+    };
+    let csv = args.get("format").and_then(Value::as_str) == Some("csv");
+    let filtered = block["text"].as_str().and_then(|text| {
+        if csv {
+            filter_csv(text, &expr, &columns)
+        } else {
+            filter_json(text, &expr, &columns)
+        }
+    });
+    let Some(filtered) = filtered else {
+        blocks.insert(
+            0,
+            summary_block("The output has no rows to filter; it is returned whole."),
+        );
+        return result;
+    };
+    block["text"] = json!(filtered.text);
+    let mut note = format!(
+        "{} of {} rows match the filter.",
+        filtered.matched, filtered.total
+    );
+    if !filtered.missing.is_empty() {
+        note.push_str(&format!(
+            " The output has no column named {}.",
+            filtered
+                .missing
+                .iter()
+                .map(|column| format!("'{}'", column))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    blocks.insert(0, summary_block(note));
+    result["_meta"]["filter"] = json!({
+        "matched": filtered.matched,
+        "total": filtered.total
+    });
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::{raw_output_block, tool_result};
+
+    fn keeps(filter: &str, row: Value) -> bool {
+        let expr = parse(filter).unwrap();
+        expr.matches(&|column| json_cell(&row, column))
+    }
+
+    #[test]
+    fn test_filter_expressions() {
+        let row = json!({
+            "score": "92.5%",
+            "path": "/Root/Bracket.STL",
+            "metadata": { "Material": "Steel" },
+            "archived": false
+        });
+        assert!(keeps("score >= 90 && path ends_with \".stl\"", row.clone()));
+        assert!(!keeps("score > 95 || path contains 'bolt'", row.clone()));
+        assert!(keeps(
+            "!(score < 90) and metadata.Material == 'steel'",
+            row.clone()
+        ));
+        assert!(keeps("owner == null && archived = false", row.clone()));
+        assert!(keeps("not owner != null", row.clone()));
+        assert!(!keeps("owner >= 1", row));
+
+        for invalid in [
+            "score >=",
+            "score 90",
+            "(score > 1",
+            "score > 1 extra",
+            "'open",
+        ] {
+            assert!(parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_rows_are_filtered() {
+        let matches = json!({ "matches": [
+            { "path": "/A.stl", "score": 99 },
+            { "path": "/B.step", "score": 95 },
+            { "path": "/C.stl", "score": 80 }
+        ] });
+        let args = json!({ "filter": "score >= 90 && path ends_with '.stl' && color == 'red'" });
+        let result = filter_result(
+            &args,
+            tool_result(vec![raw_output_block(matches.to_string())]),
+        );
+        let output: Value =
+            serde_json::from_str(result["content"][1]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(output, json!({ "matches": [] }));
+        assert_eq!(
+            result["_meta"]["filter"],
+            json!({ "matched": 0, "total": 3 })
+        );
+        assert!(
+            result["content"][0]["text"]
+                .as_str()
+                .unwrap()
+                .contains("no column named 'color'")
+        );
+
+        let csv = "PATH,SCORE\n/A.stl,99\n\"/B, copy.stl\",95\n/C.stl,80\n";
+        let args = json!({ "format": "csv", "headers": true, "filter": "score > 90" });
+        let result = filter_result(&args, tool_result(vec![raw_output_block(csv)]));
+        assert_eq!(
+            result["content"][1]["text"],
+            "PATH,SCORE\n/A.stl,99\n\"/B, copy.stl\",95"
+        );
+    }
+
+    #[test]
+    fn test_filter_options_are_checked() {
+        let mut args = json!({ "filter": "score > 90" });
+        prepare_filter(&mut args).unwrap();
+        assert_eq!(args["format"], "json");
+        assert!(prepare_filter(&mut json!({ "filter": "score >" })).is_err());
+        assert!(prepare_filter(&mut json!({ "filter": 5 })).is_err());
+        assert!(prepare_filter(&mut json!({ "filter": "a == 1", "format": "tree" })).is_err());
+        assert!(wants_filter(
+            "pcli2_asset_list",
+            &json!({ "filter": "a == 1" })
+        ));
+        assert!(!wants_filter(
+            "pcli2_asset_get",
+            &json!({ "filter": "a == 1" })
+        ));
+    }
+}
@@ -0,0 +1,174 @@
+use crate::{
+    AppState,
+    config::{FeatureConfig, ServerConfig},
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use utoipa::ToSchema;
+
+/// `outputSchema` in `tools/list` and `structuredContent` in tool results.
+pub const STRUCTURED_OUTPUT: &str = "structured_output";
+/// `notifications/tools/list_changed` when the tool list changes while the
+/// server runs, and the `listChanged` capability.
+pub const DYNAMIC_TOOLS: &str = "dynamic_tools";
+/// Calls held for an operator's approval that finish later; without it
+/// such calls are refused.
+pub const APPROVALS: &str = "approvals";
+pub const FEATURES: [&str; 3] = [STRUCTURED_OUTPUT, DYNAMIC_TOOLS, APPROVALS];
+
+/// Where a feature's rollout comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    Default,
+    Config,
+    Override,
+}
+
+/// Rollouts set at runtime through `/admin/features`. They outlive
+/// configuration reloads and end with the process.
+#[derive(Clone, Default)]
+pub struct FeatureFlags {
+    overrides: Arc<Mutex<HashMap<String, FeatureConfig>>>,
+}
+
+impl FeatureFlags {
+    /// The rollout of `feature`: the runtime override, else `features` in
+    /// the configuration, else on for every session.
+    pub fn rollout(&self, config: &ServerConfig, feature: &str) -> (FeatureConfig, Source) {
+        let overrides = self.overrides.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(rollout) = overrides.get(feature) {
+            return (rollout.clone(), Source::Override);
+        }
+        match config.features.get(feature) {
+            Some(rollout) => (rollout.clone(), Source::Config),
+            None => (FeatureConfig::default(), Source::Default),
+        }
+    }
+
+    pub fn set(&self, feature: &str, rollout: FeatureConfig) {
+        let mut overrides = self.overrides.lock().unwrap_or_else(|e| e.into_inner());
+        overrides.insert(feature.to_string(), rollout);
+    }
+
+    /// Drops the override of `feature`; returns whether there was one.
+    pub fn clear(&self, feature: &str) -> bool {
+        let mut overrides = self.overrides.lock().unwrap_or_else(|e| e.into_inner());
+        overrides.remove(feature).is_some()
+    }
+}
+
+/// Where `session` falls in 0..100 for `feature`. Hashing the feature name
+/// in keeps one session from landing in the first share of every rollout.
+fn bucket(feature: &str, session: &str) -> u8 {
+    let digest = Sha256::digest(format!("{}:{}", feature, session).as_bytes());
+    (u16::from_be_bytes([digest[0], digest[1]]) % 100) as u8
+}
+
+pub fn admits(rollout: &FeatureConfig, feature: &str, session: &str) -> bool {
+    rollout.enabled
+        && (rollout.sessions.iter().any(|name| name == session)
+            || bucket(feature, session) < rollout.percent)
+}
+
+/// Whether the request's session gets `feature`.
+pub fn enabled(state: &AppState, feature: &str) -> bool {
+    let (rollout, _) = state.features.rollout(&state.config, feature);
+    admits(&rollout, feature, state.session_key())
+}
+
+/// A feature's rollout as `/admin/features` shows it.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[schema(as = Feature)]
+pub struct FeatureStatus {
+    pub name: String,
+    pub enabled: bool,
+    #[schema(maximum = 100)]
+    pub percent: u8,
+    pub sessions: Vec<String>,
+    pub source: Source,
+}
+
+/// `feature` as `/admin/features` shows it.
+pub fn feature_status(state: &AppState, feature: &str) -> FeatureStatus {
+    let (rollout, source) = state.features.rollout(&state.config, feature);
+    FeatureStatus {
+        name: feature.to_string(),
+        enabled: rollout.enabled,
+        percent: rollout.percent,
+        sessions: rollout.sessions,
+        source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rollouts_pick_a_stable_share_of_sessions() {
+        let quarter = FeatureConfig {
+            percent: 25,
+            ..FeatureConfig::default()
+        };
+        let admitted = (0..1000)
+            .filter(|n| admits(&quarter, DYNAMIC_TOOLS, &format!("session-{}", n)))
+            .count();
+        assert!((200..300).contains(&admitted), "{}", admitted);
+        for n in 0..20 {
+            let session = format!("session-{}", n);
+            assert_eq!(
+                admits(&quarter, DYNAMIC_TOOLS, &session),
+                admits(&quarter, DYNAMIC_TOOLS, &session)
+            );
+        }
+
+        let none = FeatureConfig {
+            percent: 0,
+            sessions: vec!["pilot".to_string()],
+            ..FeatureConfig::default()
+        };
+        assert!(admits(&none, APPROVALS, "pilot"));
+        assert!(!admits(&none, APPROVALS, "other"));
+        let killed = FeatureConfig {
+            enabled: false,
+            ..none
+        };
+        assert!(!admits(&killed, APPROVALS, "pilot"));
+        assert!(admits(&FeatureConfig::default(), APPROVALS, "any"));
+    }
+
+    #[test]
+    fn test_overrides_replace_the_config_until_cleared() {
+        let config = ServerConfig {
+            features: HashMap::from([(
+                STRUCTURED_OUTPUT.to_string(),
+                FeatureConfig {
+                    percent: 50,
+                    ..FeatureConfig::default()
+                },
+            )]),
+            ..ServerConfig::default()
+        };
+        let flags = FeatureFlags::default();
+        assert_eq!(flags.rollout(&config, APPROVALS).1, Source::Default);
+        assert_eq!(flags.rollout(&config, STRUCTURED_OUTPUT).0.percent, 50);
+        flags.set(
+            STRUCTURED_OUTPUT,
+            FeatureConfig {
+                enabled: false,
+                ..FeatureConfig::default()
+            },
+        );
+        let (rollout, source) = flags.rollout(&config, STRUCTURED_OUTPUT);
+        assert!(!rollout.enabled);
+        assert_eq!(source, Source::Override);
+        assert!(flags.clone().clear(STRUCTURED_OUTPUT));
+        assert!(!flags.clear(STRUCTURED_OUTPUT));
+        assert_eq!(flags.rollout(&config, STRUCTURED_OUTPUT).1, Source::Config);
+    }
+}
@@ -0,0 +1,174 @@
+use crate::{
+    content::{
+        AUDIENCE_ASSISTANT, AUDIENCE_USER, PRIORITY_IMAGE, PRIORITY_SUMMARY, annotations,
+        raw_output_block, summary_block, tool_result,
+    },
+    pcli::{
+        DEFAULT_FAN_OUT_CONCURRENCY, in_current_execution, run_pcli2_asset_thumbnail,
+        validate_range_u64,
+    },
+    snapshot::{folder_selection, list_folder_assets},
+};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use tokio::{sync::Semaphore, task::JoinSet};
+
+pub const GALLERY_TOOL: &str = "pcli2_folder_thumbnail_gallery";
+pub const DEFAULT_GALLERY_LIMIT: u64 = 12;
+pub const MAX_GALLERY_LIMIT: u64 = 50;
+
+fn label(asset: &Value) -> String {
+    ["name", "path", "uuid"]
+        .iter()
+        .find_map(|key| asset.get(*key).and_then(Value::as_str))
+        .unwrap_or("unnamed asset")
+        .to_string()
+}
+
+/// Downloads the thumbnails of up to `limit` assets of a folder (from
+/// `offset` on) and returns each as an image block after a caption naming
+/// the asset, so a multimodal model can scan the folder in one call.
+pub async fn thumbnail_gallery(args: Value) -> Result<Value, String> {
+    validate_range_u64(&args, "limit", 1, MAX_GALLERY_LIMIT)?;
+    validate_range_u64(&args, "concurrent", 1, 10)?;
+    let folder = folder_selection(&args)?;
+    let limit = args
+        .get("limit")
+        .and_then(Value::as_u64)
+        .unwrap_or(DEFAULT_GALLERY_LIMIT) as usize;
+    let offset = args.get("offset").and_then(Value::as_u64).unwrap_or(0) as usize;
+    let concurrency = args
+        .get("concurrent")
+        .and_then(Value::as_u64)
+        .unwrap_or(DEFAULT_FAN_OUT_CONCURRENCY as u64) as usize;
+
+    let assets = list_folder_assets(&folder, false).await?;
+    let total = assets.len();
+    let page: Vec<Value> = assets.into_iter().skip(offset).take(limit).collect();
+
+    let semaphore = Arc::new(Semaphore::new(concurrency));
+    let mut tasks = JoinSet::new();
+    for (index, asset) in page.iter().enumerate() {
+        let mut single = json!({});
+        if let Some(tenant) = folder.get("tenant") {
+            single["tenant"] = tenant.clone();
+        }
+        match (
+            asset.get("uuid").and_then(Value::as_str),
+            asset.get("path").and_then(Value::as_str),
+        ) {
+            (Some(uuid), _) => single["uuid"] = json!(uuid),
+            (None, Some(path)) => single["path"] = json!(path),
+            (None, None) => continue,
+        }
+        let semaphore = semaphore.clone();
+        tasks.spawn(in_current_execution(async move {
+            let _permit = semaphore.acquire_owned().await;
+            (index, run_pcli2_asset_thumbnail(single).await)
+        }));
+    }
+    let mut outcomes: Vec<Option<Result<String, String>>> = vec![None; page.len()];
+    while let Some(joined) = tasks.join_next().await {
+        let (index, outcome) = joined.map_err(|err| format!("Thumbnail task failed: {}", err))?;
+        outcomes[index] = Some(outcome);
+    }
+
+    let mut blocks = Vec::with_capacity(page.len() * 2 + 2);
+    let mut listing = Vec::with_capacity(page.len());
+    let mut shown = 0;
+    for (position, (asset, outcome)) in page.iter().zip(outcomes).enumerate() {
+        let number = offset + position + 1;
+        let name = label(asset);
+        let uuid = asset.get("uuid").cloned().unwrap_or(Value::Null);
+        let mut entry = json!({
+            "index": number,
+            "name": name,
+            "uuid": uuid,
+            "path": asset.get("path").cloned().unwrap_or(Value::Null)
+        });
+        let caption = match uuid.as_str() {
+            Some(uuid) => format!("{}. {} ({})", number, name, uuid),
+            None => format!("{}. {}", number, name),
+        };
+        match outcome {
+            Some(Ok(encoded)) => {
+                shown += 1;
+                entry["status"] = json!("ok");
+                blocks.push(json!({
+                    "type": "text",
+                    "text": caption,
+                    "annotations": annotations(&[AUDIENCE_USER, AUDIENCE_ASSISTANT], PRIORITY_SUMMARY)
+                }));
+                blocks.push(json!({
+                    "type": "image",
+                    "mimeType": "image/png",
+                    "annotations": annotations(&[AUDIENCE_USER, AUDIENCE_ASSISTANT], PRIORITY_IMAGE),
+                    "data": encoded
+                }));
+            }
+            outcome => {
+                let message = match outcome {
+                    Some(Err(message)) => message,
+                    _ => "The asset has neither a UUID nor a path".to_string(),
+                };
+                entry["status"] = json!("error");
+                entry["error"] = json!(message);
+                blocks.push(summary_block(format!(
+                    "{}: no thumbnail ({})",
+                    caption, message
+                )));
+            }
+        }
+        listing.push(entry);
+    }
+
+    let mut headline = if page.is_empty() {
+        format!(
+            "The folder has {} assets; none from position {} on.",
+            total,
+            offset + 1
+        )
+    } else {
+        format!(
+            "Thumbnails of assets {}-{} of {} in the folder ({} shown, {} unavailable).",
+            offset + 1,
+            offset + page.len(),
+            total,
+            shown,
+            page.len() - shown
+        )
+    };
+    if offset + page.len() < total {
+        headline.push_str(&format!(
+            " Pass offset {} for the next page.",
+            offset + page.len()
+        ));
+    }
+    let rendered = serde_json::to_string_pretty(&json!({
+        "total": total,
+        "offset": offset,
+        "assets": listing
+    }))
+    .map_err(|err| format!("Failed to render the gallery: {}", err))?;
+    blocks.insert(0, summary_block(headline));
+    blocks.push(raw_output_block(rendered));
+    Ok(tool_result(blocks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_label_prefers_the_name() {
+        assert_eq!(
+            label(&json!({ "name": "a.stl", "path": "/R/a.stl", "uuid": "1" })),
+            "a.stl"
+        );
+        assert_eq!(
+            label(&json!({ "path": "/R/a.stl", "uuid": "1" })),
+            "/R/a.stl"
+        );
+        assert_eq!(label(&json!({})), "unnamed asset");
+    }
+}
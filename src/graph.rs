@@ -0,0 +1,440 @@
+use crate::{
+    AppState,
+    content::{raw_output_block, summary_block, tool_result},
+    pcli::{
+        parse_string_list, run_pcli2_folder_geometric_match, run_pcli2_folder_part_match,
+        run_pcli2_folder_visual_match, validate_range_u64,
+    },
+    resources::resource_link,
+    split::match_rows,
+    summary::score_of,
+};
+use serde_json::{Map, Value, json};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+pub const SIMILARITY_GRAPH_TOOL: &str = "pcli2_similarity_graph";
+pub const GRAPH_SEARCHES: [&str; 3] = ["geometric", "part", "visual"];
+pub const GRAPH_EXPORTS: [&str; 2] = ["dot", "graphml"];
+pub const DEFAULT_TOP: u64 = 10;
+pub const MAX_TOP: u64 = 100;
+/// Match arguments passed on to the folder match.
+const MATCH_KEYS: [&str; 5] = [
+    "tenant",
+    "folder_path",
+    "threshold",
+    "exclusive",
+    "concurrent",
+];
+
+/// One asset of the graph, named by whatever the match rows gave for it.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Asset {
+    uuid: Option<String>,
+    path: Option<String>,
+}
+
+impl Asset {
+    fn of(row: &Value, uuid_key: &str, path_key: &str) -> Option<Self> {
+        let text = |key: &str| {
+            row.get(key)
+                .and_then(Value::as_str)
+                .filter(|text| !text.is_empty())
+                .map(str::to_string)
+        };
+        let asset = Self {
+            uuid: text(uuid_key),
+            path: text(path_key),
+        };
+        (asset.uuid.is_some() || asset.path.is_some()).then_some(asset)
+    }
+
+    fn key(&self) -> &str {
+        self.uuid
+            .as_deref()
+            .or(self.path.as_deref())
+            .unwrap_or_default()
+    }
+
+    fn label(&self) -> &str {
+        self.path
+            .as_deref()
+            .or(self.uuid.as_deref())
+            .unwrap_or_default()
+    }
+
+    fn json(&self) -> Value {
+        json!({ "uuid": self.uuid, "path": self.path })
+    }
+}
+
+/// Assets joined by their best match score in either direction.
+#[derive(Debug, Default)]
+pub struct SimilarityGraph {
+    assets: Vec<Asset>,
+    keys: HashMap<String, usize>,
+    /// Keyed by the lower asset index first.
+    edges: BTreeMap<(usize, usize), f64>,
+}
+
+impl SimilarityGraph {
+    /// The graph of the match rows scoring at least `threshold`. Rows that
+    /// do not name both assets, or match an asset with itself, are skipped.
+    pub fn from_matches(rows: &[Value], threshold: Option<f64>) -> Self {
+        let mut graph = Self::default();
+        for row in rows {
+            let (Some(reference), Some(candidate), Some(score)) = (
+                Asset::of(row, "reference_asset_uuid", "reference_asset_path"),
+                Asset::of(row, "candidate_asset_uuid", "candidate_asset_path"),
+                score_of(row),
+            ) else {
+                continue;
+            };
+            if threshold.is_some_and(|threshold| score < threshold)
+                || reference.key() == candidate.key()
+            {
+                continue;
+            }
+            let (a, b) = (graph.node(reference), graph.node(candidate));
+            let best = graph.edges.entry((a.min(b), a.max(b))).or_insert(score);
+            *best = best.max(score);
+        }
+        graph
+    }
+
+    fn node(&mut self, asset: Asset) -> usize {
+        if let Some(&index) = self.keys.get(asset.key()) {
+            let known = &mut self.assets[index];
+            known.uuid = known.uuid.take().or(asset.uuid);
+            known.path = known.path.take().or(asset.path);
+            return index;
+        }
+        self.keys.insert(asset.key().to_string(), self.assets.len());
+        self.assets.push(asset);
+        self.assets.len() - 1
+    }
+
+    fn neighbours(&self) -> Vec<Vec<(usize, f64)>> {
+        let mut neighbours = vec![Vec::new(); self.assets.len()];
+        for (&(a, b), &score) in &self.edges {
+            neighbours[a].push((b, score));
+            neighbours[b].push((a, score));
+        }
+        neighbours
+    }
+
+    /// Groups of assets linked by matches, largest first.
+    fn components(&self, neighbours: &[Vec<(usize, f64)>]) -> Vec<Vec<usize>> {
+        let mut seen = vec![false; self.assets.len()];
+        let mut components = Vec::new();
+        for start in 0..self.assets.len() {
+            if seen[start] {
+                continue;
+            }
+            seen[start] = true;
+            let mut component = Vec::new();
+            let mut queue = VecDeque::from([start]);
+            while let Some(node) = queue.pop_front() {
+                component.push(node);
+                for &(next, _) in &neighbours[node] {
+                    if !seen[next] {
+                        seen[next] = true;
+                        queue.push_back(next);
+                    }
+                }
+            }
+            component.sort_by(|a, b| self.assets[*a].label().cmp(self.assets[*b].label()));
+            components.push(component);
+        }
+        components.sort_by(|a, b| {
+            b.len()
+                .cmp(&a.len())
+                .then_with(|| self.assets[a[0]].label().cmp(self.assets[b[0]].label()))
+        });
+        components
+    }
+
+    /// Components, the `top` most central assets, and counts.
+    pub fn report(&self, top: usize) -> Value {
+        let neighbours = self.neighbours();
+        let components = self.components(&neighbours);
+        let mut component_of = vec![0; self.assets.len()];
+        for (number, component) in components.iter().enumerate() {
+            for &node in component {
+                component_of[node] = number + 1;
+            }
+        }
+        let strength = |node: usize| neighbours[node].iter().map(|(_, score)| score).sum::<f64>();
+        let mut ranked: Vec<usize> = (0..self.assets.len()).collect();
+        ranked.sort_by(|&a, &b| {
+            neighbours[b]
+                .len()
+                .cmp(&neighbours[a].len())
+                .then_with(|| strength(b).total_cmp(&strength(a)))
+                .then_with(|| self.assets[a].label().cmp(self.assets[b].label()))
+        });
+        let others = self.assets.len().saturating_sub(1).max(1) as f64;
+        let centrality: Vec<Value> = ranked
+            .iter()
+            .take(top)
+            .enumerate()
+            .map(|(rank, &node)| {
+                let degree = neighbours[node].len();
+                let mut entry = self.assets[node].json();
+                entry["rank"] = json!(rank + 1);
+                entry["component"] = json!(component_of[node]);
+                entry["degree"] = json!(degree);
+                entry["degree_centrality"] = json!(round(degree as f64 / others, 4));
+                entry["weighted_degree"] = json!(round(strength(node), 2));
+                entry["mean_score"] = json!(round(strength(node) / degree.max(1) as f64, 2));
+                entry
+            })
+            .collect();
+        let components: Vec<Value> = components
+            .iter()
+            .enumerate()
+            .map(|(number, component)| {
+                let scores: Vec<f64> = self
+                    .edges
+                    .iter()
+                    .filter(|((a, _), _)| component_of[*a] == number + 1)
+                    .map(|(_, score)| *score)
+                    .collect();
+                let hub = ranked
+                    .iter()
+                    .find(|node| component_of[**node] == number + 1)
+                    .map(|node| self.assets[*node].json());
+                json!({
+                    "component": number + 1,
+                    "size": component.len(),
+                    "matches": scores.len(),
+                    "mean_score": round(scores.iter().sum::<f64>() / scores.len().max(1) as f64, 2),
+                    "hub": hub,
+                    "assets": component.iter().map(|node| self.assets[*node].json()).collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        json!({
+            "assets": self.assets.len(),
+            "matches": self.edges.len(),
+            "components": components,
+            "centrality": centrality
+        })
+    }
+
+    /// Graphviz DOT, with each match's score as its weight and label.
+    pub fn to_dot(&self) -> String {
+        let quote = |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut dot = String::from("graph similarity {\n");
+        for asset in &self.assets {
+            dot.push_str(&format!(
+                "  {} [label={}];\n",
+                quote(asset.key()),
+                quote(asset.label())
+            ));
+        }
+        for (&(a, b), score) in &self.edges {
+            dot.push_str(&format!(
+                "  {} -- {} [weight={}, label=\"{}\"];\n",
+                quote(self.assets[a].key()),
+                quote(self.assets[b].key()),
+                score,
+                score
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// GraphML, with `uuid` and `path` on nodes and `score` on edges.
+    pub fn to_graphml(&self) -> String {
+        let mut xml = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"uuid\" for=\"node\" attr.name=\"uuid\" attr.type=\"string\"/>\n",
+            "  <key id=\"path\" for=\"node\" attr.name=\"path\" attr.type=\"string\"/>\n",
+            "  <key id=\"score\" for=\"edge\" attr.name=\"score\" attr.type=\"double\"/>\n",
+            "  <graph id=\"similarity\" edgedefault=\"undirected\">\n"
+        ));
+        for (index, asset) in self.assets.iter().enumerate() {
+            xml.push_str(&format!("    <node id=\"n{}\">", index));
+            for (key, value) in [("uuid", &asset.uuid), ("path", &asset.path)] {
+                if let Some(value) = value {
+                    xml.push_str(&format!(
+                        "<data key=\"{}\">{}</data>",
+                        key,
+                        xml_escape(value)
+                    ));
+                }
+            }
+            xml.push_str("</node>\n");
+        }
+        for (&(a, b), score) in &self.edges {
+            xml.push_str(&format!(
+                "    <edge source=\"n{}\" target=\"n{}\"><data key=\"score\">{}</data></edge>\n",
+                a, b, score
+            ));
+        }
+        xml.push_str("  </graph>\n</graphml>\n");
+        xml
+    }
+}
+
+fn round(value: f64, digits: i32) -> f64 {
+    let scale = 10f64.powi(digits);
+    (value * scale).round() / scale
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The `pcli2_similarity_graph` result: runs the folder match named by
+/// `search` and reports the graph of its matches, storing an export of it
+/// as a resource when `export` asks for one.
+pub async fn similarity_graph(state: &AppState, args: Value) -> Result<Value, String> {
+    validate_range_u64(&args, "top", 1, MAX_TOP)?;
+    let search = args
+        .get("search")
+        .and_then(Value::as_str)
+        .unwrap_or(GRAPH_SEARCHES[0]);
+    let mut match_args: Map<String, Value> = MATCH_KEYS
+        .iter()
+        .filter_map(|key| Some((key.to_string(), args.get(*key)?.clone())))
+        .collect();
+    match_args.insert("format".to_string(), json!("json"));
+    let match_args = Value::Object(match_args);
+    let output = match search {
+        "geometric" => run_pcli2_folder_geometric_match(match_args).await,
+        "part" => run_pcli2_folder_part_match(match_args).await,
+        "visual" => run_pcli2_folder_visual_match(match_args).await,
+        other => return Err(format!("Unknown search '{}'", other)),
+    }?;
+    let rows = match_rows(&output)?;
+    let threshold = args.get("threshold").and_then(Value::as_f64);
+    let graph = SimilarityGraph::from_matches(&rows, threshold);
+    let top = args
+        .get("top")
+        .and_then(Value::as_u64)
+        .unwrap_or(DEFAULT_TOP) as usize;
+    let mut report = graph.report(top);
+    let folders = parse_string_list(&args, "folder_path");
+    report["search"] = json!(search);
+    report["folders"] = json!(folders);
+
+    let largest = report["components"][0]["size"].as_u64().unwrap_or(0);
+    let groups = report["components"]
+        .as_array()
+        .map_or(0, |components| components.len());
+    let mut headline = format!(
+        "Built a similarity graph of {} assets and {} {} matches in {}: {} groups of similar assets, the largest with {} assets.",
+        graph.assets.len(),
+        graph.edges.len(),
+        search,
+        folders.join(", "),
+        groups,
+        largest
+    );
+    let export = match args.get("export").and_then(Value::as_str) {
+        Some(format) => {
+            let (text, extension, mime_type) = match format {
+                "dot" => (graph.to_dot(), "dot", "text/vnd.graphviz"),
+                "graphml" => (graph.to_graphml(), "graphml", "application/graphml+xml"),
+                other => return Err(format!("Unknown export format '{}'", other)),
+            };
+            let stored = state.resources.insert(
+                &format!("similarity graph.{}", extension),
+                mime_type,
+                text,
+                args.get("tenant").and_then(Value::as_str),
+            );
+            report["export"] = json!(stored.uri);
+            headline.push_str(&format!(
+                " The {} export is resource {}.",
+                extension, stored.uri
+            ));
+            Some(resource_link(&stored))
+        }
+        None => None,
+    };
+    let text = serde_json::to_string_pretty(&report)
+        .map_err(|err| format!("Failed to render results: {}", err))?;
+    let mut blocks = vec![summary_block(headline), raw_output_block(text)];
+    blocks.extend(export);
+    Ok(tool_result(blocks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(reference: &str, candidate: &str, score: f64) -> Value {
+        json!({
+            "reference_asset_path": reference,
+            "candidate_asset_path": candidate,
+            "match_percentage": score
+        })
+    }
+
+    fn rows() -> Vec<Value> {
+        vec![
+            row("/R/a", "/R/b", 97.0),
+            row("/R/b", "/R/a", 98.5),
+            row("/R/a", "/R/c", 91.0),
+            row("/R/b", "/R/c", 88.0),
+            row("/R/a", "/R/a", 100.0),
+            row("/R/d", "/R/e", 82.0),
+            json!({ "reference_asset_path": "/R/f", "match_percentage": 99.0 }),
+        ]
+    }
+
+    #[test]
+    fn test_matches_become_components_and_rankings() {
+        let graph = SimilarityGraph::from_matches(&rows(), None);
+        assert_eq!(graph.assets.len(), 5);
+        assert_eq!(graph.edges.len(), 4);
+        assert_eq!(graph.edges[&(0, 1)], 98.5);
+
+        let report = graph.report(2);
+        assert_eq!(report["components"][0]["size"], 3);
+        assert_eq!(report["components"][0]["matches"], 3);
+        assert_eq!(report["components"][0]["hub"]["path"], "/R/a");
+        assert_eq!(report["components"][1]["assets"][1]["path"], "/R/e");
+        let centrality = report["centrality"].as_array().unwrap();
+        assert_eq!(centrality.len(), 2);
+        assert_eq!(centrality[0]["path"], "/R/a");
+        assert_eq!(centrality[0]["degree"], 2);
+        assert_eq!(centrality[0]["degree_centrality"], 0.5);
+        assert_eq!(centrality[0]["weighted_degree"], 189.5);
+        assert_eq!(centrality[1]["path"], "/R/b");
+
+        let strong = SimilarityGraph::from_matches(&rows(), Some(90.0));
+        assert_eq!(strong.edges.len(), 2);
+        assert_eq!(strong.report(10)["components"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_exports_name_every_asset_and_match() {
+        let graph = SimilarityGraph::from_matches(
+            &[
+                row("/R/a \"1\"", "/R/b", 97.0),
+                json!({ "reference_asset_uuid": "u1", "candidate_asset_uuid": "u2", "candidate_asset_path": "/R/<c>", "score": "91.5%" }),
+            ],
+            None,
+        );
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("graph similarity {\n"));
+        assert!(dot.contains(r#""/R/a \"1\"" -- "/R/b" [weight=97, label="97"];"#));
+        assert!(dot.contains(r#""u2" [label="/R/<c>"];"#));
+        let graphml = graph.to_graphml();
+        assert!(graphml.contains(
+            r#"<node id="n3"><data key="uuid">u2</data><data key="path">/R/&lt;c&gt;</data></node>"#
+        ));
+        assert!(
+            graphml
+                .contains(r#"<edge source="n2" target="n3"><data key="score">91.5</data></edge>"#)
+        );
+    }
+}
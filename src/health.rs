@@ -0,0 +1,256 @@
+use crate::{
+    AppState,
+    config::{HealthConfig, ToolPriority},
+};
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use utoipa::ToSchema;
+
+/// Most runs kept, however short they are.
+pub const MAX_HEALTH_SAMPLES: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthState {
+    Healthy,
+    /// Too many recent runs failed or took too long; low-priority tools
+    /// are shed.
+    Degraded,
+    /// The circuit breaker is open.
+    Unavailable,
+}
+
+impl HealthState {
+    pub fn name(self) -> &'static str {
+        match self {
+            HealthState::Healthy => "healthy",
+            HealthState::Degraded => "degraded",
+            HealthState::Unavailable => "unavailable",
+        }
+    }
+}
+
+/// The backend's health over the window, as `/health/ready` and
+/// `initialize` report it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthReport {
+    pub state: HealthState,
+    pub runs: usize,
+    pub failures: usize,
+    pub p95_ms: Option<u64>,
+    pub window_secs: u64,
+    pub reason: Option<String>,
+}
+
+impl HealthReport {
+    pub fn error_rate(&self) -> f64 {
+        if self.runs == 0 {
+            0.0
+        } else {
+            self.failures as f64 / self.runs as f64
+        }
+    }
+
+    pub fn view(&self) -> HealthView {
+        HealthView {
+            status: self.state,
+            runs: self.runs,
+            failures: self.failures,
+            error_rate: (self.error_rate() * 1000.0).round() / 1000.0,
+            p95_ms: self.p95_ms,
+            window_secs: self.window_secs,
+            reason: self.reason.clone(),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!(self.view())
+    }
+}
+
+/// A `HealthReport` as it is shown.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct HealthView {
+    pub status: HealthState,
+    pub runs: usize,
+    pub failures: usize,
+    /// Share of the runs that failed, to three decimals.
+    pub error_rate: f64,
+    pub p95_ms: Option<u64>,
+    pub window_secs: u64,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Run {
+    at: Instant,
+    ok: bool,
+    elapsed: Duration,
+}
+
+/// Outcomes and run times of recent `pcli2` runs made for tool calls.
+#[derive(Clone, Default)]
+pub struct BackendHealth {
+    runs: Arc<Mutex<VecDeque<Run>>>,
+}
+
+impl BackendHealth {
+    pub fn record(&self, ok: bool, elapsed: Duration) {
+        let mut runs = self.runs.lock().unwrap_or_else(|e| e.into_inner());
+        if runs.len() >= MAX_HEALTH_SAMPLES {
+            runs.pop_front();
+        }
+        runs.push_back(Run {
+            at: Instant::now(),
+            ok,
+            elapsed,
+        });
+    }
+
+    /// Judges the runs of the last `config.window_secs`.
+    pub fn report(&self, config: &HealthConfig, breaker_open: bool) -> HealthReport {
+        let window = config.window();
+        let now = Instant::now();
+        let mut runs = self.runs.lock().unwrap_or_else(|e| e.into_inner());
+        while runs
+            .front()
+            .is_some_and(|run| now.duration_since(run.at) >= window)
+        {
+            runs.pop_front();
+        }
+        let failures = runs.iter().filter(|run| !run.ok).count();
+        let mut elapsed: Vec<Duration> = runs.iter().map(|run| run.elapsed).collect();
+        elapsed.sort();
+        let p95_ms = match elapsed.len() {
+            0 => None,
+            len => Some(elapsed[(len * 95).div_ceil(100) - 1].as_millis() as u64),
+        };
+        let mut report = HealthReport {
+            state: HealthState::Healthy,
+            runs: runs.len(),
+            failures,
+            p95_ms,
+            window_secs: config.window_secs,
+            reason: None,
+        };
+        if breaker_open {
+            report.state = HealthState::Unavailable;
+            report.reason = Some("the circuit breaker is open".to_string());
+        } else if report.runs >= config.min_samples {
+            let error_rate = report.error_rate();
+            if error_rate >= config.max_error_rate {
+                report.state = HealthState::Degraded;
+                report.reason = Some(format!(
+                    "{} of the last {} pcli2 runs failed",
+                    failures, report.runs
+                ));
+            } else if let (Some(limit), Some(p95)) = (config.max_p95_ms, p95_ms)
+                && p95 >= limit
+            {
+                report.state = HealthState::Degraded;
+                report.reason = Some(format!(
+                    "95% of the last {} pcli2 runs took up to {} ms (limit {} ms)",
+                    report.runs, p95, limit
+                ));
+            }
+        }
+        report
+    }
+}
+
+/// The health of the state's backend.
+pub fn backend_health(state: &AppState) -> HealthReport {
+    state
+        .health
+        .report(&state.config.health, state.breaker.is_open())
+}
+
+/// Refuses a low-priority `tool` while the backend is not healthy, so the
+/// calls that matter get what capacity is left.
+pub fn shed_low_priority(state: &AppState, tool: &str) -> Result<(), String> {
+    if state.config.health.priority(tool) != ToolPriority::Low {
+        return Ok(());
+    }
+    let report = backend_health(state);
+    match report.reason {
+        Some(reason) if report.state != HealthState::Healthy => Err(format!(
+            "The pcli2 backend is {} ({}); low-priority tool '{}' is not run until it recovers. Retry later.",
+            report.state.name(),
+            reason,
+            tool
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> HealthConfig {
+        HealthConfig {
+            min_samples: 4,
+            max_p95_ms: Some(1_000),
+            ..HealthConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_error_rate_degrades_the_backend() {
+        let health = BackendHealth::default();
+        for ok in [true, false, false] {
+            health.record(ok, Duration::from_millis(10));
+        }
+        // Too few runs to judge.
+        assert_eq!(health.report(&config(), false).state, HealthState::Healthy);
+        health.record(true, Duration::from_millis(10));
+        let report = health.report(&config(), false);
+        assert_eq!(report.state, HealthState::Degraded);
+        assert_eq!(report.error_rate(), 0.5);
+        assert!(report.reason.unwrap().contains("2 of the last 4"));
+        assert_eq!(
+            health.report(&config(), true).state,
+            HealthState::Unavailable
+        );
+    }
+
+    #[test]
+    fn test_slow_runs_degrade_the_backend() {
+        let health = BackendHealth::default();
+        for millis in [100, 200, 300, 1_500] {
+            health.record(true, Duration::from_millis(millis));
+        }
+        let report = health.report(&config(), false);
+        assert_eq!(report.state, HealthState::Degraded);
+        assert_eq!(report.p95_ms, Some(1_500));
+        assert_eq!(report.to_json()["status"], "degraded");
+        let relaxed = HealthConfig {
+            max_p95_ms: None,
+            ..config()
+        };
+        assert_eq!(health.report(&relaxed, false).state, HealthState::Healthy);
+    }
+
+    #[test]
+    fn test_old_runs_leave_the_window() {
+        let health = BackendHealth::default();
+        for _ in 0..4 {
+            health.record(false, Duration::from_millis(10));
+        }
+        for run in health.runs.lock().unwrap().iter_mut() {
+            run.at -= Duration::from_secs(2);
+        }
+        let short = HealthConfig {
+            window_secs: 1,
+            ..config()
+        };
+        let report = health.report(&short, false);
+        assert_eq!(report.runs, 0);
+        assert_eq!(report.state, HealthState::Healthy);
+    }
+}
@@ -0,0 +1,439 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Language of tool descriptions and of the errors the server writes
+/// itself (`locale` config). Output of `pcli2` is passed on as it is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    En,
+    Ja,
+}
+
+const EXAMPLES_HEADING: &str = "\n\nExamples:";
+
+/// Japanese descriptions of the tools, without their examples.
+const JA_TOOL_DESCRIPTIONS: [(&str, &str); 48] = [
+    (
+        "pcli2",
+        "非推奨: 代わりに個別のフォルダー一覧ツールとアセット一覧ツールを使用してください。Physna Command Line Interface v2 (PCLI2)。指定したオプションで `pcli2 folder list` または `pcli2 asset list` を実行します。",
+    ),
+    ("pcli2_folder_list", "`pcli2 folder list` を実行します。"),
+    (
+        "pcli2_folder_suggest",
+        "途中まで入力された、または綴りを誤ったフォルダー名に対して、キャッシュ済みのフォルダーツリー (`pcli2 folder list`) とのあいまい一致の類似度順にフォルダーパスを提案します。フォルダーパスが見つからないときに使用してください。",
+    ),
+    ("pcli2_asset_list", "`pcli2 asset list` を実行します。"),
+    ("pcli2_tenant_list", "`pcli2 tenant list` を実行します。"),
+    ("pcli2_version", "`pcli2 --version` を実行します。"),
+    ("pcli2_config_get", "`pcli2 config get` を実行します。"),
+    (
+        "pcli2_config_get_path",
+        "`pcli2 config get path` を実行します。",
+    ),
+    (
+        "pcli2_config_environment_list",
+        "`pcli2 config environment list` を実行します。",
+    ),
+    (
+        "pcli2_config_environment_get",
+        "`pcli2 config environment get` を実行します。",
+    ),
+    (
+        "pcli2_tenant_get",
+        "`pcli2 tenant get` を実行します (現在のテナント)。",
+    ),
+    ("pcli2_tenant_state", "`pcli2 tenant state` を実行します。"),
+    (
+        "pcli2_tenant_use",
+        "`pcli2 tenant use --name <tenantName>` を実行します。",
+    ),
+    ("pcli2_folder_get", "`pcli2 folder get` を実行します。"),
+    (
+        "pcli2_folder_resolve",
+        "`pcli2 folder resolve` を実行します。",
+    ),
+    (
+        "pcli2_folder_dependencies",
+        "`pcli2 folder dependencies` を実行します。深いアセンブリには `max_depth`、`page`、`root` を指定してください。サーバーはトップレベルのアセットを 1 ページずつ、`max_depth` で切り詰めた JSON ツリーとして返し、各フォルダーの完全なツリーを `pcli2://dependencies/...` リソースとして保持するため、以降のページや `root` の呼び出しで `pcli2` を再実行しません。",
+    ),
+    (
+        "pcli2_folder_geometric_match",
+        "`pcli2 folder geometric-match` を実行します。",
+    ),
+    (
+        "pcli2_folder_part_match",
+        "`pcli2 folder part-match` を実行します。",
+    ),
+    (
+        "pcli2_folder_visual_match",
+        "`pcli2 folder visual-match` を実行します。",
+    ),
+    ("pcli2_asset_get", "`pcli2 asset get` を実行します。"),
+    (
+        "pcli2_asset_dependencies",
+        "`pcli2 asset dependencies` を実行します。",
+    ),
+    (
+        "pcli2_asset_thumbnail",
+        "`pcli2 asset thumbnail` を実行し、base64 でエンコードした PNG を返します。",
+    ),
+    (
+        "pcli2_asset_export",
+        "`pcli2 asset download` を実行し、アセットのファイルを resources/read で読み取るリソースとして返します。CAM、シミュレーションなどのツールで使用できます。サーバーの pcli2 が対応していれば、`target_format` と `units` でファイルを変換します。",
+    ),
+    (
+        "pcli2_folder_thumbnail_gallery",
+        "フォルダー内のアセットのサムネイルをダウンロードし (`pcli2 asset list` の後、アセットごとに `pcli2 asset thumbnail`)、アセット名と UUID のキャプションに続く画像ブロックとして返します。1 回の呼び出しでフォルダーを目視で確認できます。",
+    ),
+    (
+        "pcli2_asset_batch_get",
+        "スプレッドシートから貼り付けた列など、UUID またはパスの CSV または JSON リストからまとめてアセットを検索し、異なるアセットごとに `pcli2 asset get` を実行します。入力の各行に対して入力順に 1 行を返し、アセット、または見つからなかった理由を示します。",
+    ),
+    (
+        "pcli2_folder_download_archive",
+        "フォルダー内のアセットのファイル (または `content: thumbnails` でサムネイル) をダウンロードし、各ファイルのサイズと SHA-256 を記載した `manifest.json` と共に 1 つの zip または tar.zst アーカイブにまとめ、resources/read で読み取るリソースとして返します。",
+    ),
+    (
+        "pcli2_asset_reprocess",
+        "`pcli2 asset reprocess` を実行します。",
+    ),
+    (
+        "pcli2_geometric_match",
+        "Physna Command Line Interface v2 (PCLI2)。指定したオプションで `pcli2 asset geometric-match` を実行します。",
+    ),
+    (
+        "pcli2_asset_part_match",
+        "`pcli2 asset part-match` を実行します。",
+    ),
+    (
+        "pcli2_asset_visual_match",
+        "`pcli2 asset visual-match` を実行します。",
+    ),
+    (
+        "pcli2_image_visual_match",
+        "部品の写真やスケッチなど、クライアントが渡した画像に見た目が似ているアセットを検索します。画像はサーバー上のファイルに書き込まれ、管理者が設定した `pcli2` の視覚一致コマンドに渡されます。設定されている場合のみ使用できます。",
+    ),
+    (
+        "pcli2_asset_text_match",
+        "`pcli2 asset text-match` を実行します。",
+    ),
+    (
+        "pcli2_multi_tenant_search",
+        "複数のテナントを並行して検索します。`text` のテキスト一致を実行し、`uuid` または `path` が指定されていれば、各テナントでそのアセットに対する幾何一致も実行します。結果は `tenant` 列と `search` 列を持つ 1 つの表にまとめられます。",
+    ),
+    (
+        "pcli2_asset_metadata_create",
+        "`pcli2 asset metadata create` を実行します。",
+    ),
+    (
+        "pcli2_asset_upload",
+        "`pcli2 asset create` でファイルを新しいアセットとしてフォルダーにアップロードします。ファイルは base64 の `content`、このサーバーが保持する `pcli2://results/...` または `job://` の `resource`、またはサーバー上の `file` で指定します。content とリソースは一時ファイルに書き込まれ、終了後に削除されます。",
+    ),
+    (
+        "pcli2_batch",
+        "pcli2 ツールの呼び出しを順番に並べた一覧を 1 回のリクエストで実行し、ステップごとの結果を返します。",
+    ),
+    (
+        "pcli2_asset_metadata_delete",
+        "`pcli2 asset metadata delete` を実行します。",
+    ),
+    (
+        "pcli2_folder_snapshot",
+        "フォルダーの現在のアセット一覧をメタデータを含めて名前付きスナップショットとしてサーバーに保存し、後で `pcli2_folder_diff` で比較できるようにします。`pcli2 asset list --metadata` を実行します。",
+    ),
+    (
+        "pcli2_folder_diff",
+        "2 つのフォルダースナップショット、またはスナップショットと現在のフォルダーを比較し、追加、削除、変更されたアセット (メタデータの変更を含む) を報告します。",
+    ),
+    (
+        "pcli2_credentials_register",
+        "サーバー上の pcli2 の認証情報を登録または更新します。`environment` と `tenant` が指定されていればそれらに切り替えてから、`client_id` と `client_secret` でログインします。サーバー管理者が有効にした場合のみ使用できます。",
+    ),
+    (
+        "pcli2_similarity_graph",
+        "フォルダー一致を実行し、その組ごとの一致を、最も高い類似度スコアで結ばれたアセットのグラフにします。類似アセットの連結グループ (大きい順、各グループで最も多く結ばれたアセットを `hub` として表示)、一致の数と強さで順位付けした中心的なアセットを報告し、`export` を指定するとグラフを DOT または GraphML のリソースとして保存します。",
+    ),
+    (
+        "pcli2_index_search",
+        "サーバーがインデックスしているフォルダーについて、アセット名、パス、メタデータのローカルインデックスを検索します。pcli2 を実行せず、ミリ秒で応答します。すべての語が一致する必要があり、語句を引用符で囲むとまとめて一致させます。結果はテナントより最大で更新間隔分だけ古いことがあり、各フォルダーが最後にインデックスされた時刻が結果に示されます。サーバー管理者がインデックス対象のフォルダーを設定した場合のみ使用できます。",
+    ),
+    (
+        "pcli2_artifacts_list",
+        "以前の呼び出しのためにサーバーがディスクとメモリに保持しているものを一覧表示します。保存された結果のサイズ、経過時間、クライアントが最後に読み取った時刻と、失敗または中断した呼び出しが残したファイルです。保持期間の制限も表示します。サーバー管理者が有効にした場合のみ使用できます。",
+    ),
+    (
+        "pcli2_artifacts_purge",
+        "保存された結果と残されたファイルを削除します。`uris` に列挙した結果、`older_than_secs` より古いものすべて、どちらも指定しない場合はサーバーの保持制限で不要になったものです。最近読み取られた結果は `uris` で指定しない限り残されます。サーバー管理者が有効にした場合のみ使用できます。",
+    ),
+    (
+        "pcli2_audit_tail",
+        "このサーバーでの最近のツール呼び出しを古い順に表示します。ツール、リクエスト ID、テナント、引数 (秘密情報は伏せ字)、状態、エラー、所要時間です。`audit://recent` リソースとしても読み取れます。",
+    ),
+    (
+        "pcli2_plan",
+        "「鋳物フォルダーでブラケット X に似た部品を探す」のような自然言語の依頼に対するツール呼び出しを計画します。実行するステップを順番に、依頼から取り出した引数とともに返します。`$steps[0].matches[0].path` のような引数は前のステップの出力の値です。依頼に不足しているものも列挙します。何も実行しません。",
+    ),
+    (
+        "pcli2_quota_status",
+        "このセッションのツール呼び出しクォータを報告します。クォータごとの使用回数と残り回数、最も古い呼び出しが集計期間から外れるまでの秒数です。どのクォータにも数えられません。",
+    ),
+    (
+        "pcli2_approval_status",
+        "管理者の承認を待っている呼び出しを、その呼び出しが返した承認 ID で報告します。保留中、実行中、却下のいずれか、または実行済みであればその結果を返します。サーバーが一部のツールを承認待ちにしている場合のみ使用できます。",
+    ),
+];
+
+/// Japanese forms of the errors the server writes, with `{}` standing for
+/// the same values in the same order as in the English message. More
+/// specific messages come first.
+const JA_ERRORS: [(&str, &str); 30] = [
+    ("Missing tool name", "ツール名がありません"),
+    (
+        "Folder '{}' is not indexed; indexed folders: {}",
+        "フォルダー '{}' はインデックスされていません。インデックス対象のフォルダー: {}",
+    ),
+    (
+        "The asset index is not built yet: no indexed folder has been listed. Try again shortly",
+        "アセットインデックスはまだ作成されていません: インデックス対象のフォルダーがまだ一覧取得されていません。しばらくしてから再試行してください",
+    ),
+    ("Unknown tool '{}'", "不明なツール '{}'"),
+    (
+        "Tool '{}' is disabled: the installed pcli2 cannot run it ({})",
+        "ツール '{}' は無効です: インストールされている pcli2 では実行できません ({})",
+    ),
+    (
+        "Tool '{}' is not available to API keys bound to tenant '{}'",
+        "ツール '{}' はテナント '{}' に限定された API キーでは使用できません",
+    ),
+    (
+        "Tool '{}' is not allowed by policy '{}'",
+        "ツール '{}' はポリシー '{}' で許可されていません",
+    ),
+    (
+        "Tenant '{}' is not allowed by policy '{}'; use one of: {}",
+        "テナント '{}' はポリシー '{}' で許可されていません。次のいずれかを使用してください: {}",
+    ),
+    (
+        "Policy '{}' requires 'tenant': one of {}",
+        "ポリシー '{}' では 'tenant' が必要です: {} のいずれか",
+    ),
+    (
+        "Invalid arguments: expected a JSON object",
+        "引数が不正です: JSON オブジェクトが必要です",
+    ),
+    (
+        "Missing required argument: provide either 'uuid' or 'path'",
+        "必須の引数がありません: 'uuid' または 'path' を指定してください",
+    ),
+    (
+        "Missing required argument: '{}'",
+        "必須の引数がありません: '{}'",
+    ),
+    (
+        "Unknown argument '{}': expected one of [{}]",
+        "不明な引数 '{}': 次のいずれかを指定してください: [{}]",
+    ),
+    (
+        "Invalid argument 'steps': at least one step is required",
+        "引数 'steps' が不正です: ステップを 1 つ以上指定してください",
+    ),
+    (
+        "Invalid argument '{}': value {} must be between {} and {}",
+        "引数 '{}' が不正です: 値 {} は {} から {} の範囲で指定してください",
+    ),
+    (
+        "Invalid argument '{}': expected {}, got {}",
+        "引数 '{}' が不正です: {} が必要ですが {} が指定されました",
+    ),
+    (
+        "Too many inputs: {} provided, at most {} are allowed per call",
+        "入力が多すぎます: {} 件指定されましたが、1 回の呼び出しで指定できるのは {} 件までです",
+    ),
+    (
+        "{} needs an operator's approval, which is turned off for this session",
+        "{} には管理者の承認が必要ですが、このセッションでは承認が無効になっています",
+    ),
+    (
+        "Quota '{}' exhausted: {} of {} calls used{}. {}",
+        "クォータ '{}' を使い切りました: {} / {} 回使用済み{}。{}",
+    ),
+    (" in the last {}s", " (直近 {} 秒)"),
+    (
+        "Next call allowed in {}s.",
+        "次の呼び出しは {} 秒後に可能です。",
+    ),
+    (
+        "The limit applies for the rest of the session.",
+        "この制限はセッションの終わりまで適用されます。",
+    ),
+    (
+        "{} Call {} to see current usage.",
+        "{} 現在の使用状況は {} で確認できます。",
+    ),
+    (
+        "Server overloaded: {} requests in flight. Retry after {} seconds.",
+        "サーバーが混雑しています: 処理中のリクエストが {} 件あります。{} 秒後に再試行してください。",
+    ),
+    (
+        "pcli2 backend unavailable since {} ({} consecutive failures), last error: {}. Next health probe in {}s.",
+        "pcli2 バックエンドは {} から利用できません (連続 {} 回失敗)。最後のエラー: {}。次のヘルスチェックは {} 秒後です。",
+    ),
+    (
+        "The pcli2 backend is {} ({}); low-priority tool '{}' is not run until it recovers. Retry later.",
+        "pcli2 バックエンドは {} です ({})。優先度の低いツール '{}' は回復するまで実行されません。しばらくしてから再試行してください。",
+    ),
+    (
+        "Call to {} was cancelled by the server operator",
+        "{} の呼び出しはサーバー管理者によって取り消されました",
+    ),
+    (
+        "Call to {} did not finish in time",
+        "{} の呼び出しは時間内に完了しませんでした",
+    ),
+    (
+        "{} failed (code {}):\n{}",
+        "{} が失敗しました (コード {}):\n{}",
+    ),
+    (
+        "Failed to execute pcli2: {}",
+        "pcli2 を実行できませんでした: {}",
+    ),
+];
+
+/// Values of the `{}`s of `template` in `message`, if `message` is one.
+/// Each value ends at the first place the text that follows it appears.
+fn captures<'a>(template: &str, message: &'a str) -> Option<Vec<&'a str>> {
+    let mut pieces = template.split("{}");
+    let mut rest = message.strip_prefix(pieces.next()?)?;
+    let pieces: Vec<&str> = pieces.collect();
+    let mut values = Vec::new();
+    for (index, piece) in pieces.iter().enumerate() {
+        if index + 1 == pieces.len() {
+            values.push(rest.strip_suffix(piece)?);
+            return Some(values);
+        }
+        let end = rest.find(piece).filter(|_| !piece.is_empty())?;
+        values.push(&rest[..end]);
+        rest = &rest[end + piece.len()..];
+    }
+    (rest.is_empty()).then_some(values)
+}
+
+fn fill(template: &str, values: &[String]) -> String {
+    let mut out = String::new();
+    let mut values = values.iter();
+    let mut pieces = template.split("{}").peekable();
+    while let Some(piece) = pieces.next() {
+        out.push_str(piece);
+        if pieces.peek().is_some() {
+            out.push_str(values.next().map(String::as_str).unwrap_or_default());
+        }
+    }
+    out
+}
+
+fn translate(catalog: &[(&str, &str)], message: &str, depth: usize) -> Option<String> {
+    if depth == 0 {
+        return None;
+    }
+    catalog.iter().find_map(|(english, translated)| {
+        let values: Vec<String> = captures(english, message)?
+            .into_iter()
+            .map(|value| translate(catalog, value, depth - 1).unwrap_or_else(|| value.to_string()))
+            .collect();
+        Some(fill(translated, &values))
+    })
+}
+
+impl Locale {
+    fn errors(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::En => &[],
+            Self::Ja => &JA_ERRORS,
+        }
+    }
+
+    /// `message` in this locale when the server wrote it; values inside it,
+    /// such as `pcli2` output, stay as they are.
+    pub fn error(self, message: &str) -> String {
+        translate(self.errors(), message, 3).unwrap_or_else(|| message.to_string())
+    }
+
+    /// Rewrites the description of `tool` (an entry of `tools/list`, under
+    /// its internal `name`) in this locale, keeping its examples.
+    pub fn describe_tool(self, tool: &mut Value) {
+        let translated = match self {
+            Self::En => None,
+            Self::Ja => tool["name"].as_str().and_then(|name| {
+                JA_TOOL_DESCRIPTIONS
+                    .iter()
+                    .find(|(tool, _)| *tool == name)
+                    .map(|(_, description)| *description)
+            }),
+        };
+        let (Some(translated), Some(description)) = (translated, tool["description"].as_str())
+        else {
+            return;
+        };
+        let localized = match description.split_once(EXAMPLES_HEADING) {
+            Some((_, examples)) => format!("{}\n\n例:{}", translated, examples),
+            None => translated.to_string(),
+        };
+        tool["description"] = Value::String(localized);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pcli::tool_list;
+    use serde_json::json;
+
+    #[test]
+    fn test_every_tool_has_a_japanese_description() {
+        for mut tool in tool_list() {
+            let english = tool["description"].as_str().unwrap().to_string();
+            Locale::Ja.describe_tool(&mut tool);
+            let japanese = tool["description"].as_str().unwrap();
+            assert_ne!(japanese, english, "{}", tool["name"]);
+            assert_eq!(
+                japanese.contains("\n\n例:"),
+                english.contains(EXAMPLES_HEADING),
+                "{}",
+                tool["name"]
+            );
+        }
+        let mut tool = json!({ "name": "pcli2_asset_get", "description": "Runs." });
+        Locale::En.describe_tool(&mut tool);
+        assert_eq!(tool["description"], "Runs.");
+    }
+
+    #[test]
+    fn test_errors_are_translated_with_their_values() {
+        assert_eq!(
+            Locale::Ja.error("Unknown tool 'pcli2_nope'"),
+            "不明なツール 'pcli2_nope'"
+        );
+        assert_eq!(
+            Locale::Ja.error("Invalid argument 'threshold': value 120 must be between 0 and 100"),
+            "引数 'threshold' が不正です: 値 120 は 0 から 100 の範囲で指定してください"
+        );
+        assert_eq!(
+            Locale::Ja.error(
+                "Quota 'hourly' exhausted: 5 of 5 calls used in the last 3600s. Next call allowed in 12s. Call pcli2_quota_status to see current usage."
+            ),
+            "クォータ 'hourly' を使い切りました: 5 / 5 回使用済み (直近 3600 秒)。次の呼び出しは 12 秒後に可能です。 現在の使用状況は pcli2_quota_status で確認できます。"
+        );
+        assert_eq!(
+            Locale::Ja.error("pcli2 asset get failed (code exit status: 1):\nnot found"),
+            "pcli2 asset get が失敗しました (コード exit status: 1):\nnot found"
+        );
+        assert_eq!(Locale::Ja.error("Something else"), "Something else");
+        assert_eq!(
+            Locale::En.error("Unknown tool 'pcli2_nope'"),
+            "Unknown tool 'pcli2_nope'"
+        );
+    }
+}
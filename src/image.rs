@@ -0,0 +1,242 @@
+use crate::{
+    AppState,
+    config::{ImageMatchConfig, ServerConfig},
+    pcli::{call_workspace, run_pcli2_command},
+};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64_STANDARD};
+use serde_json::Value;
+use std::{
+    env,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub const IMAGE_MATCH_TOOL: &str = "pcli2_image_visual_match";
+/// Accepted image formats: MIME type, file extension and leading bytes.
+pub const IMAGE_FORMATS: [(&str, &str, &[u8]); 2] = [
+    ("image/png", "png", b"\x89PNG\r\n\x1a\n"),
+    ("image/jpeg", "jpg", b"\xff\xd8\xff"),
+];
+
+/// Decodes `value` of argument `name`, plain base64 or a base64 `data:`
+/// URL, refusing it when it is empty or holds more than `max_bytes`.
+/// Returns the bytes with the MIME type of a `data:` URL.
+pub(crate) fn decode_base64<'a>(
+    name: &str,
+    value: &'a str,
+    max_bytes: usize,
+) -> Result<(Vec<u8>, Option<&'a str>), String> {
+    let (declared, encoded) = match value.strip_prefix("data:") {
+        Some(url) => {
+            let (header, data) = url
+                .split_once(',')
+                .ok_or_else(|| format!("Invalid '{}': the data URL has no data", name))?;
+            let mime_type = header.strip_suffix(";base64").ok_or_else(|| {
+                format!("Invalid '{}': only base64 data URLs are supported", name)
+            })?;
+            (Some(mime_type), data)
+        }
+        None => (None, value),
+    };
+    let encoded: String = encoded.chars().filter(|c| !c.is_whitespace()).collect();
+    // Refuse oversized content before decoding it.
+    if encoded.len() / 4 * 3 > max_bytes + 3 {
+        return Err(format!(
+            "Invalid '{}': larger than the {} bytes allowed",
+            name, max_bytes
+        ));
+    }
+    let bytes = BASE64_STANDARD
+        .decode(encoded.as_bytes())
+        .map_err(|err| format!("Invalid '{}': not valid base64 ({})", name, err))?;
+    if bytes.is_empty() || bytes.len() > max_bytes {
+        return Err(format!(
+            "Invalid '{}': expected 1 to {} bytes, got {}",
+            name,
+            max_bytes,
+            bytes.len()
+        ));
+    }
+    Ok((bytes, declared))
+}
+
+/// Decodes the call's `image`, plain base64 or a `data:` URL, and returns
+/// its bytes with the file extension of its format.
+fn decode_image(args: &Value, max_bytes: usize) -> Result<(Vec<u8>, &'static str), String> {
+    let image = args
+        .get("image")
+        .and_then(Value::as_str)
+        .filter(|image| !image.trim().is_empty())
+        .ok_or_else(|| "Missing required parameter 'image'".to_string())?;
+    let (bytes, declared) = decode_base64("image", image, max_bytes)?;
+    let declared = declared.or_else(|| args.get("mime_type").and_then(Value::as_str));
+    let (mime_type, extension, _) = IMAGE_FORMATS
+        .iter()
+        .find(|(_, _, magic)| bytes.starts_with(magic))
+        .ok_or_else(|| "Invalid 'image': expected a PNG or JPEG image".to_string())?;
+    if let Some(declared) = declared
+        && declared != *mime_type
+    {
+        return Err(format!(
+            "Invalid 'mime_type': '{}' does not match the image, which is {}",
+            declared, mime_type
+        ));
+    }
+    Ok((bytes, extension))
+}
+
+/// A new path for a file the server hands to `pcli2`: in the call's
+/// working directory, else the sandbox's working directory when the
+/// sandbox has one, so a restricted `pcli2` can read it, else the temp
+/// directory. The name is `prefix`, the process, time and a sequence
+/// number, then `suffix`.
+pub(crate) fn scratch_path(
+    config: &ServerConfig,
+    workspace: Option<&Path>,
+    prefix: &str,
+    suffix: &str,
+) -> Result<PathBuf, String> {
+    static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+    let mut path = match (workspace, &config.sandbox.working_dir) {
+        (Some(dir), _) => dir.to_path_buf(),
+        (None, Some(dir)) if config.sandbox.enabled => dir.clone(),
+        _ => env::temp_dir(),
+    };
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|err| format!("Failed to read system time: {}", err))?
+        .as_millis();
+    path.push(format!(
+        "{}{}-{}-{}{}",
+        prefix,
+        std::process::id(),
+        timestamp,
+        SEQUENCE.fetch_add(1, Ordering::Relaxed),
+        suffix
+    ));
+    Ok(path)
+}
+
+fn tenant(args: &Value) -> Result<Option<&str>, String> {
+    match args.get("tenant").and_then(Value::as_str) {
+        Some(tenant) if tenant.starts_with('-') => Err(
+            "Invalid 'tenant': values starting with '-' would be read as a pcli2 option"
+                .to_string(),
+        ),
+        Some(tenant) if tenant.chars().any(char::is_control) => {
+            Err("Invalid 'tenant': control characters are not allowed".to_string())
+        }
+        tenant => Ok(tenant),
+    }
+}
+
+/// The configured `pcli2` arguments for the image at `file`.
+fn build_args(config: &ImageMatchConfig, file: &Path, tenant: Option<&str>) -> Vec<String> {
+    let file = file.display().to_string();
+    let mut args: Vec<String> = config
+        .args
+        .iter()
+        .map(|arg| arg.replace("{file}", &file))
+        .collect();
+    if let Some(tenant) = tenant {
+        args.extend(
+            config
+                .tenant_args
+                .iter()
+                .map(|arg| arg.replace("{tenant}", tenant)),
+        );
+    }
+    args
+}
+
+/// Removes a file (or directory) written for `pcli2` once the command is
+/// done, whatever its outcome.
+pub(crate) struct ScratchFile(pub PathBuf);
+
+impl Drop for ScratchFile {
+    fn drop(&mut self) {
+        if self.0.is_dir() {
+            let _ = std::fs::remove_dir_all(&self.0);
+        } else {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+}
+
+/// Writes the client's image to a file and runs the configured visual
+/// match on it.
+pub async fn visual_match_image(state: &AppState, args: Value) -> Result<String, String> {
+    let config = &state.config.image_match;
+    if !config.is_enabled() {
+        return Err("Image matching is not configured on this server".to_string());
+    }
+    let tenant = tenant(&args)?;
+    let (bytes, extension) = decode_image(&args, config.max_image_bytes)?;
+    let file = ScratchFile(scratch_path(
+        &state.config,
+        call_workspace().as_deref(),
+        "pcli2-image-",
+        &format!(".{}", extension),
+    )?);
+    let cmd_args = build_args(config, &file.0, tenant);
+    tokio::fs::write(&file.0, bytes)
+        .await
+        .map_err(|err| format!("Failed to write image {}: {}", file.0.display(), err))?;
+    run_pcli2_command(cmd_args, "pcli2 image visual match").await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\nrest";
+
+    #[test]
+    fn test_images_are_decoded_and_checked() {
+        let encoded = BASE64_STANDARD.encode(PNG);
+        let (bytes, extension) = decode_image(&json!({ "image": encoded }), 1024).unwrap();
+        assert_eq!((bytes.as_slice(), extension), (PNG, "png"));
+        let url = format!("data:image/png;base64,{}", encoded);
+        assert!(decode_image(&json!({ "image": url }), 1024).is_ok());
+
+        let jpeg = format!("data:image/jpeg;base64,{}", encoded);
+        assert!(
+            decode_image(&json!({ "image": jpeg }), 1024)
+                .unwrap_err()
+                .contains("does not match")
+        );
+        assert!(
+            decode_image(&json!({ "image": encoded }), 4)
+                .unwrap_err()
+                .contains("larger than")
+        );
+        let text = BASE64_STANDARD.encode(b"hello");
+        assert!(
+            decode_image(&json!({ "image": text }), 1024)
+                .unwrap_err()
+                .contains("PNG or JPEG")
+        );
+        assert!(decode_image(&json!({ "image": "not base64!" }), 1024).is_err());
+        assert!(decode_image(&json!({}), 1024).is_err());
+    }
+
+    #[test]
+    fn test_args_name_the_file_and_tenant() {
+        let config = ImageMatchConfig {
+            args: ["asset", "visual-match", "--file={file}"]
+                .map(str::to_string)
+                .to_vec(),
+            ..ImageMatchConfig::default()
+        };
+        let file = Path::new("/tmp/a.png");
+        assert_eq!(
+            build_args(&config, file, Some("acme")),
+            ["asset", "visual-match", "--file=/tmp/a.png", "-t", "acme"]
+        );
+        assert_eq!(build_args(&config, file, None).len(), 3);
+        assert!(tenant(&json!({ "tenant": "--help" })).is_err());
+        assert_eq!(tenant(&json!({})), Ok(None));
+    }
+}
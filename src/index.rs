@@ -0,0 +1,594 @@
+use crate::{
+    AppState,
+    config::IndexConfig,
+    content::{raw_output_block, summary_block, tool_result},
+    pcli::validate_range_u64,
+    snapshot::list_assets,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::Path,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+use tantivy::{
+    Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term,
+    collector::{Count, TopDocs},
+    directory::MmapDirectory,
+    query::{BooleanQuery, Occur, Query, QueryParser, TermQuery},
+    schema::{Field, IndexRecordOption, STORED, STRING, Schema, TEXT, Value as _},
+};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+pub const INDEX_SEARCH_TOOL: &str = "pcli2_index_search";
+pub const DEFAULT_SEARCH_LIMIT: u64 = 20;
+pub const MAX_SEARCH_LIMIT: u64 = 200;
+/// Refresh times of the indexed folders, kept next to an on-disk index.
+const FOLDERS_FILE: &str = "folders.json";
+const WRITER_MEMORY_BYTES: usize = 20_000_000;
+
+/// The last listing of an indexed folder.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FolderState {
+    /// Unix time in milliseconds of the listing the index holds.
+    pub indexed_at: Option<i64>,
+    pub assets: usize,
+    /// Why the latest listing failed; the earlier one is still searched.
+    pub error: Option<String>,
+}
+
+struct Fields {
+    uuid: Field,
+    folder: Field,
+    name: Field,
+    path: Field,
+    metadata: Field,
+    /// The asset as `pcli2 asset list` returned it, for the results.
+    asset: Field,
+}
+
+struct Searchable {
+    index: Index,
+    reader: IndexReader,
+    writer: Mutex<IndexWriter>,
+    fields: Fields,
+}
+
+fn index_error(err: impl std::fmt::Display) -> String {
+    format!("Asset index failed: {}", err)
+}
+
+impl Searchable {
+    fn open(dir: Option<&Path>) -> Result<Self, String> {
+        let mut builder = Schema::builder();
+        let fields = Fields {
+            uuid: builder.add_text_field("uuid", STRING | STORED),
+            folder: builder.add_text_field("folder", STRING | STORED),
+            name: builder.add_text_field("name", TEXT),
+            path: builder.add_text_field("path", TEXT),
+            metadata: builder.add_text_field("metadata", TEXT),
+            asset: builder.add_text_field("asset", STORED),
+        };
+        let schema = builder.build();
+        let index = match dir {
+            Some(dir) => {
+                fs::create_dir_all(dir).map_err(index_error)?;
+                let directory = MmapDirectory::open(dir).map_err(index_error)?;
+                Index::open_or_create(directory, schema).map_err(index_error)?
+            }
+            None => Index::create_in_ram(schema),
+        };
+        let writer = index
+            .writer_with_num_threads(1, WRITER_MEMORY_BYTES)
+            .map_err(index_error)?;
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()
+            .map_err(index_error)?;
+        Ok(Self {
+            index,
+            reader,
+            writer: Mutex::new(writer),
+            fields,
+        })
+    }
+
+    fn document(&self, folder_path: &str, asset: &Value) -> TantivyDocument {
+        let fields = &self.fields;
+        let path = asset["path"].as_str().unwrap_or_default();
+        let name = asset["name"]
+            .as_str()
+            .unwrap_or_else(|| path.rsplit('/').next().unwrap_or_default());
+        let mut document = TantivyDocument::default();
+        document.add_text(fields.uuid, asset["uuid"].as_str().unwrap_or_default());
+        document.add_text(fields.folder, folder_path);
+        document.add_text(fields.name, name);
+        document.add_text(fields.path, path);
+        document.add_text(fields.metadata, metadata_text(asset));
+        document.add_text(fields.asset, asset.to_string());
+        document
+    }
+
+    /// Replaces what the index holds for each of `folders`; an empty
+    /// listing only drops the folder.
+    fn replace(&self, folders: &[(&str, &[Value])]) -> Result<(), String> {
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let written = folders.iter().try_for_each(|(folder_path, assets)| {
+            writer.delete_term(Term::from_field_text(self.fields.folder, folder_path));
+            assets.iter().try_for_each(|asset| {
+                writer
+                    .add_document(self.document(folder_path, asset))
+                    .map(|_| ())
+            })
+        });
+        if let Err(err) = written.and_then(|()| writer.commit().map(|_| ())) {
+            let _ = writer.rollback();
+            return Err(index_error(err));
+        }
+        self.reader.reload().map_err(index_error)
+    }
+
+    /// The best `limit` matches of `text` with their scores, and how many
+    /// assets match in all.
+    fn search(
+        &self,
+        text: &str,
+        folder_path: Option<&str>,
+        limit: usize,
+    ) -> Result<(Vec<(f32, Value)>, usize), String> {
+        let fields = &self.fields;
+        let mut parser =
+            QueryParser::for_index(&self.index, vec![fields.name, fields.path, fields.metadata]);
+        parser.set_conjunction_by_default();
+        let (query, _) = parser.parse_query_lenient(text);
+        let query: Box<dyn Query> = match folder_path {
+            Some(folder_path) => Box::new(BooleanQuery::new(vec![
+                (Occur::Must, query),
+                (
+                    Occur::Must,
+                    Box::new(TermQuery::new(
+                        Term::from_field_text(fields.folder, folder_path),
+                        IndexRecordOption::Basic,
+                    )),
+                ),
+            ])),
+            None => query,
+        };
+        let searcher = self.reader.searcher();
+        let (top, total) = searcher
+            .search(&query, &(TopDocs::with_limit(limit), Count))
+            .map_err(index_error)?;
+        let mut matches = Vec::with_capacity(top.len());
+        for (score, address) in top {
+            let document: TantivyDocument = searcher.doc(address).map_err(index_error)?;
+            let mut asset: Value = document
+                .get_first(fields.asset)
+                .and_then(|value| value.as_str())
+                .and_then(|text| serde_json::from_str(text).ok())
+                .unwrap_or_else(|| json!({}));
+            if let Some(folder) = document
+                .get_first(fields.folder)
+                .and_then(|value| value.as_str())
+            {
+                asset["folder_path"] = json!(folder);
+            }
+            matches.push((score, asset));
+        }
+        Ok((matches, total))
+    }
+}
+
+/// Metadata as searchable text: each property's name and value.
+fn metadata_text(asset: &Value) -> String {
+    let Some(metadata) = asset["metadata"].as_object() else {
+        return String::new();
+    };
+    metadata
+        .iter()
+        .flat_map(|(name, value)| {
+            let value = match value {
+                Value::String(text) => text.clone(),
+                other => other.to_string(),
+            };
+            [name.clone(), value]
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The full-text index of the `index.folders` assets, opened on first use
+/// in `index.path` or in memory, and when each folder was last listed.
+#[derive(Clone, Default)]
+pub struct AssetIndex {
+    opened: Arc<OnceLock<Result<Searchable, String>>>,
+    folders: Arc<Mutex<BTreeMap<String, FolderState>>>,
+}
+
+impl AssetIndex {
+    fn open(&self, config: &IndexConfig) -> Result<&Searchable, String> {
+        self.opened
+            .get_or_init(|| {
+                let searchable = Searchable::open(config.path.as_deref())?;
+                if let Some(dir) = &config.path {
+                    self.restore(&searchable, config, dir);
+                }
+                Ok(searchable)
+            })
+            .as_ref()
+            .map_err(String::clone)
+    }
+
+    /// Picks up the refresh times saved with an on-disk index, and drops
+    /// folders that are no longer configured.
+    fn restore(&self, searchable: &Searchable, config: &IndexConfig, dir: &Path) {
+        let Ok(text) = fs::read_to_string(dir.join(FOLDERS_FILE)) else {
+            return;
+        };
+        let Ok(mut saved) = serde_json::from_str::<BTreeMap<String, FolderState>>(&text) else {
+            warn!("⚠ ignoring unreadable {}", dir.join(FOLDERS_FILE).display());
+            return;
+        };
+        let dropped: Vec<String> = saved
+            .keys()
+            .filter(|folder| !config.folders.contains(folder))
+            .cloned()
+            .collect();
+        let empty: &[Value] = &[];
+        let forgotten: Vec<(&str, &[Value])> = dropped
+            .iter()
+            .map(|folder| (folder.as_str(), empty))
+            .collect();
+        if !forgotten.is_empty()
+            && let Err(message) = searchable.replace(&forgotten)
+        {
+            warn!("⚠ could not drop unconfigured folders: {}", message);
+        }
+        saved.retain(|folder, _| config.folders.contains(folder));
+        *self.folders.lock().unwrap_or_else(|e| e.into_inner()) = saved;
+    }
+
+    fn record(
+        &self,
+        config: &IndexConfig,
+        folder_path: &str,
+        update: impl FnOnce(&mut FolderState),
+    ) {
+        let mut folders = self.folders.lock().unwrap_or_else(|e| e.into_inner());
+        update(folders.entry(folder_path.to_string()).or_default());
+        let Some(dir) = &config.path else {
+            return;
+        };
+        let file = dir.join(FOLDERS_FILE);
+        let written = serde_json::to_vec(&*folders)
+            .map_err(std::io::Error::other)
+            .and_then(|bytes| fs::write(&file, bytes));
+        if let Err(err) = written {
+            warn!("⚠ could not write {}: {}", file.display(), err);
+        }
+    }
+
+    /// Replaces the documents of `folder_path` with `assets`.
+    pub fn replace(
+        &self,
+        config: &IndexConfig,
+        folder_path: &str,
+        assets: &[Value],
+    ) -> Result<(), String> {
+        self.open(config)?.replace(&[(folder_path, assets)])?;
+        self.record(config, folder_path, |folder| {
+            *folder = FolderState {
+                indexed_at: Some(Utc::now().timestamp_millis()),
+                assets: assets.len(),
+                error: None,
+            }
+        });
+        Ok(())
+    }
+
+    /// Notes a failed listing of `folder_path`; its earlier one stays.
+    pub fn failed(&self, config: &IndexConfig, folder_path: &str, message: String) {
+        self.record(config, folder_path, |folder| folder.error = Some(message));
+    }
+
+    pub fn folder(&self, folder_path: &str) -> Option<FolderState> {
+        let folders = self.folders.lock().unwrap_or_else(|e| e.into_inner());
+        folders.get(folder_path).cloned()
+    }
+}
+
+/// Lists the `index.folders` assets with their metadata into the index at
+/// startup and every `index.interval_secs`.
+pub struct Indexer {
+    state: AppState,
+}
+
+impl Indexer {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    fn config(&self) -> &IndexConfig {
+        &self.state.config.index
+    }
+
+    /// Lists and indexes every folder once. A folder that fails keeps what
+    /// the index held for it. Returns how many folders were refreshed.
+    pub async fn refresh(&self) -> usize {
+        let mut refreshed = 0;
+        for folder_path in self.config().folders.clone() {
+            let started = Instant::now();
+            let mut folder = json!({ "folder_path": folder_path });
+            if let Some(tenant) = &self.config().tenant {
+                folder["tenant"] = json!(tenant);
+            }
+            let indexed = match list_assets(&folder).await {
+                Ok(assets) => {
+                    let index = self.state.index.clone();
+                    let config = self.state.config.clone();
+                    let path = folder_path.clone();
+                    tokio::task::spawn_blocking(move || {
+                        index
+                            .replace(&config.index, &path, &assets)
+                            .map(|()| assets.len())
+                    })
+                    .await
+                    .unwrap_or_else(|err| Err(index_error(err)))
+                }
+                Err(message) => Err(message),
+            };
+            match indexed {
+                Ok(count) => {
+                    refreshed += 1;
+                    info!(
+                        "🔎 indexed {} assets of {} in {} ms",
+                        count,
+                        folder_path,
+                        started.elapsed().as_millis()
+                    );
+                }
+                Err(message) => {
+                    warn!("⚠ indexing {} failed: {}", folder_path, message);
+                    self.state
+                        .index
+                        .failed(self.config(), &folder_path, message);
+                }
+            }
+        }
+        refreshed
+    }
+
+    /// Starts indexing every `index.interval_secs`, or returns `None` when
+    /// no folders are indexed.
+    pub fn spawn(self) -> Option<JoinHandle<()>> {
+        if !self.config().is_enabled() {
+            return None;
+        }
+        let interval = Duration::from_secs(self.config().interval_secs);
+        info!(
+            "indexing {} folder(s) every {}s",
+            self.config().folders.len(),
+            interval.as_secs()
+        );
+        Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                self.refresh().await;
+            }
+        }))
+    }
+}
+
+fn timestamp(millis: i64) -> String {
+    DateTime::<Utc>::from_timestamp_millis(millis)
+        .map(|time| time.to_rfc3339())
+        .unwrap_or_default()
+}
+
+/// The `pcli2_index_search` result: the best matches from the index, and
+/// when the folders searched were listed.
+pub fn search_index(state: &AppState, args: &Value) -> Result<Value, String> {
+    let config = &state.config.index;
+    let text = args
+        .get("query")
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+        .ok_or_else(|| "Missing required argument: query".to_string())?;
+    validate_range_u64(args, "limit", 1, MAX_SEARCH_LIMIT)?;
+    let limit = args
+        .get("limit")
+        .and_then(Value::as_u64)
+        .unwrap_or(DEFAULT_SEARCH_LIMIT) as usize;
+    let folder_path = args.get("folder_path").and_then(Value::as_str);
+    let searched: Vec<&String> = match folder_path {
+        Some(folder_path) => {
+            let folder = config
+                .folders
+                .iter()
+                .find(|folder| *folder == folder_path)
+                .ok_or_else(|| {
+                    format!(
+                        "Folder '{}' is not indexed; indexed folders: {}",
+                        folder_path,
+                        config.folders.join(", ")
+                    )
+                })?;
+            vec![folder]
+        }
+        None => config.folders.iter().collect(),
+    };
+    let now = Utc::now().timestamp_millis();
+    let folders: Vec<(&String, FolderState)> = searched
+        .into_iter()
+        .map(|folder| (folder, state.index.folder(folder).unwrap_or_default()))
+        .collect();
+    let Some(oldest) = folders.iter().filter_map(|(_, f)| f.indexed_at).min() else {
+        return Err(
+            "The asset index is not built yet: no indexed folder has been listed. Try again shortly"
+                .to_string(),
+        );
+    };
+
+    let (matches, total) = state.index.open(config)?.search(text, folder_path, limit)?;
+    let matches: Vec<Value> = matches
+        .into_iter()
+        .map(|(score, mut asset)| {
+            asset["score"] = json!(score);
+            asset
+        })
+        .collect();
+    let age_secs = (now.saturating_sub(oldest) / 1000).max(0) as u64;
+    let headline = format!(
+        "Found {} indexed assets matching '{}'{}; the index was refreshed {}s ago.",
+        total,
+        text,
+        if total > matches.len() {
+            format!(" (showing the best {})", matches.len())
+        } else {
+            String::new()
+        },
+        age_secs
+    );
+    let report = json!({
+        "query": text,
+        "matches": matches,
+        "total": total,
+        "folders": folders
+            .iter()
+            .map(|(folder_path, folder)| json!({
+                "folder_path": folder_path,
+                "indexed_at": folder.indexed_at.map(timestamp),
+                "age_secs": folder
+                    .indexed_at
+                    .map(|at| (now.saturating_sub(at) / 1000).max(0)),
+                "assets": folder.assets,
+                "error": folder.error
+            }))
+            .collect::<Vec<_>>()
+    });
+    let text = serde_json::to_string_pretty(&report)
+        .map_err(|err| format!("Failed to render results: {}", err))?;
+    let mut result = tool_result(vec![summary_block(headline), raw_output_block(text)]);
+    result["_meta"]["freshness"] = json!({
+        "fetched_at": timestamp(oldest),
+        "age_secs": age_secs,
+        "max_age_secs": config.interval_secs
+    });
+    result["_meta"]["cacheable_for_seconds"] = json!(config.interval_secs.saturating_sub(age_secs));
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> IndexConfig {
+        IndexConfig {
+            folders: vec!["/Root/Castings".to_string(), "/Root/Sheet".to_string()],
+            ..IndexConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_searches_names_paths_and_metadata() {
+        let config = config();
+        let index = AssetIndex::default();
+        index
+            .replace(
+                &config,
+                "/Root/Castings",
+                &[
+                    json!({ "uuid": "1", "path": "/Root/Castings/Bracket.stl", "metadata": { "Supplier": "Acme Foundry" } }),
+                    json!({ "uuid": "2", "path": "/Root/Castings/Housing.stl", "metadata": { "Part Number": "BRK-1042" } }),
+                ],
+            )
+            .unwrap();
+        index
+            .replace(
+                &config,
+                "/Root/Sheet",
+                &[json!({ "uuid": "3", "name": "Bracket flat", "path": "/Root/Sheet/B.step" })],
+            )
+            .unwrap();
+        let searchable = index.open(&config).unwrap();
+
+        let (matches, total) = searchable.search("bracket", None, 10).unwrap();
+        assert_eq!(total, 2);
+        let (matches_in_sheet, _) = searchable
+            .search("bracket", Some("/Root/Sheet"), 10)
+            .unwrap();
+        assert_eq!(matches_in_sheet[0].1["uuid"], "3");
+        assert_eq!(matches_in_sheet[0].1["folder_path"], "/Root/Sheet");
+        assert!(matches.iter().any(|(_, asset)| asset["uuid"] == "1"));
+        let (by_metadata, _) = searchable.search("acme foundry", None, 10).unwrap();
+        assert_eq!(by_metadata[0].1["uuid"], "1");
+        let (by_number, _) = searchable.search("BRK-1042", None, 10).unwrap();
+        assert_eq!(by_number[0].1["uuid"], "2");
+        assert_eq!(searchable.search("\"unbalanced", None, 10).unwrap().1, 0);
+
+        index
+            .replace(
+                &config,
+                "/Root/Castings",
+                &[json!({ "uuid": "4", "path": "/Root/Castings/Gear.stl" })],
+            )
+            .unwrap();
+        assert_eq!(searchable.search("bracket", None, 10).unwrap().1, 1);
+        assert_eq!(index.folder("/Root/Castings").unwrap().assets, 1);
+        index.failed(&config, "/Root/Castings", "timed out".to_string());
+        let folder = index.folder("/Root/Castings").unwrap();
+        assert_eq!(folder.assets, 1);
+        assert_eq!(folder.error.as_deref(), Some("timed out"));
+    }
+
+    #[test]
+    fn test_on_disk_index_keeps_refresh_times_and_drops_old_folders() {
+        let dir = std::env::temp_dir().join(format!("pcli2-mcp-index-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let config = IndexConfig {
+            path: Some(dir.clone()),
+            ..config()
+        };
+        let index = AssetIndex::default();
+        index
+            .replace(
+                &config,
+                "/Root/Castings",
+                &[json!({ "uuid": "1", "path": "/Root/Castings/Bracket.stl" })],
+            )
+            .unwrap();
+        index
+            .replace(
+                &config,
+                "/Root/Sheet",
+                &[json!({ "uuid": "2", "path": "/Root/Sheet/Bracket.step" })],
+            )
+            .unwrap();
+        let indexed_at = index.folder("/Root/Sheet").unwrap().indexed_at;
+        drop(index);
+
+        let config = IndexConfig {
+            folders: vec!["/Root/Sheet".to_string()],
+            ..config
+        };
+        let reopened = AssetIndex::default();
+        let searchable = reopened.open(&config).unwrap();
+        assert_eq!(
+            reopened.folder("/Root/Sheet").unwrap().indexed_at,
+            indexed_at
+        );
+        assert!(reopened.folder("/Root/Castings").is_none());
+        let (matches, total) = searchable.search("bracket", None, 10).unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(matches[0].1["uuid"], "2");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,298 @@
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, watch};
+use tracing::info;
+
+pub const DEFAULT_MAX_IN_FLIGHT: usize = 64;
+/// `Retry-After` sent with overload errors.
+pub const OVERLOAD_RETRY_AFTER_SECS: u64 = 2;
+
+type Outcome = Result<Value, String>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct InflightKey {
+    principal: String,
+    session: String,
+    id: String,
+    method: String,
+}
+
+struct Execution {
+    generation: u64,
+    params: Value,
+    outcome: watch::Receiver<Option<Outcome>>,
+}
+
+#[derive(Default)]
+struct Executions {
+    next_generation: u64,
+    running: HashMap<InflightKey, Execution>,
+}
+
+/// Requests still executing, keyed by principal, session, JSON-RPC id, and
+/// method, so a retried request waits for the original instead of running
+/// `pcli2` again.
+#[derive(Clone, Default)]
+pub struct InflightRequests {
+    executions: Arc<Mutex<Executions>>,
+}
+
+/// Removes the execution when the original request finishes or is dropped.
+struct Registration<'a> {
+    requests: &'a InflightRequests,
+    key: InflightKey,
+    generation: u64,
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        let mut executions = self
+            .requests
+            .executions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if executions
+            .running
+            .get(&self.key)
+            .is_some_and(|execution| execution.generation == self.generation)
+        {
+            executions.running.remove(&self.key);
+        }
+    }
+}
+
+impl InflightRequests {
+    /// Runs `work`, or waits for an identical request (same principal,
+    /// session, id, method, and params) that is already executing and returns
+    /// its outcome. Requests without a session or with a null id are never
+    /// deduplicated, since their ids are not unique to one client.
+    pub async fn run<F>(
+        &self,
+        principal: &str,
+        session: Option<&str>,
+        id: &Value,
+        method: &str,
+        params: &Value,
+        work: F,
+    ) -> Outcome
+    where
+        F: Future<Output = Outcome>,
+    {
+        let Some(session) = session.filter(|_| !id.is_null()) else {
+            return work.await;
+        };
+        let key = InflightKey {
+            principal: principal.to_string(),
+            session: session.to_string(),
+            id: id.to_string(),
+            method: method.to_string(),
+        };
+        let (sender, receiver) = watch::channel(None);
+        // Looked up and registered under one lock, so of two identical
+        // requests arriving together exactly one runs.
+        let generation = loop {
+            let attached = {
+                let mut executions = self.executions.lock().unwrap_or_else(|e| e.into_inner());
+                match executions.running.get(&key) {
+                    Some(execution) if execution.params == *params => {
+                        Some(execution.outcome.clone())
+                    }
+                    _ => {
+                        executions.next_generation += 1;
+                        let generation = executions.next_generation;
+                        executions.running.insert(
+                            key.clone(),
+                            Execution {
+                                generation,
+                                params: params.clone(),
+                                outcome: receiver.clone(),
+                            },
+                        );
+                        break generation;
+                    }
+                }
+            };
+            if let Some(mut outcome) = attached {
+                info!(
+                    "🔁 request {} ({}) is already running; waiting for its result",
+                    key.id, key.method
+                );
+                if let Ok(outcome) = outcome.wait_for(Option::is_some).await
+                    && let Some(outcome) = outcome.clone()
+                {
+                    return outcome;
+                }
+                // The original request was cancelled before it finished.
+            }
+        };
+        let _registration = Registration {
+            requests: self,
+            key,
+            generation,
+        };
+        let outcome = work.await;
+        sender.send_replace(Some(outcome.clone()));
+        outcome
+    }
+
+    pub fn len(&self) -> usize {
+        let executions = self.executions.lock().unwrap_or_else(|e| e.into_inner());
+        executions.running.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Hard cap on `/mcp` requests handled at once (`serve --max-in-flight`).
+#[derive(Clone)]
+pub struct RequestLimiter {
+    permits: Arc<Semaphore>,
+    max_in_flight: usize,
+}
+
+impl RequestLimiter {
+    pub fn new(max_in_flight: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(max_in_flight)),
+            max_in_flight,
+        }
+    }
+
+    /// Admits one request, or explains the overload when the cap is reached.
+    /// The request counts until the permit is dropped.
+    pub fn try_admit(&self) -> Result<OwnedSemaphorePermit, String> {
+        self.permits.clone().try_acquire_owned().map_err(|_| {
+            format!(
+                "Server overloaded: {} requests in flight. Retry after {} seconds.",
+                self.max_in_flight, OVERLOAD_RETRY_AFTER_SECS
+            )
+        })
+    }
+}
+
+impl Default for RequestLimiter {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_IN_FLIGHT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    async fn slow_call(runs: &AtomicUsize) -> Outcome {
+        runs.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        Ok(json!({ "run": runs.load(Ordering::SeqCst) }))
+    }
+
+    #[tokio::test]
+    async fn test_retry_attaches_to_running_request() {
+        let requests = InflightRequests::default();
+        let runs = AtomicUsize::new(0);
+        let params = json!({ "name": "pcli2_tenant_list" });
+        let id = json!("req-1");
+        let (first, retry) = tokio::join!(
+            requests.run("k", Some("s"), &id, "tools/call", &params, slow_call(&runs)),
+            requests.run("k", Some("s"), &id, "tools/call", &params, slow_call(&runs)),
+        );
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert_eq!(first, retry);
+        assert!(requests.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_retries_run_once() {
+        let requests = InflightRequests::default();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let start = Arc::new(tokio::sync::Barrier::new(16));
+        let retries: Vec<_> = (0..16)
+            .map(|_| {
+                let (requests, runs, start) = (requests.clone(), runs.clone(), start.clone());
+                tokio::spawn(async move {
+                    start.wait().await;
+                    let params = json!({ "name": "pcli2_asset_get" });
+                    requests
+                        .run("k", Some("s"), &json!(1), "tools/call", &params, async {
+                            slow_call(&runs).await
+                        })
+                        .await
+                })
+            })
+            .collect();
+        for retry in retries {
+            assert_eq!(retry.await.unwrap(), Ok(json!({ "run": 1 })));
+        }
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_distinct_requests_run_separately() {
+        let requests = InflightRequests::default();
+        let runs = AtomicUsize::new(0);
+        let params = json!({ "name": "pcli2_tenant_list" });
+        let other = json!({ "name": "pcli2_version" });
+        let id = json!(1);
+        let _ = tokio::join!(
+            requests.run("k", Some("s"), &id, "tools/call", &params, slow_call(&runs)),
+            requests.run("k", Some("s"), &id, "tools/call", &other, slow_call(&runs)),
+            requests.run("k", Some("t"), &id, "tools/call", &params, slow_call(&runs)),
+            requests.run("k", None, &id, "tools/call", &params, slow_call(&runs)),
+            requests.run(
+                "other-key",
+                Some("s"),
+                &id,
+                "tools/call",
+                &params,
+                slow_call(&runs)
+            ),
+            requests.run(
+                "k",
+                Some("s"),
+                &Value::Null,
+                "tools/call",
+                &params,
+                slow_call(&runs)
+            ),
+        );
+        assert_eq!(runs.load(Ordering::SeqCst), 6);
+    }
+
+    #[tokio::test]
+    async fn test_retry_runs_when_original_is_cancelled() {
+        let requests = InflightRequests::default();
+        let runs = AtomicUsize::new(0);
+        let params = json!({});
+        let id = json!(7);
+        let original = requests.run("k", Some("s"), &id, "tools/call", &params, slow_call(&runs));
+        let cancelled = tokio::time::timeout(Duration::from_millis(10), original).await;
+        assert!(cancelled.is_err());
+        assert!(requests.is_empty());
+        let outcome = requests
+            .run("k", Some("s"), &id, "tools/call", &params, slow_call(&runs))
+            .await;
+        assert_eq!(outcome, Ok(json!({ "run": 2 })));
+    }
+
+    #[test]
+    fn test_limiter_rejects_beyond_cap() {
+        let limiter = RequestLimiter::new(2);
+        let first = limiter.try_admit().unwrap();
+        let _second = limiter.try_admit().unwrap();
+        let err = limiter.try_admit().unwrap_err();
+        assert!(err.contains("2 requests in flight"));
+        drop(first);
+        assert!(limiter.try_admit().is_ok());
+    }
+}
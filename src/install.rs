@@ -0,0 +1,296 @@
+use crate::cli::ARG_VERSION;
+use anyhow::{Context, Result, anyhow};
+use clap::ArgMatches;
+use sha2::{Digest, Sha256};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+use tokio::process::Command;
+
+pub const PCLI2_REPOSITORY: &str = "jchultarsky101/pcli2";
+/// Overrides the directory managed by `install-pcli2` (default `~/.pcli2-mcp`).
+pub const MANAGED_HOME_ENV: &str = "PCLI2_MCP_HOME";
+
+const PCLI2_FILE_NAME: &str = if cfg!(windows) { "pcli2.exe" } else { "pcli2" };
+
+/// Directory holding server-managed files such as the installed `pcli2`.
+pub fn managed_home() -> Option<PathBuf> {
+    if let Some(home) = env::var_os(MANAGED_HOME_ENV).filter(|value| !value.is_empty()) {
+        return Some(PathBuf::from(home));
+    }
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .filter(|value| !value.is_empty())
+        .map(|home| PathBuf::from(home).join(".pcli2-mcp"))
+}
+
+fn managed_path() -> Option<PathBuf> {
+    managed_home().map(|home| home.join("bin").join(PCLI2_FILE_NAME))
+}
+
+/// The `pcli2` installed by `install-pcli2`, if there is one.
+pub fn managed_pcli2() -> Option<PathBuf> {
+    managed_path().filter(|path| path.is_file())
+}
+
+/// Release target triple of the running platform, `None` where releases
+/// are not built.
+pub(crate) fn release_target() -> Option<&'static str> {
+    let target = match (env::consts::OS, env::consts::ARCH) {
+        ("linux", "x86_64") => "x86_64-unknown-linux-gnu",
+        ("linux", "aarch64") => "aarch64-unknown-linux-gnu",
+        ("macos", "x86_64") => "x86_64-apple-darwin",
+        ("macos", "aarch64") => "aarch64-apple-darwin",
+        ("windows", "x86_64") => "x86_64-pc-windows-msvc",
+        _ => return None,
+    };
+    Some(target)
+}
+
+/// The release archive of `app` for `target`, as `dist` names it.
+pub(crate) fn artifact_name(app: &str, target: &str) -> String {
+    if target.contains("windows") {
+        format!("{}-{}.zip", app, target)
+    } else {
+        format!("{}-{}.tar.xz", app, target)
+    }
+}
+
+pub(crate) fn release_url(repository: &str, version: Option<&str>, file: &str) -> String {
+    match version {
+        Some(version) => format!(
+            "https://github.com/{}/releases/download/v{}/{}",
+            repository,
+            version.trim_start_matches('v'),
+            file
+        ),
+        None => format!(
+            "https://github.com/{}/releases/latest/download/{}",
+            repository, file
+        ),
+    }
+}
+
+/// Reads the digest from a `<hex>  <file>` checksum file.
+fn parse_checksum(text: &str) -> Result<String, String> {
+    text.split_whitespace()
+        .next()
+        .filter(|digest| digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit()))
+        .map(str::to_ascii_lowercase)
+        .ok_or_else(|| "Checksum file does not start with a SHA-256 digest".to_string())
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub(crate) async fn run_tool(program: &str, args: &[&str]) -> Result<()> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .await
+        .with_context(|| format!("Failed to run {}; is it installed?", program))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{} {} failed: {}",
+            program,
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) async fn download(url: &str, dest: &Path) -> Result<()> {
+    let dest = dest.to_string_lossy();
+    run_tool(
+        "curl",
+        &[
+            "--fail",
+            "--silent",
+            "--show-error",
+            "--location",
+            "--retry",
+            "2",
+            "--output",
+            &dest,
+            url,
+        ],
+    )
+    .await
+}
+
+pub(crate) fn find_file(dir: &Path, name: &str) -> Option<PathBuf> {
+    for entry in fs::read_dir(dir).ok()?.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if let Some(found) = find_file(&path, name) {
+                return Some(found);
+            }
+        } else if path.file_name().is_some_and(|file| file == name) {
+            return Some(path);
+        }
+    }
+    None
+}
+
+/// Downloads release archive `artifact` of `repository` into `work`,
+/// checks it against the published SHA-256 checksum, and unpacks it.
+/// Returns the directory it was unpacked to.
+pub(crate) async fn fetch_release(
+    repository: &str,
+    version: Option<&str>,
+    artifact: &str,
+    work: &Path,
+) -> Result<PathBuf> {
+    let archive = work.join(artifact);
+    let url = release_url(repository, version, artifact);
+    eprintln!("Downloading {}", url);
+    download(&url, &archive).await?;
+    let checksum = work.join(format!("{}.sha256", artifact));
+    download(
+        &release_url(repository, version, &format!("{}.sha256", artifact)),
+        &checksum,
+    )
+    .await?;
+
+    let expected = parse_checksum(&fs::read_to_string(&checksum)?).map_err(|e| anyhow!(e))?;
+    let actual = sha256_hex(&fs::read(&archive)?);
+    if actual != expected {
+        return Err(anyhow!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            artifact,
+            expected,
+            actual
+        ));
+    }
+    eprintln!("Checksum verified ({})", actual);
+
+    let extracted = work.join("extracted");
+    fs::create_dir_all(&extracted)?;
+    run_tool(
+        "tar",
+        &[
+            "-xf",
+            &archive.to_string_lossy(),
+            "-C",
+            &extracted.to_string_lossy(),
+        ],
+    )
+    .await?;
+    Ok(extracted)
+}
+
+/// Downloads, verifies, and installs the `pcli2` release into the managed
+/// directory. Returns the installed path.
+async fn install(version: Option<&str>, work: &Path) -> Result<PathBuf> {
+    let target = release_target().ok_or_else(|| {
+        anyhow!(
+            "No pcli2 release is published for {}/{}; install pcli2 manually and set PCLI2_BIN",
+            env::consts::OS,
+            env::consts::ARCH
+        )
+    })?;
+    let artifact = artifact_name("pcli2", target);
+    let destination = managed_path()
+        .ok_or_else(|| anyhow!("Cannot find a home directory; set {}", MANAGED_HOME_ENV))?;
+
+    let extracted = fetch_release(PCLI2_REPOSITORY, version, &artifact, work).await?;
+    let binary = find_file(&extracted, PCLI2_FILE_NAME)
+        .ok_or_else(|| anyhow!("{} does not contain {}", artifact, PCLI2_FILE_NAME))?;
+
+    let bin_dir = destination
+        .parent()
+        .ok_or_else(|| anyhow!("Invalid install path {}", destination.display()))?;
+    fs::create_dir_all(bin_dir)
+        .with_context(|| format!("Failed to create {}", bin_dir.display()))?;
+    // Copy next to the destination first so a running server never sees a
+    // partially written binary.
+    let staged = bin_dir.join(format!(".{}.partial", PCLI2_FILE_NAME));
+    fs::copy(&binary, &staged)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))?;
+    }
+    fs::rename(&staged, &destination)
+        .with_context(|| format!("Failed to install {}", destination.display()))?;
+    Ok(destination)
+}
+
+pub async fn run_install_pcli2(matches: &ArgMatches) -> Result<()> {
+    let version = matches.get_one::<String>(ARG_VERSION).map(String::as_str);
+    let work = env::temp_dir().join(format!("pcli2-mcp-install-{}", std::process::id()));
+    fs::create_dir_all(&work)?;
+    let result = install(version, &work).await;
+    let _ = fs::remove_dir_all(&work);
+    let path = result?;
+
+    let output = Command::new(&path)
+        .arg("--version")
+        .output()
+        .await
+        .with_context(|| format!("Installed {} does not run", path.display()))?;
+    println!(
+        "Installed {} at {}",
+        String::from_utf8_lossy(&output.stdout).trim(),
+        path.display()
+    );
+    println!("pcli2-mcp uses it automatically unless PCLI2_BIN is set");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_release_url() {
+        assert_eq!(
+            release_url(
+                PCLI2_REPOSITORY,
+                Some("v0.2.1"),
+                "pcli2-x86_64-apple-darwin.tar.xz"
+            ),
+            "https://github.com/jchultarsky101/pcli2/releases/download/v0.2.1/pcli2-x86_64-apple-darwin.tar.xz"
+        );
+        assert_eq!(
+            release_url(
+                PCLI2_REPOSITORY,
+                None,
+                &artifact_name("pcli2", "x86_64-pc-windows-msvc")
+            ),
+            "https://github.com/jchultarsky101/pcli2/releases/latest/download/pcli2-x86_64-pc-windows-msvc.zip"
+        );
+    }
+
+    #[test]
+    fn test_checksum_verification() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let file =
+            "BA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD  pcli2.tar.xz\n";
+        assert_eq!(parse_checksum(file).unwrap(), sha256_hex(b"abc"));
+        assert!(parse_checksum("<html>Not Found</html>").is_err());
+    }
+
+    #[test]
+    fn test_find_file_searches_subdirectories() {
+        let root = env::temp_dir().join(format!("pcli2-mcp-find-{}", std::process::id()));
+        let nested = root.join("pcli2-x86_64-unknown-linux-gnu");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(nested.join(PCLI2_FILE_NAME), b"").unwrap();
+        assert_eq!(
+            find_file(&root, PCLI2_FILE_NAME),
+            Some(nested.join(PCLI2_FILE_NAME))
+        );
+        assert_eq!(find_file(&root, "missing"), None);
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,366 @@
+use crate::{
+    AppState,
+    approval::APPROVAL_STATUS_TOOL,
+    content::raw_output_result,
+    paging::json_rows,
+    pcli::{public_tool_list, run_pcli2_background},
+    quota::QUOTA_STATUS_TOOL,
+    suggest::FOLDER_SUGGEST_TOOL,
+};
+use serde_json::{Value, json};
+use std::time::Duration;
+use tracing::debug;
+
+const TENANT_LIST_TOOL: &str = "pcli2_tenant_list";
+/// Fields naming a tenant in `pcli2 tenant list` output, in order of
+/// preference.
+const TENANT_NAME_KEYS: [&str; 5] = [
+    "tenant_short_name",
+    "short_name",
+    "name",
+    "tenant_name",
+    "id",
+];
+/// Fields marking the tenant `pcli2` currently uses.
+const ACTIVE_KEYS: [&str; 4] = ["active", "is_active", "current", "selected"];
+/// Tenants named before the list is cut short.
+const MAX_TENANTS: usize = 20;
+
+/// A tenant from `pcli2 tenant list`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Tenant {
+    pub(crate) name: String,
+    pub(crate) active: bool,
+}
+
+fn parse_tenants(text: &str) -> Option<Vec<Tenant>> {
+    let mut output = serde_json::from_str::<Value>(text).ok()?;
+    let rows = json_rows(&mut output)?;
+    Some(
+        rows.iter()
+            .filter_map(|row| {
+                let name = TENANT_NAME_KEYS
+                    .iter()
+                    .find_map(|key| row.get(*key).and_then(Value::as_str))?;
+                let active = ACTIVE_KEYS
+                    .iter()
+                    .any(|key| row.get(*key).and_then(Value::as_bool) == Some(true));
+                Some(Tenant {
+                    name: name.to_string(),
+                    active,
+                })
+            })
+            .collect(),
+    )
+}
+
+/// The tenants from a cached `pcli2 tenant list`, without running it.
+pub(crate) async fn cached_tenants(state: &AppState) -> Option<Vec<Tenant>> {
+    let result = state
+        .cache
+        .get(TENANT_LIST_TOOL, None, &json!({ "format": "json" }))
+        .await?;
+    parse_tenants(last_text(&result)?)
+}
+
+/// The text of the last text block of a tool result: pcli2's output.
+pub(crate) fn last_text(result: &Value) -> Option<&str> {
+    result["content"]
+        .as_array()?
+        .iter()
+        .rev()
+        .find(|block| block["type"] == "text")?["text"]
+        .as_str()
+}
+
+/// The tenants from the result cache, else from a `pcli2 tenant list` run
+/// bounded by `instructions.probe_timeout_secs`; `None` when neither
+/// answers in time.
+async fn probe_tenants(state: &AppState) -> Option<Vec<Tenant>> {
+    if let Some(tenants) = cached_tenants(state).await {
+        return Some(tenants);
+    }
+    let args = json!({ "format": "json" });
+    let timeout = Duration::from_secs(state.config.instructions.probe_timeout_secs);
+    if timeout.is_zero() {
+        return None;
+    }
+    let cmd_args = ["tenant", "list", "-f", "json"]
+        .map(str::to_string)
+        .to_vec();
+    let run = run_pcli2_background(state, cmd_args, "pcli2 tenant list");
+    let text = match tokio::time::timeout(timeout, run).await {
+        Ok(Ok(text)) => text,
+        Ok(Err(message)) => {
+            debug!("Tenant probe for instructions failed: {}", message);
+            return None;
+        }
+        Err(_) => {
+            debug!("Tenant probe for instructions timed out");
+            return None;
+        }
+    };
+    let tenants = parse_tenants(&text)?;
+    state
+        .cache
+        .put(TENANT_LIST_TOOL, None, &args, &raw_output_result(text))
+        .await;
+    Some(tenants)
+}
+
+fn tenant_lines(state: &AppState, tenants: Option<&[Tenant]>) -> Vec<String> {
+    if let Some(bound) = &state.tenant {
+        return vec![format!(
+            "Your API key is bound to tenant '{}'; every call runs in it.",
+            bound
+        )];
+    }
+    let mut lines = Vec::new();
+    match tenants {
+        Some(tenants) if !tenants.is_empty() => {
+            let mut names: Vec<String> = tenants
+                .iter()
+                .take(MAX_TENANTS)
+                .map(|tenant| format!("'{}'", tenant.name))
+                .collect();
+            if tenants.len() > MAX_TENANTS {
+                names.push(format!("and {} more", tenants.len() - MAX_TENANTS));
+            }
+            lines.push(format!("Tenants: {}.", names.join(", ")));
+            if let Some(active) = tenants.iter().find(|tenant| tenant.active) {
+                lines.push(format!(
+                    "Calls without 'tenant' run in '{}', pcli2's current tenant.",
+                    active.name
+                ));
+            }
+        }
+        _ => lines.push(format!(
+            "Call {} to see the tenants.",
+            state.config.tools.public_name(TENANT_LIST_TOOL)
+        )),
+    }
+    let search = &state.config.multi_tenant_search.tenants;
+    if !search.is_empty() {
+        lines.push(format!(
+            "Multi-tenant search covers {} by default.",
+            search
+                .iter()
+                .map(|tenant| format!("'{}'", tenant))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    lines.push("Pass 'tenant' to a tool to work in another tenant.".to_string());
+    lines
+}
+
+fn policy_lines(state: &AppState, enabled: &[String]) -> Vec<String> {
+    let config = &state.config;
+    let public = |tool: &str| config.tools.public_name(tool);
+    let mut lines = Vec::new();
+    let held: Vec<String> = config
+        .approval
+        .tools
+        .iter()
+        .map(|tool| public(tool))
+        .filter(|tool| enabled.contains(tool))
+        .collect();
+    if !held.is_empty() {
+        lines.push(format!(
+            "Calls to {} wait for an operator's approval; {} reports on held calls.",
+            held.join(", "),
+            public(APPROVAL_STATUS_TOOL)
+        ));
+    }
+    if !config.quotas.is_empty() {
+        let mut quotas: Vec<String> = config
+            .quotas
+            .iter()
+            .map(|(name, rule)| {
+                let tools = if rule.tools.is_empty() {
+                    "all tools".to_string()
+                } else {
+                    rule.tools
+                        .iter()
+                        .map(|tool| public(tool))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                };
+                match rule.window_secs {
+                    Some(window) => format!(
+                        "'{}' allows {} calls per {} s to {}",
+                        name, rule.max_calls, window, tools
+                    ),
+                    None => format!("'{}' allows {} calls to {}", name, rule.max_calls, tools),
+                }
+            })
+            .collect();
+        quotas.sort();
+        lines.push(format!(
+            "Quotas per session: {}. {} shows what is left.",
+            quotas.join("; "),
+            public(QUOTA_STATUS_TOOL)
+        ));
+    }
+    let unavailable = state.compat.list();
+    if !unavailable.is_empty() {
+        lines.push(format!(
+            "Not available with the installed pcli2: {}.",
+            unavailable
+                .iter()
+                .map(|(tool, _)| public(tool))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    lines
+}
+
+/// Example workflows, each kept only when all of its tools are enabled.
+fn workflow_lines(state: &AppState, enabled: &[String]) -> Vec<String> {
+    let public = |tool: &str| state.config.tools.public_name(tool);
+    let workflows: [(&str, &[&str]); 4] = [
+        (
+            "Browse the library: {0} for folders ({1} when a path is not found), then {2} with 'folder_path'.",
+            &["pcli2_folder_list", FOLDER_SUGGEST_TOOL, "pcli2_asset_list"],
+        ),
+        (
+            "Find similar parts: {0} with an asset's 'path' and 'summarize': true, then {1} on the best matches.",
+            &["pcli2_geometric_match", "pcli2_asset_get"],
+        ),
+        (
+            "Search by name: {0} with 'text', narrowed with 'filter', 'sort_by', 'fields', and 'limit'.",
+            &["pcli2_asset_text_match"],
+        ),
+        (
+            "Tag assets: {0} with 'metadata': true to read properties, {1} to set them.",
+            &["pcli2_asset_get", "pcli2_asset_metadata_create"],
+        ),
+    ];
+    workflows
+        .iter()
+        .filter_map(|(template, tools)| {
+            let names: Vec<String> = tools.iter().map(|tool| public(tool)).collect();
+            if !names.iter().all(|name| enabled.contains(name)) {
+                return None;
+            }
+            let mut line = format!("- {}", template);
+            for (index, name) in names.iter().enumerate() {
+                line = line.replace(&format!("{{{}}}", index), name);
+            }
+            Some(line)
+        })
+        .collect()
+}
+
+fn compose(state: &AppState, tenants: Option<&[Tenant]>) -> String {
+    let enabled: Vec<String> = public_tool_list(state)
+        .iter()
+        .filter_map(|tool| tool["name"].as_str().map(str::to_string))
+        .collect();
+    let mut sections = vec![format!(
+        "This server runs pcli2, the Physna command line, for {} tools over a Physna tenant's 3D assets: folders, assets, metadata, and geometric, part, visual, and text matching.",
+        enabled.len()
+    )];
+    sections.push(format!(
+        "Tenants\n{}",
+        tenant_lines(state, tenants).join("\n")
+    ));
+    let policies = policy_lines(state, &enabled);
+    if !policies.is_empty() {
+        sections.push(format!("Policies\n{}", policies.join("\n")));
+    }
+    let workflows = workflow_lines(state, &enabled);
+    if !workflows.is_empty() {
+        sections.push(format!("Example workflows\n{}", workflows.join("\n")));
+    }
+    if let Some(text) = state
+        .config
+        .instructions
+        .text
+        .as_deref()
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+    {
+        sections.push(text.to_string());
+    }
+    sections.join("\n\n")
+}
+
+/// Guidance for the `instructions` field of the `initialize` result, or
+/// `None` when `instructions.enabled` is off.
+pub async fn server_instructions(state: &AppState) -> Option<String> {
+    if !state.config.instructions.enabled {
+        return None;
+    }
+    let tenants = match state.tenant {
+        Some(_) => None,
+        None => probe_tenants(state).await,
+    };
+    Some(compose(state, tenants.as_deref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ApprovalConfig, ServerConfig, ToolsConfig};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_tenants_are_read_from_tenant_list_output() {
+        let text =
+            r#"[{"tenant_short_name": "acme", "active": true}, {"name": "beta"}, {"other": 1}]"#;
+        assert_eq!(
+            parse_tenants(text).unwrap(),
+            vec![
+                Tenant {
+                    name: "acme".to_string(),
+                    active: true
+                },
+                Tenant {
+                    name: "beta".to_string(),
+                    active: false
+                }
+            ]
+        );
+        assert!(parse_tenants("acme\nbeta").is_none());
+    }
+
+    #[test]
+    fn test_instructions_follow_the_configuration() {
+        let state = AppState {
+            config: Arc::new(ServerConfig {
+                tools: ToolsConfig {
+                    prefix: Some("physna_".to_string()),
+                    ..ToolsConfig::default()
+                },
+                approval: ApprovalConfig {
+                    tools: vec!["pcli2_asset_metadata_delete".to_string()],
+                    ..ApprovalConfig::default()
+                },
+                ..ServerConfig::default()
+            }),
+            ..AppState::new("test", "0.0.0")
+        };
+        let tenants = [Tenant {
+            name: "acme".to_string(),
+            active: true,
+        }];
+        let text = compose(&state, Some(&tenants));
+        assert!(text.contains("Tenants: 'acme'."));
+        assert!(text.contains("run in 'acme'"));
+        assert!(
+            text.contains("Calls to physna_asset_metadata_delete wait for an operator's approval")
+        );
+        assert!(text.contains("- Find similar parts: physna_geometric_match"));
+        assert!(!text.contains("pcli2_geometric_match"));
+
+        let bound = AppState {
+            tenant: Some("beta".to_string()),
+            ..AppState::new("test", "0.0.0")
+        };
+        let text = compose(&bound, None);
+        assert!(text.contains("bound to tenant 'beta'"));
+        assert!(!text.contains("Pass 'tenant'"));
+    }
+}
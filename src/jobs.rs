@@ -0,0 +1,286 @@
+use crate::{
+    AppState,
+    artifacts::leftover_roots,
+    policy::tenant_permitted,
+    resources::{StoredResource, read_content},
+    tree::{decode_segment, encode_segment},
+    workspace::Workspace,
+};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64_STANDARD};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Files kept from a job are `job://{id}/{file}`, one percent-encoded URI
+/// segment per directory and file name.
+pub const JOB_URI_SCHEME: &str = "job://";
+/// Prefix of the directories jobs are kept in, beside the call directories.
+pub const JOB_PREFIX: &str = "pcli2-job-";
+/// What the job was, kept in its directory.
+const MANIFEST: &str = "job.json";
+/// Largest job file `resources/read` returns; `offset` and `length` page
+/// through it.
+const MAX_JOB_FILE_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    tool: String,
+    tenant: Option<String>,
+    finished_at: String,
+}
+
+/// A job id unique across restarts and replicas sharing a workspace.
+fn next_id() -> String {
+    static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    format!(
+        "{}-{}-{}",
+        millis,
+        std::process::id(),
+        SEQUENCE.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+fn output_name(mime_type: &str) -> &'static str {
+    match mime_type {
+        "text/csv" => "output.csv",
+        "application/json" => "output.json",
+        "text/markdown" => "output.md",
+        _ => "output.txt",
+    }
+}
+
+fn mime_type_of(file: &str) -> &'static str {
+    let extension = file
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "csv" => "text/csv",
+        "json" => "application/json",
+        "md" => "text/markdown",
+        "txt" | "log" => "text/plain",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "stl" => "model/stl",
+        "step" | "stp" => "model/step",
+        "zip" => "application/zip",
+        _ => "application/octet-stream",
+    }
+}
+
+pub fn job_uri(id: &str, file: &str) -> String {
+    let segments: Vec<String> = file.split('/').map(encode_segment).collect();
+    format!("{}{}/{}", JOB_URI_SCHEME, id, segments.join("/"))
+}
+
+/// The job id and file (relative to the job, `/`-separated) of a job URI.
+pub fn parse_job_uri(uri: &str) -> Option<(String, String)> {
+    let (id, rest) = uri.strip_prefix(JOB_URI_SCHEME)?.split_once('/')?;
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit() || c == '-') {
+        return None;
+    }
+    let mut segments = Vec::new();
+    for segment in rest.split('/') {
+        let name = decode_segment(segment)?;
+        if matches!(name.as_str(), "" | "." | "..") || name.contains(['/', '\\']) {
+            return None;
+        }
+        segments.push(name);
+    }
+    Some((id.to_string(), segments.join("/")))
+}
+
+/// The regular files under `dir`, relative to it, leaving out the manifest
+/// and anything reached through a symlink.
+fn files_of(dir: &Path) -> Vec<String> {
+    fn walk(dir: &Path, prefix: &str, files: &mut Vec<String>) {
+        for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let relative = format!("{}{}", prefix, name);
+            if file_type.is_dir() {
+                walk(&entry.path(), &format!("{}/", relative), files);
+            } else if file_type.is_file() && relative != MANIFEST {
+                files.push(relative);
+            }
+        }
+    }
+    let mut files = Vec::new();
+    walk(dir, "", &mut files);
+    files.sort();
+    files
+}
+
+/// Keeps the working directory of a finished call to `tool` as a job: the
+/// files the call downloaded into it, and `output` as `output.<ext>`.
+/// Returns the `_meta.job` of the call's result.
+pub fn keep_job(
+    workspace: Workspace,
+    tool: &str,
+    tenant: Option<&str>,
+    output: Option<&str>,
+    mime_type: &str,
+) -> Result<Value, String> {
+    let id = next_id();
+    let dir = workspace.keep_as(&format!("{}{}", JOB_PREFIX, id))?;
+    let write = |name: &str, contents: &[u8]| {
+        fs::write(dir.join(name), contents)
+            .map_err(|err| format!("Failed to write the job's {}: {}", name, err))
+    };
+    if let Some(output) = output {
+        write(output_name(mime_type), output.as_bytes())?;
+    }
+    let manifest = Manifest {
+        tool: tool.to_string(),
+        tenant: tenant.map(str::to_string),
+        finished_at: Utc::now().to_rfc3339(),
+    };
+    let manifest = serde_json::to_vec_pretty(&manifest)
+        .map_err(|err| format!("Failed to render the job manifest: {}", err))?;
+    write(MANIFEST, &manifest)?;
+    let uris: Vec<String> = files_of(&dir)
+        .iter()
+        .map(|file| job_uri(&id, file))
+        .collect();
+    Ok(json!({ "id": id, "resources": uris }))
+}
+
+fn visible(state: &AppState, manifest: &Manifest) -> bool {
+    let tenant = manifest.tenant.as_deref();
+    (state.tenant.is_none() || state.tenant.as_deref() == tenant)
+        && tenant.is_none_or(|tenant| tenant_permitted(state, tenant))
+}
+
+fn read_manifest(dir: &Path) -> Option<Manifest> {
+    serde_json::from_slice(&fs::read(dir.join(MANIFEST)).ok()?).ok()
+}
+
+/// The kept jobs the request may read, with their directories.
+fn jobs(state: &AppState) -> Vec<(String, PathBuf, Manifest)> {
+    let mut jobs: Vec<(String, PathBuf, Manifest)> = leftover_roots(&state.config)
+        .iter()
+        .filter_map(|root| fs::read_dir(root).ok())
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let id = name.strip_prefix(JOB_PREFIX)?.to_string();
+            let manifest = read_manifest(&entry.path())?;
+            Some((id, entry.path(), manifest))
+        })
+        .filter(|(_, _, manifest)| visible(state, manifest))
+        .collect();
+    jobs.sort_by(|a, b| a.2.finished_at.cmp(&b.2.finished_at));
+    jobs
+}
+
+/// `resources/list` entries for the files of the jobs the request may read.
+pub fn job_descriptors(state: &AppState) -> Vec<Value> {
+    jobs(state)
+        .into_iter()
+        .flat_map(|(id, dir, manifest)| {
+            files_of(&dir).into_iter().map(move |file| {
+                let size = fs::metadata(dir.join(&file)).map_or(0, |meta| meta.len());
+                json!({
+                    "uri": job_uri(&id, &file),
+                    "name": format!("{} ({} job {})", file, manifest.tool, id),
+                    "mimeType": mime_type_of(&file),
+                    "size": size
+                })
+            })
+        })
+        .collect()
+}
+
+/// The contents of a job file and the job's tenant.
+type JobFile = (Vec<u8>, Option<String>);
+
+/// The contents of `file` of job `id`, with the job's tenant. `None` when
+/// there is no such job or file, or the request may not read it.
+pub(crate) fn job_file(state: &AppState, id: &str, file: &str) -> Result<Option<JobFile>, String> {
+    let Some((_, dir, manifest)) = jobs(state).into_iter().find(|(job, _, _)| job == id) else {
+        return Ok(None);
+    };
+    let path = dir.join(file);
+    if file == MANIFEST || !files_of(&dir).iter().any(|known| known == file) {
+        return Ok(None);
+    }
+    let size = fs::metadata(&path).map_or(0, |meta| meta.len());
+    if size > MAX_JOB_FILE_BYTES {
+        return Err(format!(
+            "Job file '{}' is {} bytes, more than the {} a read returns",
+            file, size, MAX_JOB_FILE_BYTES
+        ));
+    }
+    let bytes = fs::read(&path).map_err(|err| format!("Failed to read job file: {}", err))?;
+    Ok(Some((bytes, manifest.tenant)))
+}
+
+/// Reads `file` of job `id`, or the chunk of it from `offset` that is
+/// `length` bytes long. `None` when there is no such job or file, or the
+/// request may not read it.
+pub fn read_job(
+    state: &AppState,
+    id: &str,
+    file: &str,
+    offset: Option<usize>,
+    length: Option<usize>,
+) -> Result<Option<Value>, String> {
+    let Some((bytes, tenant)) = job_file(state, id, file)? else {
+        return Ok(None);
+    };
+    let mime_type = mime_type_of(file);
+    let textual = mime_type.starts_with("text/") || mime_type == "application/json";
+    let (text, blob) = match String::from_utf8(bytes) {
+        Ok(text) if textual => (text, false),
+        Ok(text) => (BASE64_STANDARD.encode(text), true),
+        Err(err) => (BASE64_STANDARD.encode(err.into_bytes()), true),
+    };
+    let resource = StoredResource {
+        uri: job_uri(id, file),
+        name: file.to_string(),
+        mime_type: mime_type.to_string(),
+        text: Arc::new(text),
+        tenant,
+        blob,
+    };
+    Ok(Some(read_content(&resource, offset, length)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_uris_round_trip() {
+        let uri = job_uri("1700000000000-42-0", "exports/part 1.stl");
+        assert_eq!(uri, "job://1700000000000-42-0/exports/part%201.stl");
+        assert_eq!(
+            parse_job_uri(&uri),
+            Some((
+                "1700000000000-42-0".to_string(),
+                "exports/part 1.stl".to_string()
+            ))
+        );
+        assert!(parse_job_uri("job://1-2-3/../secret").is_none());
+        assert!(parse_job_uri("job://1-2-3/a%2F..%2Fb").is_none());
+        assert!(parse_job_uri("job://1-2-3/").is_none());
+        assert!(parse_job_uri("job://x/output.csv").is_none());
+        assert!(parse_job_uri("pcli2://results/1-2").is_none());
+    }
+}
@@ -1,26 +1,273 @@
+pub mod access;
+pub mod activity;
+pub mod admin;
+pub mod approval;
+pub mod archive;
+pub mod args;
+pub mod artifacts;
+pub mod asset_metadata;
+pub mod audit;
+pub mod auth;
+pub mod banner;
+pub mod batch_get;
+pub mod bench;
+pub mod breaker;
+pub mod cache;
+pub mod cassette;
 pub mod cli;
+pub mod compat;
+pub mod config;
+pub mod conformance;
+pub mod content;
+pub mod correlation;
+pub mod daemon;
+pub mod deadline;
+pub mod deptree;
+pub mod docs;
 pub mod error;
+pub mod export;
+pub mod fair;
+pub mod filter;
+pub mod flags;
+pub mod gallery;
+pub mod graph;
+pub mod health;
+pub mod i18n;
+pub mod image;
+pub mod index;
+pub mod inflight;
+pub mod install;
+pub mod instructions;
+pub mod jobs;
 pub mod mcp;
+pub mod notify;
+pub mod openapi;
+pub mod paging;
 pub mod pcli;
+pub mod plan;
+pub mod policy;
+pub mod projection;
+pub mod proxy;
+pub mod quota;
+pub mod redact;
+pub mod reformat;
+pub mod reload;
+pub mod replay;
+pub mod report;
+pub mod resources;
+pub mod runner;
+pub mod sandbox;
+pub mod search;
+pub mod secrets;
+pub mod selftest;
 pub mod server;
+pub mod session;
+#[cfg(feature = "redis")]
+pub mod shared;
+pub mod snapshot;
+pub mod split;
+pub mod spool;
+pub mod structured;
+pub mod suggest;
+pub mod summary;
+pub mod tenancy;
+pub mod tree;
+pub mod tui;
+pub mod update;
+pub mod upload;
+pub mod usage;
+pub mod warm;
+pub mod watch;
+pub mod websocket;
+pub mod workspace;
 
+use activity::CallTracker;
 use anyhow::Result;
+use approval::ApprovalQueue;
+use args::ArgMode;
+use audit::AuditLog;
+use auth::Reauthenticator;
+use banner::ColorMode;
+use bench::run_bench;
+use breaker::CircuitBreaker;
+use cache::ResultCache;
+use cassette::Cassette;
 use clap::ArgMatches;
-use cli::{ARG_LOG_LEVEL, CMD_CONFIG, CMD_HELP, CMD_SERVE, build_cli};
+use cli::{
+    ARG_DAEMON, ARG_LOG_LEVEL, ARG_TUI, CMD_BENCH, CMD_CONFIG, CMD_DOCS, CMD_DOCTOR, CMD_HELP,
+    CMD_INSTALL_PCLI2, CMD_PROXY, CMD_SECRETS, CMD_SELF_UPDATE, CMD_SELFTEST, CMD_SERVE,
+    CMD_STATUS, CMD_STOP, build_cli,
+};
+use compat::{ToolCompatibility, run_doctor};
+use config::ServerConfig;
+use conformance::ConformanceRecorder;
+use daemon::{run_status, run_stop, start_daemon};
+use docs::run_docs;
+use flags::FeatureFlags;
+use health::BackendHealth;
+use index::AssetIndex;
+use inflight::{InflightRequests, RequestLimiter};
+use install::run_install_pcli2;
 use mcp::run_config;
-use server::run_server;
-use std::sync::OnceLock;
-use tracing_subscriber::{EnvFilter, FmtSubscriber};
+use notify::Notifier;
+use pcli::ConcurrencyLimits;
+use policy::PolicyQuotas;
+use proxy::run_proxy;
+use quota::{ANONYMOUS_SESSION, QuotaTracker};
+use reload::ConfigReloader;
+use replay::NonceCache;
+use resources::{DEFAULT_INLINE_RESULT_BYTES, ResourceStore};
+use runner::{CommandRunner, LocalRunner};
+use secrets::run_secrets;
+use selftest::run_selftest;
+use server::{color_mode, run_server};
+use session::SessionRegistry;
+use std::io::IsTerminal;
+use std::sync::{Arc, OnceLock};
+use tenancy::Principal;
+use tracing::subscriber::SetGlobalDefaultError;
+use tracing_subscriber::{
+    EnvFilter, FmtSubscriber,
+    fmt::{
+        MakeWriter, SubscriberBuilder,
+        format::{DefaultFields, Format},
+    },
+};
+use tui::LogBuffer;
+use update::run_self_update;
+use usage::{ANONYMOUS_KEY, UsageLedger};
 #[derive(Clone)]
 pub struct AppState {
     pub server_name: String,
     pub server_version: String,
+    pub arg_mode: ArgMode,
+    pub resources: ResourceStore,
+    /// Folder listings, tenant lists, and resolved paths (`cache` config).
+    pub cache: ResultCache,
+    pub inline_result_limit: usize,
+    /// Keep the text block next to `structuredContent` (`serve --compat-text`).
+    pub compat_text: bool,
+    pub config: Arc<ServerConfig>,
+    pub limits: ConcurrencyLimits,
+    pub breaker: CircuitBreaker,
+    /// Recent `pcli2` failures and run times (`health` config).
+    pub health: BackendHealth,
+    pub reauth: Reauthenticator,
+    pub quotas: QuotaTracker,
+    /// Running `tools/call` requests, so client retries attach to them.
+    pub inflight: InflightRequests,
+    pub limiter: RequestLimiter,
+    /// Running and recent tool calls, shown by `serve --tui`.
+    pub calls: CallTracker,
+    /// Redacted log of finished tool calls (`audit://recent`).
+    pub audit: AuditLog,
+    /// Resource subscriptions and update notifications for `GET /mcp` streams.
+    pub notifier: Notifier,
+    /// Runs `pcli2` for the tools; a local subprocess unless replaced.
+    pub runner: Arc<dyn CommandRunner>,
+    /// Records or replays every `pcli2` run (`serve --record/--replay`).
+    pub cassette: Option<Arc<Cassette>>,
+    /// Records every `/mcp` exchange (`serve --record-conformance`).
+    pub conformance: Option<Arc<ConformanceRecorder>>,
+    /// `Mcp-Session-Id` of the request being handled, if the client sent one.
+    pub session: Option<String>,
+    /// Session ids issued on `initialize`; others are refused.
+    pub sessions: SessionRegistry,
+    /// Tenant the request's API key is bound to; its tool calls stay in it.
+    pub tenant: Option<String>,
+    /// `policies` entry the request's API key is held to.
+    pub policy: Option<String>,
+    /// Name of the request's API key, which its calls are accounted to.
+    pub api_key: Option<String>,
+    /// Per-key quotas of each policy (`policies.<name>.quotas`).
+    pub policy_quotas: PolicyQuotas,
+    /// Correlation id of the request being handled (`X-Request-Id`).
+    pub request_id: Option<String>,
+    /// Calls held for an operator's approval (`approval` config).
+    pub approvals: ApprovalQueue,
+    /// Set while running a call an operator approved, so it is not held again.
+    pub approved: bool,
+    /// Tools turned off because the installed `pcli2` cannot run them.
+    pub compat: ToolCompatibility,
+    /// Rereads `--config` on SIGHUP or `POST /admin/reload`.
+    pub reloader: ConfigReloader,
+    /// Runtime rollouts of experimental features (`/admin/features`).
+    pub features: FeatureFlags,
+    /// Full-text index of the `index.folders` assets.
+    pub index: AssetIndex,
+    /// What calls used, by session, API key, and month.
+    pub usage: UsageLedger,
+    /// Nonces of recent `/mcp` requests (`access.replay`).
+    pub nonces: NonceCache,
+    /// Whom a WebSocket connection was admitted as when it opened, under
+    /// `access.replay`; its messages carry no nonces of their own.
+    pub connection: Option<Principal>,
+}
+
+impl AppState {
+    pub fn new(server_name: impl Into<String>, server_version: impl Into<String>) -> Self {
+        Self {
+            server_name: server_name.into(),
+            server_version: server_version.into(),
+            arg_mode: ArgMode::default(),
+            resources: ResourceStore::default(),
+            cache: ResultCache::default(),
+            inline_result_limit: DEFAULT_INLINE_RESULT_BYTES,
+            compat_text: true,
+            config: Arc::new(ServerConfig::default()),
+            limits: ConcurrencyLimits::default(),
+            breaker: CircuitBreaker::default(),
+            health: BackendHealth::default(),
+            reauth: Reauthenticator::default(),
+            quotas: QuotaTracker::default(),
+            inflight: InflightRequests::default(),
+            limiter: RequestLimiter::default(),
+            calls: CallTracker::default(),
+            audit: AuditLog::default(),
+            notifier: Notifier::default(),
+            runner: Arc::new(LocalRunner),
+            cassette: None,
+            conformance: None,
+            session: None,
+            sessions: SessionRegistry::default(),
+            tenant: None,
+            policy: None,
+            api_key: None,
+            policy_quotas: PolicyQuotas::default(),
+            request_id: None,
+            approvals: ApprovalQueue::default(),
+            approved: false,
+            compat: ToolCompatibility::default(),
+            reloader: ConfigReloader::default(),
+            features: FeatureFlags::default(),
+            index: AssetIndex::default(),
+            usage: UsageLedger::default(),
+            nonces: NonceCache::default(),
+            connection: None,
+        }
+    }
+
+    /// Picks up the configuration of the last reload, if any.
+    pub fn refresh_config(&mut self) {
+        let reloader = self.reloader.clone();
+        reloader.apply(self);
+    }
+
+    pub fn session_key(&self) -> &str {
+        self.session.as_deref().unwrap_or(ANONYMOUS_SESSION)
+    }
+
+    /// Whom the request's calls count against: the name of its API key, or
+    /// `anonymous`. Unlike the session, the client cannot pick it.
+    pub fn principal_key(&self) -> &str {
+        self.api_key.as_deref().unwrap_or(ANONYMOUS_KEY)
+    }
 }
 
 pub async fn run() -> Result<()> {
     let matches = build_cli().get_matches();
     let log_level = matches.subcommand().and_then(|(name, sub_matches)| {
-        if name == CMD_SERVE {
+        if [CMD_SERVE, CMD_PROXY, CMD_SELFTEST, CMD_BENCH, CMD_DOCTOR].contains(&name) {
             sub_matches
                 .get_one::<String>(ARG_LOG_LEVEL)
                 .map(|value| value.as_str())
@@ -28,11 +275,44 @@ pub async fn run() -> Result<()> {
             None
         }
     });
-    setup_logging(log_level);
+    // stdout carries the MCP protocol in proxy mode and the report in
+    // selftest, bench, doctor, and docs mode, so logs go to stderr; `serve --tui` shows
+    // them itself.
+    let tui_logs = matches
+        .subcommand_matches(CMD_SERVE)
+        .filter(|sub_matches| sub_matches.get_flag(ARG_TUI))
+        .map(|_| LogBuffer::default());
+    let target = match (&tui_logs, matches.subcommand_name()) {
+        (Some(logs), _) => LogTarget::Buffer(logs.clone()),
+        (None, Some(name))
+            if [CMD_PROXY, CMD_SELFTEST, CMD_BENCH, CMD_DOCTOR, CMD_DOCS].contains(&name) =>
+        {
+            LogTarget::Stderr
+        }
+        _ => LogTarget::Stdout,
+    };
+    let color = matches
+        .subcommand()
+        .map(|(_, sub_matches)| color_mode(sub_matches))
+        .unwrap_or_default();
+    init_logging(log_level, target, color);
 
     match matches.subcommand() {
-        Some((CMD_SERVE, sub_matches)) => run_server(sub_matches).await,
+        Some((CMD_SERVE, sub_matches)) if sub_matches.get_flag(ARG_DAEMON) => {
+            start_daemon(sub_matches).await
+        }
+        Some((CMD_SERVE, sub_matches)) => run_server(sub_matches, tui_logs).await,
         Some((CMD_CONFIG, sub_matches)) => run_config(sub_matches),
+        Some((CMD_PROXY, sub_matches)) => run_proxy(sub_matches).await,
+        Some((CMD_SELFTEST, sub_matches)) => run_selftest(sub_matches).await,
+        Some((CMD_BENCH, sub_matches)) => run_bench(sub_matches).await,
+        Some((CMD_DOCTOR, sub_matches)) => run_doctor(sub_matches).await,
+        Some((CMD_DOCS, sub_matches)) => run_docs(sub_matches),
+        Some((CMD_SECRETS, sub_matches)) => run_secrets(sub_matches),
+        Some((CMD_INSTALL_PCLI2, sub_matches)) => run_install_pcli2(sub_matches).await,
+        Some((CMD_SELF_UPDATE, sub_matches)) => run_self_update(sub_matches).await,
+        Some((CMD_STOP, sub_matches)) => run_stop(sub_matches).await,
+        Some((CMD_STATUS, sub_matches)) => run_status(sub_matches),
         Some((CMD_HELP, sub_matches)) => run_help(sub_matches),
         _ => Ok(()),
     }
@@ -40,7 +320,22 @@ pub async fn run() -> Result<()> {
 
 static TRACING_INIT: OnceLock<()> = OnceLock::new();
 
+type FilterReload = Box<dyn Fn(EnvFilter) -> Result<(), String> + Send + Sync>;
+
+/// Swaps the filter of the subscriber installed by `init_logging`.
+static LOG_FILTER: OnceLock<FilterReload> = OnceLock::new();
+
 pub fn setup_logging(level: Option<&str>) {
+    init_logging(level, LogTarget::Stdout, ColorMode::default());
+}
+
+enum LogTarget {
+    Stdout,
+    Stderr,
+    Buffer(LogBuffer),
+}
+
+fn init_logging(level: Option<&str>, target: LogTarget, color: ColorMode) {
     // Only initialize tracing once
     TRACING_INIT.get_or_init(|| {
         if let Some(level) = level
@@ -50,16 +345,49 @@ pub fn setup_logging(level: Option<&str>) {
                 std::env::set_var("RUST_LOG", level);
             }
         }
-        let subscriber = FmtSubscriber::builder()
-            .with_env_filter(
-                EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
-            )
-            .finish();
-        tracing::subscriber::set_global_default(subscriber)
-            .expect("setting default subscriber failed");
+        let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+        let builder = FmtSubscriber::builder().with_env_filter(filter);
+        let result = match target {
+            LogTarget::Stdout => install_subscriber(
+                builder.with_ansi(color.enabled(std::io::stdout().is_terminal())),
+            ),
+            LogTarget::Stderr => install_subscriber(
+                builder
+                    .with_ansi(color.enabled(std::io::stderr().is_terminal()))
+                    .with_writer(std::io::stderr),
+            ),
+            LogTarget::Buffer(logs) => {
+                install_subscriber(builder.with_ansi(false).with_writer(move || logs.clone()))
+            }
+        };
+        result.expect("setting default subscriber failed");
     });
 }
 
+/// Replaces the log filter of the installed subscriber, e.g. after the
+/// configuration file's `log_level` changed.
+pub fn set_log_level(level: &str) -> Result<(), String> {
+    let filter = EnvFilter::try_new(level).map_err(|err| err.to_string())?;
+    match LOG_FILTER.get() {
+        Some(reload) => reload(filter),
+        None => Err("logging is not initialized".to_string()),
+    }
+}
+
+fn install_subscriber<W>(
+    builder: SubscriberBuilder<DefaultFields, Format, EnvFilter, W>,
+) -> Result<(), SetGlobalDefaultError>
+where
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let builder = builder.with_filter_reloading();
+    let handle = builder.reload_handle();
+    let _ = LOG_FILTER.set(Box::new(move |filter| {
+        handle.reload(filter).map_err(|err| err.to_string())
+    }));
+    tracing::subscriber::set_global_default(builder.finish())
+}
+
 fn run_help(matches: &ArgMatches) -> Result<()> {
     let mut cmd = build_cli();
     if let Some(subcommand) = matches.subcommand_name() {
@@ -82,14 +410,12 @@ mod tests {
 
     #[test]
     fn test_app_state_clone() {
-        let state = AppState {
-            server_name: "test-server".to_string(),
-            server_version: "1.0.0".to_string(),
-        };
+        let state = AppState::new("test-server", "1.0.0");
         let cloned_state = state.clone();
 
         assert_eq!(state.server_name, cloned_state.server_name);
         assert_eq!(state.server_version, cloned_state.server_version);
+        assert_eq!(state.arg_mode, cloned_state.arg_mode);
     }
 
     #[test]
@@ -1,8 +1,7 @@
 use anyhow::Result;
-use pcli2_mcp::{run, setup_logging};
+use pcli2_mcp::run;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    setup_logging(None);
     run().await
 }
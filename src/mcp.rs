@@ -1,32 +1,77 @@
 use crate::{
     AppState,
-    cli::{ARG_CLIENT, ARG_HOST, ARG_PORT, CLIENT_CLAUDE, CLIENT_QWEN_AGENT, CLIENT_QWEN_CODE},
+    approval::{APPROVAL_STATUS_TOOL, APPROVAL_URI_PREFIX},
+    asset_metadata::{ASSET_METADATA_URI_TEMPLATE, parse_asset_metadata_uri, read_asset_metadata},
+    audit::{AUDIT_URI, audit_descriptor, audit_resource},
+    cli::{
+        ARG_CLIENT, ARG_CWD, ARG_ENV, ARG_FORMAT, ARG_HOST, ARG_PORT, ARG_TRANSPORT, CLIENT_CLAUDE,
+        CLIENT_CODEX, CLIENT_LIBRECHAT, CLIENT_OPEN_WEBUI, CLIENT_QWEN_AGENT, CLIENT_QWEN_CODE,
+        FORMAT_JSON, FORMAT_TOML, FORMAT_YAML, TRANSPORT_LOCAL, TRANSPORT_REMOTE,
+    },
+    compat::unavailable_tools,
+    correlation::{correlation_id, set_request_id_header, tag_result, with_request_id},
+    flags::{self, APPROVALS, DYNAMIC_TOOLS},
+    health::backend_health,
+    inflight::OVERLOAD_RETRY_AFTER_SECS,
+    instructions::server_instructions,
+    jobs::{job_descriptors, parse_job_uri, read_job},
+    notify::{Notification, RESOURCE_UPDATED_METHOD, TOOLS_LIST_CHANGED_METHOD},
     pcli::*,
+    redact::redactor,
+    replay::admit_mcp,
+    resources::{RESOURCE_NOT_FOUND, read_resource, resource_descriptor},
+    tree::{TREE_URI_TEMPLATE, parse_tree_uri, read_tree, tree_descriptors},
 };
 use anyhow::{Result, anyhow};
 use axum::{
     body::Bytes,
     extract::State,
-    http::StatusCode,
-    response::{IntoResponse, Json},
+    http::{HeaderMap, StatusCode, header::RETRY_AFTER},
+    response::{
+        IntoResponse, Json, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
 };
 use clap::ArgMatches;
+use futures_util::stream;
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
-use tracing::info;
+use std::{
+    collections::hash_map::RandomState,
+    convert::Infallible,
+    hash::BuildHasher,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{Instrument, info, info_span, warn};
+
+/// JSON-RPC error code for requests rejected by `serve --max-in-flight`.
+pub const SERVER_OVERLOADED: i64 = -32000;
+/// JSON-RPC error code for requests without a valid `api_keys` key.
+pub const UNAUTHORIZED: i64 = -32001;
 
 pub const MCP_SERVER_ALIAS: &str = "pcli2";
 pub const MCP_REMOTE_COMMAND: &str = "npx";
 pub const MCP_REMOTE_PACKAGE: &str = "mcp-remote";
+pub const SESSION_HEADER: &str = "mcp-session-id";
 
+/// A validated JSON-RPC 2.0 request. `id` is `None` for notifications and
+/// `Some(Value::Null)` for requests that explicitly use a null id.
 #[derive(Debug, Deserialize)]
 pub struct RpcRequest {
-    jsonrpc: Option<String>,
     id: Option<Value>,
-    method: Option<String>,
+    method: String,
     params: Option<Value>,
 }
 
+/// An envelope that is not a valid request, with the id to answer with.
+#[derive(Debug, PartialEq)]
+pub struct InvalidRequest {
+    id: Value,
+    message: String,
+}
+
 #[derive(Debug, Serialize)]
 pub struct RpcResponse {
     jsonrpc: &'static str,
@@ -57,62 +102,377 @@ pub fn run_config(matches: &ArgMatches) -> Result<()> {
         .map(String::as_str)
         .unwrap_or("localhost");
     let port = *matches.get_one::<u16>(ARG_PORT).unwrap_or(&8080);
+    let env: Vec<(String, String)> = matches
+        .get_many::<String>(ARG_ENV)
+        .map(|values| {
+            values
+                .map(|value| parse_env_var(value))
+                .collect::<Result<_>>()
+        })
+        .transpose()?
+        .unwrap_or_default();
+    let cwd = matches.get_one::<String>(ARG_CWD).map(String::as_str);
+    let transport = matches
+        .get_one::<String>(ARG_TRANSPORT)
+        .map(String::as_str)
+        .unwrap_or(TRANSPORT_LOCAL);
 
-    let config = build_client_config(client, host, port)?;
-    let output = serde_json::to_string_pretty(&config)?;
+    let format = matches
+        .get_one::<String>(ARG_FORMAT)
+        .map(String::as_str)
+        .unwrap_or(match client {
+            CLIENT_CODEX => FORMAT_TOML,
+            CLIENT_LIBRECHAT => FORMAT_YAML,
+            _ => FORMAT_JSON,
+        });
+
+    let config = build_client_config(client, host, port, transport, &env, cwd)?;
+    let output = match format {
+        FORMAT_TOML => to_toml(&config)?,
+        FORMAT_YAML => to_yaml(&config),
+        _ => serde_json::to_string_pretty(&config)?,
+    };
     println!("{}", output);
     Ok(())
 }
 
-fn build_client_config(client: &str, host: &str, port: u16) -> Result<Value> {
-    let server_entry = json!({
-        MCP_SERVER_ALIAS: {
+/// Splits a `--env KEY=VALUE` flag. The value is written to the client
+/// config as given.
+fn parse_env_var(raw: &str) -> Result<(String, String)> {
+    let (key, value) = raw
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Invalid --env '{}': expected KEY=VALUE", raw))?;
+    if key.is_empty()
+        || !key
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+    {
+        return Err(anyhow!("Invalid --env name '{}'", key));
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
+fn build_client_config(
+    client: &str,
+    host: &str,
+    port: u16,
+    transport: &str,
+    env: &[(String, String)],
+    cwd: Option<&str>,
+) -> Result<Value> {
+    let url = format!("http://{}:{}/mcp", host, port);
+    // Open WebUI only connects to MCP servers over streamable HTTP.
+    let remote = transport == TRANSPORT_REMOTE || client == CLIENT_OPEN_WEBUI;
+    if remote && (!env.is_empty() || cwd.is_some()) {
+        return Err(anyhow!(
+            "--env and --cwd only apply to --transport {} (the spawned mcp-remote process)",
+            TRANSPORT_LOCAL
+        ));
+    }
+    let entry = if remote {
+        match client {
+            CLIENT_CLAUDE => json!({ "type": "http", "url": url }),
+            CLIENT_QWEN_CODE => json!({ "httpUrl": url }),
+            CLIENT_CODEX => json!({ "url": url }),
+            _ => json!({ "type": "streamable-http", "url": url }),
+        }
+    } else {
+        let mut entry = json!({
             "command": MCP_REMOTE_COMMAND,
-            "args": [
-                "-y",
-                MCP_REMOTE_PACKAGE,
-                format!("http://{}:{}/mcp", host, port)
-            ]
+            "args": ["-y", MCP_REMOTE_PACKAGE, url]
+        });
+        if !env.is_empty() {
+            entry["env"] = env
+                .iter()
+                .map(|(key, value)| (key.clone(), json!(value)))
+                .collect::<serde_json::Map<_, _>>()
+                .into();
         }
-    });
+        if let Some(cwd) = cwd {
+            entry["cwd"] = json!(cwd);
+        }
+        entry
+    };
+    let server_entry = json!({ MCP_SERVER_ALIAS: entry });
 
     let config = match client {
-        CLIENT_CLAUDE | CLIENT_QWEN_CODE | CLIENT_QWEN_AGENT => {
+        CLIENT_CLAUDE | CLIENT_QWEN_CODE | CLIENT_QWEN_AGENT | CLIENT_LIBRECHAT => {
             json!({ "mcpServers": server_entry })
         }
+        CLIENT_CODEX => json!({ "mcp_servers": server_entry }),
+        // The `TOOL_SERVER_CONNECTIONS` list, also accepted by the admin
+        // panel's tool server import.
+        CLIENT_OPEN_WEBUI => json!([{
+            "type": "mcp",
+            "url": url,
+            "path": "",
+            "auth_type": "none",
+            "key": "",
+            "config": { "enable": true },
+            "info": { "id": MCP_SERVER_ALIAS, "name": MCP_SERVER_ALIAS }
+        }]),
         _ => return Err(anyhow!("Unsupported client '{}'", client)),
     };
 
     Ok(config)
 }
 
-fn parse_rpc_request(value: Value) -> Result<RpcRequest, String> {
-    let obj = value
-        .as_object()
-        .ok_or_else(|| "Invalid Request: expected a JSON object".to_string())?;
-    let jsonrpc = match obj.get("jsonrpc") {
-        Some(Value::String(value)) => Some(value.clone()),
-        Some(_) => {
-            return Err("Invalid Request: 'jsonrpc' must be a string".to_string());
+/// Renders a client config as block-style YAML. Scalars and arrays of
+/// scalars are written as JSON, which YAML reads as flow values.
+fn to_yaml(config: &Value) -> String {
+    let mut out = String::new();
+    write_yaml(&mut out, 0, config);
+    out.trim_end().to_string()
+}
+
+fn write_yaml(out: &mut String, indent: usize, value: &Value) {
+    let pad = " ".repeat(indent);
+    match value {
+        Value::Object(table) if !table.is_empty() => {
+            for (key, value) in table {
+                out.push_str(&format!("{}{}:", pad, yaml_key(key)));
+                if is_yaml_block(value) {
+                    out.push('\n');
+                    write_yaml(out, indent + 2, value);
+                } else {
+                    out.push_str(&format!(" {}\n", value));
+                }
+            }
         }
+        Value::Array(items) if items.iter().any(is_yaml_block) => {
+            for item in items {
+                out.push_str(&format!("{}-", pad));
+                if is_yaml_block(item) {
+                    out.push('\n');
+                    write_yaml(out, indent + 2, item);
+                } else {
+                    out.push_str(&format!(" {}\n", item));
+                }
+            }
+        }
+        _ => out.push_str(&format!("{}{}\n", pad, value)),
+    }
+}
+
+fn is_yaml_block(value: &Value) -> bool {
+    match value {
+        Value::Object(table) => !table.is_empty(),
+        Value::Array(items) => items.iter().any(is_yaml_block),
+        _ => false,
+    }
+}
+
+fn yaml_key(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-')
+    {
+        key.to_string()
+    } else {
+        Value::String(key.to_string()).to_string()
+    }
+}
+
+/// Renders a client config as TOML: nested objects become `[a.b]` tables,
+/// objects inside arrays become inline tables.
+fn to_toml(config: &Value) -> Result<String> {
+    let Some(table) = config.as_object() else {
+        return Err(anyhow!("Client config must be a JSON object"));
+    };
+    let mut out = String::new();
+    write_toml_table(&mut out, &[], table)?;
+    Ok(out.trim_end().to_string())
+}
+
+fn write_toml_table(
+    out: &mut String,
+    path: &[String],
+    table: &serde_json::Map<String, Value>,
+) -> Result<()> {
+    let (tables, values): (Vec<_>, Vec<_>) = table.iter().partition(|(_, value)| value.is_object());
+    if !path.is_empty() && (!values.is_empty() || tables.is_empty()) {
+        out.push_str(&format!("[{}]\n", path.join(".")));
+    }
+    for (key, value) in &values {
+        out.push_str(&format!("{} = {}\n", toml_key(key), toml_value(value)?));
+    }
+    if !values.is_empty() {
+        out.push('\n');
+    }
+    for (key, value) in tables {
+        let mut child = path.to_vec();
+        child.push(toml_key(key));
+        if let Some(table) = value.as_object() {
+            write_toml_table(out, &child, table)?;
+        }
+    }
+    Ok(())
+}
+
+fn toml_key(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-')
+    {
+        key.to_string()
+    } else {
+        toml_string(key)
+    }
+}
+
+/// JSON string escapes are valid in TOML basic strings.
+fn toml_string(text: &str) -> String {
+    Value::String(text.to_string()).to_string()
+}
+
+fn toml_value(value: &Value) -> Result<String> {
+    Ok(match value {
+        Value::String(text) => toml_string(text),
+        Value::Bool(_) | Value::Number(_) => value.to_string(),
+        Value::Array(items) => format!(
+            "[{}]",
+            items
+                .iter()
+                .map(toml_value)
+                .collect::<Result<Vec<_>>>()?
+                .join(", ")
+        ),
+        Value::Object(table) => format!(
+            "{{ {} }}",
+            table
+                .iter()
+                .map(|(key, value)| Ok(format!("{} = {}", toml_key(key), toml_value(value)?)))
+                .collect::<Result<Vec<_>>>()?
+                .join(", ")
+        ),
+        Value::Null => return Err(anyhow!("TOML has no null value")),
+    })
+}
+
+fn parse_rpc_request(value: Value) -> Result<RpcRequest, InvalidRequest> {
+    let invalid = |id: &Value, message: &str| InvalidRequest {
+        id: id.clone(),
+        message: format!("Invalid Request: {}", message),
+    };
+    let Some(obj) = value.as_object() else {
+        return Err(invalid(&Value::Null, "expected a JSON object"));
+    };
+    let id = match obj.get("id") {
         None => None,
+        Some(id @ (Value::String(_) | Value::Number(_) | Value::Null)) => Some(id.clone()),
+        Some(_) => {
+            return Err(invalid(
+                &Value::Null,
+                "'id' must be a string, a number, or null",
+            ));
+        }
     };
-    let id = obj.get("id").cloned();
+    let reply_id = id.clone().unwrap_or(Value::Null);
+    match obj.get("jsonrpc") {
+        Some(Value::String(version)) if version == "2.0" => {}
+        Some(_) => return Err(invalid(&reply_id, "'jsonrpc' must be \"2.0\"")),
+        None => return Err(invalid(&reply_id, "missing 'jsonrpc'")),
+    }
     let method = match obj.get("method") {
-        Some(Value::String(value)) => Some(value.clone()),
-        Some(_) => return Err("Invalid Request: 'method' must be a string".to_string()),
+        Some(Value::String(method)) => method.clone(),
+        Some(_) => return Err(invalid(&reply_id, "'method' must be a string")),
+        None => return Err(invalid(&reply_id, "missing 'method'")),
+    };
+    let params = match obj.get("params") {
         None => None,
+        Some(params @ (Value::Object(_) | Value::Array(_))) => Some(params.clone()),
+        Some(_) => {
+            return Err(invalid(&reply_id, "'params' must be an object or an array"));
+        }
     };
-    let params = obj.get("params").cloned();
-    Ok(RpcRequest {
-        jsonrpc,
-        id,
-        method,
-        params,
-    })
+    Ok(RpcRequest { id, method, params })
+}
+
+/// Returns a new unguessable session id for the `Mcp-Session-Id` header.
+pub fn new_session_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let seq = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let high = RandomState::new().hash_one((seq, nanos));
+    let low = RandomState::new().hash_one((nanos, seq));
+    format!("{:016x}{:016x}", high, low)
 }
 
-pub async fn handle_mcp(State(state): State<AppState>, bytes: Bytes) -> impl IntoResponse {
+/// The JSON-RPC id of a request body, for errors returned before parsing it.
+fn request_id(bytes: &Bytes) -> Value {
+    serde_json::from_slice::<Value>(bytes)
+        .ok()
+        .and_then(|value| value.get("id").cloned())
+        .unwrap_or(Value::Null)
+}
+
+/// `POST /mcp`. Each request runs in a span named after its correlation id,
+/// which is echoed in the `X-Request-Id` response header.
+pub async fn handle_mcp(
+    State(mut state): State<AppState>,
+    headers: HeaderMap,
+    bytes: Bytes,
+) -> Response {
+    let correlation = correlation_id(&headers);
+    state.request_id = Some(correlation.clone());
+    let span = info_span!("request", id = %correlation);
+    let recorder = state.conformance.clone();
+    let session = headers
+        .get(SESSION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let mut response = handle_rpc(state, headers, bytes.clone())
+        .instrument(span)
+        .await
+        .into_response();
+    set_request_id_header(response.headers_mut(), &correlation);
+    match recorder {
+        Some(recorder) => recorder.record(session, &bytes, response).await,
+        None => response,
+    }
+}
+
+async fn handle_rpc(mut state: AppState, headers: HeaderMap, bytes: Bytes) -> Response {
+    state.refresh_config();
+    let admitted = match state.connection.take() {
+        Some(principal) => Ok(principal),
+        None => admit_mcp(&state, &headers, &bytes).await,
+    };
+    let principal = match admitted {
+        Ok(principal) => principal,
+        Err(refusal) => {
+            warn!("⚠ rejected /mcp request: {}", refusal);
+            return (
+                refusal.status(),
+                json_error(request_id(&bytes), UNAUTHORIZED, refusal.to_string()),
+            )
+                .into_response();
+        }
+    };
+    state.tenant = principal.tenant;
+    state.policy = principal.policy;
+    state.api_key = principal.key;
+    let _permit = match state.limiter.try_admit() {
+        Ok(permit) => permit,
+        Err(message) => {
+            warn!("⚠ {}", message);
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(RETRY_AFTER, OVERLOAD_RETRY_AFTER_SECS.to_string())],
+                json_error(request_id(&bytes), SERVER_OVERLOADED, message),
+            )
+                .into_response();
+        }
+    };
+    state.session = headers
+        .get(SESSION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
     let value: Value = match serde_json::from_slice(&bytes) {
         Ok(value) => value,
         Err(_) => {
@@ -123,47 +483,50 @@ pub async fn handle_mcp(State(state): State<AppState>, bytes: Bytes) -> impl Int
 
     let request = match parse_rpc_request(value) {
         Ok(request) => request,
-        Err(message) => {
-            return json_error(Value::Null, -32600, message).into_response();
+        Err(invalid) => {
+            return json_error(invalid.id, -32600, invalid.message).into_response();
         }
     };
-
-    let id = request.id.clone().unwrap_or(Value::Null);
-    if let Some(version) = request.jsonrpc.as_deref()
-        && version != "2.0"
+    if request.method != "initialize"
+        && let Some(session) = &state.session
+        && let Err(message) = state.sessions.check(session, state.principal_key()).await
     {
-        return json_error(id, -32600, format!("Invalid jsonrpc version '{}'", version))
-            .into_response();
+        warn!("⚠ {}", message);
+        let id = request.id.unwrap_or(Value::Null);
+        return (StatusCode::NOT_FOUND, json_error(id, -32600, message)).into_response();
     }
-    let method = match request.method.as_deref() {
-        Some(method) => method,
-        None => {
-            return json_error(id, -32600, "Invalid Request: missing 'method'".to_string())
-                .into_response();
-        }
-    };
-    if id.is_null() {
+
+    // Notifications get no JSON-RPC response.
+    let Some(id) = request.id.clone() else {
         return StatusCode::OK.into_response();
-    }
+    };
+    let method = request.method.as_str();
 
     match method {
         "initialize" => {
             info!("🧩 initialize");
-            let result = json!({
+            let mut result = json!({
                 "protocolVersion": "2025-03-26",
-                "serverInfo": {
-                    "name": state.server_name,
-                    "version": state.server_version
-                },
-                "capabilities": {
-                    "tools": {}
+                "serverInfo": server_info(&state),
+                "capabilities": server_capabilities(&state),
+                "_meta": {
+                    "implementation": {
+                        "name": state.server_name,
+                        "version": state.server_version
+                    },
+                    "backend_health": backend_health(&state).to_json(),
+                    "unavailable_tools": unavailable_tools(&state)
                 }
             });
-            json_ok(id, result).into_response()
+            if let Some(instructions) = server_instructions(&state).await {
+                result["instructions"] = json!(instructions);
+            }
+            let session = state.sessions.issue(state.principal_key()).await;
+            ([(SESSION_HEADER, session)], json_ok(id, result)).into_response()
         }
         "tools/list" => {
             info!("🔧 tools/list");
-            let tools = tool_list();
+            let tools = public_tool_list(&state);
             let result = json!({ "tools": tools });
             json_ok(id, result).into_response()
         }
@@ -174,14 +537,291 @@ pub async fn handle_mcp(State(state): State<AppState>, bytes: Bytes) -> impl Int
                 .and_then(|value| value.as_str())
                 .unwrap_or("unknown");
             info!("🔧 tools/call name={}", tool_name);
-            match call_tool(params).await {
-                Ok(result) => json_ok(id, result).into_response(),
-                Err(message) => json_error(id, -32602, message).into_response(),
+            let outcome = state
+                .inflight
+                .run(
+                    state.principal_key(),
+                    state.session.as_deref(),
+                    &id,
+                    method,
+                    &params,
+                    call_tool(&state, params.clone()),
+                )
+                .await;
+            match outcome {
+                Ok(mut result) => {
+                    tag_result(&mut result, state.request_id.as_deref());
+                    json_ok(id, result).into_response()
+                }
+                Err(message) => json_error(
+                    id,
+                    -32602,
+                    with_request_id(
+                        redactor().redact_text(&state.config.locale.error(&message), &[]),
+                        state.request_id.as_deref(),
+                    ),
+                )
+                .into_response(),
             }
         }
+        "resources/list" => {
+            info!("📦 resources/list");
+            let resources: Vec<Value> = std::iter::once(audit_descriptor())
+                .chain(tree_descriptors(&state).await)
+                .chain(job_descriptors(&state))
+                .chain(
+                    state
+                        .resources
+                        .list_all()
+                        .await
+                        .iter()
+                        .filter(|resource| resource.visible_to(state.tenant.as_deref()))
+                        .map(resource_descriptor),
+                )
+                .collect();
+            json_ok(id, json!({ "resources": resources })).into_response()
+        }
+        "resources/templates/list" => {
+            info!("📦 resources/templates/list");
+            json_ok(id, json!({ "resourceTemplates": resource_templates() })).into_response()
+        }
+        "resources/read" => {
+            let params = request.params.unwrap_or_else(|| json!({}));
+            let Some(uri) = params.get("uri").and_then(|v| v.as_str()) else {
+                return json_error(id, -32602, "Missing required parameter: 'uri'".to_string())
+                    .into_response();
+            };
+            info!("📦 resources/read uri={}", uri);
+            if uri == AUDIT_URI {
+                return json_ok(id, audit_resource(&state.audit, state.tenant.as_deref()))
+                    .into_response();
+            }
+            if let Some((tenant, uuid)) = parse_asset_metadata_uri(uri) {
+                return match read_asset_metadata(&state, &tenant, &uuid).await {
+                    Ok(Some(result)) => json_ok(id, result).into_response(),
+                    Ok(None) => json_error(
+                        id,
+                        RESOURCE_NOT_FOUND,
+                        format!("Resource '{}' not found", uri),
+                    )
+                    .into_response(),
+                    Err(message) => json_error(id, -32603, message).into_response(),
+                };
+            }
+            if let Some((tenant, path)) = parse_tree_uri(uri) {
+                return match read_tree(&state, &tenant, &path).await {
+                    Ok(Some(result)) => json_ok(id, result).into_response(),
+                    Ok(None) => json_error(
+                        id,
+                        RESOURCE_NOT_FOUND,
+                        format!("Resource '{}' not found", uri),
+                    )
+                    .into_response(),
+                    Err(message) => json_error(id, -32603, message).into_response(),
+                };
+            }
+            let offset = params
+                .get("offset")
+                .and_then(|v| v.as_u64())
+                .map(|v| v as usize);
+            let length = params
+                .get("length")
+                .and_then(|v| v.as_u64())
+                .map(|v| v as usize);
+            if let Some((job, file)) = parse_job_uri(uri) {
+                return match read_job(&state, &job, &file, offset, length) {
+                    Ok(Some(result)) => json_ok(id, result).into_response(),
+                    Ok(None) => json_error(
+                        id,
+                        RESOURCE_NOT_FOUND,
+                        format!("Resource '{}' not found", uri),
+                    )
+                    .into_response(),
+                    Err(message) => json_error(id, -32603, message).into_response(),
+                };
+            }
+            state.resources.fetch(uri).await;
+            match read_resource(
+                &state.resources,
+                state.tenant.as_deref(),
+                uri,
+                offset,
+                length,
+            ) {
+                Some(result) => json_ok(id, result).into_response(),
+                None => json_error(
+                    id,
+                    RESOURCE_NOT_FOUND,
+                    format!("Resource '{}' not found", uri),
+                )
+                .into_response(),
+            }
+        }
+        "resources/subscribe" | "resources/unsubscribe"
+            if state.config.capabilities.resource_subscriptions =>
+        {
+            let params = request.params.unwrap_or_else(|| json!({}));
+            let Some(uri) = params.get("uri").and_then(|v| v.as_str()) else {
+                return json_error(id, -32602, "Missing required parameter: 'uri'".to_string())
+                    .into_response();
+            };
+            info!("🔔 {} uri={}", method, uri);
+            if state.tenant.is_some()
+                && !state
+                    .resources
+                    .get(uri)
+                    .is_some_and(|resource| resource.visible_to(state.tenant.as_deref()))
+            {
+                return json_error(
+                    id,
+                    RESOURCE_NOT_FOUND,
+                    format!("Resource '{}' not found", uri),
+                )
+                .into_response();
+            }
+            if method == "resources/unsubscribe" {
+                state.notifier.unsubscribe(state.session_key(), uri);
+            } else if let Err(message) = state.notifier.subscribe(state.session_key(), uri) {
+                return json_error(id, -32602, message).into_response();
+            }
+            json_ok(id, json!({})).into_response()
+        }
         _ => json_error(id, -32601, format!("Method '{}' not found", method)).into_response(),
     }
 }
+
+/// `GET /mcp`: a server-sent event stream carrying
+/// `notifications/resources/updated` for the session's subscriptions and
+/// `notifications/tools/list_changed` after a configuration reload.
+pub async fn handle_mcp_stream(State(mut state): State<AppState>, headers: HeaderMap) -> Response {
+    state.refresh_config();
+    let principal = match admit_mcp(&state, &headers, &[]).await {
+        Ok(principal) => principal,
+        Err(refusal) => {
+            warn!("⚠ rejected notification stream: {}", refusal);
+            return (refusal.status(), refusal.to_string()).into_response();
+        }
+    };
+    state.api_key = principal.key;
+    state.session = headers
+        .get(SESSION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    if let Some(session) = &state.session
+        && let Err(message) = state.sessions.check(session, state.principal_key()).await
+    {
+        warn!("⚠ rejected notification stream: {}", message);
+        return (StatusCode::NOT_FOUND, message).into_response();
+    }
+    info!("🔔 notification stream opened");
+    let updates = state.notifier.listen();
+    let events = stream::unfold((state, updates), |(state, mut updates)| async move {
+        loop {
+            let notification = match updates.recv().await {
+                Ok(notification) => match notification_message(&state, notification) {
+                    Some(message) => message,
+                    None => continue,
+                },
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            };
+            let event = Event::default().data(notification.to_string());
+            return Some((Ok::<_, Infallible>(event), (state, updates)));
+        }
+    });
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// The templates of `resources/templates/list`: URIs a client fills in to
+/// read any folder tree or asset's metadata.
+pub fn resource_templates() -> Vec<Value> {
+    vec![
+        json!({
+            "uriTemplate": TREE_URI_TEMPLATE,
+            "name": "Folder tree",
+            "description": "A tenant's top-level folders, each with the URI that lists its subfolders.",
+            "mimeType": "application/json"
+        }),
+        json!({
+            "uriTemplate": ASSET_METADATA_URI_TEMPLATE,
+            "name": "Asset metadata",
+            "description": "The metadata properties of the asset with this UUID, from `pcli2 asset get --metadata`.",
+            "mimeType": "application/json"
+        }),
+    ]
+}
+
+/// The `serverInfo` of the `initialize` result: the `identity` config over
+/// the built-in name and version. The built-in ones are always sent as
+/// `_meta.implementation` as well.
+pub fn server_info(state: &AppState) -> Value {
+    let identity = &state.config.identity;
+    let mut info = json!({
+        "name": identity.name.as_deref().unwrap_or(&state.server_name),
+        "version": identity.version.as_deref().unwrap_or(&state.server_version)
+    });
+    if let Some(title) = &identity.title {
+        info["title"] = json!(title);
+    }
+    info
+}
+
+/// The `capabilities` of the `initialize` result: the protocol features
+/// this server serves under its configuration. Prompts, logging, and
+/// completions are not served, so they are not advertised. Held calls are
+/// advertised under `experimental` as `pcli2/approvals` when approval is
+/// on, so clients can tell a held call's result apart from a refusal.
+pub fn server_capabilities(state: &AppState) -> Value {
+    let config = &state.config;
+    let subscribe = config.capabilities.resource_subscriptions;
+    let list_changed = tools_list_changed(state);
+    let mut capabilities = json!({
+        "tools": { "listChanged": list_changed },
+        "resources": { "subscribe": subscribe, "listChanged": false }
+    });
+    if config.approval.is_enabled() && flags::enabled(state, APPROVALS) {
+        capabilities["experimental"] = json!({
+            "pcli2/approvals": {
+                "tools": config.approval.tools.iter().map(|tool| config.tools.public_name(tool)).collect::<Vec<_>>(),
+                "statusTool": config.tools.public_name(APPROVAL_STATUS_TOOL),
+                "uriPrefix": APPROVAL_URI_PREFIX,
+                "notifications": subscribe
+            }
+        });
+    }
+    capabilities
+}
+
+/// Whether the session is told when the tool list changes: configured and
+/// rolled out to it (`dynamic_tools` feature).
+fn tools_list_changed(state: &AppState) -> bool {
+    state.config.capabilities.tools_list_changed && flags::enabled(state, DYNAMIC_TOOLS)
+}
+
+/// The JSON-RPC notification the session of `state` receives for
+/// `notification`, or `None` when it is for another session.
+pub fn notification_message(state: &AppState, notification: Notification) -> Option<Value> {
+    match notification {
+        Notification::ResourceUpdated(uri)
+            if state.notifier.is_subscribed(state.session_key(), &uri) =>
+        {
+            Some(json!({
+                "jsonrpc": "2.0",
+                "method": RESOURCE_UPDATED_METHOD,
+                "params": { "uri": uri }
+            }))
+        }
+        Notification::ResourceUpdated(_) => None,
+        Notification::ToolsListChanged if !tools_list_changed(state) => None,
+        Notification::ToolsListChanged => Some(json!({
+            "jsonrpc": "2.0",
+            "method": TOOLS_LIST_CHANGED_METHOD
+        })),
+    }
+}
+
 pub fn json_ok(id: Value, result: Value) -> Json<RpcResponse> {
     Json(RpcResponse {
         jsonrpc: "2.0",
@@ -205,7 +845,7 @@ mod tests {
 
     #[test]
     fn test_build_client_config_claude() {
-        let config = build_client_config("claude", "localhost", 8080).unwrap();
+        let config = build_client_config("claude", "localhost", 8080, "local", &[], None).unwrap();
         let expected = json!({
             "mcpServers": {
                 "pcli2": {
@@ -219,7 +859,8 @@ mod tests {
 
     #[test]
     fn test_build_client_config_qwen_code() {
-        let config = build_client_config("qwen-code", "localhost", 8080).unwrap();
+        let config =
+            build_client_config("qwen-code", "localhost", 8080, "local", &[], None).unwrap();
         let expected = json!({
             "mcpServers": {
                 "pcli2": {
@@ -233,7 +874,8 @@ mod tests {
 
     #[test]
     fn test_build_client_config_qwen_agent() {
-        let config = build_client_config("qwen-agent", "localhost", 8080).unwrap();
+        let config =
+            build_client_config("qwen-agent", "localhost", 8080, "local", &[], None).unwrap();
         let expected = json!({
             "mcpServers": {
                 "pcli2": {
@@ -245,9 +887,116 @@ mod tests {
         assert_eq!(config, expected);
     }
 
+    #[test]
+    fn test_build_client_config_env_and_cwd() {
+        let env = vec![
+            parse_env_var("PCLI2_HOME=/srv/pcli2").unwrap(),
+            parse_env_var("HTTPS_PROXY=http://proxy:3128/?a=b").unwrap(),
+        ];
+        let config =
+            build_client_config("claude", "localhost", 8080, "local", &env, Some("/srv")).unwrap();
+        let entry = &config["mcpServers"]["pcli2"];
+        assert_eq!(
+            entry["env"],
+            json!({"PCLI2_HOME": "/srv/pcli2", "HTTPS_PROXY": "http://proxy:3128/?a=b"})
+        );
+        assert_eq!(entry["cwd"], "/srv");
+        assert!(parse_env_var("PCLI2_HOME").is_err());
+        assert!(parse_env_var("=value").is_err());
+        assert!(parse_env_var("BAD NAME=1").is_err());
+    }
+
+    #[test]
+    fn test_build_client_config_codex_toml() {
+        let env = vec![parse_env_var("PCLI2_HOME=/srv/\"pcli2\"").unwrap()];
+        let config =
+            build_client_config("codex", "localhost", 8080, "local", &env, Some("/srv")).unwrap();
+        let toml = to_toml(&config).unwrap();
+        assert_eq!(
+            toml,
+            [
+                "[mcp_servers.pcli2]",
+                "args = [\"-y\", \"mcp-remote\", \"http://localhost:8080/mcp\"]",
+                "command = \"npx\"",
+                "cwd = \"/srv\"",
+                "",
+                "[mcp_servers.pcli2.env]",
+                "PCLI2_HOME = \"/srv/\\\"pcli2\\\"\"",
+            ]
+            .join("\n")
+        );
+
+        let claude =
+            to_toml(&build_client_config("claude", "h", 1, "local", &[], None).unwrap()).unwrap();
+        assert!(claude.starts_with("[mcpServers.pcli2]\n"));
+        assert_eq!(toml_key("my server"), "\"my server\"");
+        assert_eq!(
+            toml_value(&json!([{"a": 1}, true])).unwrap(),
+            "[{ a = 1 }, true]"
+        );
+        assert!(to_toml(&json!({"a": null})).is_err());
+    }
+
+    #[test]
+    fn test_build_client_config_remote_transport() {
+        let claude = build_client_config("claude", "mcp", 8080, "remote", &[], None).unwrap();
+        assert_eq!(
+            claude["mcpServers"]["pcli2"],
+            json!({"type": "http", "url": "http://mcp:8080/mcp"})
+        );
+        let qwen = build_client_config("qwen-code", "mcp", 8080, "remote", &[], None).unwrap();
+        assert_eq!(
+            qwen["mcpServers"]["pcli2"]["httpUrl"],
+            "http://mcp:8080/mcp"
+        );
+        let env = vec![parse_env_var("A=1").unwrap()];
+        assert!(build_client_config("claude", "mcp", 8080, "remote", &env, None).is_err());
+    }
+
+    #[test]
+    fn test_build_client_config_librechat_yaml() {
+        let local = build_client_config("librechat", "mcp", 8080, "local", &[], None).unwrap();
+        assert_eq!(
+            to_yaml(&local),
+            [
+                "mcpServers:",
+                "  pcli2:",
+                "    args: [\"-y\",\"mcp-remote\",\"http://mcp:8080/mcp\"]",
+                "    command: \"npx\"",
+            ]
+            .join("\n")
+        );
+        let remote = build_client_config("librechat", "mcp", 8080, "remote", &[], None).unwrap();
+        assert_eq!(
+            to_yaml(&remote),
+            [
+                "mcpServers:",
+                "  pcli2:",
+                "    type: \"streamable-http\"",
+                "    url: \"http://mcp:8080/mcp\"",
+            ]
+            .join("\n")
+        );
+        assert_eq!(
+            to_yaml(&json!({"list": [{"a": 1}, 2], "empty": {}})),
+            "empty: {}\nlist:\n  -\n    a: 1\n  - 2"
+        );
+    }
+
+    #[test]
+    fn test_build_client_config_open_webui() {
+        let config = build_client_config("open-webui", "mcp", 8080, "local", &[], None).unwrap();
+        assert_eq!(config[0]["type"], "mcp");
+        assert_eq!(config[0]["url"], "http://mcp:8080/mcp");
+        assert_eq!(config[0]["info"]["id"], "pcli2");
+        let env = vec![parse_env_var("A=1").unwrap()];
+        assert!(build_client_config("open-webui", "mcp", 8080, "local", &env, None).is_err());
+    }
+
     #[test]
     fn test_build_client_config_unsupported() {
-        let result = build_client_config("unsupported-client", "localhost", 8080);
+        let result =
+            build_client_config("unsupported-client", "localhost", 8080, "local", &[], None);
         assert!(result.is_err());
         assert!(
             result
@@ -270,9 +1019,8 @@ mod tests {
         assert!(result.is_ok());
 
         let request = result.unwrap();
-        assert_eq!(request.jsonrpc, Some("2.0".to_string()));
         assert_eq!(request.id, Some(json!(1)));
-        assert_eq!(request.method, Some("test_method".to_string()));
+        assert_eq!(request.method, "test_method");
         assert_eq!(request.params, Some(json!({"key": "value"})));
     }
 
@@ -280,14 +1028,12 @@ mod tests {
     fn test_parse_rpc_request_missing_method() {
         let json_input = json!({
             "jsonrpc": "2.0",
-            "id": 1
+            "id": "req-1"
         });
 
-        let result = parse_rpc_request(json_input);
-        assert!(result.is_ok());
-
-        let request = result.unwrap();
-        assert_eq!(request.method, None);
+        let err = parse_rpc_request(json_input).unwrap_err();
+        assert_eq!(err.id, json!("req-1"));
+        assert!(err.message.contains("missing 'method'"));
     }
 
     #[test]
@@ -298,6 +1044,110 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_parse_rpc_request_ids() {
+        let parse = |id: Value| {
+            parse_rpc_request(json!({ "jsonrpc": "2.0", "id": id, "method": "ping" }))
+                .map(|request| request.id)
+        };
+        assert_eq!(parse(json!("abc")), Ok(Some(json!("abc"))));
+        assert_eq!(parse(json!(-7)), Ok(Some(json!(-7))));
+        assert_eq!(parse(json!(1.5)), Ok(Some(json!(1.5))));
+        assert_eq!(parse(Value::Null), Ok(Some(Value::Null)));
+        assert_eq!(parse(json!([1])).unwrap_err().id, Value::Null);
+        assert_eq!(parse(json!({ "a": 1 })).unwrap_err().id, Value::Null);
+        let notification = parse_rpc_request(json!({ "jsonrpc": "2.0", "method": "ping" }));
+        assert_eq!(notification.map(|request| request.id), Ok(None));
+    }
+
+    #[test]
+    fn test_parse_rpc_request_envelope() {
+        let missing = parse_rpc_request(json!({ "id": 3, "method": "ping" })).unwrap_err();
+        assert_eq!(missing.id, json!(3));
+        assert!(missing.message.contains("missing 'jsonrpc'"));
+        assert!(parse_rpc_request(json!({ "jsonrpc": 2.0, "id": 3, "method": "ping" })).is_err());
+        assert!(parse_rpc_request(json!({ "jsonrpc": "2.0", "id": 3, "method": 5 })).is_err());
+        assert!(
+            parse_rpc_request(
+                json!({ "jsonrpc": "2.0", "id": 3, "method": "ping", "params": "x" })
+            )
+            .is_err()
+        );
+    }
+
+    /// Small deterministic generator so the property test needs no extra crate.
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self, bound: usize) -> usize {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((self.0 >> 33) as usize) % bound
+        }
+
+        fn value(&mut self, depth: usize) -> Value {
+            match self.next(if depth > 2 { 5 } else { 7 }) {
+                0 => Value::Null,
+                1 => json!(self.next(2) == 0),
+                2 => json!(self.next(1000) as i64 - 500),
+                3 => json!(self.next(1000) as f64 / 7.0),
+                4 => json!(["2.0", "1.0", "", "ping", "tools/list"][self.next(5)]),
+                5 => Value::Array((0..self.next(3)).map(|_| self.value(depth + 1)).collect()),
+                _ => {
+                    let mut object = serde_json::Map::new();
+                    for _ in 0..self.next(3) {
+                        object.insert(format!("k{}", self.next(4)), self.value(depth + 1));
+                    }
+                    Value::Object(object)
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_rpc_request_property() {
+        let mut rng = Lcg(0x5eed);
+        for _ in 0..5000 {
+            let mut envelope = serde_json::Map::new();
+            for field in ["jsonrpc", "id", "method", "params"] {
+                if rng.next(4) > 0 {
+                    envelope.insert(field.to_string(), rng.value(0));
+                }
+            }
+            let input = Value::Object(envelope.clone());
+            let valid_id = match envelope.get("id") {
+                None => Some(Value::Null),
+                Some(id @ (Value::String(_) | Value::Number(_) | Value::Null)) => Some(id.clone()),
+                Some(_) => None,
+            };
+            let valid = valid_id.is_some()
+                && envelope.get("jsonrpc") == Some(&json!("2.0"))
+                && envelope.get("method").is_some_and(Value::is_string)
+                && envelope
+                    .get("params")
+                    .is_none_or(|params| params.is_object() || params.is_array());
+            match parse_rpc_request(input.clone()) {
+                Ok(request) => {
+                    assert!(valid, "accepted {}", input);
+                    assert_eq!(request.id, envelope.get("id").cloned());
+                    assert_eq!(Some(&json!(request.method)), envelope.get("method"));
+                }
+                Err(invalid) => {
+                    assert!(!valid, "rejected {}", input);
+                    assert_eq!(invalid.id, valid_id.unwrap_or(Value::Null), "{}", input);
+                    assert!(invalid.message.starts_with("Invalid Request: "));
+                }
+            }
+            let other = rng.value(0);
+            if !other.is_object() {
+                let invalid = parse_rpc_request(other).unwrap_err();
+                assert!(invalid.id.is_null());
+            }
+        }
+    }
+
     #[test]
     fn test_json_ok() {
         let id = json!(1);
@@ -309,6 +1159,13 @@ mod tests {
         assert_eq!(response.result, json!({"test": "value"}));
     }
 
+    #[test]
+    fn test_new_session_id_is_unique() {
+        let first = new_session_id();
+        assert_eq!(first.len(), 32);
+        assert_ne!(first, new_session_id());
+    }
+
     #[test]
     fn test_json_error() {
         let id = json!(1);
@@ -319,4 +1176,71 @@ mod tests {
         assert_eq!(response.error.code, -32601);
         assert_eq!(response.error.message, "Method not found");
     }
+
+    #[test]
+    fn test_capabilities_follow_the_configuration() {
+        use crate::config::{ApprovalConfig, CapabilitiesConfig, ServerConfig};
+        use std::sync::Arc;
+
+        let capabilities = server_capabilities(&AppState::new("test", "0.0.0"));
+        assert_eq!(capabilities["tools"]["listChanged"], true);
+        assert_eq!(capabilities["resources"]["subscribe"], true);
+        assert!(capabilities.get("prompts").is_none());
+        assert!(capabilities.get("experimental").is_none());
+
+        let state = AppState {
+            config: Arc::new(ServerConfig {
+                capabilities: CapabilitiesConfig {
+                    resource_subscriptions: false,
+                    tools_list_changed: false,
+                },
+                approval: ApprovalConfig {
+                    tools: vec!["pcli2_asset_metadata_delete".to_string()],
+                    ..ApprovalConfig::default()
+                },
+                ..ServerConfig::default()
+            }),
+            ..AppState::new("test", "0.0.0")
+        };
+        let capabilities = server_capabilities(&state);
+        assert_eq!(capabilities["tools"]["listChanged"], false);
+        assert_eq!(capabilities["resources"]["subscribe"], false);
+        let approvals = &capabilities["experimental"]["pcli2/approvals"];
+        assert_eq!(approvals["tools"], json!(["pcli2_asset_metadata_delete"]));
+        assert_eq!(approvals["statusTool"], APPROVAL_STATUS_TOOL);
+        assert_eq!(approvals["notifications"], false);
+        assert!(notification_message(&state, Notification::ToolsListChanged).is_none());
+    }
+
+    #[test]
+    fn test_server_info_follows_the_identity() {
+        use crate::config::{IdentityConfig, ServerConfig};
+        use std::sync::Arc;
+
+        let state = AppState::new("pcli2-mcp", "0.1.9");
+        assert_eq!(
+            server_info(&state),
+            json!({ "name": "pcli2-mcp", "version": "0.1.9" })
+        );
+
+        let state = AppState {
+            config: Arc::new(ServerConfig {
+                identity: IdentityConfig {
+                    name: Some("acme-physna".to_string()),
+                    title: Some("Acme Physna Gateway (prod)".to_string()),
+                    version: None,
+                },
+                ..ServerConfig::default()
+            }),
+            ..state
+        };
+        assert_eq!(
+            server_info(&state),
+            json!({
+                "name": "acme-physna",
+                "title": "Acme Physna Gateway (prod)",
+                "version": "0.1.9"
+            })
+        );
+    }
 }
@@ -0,0 +1,129 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+use tokio::sync::broadcast;
+
+pub const RESOURCE_UPDATED_METHOD: &str = "notifications/resources/updated";
+pub const TOOLS_LIST_CHANGED_METHOD: &str = "notifications/tools/list_changed";
+/// Notifications buffered per stream before a slow client starts missing some.
+const NOTIFICATION_BUFFER: usize = 256;
+pub const MAX_SUBSCRIPTIONS_PER_SESSION: usize = 100;
+pub const MAX_SUBSCRIBED_SESSIONS: usize = 10_000;
+
+/// A change announced to open `GET /mcp` streams.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Notification {
+    /// Sent to the sessions subscribed to this URI.
+    ResourceUpdated(String),
+    /// Sent to every session.
+    ToolsListChanged,
+}
+
+/// Resource subscriptions per session, and the channel that carries
+/// notifications to open `GET /mcp` streams.
+#[derive(Clone)]
+pub struct Notifier {
+    updates: broadcast::Sender<Notification>,
+    subscriptions: Arc<Mutex<HashMap<String, HashSet<String>>>>,
+}
+
+impl Default for Notifier {
+    fn default() -> Self {
+        Self {
+            updates: broadcast::channel(NOTIFICATION_BUFFER).0,
+            subscriptions: Arc::default(),
+        }
+    }
+}
+
+impl Notifier {
+    pub fn subscribe(&self, session: &str, uri: &str) -> Result<(), String> {
+        let mut subscriptions = self.subscriptions.lock().unwrap_or_else(|e| e.into_inner());
+        if !subscriptions.contains_key(session) && subscriptions.len() >= MAX_SUBSCRIBED_SESSIONS {
+            return Err("Too many sessions with resource subscriptions".to_string());
+        }
+        let uris = subscriptions.entry(session.to_string()).or_default();
+        if !uris.contains(uri) && uris.len() >= MAX_SUBSCRIPTIONS_PER_SESSION {
+            return Err(format!(
+                "At most {} resource subscriptions per session",
+                MAX_SUBSCRIPTIONS_PER_SESSION
+            ));
+        }
+        uris.insert(uri.to_string());
+        Ok(())
+    }
+
+    pub fn unsubscribe(&self, session: &str, uri: &str) {
+        let mut subscriptions = self.subscriptions.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(uris) = subscriptions.get_mut(session) {
+            uris.remove(uri);
+            if uris.is_empty() {
+                subscriptions.remove(session);
+            }
+        }
+    }
+
+    pub fn is_subscribed(&self, session: &str, uri: &str) -> bool {
+        let subscriptions = self.subscriptions.lock().unwrap_or_else(|e| e.into_inner());
+        subscriptions
+            .get(session)
+            .is_some_and(|uris| uris.contains(uri))
+    }
+
+    /// Announces that the resource at `uri` changed.
+    pub fn resource_updated(&self, uri: &str) {
+        // No open streams is not an error.
+        let _ = self
+            .updates
+            .send(Notification::ResourceUpdated(uri.to_string()));
+    }
+
+    /// Announces that `tools/list` now returns different tools.
+    pub fn tools_list_changed(&self) {
+        let _ = self.updates.send(Notification::ToolsListChanged);
+    }
+
+    /// Receives every notification from now on.
+    pub fn listen(&self) -> broadcast::Receiver<Notification> {
+        self.updates.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_subscriptions_are_per_session() {
+        let notifier = Notifier::default();
+        let mut updates = notifier.listen();
+        notifier.subscribe("a", "pcli2://folders/Root").unwrap();
+        assert!(notifier.is_subscribed("a", "pcli2://folders/Root"));
+        assert!(!notifier.is_subscribed("b", "pcli2://folders/Root"));
+        notifier.resource_updated("pcli2://folders/Root");
+        assert_eq!(
+            updates.recv().await.unwrap(),
+            Notification::ResourceUpdated("pcli2://folders/Root".to_string())
+        );
+        notifier.tools_list_changed();
+        assert_eq!(
+            updates.recv().await.unwrap(),
+            Notification::ToolsListChanged
+        );
+        notifier.unsubscribe("a", "pcli2://folders/Root");
+        assert!(!notifier.is_subscribed("a", "pcli2://folders/Root"));
+    }
+
+    #[test]
+    fn test_subscriptions_are_capped() {
+        let notifier = Notifier::default();
+        for index in 0..MAX_SUBSCRIPTIONS_PER_SESSION {
+            notifier
+                .subscribe("a", &format!("pcli2://folders/{}", index))
+                .unwrap();
+        }
+        assert!(notifier.subscribe("a", "pcli2://folders/more").is_err());
+        assert!(notifier.subscribe("a", "pcli2://folders/0").is_ok());
+    }
+}
@@ -0,0 +1,269 @@
+use crate::{
+    AppState,
+    access::{Route, admit},
+    config::{AuthScheme, ServerConfig},
+    flags::FEATURES,
+    mcp::server_info,
+};
+use axum::{
+    extract::State,
+    http::HeaderMap,
+    response::{IntoResponse, Json, Response},
+};
+use serde_json::{Map, Value, json};
+use utoipa::OpenApi;
+
+/// Where the document is served; it is readable like `/health`.
+pub const OPENAPI_PATH: &str = "/openapi.json";
+
+/// Security scheme names in the document, one per `access` scheme.
+fn scheme_name(scheme: AuthScheme) -> Option<&'static str> {
+    match scheme {
+        AuthScheme::None => None,
+        AuthScheme::ApiKey => Some("apiKey"),
+        AuthScheme::AdminToken => Some("adminToken"),
+        AuthScheme::Oauth => Some("oauth"),
+        AuthScheme::Mtls => Some("mutualTLS"),
+    }
+}
+
+fn security_scheme(scheme: AuthScheme) -> Value {
+    match scheme {
+        AuthScheme::Oauth => json!({
+            "type": "http",
+            "scheme": "bearer",
+            "bearerFormat": "JWT",
+            "description": "HS256 JWT checked against `access.oauth`."
+        }),
+        AuthScheme::Mtls => json!({
+            "type": "mutualTLS",
+            "description": "Client certificate verified by the TLS proxy in front of the server."
+        }),
+        AuthScheme::AdminToken => json!({
+            "type": "http",
+            "scheme": "bearer",
+            "description": "`admin.token`."
+        }),
+        _ => json!({
+            "type": "http",
+            "scheme": "bearer",
+            "description": "A key of `api_keys`."
+        }),
+    }
+}
+
+/// The security requirements of a route group: any one scheme of its chain.
+/// An unconfigured scheme admits nobody, so it is left out, and a chain with
+/// nothing configured is open.
+fn security(config: &ServerConfig, route: Route) -> Value {
+    let configured = |scheme: &AuthScheme| match scheme {
+        AuthScheme::None => true,
+        AuthScheme::ApiKey => !config.api_keys.is_empty(),
+        AuthScheme::AdminToken => config.admin.token.is_some(),
+        AuthScheme::Oauth => config.access.oauth.is_some(),
+        AuthScheme::Mtls => config.access.mtls.is_some(),
+    };
+    let requirements: Vec<Value> = config
+        .access
+        .chain(route.name())
+        .iter()
+        .filter(|scheme| configured(scheme))
+        .map(|scheme| match scheme_name(*scheme) {
+            Some(name) => json!({ name: [] }),
+            None => json!({}),
+        })
+        .collect();
+    if requirements.is_empty() {
+        json!([{}])
+    } else {
+        Value::Array(requirements)
+    }
+}
+
+/// The HTTP endpoints besides the MCP protocol itself, read from the
+/// `#[utoipa::path]` attributes of their handlers and the types they
+/// answer with.
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        crate::server::health,
+        crate::server::health_ready,
+        handle_openapi,
+        crate::admin::handle_credentials,
+        crate::admin::handle_reload,
+        crate::admin::handle_approvals,
+        crate::admin::handle_approval_decision,
+        crate::admin::handle_features,
+        crate::admin::handle_feature_update,
+        crate::admin::handle_feature_reset,
+        crate::admin::handle_usage,
+        crate::admin::handle_usage_month
+    ),
+    tags(
+        (name = "health", description = "`access.routes.health`"),
+        (name = "admin", description = "`access.routes.admin`")
+    )
+)]
+struct ApiDoc;
+
+/// Describes the HTTP endpoints besides the MCP protocol itself, with the
+/// authentication the current `access` configuration asks for and the
+/// server's identity.
+pub fn openapi_document(state: &AppState) -> Value {
+    let config = &state.config;
+    let mut document = serde_json::to_value(ApiDoc::openapi()).unwrap_or_else(|_| json!({}));
+    if let Some(paths) = document["paths"].as_object_mut() {
+        for (path, operations) in paths.iter_mut() {
+            let Some(operations) = operations.as_object_mut() else {
+                continue;
+            };
+            for operation in operations.values_mut() {
+                let route = if operation["tags"][0] == "admin" {
+                    Route::Admin
+                } else {
+                    Route::Health
+                };
+                operation["security"] = security(config, route);
+                operation["responses"]["401"] = json!({ "description": "Refused by the route's authentication chain; the body names why each scheme turned the request down." });
+                if path == "/admin/features/{name}" {
+                    operation["parameters"][0]["schema"]["enum"] = json!(FEATURES);
+                }
+            }
+        }
+    }
+
+    let mut schemes = Map::new();
+    for route in [Route::Mcp, Route::Admin, Route::Health] {
+        for scheme in config.access.chain(route.name()) {
+            if let Some(name) = scheme_name(*scheme) {
+                schemes.insert(name.to_string(), security_scheme(*scheme));
+            }
+        }
+    }
+    document["components"]["securitySchemes"] = Value::Object(schemes);
+
+    let identity = server_info(state);
+    document["info"] = json!({
+        "title": identity["title"].as_str().or(identity["name"].as_str()),
+        "version": identity["version"],
+        "x-implementation": { "name": state.server_name, "version": state.server_version },
+        "description": "Health and admin endpoints. MCP clients use `POST /mcp` (JSON-RPC), `GET /mcp` (event stream), or a WebSocket, described by the MCP specification rather than here."
+    });
+    document
+}
+
+/// `GET /openapi.json`, behind the `health` route's chain.
+#[utoipa::path(
+    get,
+    path = OPENAPI_PATH,
+    tag = "health",
+    summary = "This document",
+    responses((status = 200, description = "OpenAPI document", body = Object))
+)]
+pub async fn handle_openapi(State(mut state): State<AppState>, headers: HeaderMap) -> Response {
+    state.refresh_config();
+    if let Err(refusal) = admit(&state.config, Route::Health, &headers) {
+        return (refusal.status(), refusal.to_string()).into_response();
+    }
+    Json(openapi_document(&state)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{admin::CREDENTIAL_FIELDS, config::AdminConfig, flags::feature_status};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_security_follows_the_access_config() {
+        let open = openapi_document(&AppState::new("test", "0.0.0"));
+        assert_eq!(open["paths"]["/health"]["get"]["security"], json!([{}]));
+        assert_eq!(
+            open["paths"]["/admin/reload"]["post"]["security"],
+            json!([{}])
+        );
+
+        let state = AppState {
+            config: Arc::new(ServerConfig {
+                admin: AdminConfig {
+                    token: Some("admin-t0ken".to_string()),
+                    ..AdminConfig::default()
+                },
+                ..ServerConfig::default()
+            }),
+            ..AppState::new("test", "0.0.0")
+        };
+        let document = openapi_document(&state);
+        assert_eq!(
+            document["paths"]["/admin/approvals/{id}"]["post"]["security"],
+            json!([{ "adminToken": [] }])
+        );
+        assert_eq!(
+            document["components"]["securitySchemes"]["adminToken"]["scheme"],
+            "bearer"
+        );
+        assert_eq!(document["info"]["version"], "0.0.0");
+    }
+
+    /// Every `$ref` under `value`.
+    fn refs<'a>(value: &'a Value, found: &mut Vec<&'a str>) {
+        match value {
+            Value::Object(map) => {
+                if let Some(reference) = map.get("$ref").and_then(Value::as_str) {
+                    found.push(reference);
+                }
+                map.values().for_each(|value| refs(value, found));
+            }
+            Value::Array(values) => values.iter().for_each(|value| refs(value, found)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_schemas_come_from_the_handlers_types() {
+        let document = openapi_document(&AppState::new("test", "0.0.0"));
+        let schemas = &document["components"]["schemas"];
+        let mut found = Vec::new();
+        refs(&document, &mut found);
+        assert!(!found.is_empty());
+        for reference in found {
+            let name = reference
+                .strip_prefix("#/components/schemas/")
+                .expect("component ref");
+            assert!(schemas.get(name).is_some(), "{} is not defined", reference);
+        }
+
+        let credentials = schemas["CredentialsRequest"]["properties"]
+            .as_object()
+            .expect("properties");
+        let mut fields: Vec<&str> = credentials.keys().map(String::as_str).collect();
+        fields.sort_unstable();
+        let mut expected = CREDENTIAL_FIELDS.to_vec();
+        expected.sort_unstable();
+        assert_eq!(fields, expected);
+        assert_eq!(schemas["CredentialsRequest"]["additionalProperties"], false);
+
+        // What the handlers answer with is what the schemas describe.
+        let approval = schemas["Approval"]["properties"]
+            .as_object()
+            .expect("properties");
+        assert!(approval.contains_key("request_id"));
+        assert_eq!(
+            schemas["HealthState"]["enum"],
+            json!(["healthy", "degraded", "unavailable"])
+        );
+        let feature =
+            serde_json::to_value(feature_status(&AppState::new("test", "0.0.0"), FEATURES[0]))
+                .expect("feature");
+        let properties = schemas["Feature"]["properties"]
+            .as_object()
+            .expect("properties");
+        for key in feature.as_object().expect("feature").keys() {
+            assert!(properties.contains_key(key), "{}", key);
+        }
+        assert_eq!(
+            document["paths"]["/admin/features/{name}"]["post"]["parameters"][0]["schema"]["enum"],
+            json!(FEATURES)
+        );
+    }
+}
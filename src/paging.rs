@@ -0,0 +1,283 @@
+use crate::{content::summary_block, pcli::validate_range_u64, summary::SUMMARIZE_ARG};
+use serde_json::{Value, json};
+
+pub const LIMIT_ARG: &str = "limit";
+pub const OFFSET_ARG: &str = "offset";
+pub const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: u64 = 1000;
+/// Tools whose output rows can be paged with `limit` and `offset`.
+pub const PAGED_TOOLS: [&str; 4] = [
+    "pcli2",
+    "pcli2_folder_list",
+    "pcli2_asset_list",
+    "pcli2_asset_text_match",
+];
+/// Object fields holding the rows of a JSON output, in order of preference.
+const ROW_KEYS: [&str; 3] = ["items", "matches", "results"];
+
+/// Whether a call of `tool` asks for one page of its rows.
+pub fn wants_page(tool: &str, args: &Value) -> bool {
+    PAGED_TOOLS.contains(&tool) && (args.get(LIMIT_ARG).is_some() || args.get(OFFSET_ARG).is_some())
+}
+
+/// Checks the paging options and asks pcli2 for JSON unless CSV was asked
+/// for; a tree has no rows to page.
+pub fn prepare_page(args: &mut Value) -> Result<(), String> {
+    validate_range_u64(args, LIMIT_ARG, 1, MAX_LIMIT)?;
+    if args
+        .get(SUMMARIZE_ARG)
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+    {
+        return Err(format!(
+            "Invalid argument '{}': a summary is not paged; drop '{}' and '{}' or '{}'",
+            SUMMARIZE_ARG, LIMIT_ARG, OFFSET_ARG, SUMMARIZE_ARG
+        ));
+    }
+    match args.get("format").and_then(Value::as_str) {
+        Some("tree") => Err(format!(
+            "Invalid argument 'format': '{}' and '{}' need json or csv output",
+            LIMIT_ARG, OFFSET_ARG
+        )),
+        Some(_) => Ok(()),
+        None => {
+            if let Some(args) = args.as_object_mut() {
+                args.insert("format".to_string(), json!("json"));
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Where a page starts and ends among `total` rows, and the offset of the
+/// next page if there is one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Page {
+    offset: usize,
+    limit: usize,
+    total: usize,
+}
+
+impl Page {
+    fn from_args(args: &Value, total: usize) -> Self {
+        let get = |key: &str| args.get(key).and_then(Value::as_u64).map(|n| n as usize);
+        Page {
+            offset: get(OFFSET_ARG).unwrap_or(0),
+            limit: get(LIMIT_ARG).unwrap_or(DEFAULT_LIMIT),
+            total,
+        }
+    }
+
+    fn range(&self) -> std::ops::Range<usize> {
+        let start = self.offset.min(self.total);
+        start..start.saturating_add(self.limit).min(self.total)
+    }
+
+    fn next_offset(&self) -> Option<usize> {
+        let end = self.range().end;
+        (end < self.total).then_some(end)
+    }
+
+    fn to_json(self) -> Value {
+        json!({
+            "offset": self.offset,
+            "limit": self.limit,
+            "returned": self.range().len(),
+            "total": self.total,
+            "next_offset": self.next_offset()
+        })
+    }
+
+    fn headline(&self) -> String {
+        let range = self.range();
+        let shown = if range.is_empty() {
+            format!(
+                "No rows at offset {}; there are {}.",
+                self.offset, self.total
+            )
+        } else {
+            format!("Rows {}-{} of {}.", range.start + 1, range.end, self.total)
+        };
+        match self.next_offset() {
+            Some(next) => format!(
+                "{} Call again with '{}': {} for the next page.",
+                shown, OFFSET_ARG, next
+            ),
+            None => shown,
+        }
+    }
+}
+
+/// The rows of a JSON output: the output itself when it is an array, or
+/// the first array of `ROW_KEYS` in an object.
+pub(crate) fn json_rows(output: &mut Value) -> Option<&mut Vec<Value>> {
+    if output.is_array() {
+        return output.as_array_mut();
+    }
+    let key = ROW_KEYS
+        .iter()
+        .find(|key| output.get(**key).is_some_and(Value::is_array))?;
+    output.get_mut(*key).and_then(Value::as_array_mut)
+}
+
+/// Splits CSV text into records, keeping line breaks inside quoted fields.
+pub(crate) fn csv_records(text: &str) -> Vec<&str> {
+    let mut records = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (index, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '\n' if !quoted => {
+                records.push(text[start..index].trim_end_matches('\r'));
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    let rest = text[start..].trim_end_matches('\r');
+    if !rest.is_empty() {
+        records.push(rest);
+    }
+    records.retain(|record| !record.is_empty());
+    records
+}
+
+/// One page of `text`, or `None` when it is neither JSON with rows nor
+/// CSV.
+fn page_text(text: &str, args: &Value) -> Option<(String, Page)> {
+    if args.get("format").and_then(Value::as_str) == Some("csv") {
+        let records = csv_records(text);
+        let headers = args
+            .get("headers")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        let (header, rows) = match records.split_first() {
+            Some((header, rows)) if headers => (Some(*header), rows),
+            _ => (None, &records[..]),
+        };
+        let page = Page::from_args(args, rows.len());
+        let lines: Vec<&str> = header
+            .into_iter()
+            .chain(rows[page.range()].iter().copied())
+            .collect();
+        return Some((lines.join("\n"), page));
+    }
+    let mut output = serde_json::from_str::<Value>(text).ok()?;
+    let rows = json_rows(&mut output)?;
+    let page = Page::from_args(args, rows.len());
+    let kept: Vec<Value> = rows.drain(page.range()).collect();
+    *rows = kept;
+    let rendered = serde_json::to_string_pretty(&output).unwrap_or_else(|_| output.to_string());
+    Some((rendered, page))
+}
+
+/// Keeps one page of the rows in the last text block of `result`, with a
+/// note saying which rows they are and `_meta.page`. An output without
+/// rows is returned whole, with a note saying so.
+pub fn page_result(args: &Value, mut result: Value) -> Value {
+    let Some(blocks) = result.get_mut("content").and_then(Value::as_array_mut) else {
+        return result;
+    };
+    let Some(block) = blocks
+        .iter_mut()
+        .rev()
+        .find(|block| block["type"] == "text")
+    else {
+        return result;
+    };
+    let paged = block["text"]
+        .as_str()
+        .and_then(|text| page_text(text, args));
+    let Some((text, page)) = paged else {
+        blocks.insert(
+            0,
+            summary_block("The output has no rows to page; it is returned whole."),
+        );
+        return result;
+    };
+    block["text"] = json!(text);
+    blocks.insert(0, summary_block(page.headline()));
+    result["_meta"]["page"] = page.to_json();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::{raw_output_block, tool_result};
+
+    fn rows(count: usize) -> Value {
+        Value::Array((0..count).map(|index| json!({ "name": index })).collect())
+    }
+
+    #[test]
+    fn test_json_rows_are_paged() {
+        let args = json!({ "format": "json", "limit": 2, "offset": 3 });
+        let result = page_result(
+            &args,
+            tool_result(vec![raw_output_block(rows(6).to_string())]),
+        );
+        let page: Value =
+            serde_json::from_str(result["content"][1]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(page, json!([{ "name": 3 }, { "name": 4 }]));
+        assert_eq!(
+            result["_meta"]["page"],
+            json!({ "offset": 3, "limit": 2, "returned": 2, "total": 6, "next_offset": 5 })
+        );
+        assert!(
+            result["content"][0]["text"]
+                .as_str()
+                .unwrap()
+                .contains("Rows 4-5 of 6")
+        );
+
+        let matches = json!({ "matches": rows(3), "total": 1 }).to_string();
+        let args = json!({ "offset": 2 });
+        let result = page_result(&args, tool_result(vec![raw_output_block(matches)]));
+        let page: Value =
+            serde_json::from_str(result["content"][1]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(page, json!({ "matches": [{ "name": 2 }], "total": 1 }));
+        assert_eq!(result["_meta"]["page"]["next_offset"], Value::Null);
+
+        let past = page_result(
+            &json!({ "offset": 10 }),
+            tool_result(vec![raw_output_block(rows(3).to_string())]),
+        );
+        assert_eq!(past["_meta"]["page"]["returned"], 0);
+    }
+
+    #[test]
+    fn test_csv_rows_are_paged_below_the_header() {
+        let csv = "NAME,NOTE\na,\"two\nlines\"\nb,x\nc,y\n";
+        let args = json!({ "format": "csv", "headers": true, "limit": 1, "offset": 1 });
+        let result = page_result(&args, tool_result(vec![raw_output_block(csv)]));
+        assert_eq!(result["content"][1]["text"], "NAME,NOTE\nb,x");
+        assert_eq!(result["_meta"]["page"]["total"], 3);
+
+        let args = json!({ "format": "csv", "limit": 1 });
+        let result = page_result(&args, tool_result(vec![raw_output_block(csv)]));
+        assert_eq!(result["content"][1]["text"], "NAME,NOTE");
+        assert_eq!(result["_meta"]["page"]["total"], 4);
+    }
+
+    #[test]
+    fn test_paging_options_are_checked() {
+        let mut args = json!({ "limit": 5 });
+        prepare_page(&mut args).unwrap();
+        assert_eq!(args["format"], "json");
+        assert!(prepare_page(&mut json!({ "limit": 0 })).is_err());
+        assert!(prepare_page(&mut json!({ "limit": 5, "format": "tree" })).is_err());
+        assert!(prepare_page(&mut json!({ "offset": 5, "summarize": true })).is_err());
+        assert!(wants_page("pcli2_asset_list", &json!({ "offset": 0 })));
+        assert!(!wants_page("pcli2_asset_list", &json!({})));
+        assert!(!wants_page("pcli2_tenant_list", &json!({ "limit": 5 })));
+
+        let text = page_result(
+            &json!({ "limit": 1 }),
+            tool_result(vec![raw_output_block("done")]),
+        );
+        assert!(text.get("_meta").is_none());
+        assert_eq!(text["content"][1]["text"], "done");
+    }
+}
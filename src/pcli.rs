@@ -1,21 +1,187 @@
+use crate::{
+    AppState,
+    admin::{CREDENTIALS_TOOL, register_credentials},
+    approval::{APPROVAL_STATUS_TOOL, approval_status, hold},
+    archive::{
+        ARCHIVE_CONTENTS, ARCHIVE_FORMATS, ARCHIVE_TOOL, DEFAULT_ARCHIVE_LIMIT, MAX_ARCHIVE_LIMIT,
+        download_archive,
+    },
+    args::{DEFS_REF_PREFIX, prepare_arguments},
+    artifacts::{ARTIFACTS_LIST_TOOL, ARTIFACTS_PURGE_TOOL, list_artifacts, purge_artifacts},
+    audit::{AUDIT_TOOL, MAX_AUDIT_ENTRIES, audit_tail_result},
+    auth::Reauthenticator,
+    batch_get::{BATCH_GET_TOOL, MAX_BATCH_ROWS, batch_get},
+    breaker::{Admission, CircuitBreaker},
+    cache::{INVALIDATING_TOOLS, RELOAD_ARG, add_freshness},
+    cassette::Cassette,
+    config::{DEFAULT_TOOL_TIMEOUT, ExecutionPolicy},
+    content::{
+        AUDIENCE_ASSISTANT, AUDIENCE_USER, PRIORITY_ENCODED_DATA, PRIORITY_IMAGE, annotations,
+        raw_output_block, raw_output_result, summary_block, tool_result,
+    },
+    deadline::Deadline,
+    deptree::{
+        DEFAULT_DEPENDENCY_PAGE_SIZE, MAX_DEPENDENCY_DEPTH, MAX_DEPENDENCY_PAGE_SIZE,
+        folder_dependencies_view, wants_view,
+    },
+    export::{EXPORT_TOOL, EXPORT_UNITS, export_asset},
+    fair::FairSemaphore,
+    filter::{FILTER_ARG, filter_result, prepare_filter, wants_filter},
+    flags::{self, APPROVALS, STRUCTURED_OUTPUT},
+    gallery::{DEFAULT_GALLERY_LIMIT, GALLERY_TOOL, MAX_GALLERY_LIMIT, thumbnail_gallery},
+    graph::{
+        DEFAULT_TOP, GRAPH_EXPORTS, GRAPH_SEARCHES, MAX_TOP, SIMILARITY_GRAPH_TOOL,
+        similarity_graph,
+    },
+    health::{BackendHealth, shed_low_priority},
+    image::{IMAGE_FORMATS, IMAGE_MATCH_TOOL, visual_match_image},
+    index::{DEFAULT_SEARCH_LIMIT, INDEX_SEARCH_TOOL, MAX_SEARCH_LIMIT, search_index},
+    install::managed_pcli2,
+    instructions::last_text,
+    jobs::keep_job,
+    paging::{LIMIT_ARG, OFFSET_ARG, page_result, prepare_page, wants_page},
+    plan::{PLAN_TOOL, plan},
+    policy::{self, tool_visible},
+    projection::{FIELDS_ARG, SORT_BY_ARG, prepare_projection, project_result, wants_projection},
+    quota::{QUOTA_STATUS_TOOL, QuotaTracker},
+    redact::redactor,
+    reformat::{
+        RESPONSE_FORMAT_ARG, RESPONSE_FORMATS, prepare_response_format, reformat_result,
+        response_mime_type, wants_response_format,
+    },
+    report::{RENDER_ARG, RENDERS, render_markdown, wants_markdown},
+    resources::offload_large_text,
+    runner::{CommandOutput, CommandRequest, CommandRunner, LocalRunner},
+    sandbox::Sandbox,
+    search::{MULTI_TENANT_SEARCH_TOOL, run_multi_tenant_search},
+    snapshot::{DIFF_TOOL, SNAPSHOT_TOOL, diff_snapshots, take_snapshot},
+    split::{
+        SPLIT_CONCURRENCY_ARG, SPLIT_FOLDERS_ARG, run_folders_with_fallback,
+        run_split_folder_match, wants_split,
+    },
+    structured::{
+        attach_structured_content, drop_structured_content, drop_text_content, output_schema,
+    },
+    suggest::{DEFAULT_MIN_SCORE, FOLDER_SUGGEST_TOOL, MAX_SUGGESTIONS, suggest_folders},
+    summary::{SUMMARIZE_ARG, TOP_N_ARG, prepare_summary, summarize_result, wants_summary},
+    tenancy::{apply_tenant_defaults, bind_arguments, tool_allowed},
+    upload::{UPLOAD_TOOL, upload_asset},
+    usage::CallUsage,
+    workspace::Workspace,
+};
 use anyhow::Result;
 use base64::{Engine, engine::general_purpose::STANDARD as BASE64_STANDARD};
 use serde_json::{Map, Value, json};
 use std::{
+    collections::HashMap,
     env, fs,
-    path::PathBuf,
-    process::Stdio,
-    time::{SystemTime, UNIX_EPOCH},
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    sync::Semaphore,
+    task::JoinSet,
 };
-use tokio::io::{AsyncRead, AsyncReadExt};
-use tracing::info;
+use tracing::{Instrument, info, warn};
 
-pub const PCLI2_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30 * 60);
+pub const PCLI2_TIMEOUT: Duration = DEFAULT_TOOL_TIMEOUT;
+pub const RETRY_BACKOFF: Duration = Duration::from_millis(250);
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(30);
 pub const MAX_PCLI2_OUTPUT_BYTES: usize = 200 * 1024 * 1024;
 pub const PCLI2_BIN_ENV: &str = "PCLI2_BIN";
+pub const MAX_FAN_OUT_INPUTS: usize = 100;
+pub const DEFAULT_FAN_OUT_CONCURRENCY: usize = 4;
+pub const MAX_BATCH_STEPS: usize = 50;
+pub const GENERIC_TOOL: &str = "pcli2";
+/// Tool argument that picks one of the `cli_versions.binaries`.
+pub const CLI_VERSION_ARG: &str = "cli_version";
 
 type Props = Map<String, Value>;
 
+tokio::task_local! {
+    static EXECUTION: ExecutionContext;
+}
+
+#[derive(Clone, Default)]
+struct ExecutionContext {
+    policy: ExecutionPolicy,
+    breaker: Option<CircuitBreaker>,
+    health: Option<BackendHealth>,
+    reauth: Option<Reauthenticator>,
+    sandbox: Option<Sandbox>,
+    cassette: Option<Arc<Cassette>>,
+    runner: Option<Arc<dyn CommandRunner>>,
+    /// `pcli2` executable chosen through `cli_versions`.
+    pcli2: Option<String>,
+    /// The client's `timeoutMs`, shared by every run of the call.
+    deadline: Option<Deadline>,
+    /// The call's own working directory (`workspace` config).
+    workspace: Option<PathBuf>,
+    /// What the call's runs used, for usage accounting.
+    usage: Option<CallUsage>,
+}
+
+impl ExecutionContext {
+    /// The state's runner, or a local subprocess outside of tool calls.
+    fn runner(&self) -> Arc<dyn CommandRunner> {
+        self.runner.clone().unwrap_or_else(|| Arc::new(LocalRunner))
+    }
+}
+
+fn current_execution() -> ExecutionContext {
+    EXECUTION.try_with(Clone::clone).unwrap_or_default()
+}
+
+/// Counts a file `pcli2` downloaded toward the calling tool's usage.
+pub(crate) fn record_download(bytes: usize) {
+    if let Ok(Some(usage)) = EXECUTION.try_with(|execution| execution.usage.clone()) {
+        usage.record_download(bytes as u64);
+    }
+}
+
+/// Carries the calling tool's execution context, and its request's span,
+/// into a spawned task.
+pub(crate) fn in_current_execution<F: Future>(fut: F) -> impl Future<Output = F::Output> {
+    EXECUTION.scope(current_execution(), fut).in_current_span()
+}
+
+/// Key of the slots every tool shares (`execution.max_concurrent_calls`).
+const ALL_TOOLS: &str = "*";
+
+#[derive(Clone, Default)]
+pub struct ConcurrencyLimits {
+    semaphores: Arc<Mutex<HashMap<String, Arc<FairSemaphore>>>>,
+}
+
+impl ConcurrencyLimits {
+    fn semaphore(&self, tool: &str, permits: usize) -> Arc<FairSemaphore> {
+        let mut semaphores = self.semaphores.lock().unwrap_or_else(|e| e.into_inner());
+        // Keyed by the limit too, so a reloaded limit takes effect for new calls.
+        semaphores
+            .entry(format!("{}/{}", tool, permits))
+            .or_insert_with(|| FairSemaphore::new(permits))
+            .clone()
+    }
+}
+
+struct ToolExample {
+    description: &'static str,
+    arguments: Value,
+}
+
+fn example(description: &'static str, arguments: Value) -> ToolExample {
+    ToolExample {
+        description,
+        arguments,
+    }
+}
+
 fn push_tool(
     tools: &mut Vec<Value>,
     name: &str,
@@ -23,15 +189,255 @@ fn push_tool(
     properties: Props,
     required: &[&str],
 ) {
-    tools.push(json!({
+    let examples = tool_examples(name);
+    let mut tool = json!({
         "name": name,
-        "description": description,
+        "description": describe_with_examples(description, &examples),
         "inputSchema": {
             "type": "object",
-            "properties": properties,
-            "required": required
+            "properties": &properties,
+            "required": required,
+            "additionalProperties": false
         }
-    }));
+    });
+    let defs = referenced_defs(&properties);
+    if !defs.is_empty() {
+        tool["inputSchema"]["$defs"] = Value::Object(defs);
+    }
+    if let Some(schema) = output_schema(name) {
+        tool["outputSchema"] = schema;
+    }
+    if !examples.is_empty() {
+        let examples: Vec<Value> = examples
+            .into_iter()
+            .map(|example| {
+                json!({
+                    "description": example.description,
+                    "arguments": example.arguments
+                })
+            })
+            .collect();
+        tool["_meta"] = json!({ "examples": examples });
+    }
+    tools.push(tool);
+}
+
+fn describe_with_examples(description: &str, examples: &[ToolExample]) -> String {
+    if examples.is_empty() {
+        return description.to_string();
+    }
+    let mut out = format!("{}\n\nExamples:", description);
+    for example in examples {
+        out.push_str(&format!(
+            "\n- {}: {}",
+            example.description, example.arguments
+        ));
+    }
+    out
+}
+
+fn tool_examples(name: &str) -> Vec<ToolExample> {
+    match name {
+        "pcli2_folder_list" => vec![
+            example(
+                "List the subfolders of a folder as JSON",
+                json!({ "folder_path": "/Root/Castings", "format": "json" }),
+            ),
+            example(
+                "Show the whole folder hierarchy as a tree",
+                json!({ "format": "tree" }),
+            ),
+        ],
+        PLAN_TOOL => vec![example(
+            "Plan a similarity search for a part known only by name",
+            json!({ "request": "find parts similar to bracket X in the castings folder" }),
+        )],
+        FOLDER_SUGGEST_TOOL => vec![example(
+            "Find the folder an agent misspelled",
+            json!({ "query": "Castngs/Brakets", "limit": 5 }),
+        )],
+        "pcli2_asset_list" => vec![example(
+            "List the assets in a folder as CSV with a header row",
+            json!({ "folder_path": "/Root/Castings", "format": "csv", "headers": true }),
+        )],
+        "pcli2_tenant_use" => vec![example(
+            "Switch the active tenant",
+            json!({ "name": "acme" }),
+        )],
+        "pcli2_tenant_state" => vec![example(
+            "List assets that failed processing",
+            json!({ "type": "failed", "format": "csv", "headers": true }),
+        )],
+        "pcli2_folder_get" => vec![example(
+            "Get a folder by path, including metadata",
+            json!({ "folder_path": "/Root/Castings", "metadata": true, "format": "json" }),
+        )],
+        "pcli2_folder_resolve" => vec![example(
+            "Resolve a folder path to its UUID",
+            json!({ "folder_path": "/Root/Castings" }),
+        )],
+        "pcli2_folder_dependencies" => vec![
+            example(
+                "Show the assembly trees of every asset in a folder",
+                json!({ "folder_path": "/Root/Assemblies", "format": "tree" }),
+            ),
+            example(
+                "Walk a deep assembly two levels at a time",
+                json!({ "folder_path": "/Root/Assemblies", "max_depth": 2, "root": "/Root/Assemblies/Gearbox.asm" }),
+            ),
+        ],
+        "pcli2_folder_geometric_match" | "pcli2_folder_part_match" => vec![
+            example(
+                "Find near-duplicates within one folder",
+                json!({ "folder_path": "/Root/Castings", "threshold": 95.0, "exclusive": true, "format": "csv", "headers": true }),
+            ),
+            example(
+                "Compare two folders against the whole tenant",
+                json!({ "folder_path": ["/Root/Castings", "/Root/Forgings"], "threshold": 85.0, "format": "json" }),
+            ),
+            example(
+                "Match three folders in parallel runs and merge the results",
+                json!({ "folder_path": ["/Root/Castings", "/Root/Forgings", "/Root/Stampings"], "threshold": 90.0, "split_folders": true, "split_concurrency": 3 }),
+            ),
+            example(
+                "Summarize a large folder's matches, keeping the best three per asset",
+                json!({ "folder_path": "/Root/Castings", "threshold": 80.0, "summarize": true, "top_n": 3 }),
+            ),
+        ],
+        SNAPSHOT_TOOL => vec![example(
+            "Snapshot a folder before this week's data load",
+            json!({ "name": "castings-2026-10-12", "folder_path": "/Root/Castings" }),
+        )],
+        DIFF_TOOL => vec![
+            example(
+                "Compare last week's snapshot with the folder as it is now",
+                json!({ "from": "castings-2026-10-12" }),
+            ),
+            example(
+                "Compare two snapshots",
+                json!({ "from": "castings-2026-10-05", "to": "castings-2026-10-12" }),
+            ),
+        ],
+        "pcli2_folder_visual_match" => vec![example(
+            "Find visually similar assets within one folder",
+            json!({ "folder_path": "/Root/Castings", "exclusive": true, "format": "json" }),
+        )],
+        "pcli2_asset_get" => vec![example(
+            "Get an asset by path, including metadata",
+            json!({ "path": "/Root/Castings/Bracket.stl", "metadata": true, "format": "json" }),
+        )],
+        BATCH_GET_TOOL => vec![
+            example(
+                "Look up the assets of a pasted spreadsheet column",
+                json!({ "input": "Part\tAsset path\nBracket\t/Root/Castings/Bracket.stl\nGear\t/Root/Castings/Gear.stl", "column": "Asset path" }),
+            ),
+            example(
+                "Check which of a list of UUIDs still exist, with their metadata",
+                json!({ "input": "[\"5f1c0c1e-1111-4a4a-9b9b-000000000001\", \"5f1c0c1e-1111-4a4a-9b9b-000000000002\"]", "metadata": true }),
+            ),
+        ],
+        "pcli2_asset_dependencies" => vec![example(
+            "Show the components of an assembly as a tree",
+            json!({ "path": "/Root/Assemblies/Gearbox.asm", "format": "tree" }),
+        )],
+        "pcli2_asset_thumbnail" => vec![example(
+            "Fetch the thumbnail of an asset",
+            json!({ "path": "/Root/Castings/Bracket.stl" }),
+        )],
+        EXPORT_TOOL => vec![
+            example(
+                "Download an asset's original file",
+                json!({ "path": "/Root/Castings/Bracket.stl" }),
+            ),
+            example(
+                "Export an asset as STEP in millimetres (when the server's pcli2 can convert)",
+                json!({ "path": "/Root/Castings/Bracket.stl", "target_format": "step", "units": "mm" }),
+            ),
+        ],
+        GALLERY_TOOL => vec![
+            example(
+                "Show the first thumbnails of a folder",
+                json!({ "folder_path": "/Root/Castings", "limit": 8 }),
+            ),
+            example(
+                "Show the next page",
+                json!({ "folder_path": "/Root/Castings", "limit": 8, "offset": 8 }),
+            ),
+        ],
+        ARCHIVE_TOOL => vec![
+            example(
+                "Download every file of a folder as one zip",
+                json!({ "folder_path": "/Root/Castings" }),
+            ),
+            example(
+                "Pack the thumbnails of a large folder, compressed",
+                json!({ "folder_path": "/Root/Castings", "content": "thumbnails", "archive": "tar.zst", "limit": 500 }),
+            ),
+        ],
+        "pcli2_geometric_match" | "pcli2_asset_part_match" => vec![
+            example(
+                "Find geometrically similar assets above 90%",
+                json!({ "path": "/Root/Castings/Bracket.stl", "threshold": 90.0, "format": "json" }),
+            ),
+            example(
+                "Match a shortlist of assets in one call",
+                json!({ "uuid": ["5f1c0c1e-1111-4a4a-9b9b-000000000001", "5f1c0c1e-1111-4a4a-9b9b-000000000002"], "threshold": 85.0, "format": "json" }),
+            ),
+        ],
+        "pcli2_asset_visual_match" => vec![example(
+            "Find visually similar assets",
+            json!({ "path": "/Root/Castings/Bracket.stl", "format": "json" }),
+        )],
+        IMAGE_MATCH_TOOL => vec![example(
+            "Find assets that look like a photo of a part",
+            json!({ "image": "data:image/png;base64,iVBORw0KGgo...", "tenant": "acme" }),
+        )],
+        "pcli2_asset_text_match" => vec![example(
+            "Fuzzy search assets by name or metadata text",
+            json!({ "text": "bracket", "fuzzy": true, "format": "json" }),
+        )],
+        MULTI_TENANT_SEARCH_TOOL => vec![
+            example(
+                "Search three tenants for a part number",
+                json!({ "tenants": ["east", "west", "north"], "text": "BRK-1042", "fuzzy": true }),
+            ),
+            example(
+                "Find an asset's geometric matches in every configured tenant",
+                json!({ "path": "/Root/Castings/Bracket.stl", "threshold": 85.0 }),
+            ),
+        ],
+        INDEX_SEARCH_TOOL => vec![example(
+            "Look up castings by supplier without calling pcli2",
+            json!({ "query": "bracket acme", "folder_path": "/Root/Castings", "limit": 10 }),
+        )],
+        SIMILARITY_GRAPH_TOOL => vec![
+            example(
+                "Find groups of near-duplicate castings to consolidate",
+                json!({ "folder_path": "/Root/Castings", "threshold": 95.0, "top": 5 }),
+            ),
+            example(
+                "Export the part similarity graph of two folders for Gephi",
+                json!({ "folder_path": ["/Root/Castings", "/Root/Forgings"], "search": "part", "threshold": 85.0, "export": "graphml" }),
+            ),
+        ],
+        "pcli2_asset_metadata_create" => vec![example(
+            "Set a numeric metadata property on an asset",
+            json!({ "path": "/Root/Castings/Bracket.stl", "name": "Weight", "value": "1.25", "type": "number" }),
+        )],
+        UPLOAD_TOOL => vec![example(
+            "Upload a STEP file the assistant was given to the castings folder",
+            json!({ "folder_path": "/Root/Castings", "name": "Bracket.step", "content": "SVNPLTEwMzAzLTIxOw==" }),
+        )],
+        "pcli2_asset_metadata_delete" => vec![example(
+            "Remove two metadata properties from an asset",
+            json!({ "path": "/Root/Castings/Bracket.stl", "name": ["Weight", "Supplier"] }),
+        )],
+        CREDENTIALS_TOOL => vec![example(
+            "Rotate the API client credentials for the production environment",
+            json!({ "environment": "production", "client_id": "mcp-agent", "client_secret": "<secret>" }),
+        )],
+        _ => Vec::new(),
+    }
 }
 
 fn define_tool<F>(
@@ -52,46 +458,156 @@ fn add_prop(props: &mut Props, key: &str, value: Value) {
     props.insert(key.to_string(), value);
 }
 
-fn add_tenant(props: &mut Props) {
+fn add_ref(props: &mut Props, key: &str, def: &str) {
     add_prop(
         props,
-        "tenant",
-        json!({ "type": "string", "description": "Tenant ID or alias." }),
+        key,
+        json!({ "$ref": format!("{}{}", DEFS_REF_PREFIX, def) }),
     );
 }
 
+fn shared_def(name: &str) -> Option<Value> {
+    let def = match name {
+        "tenant" => json!({ "type": "string", "description": "Tenant ID or alias." }),
+        "headers" => json!({ "type": "boolean", "description": "Include headers in output." }),
+        "pretty" => json!({ "type": "boolean", "description": "Pretty output." }),
+        "metadata" => json!({ "type": "boolean", "description": "Include metadata in output." }),
+        "format_json_csv" => {
+            json!({ "type": "string", "enum": ["json", "csv"], "description": "Output format." })
+        }
+        "format_json_csv_tree" => json!({
+            "type": "string",
+            "enum": ["json", "csv", "tree"],
+            "description": "Output format."
+        }),
+        "summarize" => json!({
+            "type": "boolean",
+            "description": "Return a compact summary (best matches per source asset, score distribution, counts above 80/90/95/99) instead of every match. The full output is stored as a linked resource."
+        }),
+        "top_n" => json!({
+            "type": "integer",
+            "description": "Matches kept per source asset when 'summarize' is set (1-100). Default 5."
+        }),
+        "render" => json!({
+            "type": "string",
+            "enum": RENDERS,
+            "description": "How the report is returned: json (default) or markdown, a formatted block with headings and tables for people to read."
+        }),
+        "limit" => json!({
+            "type": "integer",
+            "description": "Rows returned, counted from 'offset' (1-1000). Default 100 when 'offset' is given. The result says how many rows there are and where the next page starts."
+        }),
+        "offset" => json!({
+            "type": "integer",
+            "minimum": 0,
+            "description": "Rows skipped before the returned page. Default 0."
+        }),
+        "sort_by" => json!({
+            "oneOf": [
+                { "type": "string" },
+                { "type": "array", "items": { "type": "string" } }
+            ],
+            "description": "Column(s) to sort the rows by, e.g. -score,name. A leading '-' sorts that column in descending order; rows without a value come last."
+        }),
+        "filter" => json!({
+            "type": "string",
+            "description": "Keep only the rows matching an expression, e.g. score >= 90 && path ends_with \".stl\". Compare a column with ==, !=, >, >=, <, <=, contains, starts_with, or ends_with and a quoted string, number, true, false, or null; join comparisons with && (and), || (or), ! (not), and parentheses. Numbers compare as numbers, text ignoring case; nested columns are named with dots."
+        }),
+        "response_format" => json!({
+            "type": "string",
+            "enum": RESPONSE_FORMATS,
+            "description": "Format the server converts the rows to, whichever format pcli2 wrote them in: json, csv, or markdown_table."
+        }),
+        "fields" => json!({
+            "oneOf": [
+                { "type": "string" },
+                { "type": "array", "items": { "type": "string" } }
+            ],
+            "description": "Columns to keep in each row, e.g. name,path. CSV output keeps them in this order; the result names any column the output does not have."
+        }),
+        "folder_uuid" => json!({ "type": "string", "description": "Folder UUID." }),
+        "folder_path" => json!({
+            "type": "string",
+            "description": "Folder path, e.g. /Root/Child/Grandchild."
+        }),
+        "folder_path_list" => json!({
+            "oneOf": [
+                { "type": "string" },
+                { "type": "array", "items": { "type": "string" } }
+            ],
+            "description": "Folder path(s) to process."
+        }),
+        _ => return None,
+    };
+    Some(def)
+}
+
+fn referenced_defs(properties: &Props) -> Props {
+    let mut defs = Props::new();
+    for prop in properties.values() {
+        if let Some(name) = prop
+            .get("$ref")
+            .and_then(|v| v.as_str())
+            .and_then(|r| r.strip_prefix(DEFS_REF_PREFIX))
+            && let Some(def) = shared_def(name)
+        {
+            defs.insert(name.to_string(), def);
+        }
+    }
+    defs
+}
+
+fn add_tenant(props: &mut Props) {
+    add_ref(props, "tenant", "tenant");
+}
+
 fn add_headers(props: &mut Props) {
-    add_prop(
-        props,
-        "headers",
-        json!({ "type": "boolean", "description": "Include headers in output." }),
-    );
+    add_ref(props, "headers", "headers");
 }
 
 fn add_pretty(props: &mut Props) {
-    add_prop(
-        props,
-        "pretty",
-        json!({ "type": "boolean", "description": "Pretty output." }),
-    );
+    add_ref(props, "pretty", "pretty");
 }
 
 fn add_metadata(props: &mut Props) {
+    add_ref(props, "metadata", "metadata");
+}
+
+fn add_summarize(props: &mut Props) {
+    add_ref(props, SUMMARIZE_ARG, "summarize");
+    add_ref(props, TOP_N_ARG, "top_n");
+    add_ref(props, RENDER_ARG, "render");
+}
+
+fn add_paging(props: &mut Props) {
+    add_ref(props, LIMIT_ARG, "limit");
+    add_ref(props, OFFSET_ARG, "offset");
+}
+
+fn add_row_options(props: &mut Props) {
+    add_ref(props, FILTER_ARG, "filter");
+    add_ref(props, SORT_BY_ARG, "sort_by");
+    add_ref(props, FIELDS_ARG, "fields");
+    add_ref(props, RESPONSE_FORMAT_ARG, "response_format");
+}
+
+fn add_split(props: &mut Props) {
     add_prop(
         props,
-        "metadata",
-        json!({ "type": "boolean", "description": "Include metadata in output." }),
+        SPLIT_FOLDERS_ARG,
+        json!({ "type": "boolean", "description": "Match each folder of a folder_path list in its own pcli2 run, in parallel, and merge the matches without duplicates (JSON only)." }),
     );
-}
-
-fn add_format(props: &mut Props, values: &[&str]) {
     add_prop(
         props,
-        "format",
-        json!({ "type": "string", "enum": values, "description": "Output format." }),
+        SPLIT_CONCURRENCY_ARG,
+        json!({ "type": "integer", "description": "With split_folders, how many folder runs go at once (1-10, default 4)." }),
     );
 }
 
+fn add_format(props: &mut Props, values: &[&str]) {
+    add_ref(props, "format", &format!("format_{}", values.join("_")));
+}
+
 fn add_uuid_path(props: &mut Props) {
     add_prop(
         props,
@@ -105,33 +621,41 @@ fn add_uuid_path(props: &mut Props) {
     );
 }
 
-fn add_folder_uuid_path(props: &mut Props) {
+/// `uuid` and `path` that also take arrays, to `action` each asset in turn.
+fn add_uuid_path_list(props: &mut Props, action: &str) {
     add_prop(
         props,
-        "folder_uuid",
-        json!({ "type": "string", "description": "Folder UUID." }),
-    );
-    add_prop(
-        props,
-        "folder_path",
-        json!({ "type": "string", "description": "Folder path, e.g. /Root/Child/Grandchild." }),
+        "uuid",
+        json!({
+            "oneOf": [
+                { "type": "string" },
+                { "type": "array", "items": { "type": "string" } }
+            ],
+            "description": format!("Asset UUID, or an array of UUIDs to {} each in turn.", action)
+        }),
     );
-}
-
-fn add_folder_path_list(props: &mut Props) {
     add_prop(
         props,
-        "folder_path",
+        "path",
         json!({
             "oneOf": [
                 { "type": "string" },
                 { "type": "array", "items": { "type": "string" } }
             ],
-            "description": "Folder path(s) to process."
+            "description": format!("Asset path, e.g. /Root/Folder/Asset.stl, or an array of paths to {} each in turn.", action)
         }),
     );
 }
 
+fn add_folder_uuid_path(props: &mut Props) {
+    add_ref(props, "folder_uuid", "folder_uuid");
+    add_ref(props, "folder_path", "folder_path");
+}
+
+fn add_folder_path_list(props: &mut Props) {
+    add_ref(props, "folder_path", "folder_path_list");
+}
+
 fn add_threshold(props: &mut Props) {
     add_prop(
         props,
@@ -212,13 +736,46 @@ fn add_metadata_name(props: &mut Props) {
     );
 }
 
+fn add_cache_reload(props: &mut Props) {
+    add_prop(
+        props,
+        RELOAD_ARG,
+        json!({ "type": "boolean", "description": "Skip the server's result cache and run pcli2." }),
+    );
+}
+
+fn add_list_options(props: &mut Props) {
+    add_tenant(props);
+    add_metadata(props);
+    add_headers(props);
+    add_pretty(props);
+    add_format(props, &["json", "csv", "tree"]);
+    add_prop(
+        props,
+        "folder_uuid",
+        json!({ "type": "string", "description": "Folder UUID." }),
+    );
+    add_prop(
+        props,
+        "folder_path",
+        json!({ "type": "string", "description": "Folder path, e.g. /Root/Child." }),
+    );
+    add_prop(
+        props,
+        "reload",
+        json!({ "type": "boolean", "description": "Reload folder cache from server." }),
+    );
+    add_paging(props);
+    add_row_options(props);
+}
+
 pub fn tool_list() -> Vec<Value> {
     let mut tools = Vec::new();
 
     define_tool(
         &mut tools,
-        "pcli2",
-        "Physna Command Line Interface v2 (PCLI2). Runs `pcli2 folder list` or `pcli2 asset list` with the provided options.",
+        GENERIC_TOOL,
+        "Deprecated: use the explicit folder list and asset list tools instead. Physna Command Line Interface v2 (PCLI2). Runs `pcli2 folder list` or `pcli2 asset list` with the provided options.",
         &[],
         |props| {
             add_prop(
@@ -226,20 +783,39 @@ pub fn tool_list() -> Vec<Value> {
                 "resource",
                 json!({ "type": "string", "enum": ["folder", "asset"], "description": "Resource to list. Defaults to folder." }),
             );
+            add_list_options(props);
+        },
+    );
+
+    define_tool(
+        &mut tools,
+        "pcli2_folder_list",
+        "Runs `pcli2 folder list`.",
+        &[],
+        add_list_options,
+    );
+
+    define_tool(
+        &mut tools,
+        FOLDER_SUGGEST_TOOL,
+        "Suggests folder paths for a partial or misspelled folder name, ranked by fuzzy similarity against the cached folder tree (`pcli2 folder list`). Use it when a folder path is not found.",
+        &["query"],
+        |props| {
             add_tenant(props);
-            add_metadata(props);
-            add_headers(props);
-            add_pretty(props);
-            add_format(props, &["json", "csv", "tree"]);
             add_prop(
                 props,
-                "folder_uuid",
-                json!({ "type": "string", "description": "Folder UUID." }),
+                "query",
+                json!({ "type": "string", "description": "Folder name or partial path to look for, e.g. castngs or Castings/Brakets." }),
             );
             add_prop(
                 props,
-                "folder_path",
-                json!({ "type": "string", "description": "Folder path, e.g. /Root/Child." }),
+                "limit",
+                json!({ "type": "integer", "description": format!("Maximum number of suggestions (1-{}). Default 10.", MAX_SUGGESTIONS) }),
+            );
+            add_prop(
+                props,
+                "min_score",
+                json!({ "type": "number", "description": format!("Minimum similarity score (0 to 100). Default {}.", DEFAULT_MIN_SCORE) }),
             );
             add_prop(
                 props,
@@ -249,15 +825,25 @@ pub fn tool_list() -> Vec<Value> {
         },
     );
 
+    define_tool(
+        &mut tools,
+        "pcli2_asset_list",
+        "Runs `pcli2 asset list`.",
+        &[],
+        add_list_options,
+    );
+
     define_tool(
         &mut tools,
         "pcli2_tenant_list",
         "Runs `pcli2 tenant list`.",
         &[],
         |props| {
+            add_cache_reload(props);
             add_headers(props);
             add_pretty(props);
             add_format(props, &["json", "csv"]);
+            add_row_options(props);
         },
     );
 
@@ -415,13 +1001,14 @@ pub fn tool_list() -> Vec<Value> {
                 "folder_path",
                 json!({ "type": "string", "description": "Folder path, e.g. /Root/Child/Grandchild." }),
             );
+            add_cache_reload(props);
         },
     );
 
     define_tool(
         &mut tools,
         "pcli2_folder_dependencies",
-        "Runs `pcli2 folder dependencies`.",
+        "Runs `pcli2 folder dependencies`. For deep assemblies pass `max_depth`, `page`, or `root`: the server then returns the JSON trees a page of top-level assets at a time, cut at `max_depth`, and keeps each folder's full tree as a `pcli2://dependencies/...` resource so later pages and `root` calls do not rerun `pcli2`.",
         &["folder_path"],
         |props| {
             add_tenant(props);
@@ -431,6 +1018,31 @@ pub fn tool_list() -> Vec<Value> {
             add_pretty(props);
             add_format(props, &["json", "csv", "tree"]);
             add_progress(props);
+            add_prop(
+                props,
+                "max_depth",
+                json!({ "type": "integer", "description": format!("Levels of each tree to return (1-{}); deeper nodes are replaced by a truncated_children count.", MAX_DEPENDENCY_DEPTH) }),
+            );
+            add_prop(
+                props,
+                "page",
+                json!({ "type": "integer", "description": "Page of top-level assets to return, from 1. Default 1." }),
+            );
+            add_prop(
+                props,
+                "page_size",
+                json!({ "type": "integer", "description": format!("Top-level assets per page (1-{}). Default {}.", MAX_DEPENDENCY_PAGE_SIZE, DEFAULT_DEPENDENCY_PAGE_SIZE) }),
+            );
+            add_prop(
+                props,
+                "root",
+                json!({ "type": "string", "description": "Path or UUID of an asset in the tree; returns its dependencies instead of the folder's assets." }),
+            );
+            add_prop(
+                props,
+                "reload",
+                json!({ "type": "boolean", "description": "Rerun pcli2 instead of using the stored tree." }),
+            );
         },
     );
 
@@ -450,6 +1062,9 @@ pub fn tool_list() -> Vec<Value> {
             add_format(props, &["json", "csv"]);
             add_concurrent(props);
             add_progress(props);
+            add_summarize(props);
+            add_row_options(props);
+            add_split(props);
         },
     );
 
@@ -469,6 +1084,9 @@ pub fn tool_list() -> Vec<Value> {
             add_format(props, &["json", "csv"]);
             add_concurrent(props);
             add_progress(props);
+            add_summarize(props);
+            add_row_options(props);
+            add_split(props);
         },
     );
 
@@ -487,6 +1105,9 @@ pub fn tool_list() -> Vec<Value> {
             add_format(props, &["json", "csv"]);
             add_concurrent(props);
             add_progress(props);
+            add_summarize(props);
+            add_row_options(props);
+            add_split(props);
         },
     );
 
@@ -502,6 +1123,29 @@ pub fn tool_list() -> Vec<Value> {
             add_metadata(props);
             add_pretty(props);
             add_format(props, &["json", "csv"]);
+            add_cache_reload(props);
+        },
+    );
+
+    define_tool(
+        &mut tools,
+        BATCH_GET_TOOL,
+        "Looks up many assets at once from a CSV or JSON list of UUIDs or paths, such as a column pasted from a spreadsheet, running `pcli2 asset get` for each distinct asset. Returns one row per input row, in input order, with the asset or why it was not found.",
+        &["input"],
+        |props| {
+            add_tenant(props);
+            add_prop(
+                props,
+                "input",
+                json!({ "type": "string", "description": format!("CSV (comma, semicolon, or tab separated, optionally with a header row), one UUID or path per line, or a JSON array of UUIDs, paths, or objects. At most {} rows.", MAX_BATCH_ROWS) }),
+            );
+            add_prop(
+                props,
+                "column",
+                json!({ "type": "string", "description": "Header of the CSV column, or key of the JSON objects, holding the UUID or path. Default: a column named uuid, id, or path, else the first cell that is a UUID or a path." }),
+            );
+            add_metadata(props);
+            add_concurrent(props);
         },
     );
 
@@ -531,6 +1175,81 @@ pub fn tool_list() -> Vec<Value> {
         },
     );
 
+    define_tool(
+        &mut tools,
+        EXPORT_TOOL,
+        "Runs `pcli2 asset download` and returns the asset's file as a resource to read with resources/read, for CAM, simulation, or other tools. `target_format` and `units` convert the file when the server's pcli2 supports it.",
+        &[],
+        |props| {
+            add_tenant(props);
+            add_uuid_path(props);
+            add_prop(
+                props,
+                "target_format",
+                json!({ "type": "string", "description": "Target file format as an extension, e.g. step, stl, obj. Omit to get the original file." }),
+            );
+            add_prop(
+                props,
+                "units",
+                json!({ "type": "string", "enum": EXPORT_UNITS, "description": "Length units of the converted file." }),
+            );
+        },
+    );
+
+    define_tool(
+        &mut tools,
+        GALLERY_TOOL,
+        "Downloads the thumbnails of the assets in a folder (`pcli2 asset list`, then `pcli2 asset thumbnail` per asset) and returns them as image blocks, each after a caption with the asset's name and UUID, to scan a folder visually in one call.",
+        &[],
+        |props| {
+            add_tenant(props);
+            add_folder_uuid_path(props);
+            add_prop(
+                props,
+                "limit",
+                json!({ "type": "integer", "description": format!("Maximum number of thumbnails (1-{}). Default {}.", MAX_GALLERY_LIMIT, DEFAULT_GALLERY_LIMIT) }),
+            );
+            add_prop(
+                props,
+                "offset",
+                json!({ "type": "integer", "description": "Number of assets to skip, for the next page of a large folder. Default 0." }),
+            );
+            add_concurrent(props);
+        },
+    );
+
+    define_tool(
+        &mut tools,
+        ARCHIVE_TOOL,
+        "Downloads the files of the assets in a folder (`pcli2 asset list`, then `pcli2 asset download` per asset), or their thumbnails, and packs them with a `manifest.json` listing every asset's file, size, and SHA-256 into one zip or tar.zst archive, returned as a resource to read with resources/read.",
+        &[],
+        |props| {
+            add_tenant(props);
+            add_folder_uuid_path(props);
+            add_prop(
+                props,
+                "content",
+                json!({ "type": "string", "enum": ARCHIVE_CONTENTS, "description": "What to archive: the assets' files (default) or their PNG thumbnails." }),
+            );
+            add_prop(
+                props,
+                "archive",
+                json!({ "type": "string", "enum": ARCHIVE_FORMATS, "description": "Archive format: zip (default, uncompressed) or tar.zst (compressed)." }),
+            );
+            add_prop(
+                props,
+                "limit",
+                json!({ "type": "integer", "description": format!("Maximum number of assets (1-{}). Default {}.", MAX_ARCHIVE_LIMIT, DEFAULT_ARCHIVE_LIMIT) }),
+            );
+            add_prop(
+                props,
+                "offset",
+                json!({ "type": "integer", "description": "Number of assets to skip, for the next part of a large folder. Default 0." }),
+            );
+            add_concurrent(props);
+        },
+    );
+
     define_tool(
         &mut tools,
         "pcli2_asset_reprocess",
@@ -549,12 +1268,15 @@ pub fn tool_list() -> Vec<Value> {
         &[],
         |props| {
             add_tenant(props);
-            add_uuid_path(props);
+            add_uuid_path_list(props, "match");
             add_threshold(props);
             add_headers(props);
             add_metadata(props);
             add_pretty(props);
             add_format(props, &["json", "csv"]);
+            add_concurrent(props);
+            add_summarize(props);
+            add_row_options(props);
         },
     );
 
@@ -565,12 +1287,15 @@ pub fn tool_list() -> Vec<Value> {
         &[],
         |props| {
             add_tenant(props);
-            add_uuid_path(props);
+            add_uuid_path_list(props, "match");
             add_threshold(props);
             add_headers(props);
             add_metadata(props);
             add_pretty(props);
             add_format(props, &["json", "csv"]);
+            add_concurrent(props);
+            add_summarize(props);
+            add_row_options(props);
         },
     );
 
@@ -581,11 +1306,34 @@ pub fn tool_list() -> Vec<Value> {
         &[],
         |props| {
             add_tenant(props);
-            add_uuid_path(props);
+            add_uuid_path_list(props, "match");
             add_headers(props);
             add_metadata(props);
             add_pretty(props);
             add_format(props, &["json", "csv"]);
+            add_concurrent(props);
+            add_summarize(props);
+            add_row_options(props);
+        },
+    );
+
+    define_tool(
+        &mut tools,
+        IMAGE_MATCH_TOOL,
+        "Finds assets that look like an image supplied by the client, such as a photo or sketch of a part: the image is written to a file on the server and passed to the `pcli2` visual match command configured by the administrator. Only available when configured.",
+        &["image"],
+        |props| {
+            add_tenant(props);
+            add_prop(
+                props,
+                "image",
+                json!({ "type": "string", "description": "PNG or JPEG image, base64-encoded or as a `data:image/...;base64,` URL." }),
+            );
+            add_prop(
+                props,
+                "mime_type",
+                json!({ "type": "string", "enum": IMAGE_FORMATS.map(|(mime_type, _, _)| mime_type), "description": "Format of a plain base64 `image`; checked against its content." }),
+            );
         },
     );
 
@@ -602,6 +1350,37 @@ pub fn tool_list() -> Vec<Value> {
             add_metadata(props);
             add_pretty(props);
             add_format(props, &["json", "csv"]);
+            add_summarize(props);
+            add_row_options(props);
+            add_paging(props);
+        },
+    );
+
+    define_tool(
+        &mut tools,
+        MULTI_TENANT_SEARCH_TOOL,
+        "Searches several tenants in parallel: runs a text match for `text` and, when `uuid` or `path` is given, a geometric match against that asset in each tenant. Results are merged into one table with `tenant` and `search` columns.",
+        &[],
+        |props| {
+            add_prop(
+                props,
+                "tenants",
+                json!({
+                    "oneOf": [
+                        { "type": "string" },
+                        { "type": "array", "items": { "type": "string" } }
+                    ],
+                    "description": "Tenants to search. Defaults to the server's configured list."
+                }),
+            );
+            add_text(props);
+            add_fuzzy(props);
+            add_uuid_path(props);
+            add_threshold(props);
+            add_metadata(props);
+            add_concurrent(props);
+            add_summarize(props);
+            add_row_options(props);
         },
     );
 
@@ -612,45 +1391,856 @@ pub fn tool_list() -> Vec<Value> {
         &["name", "value"],
         |props| {
             add_tenant(props);
-            add_uuid_path(props);
+            add_uuid_path_list(props, "update");
             add_metadata_name_value(props);
         },
     );
 
     define_tool(
         &mut tools,
-        "pcli2_asset_metadata_delete",
-        "Runs `pcli2 asset metadata delete`.",
-        &["name"],
+        UPLOAD_TOOL,
+        "Uploads a file as a new asset in a folder with `pcli2 asset create`. The file is base64 `content`, a `pcli2://results/...` or `job://` `resource` this server holds, or a server-local `file`; content and resources are written to a temporary file that is removed afterwards.",
+        &["folder_path"],
+        |props| {
+            add_tenant(props);
+            add_ref(props, "folder_path", "folder_path");
+            add_prop(
+                props,
+                "content",
+                json!({ "type": "string", "description": "File content, base64-encoded or as a `data:...;base64,` URL. Requires `name`." }),
+            );
+            add_prop(
+                props,
+                "resource",
+                json!({ "type": "string", "description": "URI of a `pcli2://results/...` or `job://` resource to upload, such as an export." }),
+            );
+            add_prop(
+                props,
+                "file",
+                json!({ "type": "string", "description": "Path of a file on the server. Not available to clients bound to a tenant." }),
+            );
+            add_prop(
+                props,
+                "name",
+                json!({ "type": "string", "description": "File name of the asset, with its extension, e.g. Bracket.step. Defaults to the resource's name." }),
+            );
+        },
+    );
+
+    define_tool(
+        &mut tools,
+        "pcli2_batch",
+        "Runs an ordered list of pcli2 tool calls in one request and returns per-step results.",
+        &["steps"],
+        |props| {
+            add_prop(
+                props,
+                "steps",
+                json!({
+                    "type": "array",
+                    "description": "Ordered steps to run. Each step names a tool and its arguments.",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "tool": { "type": "string", "description": "Tool name, e.g. pcli2_asset_get." },
+                            "arguments": { "type": "object", "description": "Arguments for the tool." }
+                        },
+                        "required": ["tool"],
+                        "additionalProperties": false
+                    }
+                }),
+            );
+            add_prop(
+                props,
+                "stop_on_error",
+                json!({ "type": "boolean", "description": "Skip the remaining steps after the first failure. Default true." }),
+            );
+            add_prop(
+                props,
+                "parallelism",
+                json!({ "type": "integer", "description": "Maximum number of steps to run at once (1-10). Default 1." }),
+            );
+        },
+    );
+
+    define_tool(
+        &mut tools,
+        "pcli2_asset_metadata_delete",
+        "Runs `pcli2 asset metadata delete`.",
+        &["name"],
+        |props| {
+            add_tenant(props);
+            add_uuid_path_list(props, "update");
+            add_metadata_name(props);
+            add_format(props, &["json", "csv"]);
+        },
+    );
+
+    define_tool(
+        &mut tools,
+        SNAPSHOT_TOOL,
+        "Stores the current asset listing of a folder, including metadata, as a named snapshot on the server, for later comparison with `pcli2_folder_diff`. Runs `pcli2 asset list --metadata`.",
+        &["name"],
+        |props| {
+            add_prop(
+                props,
+                "name",
+                json!({ "type": "string", "description": "Snapshot name (letters, digits, '-', '_', '.')." }),
+            );
+            add_tenant(props);
+            add_folder_uuid_path(props);
+            add_prop(
+                props,
+                "overwrite",
+                json!({ "type": "boolean", "description": "Replace an existing snapshot with the same name. Defaults to false." }),
+            );
+        },
+    );
+
+    define_tool(
+        &mut tools,
+        DIFF_TOOL,
+        "Compares two folder snapshots, or a snapshot with the live folder, and reports added, removed, and changed assets (including metadata changes).",
+        &["from"],
+        |props| {
+            add_prop(
+                props,
+                "from",
+                json!({ "type": "string", "description": "Name of the earlier snapshot." }),
+            );
+            add_prop(
+                props,
+                "to",
+                json!({ "type": "string", "description": "Name of the later snapshot. Omit to compare with the live folder." }),
+            );
+            add_ref(props, RENDER_ARG, "render");
+        },
+    );
+
+    define_tool(
+        &mut tools,
+        CREDENTIALS_TOOL,
+        "Registers or rotates pcli2 credentials on the server: switches to `environment` and `tenant` if given, then logs in with `client_id` and `client_secret`. Only available when enabled by the server administrator.",
+        &[],
+        |props| {
+            add_prop(
+                props,
+                "environment",
+                json!({ "type": "string", "description": "pcli2 environment to switch to before logging in." }),
+            );
+            add_prop(
+                props,
+                "tenant",
+                json!({ "type": "string", "description": "Tenant to make active." }),
+            );
+            add_prop(
+                props,
+                "client_id",
+                json!({ "type": "string", "description": "API client id." }),
+            );
+            add_prop(
+                props,
+                "client_secret",
+                json!({ "type": "string", "description": "API client secret." }),
+            );
+        },
+    );
+
+    define_tool(
+        &mut tools,
+        SIMILARITY_GRAPH_TOOL,
+        "Runs a folder match and turns its pairwise matches into a graph of assets joined by their best similarity score. Reports the connected groups of similar assets (largest first, each with its most connected asset as `hub`), the most central assets ranked by number and strength of matches, and, with `export`, stores the graph as a DOT or GraphML resource.",
+        &["folder_path"],
+        |props| {
+            add_tenant(props);
+            add_folder_path_list(props);
+            add_prop(
+                props,
+                "search",
+                json!({ "type": "string", "enum": GRAPH_SEARCHES, "description": "Folder match to run: geometric (default), part, or visual." }),
+            );
+            add_prop(
+                props,
+                "threshold",
+                json!({ "type": "number", "minimum": 0.0, "maximum": 100.0, "description": "Leave out matches scoring below this (0-100)." }),
+            );
+            add_exclusive(props);
+            add_concurrent(props);
+            add_prop(
+                props,
+                "top",
+                json!({ "type": "integer", "minimum": 1, "maximum": MAX_TOP, "description": format!("Number of most central assets to rank. Default {}.", DEFAULT_TOP) }),
+            );
+            add_prop(
+                props,
+                "export",
+                json!({ "type": "string", "enum": GRAPH_EXPORTS, "description": "Also store the graph as a resource in this format." }),
+            );
+        },
+    );
+
+    define_tool(
+        &mut tools,
+        INDEX_SEARCH_TOOL,
+        "Searches the server's local index of asset names, paths, and metadata in the folders it keeps indexed, in milliseconds and without running pcli2. All words must match; quote a phrase to match it as a whole. Results may be behind the tenant by up to the refresh interval: the result reports when each folder was last indexed. Only available when the server administrator has configured indexed folders.",
+        &["query"],
+        |props| {
+            add_prop(
+                props,
+                "query",
+                json!({ "type": "string", "description": "Words to find in asset names, paths, and metadata names and values." }),
+            );
+            add_prop(
+                props,
+                "folder_path",
+                json!({ "type": "string", "description": "Only search the assets of this indexed folder." }),
+            );
+            add_prop(
+                props,
+                "limit",
+                json!({ "type": "integer", "minimum": 1, "maximum": MAX_SEARCH_LIMIT, "description": format!("Number of best matches to return. Default {}.", DEFAULT_SEARCH_LIMIT) }),
+            );
+        },
+    );
+
+    define_tool(
+        &mut tools,
+        ARTIFACTS_LIST_TOOL,
+        "Lists what the server keeps on disk and in memory for earlier calls: stored results with their size, age, and when a client last read them, and files failed or interrupted calls left behind. Also shows the retention limits. Only available when enabled by the server administrator.",
+        &[],
+        |_| {},
+    );
+
+    define_tool(
+        &mut tools,
+        ARTIFACTS_PURGE_TOOL,
+        "Removes stored results and leftover files: the results listed in `uris`, or everything older than `older_than_secs`, or, with neither, whatever the server's retention limits let go. Recently read results are kept unless named in `uris`. Only available when enabled by the server administrator.",
+        &[],
+        |props| {
+            add_prop(
+                props,
+                "uris",
+                json!({ "type": "array", "items": { "type": "string" }, "description": "pcli2://results/ URIs of stored results to remove." }),
+            );
+            add_prop(
+                props,
+                "older_than_secs",
+                json!({ "type": "integer", "minimum": 0, "description": "Remove what was stored or left behind longer ago than this." }),
+            );
+        },
+    );
+
+    define_tool(
+        &mut tools,
+        AUDIT_TOOL,
+        "Shows the most recent tool calls on this server, oldest first: tool, request id, tenant, arguments (secrets redacted), status, error, and duration. Also readable as the `audit://recent` resource.",
+        &[],
+        |props| {
+            add_prop(
+                props,
+                "limit",
+                json!({ "type": "integer", "description": format!("Number of calls to show (1-{}). Default 20.", MAX_AUDIT_ENTRIES) }),
+            );
+            add_prop(
+                props,
+                "tool",
+                json!({ "type": "string", "description": "Only show calls to this tool (internal name, e.g. pcli2_asset_get)." }),
+            );
+        },
+    );
+
+    define_tool(
+        &mut tools,
+        PLAN_TOOL,
+        "Plans the tool calls for a plain-language request such as \"find parts similar to bracket X in the castings folder\". Returns the steps to run in order, with the arguments taken from the request; an argument like `$steps[0].matches[0].path` is a value from an earlier step's output. Lists what is missing from the request. Runs nothing.",
+        &["request"],
+        |props| {
+            add_prop(
+                props,
+                "request",
+                json!({ "type": "string", "description": "What the user wants, in their words." }),
+            );
+        },
+    );
+
+    define_tool(
+        &mut tools,
+        QUOTA_STATUS_TOOL,
+        "Reports this session's tool call quotas: calls used and remaining per quota, and seconds until the oldest counted call leaves the window. Does not count against any quota.",
+        &[],
+        |_| {},
+    );
+
+    define_tool(
+        &mut tools,
+        APPROVAL_STATUS_TOOL,
+        "Reports a call that is awaiting an operator's approval, by the approval id it returned: still pending, running, or rejected, or the call's result once it has run. Only available when the server holds some tools for approval.",
+        &["id"],
         |props| {
-            add_tenant(props);
-            add_metadata_name(props);
-            add_format(props, &["json", "csv"]);
+            add_prop(
+                props,
+                "id",
+                json!({ "type": "string", "description": "Approval id from the held call's result." }),
+            );
         },
     );
 
     tools
 }
 
-pub async fn call_tool(params: Value) -> Result<Value, String> {
+fn is_tool_enabled(state: &AppState, internal: &str) -> bool {
+    if state.compat.disabled(internal).is_some() {
+        return false;
+    }
+    match internal {
+        GENERIC_TOOL => !state.config.tools.hide_generic_tool,
+        CREDENTIALS_TOOL => state.config.admin.expose_tool,
+        ARTIFACTS_LIST_TOOL | ARTIFACTS_PURGE_TOOL => state.config.artifacts.expose_tools,
+        IMAGE_MATCH_TOOL => state.config.image_match.is_enabled(),
+        INDEX_SEARCH_TOOL => state.config.index.is_enabled(),
+        APPROVAL_STATUS_TOOL => state.config.approval.is_enabled(),
+        _ => true,
+    }
+}
+
+pub fn public_tool_list(state: &AppState) -> Vec<Value> {
+    let structured = flags::enabled(state, STRUCTURED_OUTPUT);
+    tool_list()
+        .into_iter()
+        .filter(|tool| {
+            tool["name"].as_str().is_some_and(|name| {
+                is_tool_enabled(state, name)
+                    && tool_allowed(state.tenant.as_deref(), name, &tool["inputSchema"])
+                    && tool_visible(state, name, &tool["inputSchema"])
+            })
+        })
+        .map(|tool| {
+            let mut tool = add_cli_version(state, tool);
+            state.config.locale.describe_tool(&mut tool);
+            if let Some(name) = tool.get("name").and_then(|v| v.as_str()) {
+                tool["name"] = json!(state.config.tools.public_name(name));
+            }
+            if !structured && let Some(obj) = tool.as_object_mut() {
+                obj.remove("outputSchema");
+            }
+            tool
+        })
+        .collect()
+}
+
+pub fn resolve_tool(state: &AppState, public_name: &str) -> Option<(String, Value)> {
+    tool_list().into_iter().find_map(|tool| {
+        let internal = tool.get("name").and_then(|v| v.as_str())?.to_string();
+        (state.config.tools.public_name(&internal) == public_name
+            && (!matches!(
+                internal.as_str(),
+                CREDENTIALS_TOOL
+                    | ARTIFACTS_LIST_TOOL
+                    | ARTIFACTS_PURGE_TOOL
+                    | IMAGE_MATCH_TOOL
+                    | INDEX_SEARCH_TOOL
+                    | APPROVAL_STATUS_TOOL
+            ) || is_tool_enabled(state, &internal)))
+        .then(|| (internal, add_cli_version(state, tool)))
+    })
+}
+
+/// Offers `cli_version` on tools that run `pcli2` once `cli_versions` is
+/// configured.
+fn add_cli_version(state: &AppState, mut tool: Value) -> Value {
+    let versions = &state.config.cli_versions;
+    let name = tool["name"].as_str().unwrap_or_default();
+    if versions.binaries.is_empty()
+        || matches!(
+            name,
+            QUOTA_STATUS_TOOL
+                | AUDIT_TOOL
+                | PLAN_TOOL
+                | APPROVAL_STATUS_TOOL
+                | ARTIFACTS_LIST_TOOL
+                | ARTIFACTS_PURGE_TOOL
+                | INDEX_SEARCH_TOOL
+                | "pcli2_batch"
+        )
+    {
+        return tool;
+    }
+    let default = versions
+        .tools
+        .get(name)
+        .or(versions.default.as_ref())
+        .map_or_else(
+            || "the standard pcli2".to_string(),
+            |name| format!("'{}'", name),
+        );
+    if let Some(props) = tool["inputSchema"]["properties"].as_object_mut() {
+        props.insert(
+            CLI_VERSION_ARG.to_string(),
+            json!({
+                "type": "string",
+                "enum": versions.binaries.keys().collect::<Vec<_>>(),
+                "description": format!("pcli2 build to run (default: {})", default)
+            }),
+        );
+    }
+    tool
+}
+
+pub async fn call_tool(state: &AppState, params: Value) -> Result<Value, String> {
     let name = params
         .get("name")
         .and_then(|v| v.as_str())
         .ok_or_else(|| "Missing tool name".to_string())?;
-    let args = params
+    let original = params
         .get("arguments")
         .cloned()
         .unwrap_or_else(|| json!({}));
+    let (internal, tool) =
+        resolve_tool(state, name).ok_or_else(|| format!("Unknown tool '{}'", name))?;
+    if let Some(reason) = state.compat.disabled(&internal) {
+        return Err(format!(
+            "Tool '{}' is disabled: the installed pcli2 cannot run it ({})",
+            name, reason
+        ));
+    }
+    // Batch steps share the deadline of the batch.
+    let deadline = match Deadline::from_params(&params)? {
+        Some(deadline) => Some(deadline),
+        None => current_execution().deadline,
+    };
+    let mut args = prepare_arguments(&tool["inputSchema"], original.clone(), state.arg_mode)?;
+    let requested = args
+        .as_object_mut()
+        .and_then(|args| args.remove(CLI_VERSION_ARG));
+    bind_arguments(
+        state.tenant.as_deref(),
+        &internal,
+        &tool["inputSchema"],
+        &mut args,
+    )?;
+    apply_tenant_defaults(&state.config, &tool["inputSchema"], &mut args);
+    policy::enforce(state, &internal, &tool["inputSchema"], &mut args)?;
+    let cli_version = state
+        .config
+        .cli_versions
+        .select(&internal, requested.as_ref().and_then(Value::as_str))?;
+    if internal == QUOTA_STATUS_TOOL {
+        return quota_status_result(state).await;
+    }
+    if internal == AUDIT_TOOL {
+        return audit_tail_result(&state.audit, state.tenant.as_deref(), &args);
+    }
+    if internal == PLAN_TOOL {
+        return plan(state, &args);
+    }
+    if internal == APPROVAL_STATUS_TOOL {
+        return approval_status(state, &args);
+    }
+    // Held calls run later as made, once their arguments are known to be valid.
+    if !state.approved && state.config.approval.requires(&internal) {
+        if !flags::enabled(state, APPROVALS) {
+            return Err(format!(
+                "{} needs an operator's approval, which is turned off for this session",
+                name
+            ));
+        }
+        return Ok(hold(state, &internal, original));
+    }
+    let summarize = wants_summary(&internal, &args);
+    if summarize {
+        prepare_summary(&mut args)?;
+    }
+    let filtered = wants_filter(&internal, &args);
+    if filtered {
+        prepare_filter(&mut args)?;
+    }
+    let projected = wants_projection(&internal, &args);
+    if projected {
+        prepare_projection(&mut args)?;
+    }
+    let paged = wants_page(&internal, &args);
+    if paged {
+        prepare_page(&mut args)?;
+    }
+    let reformatted = wants_response_format(&internal, &args);
+    if reformatted {
+        prepare_response_format(&mut args)?;
+    }
+    let exhausted = |message: String| {
+        format!(
+            "{} Call {} to see current usage.",
+            message,
+            state.config.tools.public_name(QUOTA_STATUS_TOOL)
+        )
+    };
+    state
+        .quotas
+        .admit(state.session_key(), &internal)
+        .await
+        .map_err(exhausted)?;
+    if let Some(quotas) = policy_quotas(state) {
+        quotas
+            .admit(state.principal_key(), &internal)
+            .await
+            .map_err(exhausted)?;
+    }
+    let mime_type = output_mime_type(&args);
+    let cli_version_name = cli_version.as_ref().map(|(version, _)| version.as_str());
+    // A cached result needs neither a permit nor a working backend.
+    let cached = state.cache.get(&internal, cli_version_name, &args).await;
+    let from_cache = cached.is_some();
+    let policy = state.config.execution.policy(&internal);
+    let _permit = match policy.concurrency.filter(|_| !from_cache) {
+        Some(permits) => Some(
+            state
+                .limits
+                .semaphore(&internal, permits)
+                .acquire(state.session_key())
+                .await,
+        ),
+        None => None,
+    };
+    // Batch steps take their own slots.
+    let shared = state
+        .config
+        .execution
+        .max_concurrent_calls
+        .filter(|_| !from_cache && internal != "pcli2_batch");
+    let _slot = match shared {
+        Some(permits) => Some(
+            state
+                .limits
+                .semaphore(ALL_TOOLS, permits)
+                .acquire(state.session_key())
+                .await,
+        ),
+        None => None,
+    };
+    if !from_cache {
+        shed_low_priority(state, &internal)?;
+        admit_call(state).await?;
+    }
+    let workspace = if from_cache {
+        None
+    } else {
+        Workspace::create(
+            &state.config,
+            &internal,
+            args.get("tenant").and_then(Value::as_str),
+        )?
+    };
+    let usage = CallUsage::default();
+    let execution = ExecutionContext {
+        policy,
+        breaker: Some(state.breaker.clone()),
+        health: Some(state.health.clone()),
+        reauth: Some(state.reauth.clone()),
+        sandbox: Sandbox::for_tool(&state.config.sandbox, &internal),
+        cassette: state.cassette.clone(),
+        runner: Some(state.runner.clone()),
+        pcli2: cli_version.as_ref().map(|(_, path)| path.clone()),
+        deadline: deadline.clone(),
+        workspace: workspace
+            .as_ref()
+            .map(|workspace| workspace.path().to_path_buf()),
+        usage: Some(usage.clone()),
+    };
+    let tenant = args
+        .get("tenant")
+        .and_then(|v| v.as_str())
+        .map(str::to_string);
+    let audited_args = args.clone();
+    let started = Instant::now();
+    let call = state.calls.start(&internal, tenant.as_deref());
+    let result = match cached {
+        Some(hit) => Ok(hit),
+        None => tokio::select! {
+            result = EXECUTION.scope(execution, dispatch_tool(state, &internal, args)) => result,
+            _ = call.cancelled() => Err(format!("Call to {} was cancelled by the server operator", name)),
+            _ = deadline_expired(deadline.as_ref()) => Err(format!("Call to {} did not finish in time", name)),
+        },
+    };
+    // A result some runs were cut short for is partial, or the error says why.
+    let partial = deadline.as_ref().filter(|deadline| deadline.was_hit());
+    let result = match partial {
+        Some(deadline) => result.map_err(|message| deadline.explain(message)),
+        None => result,
+    };
+    call.finish(&result);
+    state.usage.record(
+        state.session_key(),
+        state.api_key.as_deref(),
+        &usage.finish(&internal, result.is_ok()),
+    );
+    let job = match workspace {
+        Some(workspace)
+            if result.is_ok()
+                && partial.is_none()
+                && state.config.artifacts.keeps_jobs(&internal) =>
+        {
+            let output = result.as_ref().ok().and_then(last_text);
+            keep_job(workspace, &internal, tenant.as_deref(), output, mime_type)
+                .inspect_err(|err| warn!("⚠ could not keep the job of {}: {}", name, err))
+                .ok()
+        }
+        Some(workspace) => {
+            workspace.finish(result.is_ok());
+            None
+        }
+        None => None,
+    };
+    state.audit.record(
+        &internal,
+        state.request_id.as_deref(),
+        state.tenant.as_deref().or(tenant.as_deref()),
+        &audited_args,
+        &result,
+        started.elapsed(),
+    );
+    // Even a failed change may have gone through for some assets.
+    state.cache.invalidate(&internal, &audited_args).await;
+    let mut result = result?;
+    if !from_cache && partial.is_none() {
+        state
+            .cache
+            .put(&internal, cli_version_name, &audited_args, &result)
+            .await;
+    }
+    if INVALIDATING_TOOLS.contains(&internal.as_str()) {
+        state.cache.clear().await;
+    }
+    // Rows are filtered and sorted before they are paged.
+    if filtered {
+        result = filter_result(&audited_args, result);
+    }
+    if projected {
+        result = project_result(&audited_args, result);
+    }
+    if paged {
+        result = page_result(&audited_args, result);
+    }
+    if reformatted {
+        result = reformat_result(&audited_args, result);
+    }
+    if summarize {
+        result = summarize_result(
+            &state.resources,
+            state.tenant.as_deref(),
+            name,
+            &internal,
+            &audited_args,
+            result,
+        );
+    }
+    let result = if flags::enabled(state, STRUCTURED_OUTPUT) {
+        attach_structured_content(&internal, result)
+    } else {
+        drop_structured_content(result)
+    };
+    let result = offload_large_text(
+        &state.resources,
+        state.tenant.as_deref(),
+        name,
+        mime_type,
+        state.inline_result_limit,
+        result,
+    );
+    let result = if state.config.tools.compat_text(&internal, state.compat_text) {
+        result
+    } else {
+        drop_text_content(result)
+    };
+    let mut result = if wants_markdown(&internal, &audited_args) {
+        render_markdown(&internal, result)
+    } else {
+        result
+    };
+    if let Some((version, _)) = cli_version {
+        result["_meta"]["cli_version"] = json!(version);
+    }
+    if let Some(job) = job {
+        result["_meta"]["job"] = job;
+    }
+    match partial {
+        Some(deadline) => deadline.mark_partial(&mut result),
+        None => add_freshness(&state.config.cache, &internal, &mut result),
+    }
+    state.resources.publish(&result).await;
+    Ok(result)
+}
+
+/// Whether `tool` is in the request's `tools/list`, so it may read the
+/// resources built on it.
+pub(crate) fn tool_listed(state: &AppState, tool: &str) -> bool {
+    tool_list().into_iter().any(|listed| {
+        listed["name"] == tool
+            && is_tool_enabled(state, tool)
+            && tool_allowed(state.tenant.as_deref(), tool, &listed["inputSchema"])
+            && tool_visible(state, tool, &listed["inputSchema"])
+    })
+}
+
+/// Calls `tool` with `args` to answer a resource read, held to everything
+/// a client's call of it is: the request's policy, quotas, cache,
+/// concurrency limits, circuit breaker, and audit log. `None` when the
+/// request may not see the tool.
+pub(crate) async fn call_for_resource(
+    state: &AppState,
+    tool: &str,
+    args: Value,
+) -> Result<Option<Value>, String> {
+    if !tool_listed(state, tool) {
+        return Ok(None);
+    }
+    // The whole output inline, so it can be read back.
+    let state = AppState {
+        inline_result_limit: usize::MAX,
+        compat_text: true,
+        ..state.clone()
+    };
+    let params = json!({ "name": state.config.tools.public_name(tool), "arguments": args });
+    call_tool(&state, params).await.map(Some)
+}
+
+/// Quotas of the policy the request's API key is held to.
+fn policy_quotas(state: &AppState) -> Option<&QuotaTracker> {
+    state
+        .policy
+        .as_deref()
+        .and_then(|policy| state.policy_quotas.get(policy))
+}
+
+async fn quota_status_result(state: &AppState) -> Result<Value, String> {
+    let mut status = state.quotas.status(state.session_key()).await;
+    if let (Some(policy), Some(quotas)) = (&state.policy, policy_quotas(state)) {
+        status["policy"] = json!({
+            "name": policy,
+            "quotas": quotas.status(state.principal_key()).await["quotas"]
+        });
+    }
+    let all: Vec<&Value> = ["/quotas", "/policy/quotas"]
+        .iter()
+        .filter_map(|pointer| status.pointer(pointer).and_then(Value::as_array))
+        .flatten()
+        .collect();
+    let quotas = all.len();
+    let exhausted = all.iter().filter(|quota| quota["remaining"] == 0).count();
+    let headline = if quotas == 0 {
+        "No quotas are configured.".to_string()
+    } else {
+        format!("{} quotas configured, {} exhausted.", quotas, exhausted)
+    };
+    let text = serde_json::to_string_pretty(&status)
+        .map_err(|err| format!("Failed to render quota status: {}", err))?;
+    Ok(tool_result(vec![
+        summary_block(headline),
+        raw_output_block(text),
+    ]))
+}
+
+/// Runs `pcli2` outside of a tool call with the server's runner, sandbox,
+/// and cassette, e.g. for warm-up runs. The circuit breaker does not count
+/// its failures.
+pub(crate) async fn run_pcli2_background(
+    state: &AppState,
+    cmd_args: Vec<String>,
+    label: &str,
+) -> Result<String, String> {
+    let execution = ExecutionContext {
+        policy: state.config.execution.policy(""),
+        breaker: None,
+        health: None,
+        reauth: None,
+        sandbox: Sandbox::for_tool(&state.config.sandbox, ""),
+        cassette: state.cassette.clone(),
+        runner: Some(state.runner.clone()),
+        pcli2: None,
+        deadline: None,
+        workspace: None,
+        usage: None,
+    };
+    EXECUTION
+        .scope(execution, run_pcli2_command(cmd_args, label))
+        .await
+}
+
+async fn admit_call(state: &AppState) -> Result<(), String> {
+    match state.breaker.admit() {
+        Admission::Allow => Ok(()),
+        Admission::Reject(message) => Err(message),
+        Admission::Probe => {
+            let probe = ExecutionContext {
+                policy: ExecutionPolicy {
+                    timeout: PROBE_TIMEOUT,
+                    retries: 0,
+                    concurrency: None,
+                },
+                breaker: None,
+                health: None,
+                reauth: None,
+                sandbox: Sandbox::for_tool(&state.config.sandbox, ""),
+                cassette: state.cassette.clone(),
+                runner: Some(state.runner.clone()),
+                pcli2: None,
+                deadline: None,
+                workspace: None,
+                usage: None,
+            };
+            let outcome = EXECUTION
+                .scope(
+                    probe,
+                    run_pcli2_command(state.breaker.probe_args(), "pcli2 health probe"),
+                )
+                .await;
+            match outcome {
+                Ok(_) => {
+                    info!("✔ pcli2 health probe succeeded; closing circuit breaker");
+                    state.breaker.record_success();
+                    Ok(())
+                }
+                Err(message) => {
+                    state.breaker.record_failure(&message);
+                    match state.breaker.admit() {
+                        Admission::Reject(rejection) => Err(rejection),
+                        _ => Err(message),
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn output_mime_type(args: &Value) -> &'static str {
+    if let Some(mime_type) = response_mime_type(args) {
+        return mime_type;
+    }
+    match args.get("format").and_then(|v| v.as_str()) {
+        Some("json") => "application/json",
+        Some("csv") => "text/csv",
+        _ => "text/plain",
+    }
+}
 
+async fn dispatch_tool(state: &AppState, name: &str, args: Value) -> Result<Value, String> {
     match name {
-        "pcli2" => {
-            let output = run_pcli2_list(args).await?;
-            Ok(json!({
-                "content": [{
-                    "type": "text",
-                    "text": output
-                }]
-            }))
+        GENERIC_TOOL => {
+            let resource = args
+                .get("resource")
+                .and_then(|v| v.as_str())
+                .unwrap_or("folder")
+                .to_string();
+            warn!(
+                "⚠ deprecated tool '{}' called; redirecting to pcli2_{}_list",
+                GENERIC_TOOL, resource
+            );
+            let output = run_pcli2_list(&resource, args).await?;
+            Ok(raw_output_result(output))
+        }
+        "pcli2_folder_list" => {
+            run_simple_tool("pcli2 folder list", run_pcli2_list("folder", args).await)
+        }
+        "pcli2_asset_list" => {
+            run_simple_tool("pcli2 asset list", run_pcli2_list("asset", args).await)
         }
         "pcli2_tenant_list" => {
             run_simple_tool("pcli2 tenant list", run_pcli2_tenant_list(args).await)
@@ -678,22 +2268,54 @@ pub async fn call_tool(params: Value) -> Result<Value, String> {
         "pcli2_folder_resolve" => {
             run_simple_tool("pcli2 folder resolve", run_pcli2_folder_resolve(args).await)
         }
-        "pcli2_folder_dependencies" => run_simple_tool(
-            "pcli2 folder dependencies",
-            run_pcli2_folder_dependencies(args).await,
-        ),
-        "pcli2_folder_geometric_match" => run_simple_tool(
-            "pcli2 folder geometric-match",
-            run_pcli2_folder_geometric_match(args).await,
-        ),
-        "pcli2_folder_part_match" => run_simple_tool(
-            "pcli2 folder part-match",
-            run_pcli2_folder_part_match(args).await,
-        ),
-        "pcli2_folder_visual_match" => run_simple_tool(
-            "pcli2 folder visual-match",
-            run_pcli2_folder_visual_match(args).await,
-        ),
+        "pcli2_folder_dependencies" if wants_view(&args) => {
+            folder_dependencies_view(state, args).await
+        }
+        "pcli2_folder_dependencies" => {
+            run_folders_with_fallback(
+                args,
+                "pcli2 folder dependencies",
+                false,
+                run_pcli2_folder_dependencies,
+            )
+            .await
+        }
+        "pcli2_folder_geometric_match" if wants_split(&args) => {
+            run_split_folder_match(args, run_pcli2_folder_geometric_match).await
+        }
+        "pcli2_folder_geometric_match" => {
+            run_folders_with_fallback(
+                args,
+                "pcli2 folder geometric-match",
+                true,
+                run_pcli2_folder_geometric_match,
+            )
+            .await
+        }
+        "pcli2_folder_part_match" if wants_split(&args) => {
+            run_split_folder_match(args, run_pcli2_folder_part_match).await
+        }
+        "pcli2_folder_part_match" => {
+            run_folders_with_fallback(
+                args,
+                "pcli2 folder part-match",
+                true,
+                run_pcli2_folder_part_match,
+            )
+            .await
+        }
+        "pcli2_folder_visual_match" if wants_split(&args) => {
+            run_split_folder_match(args, run_pcli2_folder_visual_match).await
+        }
+        "pcli2_folder_visual_match" => {
+            run_folders_with_fallback(
+                args,
+                "pcli2 folder visual-match",
+                true,
+                run_pcli2_folder_visual_match,
+            )
+            .await
+        }
         "pcli2_asset_get" => run_simple_tool("pcli2 asset get", run_pcli2_asset_get(args).await),
         "pcli2_asset_dependencies" => run_simple_tool(
             "pcli2 asset dependencies",
@@ -702,37 +2324,49 @@ pub async fn call_tool(params: Value) -> Result<Value, String> {
         "pcli2_asset_thumbnail" => {
             let encoded = run_pcli2_asset_thumbnail(args).await?;
             let data_url = format!("data:image/png;base64,{}", encoded);
-            Ok(json!({
-                "content": [{
+            Ok(tool_result(vec![
+                json!({
                     "type": "image",
                     "mimeType": "image/png",
-                    "annotations": {
-                        "audience": ["user"]
-                    },
+                    "annotations": annotations(&[AUDIENCE_USER], PRIORITY_IMAGE),
                     "data": encoded
-                }, {
+                }),
+                json!({
                     "type": "text",
-                    "text": data_url
-                }]
-            }))
+                    "text": data_url,
+                    "annotations": annotations(&[AUDIENCE_ASSISTANT], PRIORITY_ENCODED_DATA)
+                }),
+            ]))
         }
+        GALLERY_TOOL => thumbnail_gallery(args).await,
+        EXPORT_TOOL => export_asset(state, args).await,
+        BATCH_GET_TOOL => batch_get(state, args).await,
+        ARCHIVE_TOOL => download_archive(state, args).await,
+        IMAGE_MATCH_TOOL => run_simple_tool(
+            "pcli2 image visual match",
+            visual_match_image(state, args).await,
+        ),
         "pcli2_asset_reprocess" => run_simple_tool(
             "pcli2 asset reprocess",
             run_pcli2_asset_reprocess(args).await,
         ),
+        "pcli2_geometric_match" if is_asset_fan_out(&args) => {
+            run_asset_fan_out(args, "Matched", run_pcli2_asset_geometric_match).await
+        }
         "pcli2_geometric_match" => {
             let output = run_pcli2_asset_geometric_match(args).await?;
-            Ok(json!({
-                "content": [{
-                    "type": "text",
-                    "text": output
-                }]
-            }))
+            Ok(raw_output_result(output))
+        }
+        "pcli2_asset_part_match" if is_asset_fan_out(&args) => {
+            run_asset_fan_out(args, "Matched", run_pcli2_asset_part_match).await
         }
         "pcli2_asset_part_match" => run_simple_tool(
             "pcli2 asset part-match",
             run_pcli2_asset_part_match(args).await,
         ),
+        "pcli2_asset_visual_match" if is_asset_fan_out(&args) => {
+            run_asset_fan_out(args, "Matched", run_pcli2_asset_visual_match).await
+        }
         "pcli2_asset_visual_match" => run_simple_tool(
             "pcli2 asset visual-match",
             run_pcli2_asset_visual_match(args).await,
@@ -741,40 +2375,280 @@ pub async fn call_tool(params: Value) -> Result<Value, String> {
             "pcli2 asset text-match",
             run_pcli2_asset_text_match(args).await,
         ),
+        "pcli2_asset_metadata_create" if is_asset_fan_out(&args) => {
+            run_asset_fan_out(args, "Updated", run_pcli2_asset_metadata_create).await
+        }
         "pcli2_asset_metadata_create" => run_simple_tool(
             "pcli2 asset metadata create",
             run_pcli2_asset_metadata_create(args).await,
         ),
+        UPLOAD_TOOL => run_simple_tool("pcli2 asset create", upload_asset(state, args).await),
+        "pcli2_asset_metadata_delete" if is_asset_fan_out(&args) => {
+            run_asset_fan_out(args, "Updated", run_pcli2_asset_metadata_delete).await
+        }
         "pcli2_asset_metadata_delete" => run_simple_tool(
             "pcli2 asset metadata delete",
             run_pcli2_asset_metadata_delete(args).await,
         ),
+        "pcli2_batch" => run_batch(state, args).await,
+        MULTI_TENANT_SEARCH_TOOL => run_multi_tenant_search(state, args).await,
+        SIMILARITY_GRAPH_TOOL => similarity_graph(state, args).await,
+        FOLDER_SUGGEST_TOOL => suggest_folders(args).await,
+        SNAPSHOT_TOOL => take_snapshot(state, args).await,
+        DIFF_TOOL => diff_snapshots(state, args).await,
+        CREDENTIALS_TOOL => {
+            let commands = register_credentials(state, &args).await?;
+            Ok(tool_result(vec![summary_block(format!(
+                "Credentials registered ({} pcli2 commands run).",
+                commands.len()
+            ))]))
+        }
+        INDEX_SEARCH_TOOL => search_index(state, &args),
+        ARTIFACTS_LIST_TOOL => list_artifacts(state),
+        ARTIFACTS_PURGE_TOOL => purge_artifacts(state, &args),
         _ => Err(format!("Unknown tool '{}'", name)),
     }
 }
 
+/// Completes when the client's deadline has passed; never without one.
+async fn deadline_expired(deadline: Option<&Deadline>) {
+    match deadline {
+        Some(deadline) => {
+            tokio::time::sleep_until(deadline.expires().into()).await;
+            deadline.mark_hit();
+        }
+        None => std::future::pending().await,
+    }
+}
+
+fn call_tool_boxed(
+    state: AppState,
+    params: Value,
+) -> Pin<Box<dyn Future<Output = Result<Value, String>> + Send>> {
+    Box::pin(async move { call_tool(&state, params).await })
+}
+
+async fn run_batch(state: &AppState, args: Value) -> Result<Value, String> {
+    validate_range_u64(&args, "parallelism", 1, 10)?;
+    let steps = args
+        .get("steps")
+        .and_then(|v| v.as_array())
+        .cloned()
+        .ok_or_else(|| "Missing required argument: 'steps'".to_string())?;
+    if steps.is_empty() {
+        return Err("Invalid argument 'steps': at least one step is required".to_string());
+    }
+    if steps.len() > MAX_BATCH_STEPS {
+        return Err(format!(
+            "Too many steps: {} provided, at most {} are allowed per batch",
+            steps.len(),
+            MAX_BATCH_STEPS
+        ));
+    }
+    let mut calls = Vec::with_capacity(steps.len());
+    for (index, step) in steps.iter().enumerate() {
+        let tool = step
+            .get("tool")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| format!("Invalid step {}: missing 'tool'", index))?;
+        if resolve_tool(state, tool).is_some_and(|(internal, _)| internal == "pcli2_batch") {
+            return Err(format!("Invalid step {}: batches cannot be nested", index));
+        }
+        let arguments = step.get("arguments").cloned().unwrap_or_else(|| json!({}));
+        calls.push(json!({ "name": tool, "arguments": arguments }));
+    }
+    let stop_on_error = args
+        .get("stop_on_error")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true);
+    let parallelism = args
+        .get("parallelism")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(1) as usize;
+
+    let semaphore = Arc::new(Semaphore::new(parallelism));
+    let failed = Arc::new(AtomicBool::new(false));
+    let mut tasks = JoinSet::new();
+    for (index, params) in calls.iter().cloned().enumerate() {
+        let permit = semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|err| format!("Batch scheduling failed: {}", err))?;
+        if stop_on_error && failed.load(Ordering::SeqCst) {
+            break;
+        }
+        let failed = failed.clone();
+        let state = state.clone();
+        tasks.spawn(in_current_execution(async move {
+            let _permit = permit;
+            let outcome = call_tool_boxed(state, params).await;
+            if outcome.is_err() {
+                failed.store(true, Ordering::SeqCst);
+            }
+            (index, outcome)
+        }));
+    }
+
+    let mut outcomes: Vec<Option<Result<Value, String>>> = vec![None; calls.len()];
+    while let Some(joined) = tasks.join_next().await {
+        let (index, outcome) = joined.map_err(|err| format!("Batch step failed: {}", err))?;
+        outcomes[index] = Some(outcome);
+    }
+
+    let mut succeeded = 0;
+    let mut errored = 0;
+    let results: Vec<Value> = calls
+        .iter()
+        .zip(outcomes)
+        .enumerate()
+        .map(|(index, (params, outcome))| {
+            let tool = params["name"].clone();
+            match outcome {
+                Some(Ok(result)) => {
+                    succeeded += 1;
+                    json!({ "step": index, "tool": tool, "status": "ok", "content": result["content"] })
+                }
+                Some(Err(message)) => {
+                    errored += 1;
+                    json!({ "step": index, "tool": tool, "status": "error", "error": message })
+                }
+                None => json!({ "step": index, "tool": tool, "status": "skipped" }),
+            }
+        })
+        .collect();
+    let skipped = results.len() - succeeded - errored;
+    let headline = format!(
+        "Batch of {} steps: {} succeeded, {} failed, {} skipped.",
+        results.len(),
+        succeeded,
+        errored,
+        skipped
+    );
+    let summary = json!({
+        "total": results.len(),
+        "succeeded": succeeded,
+        "failed": errored,
+        "skipped": skipped,
+        "steps": results
+    });
+    let text = serde_json::to_string_pretty(&summary)
+        .map_err(|err| format!("Failed to render results: {}", err))?;
+    Ok(tool_result(vec![
+        summary_block(headline),
+        raw_output_block(text),
+    ]))
+}
+
+fn is_asset_fan_out(args: &Value) -> bool {
+    ["uuid", "path"]
+        .iter()
+        .any(|key| args.get(*key).is_some_and(Value::is_array))
+}
+
+async fn run_asset_fan_out<F, Fut>(args: Value, verb: &str, run: F) -> Result<Value, String>
+where
+    F: Fn(Value) -> Fut,
+    Fut: Future<Output = Result<String, String>> + Send + 'static,
+{
+    validate_range_f64(&args, "threshold", 0.0, 100.0)?;
+    validate_range_u64(&args, "concurrent", 1, 10)?;
+    let selectors: Vec<(&str, String)> = parse_string_list(&args, "uuid")
+        .into_iter()
+        .map(|uuid| ("uuid", uuid))
+        .chain(
+            parse_string_list(&args, "path")
+                .into_iter()
+                .map(|path| ("path", path)),
+        )
+        .collect();
+    if selectors.is_empty() {
+        return Err("Missing required argument: provide either 'uuid' or 'path'".to_string());
+    }
+    if selectors.len() > MAX_FAN_OUT_INPUTS {
+        return Err(format!(
+            "Too many inputs: {} provided, at most {} are allowed per call",
+            selectors.len(),
+            MAX_FAN_OUT_INPUTS
+        ));
+    }
+    let concurrency = args
+        .get("concurrent")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(DEFAULT_FAN_OUT_CONCURRENCY as u64) as usize;
+
+    let semaphore = Arc::new(Semaphore::new(concurrency));
+    let mut tasks = JoinSet::new();
+    for (index, (key, value)) in selectors.iter().enumerate() {
+        let mut single = args.clone();
+        if let Some(obj) = single.as_object_mut() {
+            obj.remove("uuid");
+            obj.remove("path");
+            obj.remove("concurrent");
+            obj.insert(key.to_string(), Value::String(value.clone()));
+        }
+        let semaphore = semaphore.clone();
+        let fut = run(single);
+        tasks.spawn(in_current_execution(async move {
+            let _permit = semaphore.acquire_owned().await;
+            (index, fut.await)
+        }));
+    }
+
+    let mut outcomes: Vec<Option<Result<String, String>>> = vec![None; selectors.len()];
+    while let Some(joined) = tasks.join_next().await {
+        let (index, outcome) = joined.map_err(|err| format!("Fan-out task failed: {}", err))?;
+        outcomes[index] = Some(outcome);
+    }
+
+    let mut succeeded = 0;
+    let results: Vec<Value> = selectors
+        .into_iter()
+        .zip(outcomes)
+        .map(|((key, value), outcome)| match outcome {
+            Some(Ok(output)) => {
+                succeeded += 1;
+                let output =
+                    serde_json::from_str::<Value>(&output).unwrap_or(Value::String(output));
+                json!({ key: value, "status": "ok", "output": output })
+            }
+            Some(Err(message)) => json!({ key: value, "status": "error", "error": message }),
+            None => json!({ key: value, "status": "error", "error": "No result produced" }),
+        })
+        .collect();
+    let headline = format!(
+        "{} {} inputs: {} succeeded, {} failed.",
+        verb,
+        results.len(),
+        succeeded,
+        results.len() - succeeded
+    );
+    let merged = json!({
+        "total": results.len(),
+        "succeeded": succeeded,
+        "failed": results.len() - succeeded,
+        "results": results
+    });
+    let text = serde_json::to_string_pretty(&merged)
+        .map_err(|err| format!("Failed to render results: {}", err))?;
+    Ok(tool_result(vec![
+        summary_block(headline),
+        raw_output_block(text),
+    ]))
+}
+
 fn run_simple_tool(label: &str, result: Result<String, String>) -> Result<Value, String> {
     match result {
-        Ok(output) => Ok(json!({
-            "content": [{
-                "type": "text",
-                "text": output
-            }]
-        })),
+        Ok(output) => Ok(raw_output_result(output)),
         Err(message) => Err(format!("{} failed: {}", label, message)),
     }
 }
 
-async fn run_pcli2_list(args: Value) -> Result<String, String> {
-    let resource = args
-        .get("resource")
-        .and_then(|v| v.as_str())
-        .unwrap_or("folder");
+pub(crate) async fn run_pcli2_list(resource: &str, args: Value) -> Result<String, String> {
     let mut cmd_args: Vec<String> = vec![resource.to_string(), "list".to_string()];
 
     if let Some(tenant) = args.get("tenant").and_then(|v| v.as_str()) {
-        cmd_args.push("-t".to_string());
-        cmd_args.push(tenant.to_string());
+        push_value(&mut cmd_args, "-t", tenant)?;
     }
     if args
         .get("metadata")
@@ -798,16 +2672,13 @@ async fn run_pcli2_list(args: Value) -> Result<String, String> {
         cmd_args.push("--pretty".to_string());
     }
     if let Some(format) = args.get("format").and_then(|v| v.as_str()) {
-        cmd_args.push("-f".to_string());
-        cmd_args.push(format.to_string());
+        push_value(&mut cmd_args, "-f", format)?;
     }
     if let Some(folder_uuid) = args.get("folder_uuid").and_then(|v| v.as_str()) {
-        cmd_args.push("--folder-uuid".to_string());
-        cmd_args.push(folder_uuid.to_string());
+        push_value(&mut cmd_args, "--folder-uuid", folder_uuid)?;
     }
     if let Some(folder_path) = args.get("folder_path").and_then(|v| v.as_str()) {
-        cmd_args.push("--folder-path".to_string());
-        cmd_args.push(folder_path.to_string());
+        push_value(&mut cmd_args, "--folder-path", folder_path)?;
     }
     if args
         .get("reload")
@@ -820,18 +2691,17 @@ async fn run_pcli2_list(args: Value) -> Result<String, String> {
     run_pcli2_command(cmd_args, &format!("pcli2 {} list", resource)).await
 }
 
-async fn run_pcli2_asset_geometric_match(args: Value) -> Result<String, String> {
+pub(crate) async fn run_pcli2_asset_geometric_match(args: Value) -> Result<String, String> {
     validate_range_f64(&args, "threshold", 0.0, 100.0)?;
     let mut cmd_args: Vec<String> = vec!["asset".to_string(), "geometric-match".to_string()];
 
     if let Some(tenant) = args.get("tenant").and_then(|v| v.as_str()) {
-        cmd_args.push("-t".to_string());
-        cmd_args.push(tenant.to_string());
+        push_value(&mut cmd_args, "-t", tenant)?;
     }
 
     let (uuid, path) = require_uuid_or_path(&args)?;
-    push_opt_string(&mut cmd_args, "--uuid", uuid.as_deref());
-    push_opt_string(&mut cmd_args, "--path", path.as_deref());
+    push_opt_string(&mut cmd_args, "--uuid", uuid.as_deref())?;
+    push_opt_string(&mut cmd_args, "--path", path.as_deref())?;
     push_opt_f64(&mut cmd_args, &args, "threshold", "--threshold");
     push_flag_if(&mut cmd_args, &args, "headers", "--headers");
     push_flag_if(&mut cmd_args, &args, "metadata", "--metadata");
@@ -840,7 +2710,7 @@ async fn run_pcli2_asset_geometric_match(args: Value) -> Result<String, String>
         &mut cmd_args,
         "-f",
         args.get("format").and_then(|v| v.as_str()),
-    );
+    )?;
 
     run_pcli2_command(cmd_args, "pcli2 asset geometric-match").await
 }
@@ -853,7 +2723,7 @@ pub async fn run_pcli2_tenant_list(args: Value) -> Result<String, String> {
         &mut cmd_args,
         "-f",
         args.get("format").and_then(|v| v.as_str()),
-    );
+    )?;
     run_pcli2_command(cmd_args, "pcli2 tenant list").await
 }
 
@@ -870,7 +2740,7 @@ async fn run_pcli2_config_get(args: Value) -> Result<String, String> {
         &mut cmd_args,
         "-f",
         args.get("format").and_then(|v| v.as_str()),
-    );
+    )?;
     run_pcli2_command(cmd_args, "pcli2 config get").await
 }
 
@@ -881,7 +2751,7 @@ async fn run_pcli2_config_get_path(args: Value) -> Result<String, String> {
         &mut cmd_args,
         "-f",
         args.get("format").and_then(|v| v.as_str()),
-    );
+    )?;
     run_pcli2_command(cmd_args, "pcli2 config get path").await
 }
 
@@ -897,7 +2767,7 @@ async fn run_pcli2_config_environment_list(args: Value) -> Result<String, String
         &mut cmd_args,
         "-f",
         args.get("format").and_then(|v| v.as_str()),
-    );
+    )?;
     run_pcli2_command(cmd_args, "pcli2 config environment list").await
 }
 
@@ -911,14 +2781,14 @@ async fn run_pcli2_config_environment_get(args: Value) -> Result<String, String>
         &mut cmd_args,
         "-n",
         args.get("name").and_then(|v| v.as_str()),
-    );
+    )?;
     push_flag_if(&mut cmd_args, &args, "headers", "--headers");
     push_flag_if(&mut cmd_args, &args, "pretty", "--pretty");
     push_opt_string(
         &mut cmd_args,
         "-f",
         args.get("format").and_then(|v| v.as_str()),
-    );
+    )?;
     run_pcli2_command(cmd_args, "pcli2 config environment get").await
 }
 
@@ -930,28 +2800,27 @@ async fn run_pcli2_tenant_get(args: Value) -> Result<String, String> {
         &mut cmd_args,
         "-f",
         args.get("format").and_then(|v| v.as_str()),
-    );
+    )?;
     run_pcli2_command(cmd_args, "pcli2 tenant get").await
 }
 
 async fn run_pcli2_tenant_state(args: Value) -> Result<String, String> {
     let mut cmd_args: Vec<String> = vec!["tenant".to_string(), "state".to_string()];
     if let Some(tenant) = args.get("tenant").and_then(|v| v.as_str()) {
-        cmd_args.push("-t".to_string());
-        cmd_args.push(tenant.to_string());
+        push_value(&mut cmd_args, "-t", tenant)?;
     }
     push_opt_string(
         &mut cmd_args,
         "--type",
         args.get("type").and_then(|v| v.as_str()),
-    );
+    )?;
     push_flag_if(&mut cmd_args, &args, "headers", "--headers");
     push_flag_if(&mut cmd_args, &args, "pretty", "--pretty");
     push_opt_string(
         &mut cmd_args,
         "-f",
         args.get("format").and_then(|v| v.as_str()),
-    );
+    )?;
     run_pcli2_command(cmd_args, "pcli2 tenant state").await
 }
 
@@ -962,8 +2831,7 @@ async fn run_pcli2_tenant_use(args: Value) -> Result<String, String> {
         .and_then(|v| v.as_str())
         .or_else(|| args.get("name").and_then(|v| v.as_str()))
         .ok_or_else(|| "Missing required argument: provide 'tenant_name' or 'name'".to_string())?;
-    cmd_args.push("--name".to_string());
-    cmd_args.push(name.to_string());
+    push_value(&mut cmd_args, "--name", name)?;
     push_flag_if(&mut cmd_args, &args, "refresh", "--refresh");
     push_flag_if(&mut cmd_args, &args, "headers", "--headers");
     push_flag_if(&mut cmd_args, &args, "pretty", "--pretty");
@@ -971,19 +2839,18 @@ async fn run_pcli2_tenant_use(args: Value) -> Result<String, String> {
         &mut cmd_args,
         "-f",
         args.get("format").and_then(|v| v.as_str()),
-    );
+    )?;
     run_pcli2_command(cmd_args, "pcli2 tenant use").await
 }
 
 async fn run_pcli2_folder_get(args: Value) -> Result<String, String> {
     let mut cmd_args: Vec<String> = vec!["folder".to_string(), "get".to_string()];
     if let Some(tenant) = args.get("tenant").and_then(|v| v.as_str()) {
-        cmd_args.push("-t".to_string());
-        cmd_args.push(tenant.to_string());
+        push_value(&mut cmd_args, "-t", tenant)?;
     }
     let (folder_uuid, folder_path) = require_folder_uuid_or_path(&args)?;
-    push_opt_string(&mut cmd_args, "--folder-uuid", folder_uuid.as_deref());
-    push_opt_string(&mut cmd_args, "--folder-path", folder_path.as_deref());
+    push_opt_string(&mut cmd_args, "--folder-uuid", folder_uuid.as_deref())?;
+    push_opt_string(&mut cmd_args, "--folder-path", folder_path.as_deref())?;
     push_flag_if(&mut cmd_args, &args, "metadata", "--metadata");
     push_flag_if(&mut cmd_args, &args, "headers", "--headers");
     push_flag_if(&mut cmd_args, &args, "pretty", "--pretty");
@@ -991,38 +2858,34 @@ async fn run_pcli2_folder_get(args: Value) -> Result<String, String> {
         &mut cmd_args,
         "-f",
         args.get("format").and_then(|v| v.as_str()),
-    );
+    )?;
     run_pcli2_command(cmd_args, "pcli2 folder get").await
 }
 
 async fn run_pcli2_folder_resolve(args: Value) -> Result<String, String> {
     let mut cmd_args: Vec<String> = vec!["folder".to_string(), "resolve".to_string()];
     if let Some(tenant) = args.get("tenant").and_then(|v| v.as_str()) {
-        cmd_args.push("-t".to_string());
-        cmd_args.push(tenant.to_string());
+        push_value(&mut cmd_args, "-t", tenant)?;
     }
     let folder_path = args
         .get("folder_path")
         .and_then(|v| v.as_str())
         .ok_or_else(|| "Missing required argument: 'folder_path'".to_string())?;
-    cmd_args.push("--folder-path".to_string());
-    cmd_args.push(folder_path.to_string());
+    push_value(&mut cmd_args, "--folder-path", folder_path)?;
     run_pcli2_command(cmd_args, "pcli2 folder resolve").await
 }
 
-async fn run_pcli2_folder_dependencies(args: Value) -> Result<String, String> {
+pub(crate) async fn run_pcli2_folder_dependencies(args: Value) -> Result<String, String> {
     let mut cmd_args: Vec<String> = vec!["folder".to_string(), "dependencies".to_string()];
     if let Some(tenant) = args.get("tenant").and_then(|v| v.as_str()) {
-        cmd_args.push("-t".to_string());
-        cmd_args.push(tenant.to_string());
+        push_value(&mut cmd_args, "-t", tenant)?;
     }
     let folder_paths = parse_string_list(&args, "folder_path");
     if folder_paths.is_empty() {
         return Err("Missing required argument: 'folder_path'".to_string());
     }
     for path in folder_paths {
-        cmd_args.push("--folder-path".to_string());
-        cmd_args.push(path);
+        push_value(&mut cmd_args, "--folder-path", &path)?;
     }
     push_flag_if(&mut cmd_args, &args, "headers", "--headers");
     push_flag_if(&mut cmd_args, &args, "metadata", "--metadata");
@@ -1031,26 +2894,24 @@ async fn run_pcli2_folder_dependencies(args: Value) -> Result<String, String> {
         &mut cmd_args,
         "-f",
         args.get("format").and_then(|v| v.as_str()),
-    );
+    )?;
     push_flag_if(&mut cmd_args, &args, "progress", "--progress");
     run_pcli2_command(cmd_args, "pcli2 folder dependencies").await
 }
 
-async fn run_pcli2_folder_geometric_match(args: Value) -> Result<String, String> {
+pub(crate) async fn run_pcli2_folder_geometric_match(args: Value) -> Result<String, String> {
     validate_range_f64(&args, "threshold", 0.0, 100.0)?;
     validate_range_u64(&args, "concurrent", 1, 10)?;
     let mut cmd_args: Vec<String> = vec!["folder".to_string(), "geometric-match".to_string()];
     if let Some(tenant) = args.get("tenant").and_then(|v| v.as_str()) {
-        cmd_args.push("-t".to_string());
-        cmd_args.push(tenant.to_string());
+        push_value(&mut cmd_args, "-t", tenant)?;
     }
     let folder_paths = parse_string_list(&args, "folder_path");
     if folder_paths.is_empty() {
         return Err("Missing required argument: 'folder_path'".to_string());
     }
     for path in folder_paths {
-        cmd_args.push("--folder-path".to_string());
-        cmd_args.push(path);
+        push_value(&mut cmd_args, "--folder-path", &path)?;
     }
     push_opt_f64(&mut cmd_args, &args, "threshold", "--threshold");
     push_flag_if(&mut cmd_args, &args, "exclusive", "--exclusive");
@@ -1061,27 +2922,25 @@ async fn run_pcli2_folder_geometric_match(args: Value) -> Result<String, String>
         &mut cmd_args,
         "-f",
         args.get("format").and_then(|v| v.as_str()),
-    );
+    )?;
     push_opt_u64(&mut cmd_args, &args, "concurrent", "--concurrent");
     push_flag_if(&mut cmd_args, &args, "progress", "--progress");
     run_pcli2_command(cmd_args, "pcli2 folder geometric-match").await
 }
 
-async fn run_pcli2_folder_part_match(args: Value) -> Result<String, String> {
+pub(crate) async fn run_pcli2_folder_part_match(args: Value) -> Result<String, String> {
     validate_range_f64(&args, "threshold", 0.0, 100.0)?;
     validate_range_u64(&args, "concurrent", 1, 10)?;
     let mut cmd_args: Vec<String> = vec!["folder".to_string(), "part-match".to_string()];
     if let Some(tenant) = args.get("tenant").and_then(|v| v.as_str()) {
-        cmd_args.push("-t".to_string());
-        cmd_args.push(tenant.to_string());
+        push_value(&mut cmd_args, "-t", tenant)?;
     }
     let folder_paths = parse_string_list(&args, "folder_path");
     if folder_paths.is_empty() {
         return Err("Missing required argument: 'folder_path'".to_string());
     }
     for path in folder_paths {
-        cmd_args.push("--folder-path".to_string());
-        cmd_args.push(path);
+        push_value(&mut cmd_args, "--folder-path", &path)?;
     }
     push_opt_f64(&mut cmd_args, &args, "threshold", "--threshold");
     push_flag_if(&mut cmd_args, &args, "exclusive", "--exclusive");
@@ -1092,26 +2951,24 @@ async fn run_pcli2_folder_part_match(args: Value) -> Result<String, String> {
         &mut cmd_args,
         "-f",
         args.get("format").and_then(|v| v.as_str()),
-    );
+    )?;
     push_opt_u64(&mut cmd_args, &args, "concurrent", "--concurrent");
     push_flag_if(&mut cmd_args, &args, "progress", "--progress");
     run_pcli2_command(cmd_args, "pcli2 folder part-match").await
 }
 
-async fn run_pcli2_folder_visual_match(args: Value) -> Result<String, String> {
+pub(crate) async fn run_pcli2_folder_visual_match(args: Value) -> Result<String, String> {
     validate_range_u64(&args, "concurrent", 1, 10)?;
     let mut cmd_args: Vec<String> = vec!["folder".to_string(), "visual-match".to_string()];
     if let Some(tenant) = args.get("tenant").and_then(|v| v.as_str()) {
-        cmd_args.push("-t".to_string());
-        cmd_args.push(tenant.to_string());
+        push_value(&mut cmd_args, "-t", tenant)?;
     }
     let folder_paths = parse_string_list(&args, "folder_path");
     if folder_paths.is_empty() {
         return Err("Missing required argument: 'folder_path'".to_string());
     }
     for path in folder_paths {
-        cmd_args.push("--folder-path".to_string());
-        cmd_args.push(path);
+        push_value(&mut cmd_args, "--folder-path", &path)?;
     }
     push_flag_if(&mut cmd_args, &args, "exclusive", "--exclusive");
     push_flag_if(&mut cmd_args, &args, "headers", "--headers");
@@ -1121,21 +2978,20 @@ async fn run_pcli2_folder_visual_match(args: Value) -> Result<String, String> {
         &mut cmd_args,
         "-f",
         args.get("format").and_then(|v| v.as_str()),
-    );
+    )?;
     push_opt_u64(&mut cmd_args, &args, "concurrent", "--concurrent");
     push_flag_if(&mut cmd_args, &args, "progress", "--progress");
     run_pcli2_command(cmd_args, "pcli2 folder visual-match").await
 }
 
-async fn run_pcli2_asset_get(args: Value) -> Result<String, String> {
+pub(crate) async fn run_pcli2_asset_get(args: Value) -> Result<String, String> {
     let mut cmd_args: Vec<String> = vec!["asset".to_string(), "get".to_string()];
     if let Some(tenant) = args.get("tenant").and_then(|v| v.as_str()) {
-        cmd_args.push("-t".to_string());
-        cmd_args.push(tenant.to_string());
+        push_value(&mut cmd_args, "-t", tenant)?;
     }
     let (uuid, path) = require_uuid_or_path(&args)?;
-    push_opt_string(&mut cmd_args, "--uuid", uuid.as_deref());
-    push_opt_string(&mut cmd_args, "--path", path.as_deref());
+    push_opt_string(&mut cmd_args, "--uuid", uuid.as_deref())?;
+    push_opt_string(&mut cmd_args, "--path", path.as_deref())?;
     push_flag_if(&mut cmd_args, &args, "headers", "--headers");
     push_flag_if(&mut cmd_args, &args, "metadata", "--metadata");
     push_flag_if(&mut cmd_args, &args, "pretty", "--pretty");
@@ -1143,19 +2999,18 @@ async fn run_pcli2_asset_get(args: Value) -> Result<String, String> {
         &mut cmd_args,
         "-f",
         args.get("format").and_then(|v| v.as_str()),
-    );
+    )?;
     run_pcli2_command(cmd_args, "pcli2 asset get").await
 }
 
 async fn run_pcli2_asset_dependencies(args: Value) -> Result<String, String> {
     let mut cmd_args: Vec<String> = vec!["asset".to_string(), "dependencies".to_string()];
     if let Some(tenant) = args.get("tenant").and_then(|v| v.as_str()) {
-        cmd_args.push("-t".to_string());
-        cmd_args.push(tenant.to_string());
+        push_value(&mut cmd_args, "-t", tenant)?;
     }
     let (uuid, path) = require_uuid_or_path(&args)?;
-    push_opt_string(&mut cmd_args, "--uuid", uuid.as_deref());
-    push_opt_string(&mut cmd_args, "--path", path.as_deref());
+    push_opt_string(&mut cmd_args, "--uuid", uuid.as_deref())?;
+    push_opt_string(&mut cmd_args, "--path", path.as_deref())?;
     push_flag_if(&mut cmd_args, &args, "metadata", "--metadata");
     push_flag_if(&mut cmd_args, &args, "headers", "--headers");
     push_flag_if(&mut cmd_args, &args, "pretty", "--pretty");
@@ -1163,30 +3018,30 @@ async fn run_pcli2_asset_dependencies(args: Value) -> Result<String, String> {
         &mut cmd_args,
         "-f",
         args.get("format").and_then(|v| v.as_str()),
-    );
+    )?;
     run_pcli2_command(cmd_args, "pcli2 asset dependencies").await
 }
 
-async fn run_pcli2_asset_thumbnail(args: Value) -> Result<String, String> {
+pub(crate) async fn run_pcli2_asset_thumbnail(args: Value) -> Result<String, String> {
     let mut cmd_args: Vec<String> = vec!["asset".to_string(), "thumbnail".to_string()];
     if let Some(tenant) = args.get("tenant").and_then(|v| v.as_str()) {
-        cmd_args.push("-t".to_string());
-        cmd_args.push(tenant.to_string());
+        push_value(&mut cmd_args, "-t", tenant)?;
     }
     let (uuid, path) = require_uuid_or_path(&args)?;
-    push_opt_string(&mut cmd_args, "--uuid", uuid.as_deref());
-    push_opt_string(&mut cmd_args, "--path", path.as_deref());
+    push_opt_string(&mut cmd_args, "--uuid", uuid.as_deref())?;
+    push_opt_string(&mut cmd_args, "--path", path.as_deref())?;
     let temp_path = temp_thumbnail_path()?;
     let temp_path_str = temp_path
         .to_str()
         .ok_or_else(|| "Failed to build temporary thumbnail path".to_string())?;
-    push_opt_string(&mut cmd_args, "--file", Some(temp_path_str));
+    push_opt_string(&mut cmd_args, "--file", Some(temp_path_str))?;
     run_pcli2_command(cmd_args, "pcli2 asset thumbnail").await?;
 
     let bytes_result =
         fs::read(&temp_path).map_err(|err| format!("Failed to read thumbnail output: {}", err));
     let _ = fs::remove_file(&temp_path);
     let bytes = bytes_result?;
+    record_download(bytes.len());
     if !bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
         return Err("Thumbnail output was not a valid PNG file.".to_string());
     }
@@ -1194,15 +3049,36 @@ async fn run_pcli2_asset_thumbnail(args: Value) -> Result<String, String> {
     Ok(encoded)
 }
 
+/// Runs `pcli2 asset download` into `file`, followed by the `conversion`
+/// arguments configured for the export.
+pub(crate) async fn run_pcli2_asset_download(
+    args: &Value,
+    file: &Path,
+    conversion: Vec<String>,
+) -> Result<String, String> {
+    let mut cmd_args: Vec<String> = vec!["asset".to_string(), "download".to_string()];
+    if let Some(tenant) = args.get("tenant").and_then(|v| v.as_str()) {
+        push_value(&mut cmd_args, "-t", tenant)?;
+    }
+    let (uuid, path) = require_uuid_or_path(args)?;
+    push_opt_string(&mut cmd_args, "--uuid", uuid.as_deref())?;
+    push_opt_string(&mut cmd_args, "--path", path.as_deref())?;
+    let file = file
+        .to_str()
+        .ok_or_else(|| "Failed to build temporary download path".to_string())?;
+    push_opt_string(&mut cmd_args, "--file", Some(file))?;
+    cmd_args.extend(conversion);
+    run_pcli2_command(cmd_args, "pcli2 asset download").await
+}
+
 async fn run_pcli2_asset_reprocess(args: Value) -> Result<String, String> {
     let mut cmd_args: Vec<String> = vec!["asset".to_string(), "reprocess".to_string()];
     if let Some(tenant) = args.get("tenant").and_then(|v| v.as_str()) {
-        cmd_args.push("-t".to_string());
-        cmd_args.push(tenant.to_string());
+        push_value(&mut cmd_args, "-t", tenant)?;
     }
     let (uuid, path) = require_uuid_or_path(&args)?;
-    push_opt_string(&mut cmd_args, "--uuid", uuid.as_deref());
-    push_opt_string(&mut cmd_args, "--path", path.as_deref());
+    push_opt_string(&mut cmd_args, "--uuid", uuid.as_deref())?;
+    push_opt_string(&mut cmd_args, "--path", path.as_deref())?;
     run_pcli2_command(cmd_args, "pcli2 asset reprocess").await
 }
 
@@ -1210,12 +3086,11 @@ async fn run_pcli2_asset_part_match(args: Value) -> Result<String, String> {
     validate_range_f64(&args, "threshold", 0.0, 100.0)?;
     let mut cmd_args: Vec<String> = vec!["asset".to_string(), "part-match".to_string()];
     if let Some(tenant) = args.get("tenant").and_then(|v| v.as_str()) {
-        cmd_args.push("-t".to_string());
-        cmd_args.push(tenant.to_string());
+        push_value(&mut cmd_args, "-t", tenant)?;
     }
     let (uuid, path) = require_uuid_or_path(&args)?;
-    push_opt_string(&mut cmd_args, "--uuid", uuid.as_deref());
-    push_opt_string(&mut cmd_args, "--path", path.as_deref());
+    push_opt_string(&mut cmd_args, "--uuid", uuid.as_deref())?;
+    push_opt_string(&mut cmd_args, "--path", path.as_deref())?;
     push_opt_f64(&mut cmd_args, &args, "threshold", "--threshold");
     push_flag_if(&mut cmd_args, &args, "headers", "--headers");
     push_flag_if(&mut cmd_args, &args, "metadata", "--metadata");
@@ -1224,19 +3099,18 @@ async fn run_pcli2_asset_part_match(args: Value) -> Result<String, String> {
         &mut cmd_args,
         "-f",
         args.get("format").and_then(|v| v.as_str()),
-    );
+    )?;
     run_pcli2_command(cmd_args, "pcli2 asset part-match").await
 }
 
 async fn run_pcli2_asset_visual_match(args: Value) -> Result<String, String> {
     let mut cmd_args: Vec<String> = vec!["asset".to_string(), "visual-match".to_string()];
     if let Some(tenant) = args.get("tenant").and_then(|v| v.as_str()) {
-        cmd_args.push("-t".to_string());
-        cmd_args.push(tenant.to_string());
+        push_value(&mut cmd_args, "-t", tenant)?;
     }
     let (uuid, path) = require_uuid_or_path(&args)?;
-    push_opt_string(&mut cmd_args, "--uuid", uuid.as_deref());
-    push_opt_string(&mut cmd_args, "--path", path.as_deref());
+    push_opt_string(&mut cmd_args, "--uuid", uuid.as_deref())?;
+    push_opt_string(&mut cmd_args, "--path", path.as_deref())?;
     push_flag_if(&mut cmd_args, &args, "headers", "--headers");
     push_flag_if(&mut cmd_args, &args, "metadata", "--metadata");
     push_flag_if(&mut cmd_args, &args, "pretty", "--pretty");
@@ -1244,22 +3118,20 @@ async fn run_pcli2_asset_visual_match(args: Value) -> Result<String, String> {
         &mut cmd_args,
         "-f",
         args.get("format").and_then(|v| v.as_str()),
-    );
+    )?;
     run_pcli2_command(cmd_args, "pcli2 asset visual-match").await
 }
 
-async fn run_pcli2_asset_text_match(args: Value) -> Result<String, String> {
+pub(crate) async fn run_pcli2_asset_text_match(args: Value) -> Result<String, String> {
     let mut cmd_args: Vec<String> = vec!["asset".to_string(), "text-match".to_string()];
     if let Some(tenant) = args.get("tenant").and_then(|v| v.as_str()) {
-        cmd_args.push("-t".to_string());
-        cmd_args.push(tenant.to_string());
+        push_value(&mut cmd_args, "-t", tenant)?;
     }
     let text = args
         .get("text")
         .and_then(|v| v.as_str())
         .ok_or_else(|| "Missing required argument: 'text'".to_string())?;
-    cmd_args.push("--text".to_string());
-    cmd_args.push(text.to_string());
+    push_value(&mut cmd_args, "--text", text)?;
     push_flag_if(&mut cmd_args, &args, "fuzzy", "--fuzzy");
     push_flag_if(&mut cmd_args, &args, "headers", "--headers");
     push_flag_if(&mut cmd_args, &args, "metadata", "--metadata");
@@ -1268,7 +3140,7 @@ async fn run_pcli2_asset_text_match(args: Value) -> Result<String, String> {
         &mut cmd_args,
         "-f",
         args.get("format").and_then(|v| v.as_str()),
-    );
+    )?;
     run_pcli2_command(cmd_args, "pcli2 asset text-match").await
 }
 
@@ -1279,12 +3151,11 @@ async fn run_pcli2_asset_metadata_create(args: Value) -> Result<String, String>
         "create".to_string(),
     ];
     if let Some(tenant) = args.get("tenant").and_then(|v| v.as_str()) {
-        cmd_args.push("-t".to_string());
-        cmd_args.push(tenant.to_string());
+        push_value(&mut cmd_args, "-t", tenant)?;
     }
     let (uuid, path) = require_uuid_or_path(&args)?;
-    push_opt_string(&mut cmd_args, "--uuid", uuid.as_deref());
-    push_opt_string(&mut cmd_args, "--path", path.as_deref());
+    push_opt_string(&mut cmd_args, "--uuid", uuid.as_deref())?;
+    push_opt_string(&mut cmd_args, "--path", path.as_deref())?;
 
     let name = args
         .get("name")
@@ -1294,18 +3165,31 @@ async fn run_pcli2_asset_metadata_create(args: Value) -> Result<String, String>
         .get("value")
         .and_then(|v| v.as_str())
         .ok_or_else(|| "Missing required argument: 'value'".to_string())?;
-    cmd_args.push("--name".to_string());
-    cmd_args.push(name.to_string());
-    cmd_args.push("--value".to_string());
-    cmd_args.push(value.to_string());
+    push_value(&mut cmd_args, "--name", name)?;
+    push_value(&mut cmd_args, "--value", value)?;
     push_opt_string(
         &mut cmd_args,
         "--type",
         args.get("type").and_then(|v| v.as_str()),
-    );
+    )?;
     run_pcli2_command(cmd_args, "pcli2 asset metadata create").await
 }
 
+/// Runs `pcli2 asset create` for `file` in the call's `folder_path`.
+pub(crate) async fn run_pcli2_asset_create(args: &Value, file: &Path) -> Result<String, String> {
+    let mut cmd_args: Vec<String> = vec!["asset".to_string(), "create".to_string()];
+    if let Some(tenant) = args.get("tenant").and_then(|v| v.as_str()) {
+        push_value(&mut cmd_args, "-t", tenant)?;
+    }
+    push_value(&mut cmd_args, "--file", &file.display().to_string())?;
+    let folder_path = args
+        .get("folder_path")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| "Missing required argument: 'folder_path'".to_string())?;
+    push_value(&mut cmd_args, "--folder-path", folder_path)?;
+    run_pcli2_command(cmd_args, "pcli2 asset create").await
+}
+
 async fn run_pcli2_asset_metadata_delete(args: Value) -> Result<String, String> {
     let mut cmd_args: Vec<String> = vec![
         "asset".to_string(),
@@ -1313,12 +3197,11 @@ async fn run_pcli2_asset_metadata_delete(args: Value) -> Result<String, String>
         "delete".to_string(),
     ];
     if let Some(tenant) = args.get("tenant").and_then(|v| v.as_str()) {
-        cmd_args.push("-t".to_string());
-        cmd_args.push(tenant.to_string());
+        push_value(&mut cmd_args, "-t", tenant)?;
     }
     let (uuid, path) = require_uuid_or_path(&args)?;
-    push_opt_string(&mut cmd_args, "--uuid", uuid.as_deref());
-    push_opt_string(&mut cmd_args, "--path", path.as_deref());
+    push_opt_string(&mut cmd_args, "--uuid", uuid.as_deref())?;
+    push_opt_string(&mut cmd_args, "--path", path.as_deref())?;
     let names: Vec<String> = match args.get("name") {
         Some(Value::Array(values)) => values
             .iter()
@@ -1340,18 +3223,17 @@ async fn run_pcli2_asset_metadata_delete(args: Value) -> Result<String, String>
         return Err("Missing required argument: 'name'".to_string());
     }
     for name in names {
-        cmd_args.push("--name".to_string());
-        cmd_args.push(name);
+        push_value(&mut cmd_args, "--name", &name)?;
     }
     push_opt_string(
         &mut cmd_args,
         "-f",
         args.get("format").and_then(|v| v.as_str()),
-    );
+    )?;
     run_pcli2_command(cmd_args, "pcli2 asset metadata delete").await
 }
 
-fn parse_string_list(args: &Value, key: &str) -> Vec<String> {
+pub(crate) fn parse_string_list(args: &Value, key: &str) -> Vec<String> {
     match args.get(key) {
         Some(Value::Array(values)) => values
             .iter()
@@ -1362,14 +3244,31 @@ fn parse_string_list(args: &Value, key: &str) -> Vec<String> {
     }
 }
 
+/// The running tool call's working directory (`workspace` config).
+pub(crate) fn call_workspace() -> Option<PathBuf> {
+    current_execution().workspace
+}
+
+/// Where a tool call writes the files it downloads: its working directory,
+/// else the temp directory.
+pub(crate) fn scratch_dir() -> PathBuf {
+    call_workspace().unwrap_or_else(env::temp_dir)
+}
+
 fn temp_thumbnail_path() -> Result<PathBuf, String> {
-    let mut path = env::temp_dir();
+    // Thumbnails downloaded in the same millisecond need different files.
+    static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+    let mut path = scratch_dir();
     let timestamp = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .map_err(|err| format!("Failed to read system time: {}", err))?
         .as_millis();
     let pid = std::process::id();
-    path.push(format!("pcli2-thumbnail-{}-{}.png", pid, timestamp));
+    let sequence = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+    path.push(format!(
+        "pcli2-thumbnail-{}-{}-{}.png",
+        pid, timestamp, sequence
+    ));
     Ok(path)
 }
 
@@ -1405,7 +3304,12 @@ fn require_folder_uuid_or_path(args: &Value) -> Result<(Option<String>, Option<S
     Ok((uuid, path))
 }
 
-fn validate_range_f64(args: &Value, key: &str, min: f64, max: f64) -> Result<(), String> {
+pub(crate) fn validate_range_f64(
+    args: &Value,
+    key: &str,
+    min: f64,
+    max: f64,
+) -> Result<(), String> {
     if let Some(value) = args.get(key).and_then(|v| v.as_f64())
         && (value < min || value > max)
     {
@@ -1417,7 +3321,12 @@ fn validate_range_f64(args: &Value, key: &str, min: f64, max: f64) -> Result<(),
     Ok(())
 }
 
-fn validate_range_u64(args: &Value, key: &str, min: u64, max: u64) -> Result<(), String> {
+pub(crate) fn validate_range_u64(
+    args: &Value,
+    key: &str,
+    min: u64,
+    max: u64,
+) -> Result<(), String> {
     if let Some(value) = args.get(key).and_then(|v| v.as_u64())
         && (value < min || value > max)
     {
@@ -1435,10 +3344,41 @@ fn push_flag_if(cmd_args: &mut Vec<String>, args: &Value, key: &str, flag: &str)
     }
 }
 
-fn push_opt_string(cmd_args: &mut Vec<String>, flag: &str, value: Option<&str>) {
-    if let Some(value) = value {
+/// Flags whose values are free text and may legitimately start with `-`.
+/// pcli2 takes no positional arguments, so there is no `--` separator to put
+/// them behind; they are attached as `--flag=value` instead.
+const FREE_TEXT_FLAGS: [&str; 2] = ["--text", "--value"];
+
+fn push_value(cmd_args: &mut Vec<String>, flag: &str, value: &str) -> Result<(), String> {
+    let free_text = FREE_TEXT_FLAGS.contains(&flag);
+    if value.contains('\0') || (!free_text && value.chars().any(char::is_control)) {
+        return Err(format!(
+            "Invalid value for {}: control characters are not allowed",
+            flag
+        ));
+    }
+    if !value.starts_with('-') {
         cmd_args.push(flag.to_string());
         cmd_args.push(value.to_string());
+    } else if free_text {
+        cmd_args.push(format!("{}={}", flag, value));
+    } else {
+        return Err(format!(
+            "Invalid value '{}' for {}: values starting with '-' would be read as a pcli2 option",
+            value, flag
+        ));
+    }
+    Ok(())
+}
+
+fn push_opt_string(
+    cmd_args: &mut Vec<String>,
+    flag: &str,
+    value: Option<&str>,
+) -> Result<(), String> {
+    match value {
+        Some(value) => push_value(cmd_args, flag, value),
+        None => Ok(()),
     }
 }
 
@@ -1506,79 +3446,185 @@ pub async fn read_limited<R: AsyncRead + Unpin>(
 }
 
 pub async fn run_pcli2_command(cmd_args: Vec<String>, label: &str) -> Result<String, String> {
-    let rendered = cmd_args
+    let execution = current_execution();
+    let policy = execution.policy;
+    let redactor = redactor();
+    let rendered = redactor
+        .redact_args(&cmd_args)
         .iter()
         .map(|arg| shell_escape_arg(arg))
         .collect::<Vec<_>>()
         .join(" ");
     info!("▶ pcli2 {}", rendered);
-    let mut child = tokio::process::Command::new(pcli2_executable())
-        .args(&cmd_args)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .map_err(|e| format!("Failed to execute pcli2: {}", e))?;
-
-    let stdout = child
-        .stdout
-        .take()
-        .ok_or_else(|| "Failed to capture pcli2 stdout".to_string())?;
-    let stderr = child
-        .stderr
-        .take()
-        .ok_or_else(|| "Failed to capture pcli2 stderr".to_string())?;
-
-    let stdout_task = tokio::spawn(read_limited(stdout, MAX_PCLI2_OUTPUT_BYTES, "stdout"));
-    let stderr_task = tokio::spawn(read_limited(stderr, MAX_PCLI2_OUTPUT_BYTES, "stderr"));
-
-    let output = tokio::time::timeout(PCLI2_TIMEOUT, async {
-        let status = child
-            .wait()
-            .await
-            .map_err(|err| format!("Failed waiting for pcli2: {}", err))?;
-        let stdout = stdout_task
-            .await
-            .map_err(|err| format!("Failed to read pcli2 stdout: {}", err))??;
-        let stderr = stderr_task
-            .await
-            .map_err(|err| format!("Failed to read pcli2 stderr: {}", err))??;
-        Ok((status, stdout, stderr))
-    })
-    .await;
-
-    let (status, stdout, stderr) = match output {
-        Ok(Ok(output)) => output,
-        Ok(Err(message)) => {
-            let _ = child.kill().await;
-            return Err(message);
-        }
-        Err(_) => {
-            let _ = child.kill().await;
-            return Err(format!(
-                "{} failed: timed out after {:?}",
-                label, PCLI2_TIMEOUT
-            ));
+    let secrets = redactor.secrets(&cmd_args);
+    let started = Instant::now();
+    let mut attempt = 0;
+    let mut reauthenticated = false;
+    let outcome = loop {
+        // Runs end at the client's deadline, when it comes before the policy's.
+        let (timeout, clamped) = match &execution.deadline {
+            Some(deadline) => {
+                let remaining = deadline.remaining();
+                if remaining.is_zero() {
+                    deadline.mark_hit();
+                    break Err(format!(
+                        "{} skipped: the client's deadline has passed",
+                        label
+                    ));
+                }
+                (remaining.min(policy.timeout), remaining < policy.timeout)
+            }
+            None => (policy.timeout, false),
+        };
+        let outcome = run_pcli2_attempt(
+            &cmd_args,
+            label,
+            timeout,
+            &execution,
+            execution.sandbox.as_ref(),
+        )
+        .await
+        .map_err(|message| redactor.redact_text(&message, &secrets));
+        match outcome {
+            Err(message)
+                if clamped
+                    && execution
+                        .deadline
+                        .as_ref()
+                        .is_some_and(|deadline| deadline.remaining().is_zero()) =>
+            {
+                if let Some(deadline) = &execution.deadline {
+                    deadline.mark_hit();
+                }
+                break Err(message);
+            }
+            Err(message)
+                if !reauthenticated
+                    && execution.reauth.as_ref().is_some_and(|reauth| {
+                        reauth.is_enabled() && reauth.is_token_error(&message)
+                    }) =>
+            {
+                reauthenticated = true;
+                if let Some(reauth) = &execution.reauth
+                    && let Err(err) = reauthenticate(reauth, started, &execution).await
+                {
+                    warn!("⚠ pcli2 re-authentication failed: {}", err);
+                    break Err(message);
+                }
+            }
+            Err(message) if attempt < policy.retries => {
+                attempt += 1;
+                warn!(
+                    "↻ {} failed, retrying ({}/{}): {}",
+                    label,
+                    attempt,
+                    policy.retries,
+                    message.lines().next().unwrap_or_default()
+                );
+                tokio::time::sleep(RETRY_BACKOFF * attempt).await;
+            }
+            outcome => break outcome,
         }
     };
+    // A run the client's deadline cut short says nothing about the backend.
+    if execution
+        .deadline
+        .as_ref()
+        .is_some_and(|deadline| outcome.is_err() && deadline.remaining().is_zero())
+    {
+        return outcome;
+    }
+    if let Some(health) = &execution.health {
+        health.record(outcome.is_ok(), started.elapsed());
+    }
+    if let Some(breaker) = &execution.breaker {
+        match &outcome {
+            Ok(_) => breaker.record_success(),
+            Err(message) => {
+                let was_open = breaker.is_open();
+                breaker.record_failure(message);
+                if !was_open && breaker.is_open() {
+                    warn!(
+                        "⛔ pcli2 keeps failing; circuit breaker opened: {}",
+                        message
+                    );
+                }
+            }
+        }
+    }
+    outcome
+}
 
-    let stdout = String::from_utf8_lossy(&stdout);
-    let stderr = String::from_utf8_lossy(&stderr);
+async fn reauthenticate(
+    reauth: &Reauthenticator,
+    since: Instant,
+    execution: &ExecutionContext,
+) -> Result<(), String> {
+    let sandbox = execution.sandbox.as_ref().map(Sandbox::online);
+    reauth
+        .refresh(since, |args| async move {
+            info!(
+                "🔑 pcli2 token expired; running pcli2 {}",
+                reauth.display_args()
+            );
+            run_pcli2_attempt(
+                &args,
+                "pcli2 re-authentication",
+                PROBE_TIMEOUT,
+                execution,
+                sandbox.as_ref(),
+            )
+            .await
+            .map_err(|message| redactor().redact_text(&message, &redactor().secrets(&args)))
+        })
+        .await
+}
 
-    if status.success() {
-        Ok(stdout.trim_end().to_string())
-    } else {
-        Err(format!(
-            "{} failed (code {}):\n{}\n{}",
+async fn run_pcli2_attempt(
+    cmd_args: &[String],
+    label: &str,
+    timeout: Duration,
+    execution: &ExecutionContext,
+    sandbox: Option<&Sandbox>,
+) -> Result<String, String> {
+    let cassette = execution.cassette.as_deref();
+    if let Some(cassette) = cassette.filter(|cassette| cassette.is_replay()) {
+        let recording = cassette.play(cmd_args)?;
+        return CommandOutput::new(recording.exit_code, recording.stdout, recording.stderr)
+            .into_result(label);
+    }
+
+    let output = execution
+        .runner()
+        .run(CommandRequest {
+            args: cmd_args,
             label,
-            status,
-            stdout.trim_end(),
-            stderr.trim_end()
-        ))
+            timeout,
+            sandbox,
+            pcli2: execution.pcli2.as_deref(),
+            cwd: execution.workspace.as_deref(),
+        })
+        .await?;
+    if let Some(usage) = &execution.usage {
+        usage.record_run(cmd_args, output.cpu, output.stdout.len());
+    }
+    if let Some(cassette) = cassette
+        && let Err(err) =
+            cassette.append(cmd_args, output.exit_code, &output.stdout, &output.stderr)
+    {
+        warn!("⚠ {}", err);
     }
+    output.into_result(label)
 }
 
+/// `PCLI2_BIN`, else the copy installed by `install-pcli2`, else `pcli2`
+/// from `PATH`.
 pub fn pcli2_executable() -> String {
-    env::var(PCLI2_BIN_ENV).unwrap_or_else(|_| "pcli2".to_string())
+    env::var(PCLI2_BIN_ENV).unwrap_or_else(|_| {
+        managed_pcli2()
+            .map(|path| path.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "pcli2".to_string())
+    })
 }
 
 #[cfg(test)]
@@ -1623,18 +3669,46 @@ mod tests {
     #[test]
     fn test_push_opt_string_some() {
         let mut cmd_args: Vec<String> = vec![];
-        push_opt_string(&mut cmd_args, "--opt", Some("value"));
+        push_opt_string(&mut cmd_args, "--opt", Some("value")).unwrap();
         assert_eq!(cmd_args, vec!["--opt".to_string(), "value".to_string()]);
     }
 
     #[test]
     fn test_push_opt_string_none() {
         let mut cmd_args: Vec<String> = vec![];
-        push_opt_string(&mut cmd_args, "--opt", None);
+        push_opt_string(&mut cmd_args, "--opt", None).unwrap();
         let expected: Vec<String> = vec![];
         assert_eq!(cmd_args, expected);
     }
 
+    #[test]
+    fn test_push_value_rejects_flag_like_values() {
+        let mut cmd_args: Vec<String> = vec![];
+        let err = push_value(&mut cmd_args, "--folder-path", "--help").unwrap_err();
+        assert!(err.contains("would be read as a pcli2 option"));
+        assert!(push_value(&mut cmd_args, "-t", "-x").is_err());
+        assert!(push_value(&mut cmd_args, "--uuid", "abc\n--help").is_err());
+        assert!(cmd_args.is_empty());
+    }
+
+    #[test]
+    fn test_push_value_attaches_free_text() {
+        let mut cmd_args: Vec<String> = vec![];
+        push_value(&mut cmd_args, "--text", "-rev B").unwrap();
+        push_value(&mut cmd_args, "--value", "-12.5").unwrap();
+        push_value(&mut cmd_args, "--text", "bracket").unwrap();
+        assert_eq!(
+            cmd_args,
+            vec![
+                "--text=-rev B".to_string(),
+                "--value=-12.5".to_string(),
+                "--text".to_string(),
+                "bracket".to_string()
+            ]
+        );
+        assert!(push_value(&mut cmd_args, "--text", "a\0b").is_err());
+    }
+
     #[test]
     fn test_push_opt_f64() {
         let mut cmd_args = vec![];
@@ -1796,6 +3870,62 @@ mod tests {
         assert_eq!(result, vec!["single-item"]);
     }
 
+    #[test]
+    fn test_tool_examples_match_schemas() {
+        for tool in tool_list() {
+            let Some(examples) = tool["_meta"]["examples"].as_array() else {
+                continue;
+            };
+            for example in examples {
+                let result = crate::args::prepare_arguments(
+                    &tool["inputSchema"],
+                    example["arguments"].clone(),
+                    crate::args::ArgMode::Strict,
+                );
+                assert!(
+                    result.is_ok(),
+                    "example for {} is invalid: {:?}",
+                    tool["name"],
+                    result
+                );
+            }
+            assert!(tool["description"].as_str().unwrap().contains("Examples:"));
+        }
+    }
+
+    #[test]
+    fn test_schemas_are_closed_and_refs_resolve() {
+        for tool in tool_list() {
+            let schema = &tool["inputSchema"];
+            assert_eq!(schema["additionalProperties"], false, "{}", tool["name"]);
+            let properties = schema["properties"].as_object().unwrap();
+            for prop in properties.values() {
+                if let Some(reference) = prop["$ref"].as_str() {
+                    let name = reference.strip_prefix(DEFS_REF_PREFIX).unwrap();
+                    assert!(
+                        schema["$defs"][name].is_object(),
+                        "{} references missing definition {}",
+                        tool["name"],
+                        name
+                    );
+                }
+            }
+            if let Some(format) = properties.get("format") {
+                let name = format["$ref"].as_str().unwrap();
+                let name = name.strip_prefix(DEFS_REF_PREFIX).unwrap();
+                assert!(schema["$defs"][name]["enum"].is_array());
+            }
+        }
+    }
+
+    #[test]
+    fn test_is_asset_fan_out() {
+        assert!(is_asset_fan_out(&json!({"uuid": ["a", "b"]})));
+        assert!(is_asset_fan_out(&json!({"path": ["/A"]})));
+        assert!(!is_asset_fan_out(&json!({"uuid": "a"})));
+        assert!(!is_asset_fan_out(&json!({})));
+    }
+
     #[test]
     fn test_parse_string_list_empty() {
         let args = json!({});
@@ -0,0 +1,666 @@
+use crate::{
+    AppState,
+    config::{PlanRule, PlanStep},
+    content::{raw_output_block, summary_block, tool_result},
+    export::EXPORT_TOOL,
+    gallery::GALLERY_TOOL,
+    search::MULTI_TENANT_SEARCH_TOOL,
+    suggest::FOLDER_SUGGEST_TOOL,
+};
+use serde_json::{Map, Value, json};
+
+pub const PLAN_TOOL: &str = "pcli2_plan";
+const MAX_REQUEST_CHARS: usize = 2000;
+/// Words that introduce the name of the asset a request is about.
+const TEXT_MARKERS: [&str; 6] = [
+    "similar to ",
+    "named ",
+    "called ",
+    "search for ",
+    "look for ",
+    "like ",
+];
+/// Words that end a name taken from the request.
+const TEXT_STOPS: [&str; 8] = [
+    " in ", " from ", " with ", " above ", " over ", " at ", " on ", " and ",
+];
+const ARTICLES: [&str; 5] = ["the", "a", "an", "this", "my"];
+
+/// Details taken from a free-text request.
+#[derive(Debug, Default, PartialEq)]
+struct Entities {
+    uuid: Option<String>,
+    path: Option<String>,
+    folder_path: Option<String>,
+    folder_name: Option<String>,
+    text: Option<String>,
+    tenant: Option<String>,
+    threshold: Option<f64>,
+    metadata: Option<(String, String)>,
+}
+
+fn step(tool: &str, inputs: &[&str], arguments: Value) -> PlanStep {
+    PlanStep {
+        tool: tool.to_string(),
+        inputs: inputs.iter().map(|input| input.to_string()).collect(),
+        arguments: match arguments {
+            Value::Object(arguments) => arguments,
+            _ => Map::new(),
+        },
+    }
+}
+
+fn rule(keywords: &[&str], description: &str, steps: Vec<PlanStep>) -> PlanRule {
+    PlanRule {
+        keywords: keywords.iter().map(|keyword| keyword.to_string()).collect(),
+        description: description.to_string(),
+        steps,
+    }
+}
+
+/// The rules used when no configured rule fits better; earlier rules win ties.
+pub fn builtin_rules() -> Vec<PlanRule> {
+    let json_format = json!({ "format": "json" });
+    vec![
+        rule(
+            &[
+                "similar",
+                "geometric",
+                "shape",
+                "alike",
+                "resembl",
+                "lookalike",
+            ],
+            "Find assets geometrically similar to an asset",
+            vec![step(
+                "pcli2_geometric_match",
+                &["asset", "tenant", "threshold"],
+                json_format.clone(),
+            )],
+        ),
+        rule(
+            &["duplicate", "duplicates"],
+            "Find duplicate assets within a folder",
+            vec![step(
+                "pcli2_folder_geometric_match",
+                &["folder", "tenant", "threshold"],
+                json!({ "format": "json", "exclusive": true }),
+            )],
+        ),
+        rule(
+            &["visual", "visually", "looks like", "image"],
+            "Find visually similar assets",
+            vec![step(
+                "pcli2_asset_visual_match",
+                &["asset", "tenant"],
+                json_format.clone(),
+            )],
+        ),
+        rule(
+            &["part match", "contains", "component", "subpart", "used in"],
+            "Find assets that contain an asset as a part",
+            vec![step(
+                "pcli2_asset_part_match",
+                &["asset", "tenant", "threshold"],
+                json_format.clone(),
+            )],
+        ),
+        rule(
+            &["metadata", "set ", "tag", "label", "property"],
+            "Set a metadata property on an asset",
+            vec![step(
+                "pcli2_asset_metadata_create",
+                &["asset", "metadata", "tenant"],
+                json!({}),
+            )],
+        ),
+        rule(
+            &[
+                "gallery",
+                "thumbnails",
+                "thumbnails of",
+                "folder thumbnails",
+            ],
+            "Browse the thumbnails of a folder's assets",
+            vec![step(GALLERY_TOOL, &["folder", "tenant"], json!({}))],
+        ),
+        rule(
+            &["export", "download", "convert", "step file", "stl file"],
+            "Export an asset's file",
+            vec![step(EXPORT_TOOL, &["asset", "tenant"], json!({}))],
+        ),
+        rule(
+            &["thumbnail", "picture", "preview", "show me"],
+            "Show an asset's thumbnail",
+            vec![step(
+                "pcli2_asset_thumbnail",
+                &["asset", "tenant"],
+                json!({}),
+            )],
+        ),
+        rule(
+            &["dependencies", "depends", "bom", "assembly", "children"],
+            "List an asset's dependencies",
+            vec![step(
+                "pcli2_asset_dependencies",
+                &["asset", "tenant"],
+                json_format.clone(),
+            )],
+        ),
+        rule(
+            &["every tenant", "all tenants", "across tenants"],
+            "Search several tenants at once",
+            vec![step(MULTI_TENANT_SEARCH_TOOL, &["text"], json!({}))],
+        ),
+        rule(
+            &["search", "named", "called", "find", "text"],
+            "Search assets by name or metadata text",
+            vec![step(
+                "pcli2_asset_text_match",
+                &["text", "tenant"],
+                json!({ "format": "json", "fuzzy": true }),
+            )],
+        ),
+        rule(
+            &["subfolders", "folders", "folder tree", "hierarchy"],
+            "List folders",
+            vec![step(
+                "pcli2_folder_list",
+                &["folder?", "tenant"],
+                json_format.clone(),
+            )],
+        ),
+        rule(
+            &["list", "contents", "what's in", "assets in"],
+            "List the assets in a folder",
+            vec![step(
+                "pcli2_asset_list",
+                &["folder?", "tenant"],
+                json_format.clone(),
+            )],
+        ),
+        rule(
+            &["tenants", "tenant list"],
+            "List the tenants",
+            vec![step("pcli2_tenant_list", &[], json_format)],
+        ),
+    ]
+}
+
+/// Whether `phrase` starts a word in `haystack` (so `set` does not match `asset`).
+fn has_phrase(haystack: &str, phrase: &str) -> bool {
+    haystack.match_indices(phrase).any(|(index, _)| {
+        index == 0
+            || !haystack[..index]
+                .chars()
+                .next_back()
+                .is_some_and(char::is_alphanumeric)
+    })
+}
+
+fn trim_token(token: &str) -> &str {
+    token
+        .trim_matches(|c: char| matches!(c, ',' | ';' | ':' | '!' | '?' | '"' | '\'' | '(' | ')'))
+        .trim_end_matches('.')
+}
+
+pub(crate) fn is_uuid(token: &str) -> bool {
+    let groups: Vec<&str> = token.split('-').collect();
+    groups.len() == 5
+        && groups
+            .iter()
+            .zip([8, 4, 4, 4, 12])
+            .all(|(group, len)| group.len() == len && group.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn word_after<'a>(words: &[&'a str], index: usize) -> Option<&'a str> {
+    words
+        .iter()
+        .skip(index + 1)
+        .map(|word| trim_token(word))
+        .find(|word| !ARTICLES.contains(&word.to_ascii_lowercase().as_str()))
+        .filter(|word| !word.is_empty())
+}
+
+fn extract(request: &str) -> Entities {
+    let mut entities = Entities::default();
+    let lower = request.to_ascii_lowercase();
+    let words: Vec<&str> = request.split_whitespace().collect();
+
+    for word in &words {
+        let token = trim_token(word);
+        if is_uuid(token) {
+            entities.uuid.get_or_insert_with(|| token.to_string());
+        } else if token.starts_with('/') && token.len() > 1 {
+            let name = token.rsplit('/').next().unwrap_or_default();
+            let is_file = name
+                .rsplit_once('.')
+                .is_some_and(|(stem, ext)| !stem.is_empty() && !ext.is_empty());
+            let slot = if is_file {
+                &mut entities.path
+            } else {
+                &mut entities.folder_path
+            };
+            slot.get_or_insert_with(|| token.trim_end_matches('/').to_string());
+        } else if let Some(number) = token.strip_suffix('%')
+            && let Ok(threshold) = number.parse::<f64>()
+            && (0.0..=100.0).contains(&threshold)
+        {
+            entities.threshold.get_or_insert(threshold);
+        }
+    }
+
+    for (index, word) in words.iter().enumerate() {
+        let lower_word = trim_token(word).to_ascii_lowercase();
+        if lower_word == "tenant" && entities.tenant.is_none() {
+            entities.tenant = word_after(&words, index).map(str::to_string);
+        } else if lower_word == "threshold" && entities.threshold.is_none() {
+            entities.threshold = word_after(&words, index)
+                .and_then(|value| value.trim_end_matches('%').parse().ok())
+                .filter(|threshold| (0.0..=100.0).contains(threshold));
+        } else if lower_word == "folder" && entities.folder_name.is_none() {
+            let before = index
+                .checked_sub(1)
+                .map(|before| trim_token(words[before]))
+                .filter(|before| {
+                    !before.is_empty()
+                        && !ARTICLES.contains(&before.to_ascii_lowercase().as_str())
+                        && !["in", "from", "of"].contains(&before.to_ascii_lowercase().as_str())
+                });
+            entities.folder_name = before
+                .or_else(|| word_after(&words, index))
+                .filter(|name| !name.starts_with('/'))
+                .map(str::to_string);
+        }
+    }
+    if entities.folder_path.is_some() {
+        entities.folder_name = None;
+    }
+
+    // Quoted text wins over text introduced by a marker word.
+    let quoted = request
+        .split('"')
+        .nth(1)
+        .filter(|_| request.matches('"').count() >= 2)
+        .map(str::trim)
+        .filter(|text| !text.is_empty());
+    entities.text = quoted.map(str::to_string).or_else(|| {
+        TEXT_MARKERS.iter().find_map(|marker| {
+            let start = lower.find(marker)? + marker.len();
+            let rest = &lower[start..];
+            let end = TEXT_STOPS
+                .iter()
+                .filter_map(|stop| rest.find(stop))
+                .chain(rest.find(['.', ',', ';', '?', '!']))
+                .min()
+                .unwrap_or(rest.len());
+            let text = request[start..start + end].trim();
+            let text = text
+                .split_once(' ')
+                .filter(|(first, _)| ARTICLES.contains(&first.to_ascii_lowercase().as_str()))
+                .map_or(text, |(_, rest)| rest);
+            (!text.is_empty() && !text.starts_with('/')).then(|| text.to_string())
+        })
+    });
+
+    if let Some(start) = lower.find("set ").map(|index| index + "set ".len())
+        && let Some(to) = lower[start..].find(" to ")
+    {
+        let name = request[start..start + to].trim();
+        let rest = &request[start + to + " to ".len()..];
+        let end = [" on ", " for "]
+            .iter()
+            .filter_map(|stop| rest.to_ascii_lowercase().find(stop))
+            .min()
+            .unwrap_or(rest.len());
+        let value = trim_token(rest[..end].trim());
+        if !name.is_empty() && !value.is_empty() {
+            entities.metadata = Some((name.to_string(), value.to_string()));
+        }
+    }
+    entities
+}
+
+/// The rule with the most keywords in `request`, if any.
+fn choose_rule<'a>(rules: &'a [PlanRule], request: &str) -> Option<&'a PlanRule> {
+    let lower = request.to_ascii_lowercase();
+    let mut best: Option<(usize, &PlanRule)> = None;
+    for rule in rules {
+        let hits = rule
+            .keywords
+            .iter()
+            .map(|keyword| keyword.to_ascii_lowercase())
+            .filter(|keyword| !keyword.trim().is_empty() && has_phrase(&lower, keyword))
+            .count();
+        if hits > 0 && best.is_none_or(|(best_hits, _)| hits > best_hits) {
+            best = Some((hits, rule));
+        }
+    }
+    best.map(|(_, rule)| rule)
+}
+
+struct Planner<'a> {
+    state: &'a AppState,
+    entities: &'a Entities,
+    steps: Vec<Value>,
+    missing: Vec<&'static str>,
+    asset_step: Option<usize>,
+    folder_step: Option<usize>,
+}
+
+impl Planner<'_> {
+    fn push(&mut self, tool: &str, arguments: Map<String, Value>, purpose: String) -> usize {
+        self.steps.push(json!({
+            "tool": self.state.config.tools.public_name(tool),
+            "arguments": arguments,
+            "purpose": purpose
+        }));
+        self.steps.len() - 1
+    }
+
+    fn tenant_argument(&self) -> Map<String, Value> {
+        let mut arguments = Map::new();
+        if let Some(tenant) = &self.entities.tenant {
+            arguments.insert("tenant".to_string(), json!(tenant));
+        }
+        arguments
+    }
+
+    fn missing(&mut self, input: &'static str) {
+        if !self.missing.contains(&input) {
+            self.missing.push(input);
+        }
+    }
+
+    /// Fills the asset arguments, finding the asset by name first if needed.
+    fn asset(&mut self, arguments: &mut Map<String, Value>, optional: bool) {
+        let entities = self.entities;
+        if let Some(uuid) = &entities.uuid {
+            arguments.insert("uuid".to_string(), json!(uuid));
+        } else if let Some(path) = &entities.path {
+            arguments.insert("path".to_string(), json!(path));
+        } else if let Some(text) = &entities.text {
+            let index = match self.asset_step {
+                Some(index) => index,
+                None => {
+                    let mut search = self.tenant_argument();
+                    search.insert("text".to_string(), json!(text));
+                    search.insert("fuzzy".to_string(), json!(true));
+                    search.insert("format".to_string(), json!("json"));
+                    let mut purpose = format!("Find the asset '{}'", text);
+                    if let Some(folder) = entities
+                        .folder_path
+                        .as_ref()
+                        .or(entities.folder_name.as_ref())
+                    {
+                        purpose.push_str(&format!("; prefer a match in the {} folder", folder));
+                    }
+                    let index = self.push("pcli2_asset_text_match", search, purpose);
+                    self.asset_step = Some(index);
+                    index
+                }
+            };
+            arguments.insert(
+                "path".to_string(),
+                json!(format!("$steps[{}].matches[0].path", index)),
+            );
+        } else if !optional {
+            self.missing("asset");
+        }
+    }
+
+    /// Fills `folder_path`, looking the folder up by name first if needed.
+    fn folder(&mut self, arguments: &mut Map<String, Value>, optional: bool) {
+        let entities = self.entities;
+        if let Some(folder_path) = &entities.folder_path {
+            arguments.insert("folder_path".to_string(), json!(folder_path));
+        } else if let Some(name) = &entities.folder_name {
+            let index = match self.folder_step {
+                Some(index) => index,
+                None => {
+                    let mut suggest = self.tenant_argument();
+                    suggest.insert("query".to_string(), json!(name));
+                    suggest.insert("limit".to_string(), json!(3));
+                    let index = self.push(
+                        FOLDER_SUGGEST_TOOL,
+                        suggest,
+                        format!("Find the path of the '{}' folder", name),
+                    );
+                    self.folder_step = Some(index);
+                    index
+                }
+            };
+            arguments.insert(
+                "folder_path".to_string(),
+                json!(format!("$steps[{}].suggestions[0].path", index)),
+            );
+        } else if !optional {
+            self.missing("folder");
+        }
+    }
+
+    fn plan_step(&mut self, step: &PlanStep, purpose: &str) {
+        let entities = self.entities;
+        let mut arguments = step.arguments.clone();
+        for input in &step.inputs {
+            let optional = input.ends_with('?');
+            match input.trim_end_matches('?') {
+                "asset" => self.asset(&mut arguments, optional),
+                "folder" => self.folder(&mut arguments, optional),
+                "text" => match &entities.text {
+                    Some(text) => {
+                        arguments.insert("text".to_string(), json!(text));
+                    }
+                    None if !optional => self.missing("text"),
+                    None => {}
+                },
+                "metadata" => match &entities.metadata {
+                    Some((name, value)) => {
+                        arguments.insert("name".to_string(), json!(name));
+                        arguments.insert("value".to_string(), json!(value));
+                    }
+                    None if !optional => self.missing("metadata"),
+                    None => {}
+                },
+                "tenant" => arguments.extend(self.tenant_argument()),
+                "threshold" => {
+                    if let Some(threshold) = entities.threshold {
+                        arguments.insert("threshold".to_string(), json!(threshold));
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.push(&step.tool, arguments, purpose.to_string());
+    }
+}
+
+pub fn plan(state: &AppState, args: &Value) -> Result<Value, String> {
+    let request = args
+        .get("request")
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|request| !request.is_empty())
+        .ok_or_else(|| "Missing required argument: 'request'".to_string())?;
+    if request.chars().count() > MAX_REQUEST_CHARS {
+        return Err(format!(
+            "Request is too long: at most {} characters are allowed",
+            MAX_REQUEST_CHARS
+        ));
+    }
+    let rules: Vec<PlanRule> = state
+        .config
+        .plan
+        .rules
+        .iter()
+        .cloned()
+        .chain(builtin_rules())
+        .collect();
+    let entities = extract(request);
+    let Some(rule) = choose_rule(&rules, request) else {
+        let known: Vec<&str> = rules.iter().map(|rule| rule.description.as_str()).collect();
+        let text = serde_json::to_string_pretty(&json!({
+            "request": request,
+            "steps": [],
+            "known_workflows": known
+        }))
+        .map_err(|err| format!("Failed to render plan: {}", err))?;
+        return Ok(tool_result(vec![
+            summary_block(
+                "No workflow matches this request; rephrase it with one of the known workflows."
+                    .to_string(),
+            ),
+            raw_output_block(text),
+        ]));
+    };
+
+    let mut planner = Planner {
+        state,
+        entities: &entities,
+        steps: Vec::new(),
+        missing: Vec::new(),
+        asset_step: None,
+        folder_step: None,
+    };
+    for step in &rule.steps {
+        planner.plan_step(step, &rule.description);
+    }
+    let tools: Vec<&str> = planner
+        .steps
+        .iter()
+        .filter_map(|step| step["tool"].as_str())
+        .collect();
+    let mut headline = format!(
+        "Plan for \"{}\": {} ({} steps: {}).",
+        request,
+        rule.description,
+        tools.len(),
+        tools.join(" -> ")
+    );
+    if !planner.missing.is_empty() {
+        headline.push_str(&format!(
+            " Ask the user for: {}.",
+            planner.missing.join(", ")
+        ));
+    }
+    let text = serde_json::to_string_pretty(&json!({
+        "request": request,
+        "workflow": rule.description,
+        "steps": planner.steps,
+        "missing": planner.missing,
+        "note": "Run the steps in order. Replace an argument like $steps[0].matches[0].path with that value from the JSON output of the step it names."
+    }))
+    .map_err(|err| format!("Failed to render plan: {}", err))?;
+    Ok(tool_result(vec![
+        summary_block(headline),
+        raw_output_block(text),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pcli::tool_list;
+
+    fn steps(request: &str) -> Value {
+        let result = plan(
+            &AppState::new("test", "0.0.0"),
+            &json!({ "request": request }),
+        )
+        .unwrap();
+        let text = result["content"][1]["text"].as_str().unwrap();
+        serde_json::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_builtin_rules_name_existing_tools() {
+        let tools: Vec<String> = tool_list()
+            .iter()
+            .filter_map(|tool| tool["name"].as_str().map(str::to_string))
+            .collect();
+        for rule in builtin_rules() {
+            for step in &rule.steps {
+                assert!(tools.contains(&step.tool), "{}", step.tool);
+            }
+        }
+    }
+
+    #[test]
+    fn test_extract() {
+        let entities = extract(
+            "Find parts similar to bracket X in the castings folder above 85% in tenant acme",
+        );
+        assert_eq!(entities.text.as_deref(), Some("bracket X"));
+        assert_eq!(entities.folder_name.as_deref(), Some("castings"));
+        assert_eq!(entities.threshold, Some(85.0));
+        assert_eq!(entities.tenant.as_deref(), Some("acme"));
+
+        let entities = extract("set Supplier to Acme Corp on /Root/Castings/Bracket.stl");
+        assert_eq!(entities.path.as_deref(), Some("/Root/Castings/Bracket.stl"));
+        assert_eq!(
+            entities.metadata,
+            Some(("Supplier".to_string(), "Acme Corp".to_string()))
+        );
+
+        let entities = extract("thumbnail of 123e4567-e89b-12d3-a456-426614174000");
+        assert_eq!(
+            entities.uuid.as_deref(),
+            Some("123e4567-e89b-12d3-a456-426614174000")
+        );
+        assert_eq!(
+            extract("list /Root/Castings/").folder_path.as_deref(),
+            Some("/Root/Castings")
+        );
+    }
+
+    #[test]
+    fn test_similar_parts_are_found_by_name_first() {
+        let plan = steps("find parts similar to bracket X in the castings folder");
+        assert_eq!(
+            plan["workflow"],
+            "Find assets geometrically similar to an asset"
+        );
+        assert_eq!(plan["steps"][0]["tool"], "pcli2_asset_text_match");
+        assert_eq!(plan["steps"][0]["arguments"]["text"], "bracket X");
+        assert_eq!(plan["steps"][1]["tool"], "pcli2_geometric_match");
+        assert_eq!(
+            plan["steps"][1]["arguments"]["path"],
+            "$steps[0].matches[0].path"
+        );
+        assert_eq!(plan["missing"], json!([]));
+    }
+
+    #[test]
+    fn test_folder_names_are_resolved_and_gaps_reported() {
+        let plan = steps("find duplicates in the castings folder");
+        assert_eq!(plan["steps"][0]["tool"], "pcli2_folder_suggest");
+        assert_eq!(plan["steps"][0]["arguments"]["query"], "castings");
+        assert_eq!(
+            plan["steps"][1]["arguments"]["folder_path"],
+            "$steps[0].suggestions[0].path"
+        );
+
+        let plan = steps("show me the thumbnail");
+        assert_eq!(plan["missing"], json!(["asset"]));
+        let plan = steps("show me the thumbnails of /Root/Castings");
+        assert_eq!(plan["steps"][0]["tool"], "pcli2_folder_thumbnail_gallery");
+        assert_eq!(
+            plan["steps"][0]["arguments"]["folder_path"],
+            "/Root/Castings"
+        );
+        let plan = steps("export /Root/Castings/Bracket.stl for the CAM team");
+        assert_eq!(plan["steps"][0]["tool"], "pcli2_asset_export");
+        assert_eq!(
+            plan["steps"][0]["arguments"]["path"],
+            "/Root/Castings/Bracket.stl"
+        );
+        assert_eq!(steps("what's the weather")["steps"], json!([]));
+    }
+
+    #[test]
+    fn test_phrases_match_word_starts() {
+        assert!(has_phrase("set the weight", "set "));
+        assert!(!has_phrase("list asset names", "set "));
+        assert!(has_phrase("it resembles", "resembl"));
+    }
+}
@@ -0,0 +1,333 @@
+#[cfg(feature = "redis")]
+use crate::shared::SharedStore;
+use crate::{
+    AppState,
+    admin::CREDENTIALS_TOOL,
+    artifacts::ARTIFACTS_PURGE_TOOL,
+    cache::MUTATING_TOOLS,
+    config::PolicyConfig,
+    pcli::GENERIC_TOOL,
+    quota::{QUOTA_STATUS_TOOL, QuotaTracker},
+    search::MULTI_TENANT_SEARCH_TOOL,
+    tenancy::tenant_scoped,
+};
+use serde_json::{Value, json};
+use std::{collections::HashMap, sync::Arc};
+
+/// Tools besides the asset-changing ones that a policy without
+/// `destructive` takes away: switching pcli2's tenant, registering
+/// credentials, purging stored results, and the generic tool, which runs
+/// any pcli2 command.
+const DESTRUCTIVE_TOOLS: [&str; 4] = [
+    "pcli2_tenant_use",
+    CREDENTIALS_TOOL,
+    ARTIFACTS_PURGE_TOOL,
+    GENERIC_TOOL,
+];
+
+pub fn is_destructive(tool: &str) -> bool {
+    DESTRUCTIVE_TOOLS.contains(&tool) || MUTATING_TOOLS.iter().any(|(name, _)| *name == tool)
+}
+
+/// The policy the request's API key is held to. A key naming a policy the
+/// configuration no longer has, after a reload, is refused rather than let
+/// through unrestricted.
+fn policy_of(state: &AppState) -> Result<Option<(&str, &PolicyConfig)>, String> {
+    let Some(name) = state.policy.as_deref() else {
+        return Ok(None);
+    };
+    match state.config.policies.get(name) {
+        Some(policy) => Ok(Some((name, policy))),
+        None => Err(format!("API key policy '{}' is not configured", name)),
+    }
+}
+
+/// Whether `policy` lets its keys see and call `tool`. Keys held to some
+/// tenants get the tools a key bound to one would.
+fn permits(policy: &PolicyConfig, tool: &str, schema: &Value) -> bool {
+    if tool == QUOTA_STATUS_TOOL {
+        return true;
+    }
+    (policy.tools.is_empty() || policy.tools.iter().any(|name| name == tool))
+        && (policy.destructive || !is_destructive(tool))
+        && (policy.tenants.is_empty() || tenant_scoped(tool, schema))
+}
+
+/// Whether the request's policy lets it see `tool` in `tools/list`.
+pub fn tool_visible(state: &AppState, tool: &str, schema: &Value) -> bool {
+    match policy_of(state) {
+        Ok(Some((_, policy))) => permits(policy, tool, schema),
+        Ok(None) => true,
+        Err(_) => false,
+    }
+}
+
+/// Whether the request's policy lets it work in `tenant`.
+pub fn tenant_permitted(state: &AppState, tenant: &str) -> bool {
+    match policy_of(state) {
+        Ok(Some((_, policy))) => {
+            policy.tenants.is_empty() || policy.tenants.iter().any(|name| name == tenant)
+        }
+        Ok(None) => true,
+        Err(_) => false,
+    }
+}
+
+/// Refuses a call the request's policy does not allow, and keeps the calls
+/// of unbound keys held to some tenants within them: a missing `tenant` is
+/// filled in when the policy allows just one, and multi-tenant searches
+/// default to the policy's tenants.
+pub fn enforce(
+    state: &AppState,
+    tool: &str,
+    schema: &Value,
+    args: &mut Value,
+) -> Result<(), String> {
+    let Some((name, policy)) = policy_of(state)? else {
+        return Ok(());
+    };
+    if !permits(policy, tool, schema) {
+        return Err(format!(
+            "Tool '{}' is not allowed by policy '{}'",
+            state.config.tools.public_name(tool),
+            name
+        ));
+    }
+    // A bound key's tenant was checked against the policy when it loaded.
+    if policy.tenants.is_empty() || state.tenant.is_some() {
+        return Ok(());
+    }
+    let Some(args) = args.as_object_mut() else {
+        return Ok(());
+    };
+    let allowed = |tenant: &str| policy.tenants.iter().any(|name| name == tenant);
+    let outside = |tenant: &str| {
+        format!(
+            "Tenant '{}' is not allowed by policy '{}'; use one of: {}",
+            tenant,
+            name,
+            policy.tenants.join(", ")
+        )
+    };
+    if tool == MULTI_TENANT_SEARCH_TOOL {
+        match args.get("tenants").and_then(Value::as_array) {
+            Some(requested) => {
+                if let Some(tenant) = requested
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .find(|tenant| !allowed(tenant))
+                {
+                    return Err(outside(tenant));
+                }
+            }
+            None => {
+                args.insert("tenants".to_string(), json!(policy.tenants));
+            }
+        }
+    } else if schema["properties"].get("tenant").is_some() {
+        match args.get("tenant").and_then(Value::as_str) {
+            Some(tenant) if !allowed(tenant) => return Err(outside(tenant)),
+            Some(_) => {}
+            None if policy.tenants.len() == 1 => {
+                args.insert("tenant".to_string(), json!(policy.tenants[0]));
+            }
+            None => {
+                return Err(format!(
+                    "Policy '{}' requires 'tenant': one of {}",
+                    name,
+                    policy.tenants.join(", ")
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Per-key quotas of each policy, counted apart from `quotas`.
+#[derive(Clone, Default)]
+pub struct PolicyQuotas {
+    trackers: Arc<HashMap<String, QuotaTracker>>,
+    #[cfg(feature = "redis")]
+    shared: Option<SharedStore>,
+}
+
+impl PolicyQuotas {
+    pub fn new(policies: &HashMap<String, PolicyConfig>) -> Self {
+        Self::default().with_policies(policies)
+    }
+
+    /// Quotas for `policies` counted in `shared`, across replicas.
+    #[cfg(feature = "redis")]
+    pub fn with_shared(policies: &HashMap<String, PolicyConfig>, shared: SharedStore) -> Self {
+        Self {
+            shared: Some(shared),
+            ..Self::default()
+        }
+        .with_policies(policies)
+    }
+
+    /// Quotas for new `policies`, keeping the usage counted under the
+    /// policies that stay.
+    pub fn with_policies(&self, policies: &HashMap<String, PolicyConfig>) -> Self {
+        let trackers = policies
+            .iter()
+            .map(|(name, policy)| {
+                let tracker = match self.trackers.get(name) {
+                    Some(tracker) => tracker.with_rules(&policy.quotas),
+                    None => self.tracker(name, policy),
+                };
+                (name.clone(), tracker)
+            })
+            .collect();
+        Self {
+            trackers: Arc::new(trackers),
+            #[cfg(feature = "redis")]
+            shared: self.shared.clone(),
+        }
+    }
+
+    fn tracker(&self, name: &str, policy: &PolicyConfig) -> QuotaTracker {
+        let tracker = QuotaTracker::new(&policy.quotas);
+        #[cfg(feature = "redis")]
+        if let Some(shared) = &self.shared {
+            return tracker.with_shared(shared.clone(), &format!("policy:{}", name));
+        }
+        #[cfg(not(feature = "redis"))]
+        let _ = name;
+        tracker
+    }
+
+    pub fn get(&self, policy: &str) -> Option<&QuotaTracker> {
+        self.trackers.get(policy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{QuotaRule, ServerConfig};
+
+    fn held_to(policy: PolicyConfig) -> AppState {
+        AppState {
+            config: Arc::new(ServerConfig {
+                policies: HashMap::from([("analyst".to_string(), policy)]),
+                ..ServerConfig::default()
+            }),
+            policy: Some("analyst".to_string()),
+            ..AppState::new("test", "0.0.0")
+        }
+    }
+
+    #[test]
+    fn test_policies_limit_tools() {
+        let schema = json!({ "properties": { "tenant": {}, "path": {} } });
+        let state = held_to(PolicyConfig {
+            tools: vec![
+                "pcli2_asset_get".to_string(),
+                "pcli2_asset_metadata_delete".to_string(),
+            ],
+            destructive: false,
+            ..PolicyConfig::default()
+        });
+        assert!(tool_visible(&state, "pcli2_asset_get", &schema));
+        assert!(tool_visible(&state, QUOTA_STATUS_TOOL, &json!({})));
+        assert!(!tool_visible(&state, "pcli2_asset_list", &schema));
+        assert!(!tool_visible(
+            &state,
+            "pcli2_asset_metadata_delete",
+            &schema
+        ));
+        let err = enforce(&state, "pcli2_asset_list", &schema, &mut json!({})).unwrap_err();
+        assert_eq!(
+            err,
+            "Tool 'pcli2_asset_list' is not allowed by policy 'analyst'"
+        );
+
+        let gone = AppState {
+            policy: Some("admin".to_string()),
+            ..state
+        };
+        assert!(!tool_visible(&gone, "pcli2_asset_get", &schema));
+        assert!(enforce(&gone, "pcli2_asset_get", &schema, &mut json!({})).is_err());
+        assert!(tool_visible(
+            &AppState::new("test", "0.0.0"),
+            GENERIC_TOOL,
+            &json!({})
+        ));
+    }
+
+    #[test]
+    fn test_policies_keep_calls_in_their_tenants() {
+        let schema = json!({ "properties": { "tenant": {}, "path": {} } });
+        let policy = PolicyConfig {
+            tenants: vec!["castings".to_string(), "forgings".to_string()],
+            ..PolicyConfig::default()
+        };
+        let state = held_to(policy.clone());
+        assert!(tenant_permitted(&state, "forgings"));
+        assert!(!tenant_permitted(&state, "acme"));
+        assert!(!tool_visible(&state, "pcli2_tenant_list", &json!({})));
+
+        let mut args = json!({ "tenant": "forgings" });
+        enforce(&state, "pcli2_asset_get", &schema, &mut args).unwrap();
+        let err = enforce(
+            &state,
+            "pcli2_asset_get",
+            &schema,
+            &mut json!({ "tenant": "acme" }),
+        )
+        .unwrap_err();
+        assert!(err.contains("Tenant 'acme' is not allowed by policy 'analyst'"));
+        let err = enforce(&state, "pcli2_asset_get", &schema, &mut json!({})).unwrap_err();
+        assert!(err.contains("requires 'tenant'"));
+
+        let mut args = json!({ "text": "bracket" });
+        enforce(&state, MULTI_TENANT_SEARCH_TOOL, &json!({}), &mut args).unwrap();
+        assert_eq!(args["tenants"], json!(["castings", "forgings"]));
+        let mut args = json!({ "text": "bracket", "tenants": ["castings", "acme"] });
+        assert!(enforce(&state, MULTI_TENANT_SEARCH_TOOL, &json!({}), &mut args).is_err());
+
+        let single = held_to(PolicyConfig {
+            tenants: vec!["castings".to_string()],
+            ..policy
+        });
+        let mut args = json!({ "path": "/Root/a.stl" });
+        enforce(&single, "pcli2_asset_get", &schema, &mut args).unwrap();
+        assert_eq!(args["tenant"], "castings");
+    }
+
+    #[tokio::test]
+    async fn test_policy_quotas_survive_new_policies() {
+        let rule = QuotaRule {
+            tools: Vec::new(),
+            max_calls: 1,
+            window_secs: None,
+        };
+        let policies = HashMap::from([(
+            "analyst".to_string(),
+            PolicyConfig {
+                quotas: HashMap::from([("calls".to_string(), rule)]),
+                ..PolicyConfig::default()
+            },
+        )]);
+        let quotas = PolicyQuotas::new(&policies);
+        let tracker = quotas.get("analyst").unwrap();
+        tracker.admit("a", "pcli2_asset_get").await.unwrap();
+        assert!(tracker.admit("a", "pcli2_asset_get").await.is_err());
+        let reloaded = quotas.with_policies(&policies);
+        assert!(
+            reloaded
+                .get("analyst")
+                .unwrap()
+                .admit("a", "pcli2_asset_get")
+                .await
+                .is_err()
+        );
+        assert!(
+            reloaded
+                .with_policies(&HashMap::new())
+                .get("analyst")
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,438 @@
+use crate::{
+    content::summary_block,
+    paging::{csv_records, json_rows},
+    structured::is_match_tool,
+    summary::SUMMARIZE_ARG,
+};
+use serde_json::{Map, Value, json};
+use std::cmp::Ordering;
+
+pub const SORT_BY_ARG: &str = "sort_by";
+pub const FIELDS_ARG: &str = "fields";
+/// Listing tools whose rows can be sorted and trimmed; the match tools can
+/// be too.
+const LIST_TOOLS: [&str; 4] = [
+    "pcli2",
+    "pcli2_folder_list",
+    "pcli2_asset_list",
+    "pcli2_tenant_list",
+];
+
+/// Whether `tool` returns rows that can be sorted, trimmed, or filtered.
+pub fn has_rows(tool: &str) -> bool {
+    LIST_TOOLS.contains(&tool) || is_match_tool(tool)
+}
+
+/// Whether a call of `tool` asks for its rows to be sorted or trimmed to
+/// some columns.
+pub fn wants_projection(tool: &str, args: &Value) -> bool {
+    has_rows(tool) && (args.get(SORT_BY_ARG).is_some() || args.get(FIELDS_ARG).is_some())
+}
+
+/// Names given as an array or as a comma-separated string.
+fn names(args: &Value, key: &str) -> Vec<String> {
+    let names: Vec<String> = match args.get(key) {
+        Some(Value::String(text)) => text.split(',').map(str::to_string).collect(),
+        Some(Value::Array(items)) => items
+            .iter()
+            .filter_map(Value::as_str)
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    };
+    names
+        .into_iter()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// A column to sort by; `-name` sorts it in descending order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SortKey {
+    column: String,
+    descending: bool,
+}
+
+fn sort_keys(args: &Value) -> Vec<SortKey> {
+    names(args, SORT_BY_ARG)
+        .into_iter()
+        .map(|name| match name.strip_prefix('-') {
+            Some(column) => SortKey {
+                column: column.trim().to_string(),
+                descending: true,
+            },
+            None => SortKey {
+                column: name.strip_prefix('+').unwrap_or(&name).trim().to_string(),
+                descending: false,
+            },
+        })
+        .filter(|key| !key.column.is_empty())
+        .collect()
+}
+
+/// Checks the sort and column options and asks pcli2 for JSON unless CSV
+/// was asked for, in which case the header row names the columns.
+pub fn prepare_projection(args: &mut Value) -> Result<(), String> {
+    for key in [SORT_BY_ARG, FIELDS_ARG] {
+        if args.get(key).is_some() && names(args, key).is_empty() {
+            return Err(format!(
+                "Invalid argument '{}': expected one or more column names",
+                key
+            ));
+        }
+    }
+    if args
+        .get(SUMMARIZE_ARG)
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+    {
+        return Err(format!(
+            "Invalid argument '{}': a summary has its own order and columns; drop '{}' and '{}' or '{}'",
+            SUMMARIZE_ARG, SORT_BY_ARG, FIELDS_ARG, SUMMARIZE_ARG
+        ));
+    }
+    let options = format!("'{}' and '{}' need", SORT_BY_ARG, FIELDS_ARG);
+    ask_for_rows(args, &options)
+}
+
+/// Asks pcli2 for JSON unless CSV was asked for, in which case the header
+/// row names the columns; a tree has no rows. `options` names what needs
+/// the rows in the error.
+pub(crate) fn ask_for_rows(args: &mut Value, options: &str) -> Result<(), String> {
+    let Some(object) = args.as_object_mut() else {
+        return Ok(());
+    };
+    match object.get("format").and_then(Value::as_str) {
+        Some("tree") => Err(format!(
+            "Invalid argument 'format': {} json or csv output",
+            options
+        )),
+        Some("csv") => {
+            object.insert("headers".to_string(), json!(true));
+            Ok(())
+        }
+        Some(_) => Ok(()),
+        None => {
+            object.insert("format".to_string(), json!("json"));
+            Ok(())
+        }
+    }
+}
+
+/// A cell to sort on: numbers (including `95.5%`) before text, as numbers.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Cell {
+    Number(f64),
+    Text(String),
+}
+
+impl Cell {
+    pub(crate) fn parse(text: &str) -> Option<Cell> {
+        let text = text.trim();
+        if text.is_empty() {
+            return None;
+        }
+        Some(match text.trim_end_matches('%').parse::<f64>() {
+            Ok(number) => Cell::Number(number),
+            Err(_) => Cell::Text(text.to_lowercase()),
+        })
+    }
+
+    fn of(value: Option<&Value>) -> Option<Cell> {
+        match value? {
+            Value::Null => None,
+            Value::Number(number) => number.as_f64().map(Cell::Number),
+            Value::String(text) => Cell::parse(text),
+            other => Some(Cell::Text(other.to_string())),
+        }
+    }
+
+    pub(crate) fn cmp(&self, other: &Cell) -> Ordering {
+        match (self, other) {
+            (Cell::Number(a), Cell::Number(b)) => a.total_cmp(b),
+            (Cell::Number(_), Cell::Text(_)) => Ordering::Less,
+            (Cell::Text(_), Cell::Number(_)) => Ordering::Greater,
+            (Cell::Text(a), Cell::Text(b)) => a.cmp(b),
+        }
+    }
+}
+
+/// Orders rows by `keys` given the cells of each; rows without a value
+/// sort last whichever the direction.
+fn compare(keys: &[SortKey], a: &[Option<Cell>], b: &[Option<Cell>]) -> Ordering {
+    for (index, key) in keys.iter().enumerate() {
+        let ordering = match (&a[index], &b[index]) {
+            (Some(a), Some(b)) if key.descending => b.cmp(a),
+            (Some(a), Some(b)) => a.cmp(b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+/// Splits a CSV record into its fields as written, quotes included.
+pub(crate) fn csv_fields(record: &str) -> Vec<&str> {
+    let mut fields = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (index, c) in record.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                fields.push(&record[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    fields.push(&record[start..]);
+    fields
+}
+
+pub(crate) fn unquote(field: &str) -> String {
+    let field = field.trim();
+    match field
+        .strip_prefix('"')
+        .and_then(|field| field.strip_suffix('"'))
+    {
+        Some(inner) => inner.replace("\"\"", "\""),
+        None => field.to_string(),
+    }
+}
+
+/// The columns asked for that the output does not have.
+fn missing_columns(args: &Value, known: impl Fn(&str) -> bool) -> Vec<String> {
+    let mut missing: Vec<String> = Vec::new();
+    let columns = sort_keys(args)
+        .into_iter()
+        .map(|key| key.column)
+        .chain(names(args, FIELDS_ARG));
+    for column in columns {
+        if !known(&column) && !missing.contains(&column) {
+            missing.push(column);
+        }
+    }
+    missing
+}
+
+/// Sorts and trims the rows of a JSON output; `None` when it has no rows.
+fn project_json(text: &str, args: &Value) -> Option<(String, Vec<String>)> {
+    let mut output = serde_json::from_str::<Value>(text).ok()?;
+    let rows = json_rows(&mut output)?;
+    let missing = if rows.is_empty() {
+        Vec::new()
+    } else {
+        missing_columns(args, |column| {
+            rows.iter().any(|row| row.get(column).is_some())
+        })
+    };
+    let keys = sort_keys(args);
+    if !keys.is_empty() {
+        let mut keyed: Vec<(Vec<Option<Cell>>, Value)> = rows
+            .drain(..)
+            .map(|row| {
+                let cells = keys
+                    .iter()
+                    .map(|key| Cell::of(row.get(&key.column)))
+                    .collect();
+                (cells, row)
+            })
+            .collect();
+        keyed.sort_by(|(a, _), (b, _)| compare(&keys, a, b));
+        rows.extend(keyed.into_iter().map(|(_, row)| row));
+    }
+    let fields = names(args, FIELDS_ARG);
+    if !fields.is_empty() {
+        for row in rows.iter_mut() {
+            if let Value::Object(object) = row {
+                let kept: Map<String, Value> = fields
+                    .iter()
+                    .filter_map(|field| Some((field.clone(), object.remove(field)?)))
+                    .collect();
+                *object = kept;
+            }
+        }
+    }
+    let rendered = serde_json::to_string_pretty(&output).unwrap_or_else(|_| output.to_string());
+    Some((rendered, missing))
+}
+
+/// Sorts the rows below the header of a CSV output and keeps the asked for
+/// columns in the order they were named.
+fn project_csv(text: &str, args: &Value) -> Option<(String, Vec<String>)> {
+    let records = csv_records(text);
+    let (header, rows) = records.split_first()?;
+    let header = csv_fields(header);
+    let columns: Vec<String> = header.iter().map(|field| unquote(field)).collect();
+    let position = |name: &str| {
+        columns
+            .iter()
+            .position(|column| column.eq_ignore_ascii_case(name))
+    };
+    let missing = missing_columns(args, |column| position(column).is_some());
+    let mut rows: Vec<Vec<&str>> = rows.iter().map(|row| csv_fields(row)).collect();
+    let keys: Vec<(SortKey, usize)> = sort_keys(args)
+        .into_iter()
+        .filter_map(|key| {
+            let index = position(&key.column)?;
+            Some((key, index))
+        })
+        .collect();
+    if !keys.is_empty() {
+        let sort: Vec<SortKey> = keys.iter().map(|(key, _)| key.clone()).collect();
+        let mut keyed: Vec<(Vec<Option<Cell>>, Vec<&str>)> = rows
+            .into_iter()
+            .map(|row| {
+                let cells = keys
+                    .iter()
+                    .map(|(_, index)| {
+                        row.get(*index)
+                            .and_then(|field| Cell::parse(&unquote(field)))
+                    })
+                    .collect();
+                (cells, row)
+            })
+            .collect();
+        keyed.sort_by(|(a, _), (b, _)| compare(&sort, a, b));
+        rows = keyed.into_iter().map(|(_, row)| row).collect();
+    }
+    let fields = names(args, FIELDS_ARG);
+    let kept: Vec<usize> = if fields.is_empty() {
+        (0..header.len()).collect()
+    } else {
+        fields.iter().filter_map(|field| position(field)).collect()
+    };
+    let pick = |row: &[&str]| -> String {
+        kept.iter()
+            .map(|index| row.get(*index).copied().unwrap_or(""))
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    let lines: Vec<String> = std::iter::once(pick(&header))
+        .chain(rows.iter().map(|row| pick(row)))
+        .collect();
+    Some((lines.join("\n"), missing))
+}
+
+/// Sorts the rows in the last text block of `result` and keeps only the
+/// asked for columns. Columns the output does not have are named in a
+/// note; an output without rows is returned whole.
+pub fn project_result(args: &Value, mut result: Value) -> Value {
+    let Some(blocks) = result.get_mut("content").and_then(Value::as_array_mut) else {
+        return result;
+    };
+    let Some(block) = blocks
+        .iter_mut()
+        .rev()
+        .find(|block| block["type"] == "text")
+    else {
+        return result;
+    };
+    let csv = args.get("format").and_then(Value::as_str) == Some("csv");
+    let projected = block["text"].as_str().and_then(|text| {
+        if csv {
+            project_csv(text, args)
+        } else {
+            project_json(text, args)
+        }
+    });
+    let Some((text, missing)) = projected else {
+        blocks.insert(
+            0,
+            summary_block("The output has no rows to sort or trim; it is returned whole."),
+        );
+        return result;
+    };
+    block["text"] = json!(text);
+    if !missing.is_empty() {
+        blocks.insert(
+            0,
+            summary_block(format!(
+                "The output has no column named {}.",
+                missing
+                    .iter()
+                    .map(|column| format!("'{}'", column))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        );
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::{raw_output_block, tool_result};
+
+    fn text_of(result: &Value, index: usize) -> &str {
+        result["content"][index]["text"].as_str().unwrap()
+    }
+
+    #[test]
+    fn test_json_rows_are_sorted_and_trimmed() {
+        let matches = json!({ "matches": [
+            { "name": "b", "score": "91.5%", "uuid": "2" },
+            { "name": "a", "score": 99, "uuid": "1" },
+            { "name": "c", "uuid": "3" },
+            { "name": "d", "score": 91.5, "uuid": "4" }
+        ] });
+        let args = json!({ "sort_by": "-score,name", "fields": ["name", "score"] });
+        let result = project_result(
+            &args,
+            tool_result(vec![raw_output_block(matches.to_string())]),
+        );
+        let output: Value = serde_json::from_str(text_of(&result, 0)).unwrap();
+        assert_eq!(
+            output,
+            json!({ "matches": [
+                { "name": "a", "score": 99 },
+                { "name": "b", "score": "91.5%" },
+                { "name": "d", "score": 91.5 },
+                { "name": "c" }
+            ] })
+        );
+
+        let args = json!({ "fields": "name,owner" });
+        let result = project_result(
+            &args,
+            tool_result(vec![raw_output_block(json!([{ "name": "x" }]).to_string())]),
+        );
+        assert!(text_of(&result, 0).contains("'owner'"));
+    }
+
+    #[test]
+    fn test_csv_columns_follow_the_requested_order() {
+        let csv = "NAME,SCORE,UUID\n\"b, jr\",9.5,2\na,80,1\nc,95,3\n";
+        let args = json!({ "format": "csv", "headers": true, "sort_by": "-score", "fields": ["uuid", "name"] });
+        let result = project_result(&args, tool_result(vec![raw_output_block(csv)]));
+        assert_eq!(text_of(&result, 0), "UUID,NAME\n3,c\n1,a\n2,\"b, jr\"");
+    }
+
+    #[test]
+    fn test_projection_options_are_checked() {
+        let mut args = json!({ "sort_by": "name" });
+        prepare_projection(&mut args).unwrap();
+        assert_eq!(args["format"], "json");
+        let mut args = json!({ "fields": ["name"], "format": "csv" });
+        prepare_projection(&mut args).unwrap();
+        assert_eq!(args["headers"], true);
+        assert!(prepare_projection(&mut json!({ "fields": [] })).is_err());
+        assert!(prepare_projection(&mut json!({ "sort_by": "name", "format": "tree" })).is_err());
+        assert!(prepare_projection(&mut json!({ "fields": "name", "summarize": true })).is_err());
+        assert!(wants_projection(
+            "pcli2_folder_geometric_match",
+            &json!({ "sort_by": "-score" })
+        ));
+        assert!(!wants_projection(
+            "pcli2_asset_get",
+            &json!({ "fields": "name" })
+        ));
+    }
+}
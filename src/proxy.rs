@@ -0,0 +1,497 @@
+use crate::{
+    auth::expand_args,
+    cli::{ARG_HEADER, ARG_NONCE, ARG_SIGN_KEY, ARG_URL},
+    config::ReplayConfig,
+    mcp::new_session_id,
+    pcli::read_limited,
+    replay::sign,
+};
+use anyhow::{Context, Result, anyhow};
+use clap::ArgMatches;
+use serde_json::{Value, json};
+use std::{
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    sync::mpsc,
+    task::JoinSet,
+};
+use tracing::{debug, info, warn};
+
+pub const MAX_RESPONSE_BYTES: usize = 200 * 1024 * 1024;
+const SESSION_HEADER: &str = "mcp-session-id";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Endpoint {
+    pub fn parse(url: &str) -> Result<Self> {
+        if url.starts_with("https://") {
+            return Err(anyhow!(
+                "https URLs are not supported by the proxy; use http:// or a local TLS tunnel"
+            ));
+        }
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| anyhow!("Proxy URL must start with http://: {}", url))?;
+        let (authority, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, "/mcp"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => (
+                host.trim_matches(['[', ']']),
+                port.parse::<u16>()
+                    .with_context(|| format!("Invalid port in proxy URL: {}", url))?,
+            ),
+            _ => (authority.trim_matches(['[', ']']), 80),
+        };
+        if host.is_empty() {
+            return Err(anyhow!("Proxy URL has no host: {}", url));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    fn host_header(&self) -> String {
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+        if self.port == 80 {
+            host
+        } else {
+            format!("{}:{}", host, self.port)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct HttpResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl HttpResponse {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Forwards newline-delimited JSON-RPC messages from a stdio MCP client to a
+/// remote pcli2-mcp HTTP endpoint and writes the replies back.
+#[derive(Clone)]
+pub struct Proxy {
+    endpoint: Endpoint,
+    headers: Vec<(String, String)>,
+    session: Arc<Mutex<Option<String>>>,
+    /// Stamp each request with a nonce and timestamp, signed with the key
+    /// when there is one, under the default `access.replay` header names.
+    replay: Option<Option<String>>,
+}
+
+impl Proxy {
+    pub fn new(endpoint: Endpoint, headers: Vec<(String, String)>) -> Self {
+        Self {
+            endpoint,
+            headers,
+            session: Arc::new(Mutex::new(None)),
+            replay: None,
+        }
+    }
+
+    /// Sends a fresh nonce and timestamp with every request, and with
+    /// `sign_key` a signature made with it in place of a bearer key.
+    pub fn with_replay(mut self, sign_key: Option<String>) -> Self {
+        self.replay = Some(sign_key);
+        self
+    }
+
+    /// The replay protection headers of a request carrying `body`.
+    fn stamp(&self, body: &[u8]) -> Vec<(String, String)> {
+        let Some(sign_key) = &self.replay else {
+            return Vec::new();
+        };
+        let names = ReplayConfig::default();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let nonce = new_session_id();
+        let mut headers = vec![
+            (names.timestamp_header, timestamp.to_string()),
+            (names.nonce_header, nonce.clone()),
+        ];
+        if let Some(key) = sign_key {
+            headers.push((names.signature_header, sign(key, timestamp, &nonce, body)));
+        }
+        headers
+    }
+
+    fn session_id(&self) -> Option<String> {
+        self.session
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub async fn run<R, W>(&self, input: R, mut output: W) -> Result<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let (sender, mut receiver) = mpsc::unbounded_channel::<String>();
+        let writer = tokio::spawn(async move {
+            while let Some(line) = receiver.recv().await {
+                output.write_all(line.as_bytes()).await?;
+                output.write_all(b"\n").await?;
+                output.flush().await?;
+            }
+            Ok::<(), std::io::Error>(())
+        });
+
+        let mut lines = BufReader::new(input).lines();
+        let mut tasks = JoinSet::new();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let proxy = self.clone();
+            let sender = sender.clone();
+            tasks.spawn(async move {
+                for reply in proxy.forward(&line).await {
+                    let _ = sender.send(reply);
+                }
+            });
+        }
+        while tasks.join_next().await.is_some() {}
+        drop(sender);
+        writer
+            .await
+            .map_err(|err| anyhow!("Proxy output task failed: {}", err))??;
+
+        if self.session_id().is_some()
+            && let Err(err) = self.send("DELETE", None).await
+        {
+            debug!("closing remote session failed: {}", err);
+        }
+        Ok(())
+    }
+
+    /// Sends one JSON-RPC request and returns its `result`, or the error
+    /// message of an error reply.
+    pub async fn request(&self, message: &Value) -> Result<Value, String> {
+        let id = message.get("id").cloned().unwrap_or(Value::Null);
+        let reply = self
+            .forward(&message.to_string())
+            .await
+            .into_iter()
+            .filter_map(|reply| serde_json::from_str::<Value>(&reply).ok())
+            .find(|reply| reply["id"] == id)
+            .ok_or_else(|| "No reply from server".to_string())?;
+        match reply.get("error") {
+            Some(error) => Err(error["message"]
+                .as_str()
+                .unwrap_or("Unknown error")
+                .to_string()),
+            None => Ok(reply["result"].clone()),
+        }
+    }
+
+    /// POSTs `body` as JSON and returns the HTTP status, for one-way
+    /// deliveries such as webhooks.
+    pub async fn post(&self, body: &Value) -> Result<u16, String> {
+        self.send("POST", Some(body.to_string().as_bytes()))
+            .await
+            .map(|response| response.status)
+            .map_err(|err| err.to_string())
+    }
+
+    async fn forward(&self, line: &str) -> Vec<String> {
+        let message: Value = match serde_json::from_str(line) {
+            Ok(message) => message,
+            Err(_) => {
+                return vec![error_reply(
+                    Value::Null,
+                    -32700,
+                    "Parse error: invalid JSON",
+                )];
+            }
+        };
+        let id = message.get("id").cloned().unwrap_or(Value::Null);
+        let method = message.get("method").and_then(|v| v.as_str()).unwrap_or("");
+        debug!("→ {}", method);
+
+        let response = match self.send("POST", Some(line.as_bytes())).await {
+            Ok(response) => response,
+            Err(err) => {
+                warn!("⚠ forwarding {} failed: {}", method, err);
+                return reply_if_request(id, format!("Upstream request failed: {}", err));
+            }
+        };
+        if let Some(session) = response.header(SESSION_HEADER) {
+            let mut current = self.session.lock().unwrap_or_else(|e| e.into_inner());
+            if current.as_deref() != Some(session) {
+                info!("remote session {}", session);
+                *current = Some(session.to_string());
+            }
+        }
+
+        let is_event_stream = response
+            .header("content-type")
+            .is_some_and(|value| value.starts_with("text/event-stream"));
+        let payloads = if is_event_stream {
+            sse_payloads(&String::from_utf8_lossy(&response.body))
+        } else if response.body.iter().all(u8::is_ascii_whitespace) {
+            Vec::new()
+        } else {
+            vec![String::from_utf8_lossy(&response.body).into_owned()]
+        };
+        let replies: Vec<String> = payloads
+            .iter()
+            .filter_map(|payload| serde_json::from_str::<Value>(payload).ok())
+            .map(|value| value.to_string())
+            .collect();
+        if !replies.is_empty() {
+            return replies;
+        }
+        if !(200..300).contains(&response.status) {
+            return reply_if_request(
+                id,
+                format!("Upstream returned HTTP status {}", response.status),
+            );
+        }
+        Vec::new()
+    }
+
+    async fn send(&self, method: &str, body: Option<&[u8]>) -> Result<HttpResponse> {
+        let mut stream = TcpStream::connect((self.endpoint.host.as_str(), self.endpoint.port))
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to connect to {}:{}",
+                    self.endpoint.host, self.endpoint.port
+                )
+            })?;
+        let body = body.unwrap_or_default();
+        let mut request = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nAccept: application/json, text/event-stream\r\nContent-Length: {}\r\nConnection: close\r\n",
+            method,
+            self.endpoint.path,
+            self.endpoint.host_header(),
+            body.len()
+        );
+        if let Some(session) = self.session_id() {
+            request.push_str(&format!("Mcp-Session-Id: {}\r\n", session));
+        }
+        for (name, value) in self.headers.iter().chain(&self.stamp(body)) {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes()).await?;
+        stream.write_all(body).await?;
+        stream.flush().await?;
+
+        let raw = read_limited(&mut stream, MAX_RESPONSE_BYTES, "response")
+            .await
+            .map_err(|err| anyhow!(err))?;
+        parse_response(&raw)
+    }
+}
+
+pub async fn run_proxy(matches: &ArgMatches) -> Result<()> {
+    let url = matches
+        .get_one::<String>(ARG_URL)
+        .ok_or_else(|| anyhow!("Missing --url"))?;
+    let endpoint = Endpoint::parse(url)?;
+    let raw_headers: Vec<String> = matches
+        .get_many::<String>(ARG_HEADER)
+        .map(|values| values.cloned().collect())
+        .unwrap_or_default();
+    let headers = parse_headers(&raw_headers)?;
+    let sign_key = matches
+        .get_one::<String>(ARG_SIGN_KEY)
+        .map(|key| {
+            expand_args(std::slice::from_ref(key))
+                .map(|key| key.concat())
+                .map_err(|err| anyhow!("Invalid --sign-key: {}", err))
+        })
+        .transpose()?;
+    info!("proxying stdio to {}", url);
+    let mut proxy = Proxy::new(endpoint, headers);
+    if matches.get_flag(ARG_NONCE) || sign_key.is_some() {
+        proxy = proxy.with_replay(sign_key);
+    }
+    proxy.run(tokio::io::stdin(), tokio::io::stdout()).await
+}
+
+/// Parses `Name: value` header arguments, expanding `${ENV}` references.
+pub fn parse_headers(raw: &[String]) -> Result<Vec<(String, String)>> {
+    let expanded = expand_args(raw).map_err(|err| anyhow!("Invalid --header: {}", err))?;
+    expanded
+        .iter()
+        .map(|header| {
+            let (name, value) = header
+                .split_once(':')
+                .ok_or_else(|| anyhow!("Invalid --header '{}': expected 'Name: value'", header))?;
+            let name = name.trim();
+            let value = value.trim();
+            if name.is_empty()
+                || header.contains(['\r', '\n'])
+                || !name
+                    .chars()
+                    .all(|ch| ch.is_ascii_alphanumeric() || ch == '-')
+            {
+                return Err(anyhow!("Invalid --header name '{}'", name));
+            }
+            Ok((name.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+fn parse_response(raw: &[u8]) -> Result<HttpResponse> {
+    let split = raw
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or_else(|| anyhow!("Malformed HTTP response: missing header terminator"))?;
+    let head = String::from_utf8_lossy(&raw[..split]);
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| anyhow!("Malformed HTTP response status line"))?;
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect();
+    let mut response = HttpResponse {
+        status,
+        headers,
+        body: raw[split + 4..].to_vec(),
+    };
+    if response
+        .header("transfer-encoding")
+        .is_some_and(|value| value.eq_ignore_ascii_case("chunked"))
+    {
+        response.body = decode_chunked(&response.body)?;
+    }
+    Ok(response)
+}
+
+fn decode_chunked(mut body: &[u8]) -> Result<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(body.len());
+    loop {
+        let line_end = body
+            .windows(2)
+            .position(|window| window == b"\r\n")
+            .ok_or_else(|| anyhow!("Malformed chunked body"))?;
+        let size_text = String::from_utf8_lossy(&body[..line_end]);
+        let size_text = size_text.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size_text, 16)
+            .map_err(|_| anyhow!("Malformed chunk size '{}'", size_text))?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Ok(decoded);
+        }
+        let chunk = body
+            .get(..size)
+            .ok_or_else(|| anyhow!("Truncated chunked body"))?;
+        decoded.extend_from_slice(chunk);
+        body = body.get(size + 2..).unwrap_or_default();
+    }
+}
+
+/// Returns the `data` payload of each server-sent event.
+fn sse_payloads(stream: &str) -> Vec<String> {
+    stream
+        .replace("\r\n", "\n")
+        .split("\n\n")
+        .filter_map(|event| {
+            let data: Vec<&str> = event
+                .lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .map(|data| data.strip_prefix(' ').unwrap_or(data))
+                .collect();
+            (!data.is_empty()).then(|| data.join("\n"))
+        })
+        .collect()
+}
+
+fn error_reply(id: Value, code: i64, message: &str) -> String {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message }
+    })
+    .to_string()
+}
+
+fn reply_if_request(id: Value, message: String) -> Vec<String> {
+    if id.is_null() {
+        Vec::new()
+    } else {
+        vec![error_reply(id, -32603, &message)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_parse() {
+        let endpoint = Endpoint::parse("http://mcp.internal:8080/mcp").unwrap();
+        assert_eq!(endpoint.host, "mcp.internal");
+        assert_eq!(endpoint.port, 8080);
+        assert_eq!(endpoint.path, "/mcp");
+        let default = Endpoint::parse("http://localhost").unwrap();
+        assert_eq!((default.port, default.path.as_str()), (80, "/mcp"));
+        let ipv6 = Endpoint::parse("http://[::1]:9000/mcp").unwrap();
+        assert_eq!((ipv6.host.as_str(), ipv6.port), ("::1", 9000));
+        assert_eq!(ipv6.host_header(), "[::1]:9000");
+        assert!(Endpoint::parse("https://host/mcp").is_err());
+        assert!(Endpoint::parse("ftp://host").is_err());
+    }
+
+    #[test]
+    fn test_parse_headers() {
+        let headers = parse_headers(&["Authorization: Bearer abc".to_string()]).unwrap();
+        assert_eq!(
+            headers,
+            vec![("Authorization".to_string(), "Bearer abc".to_string())]
+        );
+        assert!(parse_headers(&["no separator".to_string()]).is_err());
+        assert!(parse_headers(&["Bad Name: x".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_parse_chunked_response() {
+        let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nMcp-Session-Id: s1\r\n\r\n4\r\n{\"a\"\r\n3\r\n:1}\r\n0\r\n\r\n";
+        let response = parse_response(raw).unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.header("mcp-session-id"), Some("s1"));
+        assert_eq!(response.body, b"{\"a\":1}");
+    }
+
+    #[test]
+    fn test_sse_payloads() {
+        let stream =
+            "event: message\ndata: {\"id\":1}\n\n: keep-alive\n\ndata: {\"id\":\ndata: 2}\n\n";
+        assert_eq!(sse_payloads(stream), vec!["{\"id\":1}", "{\"id\":\n2}"]);
+    }
+}
@@ -0,0 +1,349 @@
+use crate::config::QuotaRule;
+#[cfg(feature = "redis")]
+use crate::{mcp::new_session_id, shared::SharedStore};
+#[cfg(feature = "redis")]
+use chrono::Utc;
+use serde_json::{Value, json};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+#[cfg(feature = "redis")]
+use tracing::warn;
+
+/// Session key used for requests that did not send `Mcp-Session-Id`.
+pub const ANONYMOUS_SESSION: &str = "anonymous";
+pub const MAX_TRACKED_SESSIONS: usize = 10_000;
+pub const QUOTA_STATUS_TOOL: &str = "pcli2_quota_status";
+/// Tools that never count against a quota; batch steps are counted one by one.
+pub const QUOTA_EXEMPT_TOOLS: [&str; 2] = [QUOTA_STATUS_TOOL, "pcli2_batch"];
+
+#[derive(Default)]
+struct SessionUsage {
+    last_seen: Option<Instant>,
+    calls: HashMap<String, VecDeque<Instant>>,
+}
+
+/// Per-session call quotas from the `quotas` configuration. With a shared
+/// store the calls are counted there, so a quota holds across replicas.
+#[derive(Clone, Default)]
+pub struct QuotaTracker {
+    rules: Arc<BTreeMap<String, QuotaRule>>,
+    sessions: Arc<Mutex<HashMap<String, SessionUsage>>>,
+    /// The store, and the scope that keeps this tracker's counts apart
+    /// from those of trackers with quotas of the same name.
+    #[cfg(feature = "redis")]
+    shared: Option<(SharedStore, String)>,
+}
+
+impl QuotaTracker {
+    pub fn new(rules: &HashMap<String, QuotaRule>) -> Self {
+        Self {
+            rules: Arc::new(rules.clone().into_iter().collect()),
+            ..Self::default()
+        }
+    }
+
+    #[cfg(feature = "redis")]
+    pub fn with_shared(self, shared: SharedStore, scope: &str) -> Self {
+        Self {
+            shared: Some((shared, scope.to_string())),
+            ..self
+        }
+    }
+
+    /// The same sessions' usage counted against new `rules`, so reloading
+    /// the configuration does not reset anyone's quota.
+    pub fn with_rules(&self, rules: &HashMap<String, QuotaRule>) -> Self {
+        Self {
+            rules: Arc::new(rules.clone().into_iter().collect()),
+            sessions: self.sessions.clone(),
+            #[cfg(feature = "redis")]
+            shared: self.shared.clone(),
+        }
+    }
+
+    fn applicable<'a>(
+        &'a self,
+        tool: &'a str,
+    ) -> impl Iterator<Item = (&'a String, &'a QuotaRule)> {
+        self.rules.iter().filter(move |(_, rule)| {
+            !QUOTA_EXEMPT_TOOLS.contains(&tool)
+                && (rule.tools.is_empty() || rule.tools.iter().any(|name| name == tool))
+        })
+    }
+
+    /// Counts one call of `tool` for `session`, or explains which quota is
+    /// exhausted. Nothing is counted when the call is rejected. Calls are
+    /// counted locally while the shared store cannot be reached.
+    pub async fn admit(&self, session: &str, tool: &str) -> Result<(), String> {
+        if self.rules.is_empty() {
+            return Ok(());
+        }
+        #[cfg(feature = "redis")]
+        if let Some((shared, scope)) = &self.shared {
+            match self.admit_shared(shared, scope, session, tool).await {
+                Ok(admitted) => return admitted,
+                Err(err) => warn!("⚠ failed to count the call in the shared store: {}", err),
+            }
+        }
+        self.admit_local(session, tool)
+    }
+
+    fn admit_local(&self, session: &str, tool: &str) -> Result<(), String> {
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        if !sessions.contains_key(session) && sessions.len() >= MAX_TRACKED_SESSIONS {
+            evict_oldest(&mut sessions);
+        }
+        let usage = sessions.entry(session.to_string()).or_default();
+        usage.last_seen = Some(now);
+        for (name, rule) in self.applicable(tool) {
+            let calls = usage.calls.entry(name.clone()).or_default();
+            expire(calls, rule, now);
+            if calls.len() >= rule.max_calls as usize {
+                let oldest = calls.front().map(|oldest| now - *oldest);
+                return Err(exhausted(name, rule, calls.len(), oldest));
+            }
+        }
+        for (name, _) in self.applicable(tool) {
+            if let Some(calls) = usage.calls.get_mut(name) {
+                calls.push_back(now);
+            }
+        }
+        Ok(())
+    }
+
+    /// Counts the call first and takes it back if that went over a quota,
+    /// so replicas admitting calls at the same time never exceed it.
+    #[cfg(feature = "redis")]
+    async fn admit_shared(
+        &self,
+        shared: &SharedStore,
+        scope: &str,
+        session: &str,
+        tool: &str,
+    ) -> Result<Result<(), String>, String> {
+        let now = Utc::now().timestamp_millis();
+        let call = new_session_id();
+        let mut counted = Vec::new();
+        for (name, rule) in self.applicable(tool) {
+            let counter = format!("{}:{}:{}", scope, name, session);
+            let calls = shared.add_call(&counter, &call, now, rule.window()).await?;
+            counted.push(counter);
+            if calls.len() > rule.max_calls as usize {
+                for counter in &counted {
+                    shared.remove_call(counter, &call).await?;
+                }
+                let oldest = calls.first().map(|at| age(now, *at));
+                return Ok(Err(exhausted(name, rule, calls.len() - 1, oldest)));
+            }
+        }
+        Ok(Ok(()))
+    }
+
+    /// Usage of every configured quota for `session`.
+    pub async fn status(&self, session: &str) -> Value {
+        #[cfg(feature = "redis")]
+        if let Some((shared, scope)) = &self.shared {
+            match self.status_shared(shared, scope, session).await {
+                Ok(status) => return status,
+                Err(err) => warn!(
+                    "⚠ failed to read quota usage from the shared store: {}",
+                    err
+                ),
+            }
+        }
+        self.status_local(session)
+    }
+
+    fn status_local(&self, session: &str) -> Value {
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let usage = sessions.get_mut(session);
+        let mut empty = HashMap::new();
+        let calls_by_rule = match usage {
+            Some(usage) => &mut usage.calls,
+            None => &mut empty,
+        };
+        let quotas: Vec<Value> = self
+            .rules
+            .iter()
+            .map(|(name, rule)| {
+                let calls = calls_by_rule.entry(name.clone()).or_default();
+                expire(calls, rule, now);
+                let oldest = calls.front().map(|oldest| now - *oldest);
+                rule_status(name, rule, calls.len(), oldest)
+            })
+            .collect();
+        json!({ "session": session, "quotas": quotas })
+    }
+
+    #[cfg(feature = "redis")]
+    async fn status_shared(
+        &self,
+        shared: &SharedStore,
+        scope: &str,
+        session: &str,
+    ) -> Result<Value, String> {
+        let now = Utc::now().timestamp_millis();
+        let mut quotas = Vec::new();
+        for (name, rule) in self.rules.iter() {
+            let counter = format!("{}:{}:{}", scope, name, session);
+            let calls = shared.calls(&counter, now, rule.window()).await?;
+            let oldest = calls.first().map(|at| age(now, *at));
+            quotas.push(rule_status(name, rule, calls.len(), oldest));
+        }
+        Ok(json!({ "session": session, "quotas": quotas }))
+    }
+}
+
+/// Why a call is refused by quota `name`, under which `used` calls were
+/// made, the oldest of them `oldest` ago.
+fn exhausted(name: &str, rule: &QuotaRule, used: usize, oldest: Option<Duration>) -> String {
+    let reset = match (rule.window(), oldest) {
+        (Some(window), Some(oldest)) => format!(
+            "Next call allowed in {}s.",
+            window.saturating_sub(oldest).as_secs().max(1)
+        ),
+        _ => "The limit applies for the rest of the session.".to_string(),
+    };
+    format!(
+        "Quota '{}' exhausted: {} of {} calls used{}. {}",
+        name,
+        used,
+        rule.max_calls,
+        window_label(rule),
+        reset
+    )
+}
+
+fn rule_status(name: &str, rule: &QuotaRule, used: usize, oldest: Option<Duration>) -> Value {
+    let used = used as u32;
+    let resets_in_secs = match (rule.window(), oldest) {
+        (Some(window), Some(oldest)) => json!(window.saturating_sub(oldest).as_secs()),
+        _ => Value::Null,
+    };
+    json!({
+        "name": name,
+        "tools": if rule.tools.is_empty() { json!("*") } else { json!(rule.tools) },
+        "max_calls": rule.max_calls,
+        "window_secs": rule.window_secs,
+        "used": used,
+        "remaining": rule.max_calls.saturating_sub(used),
+        "resets_in_secs": resets_in_secs
+    })
+}
+
+#[cfg(feature = "redis")]
+fn age(now_ms: i64, at_ms: i64) -> Duration {
+    Duration::from_millis(now_ms.saturating_sub(at_ms).max(0) as u64)
+}
+
+fn expire(calls: &mut VecDeque<Instant>, rule: &QuotaRule, now: Instant) {
+    if let Some(window) = rule.window() {
+        while calls
+            .front()
+            .is_some_and(|call| now.duration_since(*call) >= window)
+        {
+            calls.pop_front();
+        }
+    }
+}
+
+fn window_label(rule: &QuotaRule) -> String {
+    match rule.window_secs {
+        Some(secs) => format!(" in the last {}s", secs),
+        None => " in this session".to_string(),
+    }
+}
+
+fn evict_oldest(sessions: &mut HashMap<String, SessionUsage>) {
+    let oldest = sessions
+        .iter()
+        .min_by_key(|(_, usage)| usage.last_seen)
+        .map(|(session, _)| session.clone());
+    if let Some(session) = oldest {
+        sessions.remove(&session);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker() -> QuotaTracker {
+        let rules = HashMap::from([
+            (
+                "folder_match".to_string(),
+                QuotaRule {
+                    tools: vec!["pcli2_folder_geometric_match".to_string()],
+                    max_calls: 2,
+                    window_secs: Some(3600),
+                },
+            ),
+            (
+                "calls".to_string(),
+                QuotaRule {
+                    tools: Vec::new(),
+                    max_calls: 3,
+                    window_secs: None,
+                },
+            ),
+        ]);
+        QuotaTracker::new(&rules)
+    }
+
+    #[tokio::test]
+    async fn test_quotas_are_per_session_and_per_tool() {
+        let quotas = tracker();
+        let tool = "pcli2_folder_geometric_match";
+        assert!(quotas.admit("a", tool).await.is_ok());
+        assert!(quotas.admit("a", tool).await.is_ok());
+        let err = quotas.admit("a", tool).await.unwrap_err();
+        assert!(err.contains("Quota 'folder_match' exhausted: 2 of 2"));
+        assert!(err.contains("Next call allowed in"));
+        assert!(quotas.admit("b", tool).await.is_ok());
+
+        assert!(quotas.admit("a", "pcli2_asset_get").await.is_ok());
+        let err = quotas.admit("a", "pcli2_asset_get").await.unwrap_err();
+        assert!(err.contains("Quota 'calls'"));
+        assert!(quotas.admit("a", "pcli2_quota_status").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_new_rules_keep_usage() {
+        let quotas = tracker();
+        let tool = "pcli2_folder_geometric_match";
+        quotas.admit("a", tool).await.unwrap();
+        quotas.admit("a", tool).await.unwrap();
+        let relaxed = quotas.with_rules(&HashMap::new());
+        assert!(relaxed.admit("a", tool).await.is_ok());
+        let strict = relaxed.with_rules(&HashMap::from([(
+            "folder_match".to_string(),
+            QuotaRule {
+                tools: vec![tool.to_string()],
+                max_calls: 2,
+                window_secs: Some(3600),
+            },
+        )]));
+        assert!(strict.admit("a", tool).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_status_reports_remaining_calls() {
+        let quotas = tracker();
+        quotas
+            .admit("a", "pcli2_folder_geometric_match")
+            .await
+            .unwrap();
+        let status = quotas.status("a").await;
+        assert_eq!(status["quotas"][0]["name"], "calls");
+        assert_eq!(status["quotas"][0]["remaining"], 2);
+        assert_eq!(status["quotas"][0]["resets_in_secs"], Value::Null);
+        assert_eq!(status["quotas"][1]["used"], 1);
+        assert!(status["quotas"][1]["resets_in_secs"].as_u64().unwrap() > 3500);
+        assert_eq!(quotas.status("b").await["quotas"][1]["used"], 0);
+    }
+}
@@ -0,0 +1,337 @@
+use crate::config::RedactionConfig;
+use serde_json::Value;
+use std::sync::OnceLock;
+
+pub const REDACTED: &str = "***";
+
+const SENSITIVE_FLAGS: [&str; 9] = [
+    "--client-secret",
+    "--secret",
+    "--password",
+    "--token",
+    "--access-token",
+    "--refresh-token",
+    "--api-key",
+    "--apikey",
+    "--authorization",
+];
+
+const SENSITIVE_KEYS: [&str; 9] = [
+    "access_token",
+    "refresh_token",
+    "client_secret",
+    "token",
+    "secret",
+    "password",
+    "api_key",
+    "apikey",
+    "authorization",
+];
+
+const MIN_SECRET_LEN: usize = 4;
+
+const METADATA_VALUE_FLAG: &str = "--value";
+
+static REDACTOR: OnceLock<Redactor> = OnceLock::new();
+
+pub fn install(redactor: Redactor) {
+    let _ = REDACTOR.set(redactor);
+}
+
+pub fn redactor() -> &'static Redactor {
+    REDACTOR.get_or_init(Redactor::default)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    metadata_properties: Vec<String>,
+}
+
+impl Redactor {
+    pub fn new(config: &RedactionConfig) -> Self {
+        Self {
+            metadata_properties: config
+                .metadata_properties
+                .iter()
+                .map(|name| name.to_ascii_lowercase())
+                .collect(),
+        }
+    }
+
+    fn has_sensitive_metadata(&self, args: &[String]) -> bool {
+        args.windows(2).any(|pair| {
+            pair[0] == "--name"
+                && self
+                    .metadata_properties
+                    .contains(&pair[1].to_ascii_lowercase())
+        })
+    }
+
+    /// Returns the argument values that must never be logged or echoed:
+    /// values of credential flags and of sensitive metadata properties.
+    pub fn secrets(&self, args: &[String]) -> Vec<String> {
+        let sensitive_metadata = self.has_sensitive_metadata(args);
+        let is_secret_flag = |flag: &str| {
+            is_sensitive_flag(flag) || (sensitive_metadata && flag == METADATA_VALUE_FLAG)
+        };
+        let mut secrets = Vec::new();
+        for (index, arg) in args.iter().enumerate() {
+            if let Some((flag, value)) = arg.split_once('=')
+                && is_secret_flag(flag)
+            {
+                secrets.push(value.to_string());
+            } else if let Some(value) = args.get(index + 1)
+                && is_secret_flag(arg)
+            {
+                secrets.push(value.clone());
+            }
+        }
+        secrets
+    }
+
+    pub fn redact_args(&self, args: &[String]) -> Vec<String> {
+        let sensitive_metadata = self.has_sensitive_metadata(args);
+        let is_secret_flag = |flag: &str| {
+            is_sensitive_flag(flag) || (sensitive_metadata && flag == METADATA_VALUE_FLAG)
+        };
+        let mut masked = Vec::with_capacity(args.len());
+        let mut mask_next = false;
+        for arg in args {
+            if std::mem::take(&mut mask_next) {
+                masked.push(REDACTED.to_string());
+                continue;
+            }
+            match arg.split_once('=') {
+                Some((flag, _)) if is_secret_flag(flag) => {
+                    masked.push(format!("{}={}", flag, REDACTED));
+                }
+                _ => {
+                    mask_next = is_secret_flag(arg);
+                    masked.push(arg.clone());
+                }
+            }
+        }
+        masked
+    }
+
+    /// Masks tool arguments: values under credential keys, the `value` of a
+    /// sensitive metadata property, and secrets embedded in strings.
+    pub fn redact_value(&self, value: &Value) -> Value {
+        match value {
+            Value::Object(obj) => {
+                let sensitive_metadata =
+                    obj.get("name").and_then(Value::as_str).is_some_and(|name| {
+                        self.metadata_properties
+                            .contains(&name.to_ascii_lowercase())
+                    });
+                obj.iter()
+                    .map(|(key, value)| {
+                        let masked = SENSITIVE_KEYS.contains(&key.to_ascii_lowercase().as_str())
+                            || (sensitive_metadata && key == "value");
+                        let value = if masked {
+                            Value::String(REDACTED.to_string())
+                        } else {
+                            self.redact_value(value)
+                        };
+                        (key.clone(), value)
+                    })
+                    .collect()
+            }
+            Value::Array(items) => items.iter().map(|item| self.redact_value(item)).collect(),
+            Value::String(text) => Value::String(self.redact_text(text, &[])),
+            other => other.clone(),
+        }
+    }
+
+    pub fn redact_text(&self, text: &str, secrets: &[String]) -> String {
+        let mut out = text.to_string();
+        for secret in secrets {
+            if secret.len() >= MIN_SECRET_LEN {
+                out = out.replace(secret.as_str(), REDACTED);
+            }
+        }
+        let out = mask_bearer_tokens(&out);
+        let out = mask_jwts(&out);
+        mask_key_values(&out)
+    }
+}
+
+fn is_sensitive_flag(arg: &str) -> bool {
+    SENSITIVE_FLAGS.contains(&arg.to_ascii_lowercase().as_str())
+}
+
+fn is_token_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_' | '.' | '~' | '+' | '/' | '=')
+}
+
+fn token_end(text: &str, start: usize) -> usize {
+    text[start..]
+        .char_indices()
+        .find(|(_, ch)| !is_token_char(*ch))
+        .map(|(offset, _)| start + offset)
+        .unwrap_or(text.len())
+}
+
+fn mask_bearer_tokens(text: &str) -> String {
+    let lower = text.to_ascii_lowercase();
+    let mut out = String::with_capacity(text.len());
+    let mut cursor = 0;
+    while let Some(found) = lower[cursor..].find("bearer ") {
+        let start = cursor + found + "bearer ".len();
+        let end = token_end(text, start);
+        out.push_str(&text[cursor..start]);
+        if end > start {
+            out.push_str(REDACTED);
+        }
+        cursor = end;
+    }
+    out.push_str(&text[cursor..]);
+    out
+}
+
+fn mask_jwts(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut cursor = 0;
+    while let Some(found) = text[cursor..].find("eyJ") {
+        let start = cursor + found;
+        let end = token_end(text, start);
+        out.push_str(&text[cursor..start]);
+        if text[start..end].matches('.').count() >= 2 {
+            out.push_str(REDACTED);
+        } else {
+            out.push_str(&text[start..end]);
+        }
+        cursor = end.max(start + 3);
+    }
+    out.push_str(&text[cursor..]);
+    out
+}
+
+fn mask_key_values(text: &str) -> String {
+    let lower = text.to_ascii_lowercase();
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for key in SENSITIVE_KEYS {
+        let mut cursor = 0;
+        while let Some(found) = lower[cursor..].find(key) {
+            let key_end = cursor + found + key.len();
+            cursor = key_end;
+            let rest = &text[key_end..];
+            let skipped = rest.len() - rest.trim_start_matches(['"', '\'', ' ']).len();
+            let after_key = key_end + skipped;
+            if !matches!(text[after_key..].chars().next(), Some('=' | ':')) {
+                continue;
+            }
+            let rest = &text[after_key + 1..];
+            let value_start =
+                after_key + 1 + rest.len() - rest.trim_start_matches(['"', '\'', ' ']).len();
+            let value_end = token_end(text, value_start);
+            if value_end > value_start {
+                ranges.push((value_start, value_end));
+            }
+        }
+    }
+    ranges.sort_unstable();
+    let mut out = String::with_capacity(text.len());
+    let mut cursor = 0;
+    for (start, end) in ranges {
+        if start < cursor {
+            continue;
+        }
+        out.push_str(&text[cursor..start]);
+        out.push_str(REDACTED);
+        cursor = end;
+    }
+    out.push_str(&text[cursor..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn test_redact_args_masks_credential_flags() {
+        let redactor = Redactor::default();
+        let args = strings(&[
+            "auth",
+            "login",
+            "--client-secret",
+            "abc123",
+            "--token=xyz987",
+        ]);
+        assert_eq!(
+            redactor.redact_args(&args),
+            strings(&["auth", "login", "--client-secret", "***", "--token=***"])
+        );
+    }
+
+    #[test]
+    fn test_redact_args_masks_configured_metadata() {
+        let redactor = Redactor::new(&RedactionConfig {
+            metadata_properties: strings(&["Cost"]),
+        });
+        let args = strings(&[
+            "asset", "metadata", "create", "--name", "cost", "--value", "1250",
+        ]);
+        let masked = redactor.redact_args(&args);
+        assert_eq!(masked[6], "***");
+        let attached = strings(&[
+            "asset",
+            "metadata",
+            "create",
+            "--name",
+            "cost",
+            "--value=-5",
+        ]);
+        assert_eq!(redactor.redact_args(&attached)[5], "--value=***");
+        assert_eq!(redactor.secrets(&attached), strings(&["-5"]));
+        let public = strings(&[
+            "asset", "metadata", "create", "--name", "Weight", "--value", "2",
+        ]);
+        assert_eq!(redactor.redact_args(&public), public);
+    }
+
+    #[test]
+    fn test_redact_value_masks_tool_arguments() {
+        let redactor = Redactor::new(&RedactionConfig {
+            metadata_properties: strings(&["Supplier"]),
+        });
+        let args = serde_json::json!({
+            "client_id": "mcp-agent",
+            "client_secret": "hunter22",
+            "name": "supplier",
+            "value": "Acme",
+            "path": ["Bearer abc.def", "/Root/A.stl"]
+        });
+        assert_eq!(
+            redactor.redact_value(&args),
+            serde_json::json!({
+                "client_id": "mcp-agent",
+                "client_secret": "***",
+                "name": "supplier",
+                "value": "***",
+                "path": ["Bearer ***", "/Root/A.stl"]
+            })
+        );
+    }
+
+    #[test]
+    fn test_redact_text() {
+        let redactor = Redactor::default();
+        let text = "request failed: Authorization: Bearer abc.def.ghi token=s3cr3t \"api_key\": \"k-123\" jwt eyJhbGc.eyJzdWI.sig and token has expired";
+        let masked = redactor.redact_text(text, &strings(&["hunter22"]));
+        assert!(!masked.contains("abc.def.ghi"));
+        assert!(!masked.contains("s3cr3t"));
+        assert!(!masked.contains("k-123"));
+        assert!(!masked.contains("eyJzdWI"));
+        assert!(masked.contains("token has expired"));
+        assert_eq!(
+            redactor.redact_text("echo hunter22", &strings(&["hunter22"])),
+            "echo ***"
+        );
+    }
+}
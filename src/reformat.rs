@@ -0,0 +1,330 @@
+use crate::{
+    content::summary_block,
+    paging::{csv_records, json_rows},
+    projection::{ask_for_rows, csv_fields, has_rows, unquote},
+    summary::SUMMARIZE_ARG,
+};
+use serde_json::{Map, Value, json};
+
+pub const RESPONSE_FORMAT_ARG: &str = "response_format";
+pub const RESPONSE_FORMATS: [&str; 3] = ["json", "csv", "markdown_table"];
+
+/// Whether a call of `tool` asks for its rows in a format of the server's
+/// choosing rather than pcli2's.
+pub fn wants_response_format(tool: &str, args: &Value) -> bool {
+    has_rows(tool) && args.get(RESPONSE_FORMAT_ARG).is_some()
+}
+
+fn response_format(args: &Value) -> Option<&str> {
+    args.get(RESPONSE_FORMAT_ARG).and_then(Value::as_str)
+}
+
+/// Checks the response format and asks pcli2 for rows to convert.
+pub fn prepare_response_format(args: &mut Value) -> Result<(), String> {
+    let format = response_format(args).unwrap_or_default();
+    if !RESPONSE_FORMATS.contains(&format) {
+        return Err(format!(
+            "Invalid argument '{}': expected one of {}",
+            RESPONSE_FORMAT_ARG,
+            RESPONSE_FORMATS.join(", ")
+        ));
+    }
+    let summarize = args
+        .get(SUMMARIZE_ARG)
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    if summarize && format != "json" {
+        return Err(format!(
+            "Invalid argument '{}': a summary is JSON; drop '{}' or set it to json",
+            RESPONSE_FORMAT_ARG, RESPONSE_FORMAT_ARG
+        ));
+    }
+    ask_for_rows(args, &format!("'{}' needs", RESPONSE_FORMAT_ARG))
+}
+
+/// The MIME type of a result converted to `args`' response format.
+pub fn response_mime_type(args: &Value) -> Option<&'static str> {
+    match response_format(args)? {
+        "json" => Some("application/json"),
+        "csv" => Some("text/csv"),
+        "markdown_table" => Some("text/markdown"),
+        _ => None,
+    }
+}
+
+/// Rows as named columns, in the order the columns were first seen.
+struct Table {
+    columns: Vec<String>,
+    rows: Vec<Vec<Option<String>>>,
+}
+
+fn cell_text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(text) => Some(text.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+impl Table {
+    fn from_json(rows: &[Value]) -> Table {
+        let mut columns: Vec<String> = Vec::new();
+        for row in rows {
+            match row {
+                Value::Object(object) => {
+                    for key in object.keys() {
+                        if !columns.contains(key) {
+                            columns.push(key.clone());
+                        }
+                    }
+                }
+                _ if !columns.iter().any(|column| column == "value") => {
+                    columns.push("value".to_string())
+                }
+                _ => {}
+            }
+        }
+        let rows = rows
+            .iter()
+            .map(|row| {
+                columns
+                    .iter()
+                    .map(|column| match row {
+                        Value::Object(object) => object.get(column).and_then(cell_text),
+                        other if column == "value" => cell_text(other),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .collect();
+        Table { columns, rows }
+    }
+
+    fn from_csv(text: &str) -> Option<Table> {
+        let records = csv_records(text);
+        let (header, rows) = records.split_first()?;
+        let columns: Vec<String> = csv_fields(header).iter().map(|f| unquote(f)).collect();
+        let rows = rows
+            .iter()
+            .map(|row| {
+                let fields = csv_fields(row);
+                (0..columns.len())
+                    .map(|index| {
+                        fields
+                            .get(index)
+                            .map(|field| unquote(field))
+                            .filter(|text| !text.is_empty())
+                    })
+                    .collect()
+            })
+            .collect();
+        Some(Table { columns, rows })
+    }
+
+    fn to_json(&self) -> Value {
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| {
+                let object: Map<String, Value> = self
+                    .columns
+                    .iter()
+                    .zip(row)
+                    .map(|(column, cell)| {
+                        (
+                            column.clone(),
+                            cell.clone().map_or(Value::Null, Value::String),
+                        )
+                    })
+                    .collect();
+                Value::Object(object)
+            })
+            .collect();
+        Value::Array(rows)
+    }
+
+    fn to_csv(&self) -> String {
+        let quote = |text: &str| {
+            if text.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", text.replace('"', "\"\""))
+            } else {
+                text.to_string()
+            }
+        };
+        std::iter::once(
+            self.columns
+                .iter()
+                .map(|column| quote(column))
+                .collect::<Vec<_>>(),
+        )
+        .chain(self.rows.iter().map(|row| {
+            row.iter()
+                .map(|cell| cell.as_deref().map(quote).unwrap_or_default())
+                .collect()
+        }))
+        .map(|fields| fields.join(","))
+        .collect::<Vec<_>>()
+        .join("\n")
+    }
+
+    fn to_markdown(&self) -> String {
+        let escape = |text: &str| text.replace('|', "\\|").replace(['\r', '\n'], " ");
+        let line = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+        let mut lines = vec![
+            line(self.columns.iter().map(|column| escape(column)).collect()),
+            line(self.columns.iter().map(|_| "---".to_string()).collect()),
+        ];
+        lines.extend(self.rows.iter().map(|row| {
+            line(
+                row.iter()
+                    .map(|cell| cell.as_deref().map(escape).unwrap_or_default())
+                    .collect(),
+            )
+        }));
+        lines.join("\n")
+    }
+}
+
+/// A Markdown table of JSON rows, one column per key in order of first
+/// appearance.
+pub(crate) fn markdown_table(rows: &[Value]) -> String {
+    Table::from_json(rows).to_markdown()
+}
+
+/// A Markdown table with the columns in the order given.
+pub(crate) fn markdown_columns(columns: &[&str], rows: Vec<Vec<String>>) -> String {
+    Table {
+        columns: columns.iter().map(|column| column.to_string()).collect(),
+        rows: rows
+            .into_iter()
+            .map(|row| row.into_iter().map(Some).collect())
+            .collect(),
+    }
+    .to_markdown()
+}
+
+/// The output converted to `format`; `None` when it has no rows.
+fn convert(text: &str, args: &Value, format: &str) -> Option<String> {
+    let csv = args.get("format").and_then(Value::as_str) == Some("csv");
+    if !csv && format == "json" {
+        // JSON rows are already JSON; the output keeps its own shape.
+        let mut output = serde_json::from_str::<Value>(text).ok()?;
+        json_rows(&mut output)?;
+        return Some(text.to_string());
+    }
+    let table = if csv {
+        Table::from_csv(text)?
+    } else {
+        let mut output = serde_json::from_str::<Value>(text).ok()?;
+        Table::from_json(json_rows(&mut output)?)
+    };
+    Some(match format {
+        "json" => serde_json::to_string_pretty(&table.to_json()).ok()?,
+        "csv" => table.to_csv(),
+        _ => table.to_markdown(),
+    })
+}
+
+/// Converts the rows in the last text block of `result` to the response
+/// format, whatever format pcli2 wrote them in. An output without rows is
+/// returned as it was.
+pub fn reformat_result(args: &Value, mut result: Value) -> Value {
+    let Some(format) = response_format(args) else {
+        return result;
+    };
+    let Some(blocks) = result.get_mut("content").and_then(Value::as_array_mut) else {
+        return result;
+    };
+    let Some(block) = blocks
+        .iter_mut()
+        .rev()
+        .find(|block| block["type"] == "text")
+    else {
+        return result;
+    };
+    match block["text"]
+        .as_str()
+        .and_then(|text| convert(text, args, format))
+    {
+        Some(text) => block["text"] = json!(text),
+        None => blocks.insert(
+            0,
+            summary_block(format!(
+                "The output has no rows to convert to {}; it is returned as pcli2 wrote it.",
+                format
+            )),
+        ),
+    }
+    result["_meta"][RESPONSE_FORMAT_ARG] = json!(format);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::{raw_output_block, tool_result};
+
+    fn converted(args: Value, text: &str) -> String {
+        let result = reformat_result(&args, tool_result(vec![raw_output_block(text)]));
+        result["content"][0]["text"].as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_json_rows_are_converted() {
+        let matches = json!({ "matches": [
+            { "path": "/A, B.stl", "score": 99 },
+            { "path": "/C|D.stl", "note": "x", "score": null }
+        ] })
+        .to_string();
+        assert_eq!(
+            converted(json!({ "response_format": "csv" }), &matches),
+            "path,score,note\n\"/A, B.stl\",99,\n/C|D.stl,,x"
+        );
+        assert_eq!(
+            converted(json!({ "response_format": "markdown_table" }), &matches),
+            "| path | score | note |\n| --- | --- | --- |\n| /A, B.stl | 99 |  |\n| /C\\|D.stl |  | x |"
+        );
+        assert_eq!(
+            converted(json!({ "response_format": "json" }), &matches),
+            matches
+        );
+    }
+
+    #[test]
+    fn test_csv_rows_are_converted() {
+        let csv = "NAME,SCORE\n\"a, b\",99\nc,\n";
+        let args = json!({ "format": "csv", "headers": true, "response_format": "json" });
+        let rows: Value = serde_json::from_str(&converted(args, csv)).unwrap();
+        assert_eq!(
+            rows,
+            json!([{ "NAME": "a, b", "SCORE": "99" }, { "NAME": "c", "SCORE": null }])
+        );
+
+        let text = reformat_result(
+            &json!({ "response_format": "csv" }),
+            tool_result(vec![raw_output_block("done")]),
+        );
+        assert_eq!(text["content"][1]["text"], "done");
+        assert_eq!(text["_meta"]["response_format"], "csv");
+    }
+
+    #[test]
+    fn test_response_format_is_checked() {
+        let mut args = json!({ "response_format": "markdown_table" });
+        prepare_response_format(&mut args).unwrap();
+        assert_eq!(args["format"], "json");
+        assert!(prepare_response_format(&mut json!({ "response_format": "xml" })).is_err());
+        assert!(
+            prepare_response_format(&mut json!({ "response_format": "csv", "summarize": true }))
+                .is_err()
+        );
+        assert!(
+            prepare_response_format(&mut json!({ "response_format": "csv", "format": "tree" }))
+                .is_err()
+        );
+        assert_eq!(
+            response_mime_type(&json!({ "response_format": "markdown_table" })),
+            Some("text/markdown")
+        );
+    }
+}
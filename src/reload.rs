@@ -0,0 +1,225 @@
+use crate::{
+    AppState, config::ServerConfig, pcli::public_tool_list, policy::PolicyQuotas,
+    quota::QuotaTracker, server::load_config_file, set_log_level,
+};
+use serde::Serialize;
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+/// The configuration in force after the last reload.
+#[derive(Clone)]
+struct Reloaded {
+    config: Arc<ServerConfig>,
+    quotas: QuotaTracker,
+    policy_quotas: PolicyQuotas,
+}
+
+/// What a reload did, as `POST /admin/reload` answers.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ReloadSummary {
+    #[schema(example = "reloaded")]
+    pub status: &'static str,
+    /// The configuration file that was read.
+    pub config: String,
+    /// Whether clients were told the tool list changed.
+    pub tools_changed: bool,
+    /// Changed sections that are only read at startup.
+    pub restart_required: Vec<&'static str>,
+}
+
+/// Rereads `--config` on SIGHUP or `POST /admin/reload`. Requests pick up
+/// the new configuration when they start; running calls finish with the old.
+#[derive(Clone, Default)]
+pub struct ConfigReloader {
+    path: Option<PathBuf>,
+    latest: Arc<Mutex<Option<Reloaded>>>,
+}
+
+fn keep<T: Clone + PartialEq>(
+    section: &'static str,
+    running: &T,
+    loaded: &mut T,
+    kept: &mut Vec<&'static str>,
+) {
+    if running != loaded {
+        *loaded = running.clone();
+        kept.push(section);
+    }
+}
+
+/// Puts back the sections read once at startup, and returns the names of
+/// those the file changed; they need a restart.
+fn keep_startup_sections(running: &ServerConfig, loaded: &mut ServerConfig) -> Vec<&'static str> {
+    let mut kept = Vec::new();
+    keep("ssh", &running.ssh, &mut loaded.ssh, &mut kept);
+    keep("docker", &running.docker, &mut loaded.docker, &mut kept);
+    keep(
+        "shared_store",
+        &running.shared_store,
+        &mut loaded.shared_store,
+        &mut kept,
+    );
+    keep("watch", &running.watch, &mut loaded.watch, &mut kept);
+    keep("usage", &running.usage, &mut loaded.usage, &mut kept);
+    keep("warm", &running.warm, &mut loaded.warm, &mut kept);
+    keep("cache", &running.cache, &mut loaded.cache, &mut kept);
+    keep(
+        "listeners",
+        &running.listeners,
+        &mut loaded.listeners,
+        &mut kept,
+    );
+    keep(
+        "circuit_breaker",
+        &running.circuit_breaker,
+        &mut loaded.circuit_breaker,
+        &mut kept,
+    );
+    keep("auth", &running.auth, &mut loaded.auth, &mut kept);
+    keep(
+        "redaction",
+        &running.redaction,
+        &mut loaded.redaction,
+        &mut kept,
+    );
+    kept
+}
+
+impl ConfigReloader {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self {
+            path,
+            latest: Arc::default(),
+        }
+    }
+
+    /// Brings `state` up to the last reloaded configuration.
+    pub fn apply(&self, state: &mut AppState) {
+        let latest = self.latest.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(latest) = latest.as_ref() {
+            state.config = latest.config.clone();
+            state.quotas = latest.quotas.clone();
+            state.policy_quotas = latest.policy_quotas.clone();
+        }
+    }
+
+    /// Loads and validates the configuration file, and makes it current for
+    /// new requests. Clients with an open `GET /mcp` stream are told when
+    /// the tool list changed. The old configuration stays when the file is
+    /// invalid.
+    pub fn reload(&self, state: &AppState) -> Result<ReloadSummary, String> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| "The server was started without --config".to_string())?;
+        let mut loaded = load_config_file(Some(path)).map_err(|err| format!("{:#}", err))?;
+
+        let mut latest = self.latest.lock().unwrap_or_else(|e| e.into_inner());
+        let mut current = state.clone();
+        if let Some(latest) = latest.as_ref() {
+            current.config = latest.config.clone();
+            current.quotas = latest.quotas.clone();
+            current.policy_quotas = latest.policy_quotas.clone();
+        }
+        let restart_required = keep_startup_sections(&current.config, &mut loaded);
+        if !restart_required.is_empty() {
+            warn!(
+                "⚠ config sections changed that need a restart: {}",
+                restart_required.join(", ")
+            );
+        }
+        if let Some(level) = &loaded.log_level
+            && current.config.log_level.as_ref() != Some(level)
+            && let Err(err) = set_log_level(level)
+        {
+            warn!("⚠ could not change the log level: {}", err);
+        }
+
+        let mut reloaded = current.clone();
+        reloaded.config = Arc::new(loaded);
+        reloaded.quotas = current.quotas.with_rules(&reloaded.config.quotas);
+        reloaded.policy_quotas = current
+            .policy_quotas
+            .with_policies(&reloaded.config.policies);
+        // Bound and policy-held clients see a subset of the same list, so
+        // one check covers all.
+        reloaded.tenant = None;
+        reloaded.policy = None;
+        current.tenant = None;
+        current.policy = None;
+        let tools_changed = public_tool_list(&current) != public_tool_list(&reloaded);
+        *latest = Some(Reloaded {
+            config: reloaded.config,
+            quotas: reloaded.quotas,
+            policy_quotas: reloaded.policy_quotas,
+        });
+        drop(latest);
+
+        if tools_changed {
+            state.notifier.tools_list_changed();
+        }
+        info!(
+            "🔄 reloaded {}{}",
+            path.display(),
+            if tools_changed {
+                "; the tool list changed"
+            } else {
+                ""
+            }
+        );
+        Ok(ReloadSummary {
+            status: "reloaded",
+            config: path.display().to_string(),
+            tools_changed,
+            restart_required,
+        })
+    }
+}
+
+/// Reloads the configuration whenever the process receives SIGHUP.
+#[cfg(unix)]
+pub fn spawn_sighup_reload(state: AppState) -> std::io::Result<tokio::task::JoinHandle<()>> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut hangups = signal(SignalKind::hangup())?;
+    Ok(tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            info!("SIGHUP received, reloading the configuration");
+            if let Err(message) = state.reloader.reload(&state) {
+                warn!("⚠ configuration reload failed: {}", message);
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SshConfig;
+
+    #[test]
+    fn test_startup_sections_keep_their_running_values() {
+        let running = ServerConfig::default();
+        let mut loaded = ServerConfig {
+            ssh: SshConfig {
+                host: Some("cad-01".to_string()),
+                ..SshConfig::default()
+            },
+            log_level: Some("debug".to_string()),
+            ..ServerConfig::default()
+        };
+        assert_eq!(keep_startup_sections(&running, &mut loaded), ["ssh"]);
+        assert_eq!(loaded.ssh.host, None);
+        assert_eq!(loaded.log_level.as_deref(), Some("debug"));
+    }
+
+    #[test]
+    fn test_reload_needs_a_config_file() {
+        let state = AppState::new("test", "0.0.0");
+        assert!(ConfigReloader::default().reload(&state).is_err());
+    }
+}
@@ -0,0 +1,301 @@
+#[cfg(feature = "redis")]
+use crate::shared::SharedStore;
+use crate::{
+    AppState,
+    access::{Refusal, Route, admit_request, hmac_sha256},
+    admin::token_matches,
+    auth::expand_args,
+    config::{ApiKeyConfig, ReplayConfig},
+    tenancy::{Principal, key_principal},
+};
+use axum::http::HeaderMap;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::warn;
+
+/// Longest nonce accepted; clients send a random hex string or a UUID.
+const MAX_NONCE_LEN: usize = 128;
+
+/// Nonces of the `/mcp` requests seen within `access.replay.max_skew_secs`.
+#[derive(Clone, Default)]
+pub struct NonceCache {
+    seen: Arc<Mutex<Nonces>>,
+    /// Where replicas claim nonces, so a request one replica admitted is
+    /// refused by the others.
+    #[cfg(feature = "redis")]
+    shared: Option<SharedStore>,
+}
+
+#[derive(Default)]
+struct Nonces {
+    /// Until when (Unix seconds) each nonce is remembered, by key and nonce.
+    until: HashMap<String, u64>,
+    /// The same in the order they were seen, so the oldest go first.
+    order: VecDeque<(u64, String)>,
+}
+
+impl NonceCache {
+    #[cfg(feature = "redis")]
+    pub fn with_shared(shared: SharedStore) -> Self {
+        Self {
+            shared: Some(shared),
+            ..Self::default()
+        }
+    }
+
+    /// Checks the request's time and remembers its nonce, refusing one the
+    /// same key already used. Nonces are kept apart per API key.
+    pub async fn check(
+        &self,
+        config: &ReplayConfig,
+        key: Option<&str>,
+        headers: &HeaderMap,
+    ) -> Result<(), String> {
+        let (timestamp, nonce) = stamp(config, headers)?;
+        let now = unix_now();
+        let entry = format!("{}\n{}", key.unwrap_or_default(), nonce);
+        let until = timestamp.saturating_add(config.max_skew_secs);
+        #[cfg(feature = "redis")]
+        if let Some(shared) = &self.shared {
+            match shared.claim_nonce(&entry, until.saturating_sub(now)).await {
+                Ok(true) => return Ok(()),
+                Ok(false) => return Err(replayed(nonce)),
+                // Each replica still refuses the nonces it saw itself.
+                Err(err) => warn!("⚠ failed to claim a shared nonce: {}", err),
+            }
+        }
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        while let Some((until, _)) = seen.order.front()
+            && *until < now
+        {
+            if let Some((_, forgotten)) = seen.order.pop_front() {
+                seen.until.remove(&forgotten);
+            }
+        }
+        if seen.until.contains_key(&entry) {
+            return Err(replayed(nonce));
+        }
+        seen.until.insert(entry.clone(), until);
+        seen.order.push_back((until, entry));
+        Ok(())
+    }
+}
+
+fn replayed(nonce: &str) -> String {
+    format!("Replayed request: nonce '{}' was already used", nonce)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty())
+}
+
+/// The request's timestamp, checked against the server clock, and nonce.
+fn stamp<'a>(config: &ReplayConfig, headers: &'a HeaderMap) -> Result<(u64, &'a str), String> {
+    let timestamp = header(headers, &config.timestamp_header)
+        .ok_or_else(|| format!("Missing '{}' header", config.timestamp_header))?
+        .parse::<u64>()
+        .map_err(|_| {
+            format!(
+                "Invalid '{}' header: expected Unix seconds",
+                config.timestamp_header
+            )
+        })?;
+    if timestamp.abs_diff(unix_now()) > config.max_skew_secs {
+        return Err(format!(
+            "Request timestamp is more than {}s away from the server clock",
+            config.max_skew_secs
+        ));
+    }
+    let nonce = header(headers, &config.nonce_header)
+        .ok_or_else(|| format!("Missing '{}' header", config.nonce_header))?;
+    if nonce.len() > MAX_NONCE_LEN {
+        return Err(format!(
+            "Invalid '{}' header: longer than {} characters",
+            config.nonce_header, MAX_NONCE_LEN
+        ));
+    }
+    Ok((timestamp, nonce))
+}
+
+/// The signature of a request in `access.replay.signed` mode: the
+/// lowercase hex HMAC-SHA256 of `<timestamp>\n<nonce>\n<body>`, keyed
+/// with the API key.
+pub fn sign(key: &str, timestamp: u64, nonce: &str, body: &[u8]) -> String {
+    let mut message = format!("{}\n{}\n", timestamp, nonce).into_bytes();
+    message.extend_from_slice(body);
+    hmac_sha256(key.as_bytes(), &message)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Finds the API key the request was signed with. The timestamp and nonce
+/// are checked first, so a signature over a stale request is never
+/// searched for.
+pub fn authenticate_signed(
+    api_keys: &[ApiKeyConfig],
+    config: &ReplayConfig,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<Principal, String> {
+    let (timestamp, nonce) = stamp(config, headers)?;
+    let signature = header(headers, &config.signature_header)
+        .ok_or_else(|| format!("Missing '{}' header", config.signature_header))?
+        .to_ascii_lowercase();
+    for api_key in api_keys {
+        let expected = match expand_args(std::slice::from_ref(&api_key.key)) {
+            Ok(key) => key.concat(),
+            Err(err) => {
+                warn!("⚠ skipping API key: {}", err);
+                continue;
+            }
+        };
+        if !expected.is_empty()
+            && token_matches(&sign(&expected, timestamp, nonce, body), &signature)
+        {
+            return Ok(key_principal(api_key, &expected));
+        }
+    }
+    Err("Invalid request signature".to_string())
+}
+
+/// Admits a `/mcp` request through the route's chain and, under
+/// `access.replay`, checks its timestamp and nonce. `body` is what a
+/// signed request's signature covers: the JSON-RPC message, or nothing for
+/// the event stream and WebSocket upgrades.
+pub async fn admit_mcp(
+    state: &AppState,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<Principal, Refusal> {
+    let principal = admit_request(&state.config, Route::Mcp, headers, Some(body))?;
+    if let Some(replay) = &state.config.access.replay {
+        state
+            .nonces
+            .check(replay, principal.key.as_deref(), headers)
+            .await
+            .map_err(Refusal::Unauthorized)?;
+    }
+    Ok(principal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stamped(timestamp: u64, nonce: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-request-timestamp",
+            timestamp.to_string().parse().unwrap(),
+        );
+        headers.insert("x-request-nonce", nonce.parse().unwrap());
+        headers
+    }
+
+    #[tokio::test]
+    async fn test_nonces_are_used_once_per_key_within_the_window() {
+        let config = ReplayConfig::default();
+        let nonces = NonceCache::default();
+        let now = unix_now();
+        assert!(
+            nonces
+                .check(&config, Some("a"), &stamped(now, "n1"))
+                .await
+                .is_ok()
+        );
+        let err = nonces
+            .check(&config, Some("a"), &stamped(now, "n1"))
+            .await
+            .unwrap_err();
+        assert!(err.contains("already used"), "{}", err);
+        assert!(
+            nonces
+                .check(&config, Some("b"), &stamped(now, "n1"))
+                .await
+                .is_ok()
+        );
+        assert!(
+            nonces
+                .check(&config, Some("a"), &stamped(now, "n2"))
+                .await
+                .is_ok()
+        );
+
+        let err = nonces
+            .check(&config, Some("a"), &stamped(now - 301, "n3"))
+            .await
+            .unwrap_err();
+        assert!(err.contains("300s"), "{}", err);
+        assert!(
+            nonces
+                .check(&config, Some("a"), &stamped(now + 301, "n3"))
+                .await
+                .is_err()
+        );
+        assert!(
+            nonces
+                .check(&config, None, &HeaderMap::new())
+                .await
+                .is_err()
+        );
+        assert!(
+            nonces
+                .check(&config, None, &stamped(now, &"n".repeat(129)))
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_signed_requests_find_their_key() {
+        let keys = vec![
+            ApiKeyConfig {
+                key: "team-a".to_string(),
+                name: Some("castings".to_string()),
+                tenant: Some("castings".to_string()),
+                policy: None,
+            },
+            ApiKeyConfig {
+                key: "ops".to_string(),
+                name: None,
+                tenant: None,
+                policy: None,
+            },
+        ];
+        let config = ReplayConfig {
+            signed: true,
+            ..ReplayConfig::default()
+        };
+        let now = unix_now();
+        let body = br#"{"jsonrpc":"2.0","id":1,"method":"tools/list"}"#;
+        let mut headers = stamped(now, "n1");
+        headers.insert(
+            "x-request-signature",
+            sign("team-a", now, "n1", body).parse().unwrap(),
+        );
+        let principal = authenticate_signed(&keys, &config, &headers, body).unwrap();
+        assert_eq!(principal.tenant.as_deref(), Some("castings"));
+        assert_eq!(principal.key.as_deref(), Some("castings"));
+
+        // Any change to the body or the stamp breaks the signature.
+        assert!(authenticate_signed(&keys, &config, &headers, b"{}").is_err());
+        let mut moved = headers.clone();
+        moved.insert("x-request-nonce", "n2".parse().unwrap());
+        assert!(authenticate_signed(&keys, &config, &moved, body).is_err());
+        headers.remove("x-request-signature");
+        assert!(authenticate_signed(&keys, &config, &headers, body).is_err());
+    }
+}
@@ -0,0 +1,301 @@
+use crate::{
+    content::{AUDIENCE_ASSISTANT, AUDIENCE_USER, PRIORITY_SUMMARY, annotations},
+    reformat::{markdown_columns, markdown_table},
+    snapshot::DIFF_TOOL,
+    structured::is_match_tool,
+    summary::SUMMARIZE_ARG,
+};
+use serde_json::{Value, json};
+
+pub const RENDER_ARG: &str = "render";
+pub const RENDERS: [&str; 2] = ["json", "markdown"];
+
+/// Whether a call of `tool` asks for its report as Markdown: a folder
+/// diff or a match summary.
+pub fn wants_markdown(tool: &str, args: &Value) -> bool {
+    let report = tool == DIFF_TOOL
+        || (is_match_tool(tool)
+            && args
+                .get(SUMMARIZE_ARG)
+                .and_then(Value::as_bool)
+                .unwrap_or(false));
+    report && args.get(RENDER_ARG).and_then(Value::as_str) == Some("markdown")
+}
+
+fn count(value: &Value) -> usize {
+    value.as_array().map_or(0, Vec::len)
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => "—".to_string(),
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn section(lines: &mut Vec<String>, heading: &str, rows: &[Value]) {
+    lines.push(format!("### {} ({})", heading, rows.len()));
+    lines.push(String::new());
+    if rows.is_empty() {
+        lines.push("None.".to_string());
+    } else {
+        lines.push(markdown_table(rows));
+    }
+    lines.push(String::new());
+}
+
+fn render_diff(report: &Value) -> String {
+    let mut lines = vec![
+        format!(
+            "## Folder diff: {} → {}",
+            cell(&report["from"]),
+            cell(&report["to"])
+        ),
+        String::new(),
+        format!(
+            "{} added, {} removed, {} changed, {} unchanged.",
+            count(&report["added"]),
+            count(&report["removed"]),
+            count(&report["changed"]),
+            cell(&report["unchanged"])
+        ),
+        String::new(),
+    ];
+    let empty = Vec::new();
+    let assets = |key: &str| report[key].as_array().unwrap_or(&empty).clone();
+    for (heading, key) in [("Added", "added"), ("Removed", "removed")] {
+        let rows: Vec<Vec<String>> = assets(key)
+            .iter()
+            .map(|asset| {
+                ["path", "name", "uuid"]
+                    .map(|field| cell(&asset[field]))
+                    .to_vec()
+            })
+            .collect();
+        lines.push(format!("### {} ({})", heading, rows.len()));
+        lines.push(String::new());
+        lines.push(if rows.is_empty() {
+            "None.".to_string()
+        } else {
+            markdown_columns(&["path", "name", "uuid"], rows)
+        });
+        lines.push(String::new());
+    }
+    // One row per changed field, so a table shows what changed how.
+    let changes: Vec<Vec<String>> = assets("changed")
+        .iter()
+        .flat_map(|asset| {
+            let path = asset
+                .get("path")
+                .filter(|path| !path.is_null())
+                .or_else(|| asset.get("uuid"))
+                .map(cell)
+                .unwrap_or_default();
+            asset["changes"]
+                .as_object()
+                .into_iter()
+                .flatten()
+                .map(move |(field, change)| {
+                    vec![
+                        path.clone(),
+                        field.clone(),
+                        cell(&change["before"]),
+                        cell(&change["after"]),
+                    ]
+                })
+        })
+        .collect();
+    lines.push(format!("### Changed ({})", count(&report["changed"])));
+    lines.push(String::new());
+    lines.push(if changes.is_empty() {
+        "None.".to_string()
+    } else {
+        markdown_columns(&["asset", "field", "before", "after"], changes)
+    });
+    lines.join("\n").trim_end().to_string()
+}
+
+fn render_summary(summary: &Value) -> String {
+    let mut lines = vec![
+        "## Match summary".to_string(),
+        String::new(),
+        format!(
+            "{} matches from {} sources; the top {} per source are listed.",
+            cell(&summary["match_count"]),
+            count(&summary["sources"]),
+            cell(&summary["top_n"])
+        ),
+        String::new(),
+    ];
+    if let Some(total) = summary.get("total") {
+        lines.push(format!(
+            "{} inputs: {} succeeded, {} failed.",
+            cell(total),
+            cell(&summary["succeeded"]),
+            cell(&summary["failed"])
+        ));
+        lines.push(String::new());
+    }
+    let scores = &summary["scores"];
+    lines.push("### Scores".to_string());
+    lines.push(String::new());
+    if scores.is_object() {
+        let stats = ["min", "max", "mean", "median"];
+        lines.push(markdown_columns(
+            &stats,
+            vec![stats.iter().map(|stat| cell(&scores[*stat])).collect()],
+        ));
+        lines.push(String::new());
+        let above = summary["above"]
+            .as_object()
+            .into_iter()
+            .flatten()
+            .map(|(level, count)| vec![format!("≥ {}", level), cell(count)])
+            .collect();
+        lines.push(markdown_columns(&["score", "matches"], above));
+    } else {
+        lines.push("No match has a score.".to_string());
+    }
+    lines.push(String::new());
+    let sources: Vec<Vec<String>> = summary["sources"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|source| {
+            vec![
+                cell(&source["source"]),
+                cell(&source["matches"]),
+                cell(&source["best_score"]),
+            ]
+        })
+        .collect();
+    lines.push(format!("### Sources ({})", sources.len()));
+    lines.push(String::new());
+    lines.push(markdown_columns(
+        &["source", "matches", "best score"],
+        sources,
+    ));
+    lines.push(String::new());
+    let empty = Vec::new();
+    section(
+        &mut lines,
+        "Top matches",
+        summary["matches"].as_array().unwrap_or(&empty),
+    );
+    if let Some(uri) = summary["full_result"].as_str() {
+        lines.push(format!("Full output: {}", uri));
+    }
+    lines.join("\n").trim_end().to_string()
+}
+
+/// Replaces the JSON report of `result` with a Markdown rendering for
+/// people to read; `structuredContent`, when there is one, keeps the data.
+/// A result whose report cannot be read is returned as it was.
+pub fn render_markdown(tool: &str, mut result: Value) -> Value {
+    let Some(blocks) = result.get_mut("content").and_then(Value::as_array_mut) else {
+        return result;
+    };
+    let raw = blocks.iter().rposition(|block| {
+        block["type"] == "text"
+            && block["text"]
+                .as_str()
+                .is_some_and(|text| serde_json::from_str::<Value>(text).is_ok())
+    });
+    let report = match raw {
+        Some(index) => blocks[index]["text"]
+            .as_str()
+            .and_then(|text| serde_json::from_str::<Value>(text).ok()),
+        None => result.get("structuredContent").cloned(),
+    };
+    let Some(report) = report.filter(Value::is_object) else {
+        return result;
+    };
+    let text = if tool == DIFF_TOOL {
+        render_diff(&report)
+    } else {
+        render_summary(&report)
+    };
+    let block = json!({
+        "type": "text",
+        "text": text,
+        "annotations": annotations(&[AUDIENCE_USER, AUDIENCE_ASSISTANT], PRIORITY_SUMMARY)
+    });
+    let Some(blocks) = result.get_mut("content").and_then(Value::as_array_mut) else {
+        return result;
+    };
+    match raw {
+        Some(index) => blocks[index] = block,
+        None => blocks.push(block),
+    }
+    result["_meta"][RENDER_ARG] = json!("markdown");
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::{raw_output_block, summary_block, tool_result};
+
+    #[test]
+    fn test_diff_is_rendered_as_markdown() {
+        let report = json!({
+            "from": "monday",
+            "to": "live folder",
+            "added": [{ "uuid": "4", "path": "/R/d.stl", "name": "d.stl" }],
+            "removed": [],
+            "changed": [{
+                "uuid": "1", "path": "/R/a.stl", "name": "a.stl",
+                "changes": { "metadata.Weight": { "before": 1.0, "after": 1.5 } }
+            }],
+            "unchanged": 3
+        });
+        let result = render_markdown(
+            DIFF_TOOL,
+            tool_result(vec![
+                summary_block("headline"),
+                raw_output_block(report.to_string()),
+            ]),
+        );
+        let text = result["content"][1]["text"].as_str().unwrap();
+        assert!(text.starts_with("## Folder diff: monday → live folder"));
+        assert!(text.contains("1 added, 0 removed, 1 changed, 3 unchanged."));
+        assert!(text.contains("### Removed (0)\n\nNone."));
+        assert!(text.contains("| /R/d.stl | d.stl | 4 |"));
+        assert!(text.contains("| /R/a.stl | metadata.Weight | 1.0 | 1.5 |"));
+        assert_eq!(result["_meta"]["render"], "markdown");
+    }
+
+    #[test]
+    fn test_summary_is_rendered_from_structured_content() {
+        let summary = json!({
+            "summarized": true,
+            "match_count": 2,
+            "sources": [{ "source": "/A", "matches": 2, "best_score": 99.0, "top": [] }],
+            "scores": { "min": 90.0, "max": 99.0, "mean": 94.5, "median": 94.5 },
+            "above": { "80": 2, "90": 2, "95": 1, "99": 1 },
+            "matches": [{ "path": "/X", "score": 99.0 }],
+            "top_n": 5,
+            "full_result": "pcli2://results/1"
+        });
+        let mut result = tool_result(vec![summary_block("headline")]);
+        result["structuredContent"] = summary;
+        let result = render_markdown("pcli2_geometric_match", result);
+        let text = result["content"][1]["text"].as_str().unwrap();
+        assert!(text.contains("2 matches from 1 sources"));
+        assert!(text.contains("| 90.0 | 99.0 | 94.5 | 94.5 |"));
+        assert!(text.contains("| /A | 2 | 99.0 |"));
+        assert!(text.contains("| /X | 99.0 |"));
+        assert!(text.ends_with("Full output: pcli2://results/1"));
+
+        assert!(wants_markdown(
+            "pcli2_geometric_match",
+            &json!({ "summarize": true, "render": "markdown" })
+        ));
+        assert!(!wants_markdown(
+            "pcli2_geometric_match",
+            &json!({ "render": "markdown" })
+        ));
+        assert!(!wants_markdown(DIFF_TOOL, &json!({ "render": "json" })));
+    }
+}
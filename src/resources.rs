@@ -0,0 +1,606 @@
+use crate::content::{AUDIENCE_ASSISTANT, PRIORITY_RESOURCE_LINK, annotations, summary_block};
+#[cfg(feature = "redis")]
+use crate::shared::SharedStore;
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64_STANDARD};
+use serde_json::{Value, json};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+#[cfg(feature = "redis")]
+use tracing::warn;
+
+pub const RESULT_URI_PREFIX: &str = "pcli2://results/";
+pub const DEFAULT_INLINE_RESULT_BYTES: usize = 64 * 1024;
+pub const MAX_STORED_RESOURCES: usize = 64;
+pub const MAX_STORED_RESOURCE_BYTES: usize = 512 * 1024 * 1024;
+pub const RESOURCE_PREVIEW_LINES: usize = 20;
+pub const RESOURCE_NOT_FOUND: i64 = -32002;
+
+#[derive(Debug, Clone)]
+pub struct StoredResource {
+    pub uri: String,
+    pub name: String,
+    pub mime_type: String,
+    pub text: Arc<String>,
+    /// Tenant whose data this is; only clients bound to it (or to none) see it.
+    pub tenant: Option<String>,
+    /// `text` is base64-encoded binary content, read back as `blob`.
+    pub blob: bool,
+}
+
+impl StoredResource {
+    /// Size of the content in bytes, decoded for blobs.
+    pub fn size(&self) -> usize {
+        if !self.blob {
+            return self.text.len();
+        }
+        let padding = self.text.bytes().rev().take_while(|b| *b == b'=').count();
+        (self.text.len() / 4 * 3).saturating_sub(padding)
+    }
+
+    pub fn visible_to(&self, tenant: Option<&str>) -> bool {
+        tenant.is_none() || self.tenant.as_deref() == tenant
+    }
+}
+
+/// A stored result with when it was stored and when a client last read
+/// it or was sent a link to it.
+#[derive(Debug, Clone)]
+pub struct Artifact {
+    pub resource: StoredResource,
+    pub stored_at: SystemTime,
+    pub referenced_at: SystemTime,
+}
+
+#[derive(Default)]
+struct StoreInner {
+    order: VecDeque<String>,
+    entries: HashMap<String, Artifact>,
+    total_bytes: usize,
+    /// Resources with fixed URIs, replaced in place and never evicted.
+    pinned: HashMap<String, StoredResource>,
+}
+
+#[derive(Clone, Default)]
+pub struct ResourceStore {
+    inner: Arc<Mutex<StoreInner>>,
+    counter: Arc<AtomicU64>,
+    #[cfg(feature = "redis")]
+    shared: Option<SharedStore>,
+}
+
+impl ResourceStore {
+    #[cfg(feature = "redis")]
+    pub fn with_shared(shared: SharedStore) -> Self {
+        Self {
+            shared: Some(shared),
+            ..Self::default()
+        }
+    }
+
+    pub fn insert(
+        &self,
+        name: &str,
+        mime_type: &str,
+        text: String,
+        tenant: Option<&str>,
+    ) -> StoredResource {
+        self.insert_content(name, mime_type, text, tenant, false)
+    }
+
+    /// Stores binary content, such as a downloaded file, base64-encoded.
+    pub fn insert_blob(
+        &self,
+        name: &str,
+        mime_type: &str,
+        bytes: &[u8],
+        tenant: Option<&str>,
+    ) -> StoredResource {
+        self.insert_content(name, mime_type, BASE64_STANDARD.encode(bytes), tenant, true)
+    }
+
+    fn insert_content(
+        &self,
+        name: &str,
+        mime_type: &str,
+        text: String,
+        tenant: Option<&str>,
+        blob: bool,
+    ) -> StoredResource {
+        let seq = self.counter.fetch_add(1, Ordering::SeqCst);
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let resource = StoredResource {
+            uri: format!("{}{}-{}", RESULT_URI_PREFIX, millis, seq),
+            name: name.to_string(),
+            mime_type: mime_type.to_string(),
+            text: Arc::new(text),
+            tenant: tenant.map(str::to_string),
+            blob,
+        };
+        self.insert_resource(resource.clone());
+        resource
+    }
+
+    fn insert_resource(&self, resource: StoredResource) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.total_bytes += resource.text.len();
+        inner.order.push_back(resource.uri.clone());
+        let now = SystemTime::now();
+        inner.entries.insert(
+            resource.uri.clone(),
+            Artifact {
+                resource,
+                stored_at: now,
+                referenced_at: now,
+            },
+        );
+        while inner.order.len() > 1
+            && (inner.order.len() > MAX_STORED_RESOURCES
+                || inner.total_bytes > MAX_STORED_RESOURCE_BYTES)
+        {
+            if let Some(oldest) = inner.order.pop_front()
+                && let Some(evicted) = inner.entries.remove(&oldest)
+            {
+                inner.total_bytes -= evicted.resource.text.len();
+            }
+        }
+    }
+
+    /// Stores or replaces a resource under its own URI, in the eviction
+    /// order used for offloaded results.
+    pub fn replace(&self, resource: StoredResource) {
+        {
+            let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(previous) = inner.entries.remove(&resource.uri) {
+                inner.total_bytes -= previous.resource.text.len();
+                inner.order.retain(|uri| *uri != resource.uri);
+            }
+        }
+        self.insert_resource(resource);
+    }
+
+    /// Stores or replaces a resource under its own URI, outside the
+    /// eviction order used for offloaded results.
+    pub fn pin(&self, resource: StoredResource) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.pinned.insert(resource.uri.clone(), resource);
+    }
+
+    /// The resource at `uri`, which counts as a reference to it.
+    pub fn get(&self, uri: &str) -> Option<StoredResource> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(artifact) = inner.entries.get_mut(uri) {
+            artifact.referenced_at = SystemTime::now();
+            return Some(artifact.resource.clone());
+        }
+        inner.pinned.get(uri).cloned()
+    }
+
+    /// The evictable resources, oldest first, with their timestamps.
+    pub fn artifacts(&self) -> Vec<Artifact> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner
+            .order
+            .iter()
+            .filter_map(|uri| inner.entries.get(uri).cloned())
+            .collect()
+    }
+
+    /// Removes the evictable resources at `uris` and returns those it found.
+    /// Pinned resources stay.
+    pub fn remove(&self, uris: &[String]) -> Vec<StoredResource> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let removed: Vec<StoredResource> = uris
+            .iter()
+            .filter_map(|uri| inner.entries.remove(uri))
+            .map(|artifact| artifact.resource)
+            .collect();
+        if !removed.is_empty() {
+            inner.total_bytes -= removed.iter().map(|r| r.text.len()).sum::<usize>();
+            let StoreInner { order, entries, .. } = &mut *inner;
+            order.retain(|uri| entries.contains_key(uri));
+        }
+        removed
+    }
+
+    /// Number of resources held locally and their total size in bytes.
+    pub fn stats(&self) -> (usize, usize) {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let pinned_bytes: usize = inner.pinned.values().map(|r| r.text.len()).sum();
+        (
+            inner.entries.len() + inner.pinned.len(),
+            inner.total_bytes + pinned_bytes,
+        )
+    }
+
+    pub fn list(&self) -> Vec<StoredResource> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let mut pinned: Vec<StoredResource> = inner.pinned.values().cloned().collect();
+        pinned.sort_by(|a, b| a.uri.cmp(&b.uri));
+        pinned
+            .into_iter()
+            .chain(
+                inner
+                    .order
+                    .iter()
+                    .filter_map(|uri| inner.entries.get(uri))
+                    .map(|artifact| artifact.resource.clone()),
+            )
+            .collect()
+    }
+
+    /// Copies the resources linked from a tool result to the shared store, so
+    /// any replica can serve the follow-up `resources/read`.
+    pub async fn publish(&self, result: &Value) {
+        #[cfg(feature = "redis")]
+        if let Some(shared) = &self.shared {
+            let links = result
+                .get("content")
+                .and_then(|v| v.as_array())
+                .into_iter()
+                .flatten()
+                .filter(|block| block["type"] == "resource_link")
+                .filter_map(|block| block["uri"].as_str());
+            for uri in links {
+                if let Some(resource) = self.get(uri)
+                    && let Err(err) = shared.put_resource(&resource).await
+                {
+                    warn!("⚠ failed to share resource {}: {}", uri, err);
+                }
+            }
+        }
+        #[cfg(not(feature = "redis"))]
+        let _ = result;
+    }
+
+    /// Makes `uri` available locally, loading it from the shared store when
+    /// another replica produced it.
+    pub async fn fetch(&self, uri: &str) -> Option<StoredResource> {
+        if let Some(resource) = self.get(uri) {
+            return Some(resource);
+        }
+        #[cfg(feature = "redis")]
+        if let Some(shared) = &self.shared {
+            match shared.get_resource(uri).await {
+                Ok(Some(resource)) => {
+                    self.insert_resource(resource.clone());
+                    return Some(resource);
+                }
+                Ok(None) => {}
+                Err(err) => warn!("⚠ failed to read shared resource {}: {}", uri, err),
+            }
+        }
+        None
+    }
+
+    /// Lists local resources together with those shared by other replicas.
+    pub async fn list_all(&self) -> Vec<StoredResource> {
+        #[cfg_attr(not(feature = "redis"), allow(unused_mut))]
+        let mut resources = self.list();
+        #[cfg(feature = "redis")]
+        if let Some(shared) = &self.shared {
+            match shared.list_resources().await {
+                Ok(remote) => {
+                    for resource in remote {
+                        if !resources.iter().any(|local| local.uri == resource.uri) {
+                            resources.push(resource);
+                        }
+                    }
+                }
+                Err(err) => warn!("⚠ failed to list shared resources: {}", err),
+            }
+        }
+        resources
+    }
+}
+
+pub fn resource_descriptor(resource: &StoredResource) -> Value {
+    json!({
+        "uri": resource.uri,
+        "name": resource.name,
+        "mimeType": resource.mime_type,
+        "size": resource.size()
+    })
+}
+
+/// A `resource_link` content block pointing at `resource`.
+pub fn resource_link(resource: &StoredResource) -> Value {
+    let mut link = resource_descriptor(resource);
+    link["type"] = json!("resource_link");
+    link["annotations"] = annotations(&[AUDIENCE_ASSISTANT], PRIORITY_RESOURCE_LINK);
+    link
+}
+
+pub fn read_resource(
+    store: &ResourceStore,
+    tenant: Option<&str>,
+    uri: &str,
+    offset: Option<usize>,
+    length: Option<usize>,
+) -> Option<Value> {
+    let resource = store
+        .get(uri)
+        .filter(|resource| resource.visible_to(tenant))?;
+    Some(read_content(&resource, offset, length))
+}
+
+/// The `resources/read` result of `resource`, or of the chunk of its text
+/// from `offset` that is `length` bytes long.
+pub fn read_content(
+    resource: &StoredResource,
+    offset: Option<usize>,
+    length: Option<usize>,
+) -> Value {
+    let text = resource.text.as_str();
+    let total = text.len();
+    // Blob chunks end on whole base64 quanta so each decodes on its own.
+    let boundary = |index: usize| {
+        if !resource.blob {
+            floor_char_boundary(text, index)
+        } else if index < total {
+            index - index % 4
+        } else {
+            index
+        }
+    };
+    let start = boundary(offset.unwrap_or(0).min(total));
+    let mut end = match length {
+        Some(length) => boundary(start.saturating_add(length).min(total)),
+        None => total,
+    };
+    // A length shorter than the next character or quantum still returns it,
+    // so a client paging through the resource always moves forward.
+    if end == start && start < total {
+        end = if resource.blob {
+            (start + 4).min(total)
+        } else {
+            (start + 1..=total)
+                .find(|&index| text.is_char_boundary(index))
+                .unwrap_or(total)
+        };
+    }
+    let chunk = &text[start..end];
+    let key = if resource.blob { "blob" } else { "text" };
+    let mut result = json!({
+        "contents": [{
+            "uri": resource.uri,
+            "mimeType": resource.mime_type,
+            key: chunk
+        }]
+    });
+    if offset.is_some() || length.is_some() {
+        result["_meta"] = json!({
+            "offset": start,
+            "length": chunk.len(),
+            "total": total,
+            "nextOffset": if end < total { json!(end) } else { Value::Null }
+        });
+    }
+    result
+}
+
+pub fn offload_large_text(
+    store: &ResourceStore,
+    tenant: Option<&str>,
+    tool: &str,
+    mime_type: &str,
+    limit: usize,
+    mut result: Value,
+) -> Value {
+    let Some(blocks) = result.get_mut("content").and_then(|v| v.as_array_mut()) else {
+        return result;
+    };
+    let mut offloaded = false;
+    let mut replaced = Vec::with_capacity(blocks.len());
+    for block in blocks.drain(..) {
+        let text = match block.get("text").and_then(|v| v.as_str()) {
+            Some(text) if block["type"] == "text" && text.len() > limit => text.to_string(),
+            _ => {
+                replaced.push(block);
+                continue;
+            }
+        };
+        offloaded = true;
+        let line_count = text.lines().count();
+        let preview: Vec<String> = text
+            .lines()
+            .take(RESOURCE_PREVIEW_LINES)
+            .map(str::to_string)
+            .collect();
+        let stored = store.insert(&format!("{} result", tool), mime_type, text, tenant);
+        let summary = format!(
+            "Result is too large to return inline ({} bytes, {} lines). The full output is stored as resource {}; read it with resources/read (optionally with 'offset' and 'length' to fetch it in chunks).\n\nFirst {} lines:\n{}",
+            stored.text.len(),
+            line_count,
+            stored.uri,
+            preview.len(),
+            preview.join("\n")
+        );
+        replaced.push(summary_block(summary));
+        replaced.push(resource_link(&stored));
+    }
+    *blocks = replaced;
+    if offloaded && let Some(obj) = result.as_object_mut() {
+        obj.remove("structuredContent");
+    }
+    result
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    while index > 0 && !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_insert_and_get() {
+        let store = ResourceStore::default();
+        let stored = store.insert("test", "text/plain", "hello".to_string(), None);
+        assert!(stored.uri.starts_with(RESULT_URI_PREFIX));
+        let fetched = store.get(&stored.uri).unwrap();
+        assert_eq!(fetched.text.as_str(), "hello");
+        assert_eq!(store.list().len(), 1);
+    }
+
+    #[test]
+    fn test_pinned_resources_are_replaced_not_evicted() {
+        let store = ResourceStore::default();
+        let folder = |text: &str| StoredResource {
+            uri: "pcli2://folders/Root".to_string(),
+            name: "folder /Root".to_string(),
+            mime_type: "application/json".to_string(),
+            text: Arc::new(text.to_string()),
+            tenant: None,
+            blob: false,
+        };
+        store.pin(folder("[]"));
+        store.pin(folder("[1]"));
+        for _ in 0..=MAX_STORED_RESOURCES {
+            store.insert("next", "text/plain", "b".to_string(), None);
+        }
+        assert_eq!(
+            store.get("pcli2://folders/Root").unwrap().text.as_str(),
+            "[1]"
+        );
+        assert_eq!(store.list()[0].uri, "pcli2://folders/Root");
+        assert_eq!(
+            store.stats(),
+            (MAX_STORED_RESOURCES + 1, MAX_STORED_RESOURCES + 3)
+        );
+    }
+
+    #[test]
+    fn test_store_evicts_oldest() {
+        let store = ResourceStore::default();
+        let first = store.insert("first", "text/plain", "a".to_string(), None);
+        for _ in 0..MAX_STORED_RESOURCES {
+            store.insert("next", "text/plain", "b".to_string(), None);
+        }
+        assert!(store.get(&first.uri).is_none());
+        assert_eq!(store.list().len(), MAX_STORED_RESOURCES);
+    }
+
+    #[test]
+    fn test_reads_are_references_and_removal_skips_pinned() {
+        let store = ResourceStore::default();
+        let stored = store.insert("test", "text/plain", "hello".to_string(), None);
+        let before = store.artifacts()[0].referenced_at;
+        store.get(&stored.uri).unwrap();
+        assert!(store.artifacts()[0].referenced_at >= before);
+        store.pin(StoredResource {
+            uri: "pcli2://folders/Root".to_string(),
+            ..stored.clone()
+        });
+        let removed = store.remove(&[stored.uri.clone(), "pcli2://folders/Root".to_string()]);
+        assert_eq!(removed.len(), 1);
+        assert!(store.artifacts().is_empty());
+        assert_eq!(store.stats(), (1, 5));
+    }
+
+    #[test]
+    fn test_read_resource_chunked() {
+        let store = ResourceStore::default();
+        let stored = store.insert("test", "text/plain", "0123456789".to_string(), None);
+        let result = read_resource(&store, None, &stored.uri, Some(2), Some(5)).unwrap();
+        assert_eq!(result["contents"][0]["text"], "23456");
+        assert_eq!(result["_meta"]["nextOffset"], 7);
+        assert_eq!(result["_meta"]["total"], 10);
+
+        let tail = read_resource(&store, None, &stored.uri, Some(7), Some(100)).unwrap();
+        assert_eq!(tail["contents"][0]["text"], "789");
+        assert!(tail["_meta"]["nextOffset"].is_null());
+    }
+
+    #[test]
+    fn test_read_resource_respects_char_boundaries() {
+        let store = ResourceStore::default();
+        let stored = store.insert("test", "text/plain", "aé".to_string(), None);
+        let result = read_resource(&store, None, &stored.uri, Some(0), Some(2)).unwrap();
+        assert_eq!(result["contents"][0]["text"], "a");
+
+        // Too short for the next character: it is returned whole.
+        let result = read_resource(&store, None, &stored.uri, Some(1), Some(1)).unwrap();
+        assert_eq!(result["contents"][0]["text"], "é");
+        assert_eq!(result["_meta"]["length"], 2);
+        assert!(result["_meta"]["nextOffset"].is_null());
+        let stored = store.insert("test", "text/plain", "éé".to_string(), None);
+        let mut offset = 0;
+        let mut pages = Vec::new();
+        while let Some(result) = read_resource(&store, None, &stored.uri, Some(offset), Some(1)) {
+            pages.push(result["contents"][0]["text"].as_str().unwrap().to_string());
+            match result["_meta"]["nextOffset"].as_u64() {
+                Some(next) => offset = next as usize,
+                None => break,
+            }
+        }
+        assert_eq!(pages, ["é", "é"]);
+    }
+
+    #[test]
+    fn test_blobs_are_read_in_whole_base64_chunks() {
+        let store = ResourceStore::default();
+        let stored = store.insert_blob("part.stl", "model/stl", b"solid part", None);
+        assert_eq!(resource_descriptor(&stored)["size"], 10);
+        let whole = read_resource(&store, None, &stored.uri, None, None).unwrap();
+        assert_eq!(whole["contents"][0]["blob"], "c29saWQgcGFydA==");
+        assert!(whole["contents"][0].get("text").is_none());
+
+        let chunk = read_resource(&store, None, &stored.uri, Some(0), Some(6)).unwrap();
+        assert_eq!(chunk["contents"][0]["blob"], "c29s");
+        assert_eq!(chunk["_meta"]["nextOffset"], 4);
+
+        // Shorter than a quantum: one quantum is returned.
+        let chunk = read_resource(&store, None, &stored.uri, Some(4), Some(1)).unwrap();
+        assert_eq!(chunk["contents"][0]["blob"], "aWQg");
+        assert_eq!(chunk["_meta"]["nextOffset"], 8);
+    }
+
+    #[test]
+    fn test_offload_large_text() {
+        let store = ResourceStore::default();
+        let big = "row\n".repeat(100);
+        let result = json!({ "content": [{ "type": "text", "text": big }] });
+        let result = offload_large_text(&store, None, "pcli2", "text/csv", 64, result);
+        let blocks = result["content"].as_array().unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert!(blocks[0]["text"].as_str().unwrap().contains("too large"));
+        assert_eq!(blocks[1]["type"], "resource_link");
+        assert_eq!(blocks[1]["mimeType"], "text/csv");
+        let uri = blocks[1]["uri"].as_str().unwrap();
+        assert_eq!(store.get(uri).unwrap().text.len(), 400);
+    }
+
+    #[test]
+    fn test_offload_drops_structured_content() {
+        let store = ResourceStore::default();
+        let big = "x".repeat(100);
+        let result = json!({
+            "content": [{ "type": "text", "text": big }],
+            "structuredContent": { "items": [] }
+        });
+        let result = offload_large_text(&store, None, "pcli2", "application/json", 64, result);
+        assert!(result.get("structuredContent").is_none());
+    }
+
+    #[test]
+    fn test_offload_keeps_small_text_inline() {
+        let store = ResourceStore::default();
+        let result = json!({ "content": [{ "type": "text", "text": "small" }] });
+        let result = offload_large_text(&store, None, "pcli2", "text/plain", 64, result.clone());
+        assert_eq!(result["content"][0]["text"], "small");
+        assert!(store.list().is_empty());
+    }
+}
@@ -0,0 +1,472 @@
+use crate::{
+    config::{DockerConfig, ServerConfig, SshConfig},
+    pcli::{MAX_PCLI2_OUTPUT_BYTES, pcli2_executable, shell_escape_arg},
+    sandbox::Sandbox,
+    spool::{Transcript, spool_output},
+};
+use std::{future::Future, path::Path, pin::Pin, process::Stdio, sync::Arc, time::Duration};
+use tokio::process::Command;
+use tracing::info;
+
+/// One `pcli2` run for a [`CommandRunner`].
+pub struct CommandRequest<'a> {
+    /// Arguments after the `pcli2` executable.
+    pub args: &'a [String],
+    /// Name used in error messages, e.g. `pcli2 asset get`.
+    pub label: &'a str,
+    pub timeout: Duration,
+    /// Local process limits; backends that run elsewhere may ignore them.
+    pub sandbox: Option<&'a Sandbox>,
+    /// `pcli2` executable picked through `cli_versions`, in place of the
+    /// backend's default.
+    pub pcli2: Option<&'a str>,
+    /// The call's own working directory; backends that run elsewhere
+    /// ignore it.
+    pub cwd: Option<&'a Path>,
+}
+
+/// A finished `pcli2` run, successful or not.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandOutput {
+    /// `None` when the process was ended by a signal.
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    /// stdout and stderr as shown in error messages.
+    pub transcript: String,
+    /// CPU time the process and its children used, where the backend can
+    /// tell.
+    pub cpu: Option<Duration>,
+}
+
+impl CommandOutput {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+
+    /// A run with stdout and stderr shown one after the other on failure.
+    pub fn new(exit_code: Option<i32>, stdout: String, stderr: String) -> Self {
+        let transcript = format!("{}\n{}", stdout.trim_end(), stderr.trim_end());
+        Self {
+            exit_code,
+            stdout,
+            stderr,
+            transcript,
+            cpu: None,
+        }
+    }
+
+    /// The trimmed stdout, or an error naming the exit status.
+    pub fn into_result(self, label: &str) -> Result<String, String> {
+        if !self.success() {
+            let status = match self.exit_code {
+                Some(code) => format!("exit status: {}", code),
+                None => "terminated by signal".to_string(),
+            };
+            return Err(format!(
+                "{} failed (code {}):\n{}",
+                label, status, self.transcript
+            ));
+        }
+        let mut stdout = self.stdout;
+        stdout.truncate(stdout.trim_end().len());
+        Ok(stdout)
+    }
+}
+
+pub type RunFuture<'a> = Pin<Box<dyn Future<Output = Result<CommandOutput, String>> + Send + 'a>>;
+
+/// Runs `pcli2` for the tools. Retries, re-authentication, redaction, the
+/// circuit breaker, and cassettes are handled around it, so a backend only
+/// has to start the command and collect its output.
+pub trait CommandRunner: Send + Sync {
+    /// Returns `Err` only when the run could not start or finish (for
+    /// example a timeout); a non-zero exit is an `Ok` output.
+    fn run<'a>(&'a self, request: CommandRequest<'a>) -> RunFuture<'a>;
+}
+
+/// Runs `pcli2` as a local subprocess (`PCLI2_BIN`, the managed copy, or
+/// `pcli2` from `PATH`).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalRunner;
+
+impl CommandRunner for LocalRunner {
+    fn run<'a>(&'a self, request: CommandRequest<'a>) -> RunFuture<'a> {
+        let mut command = Command::new(request.pcli2.map_or_else(pcli2_executable, str::to_string));
+        // A configured `sandbox.working_dir` stays the working directory.
+        if let Some(dir) = request.cwd {
+            command.current_dir(dir);
+        }
+        if let Some(sandbox) = request.sandbox {
+            sandbox.apply(&mut command);
+        }
+        command.args(request.args);
+        Box::pin(run_process(command, request))
+    }
+}
+
+/// Runs `pcli2` on another host with the local `ssh` client (`ssh` config).
+/// Output streams back over the connection; the sandbox does not apply.
+#[derive(Debug, Clone)]
+pub struct SshRunner {
+    config: SshConfig,
+    host: String,
+}
+
+impl SshRunner {
+    /// Returns `None` when `ssh.host` is not set.
+    pub fn new(config: &SshConfig) -> Option<Self> {
+        Some(Self {
+            host: config.host.clone()?,
+            config: config.clone(),
+        })
+    }
+
+    /// `user@host`, or the host alone.
+    pub fn destination(&self) -> String {
+        match &self.config.user {
+            Some(user) => format!("{}@{}", user, self.host),
+            None => self.host.clone(),
+        }
+    }
+
+    /// Arguments for the `ssh` client. The remote shell receives `pcli2`
+    /// and its arguments as one quoted command line.
+    pub fn ssh_args(&self, pcli2: Option<&str>, args: &[String]) -> Vec<String> {
+        let mut ssh_args = vec!["-o".to_string(), "BatchMode=yes".to_string()];
+        if let Some(port) = self.config.port {
+            ssh_args.extend(["-p".to_string(), port.to_string()]);
+        }
+        if let Some(identity) = &self.config.identity_file {
+            ssh_args.extend(["-i".to_string(), identity.to_string_lossy().into_owned()]);
+        }
+        ssh_args.extend(self.config.options.iter().cloned());
+        ssh_args.push("--".to_string());
+        ssh_args.push(self.destination());
+        ssh_args.push(
+            std::iter::once(pcli2.unwrap_or(&self.config.pcli2).to_string())
+                .chain(args.iter().map(|arg| shell_escape_arg(arg)))
+                .collect::<Vec<_>>()
+                .join(" "),
+        );
+        ssh_args
+    }
+}
+
+impl CommandRunner for SshRunner {
+    fn run<'a>(&'a self, request: CommandRequest<'a>) -> RunFuture<'a> {
+        let mut command = Command::new(&self.config.program);
+        command.args(self.ssh_args(request.pcli2, request.args));
+        Box::pin(run_process(command, request))
+    }
+}
+
+/// Runs `pcli2` inside a running container with `docker exec` (`docker`
+/// config). Paths under a configured mount are translated, so files such as
+/// thumbnails land where the server can read them.
+#[derive(Debug, Clone)]
+pub struct DockerRunner {
+    config: DockerConfig,
+    container: String,
+}
+
+impl DockerRunner {
+    /// Returns `None` when `docker.container` is not set.
+    pub fn new(config: &DockerConfig) -> Option<Self> {
+        Some(Self {
+            container: config.container.clone()?,
+            config: config.clone(),
+        })
+    }
+
+    pub fn container(&self) -> &str {
+        &self.container
+    }
+
+    /// Arguments for the `docker` client.
+    pub fn docker_args(&self, pcli2: Option<&str>, args: &[String]) -> Vec<String> {
+        let mut docker_args = vec!["exec".to_string()];
+        if let Some(user) = &self.config.user {
+            docker_args.extend(["--user".to_string(), user.clone()]);
+        }
+        if let Some(dir) = &self.config.working_dir {
+            docker_args.extend(["--workdir".to_string(), dir.clone()]);
+        }
+        docker_args.push(self.container.clone());
+        docker_args.push(pcli2.unwrap_or(&self.config.pcli2).to_string());
+        docker_args.extend(args.iter().map(|arg| self.container_path(arg)));
+        docker_args
+    }
+
+    /// `arg` with a leading mounted host path replaced by its container path.
+    fn container_path(&self, arg: &str) -> String {
+        for mount in &self.config.mounts {
+            let host = mount.host.to_string_lossy();
+            let host = host.trim_end_matches('/');
+            let Some(rest) = arg.strip_prefix(host) else {
+                continue;
+            };
+            if rest.is_empty() || rest.starts_with('/') {
+                return format!("{}{}", mount.container.trim_end_matches('/'), rest);
+            }
+        }
+        arg.to_string()
+    }
+}
+
+impl CommandRunner for DockerRunner {
+    fn run<'a>(&'a self, request: CommandRequest<'a>) -> RunFuture<'a> {
+        let mut command = Command::new(&self.config.program);
+        command.args(self.docker_args(request.pcli2, request.args));
+        Box::pin(run_process(command, request))
+    }
+}
+
+/// The runner selected by the `ssh` or `docker` config, or [`LocalRunner`].
+pub fn configured_runner(config: &ServerConfig) -> Arc<dyn CommandRunner> {
+    if let Some(runner) = SshRunner::new(&config.ssh) {
+        info!("running pcli2 on {} over ssh", runner.destination());
+        return Arc::new(runner);
+    }
+    if let Some(runner) = DockerRunner::new(&config.docker) {
+        info!("running pcli2 in container {}", runner.container());
+        return Arc::new(runner);
+    }
+    Arc::new(LocalRunner)
+}
+
+async fn run_process(
+    mut command: Command,
+    request: CommandRequest<'_>,
+) -> Result<CommandOutput, String> {
+    // Cancelled calls drop this future; take pcli2 down with it.
+    let mut child = command
+        .kill_on_drop(true)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to execute pcli2: {}", e))?;
+
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| "Failed to capture pcli2 stdout".to_string())?;
+    let stderr = child
+        .stderr
+        .take()
+        .ok_or_else(|| "Failed to capture pcli2 stderr".to_string())?;
+
+    // Output is spooled to temporary files past a small window, so a huge
+    // listing does not sit in memory while pcli2 is still writing it. Both
+    // streams also feed one transcript so errors show them interleaved.
+    let transcript = Arc::new(Transcript::new());
+    let stdout_task = tokio::spawn(spool_output(
+        stdout,
+        MAX_PCLI2_OUTPUT_BYTES,
+        "stdout",
+        transcript.clone(),
+    ));
+    let stderr_task = tokio::spawn(spool_output(
+        stderr,
+        MAX_PCLI2_OUTPUT_BYTES,
+        "stderr",
+        transcript.clone(),
+    ));
+
+    let output = tokio::time::timeout(request.timeout, async {
+        let cpu = exited_cpu(child.id()).await;
+        let status = child
+            .wait()
+            .await
+            .map_err(|err| format!("Failed waiting for pcli2: {}", err))?;
+        let stdout = stdout_task
+            .await
+            .map_err(|err| format!("Failed to read pcli2 stdout: {}", err))??;
+        let stderr = stderr_task
+            .await
+            .map_err(|err| format!("Failed to read pcli2 stderr: {}", err))??;
+        Ok((status, stdout, stderr, cpu))
+    })
+    .await;
+
+    let (status, stdout, stderr, cpu) = match output {
+        Ok(Ok(output)) => output,
+        Ok(Err(message)) => {
+            let _ = child.kill().await;
+            return Err(message);
+        }
+        Err(_) => {
+            let _ = child.kill().await;
+            return Err(format!(
+                "{} failed: timed out after {:?}\n{}",
+                request.label,
+                request.timeout,
+                transcript.render()
+            )
+            .trim_end()
+            .to_string());
+        }
+    };
+
+    Ok(CommandOutput {
+        exit_code: status.code(),
+        stdout: stdout.into_string().await?,
+        stderr: stderr.into_string().await?,
+        transcript: transcript.render(),
+        cpu,
+    })
+}
+
+/// Waits for the child to exit and reads the CPU time it and its reaped
+/// children used. The child is left for `Child::wait` to reap, since its
+/// `/proc` entry goes with it.
+#[cfg(target_os = "linux")]
+async fn exited_cpu(pid: Option<u32>) -> Option<Duration> {
+    let pid = pid?;
+    tokio::task::spawn_blocking(move || {
+        // SAFETY: `info` is a plain C struct that waitid fills in.
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let waited = unsafe {
+            libc::waitid(
+                libc::P_PID,
+                pid as libc::id_t,
+                &mut info,
+                libc::WEXITED | libc::WNOWAIT,
+            )
+        };
+        if waited != 0 {
+            return None;
+        }
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+        // utime, stime, cutime, and cstime, counted from the field after
+        // the parenthesised command name.
+        let (_, fields) = stat.rsplit_once(") ")?;
+        let ticks = fields
+            .split_whitespace()
+            .skip(11)
+            .take(4)
+            .map(|field| field.parse::<u64>().ok())
+            .sum::<Option<u64>>()?;
+        let per_second = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+        (per_second > 0).then(|| Duration::from_secs_f64(ticks as f64 / per_second as f64))
+    })
+    .await
+    .ok()
+    .flatten()
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn exited_cpu(_pid: Option<u32>) -> Option<Duration> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_docker_args_translate_mounted_paths() {
+        assert!(DockerRunner::new(&DockerConfig::default()).is_none());
+        let runner = DockerRunner::new(&DockerConfig {
+            container: Some("pcli2-runner".to_string()),
+            user: Some("pcli2".to_string()),
+            working_dir: Some("/work".to_string()),
+            mounts: vec![crate::config::PathMapping {
+                host: "/tmp/".into(),
+                container: "/host-tmp".to_string(),
+            }],
+            ..DockerConfig::default()
+        })
+        .unwrap();
+        let args = runner.docker_args(
+            None,
+            &[
+                "asset".to_string(),
+                "thumbnail".to_string(),
+                "--file".to_string(),
+                "/tmp/pcli2-thumbnail-1.png".to_string(),
+                "/tmpfoo".to_string(),
+            ],
+        );
+        assert_eq!(
+            args,
+            [
+                "exec",
+                "--user",
+                "pcli2",
+                "--workdir",
+                "/work",
+                "pcli2-runner",
+                "pcli2",
+                "asset",
+                "thumbnail",
+                "--file",
+                "/host-tmp/pcli2-thumbnail-1.png",
+                "/tmpfoo",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ssh_args() {
+        assert!(SshRunner::new(&SshConfig::default()).is_none());
+        let runner = SshRunner::new(&SshConfig {
+            host: Some("bastion.internal".to_string()),
+            user: Some("svc".to_string()),
+            port: Some(2222),
+            identity_file: Some("/keys/pcli2".into()),
+            pcli2: "/opt/pcli2/bin/pcli2".to_string(),
+            options: vec!["-o".to_string(), "ConnectTimeout=10".to_string()],
+            ..SshConfig::default()
+        })
+        .unwrap();
+        let args = runner.ssh_args(
+            None,
+            &[
+                "asset".to_string(),
+                "get".to_string(),
+                "--path".to_string(),
+                "/Root/It's here.stl".to_string(),
+            ],
+        );
+        assert_eq!(
+            args,
+            [
+                "-o",
+                "BatchMode=yes",
+                "-p",
+                "2222",
+                "-i",
+                "/keys/pcli2",
+                "-o",
+                "ConnectTimeout=10",
+                "--",
+                "svc@bastion.internal",
+                "/opt/pcli2/bin/pcli2 asset get --path '/Root/It'\"'\"'s here.stl'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_output_into_result() {
+        let ok = CommandOutput::new(Some(0), "listing\n\n".to_string(), String::new());
+        assert_eq!(
+            ok.into_result("pcli2 folder list"),
+            Ok("listing".to_string())
+        );
+
+        let failed = CommandOutput::new(Some(2), "partial".to_string(), "boom\n".to_string());
+        assert_eq!(
+            failed.into_result("pcli2 folder list"),
+            Err("pcli2 folder list failed (code exit status: 2):\npartial\nboom".to_string())
+        );
+
+        let killed = CommandOutput::new(None, String::new(), String::new());
+        assert!(
+            killed
+                .into_result("pcli2 version")
+                .unwrap_err()
+                .contains("terminated by signal")
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use crate::config::SandboxConfig;
+use tokio::process::Command;
+
+/// Restrictions applied to one `pcli2` child process.
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    settings: SandboxConfig,
+    offline: bool,
+}
+
+impl Sandbox {
+    /// Returns the sandbox for `tool`, or `None` when sandboxing is disabled.
+    pub fn for_tool(settings: &SandboxConfig, tool: &str) -> Option<Self> {
+        settings.enabled.then(|| Self {
+            settings: settings.clone(),
+            offline: settings.offline_tools.iter().any(|name| name == tool),
+        })
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    /// The same restrictions with network access, for re-authentication.
+    pub fn online(&self) -> Self {
+        Self {
+            offline: false,
+            ..self.clone()
+        }
+    }
+
+    pub fn apply(&self, command: &mut Command) {
+        if let Some(dir) = &self.settings.working_dir {
+            command.current_dir(dir);
+        }
+        #[cfg(unix)]
+        self.apply_unix(command);
+    }
+
+    #[cfg(unix)]
+    fn apply_unix(&self, command: &mut Command) {
+        if let Some(gid) = self.settings.gid {
+            command.gid(gid);
+        }
+        if let Some(uid) = self.settings.uid {
+            command.uid(uid);
+        }
+        let limits = [
+            (libc::RLIMIT_CPU, self.settings.cpu_time_secs),
+            (libc::RLIMIT_AS, self.settings.memory_bytes),
+            (libc::RLIMIT_FSIZE, self.settings.file_size_bytes),
+        ];
+        let offline = self.offline;
+        // SAFETY: the closure runs in the forked child before exec and only
+        // makes async-signal-safe system calls (setrlimit, unshare).
+        unsafe {
+            command.pre_exec(move || {
+                for (resource, limit) in limits {
+                    if let Some(limit) = limit {
+                        set_limit(resource, limit)?;
+                    }
+                }
+                if offline {
+                    isolate_network()?;
+                }
+                Ok(())
+            });
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(all(unix, not(all(target_os = "linux", target_env = "gnu"))))]
+type Resource = libc::c_int;
+
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)]
+fn set_limit(resource: Resource, limit: u64) -> std::io::Result<()> {
+    let value = libc::rlimit {
+        rlim_cur: limit as libc::rlim_t,
+        rlim_max: limit as libc::rlim_t,
+    };
+    // SAFETY: `value` is a valid rlimit that outlives the call.
+    if unsafe { libc::setrlimit(resource, &value) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+/// Moves the child into an empty network namespace. Unprivileged processes
+/// need a user namespace for that, so it is tried second.
+#[cfg(target_os = "linux")]
+fn isolate_network() -> std::io::Result<()> {
+    // SAFETY: unshare only affects the calling (child) process.
+    if unsafe { libc::unshare(libc::CLONE_NEWNET) } == 0 {
+        return Ok(());
+    }
+    if unsafe { libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET) } == 0 {
+        return Ok(());
+    }
+    Err(std::io::Error::last_os_error())
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn isolate_network() -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "network isolation requires Linux",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sandbox_only_when_enabled() {
+        let mut settings = SandboxConfig {
+            offline_tools: vec!["pcli2_version".to_string()],
+            ..Default::default()
+        };
+        assert!(Sandbox::for_tool(&settings, "pcli2_version").is_none());
+        settings.enabled = true;
+        let version = Sandbox::for_tool(&settings, "pcli2_version").unwrap();
+        assert!(version.is_offline());
+        let asset = Sandbox::for_tool(&settings, "pcli2_asset_get").unwrap();
+        assert!(!asset.is_offline());
+    }
+}
@@ -0,0 +1,219 @@
+use crate::{
+    AppState,
+    content::{raw_output_block, summary_block, tool_result},
+    pcli::{
+        DEFAULT_FAN_OUT_CONCURRENCY, MAX_FAN_OUT_INPUTS, in_current_execution, parse_string_list,
+        run_pcli2_asset_geometric_match, run_pcli2_asset_text_match, validate_range_f64,
+        validate_range_u64,
+    },
+};
+use serde_json::{Map, Value, json};
+use std::{collections::HashSet, sync::Arc};
+use tokio::{sync::Semaphore, task::JoinSet};
+
+pub const MULTI_TENANT_SEARCH_TOOL: &str = "pcli2_multi_tenant_search";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Search {
+    Text,
+    Geometric,
+}
+
+impl Search {
+    fn name(self) -> &'static str {
+        match self {
+            Search::Text => "text",
+            Search::Geometric => "geometric",
+        }
+    }
+}
+
+/// The tenants named by the call, or the configured list.
+fn tenants(state: &AppState, args: &Value) -> Result<Vec<String>, String> {
+    let requested = parse_string_list(args, "tenants");
+    let tenants = if requested.is_empty() {
+        state.config.multi_tenant_search.tenants.clone()
+    } else {
+        requested
+    };
+    if tenants.is_empty() {
+        return Err(
+            "No tenants to search: pass 'tenants' or set 'multi_tenant_search.tenants'".to_string(),
+        );
+    }
+    let mut seen = HashSet::new();
+    Ok(tenants
+        .into_iter()
+        .filter(|tenant| seen.insert(tenant.clone()))
+        .collect())
+}
+
+/// The pcli2 arguments of one search in one tenant, always as JSON.
+fn search_args(args: &Value, search: Search, tenant: &str) -> Value {
+    let keys: &[&str] = match search {
+        Search::Text => &["text", "fuzzy", "metadata"],
+        Search::Geometric => &["uuid", "path", "threshold", "metadata"],
+    };
+    let mut single: Map<String, Value> = keys
+        .iter()
+        .filter_map(|key| Some((key.to_string(), args.get(*key)?.clone())))
+        .collect();
+    single.insert("tenant".to_string(), json!(tenant));
+    single.insert("format".to_string(), json!("json"));
+    Value::Object(single)
+}
+
+/// Rows of one search's output, tagged with where they came from.
+fn tag_rows(output: &str, tenant: &str, search: Search) -> Result<Vec<Value>, String> {
+    let rows = match serde_json::from_str::<Value>(output) {
+        Ok(Value::Array(rows)) => rows,
+        Ok(row @ Value::Object(_)) => vec![row],
+        _ => return Err("pcli2 did not return a JSON list of matches".to_string()),
+    };
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let mut tagged = match row {
+                Value::Object(fields) => fields,
+                other => Map::from_iter([("value".to_string(), other)]),
+            };
+            tagged.insert("tenant".to_string(), json!(tenant));
+            tagged.insert("search".to_string(), json!(search.name()));
+            Value::Object(tagged)
+        })
+        .collect())
+}
+
+pub async fn run_multi_tenant_search(state: &AppState, args: Value) -> Result<Value, String> {
+    validate_range_f64(&args, "threshold", 0.0, 100.0)?;
+    validate_range_u64(&args, "concurrent", 1, 10)?;
+    let mut searches = Vec::new();
+    if args.get("text").and_then(Value::as_str).is_some() {
+        searches.push(Search::Text);
+    }
+    if args.get("uuid").is_some() || args.get("path").is_some() {
+        searches.push(Search::Geometric);
+    }
+    if searches.is_empty() {
+        return Err(
+            "Missing required argument: provide 'text', or 'uuid' or 'path' for a geometric match"
+                .to_string(),
+        );
+    }
+    let tenants = tenants(state, &args)?;
+    let jobs: Vec<(String, Search)> = tenants
+        .iter()
+        .flat_map(|tenant| searches.iter().map(|search| (tenant.clone(), *search)))
+        .collect();
+    if jobs.len() > MAX_FAN_OUT_INPUTS {
+        return Err(format!(
+            "Too many searches: {} tenants x {} searches, at most {} are allowed per call",
+            tenants.len(),
+            searches.len(),
+            MAX_FAN_OUT_INPUTS
+        ));
+    }
+    let concurrency = args
+        .get("concurrent")
+        .and_then(Value::as_u64)
+        .unwrap_or(DEFAULT_FAN_OUT_CONCURRENCY as u64) as usize;
+
+    let semaphore = Arc::new(Semaphore::new(concurrency));
+    let mut tasks = JoinSet::new();
+    for (index, (tenant, search)) in jobs.iter().enumerate() {
+        let single = search_args(&args, *search, tenant);
+        let search = *search;
+        let semaphore = semaphore.clone();
+        tasks.spawn(in_current_execution(async move {
+            let _permit = semaphore.acquire_owned().await;
+            let outcome = match search {
+                Search::Text => run_pcli2_asset_text_match(single).await,
+                Search::Geometric => run_pcli2_asset_geometric_match(single).await,
+            };
+            (index, outcome)
+        }));
+    }
+
+    let mut outcomes: Vec<Option<Result<String, String>>> = vec![None; jobs.len()];
+    while let Some(joined) = tasks.join_next().await {
+        let (index, outcome) = joined.map_err(|err| format!("Search task failed: {}", err))?;
+        outcomes[index] = Some(outcome);
+    }
+
+    let mut matches = Vec::new();
+    let mut results = Vec::new();
+    for ((tenant, search), outcome) in jobs.into_iter().zip(outcomes) {
+        let outcome = outcome
+            .unwrap_or_else(|| Err("No result produced".to_string()))
+            .and_then(|output| tag_rows(&output, &tenant, search));
+        results.push(match outcome {
+            Ok(rows) => {
+                let count = rows.len();
+                matches.extend(rows);
+                json!({ "tenant": tenant, "search": search.name(), "status": "ok", "matches": count })
+            }
+            Err(message) => json!({
+                "tenant": tenant,
+                "search": search.name(),
+                "status": "error",
+                "error": message
+            }),
+        });
+    }
+    let succeeded = results.iter().filter(|r| r["status"] == "ok").count();
+    let headline = format!(
+        "Found {} matches across {} tenants ({} of {} searches succeeded).",
+        matches.len(),
+        tenants.len(),
+        succeeded,
+        results.len()
+    );
+    let merged = json!({
+        "matches": matches,
+        "total": results.len(),
+        "succeeded": succeeded,
+        "failed": results.len() - succeeded,
+        "results": results
+    });
+    let text = serde_json::to_string_pretty(&merged)
+        .map_err(|err| format!("Failed to render results: {}", err))?;
+    Ok(tool_result(vec![
+        summary_block(headline),
+        raw_output_block(text),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_args_keep_only_the_search_options() {
+        let args = json!({
+            "text": "bracket",
+            "fuzzy": true,
+            "path": "/Root/Bracket.stl",
+            "threshold": 90.0,
+            "tenants": ["east"],
+            "concurrent": 2
+        });
+        assert_eq!(
+            search_args(&args, Search::Text, "east"),
+            json!({ "text": "bracket", "fuzzy": true, "tenant": "east", "format": "json" })
+        );
+        assert_eq!(
+            search_args(&args, Search::Geometric, "west"),
+            json!({ "path": "/Root/Bracket.stl", "threshold": 90.0, "tenant": "west", "format": "json" })
+        );
+    }
+
+    #[test]
+    fn test_rows_are_tagged_with_tenant_and_search() {
+        let rows = tag_rows(r#"[{"name": "Bracket.stl"}]"#, "east", Search::Text).unwrap();
+        assert_eq!(
+            rows,
+            vec![json!({ "tenant": "east", "search": "text", "name": "Bracket.stl" })]
+        );
+        assert!(tag_rows("name,path", "east", Search::Text).is_err());
+    }
+}
@@ -0,0 +1,138 @@
+use crate::cli::{ARG_NAME, CMD_SECRETS_CHECK, CMD_SECRETS_DELETE, CMD_SECRETS_SET};
+use anyhow::{Result, anyhow};
+use clap::ArgMatches;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+/// Service name of every entry the server keeps in the OS keyring.
+pub const KEYRING_SERVICE: &str = "pcli2-mcp";
+/// `${keyring:NAME}` in configuration values reads the secret `NAME`.
+pub const KEYRING_REFERENCE_PREFIX: &str = "keyring:";
+
+#[cfg(feature = "keyring")]
+fn entry(name: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, name)
+        .map_err(|err| format!("Keyring entry '{}' is invalid: {}", name, err))
+}
+
+#[cfg(not(feature = "keyring"))]
+fn unsupported() -> String {
+    "OS keyring support requires pcli2-mcp built with the 'keyring' feature".to_string()
+}
+
+/// Reads the secret `name` from the OS keyring.
+pub fn keyring_secret(name: &str) -> Result<String, String> {
+    #[cfg(feature = "keyring")]
+    {
+        entry(name)?.get_password().map_err(|err| match err {
+            keyring::Error::NoEntry => format!(
+                "Secret '{}' is not in the OS keyring; store it with `pcli2-mcp secrets set {}`",
+                name, name
+            ),
+            err => format!(
+                "Failed to read secret '{}' from the OS keyring: {}",
+                name, err
+            ),
+        })
+    }
+    #[cfg(not(feature = "keyring"))]
+    {
+        let _ = name;
+        Err(unsupported())
+    }
+}
+
+fn set_secret(name: &str, value: &str) -> Result<(), String> {
+    #[cfg(feature = "keyring")]
+    {
+        entry(name)?
+            .set_password(value)
+            .map_err(|err| format!("Failed to store secret '{}': {}", name, err))
+    }
+    #[cfg(not(feature = "keyring"))]
+    {
+        let _ = (name, value);
+        Err(unsupported())
+    }
+}
+
+fn delete_secret(name: &str) -> Result<(), String> {
+    #[cfg(feature = "keyring")]
+    {
+        entry(name)?
+            .delete_credential()
+            .map_err(|err| format!("Failed to delete secret '{}': {}", name, err))
+    }
+    #[cfg(not(feature = "keyring"))]
+    {
+        let _ = name;
+        Err(unsupported())
+    }
+}
+
+/// Reads one line from stdin, so values never appear in shell history or
+/// the process list.
+fn read_secret_value(name: &str) -> Result<String> {
+    let stdin = io::stdin();
+    if stdin.is_terminal() {
+        eprint!("Value for '{}': ", name);
+        io::stderr().flush()?;
+    }
+    let mut value = String::new();
+    stdin.lock().read_line(&mut value)?;
+    let value = value.trim_end_matches(['\r', '\n']).to_string();
+    if value.is_empty() {
+        return Err(anyhow!("Secret value must not be empty"));
+    }
+    Ok(value)
+}
+
+pub fn run_secrets(matches: &ArgMatches) -> Result<()> {
+    let (command, sub_matches) = matches
+        .subcommand()
+        .ok_or_else(|| anyhow!("missing secrets command"))?;
+    let name = sub_matches
+        .get_one::<String>(ARG_NAME)
+        .ok_or_else(|| anyhow!("missing secret name"))?;
+    match command {
+        CMD_SECRETS_SET => {
+            let value = read_secret_value(name)?;
+            set_secret(name, &value).map_err(|e| anyhow!(e))?;
+            eprintln!(
+                "Stored secret '{}'; reference it as ${{keyring:{}}}",
+                name, name
+            );
+        }
+        CMD_SECRETS_DELETE => {
+            delete_secret(name).map_err(|e| anyhow!(e))?;
+            eprintln!("Deleted secret '{}'", name);
+        }
+        CMD_SECRETS_CHECK => {
+            keyring_secret(name).map_err(|e| anyhow!(e))?;
+            println!("Secret '{}' is set", name);
+        }
+        other => return Err(anyhow!("Unknown secrets command '{}'", other)),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "keyring")]
+    #[test]
+    fn test_keyring_reports_missing_secret() {
+        keyring::set_default_credential_builder(keyring::mock::default_credential_builder());
+        // The mock store keeps values per entry object, so only errors are
+        // observable across calls.
+        assert!(keyring_secret("admin-token").is_err());
+        assert!(set_secret("admin-token", "t0ken").is_ok());
+    }
+
+    #[cfg(not(feature = "keyring"))]
+    #[test]
+    fn test_keyring_requires_feature() {
+        let err = keyring_secret("admin-token").unwrap_err();
+        assert!(err.contains("'keyring' feature"));
+    }
+}
@@ -0,0 +1,184 @@
+use crate::{
+    cli::{ARG_HEADER, ARG_OFFLINE, ARG_URL},
+    config::ToolsConfig,
+    proxy::{Endpoint, Proxy, parse_headers},
+    server::{build_state, load_config, router},
+};
+use anyhow::{Result, anyhow};
+use clap::ArgMatches;
+use serde_json::{Value, json};
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+
+pub const SELFTEST_PROTOCOL_VERSION: &str = "2025-03-26";
+
+#[derive(Debug)]
+pub struct StepOutcome {
+    pub name: String,
+    pub elapsed: Duration,
+    pub result: Result<String, String>,
+}
+
+/// Runs initialize, tools/list, and read-only tool calls against `proxy`.
+/// `offline` skips the steps that need Physna credentials.
+pub async fn run_steps(proxy: &Proxy, tools: &ToolsConfig, offline: bool) -> Vec<StepOutcome> {
+    let version_tool = tools.public_name("pcli2_version");
+    let tenant_tool = tools.public_name("pcli2_tenant_list");
+    let mut steps: Vec<(String, Value)> = vec![
+        (
+            "initialize".to_string(),
+            json!({
+                "protocolVersion": SELFTEST_PROTOCOL_VERSION,
+                "capabilities": {},
+                "clientInfo": { "name": "pcli2-mcp-selftest", "version": env!("CARGO_PKG_VERSION") }
+            }),
+        ),
+        ("tools/list".to_string(), json!({})),
+        (
+            version_tool.clone(),
+            json!({ "name": version_tool, "arguments": {} }),
+        ),
+    ];
+    if !offline {
+        steps.push((
+            tenant_tool.clone(),
+            json!({ "name": tenant_tool, "arguments": { "format": "json" } }),
+        ));
+    }
+
+    let mut outcomes = Vec::with_capacity(steps.len());
+    for (index, (name, params)) in steps.into_iter().enumerate() {
+        let method = match name.as_str() {
+            "initialize" | "tools/list" => name.as_str(),
+            _ => "tools/call",
+        };
+        let request =
+            json!({ "jsonrpc": "2.0", "id": index + 1, "method": method, "params": params });
+        let started = Instant::now();
+        let result = proxy
+            .request(&request)
+            .await
+            .and_then(|result| describe(method, &result, &version_tool));
+        outcomes.push(StepOutcome {
+            name,
+            elapsed: started.elapsed(),
+            result,
+        });
+    }
+    outcomes
+}
+
+fn describe(method: &str, result: &Value, version_tool: &str) -> Result<String, String> {
+    match method {
+        "initialize" => Ok(format!(
+            "{} {}",
+            result["serverInfo"]["name"]
+                .as_str()
+                .unwrap_or("unknown server"),
+            result["serverInfo"]["version"].as_str().unwrap_or("")
+        )),
+        "tools/list" => {
+            let tools = result["tools"].as_array().cloned().unwrap_or_default();
+            if !tools.iter().any(|tool| tool["name"] == version_tool) {
+                return Err(format!("'{}' is not advertised", version_tool));
+            }
+            Ok(format!("{} tools", tools.len()))
+        }
+        _ => {
+            let text = result["content"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|block| block["text"].as_str())
+                .next_back()
+                .unwrap_or("");
+            Ok(text.lines().next().unwrap_or("ok").trim().to_string())
+        }
+    }
+}
+
+pub fn report(outcomes: &[StepOutcome]) -> String {
+    let mut lines = Vec::with_capacity(outcomes.len() + 1);
+    for outcome in outcomes {
+        let millis = outcome.elapsed.as_millis();
+        lines.push(match &outcome.result {
+            Ok(detail) => format!("✔ {:<24} {:>6} ms  {}", outcome.name, millis, detail),
+            Err(message) => format!("✘ {:<24} {:>6} ms  {}", outcome.name, millis, message),
+        });
+    }
+    let failed = outcomes
+        .iter()
+        .filter(|outcome| outcome.result.is_err())
+        .count();
+    lines.push(format!(
+        "{} of {} steps passed",
+        outcomes.len() - failed,
+        outcomes.len()
+    ));
+    lines.join("\n")
+}
+
+pub async fn run_selftest(matches: &ArgMatches) -> Result<()> {
+    let offline = matches.get_flag(ARG_OFFLINE);
+    let config = load_config(matches)?;
+    let tools = config.tools.clone();
+    let (proxy, server) = match matches.get_one::<String>(ARG_URL) {
+        Some(url) => {
+            let raw: Vec<String> = matches
+                .get_many::<String>(ARG_HEADER)
+                .map(|values| values.cloned().collect())
+                .unwrap_or_default();
+            (
+                Proxy::new(Endpoint::parse(url)?, parse_headers(&raw)?),
+                None,
+            )
+        }
+        None => {
+            let listener = TcpListener::bind("127.0.0.1:0").await?;
+            let address = listener.local_addr()?;
+            let app = router(build_state(config)?);
+            let server = tokio::spawn(async move { axum::serve(listener, app).await });
+            let endpoint = Endpoint::parse(&format!("http://{}/mcp", address))?;
+            (Proxy::new(endpoint, Vec::new()), Some(server))
+        }
+    };
+
+    let outcomes = run_steps(&proxy, &tools, offline).await;
+    if let Some(server) = server {
+        server.abort();
+    }
+    println!("{}", report(&outcomes));
+    let failed = outcomes
+        .iter()
+        .filter(|outcome| outcome.result.is_err())
+        .count();
+    if failed > 0 {
+        return Err(anyhow!("selftest failed: {} step(s) failed", failed));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_counts_failures() {
+        let outcomes = vec![
+            StepOutcome {
+                name: "initialize".to_string(),
+                elapsed: Duration::from_millis(3),
+                result: Ok("mcp-http-server 1.0".to_string()),
+            },
+            StepOutcome {
+                name: "pcli2_version".to_string(),
+                elapsed: Duration::from_millis(40),
+                result: Err("pcli2 not found".to_string()),
+            },
+        ];
+        let report = report(&outcomes);
+        assert!(report.contains("✔ initialize"));
+        assert!(report.contains("✘ pcli2_version"));
+        assert!(report.ends_with("1 of 2 steps passed"));
+    }
+}
@@ -1,24 +1,78 @@
-use crate::AppState;
-use crate::cli::{ARG_HOST, ARG_PORT, DEFAULT_HOST};
-use crate::mcp::handle_mcp;
+use crate::access::{Route, admit};
+use crate::admin::{
+    handle_approval_decision, handle_approvals, handle_credentials, handle_feature_reset,
+    handle_feature_update, handle_features, handle_reload, handle_usage, handle_usage_month,
+};
+use crate::args::ArgMode;
+use crate::artifacts::Collector;
+use crate::auth::Reauthenticator;
+use crate::banner::{ColorMode, print_banner};
+use crate::breaker::CircuitBreaker;
+use crate::cache::ResultCache;
+use crate::cassette::Cassette;
+use crate::cli::{
+    ARG_ADMIN_HOST, ARG_ADMIN_PORT, ARG_ARGUMENT_MODE, ARG_COLD_CACHE, ARG_COLOR, ARG_COMPAT_TEXT,
+    ARG_CONFIG_FILE, ARG_HOST, ARG_MAX_IN_FLIGHT, ARG_MAX_INLINE_BYTES, ARG_PID_FILE, ARG_PORT,
+    ARG_RECORD, ARG_RECORD_CONFORMANCE, ARG_REPLAY, DEFAULT_HOST,
+};
+use crate::compat::{CatalogCheck, UnavailableTool, unavailable_tools};
+use crate::config::{ListenerConfig, ListenerTransport, ServerConfig};
+use crate::conformance::ConformanceRecorder;
+use crate::daemon::{PidFile, until_terminated};
+use crate::health::{HealthState, HealthView, backend_health};
+use crate::index::Indexer;
+use crate::inflight::{DEFAULT_MAX_IN_FLIGHT, RequestLimiter};
+use crate::mcp::{handle_mcp, handle_mcp_stream, server_info};
+use crate::openapi::{OPENAPI_PATH, handle_openapi};
+use crate::pcli::tool_list;
+use crate::policy::PolicyQuotas;
+use crate::quota::QuotaTracker;
+use crate::redact::{self, Redactor};
+use crate::reload::ConfigReloader;
+#[cfg(unix)]
+use crate::reload::spawn_sighup_reload;
+use crate::resources::DEFAULT_INLINE_RESULT_BYTES;
+use crate::runner::configured_runner;
+use crate::tui::{LogBuffer, check_terminal, run_tui};
+use crate::usage::{UsageLedger, UsageReporter};
+use crate::warm::Warmer;
+use crate::watch::FolderWatcher;
+use crate::websocket::handle_mcp_socket;
+use crate::{AppState, set_log_level};
 use anyhow::{Result, anyhow};
 use axum::{
-    BoxError, Router, error_handling::HandleErrorLayer, extract::DefaultBodyLimit,
-    http::StatusCode, response::IntoResponse, routing::get,
+    BoxError, Json, Router,
+    error_handling::HandleErrorLayer,
+    extract::{DefaultBodyLimit, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
 };
 use chrono::Utc;
 use clap::ArgMatches;
-use std::io::IsTerminal;
+use serde::Serialize;
+use std::future::{Future, IntoFuture};
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::task::JoinSet;
 use tower::{ServiceBuilder, timeout::TimeoutLayer};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
 
-const SERVER_NAME: &str = "mcp-http-server";
+pub const SERVER_NAME: &str = "mcp-http-server";
 const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
-const MAX_REQUEST_BYTES: usize = 1_048_576;
+pub const MAX_REQUEST_BYTES: usize = 1_048_576;
 const REQUEST_TIMEOUT: Duration = Duration::from_secs(30 * 60);
 
-pub async fn run_server(matches: &ArgMatches) -> Result<()> {
+/// A bound listener serving until it fails.
+pub type ServerFuture = Pin<Box<dyn Future<Output = io::Result<()>> + Send>>;
+
+/// Runs `serve`. `tui_logs` is set for `serve --tui` and receives the log
+/// output while the status view owns the terminal.
+pub async fn run_server(matches: &ArgMatches, tui_logs: Option<LogBuffer>) -> Result<()> {
     let host = matches
         .get_one::<String>(ARG_HOST)
         .map(String::as_str)
@@ -26,37 +80,151 @@ pub async fn run_server(matches: &ArgMatches) -> Result<()> {
     let port = *matches
         .get_one::<u16>(ARG_PORT)
         .ok_or_else(|| anyhow!("missing port"))?;
+    let arg_mode = match matches.get_one::<String>(ARG_ARGUMENT_MODE) {
+        Some(value) => ArgMode::parse(value).map_err(|e| anyhow!(e))?,
+        None => ArgMode::default(),
+    };
+    let inline_result_limit = *matches
+        .get_one::<usize>(ARG_MAX_INLINE_BYTES)
+        .unwrap_or(&DEFAULT_INLINE_RESULT_BYTES);
+    let compat_text = matches
+        .get_one::<bool>(ARG_COMPAT_TEXT)
+        .copied()
+        .unwrap_or(true);
+    let max_in_flight = matches
+        .get_one::<u32>(ARG_MAX_IN_FLIGHT)
+        .map_or(DEFAULT_MAX_IN_FLIGHT, |value| *value as usize);
+    let admin_port = matches.get_one::<u16>(ARG_ADMIN_PORT).copied();
+    let config = load_config(matches)?;
+    if let Some(level) = &config.log_level
+        && let Err(err) = set_log_level(level)
+    {
+        warn!("⚠ could not apply 'log_level': {}", err);
+    }
+    if let Some(replay) = &config.access.replay
+        && !replay.signed
+    {
+        warn!(
+            "⚠ 'access.replay' without 'signed' does not stop replays: the API key travels with each request, so a captured one can be resent with a fresh nonce"
+        );
+    }
 
-    print_banner();
+    if tui_logs.is_some() {
+        check_terminal()?;
+    } else {
+        print_banner(
+            &config.banner,
+            color_mode(matches).enabled(io::stdout().is_terminal()),
+        );
+    }
 
+    let mut conformance = None;
+    let cassette = match (
+        matches.get_one::<PathBuf>(ARG_RECORD),
+        matches.get_one::<PathBuf>(ARG_REPLAY),
+    ) {
+        _ if let Some(dir) = matches.get_one::<PathBuf>(ARG_RECORD_CONFORMANCE) => {
+            info!("recording a conformance suite to {}", dir.display());
+            let (recorder, cassette) = ConformanceRecorder::create(dir)?;
+            conformance = Some(Arc::new(recorder));
+            Some(Arc::new(cassette))
+        }
+        (Some(path), _) => {
+            info!("recording pcli2 runs to {}", path.display());
+            Some(Arc::new(Cassette::record(path)?))
+        }
+        (None, Some(path)) => {
+            info!("replaying pcli2 runs from {}", path.display());
+            Some(Arc::new(Cassette::replay(path)?))
+        }
+        (None, None) => None,
+    };
+    let cache = ResultCache::open(&config.cache, matches.get_flag(ARG_COLD_CACHE))?;
+    #[cfg(feature = "redis")]
+    let cache = match shared_store(&config)? {
+        Some(shared) => cache.with_shared(shared),
+        None => cache,
+    };
     let state = AppState {
-        server_name: SERVER_NAME.to_string(),
-        server_version: APP_VERSION.to_string(),
+        arg_mode,
+        inline_result_limit,
+        compat_text,
+        limiter: RequestLimiter::new(max_in_flight),
+        cassette,
+        conformance,
+        cache,
+        reloader: ConfigReloader::new(matches.get_one::<PathBuf>(ARG_CONFIG_FILE).cloned()),
+        ..build_state(config)?
     };
-
-    let app = Router::new()
-        .route("/health", get(health))
-        .route("/mcp", axum::routing::post(handle_mcp))
-        .with_state(state)
-        .layer(
-            ServiceBuilder::new()
-                .layer(HandleErrorLayer::new(|error: BoxError| async move {
-                    if error.is::<tower::timeout::error::Elapsed>() {
-                        (StatusCode::REQUEST_TIMEOUT, "Request timed out")
-                    } else {
-                        (
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            "Unhandled internal error",
-                        )
-                    }
-                }))
-                .layer(TimeoutLayer::new(REQUEST_TIMEOUT))
-                .layer(DefaultBodyLimit::max(MAX_REQUEST_BYTES)),
+    let identity = &state.config.identity;
+    if identity.name.is_some() || identity.title.is_some() || identity.version.is_some() {
+        let info = server_info(&state);
+        info!(
+            "identifying as {} {} ({} {})",
+            info["title"]
+                .as_str()
+                .or(info["name"].as_str())
+                .unwrap_or_default(),
+            info["version"].as_str().unwrap_or_default(),
+            state.server_name,
+            state.server_version
         );
+    }
+    FolderWatcher::new(state.clone())?.spawn();
+    Indexer::new(state.clone()).spawn();
+    UsageReporter::new(state.clone()).spawn();
+    Warmer::new(state.clone()).spawn();
+    Collector::new(state.clone()).spawn();
+    CatalogCheck::new(state.clone()).spawn();
+    #[cfg(unix)]
+    spawn_sighup_reload(state.clone())?;
 
     let bind_addr = format!("{host}:{port}");
+    let listener = tokio::net::TcpListener::bind(&bind_addr).await?;
     info!("listening on http://{}", bind_addr);
     debug!("MCP server bound to {}", bind_addr);
+    let server: ServerFuture = match admin_port {
+        None => {
+            Box::pin(axum::serve(listener, router(state.clone()).into_make_service()).into_future())
+        }
+        Some(admin_port) => {
+            let admin_host = matches
+                .get_one::<String>(ARG_ADMIN_HOST)
+                .map_or(host, String::as_str);
+            if admin_port == port && admin_host == host {
+                return Err(anyhow!("--admin-port must differ from --port"));
+            }
+            let admin_addr = format!("{admin_host}:{admin_port}");
+            let admin_listener = tokio::net::TcpListener::bind(&admin_addr).await?;
+            info!("admin and health endpoints on http://{}", admin_addr);
+            let mcp = axum::serve(listener, mcp_router(state.clone()).into_make_service());
+            let admin = axum::serve(
+                admin_listener,
+                admin_router(state.clone()).into_make_service(),
+            );
+            Box::pin(
+                async move { tokio::try_join!(mcp.into_future(), admin.into_future()).map(|_| ()) },
+            )
+        }
+    };
+    let server = if state.config.listeners.is_empty() {
+        server
+    } else {
+        let mut servers = vec![server];
+        for listener in &state.config.listeners {
+            let (url, extra) = bind_listener(listener, host, state.clone()).await?;
+            info!("also listening on {}", url);
+            servers.push(extra);
+        }
+        serve_all(servers)
+    };
+    // Written once the ports are bound, so `serve --daemon` reports a
+    // server that is ready.
+    let _pid_file = matches
+        .get_one::<PathBuf>(ARG_PID_FILE)
+        .map(|path| PidFile::create(path))
+        .transpose()?;
+    let server = until_terminated(server);
     info!("Press Ctrl+C to stop the server");
 
     // Log the server start time
@@ -65,96 +233,335 @@ pub async fn run_server(matches: &ArgMatches) -> Result<()> {
         Utc::now().format("%Y-%m-%d %H:%M:%S UTC")
     );
 
-    axum::serve(
-        tokio::net::TcpListener::bind(&bind_addr).await?,
-        app.into_make_service(),
-    )
-    .await?;
+    let usage = state.usage.clone();
+    let cache = state.cache.clone();
+    let result: Result<()> = match tui_logs {
+        Some(logs) => {
+            let title = format!("pcli2-mcp {} — http://{}/mcp", APP_VERSION, bind_addr);
+            // Quitting the status view stops the server.
+            tokio::select! {
+                result = server => result.map_err(Into::into),
+                result = run_tui(state, title, logs) => result,
+            }
+        }
+        None => server.await.map_err(Into::into),
+    };
+    // Calls made since the last periodic save.
+    usage.save();
+    cache.flush();
+    result
+}
 
-    Ok(())
+/// `--color` of `serve`; other commands pick colors automatically.
+pub fn color_mode(matches: &ArgMatches) -> ColorMode {
+    matches
+        .try_get_one::<String>(ARG_COLOR)
+        .ok()
+        .flatten()
+        .and_then(|value| ColorMode::parse(value).ok())
+        .unwrap_or_default()
 }
 
-async fn health() -> impl IntoResponse {
-    (StatusCode::OK, "ok")
+/// Loads and validates `--config`, and installs its redaction rules.
+pub fn load_config(matches: &ArgMatches) -> Result<ServerConfig> {
+    let config = load_config_file(matches.get_one::<PathBuf>(ARG_CONFIG_FILE))?;
+    redact::install(Redactor::new(&config.redaction));
+    Ok(config)
 }
 
-fn print_banner() {
-    let ascii = [
-        "██████╗  ██████╗██╗     ██╗██████╗     ███╗   ███╗ ██████╗██████╗ ",
-        "██╔══██╗██╔════╝██║     ██║╚════██╗    ████╗ ████║██╔════╝██╔══██╗",
-        "██████╔╝██║     ██║     ██║ █████╔╝    ██╔████╔██║██║     ██████╔╝",
-        "██╔═══╝ ██║     ██║     ██║██╔═══╝     ██║╚██╔╝██║██║     ██╔═══╝ ",
-        "██║     ╚██████╗███████╗██║███████╗    ██║ ╚═╝ ██║╚██████╗██║     ",
-        "╚═╝      ╚═════╝╚══════╝╚═╝╚══════╝    ╚═╝     ╚═╝ ╚═════╝╚═╝     ",
-    ];
-    let use_color = std::io::stdout().is_terminal();
+/// Loads and validates a configuration file, or the defaults without one.
+pub fn load_config_file(path: Option<&PathBuf>) -> Result<ServerConfig> {
+    let config = match path {
+        Some(path) => ServerConfig::load(path)?,
+        None => ServerConfig::default(),
+    };
+    let tools = tool_list();
+    config.validate(
+        tools
+            .iter()
+            .filter_map(|tool| tool.get("name").and_then(|v| v.as_str())),
+    )?;
+    Ok(config)
+}
 
-    for line in ascii {
-        if use_color {
-            println!("{}", gradient_line(line));
-        } else {
-            println!("{}", line);
-        }
-    }
-    if use_color {
-        println!(
-            "{}",
-            gradient_line("          Model Context Protocol Server for PCLI2           ")
-        );
-    } else {
-        println!("          Model Context Protocol Server for PCLI2           ");
+/// The Redis store replicas share, when `shared_store.redis_url` is set.
+#[cfg(feature = "redis")]
+fn shared_store(config: &ServerConfig) -> Result<Option<crate::shared::SharedStore>> {
+    let Some(url) = &config.shared_store.redis_url else {
+        return Ok(None);
+    };
+    let url = crate::auth::expand_args(std::slice::from_ref(url))
+        .map_err(|e| anyhow!("Config 'shared_store.redis_url': {}", e))?
+        .concat();
+    crate::shared::SharedStore::new(&config.shared_store, &url)
+        .map(Some)
+        .map_err(|e| anyhow!(e))
+}
+
+pub fn build_state(config: ServerConfig) -> Result<AppState> {
+    let state = AppState {
+        runner: configured_runner(&config),
+        breaker: CircuitBreaker::new(config.circuit_breaker.clone()),
+        reauth: Reauthenticator::new(config.auth.clone()),
+        quotas: QuotaTracker::new(&config.quotas),
+        policy_quotas: PolicyQuotas::new(&config.policies),
+        usage: UsageLedger::open(&config.usage).map_err(|e| anyhow!(e))?,
+        config: Arc::new(config),
+        ..AppState::new(SERVER_NAME, APP_VERSION)
+    };
+    #[cfg(feature = "redis")]
+    if let Some(shared) = shared_store(&state.config)? {
+        info!("sharing sessions, quotas, nonces, and results through Redis");
+        return Ok(AppState {
+            resources: crate::resources::ResourceStore::with_shared(shared.clone()),
+            nonces: crate::replay::NonceCache::with_shared(shared.clone()),
+            sessions: crate::session::SessionRegistry::with_shared(shared.clone()),
+            quotas: state.quotas.clone().with_shared(shared.clone(), "session"),
+            policy_quotas: PolicyQuotas::with_shared(&state.config.policies, shared),
+            ..state
+        });
     }
+    Ok(state)
+}
 
-    // Center the version string to match the width of the banner
-    let version_text = format!("Version {}", APP_VERSION);
-    let banner_width: usize = 67; // Width of the banner lines
-    let text_len = version_text.len();
-    let padding = if text_len < banner_width {
-        (banner_width - text_len) / 2
-    } else {
-        0
+/// All endpoints on one listener.
+pub fn router(state: AppState) -> Router {
+    with_limits(mcp_routes().merge(admin_routes()).with_state(state))
+}
+
+/// The MCP endpoint alone, for `serve --admin-port`.
+pub fn mcp_router(state: AppState) -> Router {
+    with_limits(mcp_routes().with_state(state))
+}
+
+/// Health and admin endpoints, served on `--admin-port` when it is set.
+pub fn admin_router(state: AppState) -> Router {
+    with_limits(admin_routes().with_state(state))
+}
+
+/// Binds an extra listener of the `listeners` configuration; `host` is the
+/// default for listeners without one. Returns the URL it serves and the
+/// server, which shares `state` with every other listener.
+pub async fn bind_listener(
+    listener: &ListenerConfig,
+    host: &str,
+    state: AppState,
+) -> Result<(String, ServerFuture)> {
+    let router = listener_router(listener, state);
+    let scheme = match listener.transport {
+        ListenerTransport::Http => "http",
+        ListenerTransport::Websocket => "ws",
+        ListenerTransport::Unix => return bind_unix(listener, router),
     };
-    let centered_version = format!(
-        "{}{}{}",
-        " ".repeat(padding),
-        version_text,
-        " ".repeat(banner_width - text_len - padding)
+    let addr = format!(
+        "{}:{}",
+        listener.host.as_deref().unwrap_or(host),
+        listener.port.unwrap_or_default()
     );
+    let tcp = tokio::net::TcpListener::bind(&addr)
+        .await
+        .map_err(|err| anyhow!("Failed to listen on {}: {}", addr, err))?;
+    let url = format!("{}://{}/mcp", scheme, tcp.local_addr()?);
+    Ok((
+        url,
+        Box::pin(axum::serve(tcp, router.into_make_service()).into_future()),
+    ))
+}
 
-    if use_color {
-        println!("{}", gradient_line(&centered_version));
+fn listener_router(listener: &ListenerConfig, state: AppState) -> Router {
+    let mcp = match listener.transport {
+        ListenerTransport::Websocket => Router::new().route("/mcp", get(handle_mcp_socket)),
+        ListenerTransport::Http | ListenerTransport::Unix if listener.stream => mcp_routes(),
+        ListenerTransport::Http | ListenerTransport::Unix => {
+            Router::new().route("/mcp", axum::routing::post(handle_mcp))
+        }
+    };
+    let routes = if listener.admin {
+        mcp.merge(admin_routes())
     } else {
-        println!("{}", centered_version);
+        mcp
+    };
+    with_limits(routes.with_state(state))
+}
+
+#[cfg(unix)]
+fn bind_unix(listener: &ListenerConfig, router: Router) -> Result<(String, ServerFuture)> {
+    use std::os::unix::fs::FileTypeExt;
+
+    let path = listener
+        .path
+        .clone()
+        .ok_or_else(|| anyhow!("the unix listener needs a 'path'"))?;
+    // A socket left behind by a server that did not exit cleanly.
+    if std::fs::symlink_metadata(&path).is_ok_and(|meta| meta.file_type().is_socket()) {
+        std::fs::remove_file(&path)
+            .map_err(|err| anyhow!("Failed to remove {}: {}", path.display(), err))?;
     }
-    println!();
+    let unix = tokio::net::UnixListener::bind(&path)
+        .map_err(|err| anyhow!("Failed to listen on {}: {}", path.display(), err))?;
+    let url = format!("unix:{}", path.display());
+    Ok((url, Box::pin(serve_unix(unix, path, router))))
+}
+
+#[cfg(not(unix))]
+fn bind_unix(_listener: &ListenerConfig, _router: Router) -> Result<(String, ServerFuture)> {
+    Err(anyhow!("the unix listener is only supported on Unix"))
 }
 
-fn gradient_line(line: &str) -> String {
-    let start = (36u8, 144u8, 255u8);
-    let end = (255u8, 120u8, 48u8);
-    let chars: Vec<char> = line.chars().collect();
-    let len = chars.len().max(1);
-    let mut out = String::new();
+/// Serves HTTP/1.1 on a Unix socket, removing the socket file when the
+/// server stops.
+#[cfg(unix)]
+async fn serve_unix(
+    listener: tokio::net::UnixListener,
+    path: PathBuf,
+    router: Router,
+) -> io::Result<()> {
+    use hyper_util::{rt::TokioIo, service::TowerToHyperService};
 
-    for (i, ch) in chars.iter().enumerate() {
-        let t = if len == 1 {
-            0.0
-        } else {
-            i as f32 / (len - 1) as f32
+    struct SocketFile(PathBuf);
+    impl Drop for SocketFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+    let _socket_file = SocketFile(path);
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                // Like `axum::serve`, ride out errors such as running out
+                // of file descriptors.
+                warn!("⚠ could not accept a unix socket connection: {}", err);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
         };
-        let r = lerp(start.0, end.0, t);
-        let g = lerp(start.1, end.1, t);
-        let b = lerp(start.2, end.2, t);
-        out.push_str(&format!("\x1b[38;2;{};{};{}m{}", r, g, b, ch));
+        let service = TowerToHyperService::new(router.clone());
+        tokio::spawn(async move {
+            if let Err(err) = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                debug!("unix socket connection ended: {}", err);
+            }
+        });
+    }
+}
+
+/// Runs every server until one fails.
+fn serve_all(servers: Vec<ServerFuture>) -> ServerFuture {
+    Box::pin(async move {
+        let mut running = JoinSet::new();
+        for server in servers {
+            running.spawn(server);
+        }
+        while let Some(finished) = running.join_next().await {
+            finished.map_err(io::Error::other)??;
+        }
+        Ok(())
+    })
+}
+
+fn mcp_routes() -> Router<AppState> {
+    Router::new().route(
+        "/mcp",
+        axum::routing::post(handle_mcp).get(handle_mcp_stream),
+    )
+}
+
+fn admin_routes() -> Router<AppState> {
+    Router::new()
+        .route("/health", get(health))
+        .route("/health/ready", get(health_ready))
+        .route(OPENAPI_PATH, get(handle_openapi))
+        .route(
+            "/admin/credentials",
+            axum::routing::post(handle_credentials),
+        )
+        .route("/admin/reload", axum::routing::post(handle_reload))
+        .route("/admin/approvals", get(handle_approvals))
+        .route(
+            "/admin/approvals/:id",
+            axum::routing::post(handle_approval_decision),
+        )
+        .route("/admin/features", get(handle_features))
+        .route(
+            "/admin/features/:name",
+            axum::routing::post(handle_feature_update).delete(handle_feature_reset),
+        )
+        .route("/admin/usage", get(handle_usage))
+        .route("/admin/usage/:month", get(handle_usage_month))
+}
+
+fn with_limits(router: Router) -> Router {
+    router.layer(
+        ServiceBuilder::new()
+            .layer(HandleErrorLayer::new(|error: BoxError| async move {
+                if error.is::<tower::timeout::error::Elapsed>() {
+                    (StatusCode::REQUEST_TIMEOUT, "Request timed out")
+                } else {
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Unhandled internal error",
+                    )
+                }
+            }))
+            .layer(TimeoutLayer::new(REQUEST_TIMEOUT))
+            .layer(DefaultBodyLimit::max(MAX_REQUEST_BYTES)),
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "health",
+    summary = "Liveness",
+    responses((status = 200, description = "The server is up", body = String, content_type = "text/plain"))
+)]
+async fn health(State(mut state): State<AppState>, headers: HeaderMap) -> Response {
+    state.refresh_config();
+    if let Err(refusal) = admit(&state.config, Route::Health, &headers) {
+        return (refusal.status(), refusal.to_string()).into_response();
     }
-    out.push_str("\x1b[0m");
-    out
+    (StatusCode::OK, "ok").into_response()
 }
 
-fn lerp(a: u8, b: u8, t: f32) -> u8 {
-    let af = a as f32;
-    let bf = b as f32;
-    (af + (bf - af) * t) as u8
+/// The body of `/health/ready`.
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct Readiness {
+    #[serde(flatten)]
+    health: HealthView,
+    /// Tools hidden because the installed `pcli2` cannot run them.
+    unavailable_tools: Vec<UnavailableTool>,
+}
+
+/// Readiness: `503` while the `pcli2` backend is degraded or unavailable,
+/// so a load balancer can route around this instance.
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    tag = "health",
+    summary = "Readiness of the pcli2 backend",
+    responses(
+        (status = 200, description = "Healthy", body = Readiness),
+        (status = 503, description = "Degraded or unavailable", body = Readiness)
+    )
+)]
+async fn health_ready(State(mut state): State<AppState>, headers: HeaderMap) -> Response {
+    state.refresh_config();
+    if let Err(refusal) = admit(&state.config, Route::Health, &headers) {
+        return (refusal.status(), refusal.to_string()).into_response();
+    }
+    let report = backend_health(&state);
+    let status = match report.state {
+        HealthState::Healthy => StatusCode::OK,
+        HealthState::Degraded | HealthState::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    let body = Readiness {
+        health: report.view(),
+        unavailable_tools: unavailable_tools(&state),
+    };
+    (status, Json(body)).into_response()
 }
 
 #[cfg(test)]
@@ -168,7 +575,9 @@ mod tests {
 
     #[tokio::test]
     async fn test_health_endpoint() {
-        let app = Router::new().route("/health", get(health));
+        let app = Router::new()
+            .route("/health", get(health))
+            .with_state(AppState::new("test", "0.0.0"));
 
         let response = app
             .oneshot(
@@ -188,24 +597,105 @@ mod tests {
         assert_eq!(&body[..], b"ok");
     }
 
-    #[test]
-    fn test_lerp() {
-        // Test edge cases
-        assert_eq!(lerp(0, 100, 0.0), 0);
-        assert_eq!(lerp(0, 100, 1.0), 100);
-        assert_eq!(lerp(0, 100, 0.5), 50);
+    async fn status(app: Router, method: &str, uri: &str) -> StatusCode {
+        app.oneshot(
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Body::from("{}"))
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+        .status()
+    }
 
-        // Test with different values
-        assert_eq!(lerp(50, 150, 0.5), 100);
-        assert_eq!(lerp(10, 20, 0.3), 13); // Approximation due to integer conversion
+    #[tokio::test]
+    async fn test_admin_routes_move_to_admin_router() {
+        let state = AppState::new("test", "0.0.0");
+        let mcp = mcp_router(state.clone());
+        let admin = admin_router(state.clone());
+        assert_eq!(
+            status(mcp.clone(), "GET", "/health").await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            status(mcp.clone(), "POST", "/admin/credentials").await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            status(mcp.clone(), "POST", "/admin/reload").await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(status(mcp, "POST", "/mcp").await, StatusCode::OK);
+        assert_eq!(
+            status(admin.clone(), "GET", "/health").await,
+            StatusCode::OK
+        );
+        assert_eq!(status(admin, "POST", "/mcp").await, StatusCode::NOT_FOUND);
+        assert_eq!(
+            status(router(state), "GET", "/health").await,
+            StatusCode::OK
+        );
     }
 
-    #[test]
-    fn test_gradient_line() {
-        let input = "test";
-        let result = gradient_line(input);
-        // Check that the result contains ANSI color codes
-        assert!(result.contains("\x1b[38;2;")); // Start of RGB color code
-        assert!(result.ends_with("\x1b[0m")); // Reset code at the end
+    #[tokio::test]
+    async fn test_ready_endpoint_follows_backend_health() {
+        let state = AppState {
+            config: Arc::new(ServerConfig {
+                health: crate::config::HealthConfig {
+                    min_samples: 1,
+                    ..Default::default()
+                },
+                ..ServerConfig::default()
+            }),
+            ..AppState::new("test", "0.0.0")
+        };
+        let admin = admin_router(state.clone());
+        assert_eq!(
+            status(admin.clone(), "GET", "/health/ready").await,
+            StatusCode::OK
+        );
+        state
+            .health
+            .record(false, std::time::Duration::from_millis(5));
+        assert_eq!(
+            status(admin, "GET", "/health/ready").await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+
+    #[tokio::test]
+    async fn test_listener_router_follows_its_configuration() {
+        let state = AppState::new("test", "0.0.0");
+        let plain = listener_router(
+            &ListenerConfig {
+                port: Some(0),
+                stream: false,
+                ..ListenerConfig::default()
+            },
+            state.clone(),
+        );
+        assert_eq!(status(plain.clone(), "POST", "/mcp").await, StatusCode::OK);
+        assert_eq!(
+            status(plain.clone(), "GET", "/mcp").await,
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+        assert_eq!(status(plain, "GET", "/health").await, StatusCode::NOT_FOUND);
+
+        let websocket = listener_router(
+            &ListenerConfig {
+                transport: ListenerTransport::Websocket,
+                port: Some(0),
+                admin: true,
+                ..ListenerConfig::default()
+            },
+            state,
+        );
+        assert_eq!(
+            status(websocket.clone(), "POST", "/mcp").await,
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+        assert_eq!(status(websocket, "GET", "/health").await, StatusCode::OK);
     }
 }
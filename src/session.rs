@@ -0,0 +1,173 @@
+#[cfg(feature = "redis")]
+use crate::shared::SharedStore;
+use crate::{mcp::new_session_id, quota::MAX_TRACKED_SESSIONS};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+#[cfg(feature = "redis")]
+use tracing::warn;
+
+/// Sessions one principal may hold at once; opening another ends its least
+/// recently used one, so no client can crowd out everyone else's.
+pub const MAX_SESSIONS_PER_PRINCIPAL: usize = 64;
+/// How often a session in use is kept alive in the shared store.
+const SHARED_REFRESH: Duration = Duration::from_secs(60);
+
+struct Owner {
+    principal: String,
+    last_seen: Instant,
+    shared_at: Instant,
+}
+
+/// The `Mcp-Session-Id`s the server issued on `initialize`, and the
+/// principal (API key name or `anonymous`) each was issued to. Quotas,
+/// fairness, deduplication, and subscriptions are keyed on session ids, so
+/// only issued ones are accepted, and only from their principal. With a
+/// shared store, sessions issued by any replica are accepted.
+#[derive(Clone, Default)]
+pub struct SessionRegistry {
+    sessions: Arc<Mutex<HashMap<String, Owner>>>,
+    #[cfg(feature = "redis")]
+    shared: Option<SharedStore>,
+}
+
+impl SessionRegistry {
+    #[cfg(feature = "redis")]
+    pub fn with_shared(shared: SharedStore) -> Self {
+        Self {
+            shared: Some(shared),
+            ..Self::default()
+        }
+    }
+
+    /// A new session id for `principal`.
+    pub async fn issue(&self, principal: &str) -> String {
+        let session = new_session_id();
+        self.insert(session.clone(), principal);
+        #[cfg(feature = "redis")]
+        self.share(&session, principal).await;
+        session
+    }
+
+    /// Accepts `session` for `principal` as if it had been issued, for
+    /// sessions the server opens itself (WebSocket connections, replays).
+    pub fn insert(&self, session: String, principal: &str) {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let held: Vec<(&String, &Owner)> = sessions
+            .iter()
+            .filter(|(_, owner)| owner.principal == principal)
+            .collect();
+        let evict = if held.len() >= MAX_SESSIONS_PER_PRINCIPAL {
+            least_recent(held)
+        } else if sessions.len() >= MAX_TRACKED_SESSIONS {
+            least_recent(sessions.iter().collect())
+        } else {
+            None
+        };
+        if let Some(evict) = evict {
+            sessions.remove(&evict);
+        }
+        sessions.insert(
+            session,
+            Owner {
+                principal: principal.to_string(),
+                last_seen: Instant::now(),
+                shared_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Checks that `session` was issued to `principal` and is still open.
+    pub async fn check(&self, session: &str, principal: &str) -> Result<(), String> {
+        // Whether the session is held, and if so whether it is due to be
+        // kept alive in the shared store.
+        let held = {
+            let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+            match sessions.get_mut(session) {
+                Some(owner) if owner.principal == principal => {
+                    let now = Instant::now();
+                    owner.last_seen = now;
+                    let refresh = now.duration_since(owner.shared_at) >= SHARED_REFRESH;
+                    if refresh {
+                        owner.shared_at = now;
+                    }
+                    Some(refresh)
+                }
+                _ => None,
+            }
+        };
+        #[cfg(feature = "redis")]
+        match held {
+            Some(true) => self.share(session, principal).await,
+            Some(false) => {}
+            None if self.adopt(session, principal).await => return Ok(()),
+            None => {}
+        }
+        match held {
+            Some(_) => Ok(()),
+            None => Err(format!(
+                "Session '{}' not found; send initialize to start a new one",
+                session
+            )),
+        }
+    }
+
+    #[cfg(feature = "redis")]
+    async fn share(&self, session: &str, principal: &str) {
+        if let Some(shared) = &self.shared
+            && let Err(err) = shared.put_session(session, principal).await
+        {
+            warn!("⚠ failed to share session {}: {}", session, err);
+        }
+    }
+
+    /// Accepts a session another replica issued to `principal`.
+    #[cfg(feature = "redis")]
+    async fn adopt(&self, session: &str, principal: &str) -> bool {
+        let Some(shared) = &self.shared else {
+            return false;
+        };
+        match shared.session_principal(session).await {
+            Ok(Some(owner)) if owner == principal => {
+                self.insert(session.to_string(), principal);
+                true
+            }
+            Ok(_) => false,
+            Err(err) => {
+                warn!("⚠ failed to look up shared session {}: {}", session, err);
+                false
+            }
+        }
+    }
+}
+
+fn least_recent(sessions: Vec<(&String, &Owner)>) -> Option<String> {
+    sessions
+        .into_iter()
+        .min_by_key(|(_, owner)| owner.last_seen)
+        .map(|(session, _)| session.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_only_issued_sessions_of_the_principal_are_accepted() {
+        let registry = SessionRegistry::default();
+        let session = registry.issue("analyst").await;
+        assert!(registry.check(&session, "analyst").await.is_ok());
+        assert!(registry.check(&session, "anonymous").await.is_err());
+        assert!(registry.check("made-up", "analyst").await.is_err());
+
+        // A principal opening many sessions ends only its own.
+        let other = registry.issue("admin").await;
+        for _ in 0..MAX_SESSIONS_PER_PRINCIPAL {
+            registry.issue("analyst").await;
+        }
+        assert!(registry.check(&session, "analyst").await.is_err());
+        assert!(registry.check(&other, "admin").await.is_ok());
+    }
+}
@@ -0,0 +1,724 @@
+use crate::{config::SharedStoreConfig, resources::StoredResource};
+use redis::{
+    Cmd, FromRedisValue, Pipeline,
+    aio::{ConnectionManager, ConnectionManagerConfig},
+};
+use serde_json::{Value, json};
+use std::{sync::Arc, time::Duration};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long one command or transaction may take; past it the caller falls
+/// back to local state instead of holding up the request.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(2);
+const MAX_INDEXED_RESOURCES: usize = 256;
+
+/// Checks that `url` is a `redis://` or, for TLS, a `rediss://` URL.
+pub fn check_url(url: &str) -> Result<(), String> {
+    redis::Client::open(url)
+        .map(|_| ())
+        .map_err(|err| format!("Invalid Redis URL: {}", err))
+}
+
+/// Redis-backed store shared by every replica behind a load balancer.
+/// Commands from all requests are multiplexed over one connection, each
+/// reply matched to its command, so a request that gives up waiting never
+/// leaves a reply behind for the next one.
+#[derive(Clone)]
+pub struct SharedStore {
+    prefix: String,
+    ttl_secs: u64,
+    connection: ConnectionManager,
+}
+
+impl SharedStore {
+    /// Connects on the first command, so the server starts while Redis is
+    /// down, and reconnects after the connection is lost.
+    pub fn new(config: &SharedStoreConfig, url: &str) -> Result<Self, String> {
+        let client =
+            redis::Client::open(url).map_err(|err| format!("Invalid Redis URL: {}", err))?;
+        let settings = ConnectionManagerConfig::new()
+            .set_connection_timeout(Some(CONNECT_TIMEOUT))
+            .set_response_timeout(Some(COMMAND_TIMEOUT))
+            .set_number_of_retries(1);
+        let connection = ConnectionManager::new_lazy_with_config(client, settings)
+            .map_err(|err| format!("Failed to set up the Redis connection: {}", err))?;
+        Ok(Self {
+            prefix: config.key_prefix.clone(),
+            ttl_secs: config.ttl_secs,
+            connection,
+        })
+    }
+
+    fn resource_key(&self, uri: &str) -> String {
+        format!("{}resource:{}", self.prefix, uri)
+    }
+
+    fn index_key(&self) -> String {
+        format!("{}resources", self.prefix)
+    }
+
+    /// Stores `resource` and adds it to the index in one transaction, so the
+    /// index never outlives its TTL or misses an entry.
+    pub async fn put_resource(&self, resource: &StoredResource) -> Result<(), String> {
+        let payload = json!({
+            "uri": resource.uri,
+            "name": resource.name,
+            "mimeType": resource.mime_type,
+            "text": resource.text.as_str(),
+            "tenant": resource.tenant,
+            "blob": resource.blob
+        })
+        .to_string();
+        let index = self.index_key();
+        let mut transaction = redis::pipe();
+        transaction
+            .atomic()
+            .cmd("SET")
+            .arg(self.resource_key(&resource.uri))
+            .arg(payload)
+            .arg("EX")
+            .arg(self.ttl_secs)
+            .ignore()
+            .cmd("LPUSH")
+            .arg(&index)
+            .arg(&resource.uri)
+            .ignore()
+            .cmd("LTRIM")
+            .arg(&index)
+            .arg(0)
+            .arg(MAX_INDEXED_RESOURCES - 1)
+            .ignore()
+            .cmd("EXPIRE")
+            .arg(&index)
+            .arg(self.ttl_secs)
+            .ignore();
+        self.transaction(&transaction).await
+    }
+
+    pub async fn get_resource(&self, uri: &str) -> Result<Option<StoredResource>, String> {
+        let stored: Option<Vec<u8>> = self
+            .query(Cmd::new().arg("GET").arg(self.resource_key(uri)))
+            .await?;
+        Ok(stored.and_then(|bytes| decode_resource(&bytes)))
+    }
+
+    /// Returns the shared resources, oldest first, skipping expired entries.
+    pub async fn list_resources(&self) -> Result<Vec<StoredResource>, String> {
+        let uris: Vec<String> = self
+            .query(
+                Cmd::new()
+                    .arg("LRANGE")
+                    .arg(self.index_key())
+                    .arg(0)
+                    .arg(-1),
+            )
+            .await?;
+        let mut resources = Vec::new();
+        for uri in uris.into_iter().rev() {
+            if let Some(resource) = self.get_resource(&uri).await? {
+                resources.push(resource);
+            }
+        }
+        Ok(resources)
+    }
+
+    fn session_key(&self, session: &str) -> String {
+        format!("{}session:{}", self.prefix, session)
+    }
+
+    /// Records that `session` was issued to `principal`, for `ttl_secs`.
+    pub async fn put_session(&self, session: &str, principal: &str) -> Result<(), String> {
+        self.query(
+            Cmd::new()
+                .arg("SET")
+                .arg(self.session_key(session))
+                .arg(principal)
+                .arg("EX")
+                .arg(self.ttl_secs),
+        )
+        .await
+    }
+
+    /// The principal `session` was issued to by any replica.
+    pub async fn session_principal(&self, session: &str) -> Result<Option<String>, String> {
+        self.query(Cmd::new().arg("GET").arg(self.session_key(session)))
+            .await
+    }
+
+    fn nonce_key(&self, nonce: &str) -> String {
+        format!("{}nonce:{}", self.prefix, nonce)
+    }
+
+    /// Claims `nonce` for `ttl_secs`; false when a replica already did.
+    pub async fn claim_nonce(&self, nonce: &str, ttl_secs: u64) -> Result<bool, String> {
+        let claimed: Option<String> = self
+            .query(
+                Cmd::new()
+                    .arg("SET")
+                    .arg(self.nonce_key(nonce))
+                    .arg(1)
+                    .arg("NX")
+                    .arg("EX")
+                    .arg(ttl_secs.max(1)),
+            )
+            .await?;
+        Ok(claimed.is_some())
+    }
+
+    fn quota_key(&self, counter: &str) -> String {
+        format!("{}quota:{}", self.prefix, counter)
+    }
+
+    /// Adds `call`, made at `now_ms` (Unix time in milliseconds), to the
+    /// calls counted under `counter`, dropping those older than `window`.
+    /// Returns when each counted call was made, oldest first.
+    pub async fn add_call(
+        &self,
+        counter: &str,
+        call: &str,
+        now_ms: i64,
+        window: Option<Duration>,
+    ) -> Result<Vec<i64>, String> {
+        let key = self.quota_key(counter);
+        // Calls without a window count for as long as shared sessions last.
+        let ttl = window
+            .map(|window| window.as_secs().max(1))
+            .unwrap_or(self.ttl_secs);
+        let mut transaction = redis::pipe();
+        transaction.atomic();
+        expire_calls(&mut transaction, &key, now_ms, window);
+        transaction
+            .cmd("ZADD")
+            .arg(&key)
+            .arg(now_ms)
+            .arg(call)
+            .ignore()
+            .cmd("EXPIRE")
+            .arg(&key)
+            .arg(ttl)
+            .ignore();
+        call_times(&mut transaction, &key);
+        let (calls,): (Vec<(String, f64)>,) = self.transaction(&transaction).await?;
+        Ok(times(calls))
+    }
+
+    /// Takes back `call` from the calls counted under `counter`.
+    pub async fn remove_call(&self, counter: &str, call: &str) -> Result<(), String> {
+        self.query::<i64>(
+            Cmd::new()
+                .arg("ZREM")
+                .arg(self.quota_key(counter))
+                .arg(call),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// When each call counted under `counter` within `window` of `now_ms`
+    /// was made, oldest first.
+    pub async fn calls(
+        &self,
+        counter: &str,
+        now_ms: i64,
+        window: Option<Duration>,
+    ) -> Result<Vec<i64>, String> {
+        let key = self.quota_key(counter);
+        let mut transaction = redis::pipe();
+        transaction.atomic();
+        expire_calls(&mut transaction, &key, now_ms, window);
+        call_times(&mut transaction, &key);
+        let (calls,): (Vec<(String, f64)>,) = self.transaction(&transaction).await?;
+        Ok(times(calls))
+    }
+
+    fn cache_key(&self, generations: &[i64], key: &str) -> String {
+        let generations: Vec<String> = generations.iter().map(i64::to_string).collect();
+        format!("{}cache:{}:{}", self.prefix, generations.join("."), key)
+    }
+
+    fn generation_key(&self, scope: &str) -> String {
+        format!("{}cache-generation:{}", self.prefix, scope)
+    }
+
+    /// The generations of the shared result cache in each of `scopes`. A
+    /// scope's generation moves on when a replica drops the cached results
+    /// in it, which drops them on every replica.
+    pub async fn cache_generations(&self, scopes: &[String]) -> Result<Vec<i64>, String> {
+        let keys: Vec<String> = scopes
+            .iter()
+            .map(|scope| self.generation_key(scope))
+            .collect();
+        let generations: Vec<Option<i64>> = self.query(Cmd::new().arg("MGET").arg(keys)).await?;
+        Ok(generations
+            .into_iter()
+            .map(|generation| generation.unwrap_or(0))
+            .collect())
+    }
+
+    /// Starts new generations of the shared result cache in `scopes`.
+    pub async fn next_cache_generations(&self, scopes: &[String]) -> Result<(), String> {
+        let mut transaction = redis::pipe();
+        transaction.atomic();
+        for scope in scopes {
+            transaction
+                .cmd("INCR")
+                .arg(self.generation_key(scope))
+                .ignore();
+        }
+        self.transaction(&transaction).await
+    }
+
+    pub async fn put_cached(
+        &self,
+        generations: &[i64],
+        key: &str,
+        entry: &str,
+        max_age: Duration,
+    ) -> Result<(), String> {
+        self.query(
+            Cmd::new()
+                .arg("SET")
+                .arg(self.cache_key(generations, key))
+                .arg(entry)
+                .arg("EX")
+                .arg(max_age.as_secs().max(1)),
+        )
+        .await
+    }
+
+    pub async fn get_cached(
+        &self,
+        generations: &[i64],
+        key: &str,
+    ) -> Result<Option<String>, String> {
+        self.query(Cmd::new().arg("GET").arg(self.cache_key(generations, key)))
+            .await
+    }
+
+    async fn query<T: FromRedisValue>(&self, command: &Cmd) -> Result<T, String> {
+        command
+            .query_async(&mut self.connection.clone())
+            .await
+            .map_err(|err| format!("Redis command failed: {}", err))
+    }
+
+    async fn transaction<T: FromRedisValue>(&self, transaction: &Pipeline) -> Result<T, String> {
+        transaction
+            .query_async(&mut self.connection.clone())
+            .await
+            .map_err(|err| format!("Redis transaction failed: {}", err))
+    }
+}
+
+fn expire_calls(transaction: &mut Pipeline, key: &str, now_ms: i64, window: Option<Duration>) {
+    if let Some(window) = window {
+        transaction
+            .cmd("ZREMRANGEBYSCORE")
+            .arg(key)
+            .arg("-inf")
+            .arg(now_ms - window.as_millis() as i64)
+            .ignore();
+    }
+}
+
+fn call_times(transaction: &mut Pipeline, key: &str) {
+    transaction
+        .cmd("ZRANGE")
+        .arg(key)
+        .arg(0)
+        .arg(-1)
+        .arg("WITHSCORES");
+}
+
+fn times(calls: Vec<(String, f64)>) -> Vec<i64> {
+    calls.into_iter().map(|(_, at)| at as i64).collect()
+}
+
+fn decode_resource(bytes: &[u8]) -> Option<StoredResource> {
+    let value: Value = serde_json::from_slice(bytes).ok()?;
+    Some(StoredResource {
+        uri: value["uri"].as_str()?.to_string(),
+        name: value["name"].as_str()?.to_string(),
+        mime_type: value["mimeType"].as_str()?.to_string(),
+        text: Arc::new(value["text"].as_str()?.to_string()),
+        tenant: value["tenant"].as_str().map(str::to_string),
+        blob: value["blob"].as_bool().unwrap_or(false),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cache::ResultCache,
+        config::{CacheConfig, QuotaRule, ReplayConfig},
+        quota::QuotaTracker,
+        replay::NonceCache,
+        resources::ResourceStore,
+        session::SessionRegistry,
+    };
+    use axum::http::HeaderMap;
+    use chrono::Utc;
+    use std::{collections::HashMap, sync::Mutex};
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::{TcpListener, TcpStream},
+    };
+
+    /// Keys the fake Redis answers only after a delay.
+    const SLOW_KEY: &str = "slow";
+
+    #[derive(Default)]
+    struct Data {
+        strings: HashMap<Vec<u8>, Vec<u8>>,
+        lists: HashMap<Vec<u8>, Vec<Vec<u8>>>,
+        sorted: HashMap<Vec<u8>, Vec<(f64, Vec<u8>)>>,
+    }
+
+    fn bulk(value: Option<&[u8]>) -> Vec<u8> {
+        match value {
+            Some(bytes) => {
+                let mut out = format!("${}\r\n", bytes.len()).into_bytes();
+                out.extend_from_slice(bytes);
+                out.extend_from_slice(b"\r\n");
+                out
+            }
+            None => b"$-1\r\n".to_vec(),
+        }
+    }
+
+    fn array(items: Vec<Vec<u8>>) -> Vec<u8> {
+        let mut out = format!("*{}\r\n", items.len()).into_bytes();
+        items.into_iter().for_each(|item| out.extend(item));
+        out
+    }
+
+    fn number(value: &[u8]) -> f64 {
+        match value {
+            b"-inf" => f64::NEG_INFINITY,
+            _ => String::from_utf8_lossy(value).parse().unwrap(),
+        }
+    }
+
+    /// Runs one command of the few the store uses.
+    fn apply(data: &mut Data, args: &[Vec<u8>]) -> Vec<u8> {
+        let ok = b"+OK\r\n".to_vec();
+        match args[0].to_ascii_uppercase().as_slice() {
+            b"SET" => {
+                let exists = data.strings.contains_key(&args[1]);
+                if exists && args.iter().any(|arg| arg.eq_ignore_ascii_case(b"NX")) {
+                    return bulk(None);
+                }
+                data.strings.insert(args[1].clone(), args[2].clone());
+                ok
+            }
+            b"GET" => bulk(data.strings.get(&args[1]).map(Vec::as_slice)),
+            b"MGET" => array(
+                args[1..]
+                    .iter()
+                    .map(|key| bulk(data.strings.get(key).map(Vec::as_slice)))
+                    .collect(),
+            ),
+            b"INCR" => {
+                let value = data
+                    .strings
+                    .get(&args[1])
+                    .map(|value| number(value) as i64)
+                    .unwrap_or(0)
+                    + 1;
+                data.strings
+                    .insert(args[1].clone(), value.to_string().into_bytes());
+                format!(":{}\r\n", value).into_bytes()
+            }
+            b"LPUSH" => {
+                let list = data.lists.entry(args[1].clone()).or_default();
+                list.insert(0, args[2].clone());
+                format!(":{}\r\n", list.len()).into_bytes()
+            }
+            b"LRANGE" => {
+                let list = data.lists.get(&args[1]).cloned().unwrap_or_default();
+                array(list.iter().map(|item| bulk(Some(item))).collect())
+            }
+            b"ZADD" => {
+                let set = data.sorted.entry(args[1].clone()).or_default();
+                set.retain(|(_, member)| *member != args[3]);
+                set.push((number(&args[2]), args[3].clone()));
+                set.sort_by(|a, b| a.0.total_cmp(&b.0));
+                b":1\r\n".to_vec()
+            }
+            b"ZREM" => {
+                let set = data.sorted.entry(args[1].clone()).or_default();
+                set.retain(|(_, member)| *member != args[2]);
+                b":1\r\n".to_vec()
+            }
+            b"ZREMRANGEBYSCORE" => {
+                let max = number(&args[3]);
+                let set = data.sorted.entry(args[1].clone()).or_default();
+                set.retain(|(score, _)| *score > max);
+                b":0\r\n".to_vec()
+            }
+            b"ZRANGE" => {
+                let set = data.sorted.get(&args[1]).cloned().unwrap_or_default();
+                array(
+                    set.iter()
+                        .flat_map(|(score, member)| {
+                            [bulk(Some(member)), bulk(Some(score.to_string().as_bytes()))]
+                        })
+                        .collect(),
+                )
+            }
+            b"LTRIM" | b"EXPIRE" | b"AUTH" | b"SELECT" | b"PING" => ok,
+            _ => b"-ERR unknown command\r\n".to_vec(),
+        }
+    }
+
+    async fn read_command(connection: &mut BufReader<TcpStream>) -> Option<Vec<Vec<u8>>> {
+        let mut line = String::new();
+        connection.read_line(&mut line).await.ok()?;
+        let count: usize = line.trim().strip_prefix('*')?.parse().ok()?;
+        let mut args = Vec::with_capacity(count);
+        for _ in 0..count {
+            line.clear();
+            connection.read_line(&mut line).await.ok()?;
+            let size: usize = line.trim().strip_prefix('$')?.parse().ok()?;
+            let mut arg = vec![0; size + 2];
+            connection.read_exact(&mut arg).await.ok()?;
+            arg.truncate(size);
+            args.push(arg);
+        }
+        Some(args)
+    }
+
+    /// Minimal in-memory Redis speaking just the commands the store uses,
+    /// with transactions. Reads of keys containing `SLOW_KEY` are answered
+    /// late.
+    async fn fake_redis() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let data: Arc<Mutex<Data>> = Arc::default();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let data = data.clone();
+                tokio::spawn(async move {
+                    let mut connection = BufReader::new(stream);
+                    let mut queued: Option<Vec<Vec<Vec<u8>>>> = None;
+                    while let Some(args) = read_command(&mut connection).await {
+                        let command = args[0].to_ascii_uppercase();
+                        let response = match (command.as_slice(), queued.as_mut()) {
+                            (b"MULTI", _) => {
+                                queued = Some(Vec::new());
+                                b"+OK\r\n".to_vec()
+                            }
+                            (b"EXEC", Some(_)) => {
+                                let commands = queued.take().unwrap_or_default();
+                                let mut data = data.lock().unwrap();
+                                array(commands.iter().map(|args| apply(&mut data, args)).collect())
+                            }
+                            (_, Some(commands)) => {
+                                commands.push(args);
+                                b"+QUEUED\r\n".to_vec()
+                            }
+                            (_, None) => {
+                                if args.iter().any(|arg| arg.ends_with(SLOW_KEY.as_bytes())) {
+                                    tokio::time::sleep(Duration::from_millis(300)).await;
+                                }
+                                apply(&mut data.lock().unwrap(), &args)
+                            }
+                        };
+                        if connection.get_mut().write_all(&response).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        format!("redis://:secret@{}/2", address)
+    }
+
+    #[test]
+    fn test_check_url() {
+        assert!(check_url("redis://app:pw@cache.internal:6380/3").is_ok());
+        assert!(check_url("redis://localhost").is_ok());
+        assert!(check_url("rediss://default:pw@cache.example.com:6380").is_ok());
+        assert!(check_url("http://localhost").is_err());
+        assert!(check_url("redis://localhost/x").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_commands_leave_no_reply_behind() {
+        let url = fake_redis().await;
+        let store = SharedStore::new(&SharedStoreConfig::default(), &url).unwrap();
+        store.put_session("s-1", "analyst").await.unwrap();
+        // A caller that gives up after its command went out...
+        let slow = store.session_principal(SLOW_KEY);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), slow)
+                .await
+                .is_err()
+        );
+        // ...does not hand its late reply to the next one.
+        assert_eq!(
+            store.session_principal("s-1").await.unwrap().as_deref(),
+            Some("analyst")
+        );
+        assert_eq!(store.session_principal("s-2").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_resources_round_trip() {
+        let url = fake_redis().await;
+        let store = SharedStore::new(&SharedStoreConfig::default(), &url).unwrap();
+        let resource = StoredResource {
+            uri: "pcli2://results/1-0".to_string(),
+            name: "pcli2_asset_list".to_string(),
+            mime_type: "text/plain".to_string(),
+            text: Arc::new("a,b\n1,2".to_string()),
+            tenant: Some("castings".to_string()),
+            blob: false,
+        };
+        store.put_resource(&resource).await.unwrap();
+        let fetched = store.get_resource(&resource.uri).await.unwrap().unwrap();
+        assert_eq!(fetched.text.as_str(), "a,b\n1,2");
+        assert_eq!(fetched.tenant.as_deref(), Some("castings"));
+        assert!(
+            store
+                .get_resource("pcli2://results/9-9")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let listed = store.list_resources().await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].name, "pcli2_asset_list");
+    }
+
+    #[tokio::test]
+    async fn test_replicas_share_offloaded_results() {
+        let url = fake_redis().await;
+        let config = SharedStoreConfig::default();
+        let first = ResourceStore::with_shared(SharedStore::new(&config, &url).unwrap());
+        let second = ResourceStore::with_shared(SharedStore::new(&config, &url).unwrap());
+
+        let resource = first.insert("pcli2_asset_list", "text/plain", "x".repeat(64), None);
+        let result = json!({
+            "content": [{ "type": "resource_link", "uri": resource.uri }]
+        });
+        first.publish(&result).await;
+
+        assert!(second.get(&resource.uri).is_none());
+        let fetched = second.fetch(&resource.uri).await.unwrap();
+        assert_eq!(fetched.text.len(), 64);
+        assert!(second.get(&resource.uri).is_some());
+        assert_eq!(second.list_all().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_replicas_share_sessions() {
+        let url = fake_redis().await;
+        let config = SharedStoreConfig::default();
+        let first = SessionRegistry::with_shared(SharedStore::new(&config, &url).unwrap());
+        let second = SessionRegistry::with_shared(SharedStore::new(&config, &url).unwrap());
+
+        let session = first.issue("analyst").await;
+        assert!(second.check(&session, "analyst").await.is_ok());
+        assert!(second.check(&session, "anonymous").await.is_err());
+        assert!(second.check("made-up", "analyst").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_replicas_share_nonces() {
+        let url = fake_redis().await;
+        let store = SharedStoreConfig::default();
+        let first = NonceCache::with_shared(SharedStore::new(&store, &url).unwrap());
+        let second = NonceCache::with_shared(SharedStore::new(&store, &url).unwrap());
+        let config = ReplayConfig::default();
+        let mut headers = HeaderMap::new();
+        let now = Utc::now().timestamp().to_string();
+        headers.insert("x-request-timestamp", now.parse().unwrap());
+        headers.insert("x-request-nonce", "n1".parse().unwrap());
+
+        assert!(first.check(&config, Some("a"), &headers).await.is_ok());
+        let err = second
+            .check(&config, Some("a"), &headers)
+            .await
+            .unwrap_err();
+        assert!(err.contains("already used"), "{}", err);
+        assert!(second.check(&config, Some("b"), &headers).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_replicas_share_quotas() {
+        let url = fake_redis().await;
+        let config = SharedStoreConfig::default();
+        let rules = HashMap::from([(
+            "calls".to_string(),
+            QuotaRule {
+                tools: Vec::new(),
+                max_calls: 2,
+                window_secs: Some(3600),
+            },
+        )]);
+        let tracker = |scope: &str| {
+            QuotaTracker::new(&rules).with_shared(SharedStore::new(&config, &url).unwrap(), scope)
+        };
+        let (first, second) = (tracker("session"), tracker("session"));
+        let tool = "pcli2_asset_get";
+
+        first.admit("a", tool).await.unwrap();
+        second.admit("a", tool).await.unwrap();
+        let err = first.admit("a", tool).await.unwrap_err();
+        assert!(err.contains("Quota 'calls' exhausted: 2 of 2"));
+        assert!(second.admit("b", tool).await.is_ok());
+        // A rejected call is not counted.
+        assert_eq!(second.status("a").await["quotas"][0]["used"], 2);
+        // Trackers of other scopes count apart.
+        assert!(tracker("policy:analyst").admit("a", tool).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_replicas_share_cached_results() {
+        let url = fake_redis().await;
+        let store = SharedStoreConfig::default();
+        let config = CacheConfig {
+            max_age_secs: Some(60),
+            ..CacheConfig::default()
+        };
+        let cache = || {
+            ResultCache::open(&config, false)
+                .unwrap()
+                .with_shared(SharedStore::new(&store, &url).unwrap())
+        };
+        let (first, second) = (cache(), cache());
+        let tool = "pcli2_asset_get";
+        let args = json!({ "tenant": "acme", "path": "/Root/A/a.stl" });
+        let sibling = json!({ "tenant": "acme", "path": "/Root/B/b.stl" });
+        let other = json!({ "tenant": "other", "path": "/Root/A/a.stl" });
+        let result = json!({ "content": [{ "type": "text", "text": "{}" }] });
+
+        for args in [&args, &sibling, &other] {
+            first.put(tool, None, args, &result).await;
+        }
+        let hit = second.get(tool, None, &args).await.unwrap();
+        assert_eq!(hit["_meta"]["cache"]["source"], "shared");
+        assert!(second.get(tool, None, &args).await.is_some());
+
+        // A change on one replica drops what every replica cached in its
+        // folder, and nothing in other folders or tenants.
+        let change = json!({ "tenant": "acme", "path": "/Root/A/a.stl" });
+        first.invalidate("pcli2_asset_reprocess", &change).await;
+        assert!(second.get(tool, None, &args).await.is_none());
+        assert!(first.get(tool, None, &args).await.is_none());
+        assert!(second.get(tool, None, &sibling).await.is_some());
+        assert!(second.get(tool, None, &other).await.is_some());
+
+        // One naming no folder drops the whole tenant.
+        let change = json!({ "tenant": "acme", "uuid": "u-1" });
+        first.invalidate("pcli2_asset_reprocess", &change).await;
+        assert!(second.get(tool, None, &sibling).await.is_none());
+        assert!(second.get(tool, None, &other).await.is_some());
+
+        // Clearing drops everything.
+        first.clear().await;
+        assert!(second.get(tool, None, &other).await.is_none());
+    }
+}
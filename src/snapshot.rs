@@ -0,0 +1,365 @@
+use crate::{
+    AppState,
+    content::{raw_output_block, summary_block, tool_result},
+    install::managed_home,
+    pcli::run_pcli2_list,
+};
+use chrono::Utc;
+use serde_json::{Map, Value, json};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::PathBuf,
+};
+
+pub const SNAPSHOT_TOOL: &str = "pcli2_folder_snapshot";
+pub const DIFF_TOOL: &str = "pcli2_folder_diff";
+const MAX_SNAPSHOT_NAME_LEN: usize = 64;
+
+fn snapshot_dir(state: &AppState) -> Result<PathBuf, String> {
+    match &state.config.snapshots.dir {
+        Some(dir) => Ok(dir.clone()),
+        None => managed_home()
+            .map(|home| home.join("snapshots"))
+            .ok_or_else(|| "Cannot find a home directory; set 'snapshots.dir'".to_string()),
+    }
+}
+
+fn validate_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_SNAPSHOT_NAME_LEN
+        && name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid snapshot name '{}': use up to {} letters, digits, '-', '_', or '.', starting with a letter or digit",
+            name, MAX_SNAPSHOT_NAME_LEN
+        ))
+    }
+}
+
+fn snapshot_path(state: &AppState, name: &str) -> Result<PathBuf, String> {
+    validate_name(name)?;
+    Ok(snapshot_dir(state)?.join(format!("{}.json", name)))
+}
+
+fn required_str<'a>(args: &'a Value, key: &str) -> Result<&'a str, String> {
+    args.get(key)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| format!("Missing required argument: '{}'", key))
+}
+
+/// Folder selection, tenant, and asset list of a snapshot or the live folder.
+struct Listing {
+    label: String,
+    folder: Value,
+    assets: Vec<Value>,
+}
+
+/// Lists the assets of the folder in `folder` (`folder_uuid`/`folder_path`
+/// and optional `tenant`) with their metadata.
+pub(crate) async fn list_assets(folder: &Value) -> Result<Vec<Value>, String> {
+    list_folder_assets(folder, true).await
+}
+
+/// Lists the assets of the folder in `folder`, with their metadata when
+/// `metadata` is set.
+pub(crate) async fn list_folder_assets(
+    folder: &Value,
+    metadata: bool,
+) -> Result<Vec<Value>, String> {
+    let mut args = folder.clone();
+    args["metadata"] = json!(metadata);
+    args["format"] = json!("json");
+    let output = run_pcli2_list("asset", args)
+        .await
+        .map_err(|message| format!("pcli2 asset list failed: {}", message))?;
+    let parsed: Value = serde_json::from_str(&output)
+        .map_err(|err| format!("pcli2 asset list returned invalid JSON: {}", err))?;
+    match parsed {
+        Value::Array(items) => Ok(items),
+        Value::Object(mut obj) if obj.get("items").is_some_and(Value::is_array) => {
+            Ok(match obj.remove("items") {
+                Some(Value::Array(items)) => items,
+                _ => Vec::new(),
+            })
+        }
+        _ => Err("pcli2 asset list returned an unexpected JSON shape".to_string()),
+    }
+}
+
+pub(crate) fn folder_selection(args: &Value) -> Result<Value, String> {
+    let mut folder = Map::new();
+    for key in ["tenant", "folder_uuid", "folder_path"] {
+        if let Some(value) = args.get(key).and_then(|v| v.as_str()) {
+            folder.insert(key.to_string(), json!(value));
+        }
+    }
+    if !folder.contains_key("folder_uuid") && !folder.contains_key("folder_path") {
+        return Err(
+            "Missing required argument: provide either 'folder_uuid' or 'folder_path'".to_string(),
+        );
+    }
+    Ok(Value::Object(folder))
+}
+
+fn load_snapshot(state: &AppState, name: &str) -> Result<Listing, String> {
+    let path = snapshot_path(state, name)?;
+    let raw = fs::read_to_string(&path).map_err(|_| {
+        let known = list_snapshots(state).unwrap_or_default();
+        if known.is_empty() {
+            format!("Snapshot '{}' not found; no snapshots exist yet", name)
+        } else {
+            format!(
+                "Snapshot '{}' not found; available: {}",
+                name,
+                known.join(", ")
+            )
+        }
+    })?;
+    let snapshot: Value = serde_json::from_str(&raw)
+        .map_err(|err| format!("Snapshot '{}' is corrupt: {}", name, err))?;
+    Ok(Listing {
+        label: format!("snapshot '{}'", name),
+        folder: snapshot["folder"].clone(),
+        assets: snapshot["assets"].as_array().cloned().unwrap_or_default(),
+    })
+}
+
+fn list_snapshots(state: &AppState) -> Result<Vec<String>, String> {
+    let dir = snapshot_dir(state)?;
+    let mut names: Vec<String> = fs::read_dir(&dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| {
+                    entry
+                        .file_name()
+                        .to_str()?
+                        .strip_suffix(".json")
+                        .map(str::to_string)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    Ok(names)
+}
+
+/// `pcli2_folder_snapshot`: stores the folder's asset listing and metadata
+/// under `name`.
+pub async fn take_snapshot(state: &AppState, args: Value) -> Result<Value, String> {
+    let name = required_str(&args, "name")?;
+    let path = snapshot_path(state, name)?;
+    let overwrite = args
+        .get("overwrite")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if path.exists() && !overwrite {
+        return Err(format!(
+            "Snapshot '{}' already exists; pass overwrite: true to replace it",
+            name
+        ));
+    }
+    let folder = folder_selection(&args)?;
+    let assets = list_assets(&folder).await?;
+    let created_at = Utc::now().to_rfc3339();
+    let snapshot = json!({
+        "name": name,
+        "created_at": created_at,
+        "folder": folder,
+        "assets": assets,
+    });
+    let dir = snapshot_dir(state)?;
+    fs::create_dir_all(&dir)
+        .map_err(|err| format!("Failed to create {}: {}", dir.display(), err))?;
+    let text = serde_json::to_string_pretty(&snapshot)
+        .map_err(|err| format!("Failed to encode snapshot: {}", err))?;
+    // Written next to the target and renamed so a failed write never leaves a
+    // truncated snapshot behind.
+    let staged = dir.join(format!(".{}.json.partial", name));
+    fs::write(&staged, text)
+        .and_then(|_| fs::rename(&staged, &path))
+        .map_err(|err| format!("Failed to write {}: {}", path.display(), err))?;
+    let summary = json!({
+        "name": name,
+        "created_at": created_at,
+        "folder": folder,
+        "assets": assets.len(),
+    });
+    Ok(tool_result(vec![
+        summary_block(format!(
+            "Stored snapshot '{}' with {} assets.",
+            name,
+            assets.len()
+        )),
+        raw_output_block(summary.to_string()),
+    ]))
+}
+
+/// `pcli2_folder_diff`: compares snapshot `from` with snapshot `to`, or with
+/// the live folder when `to` is omitted.
+pub async fn diff_snapshots(state: &AppState, args: Value) -> Result<Value, String> {
+    let from = required_str(&args, "from")?;
+    let to = args.get("to").and_then(|v| v.as_str());
+    validate_name(from)?;
+    to.map(validate_name).transpose()?;
+    let before = load_snapshot(state, from)?;
+    let after = match to {
+        Some(name) => load_snapshot(state, name)?,
+        None => Listing {
+            label: "live folder".to_string(),
+            assets: list_assets(&before.folder).await?,
+            folder: before.folder.clone(),
+        },
+    };
+    let diff = diff_assets(&before.assets, &after.assets);
+    let headline = format!(
+        "{} → {}: {} added, {} removed, {} changed, {} unchanged.",
+        before.label,
+        after.label,
+        diff["added"].as_array().map_or(0, Vec::len),
+        diff["removed"].as_array().map_or(0, Vec::len),
+        diff["changed"].as_array().map_or(0, Vec::len),
+        diff["unchanged"]
+    );
+    let mut report = json!({ "from": before.label, "to": after.label });
+    if let (Some(report), Value::Object(diff)) = (report.as_object_mut(), diff) {
+        report.extend(diff);
+    }
+    Ok(tool_result(vec![
+        summary_block(headline),
+        raw_output_block(report.to_string()),
+    ]))
+}
+
+/// Identity of an asset across listings: its UUID, else its path.
+fn asset_key(asset: &Value) -> Option<String> {
+    ["uuid", "path"]
+        .iter()
+        .find_map(|key| asset.get(*key).and_then(|v| v.as_str()))
+        .map(str::to_string)
+}
+
+/// Top-level fields, with nested objects such as metadata flattened to
+/// `field.key`.
+fn flatten_fields(asset: &Value) -> BTreeMap<String, Value> {
+    let mut fields = BTreeMap::new();
+    for (key, value) in asset.as_object().into_iter().flatten() {
+        match value {
+            Value::Object(nested) => {
+                for (inner, value) in nested {
+                    fields.insert(format!("{}.{}", key, inner), value.clone());
+                }
+            }
+            value => {
+                fields.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    fields
+}
+
+fn summarize(asset: &Value) -> Value {
+    json!({
+        "uuid": asset.get("uuid").cloned().unwrap_or(Value::Null),
+        "path": asset.get("path").cloned().unwrap_or(Value::Null),
+        "name": asset.get("name").cloned().unwrap_or(Value::Null),
+    })
+}
+
+pub(crate) fn diff_assets(before: &[Value], after: &[Value]) -> Value {
+    let index = |assets: &[Value]| -> BTreeMap<String, Value> {
+        assets
+            .iter()
+            .filter_map(|asset| asset_key(asset).map(|key| (key, asset.clone())))
+            .collect()
+    };
+    let (before, after) = (index(before), index(after));
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    let mut changed = Vec::new();
+    let mut unchanged = 0;
+    for (key, asset) in &after {
+        if !before.contains_key(key) {
+            added.push(summarize(asset));
+        }
+    }
+    for (key, old) in &before {
+        let Some(new) = after.get(key) else {
+            removed.push(summarize(old));
+            continue;
+        };
+        let (old_fields, new_fields) = (flatten_fields(old), flatten_fields(new));
+        let names: BTreeSet<&String> = old_fields.keys().chain(new_fields.keys()).collect();
+        let mut changes = Map::new();
+        for name in names {
+            let (was, now) = (old_fields.get(name), new_fields.get(name));
+            if was != now {
+                changes.insert(
+                    name.clone(),
+                    json!({ "before": was.cloned().unwrap_or(Value::Null), "after": now.cloned().unwrap_or(Value::Null) }),
+                );
+            }
+        }
+        if changes.is_empty() {
+            unchanged += 1;
+        } else {
+            let mut entry = summarize(new);
+            entry["changes"] = Value::Object(changes);
+            changed.push(entry);
+        }
+    }
+    json!({
+        "added": added,
+        "removed": removed,
+        "changed": changed,
+        "unchanged": unchanged,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("weekly-2026.10.12").is_ok());
+        assert!(validate_name("").is_err());
+        assert!(validate_name("../etc/passwd").is_err());
+        assert!(validate_name(".hidden").is_err());
+        assert!(validate_name("--help").is_err());
+        assert!(validate_name(&"a".repeat(65)).is_err());
+    }
+
+    #[test]
+    fn test_diff_assets() {
+        let before = vec![
+            json!({ "uuid": "1", "path": "/R/a.stl", "name": "a.stl", "metadata": { "Weight": 1.0 } }),
+            json!({ "uuid": "2", "path": "/R/b.stl", "name": "b.stl" }),
+            json!({ "uuid": "3", "path": "/R/c.stl", "name": "c.stl" }),
+        ];
+        let after = vec![
+            json!({ "uuid": "1", "path": "/R/a.stl", "name": "a.stl", "metadata": { "Weight": 1.5, "Supplier": "Acme" } }),
+            json!({ "uuid": "3", "path": "/R/c.stl", "name": "c.stl" }),
+            json!({ "uuid": "4", "path": "/R/d.stl", "name": "d.stl" }),
+        ];
+        let diff = diff_assets(&before, &after);
+        assert_eq!(diff["added"][0]["uuid"], "4");
+        assert_eq!(diff["removed"][0]["path"], "/R/b.stl");
+        assert_eq!(diff["unchanged"], 1);
+        let changes = &diff["changed"][0]["changes"];
+        assert_eq!(
+            changes["metadata.Weight"],
+            json!({ "before": 1.0, "after": 1.5 })
+        );
+        assert_eq!(
+            changes["metadata.Supplier"],
+            json!({ "before": null, "after": "Acme" })
+        );
+    }
+}
@@ -0,0 +1,373 @@
+use crate::{
+    content::{raw_output_block, raw_output_result, summary_block, tool_result},
+    pcli::{
+        DEFAULT_FAN_OUT_CONCURRENCY, MAX_FAN_OUT_INPUTS, in_current_execution, parse_string_list,
+        validate_range_u64,
+    },
+};
+use serde_json::{Value, json};
+use std::{collections::HashMap, future::Future, sync::Arc};
+use tokio::{sync::Semaphore, task::JoinSet};
+
+/// Runs a folder match once per `folder_path` instead of once for all.
+pub const SPLIT_FOLDERS_ARG: &str = "split_folders";
+/// How many of the per-folder runs go at once.
+pub const SPLIT_CONCURRENCY_ARG: &str = "split_concurrency";
+/// Fields naming the two assets of a match row; rows for the same pair from
+/// different folders are the same match.
+const REFERENCE_KEYS: [&str; 2] = ["reference_asset_uuid", "reference_asset_path"];
+const CANDIDATE_KEYS: [&str; 2] = ["candidate_asset_uuid", "candidate_asset_path"];
+const SCORE_KEYS: [&str; 2] = ["match_percentage", "score"];
+
+/// Whether a folder match call asks for one run per folder.
+pub fn wants_split(args: &Value) -> bool {
+    args.get(SPLIT_FOLDERS_ARG)
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+        && args.get("folder_path").is_some_and(Value::is_array)
+}
+
+fn pair_key(row: &Value) -> String {
+    let first = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|key| row.get(*key).and_then(Value::as_str))
+    };
+    match (first(&REFERENCE_KEYS), first(&CANDIDATE_KEYS)) {
+        (Some(reference), Some(candidate)) => format!("{}\n{}", reference, candidate),
+        _ => row.to_string(),
+    }
+}
+
+fn score(row: &Value) -> f64 {
+    SCORE_KEYS
+        .iter()
+        .find_map(|key| row.get(*key).and_then(Value::as_f64))
+        .unwrap_or(f64::NEG_INFINITY)
+}
+
+/// Merges the match rows of each folder's run, keeping one row per asset
+/// pair (the best scored) tagged with the folder whose run found it.
+/// Returns the rows and how many duplicates were dropped.
+fn merge_matches(runs: &[(String, Vec<Value>)]) -> (Vec<Value>, usize) {
+    let mut merged: Vec<Value> = Vec::new();
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut duplicates = 0;
+    for (folder, rows) in runs {
+        for row in rows {
+            let mut row = row.clone();
+            if let Some(obj) = row.as_object_mut() {
+                obj.insert("folder_path".to_string(), json!(folder));
+            }
+            match seen.get(&pair_key(&row)) {
+                Some(&index) => {
+                    duplicates += 1;
+                    if score(&row) > score(&merged[index]) {
+                        merged[index] = row;
+                    }
+                }
+                None => {
+                    seen.insert(pair_key(&row), merged.len());
+                    merged.push(row);
+                }
+            }
+        }
+    }
+    (merged, duplicates)
+}
+
+/// The match rows of one run's JSON output.
+pub(crate) fn match_rows(output: &str) -> Result<Vec<Value>, String> {
+    match serde_json::from_str::<Value>(output) {
+        Ok(Value::Array(rows)) => Ok(rows),
+        Ok(Value::Object(mut obj)) => match obj.remove("matches") {
+            Some(Value::Array(rows)) => Ok(rows),
+            _ => Ok(vec![Value::Object(obj)]),
+        },
+        Ok(Value::Null) => Ok(Vec::new()),
+        _ => Err("pcli2 did not return JSON matches".to_string()),
+    }
+}
+
+/// Runs `run` once per folder of `folder_path`, up to `split_concurrency`
+/// at a time, and returns each folder with its outcome, in order.
+async fn run_each_folder<F, Fut>(
+    args: &Value,
+    run: &F,
+) -> Result<Vec<(String, Result<String, String>)>, String>
+where
+    F: Fn(Value) -> Fut,
+    Fut: Future<Output = Result<String, String>> + Send + 'static,
+{
+    validate_range_u64(args, SPLIT_CONCURRENCY_ARG, 1, 10)?;
+    let folders = parse_string_list(args, "folder_path");
+    if folders.is_empty() {
+        return Err("Missing required argument: 'folder_path'".to_string());
+    }
+    if folders.len() > MAX_FAN_OUT_INPUTS {
+        return Err(format!(
+            "Too many folders: {} provided, at most {} are allowed per call",
+            folders.len(),
+            MAX_FAN_OUT_INPUTS
+        ));
+    }
+    let concurrency = args
+        .get(SPLIT_CONCURRENCY_ARG)
+        .and_then(Value::as_u64)
+        .unwrap_or(DEFAULT_FAN_OUT_CONCURRENCY as u64) as usize;
+    let mut args = args.clone();
+    if let Some(obj) = args.as_object_mut() {
+        obj.remove(SPLIT_FOLDERS_ARG);
+        obj.remove(SPLIT_CONCURRENCY_ARG);
+    }
+
+    let semaphore = Arc::new(Semaphore::new(concurrency));
+    let mut tasks = JoinSet::new();
+    for (index, folder) in folders.iter().enumerate() {
+        let mut single = args.clone();
+        single["folder_path"] = json!(folder);
+        let semaphore = semaphore.clone();
+        let fut = run(single);
+        tasks.spawn(in_current_execution(async move {
+            let _permit = semaphore.acquire_owned().await;
+            (index, fut.await)
+        }));
+    }
+    let mut outcomes: Vec<Option<Result<String, String>>> = vec![None; folders.len()];
+    while let Some(joined) = tasks.join_next().await {
+        let (index, outcome) = joined.map_err(|err| format!("Folder task failed: {}", err))?;
+        outcomes[index] = Some(outcome);
+    }
+    Ok(folders
+        .into_iter()
+        .zip(outcomes)
+        .map(|(folder, outcome)| {
+            (
+                folder,
+                outcome.unwrap_or_else(|| Err("No result produced".to_string())),
+            )
+        })
+        .collect())
+}
+
+/// Refuses CSV and asks each run for JSON, which the merge needs.
+fn require_json(args: &mut Value) -> Result<(), String> {
+    if args.get("format").and_then(Value::as_str) == Some("csv") {
+        return Err(format!(
+            "Invalid argument 'format': '{}' merges JSON output; drop 'format' or set it to json",
+            SPLIT_FOLDERS_ARG
+        ));
+    }
+    args["format"] = json!("json");
+    Ok(())
+}
+
+fn render(headline: String, merged: Value) -> Result<Value, String> {
+    let text = serde_json::to_string_pretty(&merged)
+        .map_err(|err| format!("Failed to render results: {}", err))?;
+    Ok(tool_result(vec![
+        summary_block(headline),
+        raw_output_block(text),
+    ]))
+}
+
+/// The merged result of per-folder match runs, led by `lead`.
+fn merged_matches(
+    lead: &str,
+    outcomes: Vec<(String, Result<String, String>)>,
+) -> Result<Value, String> {
+    let mut runs = Vec::new();
+    let mut results = Vec::with_capacity(outcomes.len());
+    for (folder, outcome) in outcomes {
+        match outcome.and_then(|output| match_rows(&output)) {
+            Ok(rows) => {
+                results.push(json!({ "folder_path": folder, "status": "ok", "count": rows.len() }));
+                runs.push((folder, rows));
+            }
+            Err(message) => {
+                results.push(json!({ "folder_path": folder, "status": "error", "error": message }))
+            }
+        }
+    }
+    let (matches, duplicates) = merge_matches(&runs);
+    let succeeded = runs.len();
+    let headline = format!(
+        "{}: {} succeeded, {} failed; {} matches after dropping {} duplicates.",
+        lead,
+        succeeded,
+        results.len() - succeeded,
+        matches.len(),
+        duplicates
+    );
+    render(
+        headline,
+        json!({
+            "total": results.len(),
+            "succeeded": succeeded,
+            "failed": results.len() - succeeded,
+            "duplicates": duplicates,
+            "results": results,
+            "matches": matches
+        }),
+    )
+}
+
+/// The per-folder status of runs whose output is not merged: each folder's
+/// output, or why it failed.
+fn folder_statuses(
+    lead: &str,
+    outcomes: Vec<(String, Result<String, String>)>,
+) -> Result<Value, String> {
+    let mut succeeded = 0;
+    let results: Vec<Value> = outcomes
+        .into_iter()
+        .map(|(folder, outcome)| match outcome {
+            Ok(output) => {
+                succeeded += 1;
+                let output =
+                    serde_json::from_str::<Value>(&output).unwrap_or(Value::String(output));
+                json!({ "folder_path": folder, "status": "ok", "output": output })
+            }
+            Err(message) => json!({ "folder_path": folder, "status": "error", "error": message }),
+        })
+        .collect();
+    let headline = format!(
+        "{}: {} succeeded, {} failed.",
+        lead,
+        succeeded,
+        results.len() - succeeded
+    );
+    render(
+        headline,
+        json!({
+            "total": results.len(),
+            "succeeded": succeeded,
+            "failed": results.len() - succeeded,
+            "results": results
+        }),
+    )
+}
+
+/// Runs a folder match once per folder of `folder_path`, up to
+/// `split_concurrency` at a time, and merges the matches. A folder whose run
+/// fails is reported in `results` while the others' matches are kept.
+pub async fn run_split_folder_match<F, Fut>(mut args: Value, run: F) -> Result<Value, String>
+where
+    F: Fn(Value) -> Fut,
+    Fut: Future<Output = Result<String, String>> + Send + 'static,
+{
+    require_json(&mut args)?;
+    let outcomes = run_each_folder(&args, &run).await?;
+    let lead = format!("Matched {} folders in separate runs", outcomes.len());
+    merged_matches(&lead, outcomes)
+}
+
+/// Runs a tool over all folders of `folder_path` at once and, when that
+/// fails for a list of folders, once per folder, so the result says which
+/// folders worked and which did not instead of failing as a whole. Match
+/// output is merged as with `split_folders` unless CSV was asked for.
+/// Fails only when every folder fails.
+pub async fn run_folders_with_fallback<F, Fut>(
+    args: Value,
+    label: &str,
+    merge: bool,
+    run: F,
+) -> Result<Value, String>
+where
+    F: Fn(Value) -> Fut,
+    Fut: Future<Output = Result<String, String>> + Send + 'static,
+{
+    let folders = parse_string_list(&args, "folder_path");
+    let combined = match run(args.clone()).await {
+        Ok(output) => return Ok(raw_output_result(output)),
+        Err(message) if folders.len() < 2 || folders.len() > MAX_FAN_OUT_INPUTS => {
+            return Err(format!("{} failed: {}", label, message));
+        }
+        Err(message) => message,
+    };
+    let mut args = args;
+    let merge = merge && require_json(&mut args).is_ok();
+    let outcomes = run_each_folder(&args, &run).await?;
+    if outcomes.iter().all(|(_, outcome)| outcome.is_err()) {
+        let errors: Vec<String> = outcomes
+            .into_iter()
+            .filter_map(|(folder, outcome)| {
+                outcome
+                    .err()
+                    .map(|message| format!("{}: {}", folder, message))
+            })
+            .collect();
+        return Err(format!(
+            "{} failed for every folder: {}",
+            label,
+            errors.join("; ")
+        ));
+    }
+    let lead = format!(
+        "{} failed for the {} folders together ({}), so each folder ran on its own",
+        label,
+        outcomes.len(),
+        combined.trim()
+    );
+    if merge {
+        merged_matches(&lead, outcomes)
+    } else {
+        folder_statuses(&lead, outcomes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(reference: &str, candidate: &str, score: f64) -> Value {
+        json!({
+            "reference_asset_path": reference,
+            "candidate_asset_path": candidate,
+            "match_percentage": score
+        })
+    }
+
+    #[test]
+    fn test_matches_are_merged_and_deduplicated() {
+        let runs = vec![
+            (
+                "/Root/A".to_string(),
+                vec![
+                    row("/Root/A/1", "/Root/B/2", 91.0),
+                    row("/Root/A/1", "/Root/A/3", 85.0),
+                ],
+            ),
+            (
+                "/Root/B".to_string(),
+                vec![
+                    row("/Root/A/1", "/Root/B/2", 93.0),
+                    row("/Root/B/2", "/Root/A/1", 93.0),
+                ],
+            ),
+        ];
+        let (matches, duplicates) = merge_matches(&runs);
+        assert_eq!(duplicates, 1);
+        assert_eq!(matches.len(), 3);
+        // The better score wins and names the folder that found it.
+        assert_eq!(matches[0]["match_percentage"], 93.0);
+        assert_eq!(matches[0]["folder_path"], "/Root/B");
+        assert_eq!(matches[1]["folder_path"], "/Root/A");
+    }
+
+    #[test]
+    fn test_split_is_opt_in_for_folder_lists() {
+        assert!(wants_split(
+            &json!({ "folder_path": ["/A", "/B"], "split_folders": true })
+        ));
+        assert!(!wants_split(&json!({ "folder_path": ["/A", "/B"] })));
+        assert!(!wants_split(
+            &json!({ "folder_path": "/A", "split_folders": true })
+        ));
+        assert_eq!(match_rows("[]").unwrap().len(), 0);
+        assert_eq!(
+            match_rows(r#"{"matches": [{"score": 1}]}"#).unwrap().len(),
+            1
+        );
+        assert!(match_rows("a,b\n1,2").is_err());
+    }
+}
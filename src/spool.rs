@@ -0,0 +1,359 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    env,
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+};
+
+/// Output kept in memory before a spool moves to a temporary file.
+pub const SPOOL_MEMORY_BYTES: usize = 256 * 1024;
+/// Lines kept from the start and from the end of a run's transcript.
+pub const TRANSCRIPT_HEAD_LINES: usize = 50;
+pub const TRANSCRIPT_TAIL_LINES: usize = 150;
+/// Longer transcript lines are cut off.
+pub const TRANSCRIPT_LINE_BYTES: usize = 2000;
+
+static SPOOL_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+struct SpillFile {
+    path: PathBuf,
+    file: File,
+}
+
+/// Output of a running `pcli2`, held in memory up to a small window and
+/// written to a temporary file beyond it. The file is removed on drop.
+pub struct Spool {
+    window: usize,
+    len: usize,
+    /// All output while it fits in the window; empty once spilled.
+    memory: Vec<u8>,
+    spill: Option<SpillFile>,
+}
+
+impl Spool {
+    pub fn new(window: usize) -> Self {
+        Self {
+            window,
+            len: 0,
+            memory: Vec::new(),
+            spill: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_spilled(&self) -> bool {
+        self.spill.is_some()
+    }
+
+    pub async fn write(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.len += bytes.len();
+        if self.spill.is_none() && self.memory.len() + bytes.len() <= self.window {
+            self.memory.extend_from_slice(bytes);
+            return Ok(());
+        }
+        if self.spill.is_none() {
+            let mut spill = create_spill_file().await?;
+            write_spill(&mut spill, &self.memory).await?;
+            self.memory = Vec::new();
+            self.spill = Some(spill);
+        }
+        match self.spill.as_mut() {
+            Some(spill) => write_spill(spill, bytes).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Reads the whole output back, from memory or from the spill file.
+    pub async fn into_bytes(mut self) -> Result<Vec<u8>, String> {
+        let Some(spill) = self.spill.as_mut() else {
+            return Ok(std::mem::take(&mut self.memory));
+        };
+        spill
+            .file
+            .flush()
+            .await
+            .map_err(|err| format!("Failed to flush pcli2 output spool: {}", err))?;
+        tokio::fs::read(&spill.path)
+            .await
+            .map_err(|err| format!("Failed to read pcli2 output spool: {}", err))
+    }
+
+    /// Reads the whole output back as text, replacing invalid UTF-8.
+    pub async fn into_string(self) -> Result<String, String> {
+        Ok(lossy_string(self.into_bytes().await?))
+    }
+}
+
+impl Drop for Spool {
+    fn drop(&mut self) {
+        if let Some(spill) = &self.spill {
+            let _ = std::fs::remove_file(&spill.path);
+        }
+    }
+}
+
+async fn create_spill_file() -> Result<SpillFile, String> {
+    let path = env::temp_dir().join(format!(
+        "pcli2-mcp-spool-{}-{}",
+        std::process::id(),
+        SPOOL_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let mut options = OpenOptions::new();
+    options.read(true).write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let file = options
+        .open(&path)
+        .await
+        .map_err(|err| format!("Failed to create pcli2 output spool: {}", err))?;
+    Ok(SpillFile { path, file })
+}
+
+async fn write_spill(spill: &mut SpillFile, bytes: &[u8]) -> Result<(), String> {
+    spill
+        .file
+        .write_all(bytes)
+        .await
+        .map_err(|err| format!("Failed to write pcli2 output spool: {}", err))
+}
+
+/// Converts output to text without copying when it is valid UTF-8.
+pub fn lossy_string(bytes: Vec<u8>) -> String {
+    String::from_utf8(bytes)
+        .unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into_owned())
+}
+
+struct TranscriptLine {
+    elapsed: Duration,
+    stream: &'static str,
+    text: String,
+}
+
+#[derive(Default)]
+struct TranscriptInner {
+    head: Vec<TranscriptLine>,
+    tail: VecDeque<TranscriptLine>,
+    omitted: usize,
+    partial: HashMap<&'static str, Vec<u8>>,
+}
+
+/// stdout and stderr lines of one run in the order they were read, each with
+/// the time since the run started. Keeps the first and last lines only.
+pub struct Transcript {
+    started: Instant,
+    inner: Mutex<TranscriptInner>,
+}
+
+impl Transcript {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            inner: Mutex::default(),
+        }
+    }
+
+    /// Adds output read from `stream`; incomplete lines wait for the rest.
+    pub fn record(&self, stream: &'static str, bytes: &[u8]) {
+        let elapsed = self.started.elapsed();
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let mut partial = inner.partial.remove(stream).unwrap_or_default();
+        for piece in bytes.split_inclusive(|byte| *byte == b'\n') {
+            if partial.len() < TRANSCRIPT_LINE_BYTES {
+                partial.extend_from_slice(piece);
+            }
+            if piece.ends_with(b"\n") {
+                inner.push(elapsed, stream, std::mem::take(&mut partial));
+            }
+        }
+        inner.partial.insert(stream, partial);
+    }
+
+    /// Adds the last line of `stream` when it did not end with a newline.
+    pub fn finish(&self, stream: &'static str) {
+        let elapsed = self.started.elapsed();
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(partial) = inner.partial.remove(stream)
+            && !partial.is_empty()
+        {
+            inner.push(elapsed, stream, partial);
+        }
+    }
+
+    /// One `[+1.234s stderr] text` line per captured line.
+    pub fn render(&self) -> String {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let line = |line: &TranscriptLine| {
+            format!(
+                "[+{:.3}s {}] {}",
+                line.elapsed.as_secs_f64(),
+                line.stream,
+                line.text
+            )
+        };
+        let mut rendered: Vec<String> = inner.head.iter().map(line).collect();
+        if inner.omitted > 0 {
+            rendered.push(format!("[… {} lines omitted …]", inner.omitted));
+        }
+        rendered.extend(inner.tail.iter().map(line));
+        rendered.join("\n")
+    }
+}
+
+impl Default for Transcript {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TranscriptInner {
+    fn push(&mut self, elapsed: Duration, stream: &'static str, mut bytes: Vec<u8>) {
+        let cut = bytes.len() > TRANSCRIPT_LINE_BYTES;
+        bytes.truncate(TRANSCRIPT_LINE_BYTES);
+        let mut text = String::from_utf8_lossy(&bytes).trim_end().to_string();
+        if cut {
+            text.push('…');
+        }
+        let line = TranscriptLine {
+            elapsed,
+            stream,
+            text,
+        };
+        if self.head.len() < TRANSCRIPT_HEAD_LINES {
+            self.head.push(line);
+            return;
+        }
+        self.tail.push_back(line);
+        if self.tail.len() > TRANSCRIPT_TAIL_LINES {
+            self.tail.pop_front();
+            self.omitted += 1;
+        }
+    }
+}
+
+/// Streams `reader` into a spool and its lines into `transcript`, failing
+/// once more than `limit` bytes arrive.
+pub async fn spool_output<R: AsyncRead + Unpin>(
+    mut reader: R,
+    limit: usize,
+    label: &'static str,
+    transcript: Arc<Transcript>,
+) -> Result<Spool, String> {
+    let mut spool = Spool::new(SPOOL_MEMORY_BYTES);
+    let mut chunk = vec![0u8; 64 * 1024];
+    loop {
+        let read = reader
+            .read(&mut chunk)
+            .await
+            .map_err(|err| format!("Failed to read pcli2 {}: {}", label, err))?;
+        if read == 0 {
+            transcript.finish(label);
+            break;
+        }
+        transcript.record(label, &chunk[..read]);
+        if spool.len() + read > limit {
+            return Err(format!(
+                "pcli2 {} exceeded maximum output size of {} bytes",
+                label, limit
+            ));
+        }
+        spool.write(&chunk[..read]).await?;
+    }
+    Ok(spool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_small_output_stays_in_memory() {
+        let transcript = Arc::new(Transcript::new());
+        let spool = spool_output(&b"hello\n"[..], 1024, "stdout", transcript.clone())
+            .await
+            .unwrap();
+        assert!(!spool.is_spilled());
+        assert_eq!(spool.into_string().await.unwrap(), "hello\n");
+        assert!(transcript.render().ends_with("stdout] hello"));
+    }
+
+    #[tokio::test]
+    async fn test_large_output_spills_to_file() {
+        let mut spool = Spool::new(16);
+        let data: Vec<u8> = (0..100_000).map(|i| b'a' + (i % 26) as u8).collect();
+        for chunk in data.chunks(1000) {
+            spool.write(chunk).await.unwrap();
+        }
+        assert!(spool.is_spilled());
+        assert_eq!(spool.len(), data.len());
+        let path = spool.spill.as_ref().unwrap().path.clone();
+        assert!(path.exists());
+        assert_eq!(spool.into_bytes().await.unwrap(), data);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_output_limit_is_enforced() {
+        let err = spool_output(&[0u8; 100][..], 10, "stdout", Arc::default())
+            .await
+            .err()
+            .unwrap();
+        assert!(err.contains("maximum output size of 10 bytes"));
+    }
+
+    #[test]
+    fn test_transcript_interleaves_streams_in_order() {
+        let transcript = Transcript::new();
+        transcript.record("stdout", b"folder A ok\nfolder B ");
+        transcript.record("stderr", b"warning: folder B is empty\n");
+        transcript.record("stdout", b"ok\n");
+        transcript.record("stderr", b"error: folder C");
+        transcript.finish("stderr");
+        let lines: Vec<String> = transcript
+            .render()
+            .lines()
+            .map(|line| line.split_once("] ").unwrap().1.to_string())
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "folder A ok",
+                "warning: folder B is empty",
+                "folder B ok",
+                "error: folder C"
+            ]
+        );
+        assert!(transcript.render().starts_with("[+0.0"));
+    }
+
+    #[test]
+    fn test_transcript_keeps_first_and_last_lines() {
+        let transcript = Transcript::new();
+        for index in 0..1000 {
+            transcript.record("stdout", format!("line {}\n", index).as_bytes());
+        }
+        transcript.record("stdout", &[b'x'; TRANSCRIPT_LINE_BYTES * 2]);
+        transcript.finish("stdout");
+        let rendered = transcript.render();
+        let omitted = 1001 - TRANSCRIPT_HEAD_LINES - TRANSCRIPT_TAIL_LINES;
+        assert!(rendered.contains(&format!("[… {} lines omitted …]", omitted)));
+        assert!(rendered.contains("] line 0\n"));
+        assert!(rendered.contains("] line 999\n"));
+        assert!(rendered.ends_with('…'));
+        assert!(rendered.len() < 20_000);
+    }
+}
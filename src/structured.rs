@@ -0,0 +1,334 @@
+use crate::args::{coerce_value, matches_type};
+use serde_json::{Value, json};
+
+const LIST_TOOLS: [&str; 3] = ["pcli2_tenant_list", "pcli2_folder_list", "pcli2_asset_list"];
+const RECORD_TOOLS: [&str; 1] = ["pcli2_asset_get"];
+const MATCH_TOOLS: [&str; 8] = [
+    "pcli2_geometric_match",
+    "pcli2_asset_part_match",
+    "pcli2_asset_visual_match",
+    "pcli2_asset_text_match",
+    "pcli2_folder_geometric_match",
+    "pcli2_folder_part_match",
+    "pcli2_folder_visual_match",
+    "pcli2_multi_tenant_search",
+];
+/// Tools taking several folders or assets whose results are not matches;
+/// when inputs ran one by one, the status of each is structured content.
+const STATUS_TOOLS: [&str; 4] = [
+    "pcli2_folder_dependencies",
+    "pcli2_asset_metadata_create",
+    "pcli2_asset_metadata_delete",
+    "pcli2_asset_batch_get",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shape {
+    List,
+    Record,
+    Matches,
+    Statuses,
+}
+
+/// Whether `tool` returns a list of matches.
+pub fn is_match_tool(tool: &str) -> bool {
+    MATCH_TOOLS.contains(&tool)
+}
+
+fn shape_of(tool: &str) -> Option<Shape> {
+    if LIST_TOOLS.contains(&tool) {
+        Some(Shape::List)
+    } else if RECORD_TOOLS.contains(&tool) {
+        Some(Shape::Record)
+    } else if is_match_tool(tool) {
+        Some(Shape::Matches)
+    } else if STATUS_TOOLS.contains(&tool) {
+        Some(Shape::Statuses)
+    } else {
+        None
+    }
+}
+
+fn resource_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "uuid": { "type": "string" },
+            "name": { "type": "string" },
+            "path": { "type": "string" }
+        }
+    })
+}
+
+pub fn output_schema(tool: &str) -> Option<Value> {
+    let schema = match shape_of(tool)? {
+        Shape::List => {
+            let item = if tool == "pcli2_tenant_list" {
+                json!({ "type": "object" })
+            } else {
+                resource_schema()
+            };
+            json!({
+                "type": "object",
+                "properties": {
+                    "items": { "type": "array", "items": item }
+                },
+                "required": ["items"]
+            })
+        }
+        Shape::Record => resource_schema(),
+        Shape::Matches => json!({
+            "type": "object",
+            "properties": {
+                "matches": { "type": "array", "items": { "type": "object" } },
+                "total": { "type": "integer" },
+                "succeeded": { "type": "integer" },
+                "failed": { "type": "integer" },
+                "results": { "type": "array", "items": { "type": "object" } }
+            },
+            "required": ["matches"]
+        }),
+        Shape::Statuses => json!({
+            "type": "object",
+            "properties": {
+                "total": { "type": "integer" },
+                "succeeded": { "type": "integer" },
+                "failed": { "type": "integer" },
+                "results": { "type": "array", "items": { "type": "object" } }
+            },
+            "required": ["results"]
+        }),
+    };
+    Some(schema)
+}
+
+pub fn structured_content(tool: &str, output: Value) -> Option<Value> {
+    let shape = shape_of(tool)?;
+    let schema = output_schema(tool)?;
+    let shaped = match (shape, output) {
+        (Shape::List, Value::Array(items)) => json!({ "items": items }),
+        (Shape::List, Value::Object(obj)) if obj.get("items").is_some_and(Value::is_array) => {
+            Value::Object(obj)
+        }
+        (Shape::List, record @ Value::Object(_)) => json!({ "items": [record] }),
+        (Shape::Record, Value::Array(mut items)) if items.len() == 1 => items.remove(0),
+        (Shape::Record, record @ Value::Object(_)) => record,
+        (Shape::Matches, Value::Array(items)) => json!({ "matches": items }),
+        (Shape::Matches, Value::Object(obj)) if obj.get("matches").is_some_and(Value::is_array) => {
+            Value::Object(obj)
+        }
+        (Shape::Matches, Value::Object(mut obj))
+            if obj.get("results").is_some_and(Value::is_array) =>
+        {
+            let matches = flatten_fan_out_matches(&obj["results"]);
+            obj.insert("matches".to_string(), Value::Array(matches));
+            Value::Object(obj)
+        }
+        (Shape::Statuses, Value::Object(obj))
+            if obj.get("results").is_some_and(Value::is_array) =>
+        {
+            Value::Object(obj)
+        }
+        _ => return None,
+    };
+    let coerced = coerce_object(&schema, shaped);
+    conforms(&schema, &coerced).then_some(coerced)
+}
+
+pub fn attach_structured_content(tool: &str, mut result: Value) -> Value {
+    let parsed = result
+        .get("content")
+        .and_then(|v| v.as_array())
+        .and_then(|blocks| blocks.iter().rev().find(|block| block["type"] == "text"))
+        .and_then(|block| block["text"].as_str())
+        .and_then(|text| serde_json::from_str::<Value>(text).ok());
+    if let Some(structured) = parsed.and_then(|output| structured_content(tool, output)) {
+        result["structuredContent"] = structured;
+    }
+    result
+}
+
+/// Drops `structuredContent` from a result, for sessions without the
+/// `structured_output` feature.
+pub fn drop_structured_content(mut result: Value) -> Value {
+    if let Some(obj) = result.as_object_mut() {
+        obj.remove("structuredContent");
+    }
+    result
+}
+
+/// Drops the text blocks of a result that carries `structuredContent`, for
+/// clients that no longer read the serialized JSON (`--compat-text false`).
+pub fn drop_text_content(mut result: Value) -> Value {
+    if result.get("structuredContent").is_none() {
+        return result;
+    }
+    if let Some(blocks) = result.get_mut("content").and_then(Value::as_array_mut) {
+        blocks.retain(|block| block["type"] != "text");
+    }
+    result
+}
+
+fn flatten_fan_out_matches(results: &Value) -> Vec<Value> {
+    let mut matches = Vec::new();
+    for result in results.as_array().into_iter().flatten() {
+        if result["status"] != "ok" {
+            continue;
+        }
+        let input = result
+            .get("uuid")
+            .or_else(|| result.get("path"))
+            .cloned()
+            .unwrap_or(Value::Null);
+        for item in result["output"].as_array().into_iter().flatten() {
+            let mut item = item.clone();
+            if let Some(obj) = item.as_object_mut() {
+                obj.insert("input".to_string(), input.clone());
+            }
+            matches.push(item);
+        }
+    }
+    matches
+}
+
+fn coerce_object(schema: &Value, value: Value) -> Value {
+    let Value::Object(mut obj) = value else {
+        return value;
+    };
+    let Some(properties) = schema.get("properties").and_then(|v| v.as_object()) else {
+        return Value::Object(obj);
+    };
+    for (key, prop) in properties {
+        let Some(field) = obj.remove(key) else {
+            continue;
+        };
+        let field = match (prop.get("items"), field) {
+            (Some(item_schema), Value::Array(items)) => Value::Array(
+                items
+                    .into_iter()
+                    .map(|item| coerce_object(item_schema, item))
+                    .collect(),
+            ),
+            (_, field) => coerce_value(prop, field),
+        };
+        obj.insert(key.clone(), field);
+    }
+    Value::Object(obj)
+}
+
+fn conforms(schema: &Value, value: &Value) -> bool {
+    let Some(obj) = value.as_object() else {
+        return false;
+    };
+    let required_ok = schema
+        .get("required")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.as_str())
+        .all(|key| obj.contains_key(key));
+    let properties = schema.get("properties").and_then(|v| v.as_object());
+    let fields_ok = properties.into_iter().flatten().all(|(key, prop)| {
+        let Some(field) = obj.get(key) else {
+            return true;
+        };
+        if field.is_null() {
+            return true;
+        }
+        if !matches_type(prop, field) {
+            return false;
+        }
+        match (prop.get("items"), field.as_array()) {
+            (Some(item_schema), Some(items)) if item_schema.get("properties").is_some() => {
+                items.iter().all(|item| conforms(item_schema, item))
+            }
+            _ => true,
+        }
+    });
+    required_ok && fields_ok
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_output_is_wrapped_and_coerced() {
+        let output = json!([{ "uuid": 42, "name": "Castings", "path": "/Root/Castings" }]);
+        let structured = structured_content("pcli2_folder_list", output).unwrap();
+        assert_eq!(structured["items"][0]["uuid"], "42");
+        assert_eq!(structured["items"][0]["path"], "/Root/Castings");
+    }
+
+    #[test]
+    fn test_record_output_unwraps_single_element() {
+        let output = json!([{ "uuid": "a", "path": "/Root/A.stl" }]);
+        let structured = structured_content("pcli2_asset_get", output).unwrap();
+        assert_eq!(structured["uuid"], "a");
+    }
+
+    #[test]
+    fn test_fan_out_matches_are_flattened() {
+        let output = json!({
+            "total": 2,
+            "succeeded": 1,
+            "failed": 1,
+            "results": [
+                { "path": "/A", "status": "ok", "output": [{ "score": 99.5 }] },
+                { "path": "/B", "status": "error", "error": "not found" }
+            ]
+        });
+        let structured = structured_content("pcli2_asset_part_match", output).unwrap();
+        assert_eq!(
+            structured["matches"],
+            json!([{ "score": 99.5, "input": "/A" }])
+        );
+        assert_eq!(structured["failed"], 1);
+    }
+
+    #[test]
+    fn test_per_input_statuses_are_structured() {
+        let output = json!({
+            "total": 2,
+            "succeeded": 1,
+            "failed": 1,
+            "results": [
+                { "folder_path": "/A", "status": "ok", "output": [] },
+                { "folder_path": "/B", "status": "error", "error": "not found" }
+            ]
+        });
+        let structured = structured_content("pcli2_folder_dependencies", output).unwrap();
+        assert_eq!(structured["results"][1]["error"], "not found");
+        // A single run's tree is not a status list.
+        assert!(
+            structured_content("pcli2_folder_dependencies", json!([{ "name": "a" }])).is_none()
+        );
+    }
+
+    #[test]
+    fn test_unexpected_shapes_are_skipped() {
+        assert!(structured_content("pcli2_asset_get", json!("text")).is_none());
+        assert!(structured_content("pcli2_folder_list", json!([{ "uuid": [1] }])).is_none());
+        assert!(structured_content("pcli2_version", json!({})).is_none());
+    }
+
+    #[test]
+    fn test_drop_text_content_keeps_unstructured_results() {
+        let text = json!({ "type": "text", "text": "[{\"uuid\": \"1\"}]" });
+        let result =
+            attach_structured_content("pcli2_folder_list", json!({ "content": [text.clone()] }));
+        let dropped = drop_text_content(result);
+        assert_eq!(dropped["content"], json!([]));
+        assert!(dropped.get("structuredContent").is_some());
+
+        let plain = json!({ "content": [text] });
+        assert_eq!(drop_text_content(plain.clone()), plain);
+    }
+
+    #[test]
+    fn test_attach_ignores_non_json_output() {
+        let result = json!({ "content": [{ "type": "text", "text": "a,b\n1,2" }] });
+        let result = attach_structured_content("pcli2_folder_list", result);
+        assert!(result.get("structuredContent").is_none());
+    }
+}
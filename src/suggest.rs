@@ -0,0 +1,239 @@
+use crate::{
+    content::{raw_output_block, summary_block, tool_result},
+    pcli::{run_pcli2_list, validate_range_f64, validate_range_u64},
+};
+use serde_json::{Value, json};
+
+pub const FOLDER_SUGGEST_TOOL: &str = "pcli2_folder_suggest";
+pub const DEFAULT_SUGGESTIONS: usize = 10;
+pub const MAX_SUGGESTIONS: usize = 50;
+pub const DEFAULT_MIN_SCORE: f64 = 50.0;
+
+/// Lowercase alphanumeric runs, so `Castings_2024` and `castings-2024` compare equal.
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Similarity of two normalized strings from 0 to 100: edit distance, with
+/// a floor for one containing the other.
+fn similarity(query: &str, candidate: &str) -> f64 {
+    if query.is_empty() || candidate.is_empty() {
+        return 0.0;
+    }
+    let (q, c): (Vec<char>, Vec<char>) = (query.chars().collect(), candidate.chars().collect());
+    let longest = q.len().max(c.len()) as f64;
+    let edit = 100.0 * (1.0 - levenshtein(&q, &c) as f64 / longest);
+    let contained = if candidate.contains(query) || query.contains(candidate) {
+        70.0 + 30.0 * q.len().min(c.len()) as f64 / longest
+    } else {
+        0.0
+    };
+    edit.max(contained)
+}
+
+/// Scores a folder path against a query of one or more `/`-separated names,
+/// comparing the query's segments with the path's trailing segments.
+pub fn score_path(query: &str, path: &str) -> f64 {
+    let query: Vec<String> = query
+        .split('/')
+        .map(normalize)
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    let segments: Vec<String> = path
+        .split('/')
+        .map(normalize)
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    if query.is_empty() || segments.len() < query.len() {
+        return 0.0;
+    }
+    let suffix_score = |end: usize| {
+        let start = end - query.len();
+        query
+            .iter()
+            .zip(&segments[start..end])
+            .map(|(q, s)| similarity(q, s))
+            .sum::<f64>()
+            / query.len() as f64
+    };
+    // The deepest segment weighs most, but a parent folder may be meant too.
+    (query.len()..=segments.len())
+        .map(|end| {
+            let depth_penalty = (segments.len() - end) as f64 * 5.0;
+            suffix_score(end) - depth_penalty
+        })
+        .fold(0.0, f64::max)
+}
+
+fn folder_entries(output: &str) -> Result<Vec<Value>, String> {
+    let parsed: Value = serde_json::from_str(output)
+        .map_err(|err| format!("pcli2 folder list returned invalid JSON: {}", err))?;
+    match parsed {
+        Value::Array(items) => Ok(items),
+        Value::Object(mut obj) if obj.get("items").is_some_and(Value::is_array) => {
+            Ok(match obj.remove("items") {
+                Some(Value::Array(items)) => items,
+                _ => Vec::new(),
+            })
+        }
+        _ => Err("pcli2 folder list returned an unexpected JSON shape".to_string()),
+    }
+}
+
+/// Ranks `folders` by how well their path matches `query`, best first.
+pub fn rank_folders(query: &str, folders: &[Value], min_score: f64, limit: usize) -> Vec<Value> {
+    let mut scored: Vec<(f64, &Value)> = folders
+        .iter()
+        .filter_map(|folder| {
+            let path = folder["path"].as_str()?;
+            Some((score_path(query, path), folder))
+        })
+        .filter(|(score, _)| *score >= min_score)
+        .collect();
+    scored.sort_by(|a, b| {
+        b.0.total_cmp(&a.0)
+            .then_with(|| a.1["path"].as_str().cmp(&b.1["path"].as_str()))
+    });
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(score, folder)| {
+            json!({
+                "path": folder["path"],
+                "uuid": folder["uuid"],
+                "name": folder["name"],
+                "score": (score * 10.0).round() / 10.0
+            })
+        })
+        .collect()
+}
+
+pub async fn suggest_folders(args: Value) -> Result<Value, String> {
+    validate_range_u64(&args, "limit", 1, MAX_SUGGESTIONS as u64)?;
+    validate_range_f64(&args, "min_score", 0.0, 100.0)?;
+    let query = args
+        .get("query")
+        .and_then(Value::as_str)
+        .filter(|query| !normalize(query).is_empty())
+        .ok_or_else(|| "Missing required argument: 'query'".to_string())?;
+    let limit = args
+        .get("limit")
+        .and_then(Value::as_u64)
+        .map_or(DEFAULT_SUGGESTIONS, |limit| limit as usize);
+    let min_score = args
+        .get("min_score")
+        .and_then(Value::as_f64)
+        .unwrap_or(DEFAULT_MIN_SCORE);
+
+    let mut list = json!({ "format": "json" });
+    for key in ["tenant", "reload"] {
+        if let Some(value) = args.get(key) {
+            list[key] = value.clone();
+        }
+    }
+    let output = run_pcli2_list("folder", list)
+        .await
+        .map_err(|message| format!("pcli2 folder list failed: {}", message))?;
+    let folders = folder_entries(&output)?;
+    let suggestions = rank_folders(query, &folders, min_score, limit);
+
+    let headline = match suggestions.first() {
+        Some(best) => format!(
+            "{} folders match '{}'; best: {} (score {}).",
+            suggestions.len(),
+            query,
+            best["path"].as_str().unwrap_or_default(),
+            best["score"]
+        ),
+        None => format!(
+            "No folder among {} matches '{}' with a score of at least {}.",
+            folders.len(),
+            query,
+            min_score
+        ),
+    };
+    let text = serde_json::to_string_pretty(&json!({
+        "query": query,
+        "suggestions": suggestions
+    }))
+    .map_err(|err| format!("Failed to render suggestions: {}", err))?;
+    Ok(tool_result(vec![
+        summary_block(headline),
+        raw_output_block(text),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folders(paths: &[&str]) -> Vec<Value> {
+        paths
+            .iter()
+            .enumerate()
+            .map(|(index, path)| {
+                json!({
+                    "uuid": format!("u{}", index),
+                    "name": path.rsplit('/').next(),
+                    "path": path
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_levenshtein() {
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        assert_eq!(levenshtein(&chars("castngs"), &chars("castings")), 1);
+        assert_eq!(levenshtein(&chars(""), &chars("abc")), 3);
+        assert_eq!(levenshtein(&chars("kitten"), &chars("sitting")), 3);
+    }
+
+    #[test]
+    fn test_misspelled_and_partial_names_rank_first() {
+        let tree = folders(&[
+            "/Root",
+            "/Root/Castings",
+            "/Root/Castings/Brackets",
+            "/Root/Forgings",
+            "/Root/Archive/Castings-2019",
+        ]);
+        let ranked = rank_folders("castngs", &tree, DEFAULT_MIN_SCORE, 10);
+        assert_eq!(ranked[0]["path"], "/Root/Castings");
+        assert_eq!(ranked[0]["uuid"], "u1");
+
+        let ranked = rank_folders("Castings/brakets", &tree, DEFAULT_MIN_SCORE, 10);
+        assert_eq!(ranked[0]["path"], "/Root/Castings/Brackets");
+
+        let ranked = rank_folders("Castings", &tree, DEFAULT_MIN_SCORE, 2);
+        assert_eq!(ranked.len(), 2);
+        assert_eq!(ranked[0]["score"], 100.0);
+
+        assert!(rank_folders("zzzz", &tree, DEFAULT_MIN_SCORE, 10).is_empty());
+    }
+
+    #[test]
+    fn test_folder_entries_accept_items_wrapper() {
+        assert_eq!(
+            folder_entries(r#"{"items": [{"path": "/Root"}]}"#).unwrap(),
+            vec![json!({ "path": "/Root" })]
+        );
+        assert!(folder_entries("not json").is_err());
+    }
+}
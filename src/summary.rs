@@ -0,0 +1,309 @@
+use crate::{
+    content::{raw_output_block, summary_block, tool_result},
+    pcli::validate_range_u64,
+    resources::{ResourceStore, resource_link},
+    structured::{is_match_tool, structured_content},
+};
+use serde_json::{Map, Value, json};
+
+pub const SUMMARIZE_ARG: &str = "summarize";
+pub const TOP_N_ARG: &str = "top_n";
+pub const DEFAULT_TOP_N: usize = 5;
+const MAX_TOP_N: u64 = 100;
+/// Fields that name the asset a match was found for, in order of preference.
+const SOURCE_KEYS: [&str; 6] = [
+    "input",
+    "reference_asset_path",
+    "reference_asset_uuid",
+    "source",
+    "source_path",
+    "tenant",
+];
+const SCORE_KEYS: [&str; 4] = ["score", "match_percentage", "similarity", "percentage"];
+/// Scores reported with "how many matches reach this level" counts.
+const SCORE_LEVELS: [u32; 4] = [80, 90, 95, 99];
+/// Fan-out counts carried over from the full result.
+const FAN_OUT_KEYS: [&str; 3] = ["total", "succeeded", "failed"];
+
+/// Whether a call of `tool` asks for its matches to be summarized.
+pub fn wants_summary(tool: &str, args: &Value) -> bool {
+    is_match_tool(tool)
+        && args
+            .get(SUMMARIZE_ARG)
+            .and_then(Value::as_bool)
+            .unwrap_or(false)
+}
+
+/// Checks the summary options and asks pcli2 for JSON, which the summary
+/// is built from.
+pub fn prepare_summary(args: &mut Value) -> Result<(), String> {
+    validate_range_u64(args, TOP_N_ARG, 1, MAX_TOP_N)?;
+    if args.get("format").and_then(Value::as_str) == Some("csv") {
+        return Err(format!(
+            "Invalid argument 'format': '{}' needs JSON output; drop 'format' or set it to json",
+            SUMMARIZE_ARG
+        ));
+    }
+    if let Some(args) = args.as_object_mut() {
+        args.insert("format".to_string(), json!("json"));
+    }
+    Ok(())
+}
+
+pub(crate) fn score_of(item: &Value) -> Option<f64> {
+    SCORE_KEYS
+        .iter()
+        .filter_map(|key| item.get(*key))
+        .find_map(|value| match value {
+            Value::Number(number) => number.as_f64(),
+            Value::String(text) => text.trim().trim_end_matches('%').parse().ok(),
+            _ => None,
+        })
+}
+
+fn source_of(item: &Value) -> Value {
+    SOURCE_KEYS
+        .iter()
+        .find_map(|key| item.get(*key).filter(|value| !value.is_null()).cloned())
+        .unwrap_or(Value::Null)
+}
+
+fn round2(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+/// Min, max, mean and median of `scores`, or `null` without any.
+fn distribution(scores: &[f64]) -> Value {
+    if scores.is_empty() {
+        return Value::Null;
+    }
+    let mut sorted = scores.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let middle = sorted.len() / 2;
+    let median = if sorted.len().is_multiple_of(2) {
+        (sorted[middle - 1] + sorted[middle]) / 2.0
+    } else {
+        sorted[middle]
+    };
+    json!({
+        "min": sorted[0],
+        "max": sorted[sorted.len() - 1],
+        "mean": round2(sorted.iter().sum::<f64>() / sorted.len() as f64),
+        "median": round2(median)
+    })
+}
+
+/// The compact summary of `matches`: the best `top_n` per source asset,
+/// the score distribution and how many matches reach each score level.
+pub fn summarize(matches: &[Value], top_n: usize) -> Value {
+    let mut sources: Vec<(Value, Vec<&Value>)> = Vec::new();
+    for item in matches {
+        let source = source_of(item);
+        match sources.iter_mut().find(|(known, _)| *known == source) {
+            Some((_, items)) => items.push(item),
+            None => sources.push((source, vec![item])),
+        }
+    }
+    let mut top_matches = Vec::new();
+    let sources: Vec<Value> = sources
+        .into_iter()
+        .map(|(source, mut items)| {
+            // Unscored matches sort last.
+            items.sort_by(|a, b| {
+                let (a, b) = (score_of(a), score_of(b));
+                b.unwrap_or(f64::NEG_INFINITY)
+                    .total_cmp(&a.unwrap_or(f64::NEG_INFINITY))
+            });
+            let count = items.len();
+            let best_score = items.first().and_then(|item| score_of(item));
+            let top: Vec<Value> = items.into_iter().take(top_n).cloned().collect();
+            top_matches.extend(top.iter().cloned());
+            json!({
+                "source": source,
+                "matches": count,
+                "best_score": best_score,
+                "top": top
+            })
+        })
+        .collect();
+    let scores: Vec<f64> = matches.iter().filter_map(score_of).collect();
+    let above: Map<String, Value> = SCORE_LEVELS
+        .iter()
+        .map(|level| {
+            let count = scores
+                .iter()
+                .filter(|score| **score >= f64::from(*level))
+                .count();
+            (level.to_string(), json!(count))
+        })
+        .collect();
+    json!({
+        "summarized": true,
+        "match_count": matches.len(),
+        "sources": sources,
+        "scores": distribution(&scores),
+        "above": above,
+        "matches": top_matches
+    })
+}
+
+fn headline(summary: &Value, uri: &str) -> String {
+    let best = match summary["scores"]["max"].as_f64() {
+        Some(max) => format!(", best score {}", max),
+        None => String::new(),
+    };
+    format!(
+        "Summarized {} matches from {} sources{}; {} at or above 90. Showing the top {} per source; the full output is stored as resource {}.",
+        summary["match_count"],
+        summary["sources"].as_array().map_or(0, Vec::len),
+        best,
+        summary["above"]["90"],
+        summary["top_n"],
+        uri
+    )
+}
+
+/// Replaces the matches of a match tool `result` with their summary and
+/// stores the full output as a resource that the summary links to. A
+/// result without JSON matches is returned whole.
+pub fn summarize_result(
+    store: &ResourceStore,
+    tenant: Option<&str>,
+    tool: &str,
+    internal: &str,
+    args: &Value,
+    result: Value,
+) -> Value {
+    let Some(text) = result
+        .get("content")
+        .and_then(Value::as_array)
+        .and_then(|blocks| blocks.iter().rev().find(|block| block["type"] == "text"))
+        .and_then(|block| block["text"].as_str())
+        .map(str::to_string)
+    else {
+        return result;
+    };
+    let Some(full) = serde_json::from_str::<Value>(&text)
+        .ok()
+        .and_then(|output| structured_content(internal, output))
+    else {
+        return result;
+    };
+    let matches = full["matches"].as_array().map_or(&[][..], Vec::as_slice);
+    let top_n = args
+        .get(TOP_N_ARG)
+        .and_then(Value::as_u64)
+        .map_or(DEFAULT_TOP_N, |n| n as usize);
+    let mut summary = summarize(matches, top_n);
+    for key in FAN_OUT_KEYS {
+        if let Some(value) = full.get(key) {
+            summary[key] = value.clone();
+        }
+    }
+    let stored = store.insert(
+        &format!("{} full result", tool),
+        "application/json",
+        text,
+        tenant,
+    );
+    summary["top_n"] = json!(top_n);
+    summary["full_result"] = json!(stored.uri);
+    let rendered = serde_json::to_string_pretty(&summary).unwrap_or_else(|_| summary.to_string());
+    tool_result(vec![
+        summary_block(headline(&summary, &stored.uri)),
+        raw_output_block(rendered),
+        resource_link(&stored),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches() -> Vec<Value> {
+        vec![
+            json!({ "input": "/A", "path": "/X", "score": 81.0 }),
+            json!({ "input": "/A", "path": "/Y", "score": "99.5%" }),
+            json!({ "input": "/B", "path": "/Z", "match_percentage": 92 }),
+            json!({ "input": "/A", "path": "/W", "score": 95.0 }),
+            json!({ "input": "/A", "path": "/V" }),
+        ]
+    }
+
+    #[test]
+    fn test_summary_keeps_the_best_matches_per_source() {
+        let summary = summarize(&matches(), 2);
+        assert_eq!(summary["match_count"], 5);
+        assert_eq!(summary["sources"][0]["source"], "/A");
+        assert_eq!(summary["sources"][0]["matches"], 4);
+        assert_eq!(summary["sources"][0]["best_score"], 99.5);
+        assert_eq!(summary["sources"][0]["top"][0]["path"], "/Y");
+        assert_eq!(summary["sources"][0]["top"][1]["path"], "/W");
+        assert_eq!(summary["sources"][1]["source"], "/B");
+        assert_eq!(summary["matches"].as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_summary_reports_the_score_distribution() {
+        let summary = summarize(&matches(), 5);
+        assert_eq!(summary["scores"]["min"], 81.0);
+        assert_eq!(summary["scores"]["max"], 99.5);
+        assert_eq!(summary["scores"]["mean"], 91.88);
+        assert_eq!(summary["scores"]["median"], 93.5);
+        assert_eq!(
+            summary["above"],
+            json!({ "80": 4, "90": 3, "95": 2, "99": 1 })
+        );
+        assert_eq!(summarize(&[], 5)["scores"], Value::Null);
+    }
+
+    #[test]
+    fn test_prepare_summary_asks_for_json() {
+        let mut args = json!({ "summarize": true, "top_n": 3 });
+        prepare_summary(&mut args).unwrap();
+        assert_eq!(args["format"], "json");
+        let mut args = json!({ "summarize": true, "format": "csv" });
+        assert!(prepare_summary(&mut args).unwrap_err().contains("format"));
+        let mut args = json!({ "summarize": true, "top_n": 0 });
+        assert!(prepare_summary(&mut args).unwrap_err().contains("top_n"));
+        assert!(wants_summary("pcli2_folder_geometric_match", &args));
+        assert!(!wants_summary("pcli2_asset_get", &args));
+    }
+
+    #[test]
+    fn test_summarized_result_links_the_full_output() {
+        let store = ResourceStore::default();
+        let full = serde_json::to_string(&matches()).unwrap();
+        let result = tool_result(vec![raw_output_block(full.clone())]);
+        let summarized = summarize_result(
+            &store,
+            None,
+            "pcli2_folder_geometric_match",
+            "pcli2_folder_geometric_match",
+            &json!({ "top_n": 1 }),
+            result,
+        );
+        let content = summarized["content"].as_array().unwrap();
+        assert_eq!(content.len(), 3);
+        assert!(content[0]["text"].as_str().unwrap().contains("5 matches"));
+        let summary: Value = serde_json::from_str(content[1]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(summary["top_n"], 1);
+        assert_eq!(content[2]["type"], "resource_link");
+        assert_eq!(content[2]["uri"], summary["full_result"]);
+        let stored = store.get(content[2]["uri"].as_str().unwrap()).unwrap();
+        assert_eq!(*stored.text, full);
+
+        let plain = tool_result(vec![raw_output_block("not json")]);
+        assert_eq!(
+            summarize_result(
+                &store,
+                None,
+                "t",
+                "pcli2_geometric_match",
+                &json!({}),
+                plain.clone()
+            ),
+            plain
+        );
+    }
+}
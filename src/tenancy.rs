@@ -0,0 +1,351 @@
+use crate::{
+    admin::{CREDENTIALS_TOOL, token_matches},
+    approval::APPROVAL_STATUS_TOOL,
+    args::resolve_properties,
+    artifacts::{ARTIFACTS_LIST_TOOL, ARTIFACTS_PURGE_TOOL},
+    audit::AUDIT_TOOL,
+    auth::expand_args,
+    config::{ApiKeyConfig, ServerConfig},
+    plan::PLAN_TOOL,
+    quota::QUOTA_STATUS_TOOL,
+    search::MULTI_TENANT_SEARCH_TOOL,
+    snapshot::{DIFF_TOOL, SNAPSHOT_TOOL},
+    split::SPLIT_CONCURRENCY_ARG,
+};
+use axum::http::{HeaderMap, header::AUTHORIZATION};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+/// Tools that reach across tenants or change which one `pcli2` uses.
+const UNBOUND_ONLY_TOOLS: [&str; 7] = [
+    "pcli2_tenant_use",
+    "pcli2_tenant_list",
+    CREDENTIALS_TOOL,
+    ARTIFACTS_LIST_TOOL,
+    ARTIFACTS_PURGE_TOOL,
+    SNAPSHOT_TOOL,
+    DIFF_TOOL,
+];
+/// Tools without a `tenant` argument that read no tenant data.
+const TENANT_FREE_TOOLS: [&str; 10] = [
+    "pcli2_version",
+    "pcli2_config_get",
+    "pcli2_config_get_path",
+    "pcli2_config_environment_list",
+    "pcli2_config_environment_get",
+    "pcli2_batch",
+    QUOTA_STATUS_TOOL,
+    AUDIT_TOOL,
+    PLAN_TOOL,
+    APPROVAL_STATUS_TOOL,
+];
+
+/// Whom a request was admitted as.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Principal {
+    /// Tenant the request is confined to.
+    pub tenant: Option<String>,
+    /// `policies` entry the request's API key is held to.
+    pub policy: Option<String>,
+    /// The API key the request carried, as usage accounting names it.
+    pub key: Option<String>,
+}
+
+impl Principal {
+    pub fn confined_to(tenant: Option<String>) -> Self {
+        Self {
+            tenant,
+            policy: None,
+            key: None,
+        }
+    }
+}
+
+/// Whom a request carrying `api_key` (`expanded` once `${VAR}`s are read)
+/// is admitted as.
+pub(crate) fn key_principal(api_key: &ApiKeyConfig, expanded: &str) -> Principal {
+    Principal {
+        tenant: api_key.tenant.clone(),
+        policy: api_key.policy.clone(),
+        key: Some(key_name(api_key, expanded)),
+    }
+}
+
+/// `api_keys[].name`, else a digest of the key that does not give it away.
+fn key_name(api_key: &ApiKeyConfig, expanded: &str) -> String {
+    match &api_key.name {
+        Some(name) => name.clone(),
+        None => {
+            let digest = Sha256::digest(expanded.as_bytes());
+            let hex: String = digest[..4]
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            format!("key-{}", hex)
+        }
+    }
+}
+
+/// Checks the request's API key against `api_keys` and returns the tenant
+/// and policy it carries. Every request passes when no keys are configured.
+pub fn authenticate(api_keys: &[ApiKeyConfig], headers: &HeaderMap) -> Result<Principal, String> {
+    if api_keys.is_empty() {
+        return Ok(Principal::default());
+    }
+    let provided = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    for api_key in api_keys {
+        let expected = match expand_args(std::slice::from_ref(&api_key.key)) {
+            Ok(key) => key.concat(),
+            Err(err) => {
+                warn!("⚠ skipping API key: {}", err);
+                continue;
+            }
+        };
+        if !expected.is_empty() && token_matches(&expected, provided) {
+            return Ok(key_principal(api_key, &expected));
+        }
+    }
+    Err("Missing or invalid API key".to_string())
+}
+
+/// Whether a client bound to `tenant` may see and call `tool`.
+pub fn tool_allowed(tenant: Option<&str>, tool: &str, schema: &Value) -> bool {
+    tenant.is_none() || tenant_scoped(tool, schema)
+}
+
+/// Whether `tool` works in a tenant named by its arguments, or in none, so
+/// a client held to some tenants may call it.
+pub fn tenant_scoped(tool: &str, schema: &Value) -> bool {
+    if UNBOUND_ONLY_TOOLS.contains(&tool) {
+        return false;
+    }
+    TENANT_FREE_TOOLS.contains(&tool)
+        || tool == MULTI_TENANT_SEARCH_TOOL
+        || schema["properties"].get("tenant").is_some()
+}
+
+/// Forces a bound client's call onto its tenant, whatever `tenant` it asked for.
+pub fn bind_arguments(
+    tenant: Option<&str>,
+    tool: &str,
+    schema: &Value,
+    args: &mut Value,
+) -> Result<(), String> {
+    let Some(tenant) = tenant else {
+        return Ok(());
+    };
+    if !tool_allowed(Some(tenant), tool, schema) {
+        return Err(format!(
+            "Tool '{}' is not available to API keys bound to tenant '{}'",
+            tool, tenant
+        ));
+    }
+    let Some(args) = args.as_object_mut() else {
+        return Ok(());
+    };
+    if tool == MULTI_TENANT_SEARCH_TOOL {
+        args.insert("tenants".to_string(), json!([tenant]));
+    } else if schema["properties"].get("tenant").is_some() {
+        if let Some(requested) = args.get("tenant").and_then(Value::as_str)
+            && requested != tenant
+        {
+            warn!(
+                "⚠ {} asked for tenant '{}'; bound to '{}'",
+                tool, requested, tenant
+            );
+        }
+        args.insert("tenant".to_string(), json!(tenant));
+    }
+    Ok(())
+}
+
+/// Fills in the `tenants` defaults of the tenant the call names for the
+/// arguments it left out. A default the tool does not take, or a `format`
+/// it does not offer, is skipped.
+pub fn apply_tenant_defaults(config: &ServerConfig, schema: &Value, args: &mut Value) {
+    let Some(defaults) = args
+        .get("tenant")
+        .and_then(Value::as_str)
+        .and_then(|tenant| config.tenants.get(tenant))
+    else {
+        return;
+    };
+    let Some(args) = args.as_object_mut() else {
+        return;
+    };
+    let properties = resolve_properties(schema);
+    let mut fill = |key: &str, value: Value| {
+        let Some(property) = properties.get(key) else {
+            return;
+        };
+        let offered = property["enum"]
+            .as_array()
+            .is_none_or(|values| values.contains(&value));
+        if offered && !args.contains_key(key) {
+            args.insert(key.to_string(), value);
+        }
+    };
+    if let Some(threshold) = defaults.threshold {
+        fill("threshold", json!(threshold));
+    }
+    if let Some(format) = &defaults.format {
+        fill("format", json!(format));
+    }
+    if let Some(concurrency) = defaults.concurrency {
+        fill("concurrent", json!(concurrency));
+        fill(SPLIT_CONCURRENCY_ARG, json!(concurrency));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys() -> Vec<ApiKeyConfig> {
+        vec![
+            ApiKeyConfig {
+                key: "team-a".to_string(),
+                name: Some("castings team".to_string()),
+                tenant: Some("castings".to_string()),
+                policy: None,
+            },
+            ApiKeyConfig {
+                key: "ops".to_string(),
+                name: None,
+                tenant: None,
+                policy: Some("admin".to_string()),
+            },
+        ]
+    }
+
+    fn bearer(key: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Ok(value) = format!("Bearer {}", key).parse() {
+            headers.insert(AUTHORIZATION, value);
+        }
+        headers
+    }
+
+    #[test]
+    fn test_authenticate() {
+        assert_eq!(
+            authenticate(&[], &HeaderMap::new()),
+            Ok(Principal::default())
+        );
+        assert_eq!(
+            authenticate(&keys(), &bearer("team-a")),
+            Ok(Principal {
+                key: Some("castings team".to_string()),
+                ..Principal::confined_to(Some("castings".to_string()))
+            })
+        );
+        let ops = authenticate(&keys(), &bearer("ops")).unwrap();
+        assert_eq!(ops.policy.as_deref(), Some("admin"));
+        let key = ops.key.unwrap();
+        assert!(key.starts_with("key-") && key.len() == 12, "{}", key);
+        assert!(!key.contains("ops"));
+        assert!(authenticate(&keys(), &bearer("team-b")).is_err());
+        assert!(authenticate(&keys(), &HeaderMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_bound_calls_are_forced_to_the_tenant() {
+        let schema = json!({ "properties": { "tenant": {}, "path": {} } });
+        let mut args = json!({ "tenant": "forgings", "path": "/Root/A.stl" });
+        bind_arguments(Some("castings"), "pcli2_asset_get", &schema, &mut args).unwrap();
+        assert_eq!(args, json!({ "tenant": "castings", "path": "/Root/A.stl" }));
+
+        let mut args = json!({ "text": "bracket", "tenants": ["castings", "forgings"] });
+        bind_arguments(
+            Some("castings"),
+            MULTI_TENANT_SEARCH_TOOL,
+            &json!({}),
+            &mut args,
+        )
+        .unwrap();
+        assert_eq!(args["tenants"], json!(["castings"]));
+
+        let mut args = json!({});
+        bind_arguments(None, "pcli2_tenant_use", &json!({}), &mut args).unwrap();
+        assert_eq!(args, json!({}));
+    }
+
+    #[test]
+    fn test_bound_clients_cannot_leave_their_tenant() {
+        let no_tenant = json!({ "properties": { "folder_path": {} } });
+        for tool in ["pcli2_tenant_use", "pcli2_tenant_list", SNAPSHOT_TOOL] {
+            assert!(!tool_allowed(
+                Some("castings"),
+                tool,
+                &json!({ "properties": { "tenant": {} } })
+            ));
+        }
+        assert!(!tool_allowed(
+            Some("castings"),
+            "pcli2_tenant_get",
+            &no_tenant
+        ));
+        assert!(tool_allowed(Some("castings"), "pcli2_version", &no_tenant));
+        assert!(tool_allowed(None, "pcli2_tenant_get", &no_tenant));
+        assert!(
+            bind_arguments(
+                Some("castings"),
+                "pcli2_tenant_use",
+                &no_tenant,
+                &mut json!({})
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_tenant_defaults_fill_missing_arguments() {
+        use crate::config::TenantDefaults;
+        use std::collections::HashMap;
+
+        let config = ServerConfig {
+            tenants: HashMap::from([(
+                "castings".to_string(),
+                TenantDefaults {
+                    threshold: Some(92.0),
+                    format: Some("tree".to_string()),
+                    concurrency: Some(2),
+                    artifact_dir: None,
+                },
+            )]),
+            ..ServerConfig::default()
+        };
+        let schema = json!({
+            "properties": {
+                "tenant": {},
+                "threshold": { "type": "number" },
+                "format": { "$ref": "#/$defs/format_json_csv" },
+                "concurrent": { "type": "integer" }
+            },
+            "$defs": { "format_json_csv": { "type": "string", "enum": ["json", "csv"] } }
+        });
+        let mut args = json!({ "tenant": "castings", "threshold": 80.0 });
+        apply_tenant_defaults(&config, &schema, &mut args);
+        assert_eq!(
+            args,
+            json!({ "tenant": "castings", "threshold": 80.0, "concurrent": 2 })
+        );
+        let mut args = json!({ "tenant": "forgings" });
+        apply_tenant_defaults(&config, &schema, &mut args);
+        assert_eq!(args, json!({ "tenant": "forgings" }));
+        let mut args = json!({});
+        apply_tenant_defaults(&config, &schema, &mut args);
+        assert_eq!(args, json!({}));
+
+        let tree =
+            json!({ "properties": { "tenant": {}, "format": { "enum": ["json", "tree"] } } });
+        let mut args = json!({ "tenant": "castings" });
+        apply_tenant_defaults(&config, &tree, &mut args);
+        assert_eq!(args, json!({ "tenant": "castings", "format": "tree" }));
+    }
+}
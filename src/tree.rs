@@ -0,0 +1,322 @@
+use crate::{
+    AppState,
+    instructions::{cached_tenants, last_text},
+    paging::json_rows,
+    pcli::{call_for_resource, tool_listed},
+    policy::tenant_permitted,
+};
+use serde_json::{Value, json};
+
+/// Scheme of the resources that read a tenant's library through pcli2.
+pub const PHYSNA_URI_SCHEME: &str = "physna://";
+/// Folder tree resources are `physna://{tenant}/tree` for the top level and
+/// `physna://{tenant}/tree/{folder}/{subfolder}` for the folders below it,
+/// one percent-encoded URI segment per folder name.
+const TREE_SEGMENT: &str = "tree";
+/// The tree as an MCP resource template.
+pub const TREE_URI_TEMPLATE: &str = "physna://{tenant}/tree";
+const FOLDER_LIST_TOOL: &str = "pcli2_folder_list";
+/// Subfolders listed per node; the rest are counted in `total`.
+const MAX_CHILDREN: usize = 500;
+
+/// Percent-encodes everything but unreserved URI characters.
+pub(crate) fn encode_segment(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Reverses [`encode_segment`]; `None` for a malformed escape or UTF-8.
+pub(crate) fn decode_segment(segment: &str) -> Option<String> {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' {
+            let hex = segment.get(index + 1..index + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            index += 3;
+        } else {
+            decoded.push(bytes[index]);
+            index += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+fn segments(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|segment| !segment.is_empty())
+}
+
+/// `path` as `/A/B`, or `""` for the top level.
+fn normalize(path: &str) -> String {
+    segments(path)
+        .map(|segment| format!("/{}", segment))
+        .collect()
+}
+
+fn parent_of(path: &str) -> String {
+    let path = normalize(path);
+    match path.rfind('/') {
+        Some(index) => path[..index].to_string(),
+        None => String::new(),
+    }
+}
+
+/// The tree resource of the folder at `path` of `tenant`; `""` is the top
+/// level.
+pub fn tree_uri(tenant: &str, path: &str) -> String {
+    let mut uri = format!(
+        "{}{}/{}",
+        PHYSNA_URI_SCHEME,
+        encode_segment(tenant),
+        TREE_SEGMENT
+    );
+    for segment in segments(path) {
+        uri.push('/');
+        uri.push_str(&encode_segment(segment));
+    }
+    uri
+}
+
+/// The tenant of a `physna://{tenant}/...` URI and the rest after its
+/// slash.
+pub(crate) fn split_tenant_uri(uri: &str) -> Option<(String, &str)> {
+    let rest = uri.strip_prefix(PHYSNA_URI_SCHEME)?;
+    let (tenant, rest) = rest.split_once('/')?;
+    let tenant = decode_segment(tenant).filter(|tenant| !tenant.is_empty())?;
+    // The tenant becomes a pcli2 argument of its own.
+    if tenant.starts_with('-') {
+        return None;
+    }
+    Some((tenant, rest))
+}
+
+/// The tenant and folder path (`""` for the top level) of a tree URI.
+pub fn parse_tree_uri(uri: &str) -> Option<(String, String)> {
+    let (tenant, rest) = split_tenant_uri(uri)?;
+    let folders = match rest.strip_prefix(TREE_SEGMENT)? {
+        "" => "",
+        folders => folders.strip_prefix('/')?,
+    };
+    let mut path = String::new();
+    for segment in folders.split('/').filter(|segment| !segment.is_empty()) {
+        let name = decode_segment(segment)?;
+        if name.contains('/') {
+            return None;
+        }
+        path.push('/');
+        path.push_str(&name);
+    }
+    Some((tenant, path))
+}
+
+fn folder_list_args(tenant: &str, path: &str) -> Value {
+    let mut args = json!({ "tenant": tenant, "format": "json" });
+    if !path.is_empty() {
+        args["folder_path"] = json!(path);
+    }
+    args
+}
+
+fn folder_rows(text: &str) -> Result<Vec<Value>, String> {
+    let mut output: Value = serde_json::from_str(text)
+        .map_err(|err| format!("pcli2 folder list returned invalid JSON: {}", err))?;
+    json_rows(&mut output)
+        .map(|rows| rows.to_vec())
+        .ok_or_else(|| "pcli2 folder list returned an unexpected JSON shape".to_string())
+}
+
+/// The folders of a `pcli2_folder_list` call with `args`, which answers
+/// from its cache when it can. `None` when the request may not call it.
+async fn list_folders(state: &AppState, args: Value) -> Result<Option<Vec<Value>>, String> {
+    let Some(result) = call_for_resource(state, FOLDER_LIST_TOOL, args).await? else {
+        return Ok(None);
+    };
+    let text = last_text(&result).ok_or("pcli2 folder list returned no output")?;
+    folder_rows(text).map(Some)
+}
+
+/// The direct subfolders of `path` among `folders`, including those only
+/// implied by the paths of deeper folders.
+fn node(tenant: &str, path: &str, folders: &[Value]) -> Value {
+    let mut children: Vec<(String, Option<&Value>)> = Vec::new();
+    for folder in folders {
+        let Some(known) = folder["path"].as_str().map(normalize) else {
+            continue;
+        };
+        let Some(below) = known
+            .strip_prefix(path)
+            .and_then(|rest| rest.strip_prefix('/'))
+        else {
+            continue;
+        };
+        let child = format!("{}/{}", path, below.split('/').next().unwrap_or_default());
+        let entry = (child == known).then_some(folder);
+        match children.iter_mut().find(|(existing, _)| *existing == child) {
+            Some(existing) => existing.1 = existing.1.or(entry),
+            None => children.push((child, entry)),
+        }
+    }
+    let children: Vec<Value> = children
+        .into_iter()
+        .map(|(child, folder)| {
+            let name = folder
+                .and_then(|folder| folder["name"].as_str())
+                .or_else(|| child.rsplit('/').next())
+                .unwrap_or_default();
+            json!({
+                "name": name,
+                "path": child,
+                "uuid": folder.and_then(|folder| folder.get("uuid")).cloned().unwrap_or(Value::Null),
+                "uri": tree_uri(tenant, &child)
+            })
+        })
+        .collect();
+    let total = children.len();
+    json!({
+        "tenant": tenant,
+        "path": if path.is_empty() { "/" } else { path },
+        "parent": (!path.is_empty()).then(|| tree_uri(tenant, &parent_of(path))),
+        "folders": children.into_iter().take(MAX_CHILDREN).collect::<Vec<_>>(),
+        "total": total
+    })
+}
+
+/// Reads a node of `tenant`'s folder tree: its direct subfolders, each
+/// with the URI to read to expand it. A cached listing of the whole tree
+/// answers every node; without one, the top level lists the whole tree
+/// and a folder lists just itself, on first read. Each read is a
+/// `pcli2_folder_list` call. `None` when the folder is not in the tree, or
+/// a client bound or held to other tenants, or one that may not call the
+/// tool, asks.
+pub async fn read_tree(
+    state: &AppState,
+    tenant: &str,
+    path: &str,
+) -> Result<Option<Value>, String> {
+    if state.tenant.as_deref().is_some_and(|bound| bound != tenant)
+        || !tenant_permitted(state, tenant)
+    {
+        return Ok(None);
+    }
+    let path = normalize(path);
+    // The whole listing answers every folder once it is cached.
+    let whole = folder_list_args(tenant, "");
+    let listed = path.is_empty()
+        || state
+            .cache
+            .get(FOLDER_LIST_TOOL, None, &whole)
+            .await
+            .is_some();
+    let args = if listed {
+        whole
+    } else {
+        folder_list_args(tenant, &path)
+    };
+    let Some(folders) = list_folders(state, args).await? else {
+        return Ok(None);
+    };
+    let prefix = format!("{}/", path);
+    let known = !listed
+        || path.is_empty()
+        || folders.iter().any(|folder| {
+            folder["path"].as_str().is_some_and(|known| {
+                let known = normalize(known);
+                known == path || known.starts_with(&prefix)
+            })
+        });
+    if !known {
+        return Ok(None);
+    }
+    let node = node(tenant, &path, &folders);
+    let uri = tree_uri(tenant, &path);
+    let text = serde_json::to_string_pretty(&node)
+        .map_err(|err| format!("Failed to render folder tree: {}", err))?;
+    Ok(Some(json!({
+        "contents": [{ "uri": uri, "mimeType": "application/json", "text": text }]
+    })))
+}
+
+/// `resources/list` entries for the top of each known tenant's tree: the
+/// tenant a client is bound to, else those of a cached tenant list. None
+/// for a client that may not call `pcli2_folder_list`.
+pub async fn tree_descriptors(state: &AppState) -> Vec<Value> {
+    if !tool_listed(state, FOLDER_LIST_TOOL) {
+        return Vec::new();
+    }
+    let tenants = match &state.tenant {
+        Some(bound) => vec![bound.clone()],
+        None => cached_tenants(state)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|tenant| tenant.name)
+            .filter(|tenant| tenant_permitted(state, tenant))
+            .collect(),
+    };
+    tenants
+        .iter()
+        .map(|tenant| {
+            json!({
+                "uri": tree_uri(tenant, ""),
+                "name": format!("{} folder tree", tenant),
+                "description": "Top-level folders; read a folder's URI to list its subfolders.",
+                "mimeType": "application/json"
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tree_uris_round_trip() {
+        let uri = tree_uri("acme", "/Root/Cast ings/50%");
+        assert_eq!(uri, "physna://acme/tree/Root/Cast%20ings/50%25");
+        assert_eq!(
+            parse_tree_uri(&uri),
+            Some(("acme".to_string(), "/Root/Cast ings/50%".to_string()))
+        );
+        assert_eq!(
+            parse_tree_uri("physna://acme/tree"),
+            Some(("acme".to_string(), String::new()))
+        );
+        assert!(parse_tree_uri("physna://acme/trees").is_none());
+        assert!(parse_tree_uri("physna://-v/tree").is_none());
+        assert!(parse_tree_uri("physna://acme/tree/a%2Fb").is_none());
+        assert!(parse_tree_uri("physna://acme/tree/%zz").is_none());
+    }
+
+    #[test]
+    fn test_node_lists_direct_subfolders() {
+        let folders = vec![
+            json!({ "name": "Root", "path": "/Root", "uuid": "r" }),
+            json!({ "name": "Castings", "path": "/Root/Castings", "uuid": "c" }),
+            json!({ "name": "Brackets", "path": "/Root/Castings/Brackets", "uuid": "b" }),
+            json!({ "path": "/Root/Forgings/" }),
+            json!({ "name": "Old", "path": "/Archive/2019/Old" }),
+        ];
+        let top = node("acme", "", &folders);
+        assert_eq!(top["path"], "/");
+        assert_eq!(top["parent"], Value::Null);
+        assert_eq!(top["folders"][0]["uri"], "physna://acme/tree/Root");
+        assert_eq!(top["folders"][1]["name"], "Archive");
+        assert_eq!(top["folders"][1]["uuid"], Value::Null);
+
+        let root = node("acme", "/Root", &folders);
+        assert_eq!(root["total"], 2);
+        assert_eq!(root["parent"], "physna://acme/tree");
+        assert_eq!(root["folders"][0]["name"], "Castings");
+        assert_eq!(root["folders"][1]["name"], "Forgings");
+        assert_eq!(root["folders"][1]["path"], "/Root/Forgings");
+    }
+}
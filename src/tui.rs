@@ -0,0 +1,456 @@
+use crate::{
+    AppState,
+    activity::{ActivitySnapshot, FinishedCall, RunningCallInfo},
+};
+use anyhow::{Result, anyhow};
+use std::{
+    collections::VecDeque,
+    io::{self, IsTerminal, Read, Write},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::mpsc;
+
+/// Log lines kept for the status view while it owns the terminal.
+pub const LOG_LINES: usize = 200;
+const REFRESH: Duration = Duration::from_millis(500);
+const KEY_HELP: &str = "↑/↓ select · c cancel selected call · q quit";
+
+/// Collects log output while `serve --tui` draws on the terminal.
+#[derive(Clone, Default)]
+pub struct LogBuffer {
+    log: Arc<Mutex<Log>>,
+}
+
+#[derive(Default)]
+struct Log {
+    lines: VecDeque<String>,
+    partial: String,
+}
+
+impl LogBuffer {
+    pub fn lines(&self) -> Vec<String> {
+        let log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+        log.lines.iter().cloned().collect()
+    }
+}
+
+impl Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+        log.partial.push_str(&String::from_utf8_lossy(buf));
+        while let Some(end) = log.partial.find('\n') {
+            let line: String = log.partial.drain(..=end).collect();
+            if !line.trim().is_empty() {
+                log.lines.push_back(line.trim_end().to_string());
+            }
+        }
+        while log.lines.len() > LOG_LINES {
+            log.lines.pop_front();
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// What the status view shows besides the call activity.
+pub struct Frame<'a> {
+    pub title: &'a str,
+    pub activity: &'a ActivitySnapshot,
+    pub stored_results: (usize, usize),
+    pub waiting_retries: usize,
+    pub logs: &'a [String],
+    pub selected: usize,
+    pub status: &'a str,
+}
+
+fn clock(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+fn size(bytes: usize) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1_048_576 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+fn fit(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut cut: String = text.chars().take(width.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
+
+fn running_row(call: &RunningCallInfo, selected: bool) -> String {
+    format!(
+        "{} {:<6} {:<36} {:<16} {}",
+        if selected { ">" } else { " " },
+        call.id,
+        fit(&call.tool, 36),
+        fit(call.tenant.as_deref().unwrap_or("-"), 16),
+        clock(call.elapsed)
+    )
+}
+
+fn finished_row(call: &FinishedCall) -> String {
+    format!(
+        "  {:<36} {:<16} {}  {}",
+        fit(&call.tool, 36),
+        fit(call.tenant.as_deref().unwrap_or("-"), 16),
+        clock(call.duration),
+        match &call.error {
+            Some(error) => format!("✗ {}", error),
+            None => "✓".to_string(),
+        }
+    )
+}
+
+/// Lays out one screen, `height` lines of at most `width` characters.
+pub fn render(frame: &Frame, width: usize, height: usize) -> Vec<String> {
+    let activity = frame.activity;
+    let mut lines = vec![
+        frame.title.to_string(),
+        format!(
+            "Calls: {} running · {} finished · {} failed    Stored results: {} ({})    Waiting retries: {}",
+            activity.running.len(),
+            activity.completed,
+            activity.failed,
+            frame.stored_results.0,
+            size(frame.stored_results.1),
+            frame.waiting_retries
+        ),
+        String::new(),
+        format!(
+            "RUNNING  {:<6} {:<36} {:<16} ELAPSED",
+            "ID", "TOOL", "TENANT"
+        ),
+    ];
+    if activity.running.is_empty() {
+        lines.push("  (none)".to_string());
+    }
+    for (index, call) in activity.running.iter().enumerate() {
+        lines.push(running_row(call, index == frame.selected));
+    }
+    lines.push(String::new());
+    lines.push("RECENT".to_string());
+    for call in activity.recent.iter().take(8) {
+        lines.push(finished_row(call));
+    }
+    if !activity.errors.is_empty() {
+        lines.push(String::new());
+        let errors: Vec<String> = activity
+            .errors
+            .iter()
+            .map(|(tool, count)| format!("{} {}", tool, count))
+            .collect();
+        lines.push(format!("ERRORS   {}", errors.join(" · ")));
+    }
+    lines.push(String::new());
+    lines.push("LOG".to_string());
+    // Log lines fill whatever room is left above the two footer lines.
+    let room = height.saturating_sub(lines.len() + 2);
+    let skip = frame.logs.len().saturating_sub(room);
+    lines.extend(frame.logs[skip..].iter().map(|line| format!("  {}", line)));
+    while lines.len() + 2 < height {
+        lines.push(String::new());
+    }
+    lines.push(frame.status.to_string());
+    lines.push(KEY_HELP.to_string());
+    lines.truncate(height.max(1));
+    lines.iter().map(|line| fit(line, width.max(1))).collect()
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Key {
+    Up,
+    Down,
+    Cancel,
+    Quit,
+    Other,
+}
+
+fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let mut index = 0;
+    while index < bytes.len() {
+        let key = match &bytes[index..] {
+            [0x1b, b'[', b'A', ..] => {
+                index += 2;
+                Key::Up
+            }
+            [0x1b, b'[', b'B', ..] => {
+                index += 2;
+                Key::Down
+            }
+            [b'k', ..] => Key::Up,
+            [b'j', ..] => Key::Down,
+            [b'c', ..] => Key::Cancel,
+            [b'q', ..] | [0x03, ..] => Key::Quit,
+            _ => Key::Other,
+        };
+        keys.push(key);
+        index += 1;
+    }
+    keys
+}
+
+/// Puts the terminal in raw mode on an alternate screen and restores it on drop.
+#[cfg(unix)]
+struct RawTerminal {
+    original: libc::termios,
+}
+
+#[cfg(unix)]
+impl RawTerminal {
+    fn enter() -> Result<Self> {
+        let mut original = std::mem::MaybeUninit::<libc::termios>::uninit();
+        // SAFETY: tcgetattr fills the termios struct for a valid fd.
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, original.as_mut_ptr()) } != 0 {
+            return Err(anyhow!(
+                "Failed to read terminal settings: {}",
+                io::Error::last_os_error()
+            ));
+        }
+        // SAFETY: initialized by the successful tcgetattr above.
+        let original = unsafe { original.assume_init() };
+        let mut raw = original;
+        // Ctrl+C arrives as a key so the terminal is restored before exiting.
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        // SAFETY: raw is a valid termios copied from the current settings.
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) } != 0 {
+            return Err(anyhow!(
+                "Failed to switch the terminal to raw mode: {}",
+                io::Error::last_os_error()
+            ));
+        }
+        print!("\x1b[?1049h\x1b[?25l");
+        let _ = io::stdout().flush();
+        Ok(Self { original })
+    }
+
+    fn size() -> (usize, usize) {
+        let mut size = std::mem::MaybeUninit::<libc::winsize>::zeroed();
+        // SAFETY: TIOCGWINSZ writes a winsize for a terminal fd.
+        let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, size.as_mut_ptr()) };
+        // SAFETY: zero-initialized, and filled in when the ioctl succeeds.
+        let size = unsafe { size.assume_init() };
+        if ok != 0 || size.ws_col == 0 || size.ws_row == 0 {
+            return (100, 30);
+        }
+        (size.ws_col as usize, size.ws_row as usize)
+    }
+}
+
+#[cfg(unix)]
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+        // SAFETY: restores the settings read in `enter`.
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+    }
+}
+
+/// Checks that `serve --tui` can take over the terminal.
+pub fn check_terminal() -> Result<()> {
+    if !cfg!(unix) {
+        return Err(anyhow!("serve --tui is only supported on Unix terminals"));
+    }
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return Err(anyhow!("serve --tui needs an interactive terminal"));
+    }
+    Ok(())
+}
+
+/// Draws the status view until the operator quits with `q` or Ctrl+C.
+#[cfg(unix)]
+pub async fn run_tui(state: AppState, title: String, logs: LogBuffer) -> Result<()> {
+    let terminal = RawTerminal::enter()?;
+    let (keys_tx, mut keys) = mpsc::unbounded_channel();
+    // Reading stdin blocks, so it gets its own thread; it ends with the process.
+    std::thread::spawn(move || {
+        let mut stdin = io::stdin();
+        let mut buf = [0u8; 64];
+        while let Ok(read) = stdin.read(&mut buf) {
+            if read == 0 || keys_tx.send(buf[..read].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut ticker = tokio::time::interval(REFRESH);
+    let mut selected = 0usize;
+    let mut status = String::new();
+    loop {
+        let activity = state.calls.snapshot();
+        selected = selected.min(activity.running.len().saturating_sub(1));
+        let (width, height) = RawTerminal::size();
+        let logs = logs.lines();
+        let lines = render(
+            &Frame {
+                title: &title,
+                activity: &activity,
+                stored_results: state.resources.stats(),
+                waiting_retries: state.inflight.len(),
+                logs: &logs,
+                selected,
+                status: &status,
+            },
+            width,
+            height,
+        );
+        print!("\x1b[H\x1b[2J{}", lines.join("\r\n"));
+        let _ = io::stdout().flush();
+
+        tokio::select! {
+            _ = ticker.tick() => {}
+            input = keys.recv() => {
+                let Some(input) = input else { break };
+                for key in parse_keys(&input) {
+                    match key {
+                        Key::Up => selected = selected.saturating_sub(1),
+                        Key::Down => selected += 1,
+                        Key::Cancel => {
+                            status = match activity.running.get(selected) {
+                                Some(call) if state.calls.cancel(call.id) => {
+                                    format!("Cancelled call {} ({})", call.id, call.tool)
+                                }
+                                Some(call) => format!("Call {} already finished", call.id),
+                                None => "No running call selected".to_string(),
+                            };
+                        }
+                        Key::Quit => {
+                            drop(terminal);
+                            return Ok(());
+                        }
+                        Key::Other => {}
+                    }
+                }
+            }
+        }
+    }
+    drop(terminal);
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub async fn run_tui(_state: AppState, _title: String, _logs: LogBuffer) -> Result<()> {
+    check_terminal()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_keys() {
+        assert_eq!(
+            parse_keys(b"\x1b[A\x1b[Bjkcq\x03x"),
+            [
+                Key::Up,
+                Key::Down,
+                Key::Down,
+                Key::Up,
+                Key::Cancel,
+                Key::Quit,
+                Key::Quit,
+                Key::Other
+            ]
+        );
+    }
+
+    #[test]
+    fn test_log_buffer_keeps_last_lines() {
+        let mut logs = LogBuffer::default();
+        for index in 0..LOG_LINES + 5 {
+            writeln!(logs, "line {}", index).unwrap();
+        }
+        let lines = logs.lines();
+        assert_eq!(lines.len(), LOG_LINES);
+        assert_eq!(lines[0], "line 5");
+    }
+
+    #[test]
+    fn test_render_fits_the_screen() {
+        let activity = ActivitySnapshot {
+            running: vec![
+                RunningCallInfo {
+                    id: 7,
+                    tool: "pcli2_folder_geometric_match".to_string(),
+                    tenant: Some("acme".to_string()),
+                    elapsed: Duration::from_secs(3723),
+                },
+                RunningCallInfo {
+                    id: 8,
+                    tool: "pcli2_asset_list".to_string(),
+                    tenant: None,
+                    elapsed: Duration::from_secs(2),
+                },
+            ],
+            recent: vec![FinishedCall {
+                tool: "pcli2_asset_get".to_string(),
+                tenant: None,
+                duration: Duration::from_secs(1),
+                error: Some("pcli2 asset get failed (code exit status: 1):".to_string()),
+            }],
+            completed: 5,
+            failed: 1,
+            errors: vec![("pcli2_asset_get".to_string(), 1)],
+        };
+        let logs: Vec<String> = (0..50).map(|index| format!("log {}", index)).collect();
+        let lines = render(
+            &Frame {
+                title: "pcli2-mcp",
+                activity: &activity,
+                stored_results: (2, 2048),
+                waiting_retries: 0,
+                logs: &logs,
+                selected: 1,
+                status: "",
+            },
+            120,
+            24,
+        );
+        assert_eq!(lines.len(), 24);
+        assert!(lines.iter().all(|line| line.chars().count() <= 120));
+        assert!(lines[1].contains("2 running · 5 finished · 1 failed"));
+        assert!(lines[4].starts_with("  7      pcli2_folder_geometric_match"));
+        assert!(lines[4].ends_with("01:02:03"));
+        assert!(lines[5].starts_with("> 8"));
+        assert!(
+            lines
+                .iter()
+                .any(|line| line.contains("✗ pcli2 asset get failed"))
+        );
+        assert!(lines.iter().any(|line| line == "  log 49"));
+        assert_eq!(lines[23], KEY_HELP);
+
+        let narrow = render(
+            &Frame {
+                title: "pcli2-mcp",
+                activity: &activity,
+                stored_results: (0, 0),
+                waiting_retries: 0,
+                logs: &logs,
+                selected: 0,
+                status: "",
+            },
+            30,
+            8,
+        );
+        assert_eq!(narrow.len(), 8);
+        assert!(narrow.iter().all(|line| line.chars().count() <= 30));
+    }
+}
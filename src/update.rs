@@ -0,0 +1,210 @@
+use crate::{
+    cli::{ARG_CHECK, ARG_VERSION},
+    install::{artifact_name, download, fetch_release, find_file, release_target, release_url},
+};
+use anyhow::{Context, Result, anyhow};
+use clap::ArgMatches;
+use serde_json::Value;
+use std::{env, fs, path::Path};
+use tokio::process::Command;
+
+pub const REPOSITORY: &str = "jchultarsky101/pcli2-mcp";
+const APP_NAME: &str = env!("CARGO_PKG_NAME");
+const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+const BINARY_FILE_NAME: &str = if cfg!(windows) {
+    "pcli2-mcp.exe"
+} else {
+    "pcli2-mcp"
+};
+
+/// `major.minor.patch` of a release tag or version, ignoring a leading `v`
+/// and any pre-release or build suffix.
+fn parse_version(text: &str) -> Option<(u64, u64, u64)> {
+    let core = text
+        .trim()
+        .trim_start_matches('v')
+        .split(['-', '+'])
+        .next()?;
+    let mut parts = core.split('.').map(|part| part.parse::<u64>().ok());
+    let version = (parts.next()??, parts.next()??, parts.next()??);
+    parts.next().is_none().then_some(version)
+}
+
+fn is_newer(candidate: &str, current: &str) -> bool {
+    match (parse_version(candidate), parse_version(current)) {
+        (Some(candidate), Some(current)) => candidate > current,
+        _ => false,
+    }
+}
+
+/// Version of the latest published release, from the GitHub API.
+async fn latest_version(work: &Path) -> Result<String> {
+    let path = work.join("latest.json");
+    download(
+        &format!(
+            "https://api.github.com/repos/{}/releases/latest",
+            REPOSITORY
+        ),
+        &path,
+    )
+    .await?;
+    let release: Value = serde_json::from_str(&fs::read_to_string(&path)?)
+        .context("GitHub returned an invalid release description")?;
+    let tag = release["tag_name"]
+        .as_str()
+        .ok_or_else(|| anyhow!("The latest release has no tag"))?;
+    if parse_version(tag).is_none() {
+        return Err(anyhow!("The latest release tag '{}' is not a version", tag));
+    }
+    Ok(tag.trim_start_matches('v').to_string())
+}
+
+/// Replaces `exe` with `binary`. The new file is written next to `exe` and
+/// renamed over it, so a server starting meanwhile never sees half a
+/// binary. Windows cannot replace a running executable, so the old one is
+/// moved aside to `.old` first.
+fn swap(binary: &Path, exe: &Path) -> Result<()> {
+    let dir = exe
+        .parent()
+        .ok_or_else(|| anyhow!("Invalid executable path {}", exe.display()))?;
+    let staged = dir.join(format!(".{}.partial", BINARY_FILE_NAME));
+    fs::copy(binary, &staged).with_context(|| {
+        format!(
+            "Cannot write to {}; run self-update as a user who can, or reinstall",
+            dir.display()
+        )
+    })?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))?;
+    }
+    #[cfg(windows)]
+    {
+        let old = exe.with_extension("exe.old");
+        let _ = fs::remove_file(&old);
+        fs::rename(exe, &old).with_context(|| format!("Failed to move {} aside", exe.display()))?;
+    }
+    fs::rename(&staged, exe).with_context(|| format!("Failed to replace {}", exe.display()))
+}
+
+async fn update(version: Option<&str>, check: bool, work: &Path) -> Result<()> {
+    let target = release_target().ok_or_else(|| {
+        anyhow!(
+            "No pcli2-mcp release is published for {}/{}; build it from source",
+            env::consts::OS,
+            env::consts::ARCH
+        )
+    })?;
+    let wanted = match version {
+        Some(version) => {
+            let version = version.trim_start_matches('v');
+            if parse_version(version).is_none() {
+                return Err(anyhow!("'{}' is not a release version", version));
+            }
+            version.to_string()
+        }
+        None => latest_version(work).await?,
+    };
+    if wanted == APP_VERSION || (version.is_none() && !is_newer(&wanted, APP_VERSION)) {
+        println!(
+            "pcli2-mcp {} is up to date (latest release: {})",
+            APP_VERSION, wanted
+        );
+        return Ok(());
+    }
+    let artifact = artifact_name(APP_NAME, target);
+    let exe = env::current_exe()
+        .and_then(fs::canonicalize)
+        .context("Cannot find the running pcli2-mcp executable")?;
+    if check {
+        println!(
+            "pcli2-mcp {} is available (running {})",
+            wanted, APP_VERSION
+        );
+        println!(
+            "It would replace {} with {}",
+            exe.display(),
+            release_url(REPOSITORY, Some(&wanted), &artifact)
+        );
+        return Ok(());
+    }
+
+    let extracted = fetch_release(REPOSITORY, Some(&wanted), &artifact, work).await?;
+    let binary = find_file(&extracted, BINARY_FILE_NAME)
+        .ok_or_else(|| anyhow!("{} does not contain {}", artifact, BINARY_FILE_NAME))?;
+    let output = Command::new(&binary)
+        .arg("--version")
+        .output()
+        .await
+        .with_context(|| format!("The downloaded {} does not run", BINARY_FILE_NAME))?;
+    let reported = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success()
+        || parse_version(reported.trim_start_matches(APP_NAME)) != parse_version(&wanted)
+    {
+        return Err(anyhow!(
+            "The downloaded binary reports '{}' instead of pcli2-mcp {}",
+            reported.trim(),
+            wanted
+        ));
+    }
+    swap(&binary, &exe)?;
+    println!(
+        "Updated pcli2-mcp {} to {} at {}",
+        APP_VERSION,
+        wanted,
+        exe.display()
+    );
+    println!("Restart running servers to use it");
+    Ok(())
+}
+
+pub async fn run_self_update(matches: &ArgMatches) -> Result<()> {
+    let version = matches.get_one::<String>(ARG_VERSION).map(String::as_str);
+    let check = matches.get_flag(ARG_CHECK);
+    let work = env::temp_dir().join(format!("pcli2-mcp-update-{}", std::process::id()));
+    fs::create_dir_all(&work)?;
+    let result = update(version, check, &work).await;
+    let _ = fs::remove_dir_all(&work);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versions_compare_numerically() {
+        assert_eq!(parse_version("v0.1.10"), Some((0, 1, 10)));
+        assert_eq!(parse_version(" 0.2.0-rc.1\n"), Some((0, 2, 0)));
+        assert_eq!(parse_version("0.2"), None);
+        assert_eq!(parse_version("0.2.0.1"), None);
+        assert_eq!(parse_version("latest"), None);
+        assert!(is_newer("0.1.10", "0.1.9"));
+        assert!(!is_newer("0.1.9", "0.1.9"));
+        assert!(!is_newer("0.1.8", "0.1.9"));
+        assert!(!is_newer("nightly", "0.1.9"));
+    }
+
+    #[test]
+    fn test_swap_replaces_the_executable() {
+        let dir = env::temp_dir().join(format!("pcli2-mcp-swap-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let exe = dir.join(BINARY_FILE_NAME);
+        let binary = dir.join("new");
+        fs::write(&exe, b"old").unwrap();
+        fs::write(&binary, b"new").unwrap();
+        swap(&binary, &exe).unwrap();
+        assert_eq!(fs::read(&exe).unwrap(), b"new");
+        assert!(!dir.join(format!(".{}.partial", BINARY_FILE_NAME)).exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(
+                fs::metadata(&exe).unwrap().permissions().mode() & 0o777,
+                0o755
+            );
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
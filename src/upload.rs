@@ -0,0 +1,156 @@
+use crate::{
+    AppState,
+    image::{ScratchFile, decode_base64, scratch_path},
+    jobs::{job_file, parse_job_uri},
+    pcli::{call_workspace, run_pcli2_asset_create},
+    resources::MAX_STORED_RESOURCE_BYTES,
+};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64_STANDARD};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+pub const UPLOAD_TOOL: &str = "pcli2_asset_upload";
+/// Where the file to upload comes from; a call names exactly one.
+pub const UPLOAD_SOURCES: [&str; 3] = ["content", "resource", "file"];
+/// Largest file the tool uploads from `content` or a resource.
+pub const MAX_UPLOAD_BYTES: usize = MAX_STORED_RESOURCE_BYTES;
+
+/// The name the uploaded file is given, which `pcli2` makes the asset's
+/// name: `name`, else the name the source already has.
+fn file_name(args: &Value, default: Option<&str>) -> Result<String, String> {
+    let name = args
+        .get("name")
+        .and_then(Value::as_str)
+        .or(default)
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| "Missing required argument: 'name'".to_string())?;
+    if matches!(name, "." | "..") || name.contains(['/', '\\']) {
+        return Err(format!(
+            "Invalid 'name': '{}' must be a file name, not a path",
+            name
+        ));
+    }
+    if name.chars().any(char::is_control) {
+        return Err("Invalid 'name': control characters are not allowed".to_string());
+    }
+    Ok(name.to_string())
+}
+
+/// The contents and name of a `pcli2://results/...` or `job://` resource
+/// the request may read.
+async fn resource_bytes(state: &AppState, uri: &str) -> Result<(Vec<u8>, String), String> {
+    let not_found = || format!("Invalid 'resource': resource '{}' not found", uri);
+    if let Some((id, file)) = parse_job_uri(uri) {
+        let (bytes, _) = job_file(state, &id, &file)?.ok_or_else(not_found)?;
+        let name = file.rsplit('/').next().unwrap_or(&file).to_string();
+        return Ok((bytes, name));
+    }
+    let resource = state
+        .resources
+        .fetch(uri)
+        .await
+        .filter(|resource| resource.visible_to(state.tenant.as_deref()))
+        .ok_or_else(not_found)?;
+    let bytes = if resource.blob {
+        BASE64_STANDARD
+            .decode(resource.text.as_bytes())
+            .map_err(|err| format!("Failed to decode resource '{}': {}", uri, err))?
+    } else {
+        resource.text.as_bytes().to_vec()
+    };
+    Ok((bytes, resource.name))
+}
+
+/// The server-local file named by `file`, which only clients not bound to
+/// a tenant may upload.
+fn local_file(state: &AppState, args: &Value, file: &str) -> Result<PathBuf, String> {
+    if state.tenant.is_some() {
+        return Err(
+            "Invalid 'file': clients bound to a tenant upload 'content' or a 'resource'"
+                .to_string(),
+        );
+    }
+    if args.get("name").is_some() {
+        return Err("Invalid 'name': a local 'file' is uploaded under its own name".to_string());
+    }
+    let path = Path::new(file);
+    if !path.is_file() {
+        return Err(format!("Invalid 'file': '{}' is not a file", file));
+    }
+    Ok(path.to_path_buf())
+}
+
+/// Uploads a file to `folder_path` with `pcli2 asset create`. Decoded
+/// `content` and resources are written to a directory beside the call's
+/// other files, under the upload's name, and removed afterwards.
+pub async fn upload_asset(state: &AppState, args: Value) -> Result<String, String> {
+    let given: Vec<&str> = UPLOAD_SOURCES
+        .into_iter()
+        .filter(|source| args.get(*source).is_some_and(|value| !value.is_null()))
+        .collect();
+    let [source] = given.as_slice() else {
+        return Err("Expected exactly one of 'content', 'resource', or 'file'".to_string());
+    };
+    let value = args
+        .get(*source)
+        .and_then(Value::as_str)
+        .filter(|value| !value.trim().is_empty())
+        .ok_or_else(|| format!("Invalid '{}': expected a non-empty string", source))?;
+    let (bytes, name) = match *source {
+        "file" => {
+            let path = local_file(state, &args, value)?;
+            return run_pcli2_asset_create(&args, &path).await;
+        }
+        "content" => {
+            let (bytes, _) = decode_base64("content", value, MAX_UPLOAD_BYTES)?;
+            (bytes, file_name(&args, None)?)
+        }
+        _ => {
+            let (bytes, name) = resource_bytes(state, value).await?;
+            (bytes, file_name(&args, Some(&name))?)
+        }
+    };
+    if bytes.len() > MAX_UPLOAD_BYTES {
+        return Err(format!(
+            "Invalid '{}': larger than the {} bytes allowed",
+            source, MAX_UPLOAD_BYTES
+        ));
+    }
+    let dir = ScratchFile(scratch_path(
+        &state.config,
+        call_workspace().as_deref(),
+        "pcli2-upload-",
+        "",
+    )?);
+    tokio::fs::create_dir_all(&dir.0)
+        .await
+        .map_err(|err| format!("Failed to create {}: {}", dir.0.display(), err))?;
+    let file = dir.0.join(&name);
+    tokio::fs::write(&file, bytes)
+        .await
+        .map_err(|err| format!("Failed to write {}: {}", file.display(), err))?;
+    run_pcli2_asset_create(&args, &file).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_upload_names_are_file_names() {
+        assert_eq!(
+            file_name(&json!({ "name": "Bracket.stl" }), None),
+            Ok("Bracket.stl".to_string())
+        );
+        assert_eq!(
+            file_name(&json!({}), Some("part 1.step")),
+            Ok("part 1.step".to_string())
+        );
+        assert!(file_name(&json!({}), None).is_err());
+        assert!(file_name(&json!({ "name": "../Bracket.stl" }), None).is_err());
+        assert!(file_name(&json!({ "name": ".." }), None).is_err());
+        assert!(file_name(&json!({ "name": "a\nb.stl" }), None).is_err());
+    }
+}
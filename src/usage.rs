@@ -0,0 +1,454 @@
+use crate::{AppState, config::UsageConfig};
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+/// Key that calls made without an API key are accounted to.
+pub const ANONYMOUS_KEY: &str = "anonymous";
+/// Sessions whose usage is kept; the one idle the longest goes first.
+const MAX_SESSIONS: usize = 1000;
+/// Subcommand words that name a Physna operation, e.g. `asset geometric-match`.
+const OPERATION_WORDS: usize = 3;
+
+/// What tool calls used: how many were made, what their `pcli2` runs cost,
+/// and which Physna operations they ran.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct Usage {
+    pub calls: u64,
+    pub failed_calls: u64,
+    /// `pcli2` processes started, retries included.
+    pub runs: u64,
+    /// CPU time of those processes, where the backend reports it.
+    pub cpu_seconds: f64,
+    /// `pcli2` output plus the files it downloaded.
+    pub bytes_downloaded: u64,
+    /// `pcli2` runs by subcommand.
+    pub operations: BTreeMap<String, u64>,
+    /// Calls by tool.
+    pub tools: BTreeMap<String, u64>,
+}
+
+impl Usage {
+    fn add(&mut self, other: &Usage) {
+        self.calls += other.calls;
+        self.failed_calls += other.failed_calls;
+        self.runs += other.runs;
+        self.cpu_seconds += other.cpu_seconds;
+        self.bytes_downloaded += other.bytes_downloaded;
+        for (operation, count) in &other.operations {
+            *self.operations.entry(operation.clone()).or_default() += count;
+        }
+        for (tool, count) in &other.tools {
+            *self.tools.entry(tool.clone()).or_default() += count;
+        }
+    }
+}
+
+/// The Physna operation a `pcli2` run counts as: its subcommand words up to
+/// the first flag.
+pub fn operation(args: &[String]) -> String {
+    let words: Vec<&str> = args
+        .iter()
+        .take_while(|arg| !arg.starts_with('-'))
+        .take(OPERATION_WORDS)
+        .map(String::as_str)
+        .collect();
+    if words.is_empty() {
+        args.first()
+            .map(|arg| arg.trim_start_matches('-').to_string())
+            .unwrap_or_default()
+    } else {
+        words.join(" ")
+    }
+}
+
+/// What one tool call's `pcli2` runs used so far, shared by every run of the
+/// call, including those of the tasks it spawns.
+#[derive(Clone, Default)]
+pub struct CallUsage {
+    usage: Arc<Mutex<Usage>>,
+}
+
+impl CallUsage {
+    pub fn record_run(&self, args: &[String], cpu: Option<Duration>, output_bytes: usize) {
+        let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        usage.runs += 1;
+        usage.cpu_seconds += cpu.map_or(0.0, |cpu| cpu.as_secs_f64());
+        usage.bytes_downloaded += output_bytes as u64;
+        *usage.operations.entry(operation(args)).or_default() += 1;
+    }
+
+    /// Counts a file `pcli2` wrote for the call.
+    pub fn record_download(&self, bytes: u64) {
+        let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        usage.bytes_downloaded += bytes;
+    }
+
+    /// The call's usage once it finished as `ok` or not.
+    pub fn finish(&self, tool: &str, ok: bool) -> Usage {
+        let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        usage.calls = 1;
+        usage.failed_calls = u64::from(!ok);
+        usage.tools.insert(tool.to_string(), 1);
+        usage
+    }
+}
+
+#[derive(Default)]
+struct Ledger {
+    /// Usage of each session since the server started, with when it was
+    /// last used.
+    sessions: HashMap<String, (Usage, u64)>,
+    ticks: u64,
+    /// Usage of each key by month (`YYYY-MM`).
+    months: BTreeMap<String, BTreeMap<String, Usage>>,
+    /// Months changed since their report was written.
+    unsaved: Vec<String>,
+}
+
+/// The usage report of a month, as written to `usage.report_dir`.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[schema(as = UsageReport)]
+pub struct MonthReport {
+    #[schema(pattern = "^[0-9]{4}-[0-9]{2}$")]
+    month: String,
+    total: Usage,
+    /// Usage by API key.
+    keys: BTreeMap<String, Usage>,
+}
+
+/// This month's report with the usage of each session the server holds,
+/// as `GET /admin/usage` shows it.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CurrentUsage {
+    #[serde(flatten)]
+    report: MonthReport,
+    sessions: BTreeMap<String, Usage>,
+}
+
+/// Usage of the calls made through the server, by session and by API key
+/// (`api_keys[].name`), and by month for the keys.
+#[derive(Clone, Default)]
+pub struct UsageLedger {
+    ledger: Arc<Mutex<Ledger>>,
+    report_dir: Option<PathBuf>,
+}
+
+impl UsageLedger {
+    /// A ledger that writes its monthly reports to `usage.report_dir`,
+    /// picking up the months already reported there.
+    pub fn open(config: &UsageConfig) -> Result<Self, String> {
+        let Some(dir) = &config.report_dir else {
+            return Ok(Self::default());
+        };
+        fs::create_dir_all(dir)
+            .map_err(|err| format!("Failed to create {}: {}", dir.display(), err))?;
+        let mut ledger = Ledger::default();
+        let entries = fs::read_dir(dir)
+            .map_err(|err| format!("Failed to read {}: {}", dir.display(), err))?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(month) = report_month(&path) else {
+                continue;
+            };
+            match fs::read(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|bytes| {
+                    serde_json::from_slice::<MonthReport>(&bytes).map_err(|err| err.to_string())
+                }) {
+                Ok(report) => {
+                    ledger.months.insert(month, report.keys);
+                }
+                Err(err) => warn!("⚠ ignoring usage report {}: {}", path.display(), err),
+            }
+        }
+        Ok(Self {
+            ledger: Arc::new(Mutex::new(ledger)),
+            report_dir: Some(dir.clone()),
+        })
+    }
+
+    /// Adds a finished call's usage to its session and, for the current
+    /// month, its key.
+    pub fn record(&self, session: &str, key: Option<&str>, usage: &Usage) {
+        self.record_in(&current_month(), session, key, usage);
+    }
+
+    fn record_in(&self, month: &str, session: &str, key: Option<&str>, usage: &Usage) {
+        let mut ledger = self.ledger.lock().unwrap_or_else(|e| e.into_inner());
+        ledger.ticks += 1;
+        let tick = ledger.ticks;
+        let entry = ledger.sessions.entry(session.to_string()).or_default();
+        entry.0.add(usage);
+        entry.1 = tick;
+        if ledger.sessions.len() > MAX_SESSIONS
+            && let Some(idle) = ledger
+                .sessions
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(session, _)| session.clone())
+        {
+            ledger.sessions.remove(&idle);
+        }
+        ledger
+            .months
+            .entry(month.to_string())
+            .or_default()
+            .entry(key.unwrap_or(ANONYMOUS_KEY).to_string())
+            .or_default()
+            .add(usage);
+        if !ledger.unsaved.iter().any(|unsaved| unsaved == month) {
+            ledger.unsaved.push(month.to_string());
+        }
+    }
+
+    /// `GET /admin/usage`: the current month by key, and every session the
+    /// server still holds.
+    pub fn current(&self) -> CurrentUsage {
+        let month = current_month();
+        let ledger = self.ledger.lock().unwrap_or_else(|e| e.into_inner());
+        let report = match ledger.months.get(&month) {
+            Some(keys) => month_report(&month, keys),
+            None => month_report(&month, &BTreeMap::new()),
+        };
+        let sessions = ledger
+            .sessions
+            .iter()
+            .map(|(session, (usage, _))| (session.clone(), usage.clone()))
+            .collect();
+        CurrentUsage { report, sessions }
+    }
+
+    /// `GET /admin/usage/{month}`: a month by key, or `None` when nothing
+    /// was recorded in it.
+    pub fn month_json(&self, month: &str) -> Option<Value> {
+        let ledger = self.ledger.lock().unwrap_or_else(|e| e.into_inner());
+        let keys = ledger.months.get(month)?;
+        Some(json!(month_report(month, keys)))
+    }
+
+    /// Writes the reports of the months that changed since the last save.
+    /// Returns how many were written.
+    pub fn save(&self) -> usize {
+        let Some(dir) = &self.report_dir else {
+            return 0;
+        };
+        let reports: Vec<MonthReport> = {
+            let mut ledger = self.ledger.lock().unwrap_or_else(|e| e.into_inner());
+            let unsaved = std::mem::take(&mut ledger.unsaved);
+            unsaved
+                .iter()
+                .filter_map(|month| {
+                    let keys = ledger.months.get(month)?;
+                    Some(month_report(month, keys))
+                })
+                .collect()
+        };
+        let mut written = 0;
+        for report in &reports {
+            match write_report(dir, report) {
+                Ok(()) => written += 1,
+                Err(err) => {
+                    warn!(
+                        "⚠ could not write the {} usage report to {}: {}",
+                        report.month,
+                        dir.display(),
+                        err
+                    );
+                    let mut ledger = self.ledger.lock().unwrap_or_else(|e| e.into_inner());
+                    ledger.unsaved.push(report.month.clone());
+                }
+            }
+        }
+        written
+    }
+}
+
+fn month_report(month: &str, keys: &BTreeMap<String, Usage>) -> MonthReport {
+    let mut total = Usage::default();
+    for usage in keys.values() {
+        total.add(usage);
+    }
+    MonthReport {
+        month: month.to_string(),
+        total,
+        keys: keys.clone(),
+    }
+}
+
+fn current_month() -> String {
+    Utc::now().format("%Y-%m").to_string()
+}
+
+/// Whether `month` reads as `YYYY-MM`.
+pub fn valid_month(month: &str) -> bool {
+    month.len() == 7 && NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d").is_ok()
+}
+
+/// The month of a `usage-YYYY-MM.json` report.
+fn report_month(path: &Path) -> Option<String> {
+    let month = path
+        .file_name()?
+        .to_str()?
+        .strip_prefix("usage-")?
+        .strip_suffix(".json")?;
+    valid_month(month).then(|| month.to_string())
+}
+
+/// Writes `usage-YYYY-MM.json`, and its per-key totals as
+/// `usage-YYYY-MM.csv`. Each is written next to its file and renamed, so a
+/// crash never leaves half a report behind.
+fn write_report(dir: &Path, report: &MonthReport) -> io::Result<()> {
+    let json = serde_json::to_vec_pretty(report).map_err(io::Error::other)?;
+    let quote = |text: &str| {
+        if text.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", text.replace('"', "\"\""))
+        } else {
+            text.to_string()
+        }
+    };
+    let mut csv = "key,calls,failed_calls,runs,cpu_seconds,bytes_downloaded\n".to_string();
+    for (key, usage) in &report.keys {
+        csv.push_str(&format!(
+            "{},{},{},{},{:.3},{}\n",
+            quote(key),
+            usage.calls,
+            usage.failed_calls,
+            usage.runs,
+            usage.cpu_seconds,
+            usage.bytes_downloaded
+        ));
+    }
+    for (extension, bytes) in [("json", json), ("csv", csv.into_bytes())] {
+        let file = dir.join(format!("usage-{}.{}", report.month, extension));
+        let partial = file.with_extension(format!("{}.{}", extension, std::process::id()));
+        fs::write(&partial, bytes).and_then(|()| fs::rename(&partial, &file))?;
+    }
+    Ok(())
+}
+
+/// Writes the monthly usage reports every `usage.save_interval_secs`.
+pub struct UsageReporter {
+    state: AppState,
+}
+
+impl UsageReporter {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    /// Starts saving in the background, or returns `None` when no
+    /// `usage.report_dir` is set.
+    pub fn spawn(self) -> Option<JoinHandle<()>> {
+        let dir = self.state.config.usage.report_dir.clone()?;
+        let interval = Duration::from_secs(self.state.config.usage.save_interval_secs);
+        info!(
+            "writing usage reports to {} every {}s",
+            dir.display(),
+            interval.as_secs()
+        );
+        Some(tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                let usage = self.state.usage.clone();
+                let _ = tokio::task::spawn_blocking(move || usage.save()).await;
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(operations: &[&[&str]], cpu_ms: u64, ok: bool) -> Usage {
+        let call = CallUsage::default();
+        for args in operations {
+            let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            call.record_run(&args, Some(Duration::from_millis(cpu_ms)), 100);
+        }
+        call.record_download(1000);
+        call.finish("pcli2_asset_geometric_match", ok)
+    }
+
+    #[test]
+    fn test_operations_are_named_by_their_subcommand() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            operation(&args(&["asset", "geometric-match", "--uuid", "u"])),
+            "asset geometric-match"
+        );
+        assert_eq!(
+            operation(&args(&["config", "environment", "list", "extra"])),
+            "config environment list"
+        );
+        assert_eq!(operation(&args(&["--version"])), "version");
+    }
+
+    #[test]
+    fn test_calls_add_up_by_session_key_and_month() {
+        let ledger = UsageLedger::default();
+        let retried = call(
+            &[
+                &["asset", "geometric-match", "--uuid", "a"],
+                &["asset", "geometric-match", "--uuid", "a"],
+            ],
+            250,
+            true,
+        );
+        assert_eq!(retried.runs, 2);
+        assert_eq!(retried.bytes_downloaded, 1200);
+        ledger.record_in("2026-09", "s1", Some("team-a"), &retried);
+        ledger.record_in("2026-09", "s1", None, &call(&[], 0, false));
+        ledger.record_in("2026-10", "s2", Some("team-a"), &retried);
+
+        let september = ledger.month_json("2026-09").unwrap();
+        assert_eq!(september["keys"]["team-a"]["runs"], 2);
+        assert_eq!(september["keys"]["anonymous"]["failed_calls"], 1);
+        assert_eq!(september["total"]["calls"], 2);
+        assert_eq!(september["total"]["operations"]["asset geometric-match"], 2);
+        assert_eq!(
+            september["total"]["tools"]["pcli2_asset_geometric_match"],
+            2
+        );
+        assert!((september["total"]["cpu_seconds"].as_f64().unwrap() - 0.5).abs() < 1e-9);
+        assert!(ledger.month_json("2026-08").is_none());
+
+        let ledger = ledger.ledger.lock().unwrap();
+        assert_eq!(ledger.sessions["s1"].0.calls, 2);
+        assert_eq!(ledger.sessions["s2"].0.calls, 1);
+    }
+
+    #[test]
+    fn test_reports_are_written_and_read_back() {
+        let dir = std::env::temp_dir().join(format!("pcli2-mcp-usage-{}", std::process::id()));
+        let config = UsageConfig {
+            report_dir: Some(dir.clone()),
+            ..UsageConfig::default()
+        };
+        let ledger = UsageLedger::open(&config).unwrap();
+        ledger.record_in("2026-09", "s1", Some("team-a,b"), &call(&[], 0, true));
+        assert_eq!(ledger.save(), 1);
+        assert_eq!(ledger.save(), 0);
+        let csv = fs::read_to_string(dir.join("usage-2026-09.csv")).unwrap();
+        assert_eq!(csv.lines().nth(1), Some("\"team-a,b\",1,0,0,0.000,1000"));
+
+        let reopened = UsageLedger::open(&config).unwrap();
+        assert_eq!(reopened.month_json("2026-09"), ledger.month_json("2026-09"));
+        let _ = fs::remove_dir_all(&dir);
+        assert!(valid_month("2026-10"));
+        assert!(!valid_month("2026-13"));
+        assert!(!valid_month("2026-1"));
+    }
+}
@@ -0,0 +1,103 @@
+use crate::{AppState, auth::expand_args, config::WarmConfig, pcli::run_pcli2_background};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+const WARM_LABEL: &str = "pcli2 warm-up";
+
+/// One warm-up run: the command as configured, so secrets stay out of the
+/// log, and how it went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WarmRun {
+    pub command: String,
+    pub elapsed: Duration,
+    pub error: Option<String>,
+}
+
+/// Runs the `warm` login and commands at startup, and again every
+/// `warm.interval_secs`. `pcli2` has no long-lived mode to keep a pool of,
+/// so each tool call still starts a process; what it saves is the login
+/// and the cache fill that the first calls would otherwise pay for.
+pub struct Warmer {
+    state: AppState,
+}
+
+impl Warmer {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    fn config(&self) -> &WarmConfig {
+        &self.state.config.warm
+    }
+
+    async fn login(&self) -> WarmRun {
+        let reauth = &self.state.reauth;
+        let started = Instant::now();
+        let outcome = reauth
+            .refresh(started, |args| {
+                run_pcli2_background(&self.state, args, "pcli2 warm-up login")
+            })
+            .await;
+        WarmRun {
+            command: reauth.display_args(),
+            elapsed: started.elapsed(),
+            error: outcome.err(),
+        }
+    }
+
+    async fn command(&self, command: &[String]) -> WarmRun {
+        let started = Instant::now();
+        let outcome = match expand_args(command) {
+            Ok(args) => run_pcli2_background(&self.state, args, WARM_LABEL)
+                .await
+                .map(|_| ()),
+            Err(message) => Err(message),
+        };
+        WarmRun {
+            command: command.join(" "),
+            elapsed: started.elapsed(),
+            error: outcome.err(),
+        }
+    }
+
+    /// Runs the login, then each command in order. A failed run is logged
+    /// and the rest still run.
+    pub async fn run_once(&self) -> Vec<WarmRun> {
+        let mut runs = Vec::new();
+        if self.config().login {
+            runs.push(self.login().await);
+        }
+        for command in &self.config().commands {
+            runs.push(self.command(command).await);
+        }
+        for run in &runs {
+            match &run.error {
+                None => info!(
+                    "🔥 warmed pcli2 {} in {} ms",
+                    run.command,
+                    run.elapsed.as_millis()
+                ),
+                Some(message) => warn!("⚠ warm-up pcli2 {} failed: {}", run.command, message),
+            }
+        }
+        runs
+    }
+
+    /// Starts the warm-up runs in the background. Returns `None` when the
+    /// `warm` section asks for none.
+    pub fn spawn(self) -> Option<JoinHandle<()>> {
+        let config = self.config();
+        if !config.login && config.commands.is_empty() {
+            return None;
+        }
+        let interval = config.interval_secs.map(Duration::from_secs);
+        Some(tokio::spawn(async move {
+            loop {
+                self.run_once().await;
+                let Some(interval) = interval else { break };
+                tokio::time::sleep(interval).await;
+            }
+        }))
+    }
+}
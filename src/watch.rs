@@ -0,0 +1,157 @@
+use crate::{
+    AppState,
+    config::WatchConfig,
+    proxy::{Endpoint, Proxy, parse_headers},
+    resources::StoredResource,
+    snapshot::{diff_assets, list_assets},
+};
+use anyhow::Result;
+use chrono::Utc;
+use serde_json::{Value, json};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+pub const FOLDER_URI_PREFIX: &str = "pcli2://folders/";
+
+pub fn folder_uri(folder_path: &str) -> String {
+    format!(
+        "{}{}",
+        FOLDER_URI_PREFIX,
+        folder_path.trim_start_matches('/')
+    )
+}
+
+/// Polls the folders in `watch.folders`, keeps their latest asset listing as
+/// a `pcli2://folders/...` resource, and announces changes to subscribers
+/// and webhooks.
+pub struct FolderWatcher {
+    state: AppState,
+    webhooks: Vec<Proxy>,
+    listings: HashMap<String, Vec<Value>>,
+}
+
+impl FolderWatcher {
+    pub fn new(state: AppState) -> Result<Self> {
+        let config = &state.config.watch;
+        let headers = parse_headers(&config.webhook_headers)?;
+        let webhooks = config
+            .webhooks
+            .iter()
+            .map(|url| Ok(Proxy::new(Endpoint::parse(url)?, headers.clone())))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            state,
+            webhooks,
+            listings: HashMap::new(),
+        })
+    }
+
+    fn config(&self) -> &WatchConfig {
+        &self.state.config.watch
+    }
+
+    /// Lists every watched folder once. The first listing of a folder only
+    /// records it; later listings that differ are reported. Returns the URIs
+    /// that changed.
+    pub async fn poll(&mut self) -> Vec<String> {
+        let mut changed = Vec::new();
+        for folder_path in self.config().folders.clone() {
+            let mut folder = json!({ "folder_path": folder_path });
+            if let Some(tenant) = &self.config().tenant {
+                folder["tenant"] = json!(tenant);
+            }
+            let assets = match list_assets(&folder).await {
+                Ok(assets) => assets,
+                Err(message) => {
+                    warn!("⚠ watching {} failed: {}", folder_path, message);
+                    continue;
+                }
+            };
+            let uri = folder_uri(&folder_path);
+            let text = serde_json::to_string(&assets).unwrap_or_else(|_| "[]".to_string());
+            self.state.resources.pin(StoredResource {
+                uri: uri.clone(),
+                name: format!("folder {}", folder_path),
+                mime_type: "application/json".to_string(),
+                text: Arc::new(text),
+                tenant: self.config().tenant.clone(),
+                blob: false,
+            });
+            let previous = self.listings.insert(folder_path.clone(), assets.clone());
+            let Some(previous) = previous.filter(|previous| *previous != assets) else {
+                continue;
+            };
+            let diff = diff_assets(&previous, &assets);
+            info!(
+                "👀 {} changed: {} added, {} removed, {} changed",
+                folder_path,
+                count(&diff["added"]),
+                count(&diff["removed"]),
+                count(&diff["changed"])
+            );
+            self.state.notifier.resource_updated(&uri);
+            self.send_webhooks(&json!({
+                "event": "folder.changed",
+                "uri": uri,
+                "folder_path": folder_path,
+                "changed_at": Utc::now().to_rfc3339(),
+                "added": diff["added"],
+                "removed": diff["removed"],
+                "changed": diff["changed"],
+            }))
+            .await;
+            changed.push(uri);
+        }
+        changed
+    }
+
+    async fn send_webhooks(&self, event: &Value) {
+        for webhook in &self.webhooks {
+            match webhook.post(event).await {
+                Ok(status) if (200..300).contains(&status) => {}
+                Ok(status) => warn!("⚠ folder webhook returned HTTP status {}", status),
+                Err(err) => warn!("⚠ folder webhook failed: {}", err),
+            }
+        }
+    }
+
+    /// Starts polling every `watch.interval_secs`, or returns `None` when no
+    /// folders are watched.
+    pub fn spawn(mut self) -> Option<JoinHandle<()>> {
+        if self.config().folders.is_empty() {
+            return None;
+        }
+        let interval = Duration::from_secs(self.config().interval_secs);
+        info!(
+            "watching {} folder(s) every {}s",
+            self.config().folders.len(),
+            interval.as_secs()
+        );
+        Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                self.poll().await;
+            }
+        }))
+    }
+}
+
+fn count(items: &Value) -> usize {
+    items.as_array().map_or(0, Vec::len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_folder_uri() {
+        assert_eq!(
+            folder_uri("/Root/Incoming"),
+            "pcli2://folders/Root/Incoming"
+        );
+    }
+}
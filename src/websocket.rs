@@ -0,0 +1,120 @@
+use crate::{
+    AppState,
+    correlation::REQUEST_ID_HEADER,
+    mcp::{SESSION_HEADER, handle_mcp, notification_message},
+    replay::admit_mcp,
+    server::MAX_REQUEST_BYTES,
+};
+use axum::{
+    body::{Bytes, to_bytes},
+    extract::{
+        State,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use tokio::sync::{broadcast::error::RecvError, mpsc};
+use tracing::{info, warn};
+
+/// `GET /mcp` of a `websocket` listener: each text frame carries one
+/// JSON-RPC message, answered like `POST /mcp` on the same connection,
+/// which also receives the session's notifications.
+pub async fn handle_mcp_socket(
+    State(mut state): State<AppState>,
+    mut headers: HeaderMap,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    state.refresh_config();
+    let principal = match admit_mcp(&state, &headers, &[]).await {
+        Ok(principal) => principal,
+        Err(refusal) => {
+            warn!("⚠ rejected WebSocket connection: {}", refusal);
+            return (refusal.status(), refusal.to_string()).into_response();
+        }
+    };
+    state.api_key = principal.key.clone();
+    // The opening request's nonce was spent; its messages are admitted as
+    // the connection was.
+    if state.config.access.replay.is_some() {
+        state.connection = Some(principal);
+    }
+    // The connection is the session: every message on it, and its
+    // subscriptions, share one id.
+    match headers
+        .get(SESSION_HEADER)
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(session) => {
+            if let Err(message) = state.sessions.check(session, state.principal_key()).await {
+                warn!("⚠ rejected WebSocket connection: {}", message);
+                return (StatusCode::NOT_FOUND, message).into_response();
+            }
+        }
+        None => {
+            let session = state.sessions.issue(state.principal_key()).await;
+            if let Ok(session) = HeaderValue::from_str(&session) {
+                headers.insert(SESSION_HEADER, session);
+            }
+        }
+    }
+    state.session = headers
+        .get(SESSION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    upgrade
+        .max_message_size(MAX_REQUEST_BYTES)
+        .on_upgrade(move |socket| serve_socket(state, headers, socket))
+}
+
+async fn serve_socket(state: AppState, headers: HeaderMap, mut socket: WebSocket) {
+    info!("🔌 WebSocket connection opened");
+    let mut updates = state.notifier.listen();
+    // Messages are handled concurrently, so a long tool call does not hold
+    // up the ones sent after it; replies go out as they finish.
+    let (replies, mut pending) = mpsc::unbounded_channel::<String>();
+    loop {
+        let outgoing = tokio::select! {
+            message = socket.recv() => {
+                let body = match message {
+                    Some(Ok(Message::Text(text))) => Bytes::from(text),
+                    Some(Ok(Message::Binary(bytes))) => Bytes::from(bytes),
+                    Some(Ok(Message::Ping(_) | Message::Pong(_))) => continue,
+                    Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                };
+                let (state, headers, replies) = (state.clone(), headers.clone(), replies.clone());
+                tokio::spawn(async move {
+                    if let Some(reply) = dispatch(state, headers, body).await {
+                        let _ = replies.send(reply);
+                    }
+                });
+                continue;
+            }
+            Some(reply) = pending.recv() => reply,
+            update = updates.recv() => match update {
+                Ok(notification) => match notification_message(&state, notification) {
+                    Some(message) => message.to_string(),
+                    None => continue,
+                },
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            },
+        };
+        if socket.send(Message::Text(outgoing)).await.is_err() {
+            break;
+        }
+    }
+    info!("🔌 WebSocket connection closed");
+}
+
+/// Handles one message as `POST /mcp` would; `None` for notifications,
+/// which get no reply. Each message gets its own correlation id rather than
+/// the one the connection was opened with.
+async fn dispatch(state: AppState, mut headers: HeaderMap, body: Bytes) -> Option<String> {
+    headers.remove(REQUEST_ID_HEADER);
+    let response = handle_mcp(State(state), headers, body)
+        .await
+        .into_response();
+    let body = to_bytes(response.into_body(), usize::MAX).await.ok()?;
+    (!body.is_empty()).then(|| String::from_utf8_lossy(&body).into_owned())
+}
@@ -0,0 +1,310 @@
+use crate::config::ServerConfig;
+use std::{
+    collections::BTreeSet,
+    env, fs,
+    path::{Path, PathBuf},
+    sync::{
+        Mutex, MutexGuard,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::{info, warn};
+
+/// Prefix of the call directories, which the artifact collector looks for.
+pub const WORKSPACE_PREFIX: &str = "pcli2-call-";
+
+/// Directories of calls still running, which the collector leaves alone.
+static IN_USE: Mutex<BTreeSet<PathBuf>> = Mutex::new(BTreeSet::new());
+
+fn in_use() -> MutexGuard<'static, BTreeSet<PathBuf>> {
+    IN_USE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Whether `path` is the directory of a call still running.
+pub fn is_in_use(path: &Path) -> bool {
+    in_use().contains(path)
+}
+
+/// Where call directories are made: `workspace.dir`, else the sandbox's
+/// working directory when the sandbox is on, else the temp directory.
+pub fn workspace_root(config: &ServerConfig) -> PathBuf {
+    match (&config.workspace.dir, &config.sandbox.working_dir) {
+        (Some(dir), _) => dir.clone(),
+        (None, Some(dir)) if config.sandbox.enabled => dir.clone(),
+        _ => env::temp_dir(),
+    }
+}
+
+/// Where the calls of `tenant` make their directories: its
+/// `tenants.<tenant>.artifact_dir` under the workspace root, if it has one.
+pub fn tenant_workspace_root(config: &ServerConfig, tenant: Option<&str>) -> PathBuf {
+    let root = workspace_root(config);
+    match tenant
+        .and_then(|tenant| config.tenants.get(tenant))
+        .and_then(|defaults| defaults.artifact_dir.as_ref())
+    {
+        Some(dir) => root.join(dir),
+        None => root,
+    }
+}
+
+/// The working directory of one tool call: `pcli2` runs in it, and files
+/// the call downloads land in it, so concurrent calls writing files with
+/// the same relative name cannot overwrite each other. It is removed when
+/// the call is done, unless the call failed and `workspace.keep_on_failure`
+/// is set.
+#[derive(Debug)]
+pub struct Workspace {
+    path: PathBuf,
+    keep_on_failure: bool,
+    keep: bool,
+}
+
+impl Workspace {
+    /// A new directory for a call to `tool`, or `None` when workspaces are
+    /// off or `pcli2` runs on another host or in a container.
+    pub fn create(
+        config: &ServerConfig,
+        tool: &str,
+        tenant: Option<&str>,
+    ) -> Result<Option<Self>, String> {
+        let settings = &config.workspace;
+        if !settings.enabled || config.ssh.host.is_some() || config.docker.container.is_some() {
+            return Ok(None);
+        }
+        // Calls started in the same millisecond need different directories.
+        static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+        let mut path = tenant_workspace_root(config, tenant);
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|err| format!("Failed to read system time: {}", err))?
+            .as_millis();
+        path.push(format!(
+            "{}{}-{}-{}-{}",
+            WORKSPACE_PREFIX,
+            std::process::id(),
+            timestamp,
+            SEQUENCE.fetch_add(1, Ordering::Relaxed),
+            tool
+        ));
+        fs::create_dir_all(&path).map_err(|err| {
+            format!(
+                "Failed to create the call's working directory {}: {}",
+                path.display(),
+                err
+            )
+        })?;
+        #[cfg(unix)]
+        hand_to_sandbox(config, &path)?;
+        in_use().insert(path.clone());
+        Ok(Some(Self {
+            path,
+            keep_on_failure: settings.keep_on_failure,
+            keep: false,
+        }))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Keeps the directory, renamed to `name` beside it. Returns its new
+    /// path.
+    pub fn keep_as(mut self, name: &str) -> Result<PathBuf, String> {
+        let kept = self.path.with_file_name(name);
+        fs::rename(&self.path, &kept).map_err(|err| {
+            format!(
+                "Failed to keep the call's working directory {}: {}",
+                self.path.display(),
+                err
+            )
+        })?;
+        self.keep = true;
+        Ok(kept)
+    }
+
+    /// Removes the directory, or keeps it when the call failed and failed
+    /// calls are kept.
+    pub fn finish(mut self, succeeded: bool) {
+        if !succeeded && self.keep_on_failure {
+            self.keep = true;
+            info!(
+                "kept the working directory of the failed call: {}",
+                self.path.display()
+            );
+        }
+    }
+}
+
+/// Gives the directory to the sandbox's user and group, so a `pcli2` that
+/// runs as another user can write its downloads into it.
+#[cfg(unix)]
+fn hand_to_sandbox(config: &ServerConfig, path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let sandbox = &config.sandbox;
+    if !sandbox.enabled || (sandbox.uid.is_none() && sandbox.gid.is_none()) {
+        return Ok(());
+    }
+    std::os::unix::fs::chown(path, sandbox.uid, sandbox.gid)
+        .and_then(|()| fs::set_permissions(path, fs::Permissions::from_mode(0o770)))
+        .map_err(|err| {
+            format!(
+                "Failed to give the call's working directory {} to the sandbox user: {}",
+                path.display(),
+                err
+            )
+        })
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        in_use().remove(&self.path);
+        if self.keep {
+            return;
+        }
+        if let Err(err) = fs::remove_dir_all(&self.path)
+            && err.kind() != std::io::ErrorKind::NotFound
+        {
+            warn!(
+                "⚠ could not remove the working directory {}: {}",
+                self.path.display(),
+                err
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{SshConfig, TenantDefaults, WorkspaceConfig};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_workspaces_are_removed_unless_kept() {
+        let config = ServerConfig::default();
+        let first = Workspace::create(&config, "pcli2_asset_get", None)
+            .unwrap()
+            .unwrap();
+        let second = Workspace::create(&config, "pcli2_asset_get", None)
+            .unwrap()
+            .unwrap();
+        assert_ne!(first.path(), second.path());
+        assert!(is_in_use(first.path()));
+        fs::write(first.path().join("a.stl"), b"solid").unwrap();
+        let path = first.path().to_path_buf();
+        first.finish(false);
+        assert!(!path.exists());
+        second.finish(true);
+
+        let config = ServerConfig {
+            workspace: WorkspaceConfig {
+                keep_on_failure: true,
+                ..WorkspaceConfig::default()
+            },
+            ..ServerConfig::default()
+        };
+        let failed = Workspace::create(&config, "pcli2_asset_get", None)
+            .unwrap()
+            .unwrap();
+        let path = failed.path().to_path_buf();
+        failed.finish(false);
+        assert!(path.is_dir());
+        assert!(!is_in_use(&path));
+        fs::remove_dir_all(&path).unwrap();
+
+        let remote = ServerConfig {
+            ssh: SshConfig {
+                host: Some("bastion".to_string()),
+                ..SshConfig::default()
+            },
+            ..ServerConfig::default()
+        };
+        assert!(
+            Workspace::create(&remote, "pcli2_asset_get", None)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_sandbox_user_can_write_to_the_call_directory() {
+        use crate::{
+            config::SandboxConfig,
+            runner::{CommandRequest, CommandRunner, LocalRunner},
+            sandbox::Sandbox,
+        };
+        use std::{os::unix::fs::MetadataExt, time::Duration};
+
+        // Switching to another user needs root.
+        if unsafe { libc::geteuid() } != 0 {
+            return;
+        }
+        let config = ServerConfig {
+            sandbox: SandboxConfig {
+                enabled: true,
+                uid: Some(65534),
+                gid: Some(65534),
+                ..SandboxConfig::default()
+            },
+            ..ServerConfig::default()
+        };
+        let workspace = Workspace::create(&config, "pcli2_asset_thumbnail", None)
+            .unwrap()
+            .unwrap();
+        let metadata = fs::metadata(workspace.path()).unwrap();
+        assert_eq!((metadata.uid(), metadata.gid()), (65534, 65534));
+
+        let sandbox = Sandbox::for_tool(&config.sandbox, "pcli2_asset_thumbnail").unwrap();
+        let args = ["-c".to_string(), "id -u > thumbnail.png".to_string()];
+        let output = LocalRunner
+            .run(CommandRequest {
+                args: &args,
+                label: "sh",
+                timeout: Duration::from_secs(10),
+                sandbox: Some(&sandbox),
+                pcli2: Some("/bin/sh"),
+                cwd: Some(workspace.path()),
+            })
+            .await
+            .unwrap();
+        assert!(output.success(), "{}", output.transcript);
+        let written = fs::read_to_string(workspace.path().join("thumbnail.png")).unwrap();
+        assert_eq!(written.trim(), "65534");
+        let path = workspace.path().to_path_buf();
+        workspace.finish(true);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_tenant_calls_work_in_their_artifact_dir() {
+        let root = env::temp_dir().join(format!("pcli2-mcp-tenant-ws-{}", std::process::id()));
+        let config = ServerConfig {
+            workspace: WorkspaceConfig {
+                dir: Some(root.clone()),
+                ..WorkspaceConfig::default()
+            },
+            tenants: HashMap::from([(
+                "acme".to_string(),
+                TenantDefaults {
+                    artifact_dir: Some(PathBuf::from("acme")),
+                    ..TenantDefaults::default()
+                },
+            )]),
+            ..ServerConfig::default()
+        };
+        let acme = Workspace::create(&config, "pcli2_asset_get", Some("acme"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(acme.path().parent(), Some(root.join("acme").as_path()));
+        let other = Workspace::create(&config, "pcli2_asset_get", Some("globex"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(other.path().parent(), Some(root.as_path()));
+        drop((acme, other));
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,25 @@
+//! Replays the recorded conformance suites in `tests/conformance/`, each
+//! made with `serve --record-conformance <dir>`, and fails on any answer
+//! that differs from the recorded one.
+
+use pcli2_mcp::{AppState, conformance::replay_suite, server::SERVER_NAME};
+use std::{fs, path::Path};
+
+#[tokio::test]
+async fn test_recorded_suites_still_pass() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance");
+    let mut suites: Vec<_> = fs::read_dir(&root)
+        .expect("conformance suites")
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_dir())
+        .collect();
+    suites.sort();
+    assert!(!suites.is_empty(), "no suites in {}", root.display());
+
+    let mut mismatches = Vec::new();
+    for suite in &suites {
+        let state = AppState::new(SERVER_NAME, env!("CARGO_PKG_VERSION"));
+        mismatches.extend(replay_suite(suite, state).await.expect("replay"));
+    }
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+}